@@ -0,0 +1,66 @@
+//! Compiles the C and Rust programs of `tests/fixtures` for the integration tests, into the
+//! `fixtures` directory of `OUT_DIR`, given to the tests as `RUSTDBG_FIXTURES`.
+//!
+//! The debugger itself builds without a C compiler: a fixture that can't be compiled is reported
+//! with a warning, and the tests needing it fail saying so. The `i386` fixture is a 32-bit program
+//! without the C library, which needs only a compiler supporting `-m32`.
+
+use std::path::Path;
+use std::process::Command;
+
+/// The C fixtures compiled a second time with other flags, for the unit tests: the name of the
+/// executable, the source it is compiled from and the flags.
+const VARIANTS: &[(&str, &str, &[&str])] = &[
+    ("unwind-release", "unwind", &["-O2", "-fomit-frame-pointer"]),
+    ("locals-nodebug", "locals", &["-O0"]),
+    ("locals-buildid", "locals", &["-g", "-O0", "-Wl,--build-id"]),
+];
+
+/// The flags compiling the fixture `name`, a C program or a Rust one (`rs`).
+fn flags(name: &str, extension: &str) -> &'static [&'static str] {
+    match (name, extension) {
+        (_, "rs") => &["-g", "-C", "opt-level=0"],
+        ("i386", _) => &["-g", "-O0", "-m32", "-nostdlib", "-static", "-fno-pic"],
+        ("stripped", _) => &["-O0", "-s"],
+        _ => &["-g", "-O0", "-pthread"],
+    }
+}
+
+fn main() {
+    let source_directory = Path::new("tests/fixtures");
+    let output_directory = Path::new(&std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo")).join("fixtures");
+    std::fs::create_dir_all(&output_directory).expect("Failed to create the fixtures directory");
+    println!("cargo:rerun-if-changed={}", source_directory.display());
+    println!("cargo:rustc-env=RUSTDBG_FIXTURES={}", output_directory.display());
+    let Ok(entries) = std::fs::read_dir(source_directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let source = entry.path();
+        let extension = source.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        let compiler = match extension {
+            "c" => std::env::var("CC").unwrap_or_else(|_| "cc".to_string()),
+            "rs" => std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()),
+            _ => continue,
+        };
+        println!("cargo:rerun-if-changed={}", source.display());
+        let Some(name) = source.file_stem() else {
+            continue;
+        };
+        compile(&compiler, flags(&name.to_string_lossy(), extension), &source, &output_directory.join(name));
+    }
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    for (name, source, flags) in VARIANTS {
+        compile(&compiler, flags, &source_directory.join(format!("{}.c", source)), &output_directory.join(name));
+    }
+}
+
+/// Compile `source` to `output`, warning if it fails.
+fn compile(compiler: &str, flags: &[&str], source: &Path, output: &Path) {
+    let status = Command::new(compiler).args(flags).arg("-o").arg(output).arg(source).status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => println!("cargo:warning=Failed to compile the fixture {}: {}", source.display(), status),
+        Err(err) => println!("cargo:warning=Failed to compile the fixture {}: {}", source.display(), err),
+    }
+}
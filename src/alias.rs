@@ -0,0 +1,164 @@
+use crate::script::split_commands;
+use std::collections::BTreeMap;
+
+/// Aliases expanding to aliases are expanded at most this deep, to stop an alias expanding to itself.
+const MAX_ALIAS_DEPTH: usize = 16;
+
+/// Split `text` into words separated by whitespace. Double quotes group words and allow `\"`,
+/// single quotes group words taken literally, and a backslash outside quotes escapes the next
+/// character.
+///
+/// # Errors
+///
+/// Returns a message if a quote is not closed.
+pub fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => word.push(escaped),
+                            None => return Err("Unterminated \" quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.next());
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parse the definition of an alias, `<name> = <commands>`. The commands are usually quoted, as in
+/// `bm = "b $1; c"`, so the `;` between them is part of the alias.
+///
+/// # Returns
+///
+/// The name of the alias and the commands it expands to.
+///
+/// # Errors
+///
+/// Returns a message if the definition is malformed or the name is invalid.
+pub fn parse_definition(text: &str) -> Result<(String, String), String> {
+    let words = tokenize(text)?;
+    let [name, equals, body @ ..] = words.as_slice() else {
+        return Err("Usage: alias <name> = \"<command>[; <command>...]\"".to_string());
+    };
+    if equals != "=" || body.is_empty() {
+        return Err("Usage: alias <name> = \"<command>[; <command>...]\"".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid alias name \"{}\": use letters, digits, - and _", name));
+    }
+    Ok((name.clone(), body.join(" ")))
+}
+
+/// Replace the positional parameters of the commands of an alias with the arguments it is called
+/// with: `$1` to `$9` and beyond, `$*` for all of them and `$$` for a `$`. Other `$` are kept, as
+/// in `$rip`. Without any positional parameter in the commands, the arguments are appended to them.
+///
+/// # Errors
+///
+/// Returns a message if a parameter has no argument.
+pub fn substitute(body: &str, args: &[String]) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut positional = false;
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                expanded.push('$');
+            }
+            Some('*') => {
+                chars.next();
+                positional = true;
+                expanded.push_str(&args.join(" "));
+            }
+            Some(digit) if digit.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    number.push(digit);
+                }
+                positional = true;
+                let index: usize = number.parse().map_err(|_| format!("Invalid parameter ${}", number))?;
+                match index.checked_sub(1).and_then(|index| args.get(index)) {
+                    Some(arg) => expanded.push_str(arg),
+                    None => return Err(format!("Missing argument ${}", number)),
+                }
+            }
+            _ => expanded.push('$'),
+        }
+    }
+    if !positional && !args.is_empty() {
+        expanded.push(' ');
+        expanded.push_str(&args.join(" "));
+    }
+    Ok(expanded)
+}
+
+/// Expand the aliases of a command into the commands to run, expanding the aliases used by the
+/// commands of an alias too.
+///
+/// # Arguments
+///
+/// * `aliases` - The commands of each alias, by name.
+/// * `command` - The command line, whose first word may be an alias.
+///
+/// # Returns
+///
+/// The commands to run: `command` itself if it is not an alias.
+///
+/// # Errors
+///
+/// Returns a message if an argument is missing, a quote is not closed, or aliases expand to
+/// each other endlessly.
+pub fn expand(aliases: &BTreeMap<String, String>, command: &str) -> Result<Vec<String>, String> {
+    let mut commands = Vec::new();
+    expand_into(aliases, command, 0, &mut commands)?;
+    Ok(commands)
+}
+
+fn expand_into(aliases: &BTreeMap<String, String>, command: &str, depth: usize, commands: &mut Vec<String>) -> Result<(), String> {
+    let name = command.split_whitespace().next().unwrap_or_default();
+    let Some(body) = aliases.get(name) else {
+        commands.push(command.to_string());
+        return Ok(());
+    };
+    if depth >= MAX_ALIAS_DEPTH {
+        return Err(format!("Aliases are nested too deeply expanding \"{}\", is an alias recursive?", name));
+    }
+    let args = tokenize(command.trim_start()[name.len()..].trim())?;
+    for command in split_commands(&substitute(body, &args)?) {
+        expand_into(aliases, &command, depth + 1, commands)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::inject::syscall_error;
+use crate::memory::read_c_string;
+use crate::threads::{self, ThreadState};
+use nix::libc::user_regs_struct;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use std::collections::HashSet;
+
+/// The system call numbers of `ptrace`, `open` and `openat` on x86_64.
+#[cfg(target_arch = "x86_64")]
+const PTRACE_X86_64: u64 = 101;
+#[cfg(target_arch = "x86_64")]
+const OPEN_X86_64: u64 = 2;
+#[cfg(target_arch = "x86_64")]
+const OPENAT_X86_64: u64 = 257;
+/// The system call numbers of `ptrace`, `open` and `openat` on i386.
+#[cfg(target_arch = "x86_64")]
+const PTRACE_I386: u64 = 26;
+#[cfg(target_arch = "x86_64")]
+const OPEN_I386: u64 = 5;
+#[cfg(target_arch = "x86_64")]
+const OPENAT_I386: u64 = 295;
+/// The system call numbers of `ptrace` and `openat` on aarch64, which has no `open`.
+#[cfg(target_arch = "aarch64")]
+const PTRACE_AARCH64: u64 = 117;
+#[cfg(target_arch = "aarch64")]
+const OPENAT_AARCH64: u64 = 56;
+/// The request of `ptrace` with which a program asks to be traced by its parent, failing with
+/// `EPERM` when it is already traced.
+const PTRACE_TRACEME: u64 = 0;
+/// The longest path read from the program.
+const MAX_PATH: usize = 4096;
+
+/// The anti-debugging checks watched for, and `set anti-anti-debug`.
+#[derive(Debug, Default)]
+pub struct AntiDebug {
+    /// `set anti-anti-debug`: make `ptrace(PTRACE_TRACEME)` succeed, and watch the system calls of
+    /// the program while it continues.
+    pub enabled: bool,
+    /// The threads stopped at the entry of a system call by the watch of `continue`, to tell the
+    /// entry from the exit at their next system call stop.
+    in_syscall: HashSet<Pid>,
+    /// The threads stopped at the entry of `ptrace(PTRACE_TRACEME)`, whose request can't be read
+    /// again at its exit on aarch64, where the return value replaces the first argument.
+    trace_me: HashSet<Pid>,
+}
+
+/// A system call a program makes to find out whether it is debugged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AntiDebugCheck {
+    /// `ptrace(PTRACE_TRACEME)`, which fails when the program is already traced.
+    TraceMe,
+    /// Another `ptrace` request, as `PTRACE_ATTACH` to its parent, which fails for a traced process
+    /// too.
+    Ptrace(u64),
+    /// The opening of a `status` file of `/proc`, whose `TracerPid` line is the process id of the
+    /// debugger.
+    TracerPid(String),
+}
+
+impl AntiDebugCheck {
+    /// The warning printed when the program makes the check, with its mitigation.
+    pub fn warning(&self, enabled: bool) -> String {
+        match self {
+            AntiDebugCheck::TraceMe if enabled => {
+                "Anti-debugging: the program calls ptrace(PTRACE_TRACEME), which fails when it is debugged. It will return 0 (set anti-anti-debug on).".to_string()
+            }
+            AntiDebugCheck::TraceMe => {
+                "Anti-debugging: the program calls ptrace(PTRACE_TRACEME), which fails when it is debugged. set anti-anti-debug on makes it return 0.".to_string()
+            }
+            AntiDebugCheck::Ptrace(request) => {
+                format!("Anti-debugging: the program calls ptrace with the request {}, which fails when it is debugged. Only PTRACE_TRACEME is made to succeed, by set anti-anti-debug on.", request)
+            }
+            AntiDebugCheck::TracerPid(path) => {
+                format!("Anti-debugging: the program opens {}, whose TracerPid is not 0 when it is debugged. Change the value read in its buffer, or attach to the program after the check.", path)
+            }
+        }
+    }
+}
+
+/// Whether `path` is the `status` file of a process or thread in `/proc`: `/proc/self/status`,
+/// `/proc/thread-self/status`, `/proc/<pid>/status` or `/proc/<pid>/task/<tid>/status`.
+pub fn is_status_file(path: &str) -> bool {
+    let is_id = |part: &str| part == "self" || (!part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()));
+    let Some(rest) = path.strip_prefix("/proc/").and_then(|rest| rest.strip_suffix("/status")) else {
+        return false;
+    };
+    match rest.split('/').collect::<Vec<_>>().as_slice() {
+        ["thread-self"] => true,
+        [process] => is_id(process),
+        [process, "task", thread] => is_id(process) && is_id(thread),
+        _ => false,
+    }
+}
+
+/// The system call the registers of a thread stopped at its entry or exit are for, with its first
+/// two arguments.
+fn syscall_of(arch: Arch, regs: &user_regs_struct) -> (u64, [u64; 2]) {
+    let [first, second, ..] = arch.syscall_arguments(regs);
+    (regs.syscall_number(), [first, second])
+}
+
+/// Recognize an anti-debugging check in a system call at its entry.
+///
+/// # Arguments
+///
+/// * `arch` - The architecture of the program, for the numbers of the system calls.
+/// * `number` - The number of the system call.
+/// * `arguments` - Its first two arguments.
+/// * `read_path` - Reads the path at an address of the program, for `open` and `openat`.
+pub fn recognize(arch: Arch, number: u64, arguments: [u64; 2], read_path: impl FnOnce(u64) -> Option<String>) -> Option<AntiDebugCheck> {
+    let (ptrace, open, openat) = match arch {
+        #[cfg(target_arch = "x86_64")]
+        Arch::X86_64 => (PTRACE_X86_64, Some(OPEN_X86_64), OPENAT_X86_64),
+        #[cfg(target_arch = "x86_64")]
+        Arch::I386 => (PTRACE_I386, Some(OPEN_I386), OPENAT_I386),
+        #[cfg(target_arch = "aarch64")]
+        Arch::Aarch64 => (PTRACE_AARCH64, None, OPENAT_AARCH64),
+    };
+    let path = match number {
+        _ if number == ptrace && arguments[0] == PTRACE_TRACEME => return Some(AntiDebugCheck::TraceMe),
+        _ if number == ptrace => return Some(AntiDebugCheck::Ptrace(arguments[0])),
+        _ if Some(number) == open => arguments[0],
+        _ if number == openat => arguments[1],
+        _ => return None,
+    };
+    read_path(path).filter(|path| is_status_file(path)).map(AntiDebugCheck::TracerPid)
+}
+
+/// Warn about the anti-debugging check the thread `tid`, stopped at the entry of a system call,
+/// makes, if any.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the registers of the thread can't be read.
+pub fn syscall_entry(debugger: &mut Debugger, tid: Pid) -> Result<(), DbgError> {
+    let regs = crate::arch::get_registers(tid).map_err(DbgError::ptrace("read the registers"))?;
+    let (number, arguments) = syscall_of(debugger.arch, &regs);
+    let check = recognize(debugger.arch, number, arguments, |address| read_c_string(&tid, address, MAX_PATH).ok());
+    if check == Some(AntiDebugCheck::TraceMe) {
+        debugger.anti_debug.trace_me.insert(tid);
+    }
+    if let Some(check) = check {
+        warnln!(debugger, "{}", check.warning(debugger.anti_debug.enabled));
+    }
+    Ok(())
+}
+
+/// Make `ptrace(PTRACE_TRACEME)` succeed with `set anti-anti-debug on`, the thread `tid` being
+/// stopped at the exit of a system call.
+///
+/// # Returns
+///
+/// The return value written, 0, if the system call was `ptrace(PTRACE_TRACEME)` and failed.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the registers of the thread can't be accessed.
+pub fn syscall_exit(debugger: &mut Debugger, tid: Pid) -> Result<Option<u64>, DbgError> {
+    if !debugger.anti_debug.trace_me.remove(&tid) || !debugger.anti_debug.enabled {
+        return Ok(None);
+    }
+    let mut regs = crate::arch::get_registers(tid).map_err(DbgError::ptrace("read the registers"))?;
+    let Some(errno) = syscall_error(debugger.arch, regs.return_value()) else {
+        return Ok(None);
+    };
+    regs.set_return_value(0);
+    crate::cache::invalidate();
+    crate::arch::set_registers(tid, regs).map_err(DbgError::ptrace("write the registers"))?;
+    outln!(debugger, "[anti-anti-debug] ptrace(PTRACE_TRACEME) returns 0 instead of failing with {}", errno);
+    Ok(Some(0))
+}
+
+/// Resume a thread of the program as `continue` does: to its next system call when `watch`, as
+/// with `set anti-anti-debug on`, to check it, else until it stops.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the thread can't be resumed.
+pub fn resume(watch: bool, tid: Pid, signal: Option<Signal>) -> Result<(), DbgError> {
+    crate::cache::invalidate();
+    if watch {
+        log::debug!("PTRACE_SYSCALL {} {:?}", tid, signal);
+        ptrace::syscall(tid, signal).map_err(DbgError::ptrace("continue execution"))
+    } else {
+        log::debug!("PTRACE_CONT {} {:?}", tid, signal);
+        ptrace::cont(tid, signal).map_err(DbgError::ptrace("continue execution"))
+    }
+}
+
+/// Start watching the system calls of the current thread as `continue` resumes it, which is in a
+/// system call if it was stopped at its entry by `syscall`.
+pub fn start_watch(debugger: &mut Debugger, in_syscall: bool) {
+    debugger.anti_debug.in_syscall.clear();
+    if in_syscall {
+        debugger.anti_debug.in_syscall.insert(debugger.thread);
+    }
+}
+
+/// Check the system call a thread stopped at while the program continues with
+/// `set anti-anti-debug on`, then resume the thread, as the wait for the program goes on.
+///
+/// # Returns
+///
+/// None if `status` was a system call stop, else `status`.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the thread can't be inspected or resumed.
+pub fn syscall_stop(debugger: &mut Debugger, tid: Pid, status: WaitStatus) -> Result<Option<WaitStatus>, DbgError> {
+    if status != WaitStatus::PtraceSyscall(tid) {
+        return Ok(Some(status));
+    }
+    if debugger.anti_debug.in_syscall.remove(&tid) {
+        syscall_exit(debugger, tid)?;
+    } else {
+        debugger.anti_debug.in_syscall.insert(tid);
+        syscall_entry(debugger, tid)?;
+    }
+    resume(debugger.anti_debug.enabled, tid, None)?;
+    threads::set_state(debugger, tid, ThreadState::Running);
+    Ok(None)
+}
@@ -0,0 +1,531 @@
+use crate::error::DbgError;
+use crate::memory::{read_bytes, read_u64};
+use crate::syscall;
+use crate::tracee::Tracee;
+use nix::errno::Errno;
+use nix::libc::{self, user_regs_struct};
+use nix::unistd::Pid;
+#[cfg(target_arch = "x86_64")]
+use std::fs::File;
+#[cfg(target_arch = "x86_64")]
+use std::io::Read;
+
+/// The software breakpoint instruction of the host: `int3`.
+#[cfg(target_arch = "x86_64")]
+pub const BREAKPOINT_INSTRUCTION: [u8; 1] = [0xcc];
+
+/// The software breakpoint instruction of the host: `brk #0`, little-endian.
+#[cfg(target_arch = "aarch64")]
+pub const BREAKPOINT_INSTRUCTION: [u8; 4] = [0x00, 0x00, 0x20, 0xd4];
+
+/// The length of [`BREAKPOINT_INSTRUCTION`], the bytes of the program a breakpoint replaces.
+pub const BREAKPOINT_LENGTH: usize = BREAKPOINT_INSTRUCTION.len();
+
+/// How far past the breakpoint the program counter is when it traps: `int3` has been executed.
+#[cfg(target_arch = "x86_64")]
+pub const BREAKPOINT_REWIND: u64 = 1;
+
+/// How far past the breakpoint the program counter is when it traps: on `brk`, it still points
+/// at it.
+#[cfg(target_arch = "aarch64")]
+pub const BREAKPOINT_REWIND: u64 = 0;
+
+/// The names of the general purpose registers of aarch64, `x0` to `x30`, in `regs`.
+#[cfg(target_arch = "aarch64")]
+pub const X_REGISTERS: [&str; 31] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15", "x16", "x17", "x18",
+    "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30",
+];
+
+/// The registers the debugger uses whatever the host, named for what they do: the rest of the
+/// crate reads and writes them through these methods, never by the fields of one architecture.
+pub trait Registers {
+    /// The program counter: `rip`, or `pc` on aarch64.
+    fn pc(&self) -> u64;
+
+    /// Set the program counter.
+    fn set_pc(&mut self, pc: u64);
+
+    /// The stack pointer: `rsp`, or `sp` on aarch64.
+    fn sp(&self) -> u64;
+
+    /// Set the stack pointer.
+    fn set_sp(&mut self, sp: u64);
+
+    /// The frame pointer: `rbp`, or `x29` on aarch64.
+    fn frame_pointer(&self) -> u64;
+
+    /// The value returned by a function or a system call: `rax`, or `x0` on aarch64.
+    fn return_value(&self) -> u64;
+
+    /// Set the value returned by a function or a system call.
+    fn set_return_value(&mut self, value: u64);
+
+    /// The number of the system call the thread is stopped in: `orig_rax`, or `x8` on aarch64.
+    fn syscall_number(&self) -> u64;
+
+    /// Keep the kernel from restarting the system call the thread was interrupted in, once its
+    /// registers are changed to run other code: `orig_rax` is set to -1. Nothing on aarch64, where
+    /// the debugger changes the registers outside of system calls only.
+    fn cancel_syscall_restart(&mut self);
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Registers for user_regs_struct {
+    fn pc(&self) -> u64 {
+        self.rip
+    }
+
+    fn set_pc(&mut self, pc: u64) {
+        self.rip = pc;
+    }
+
+    fn sp(&self) -> u64 {
+        self.rsp
+    }
+
+    fn set_sp(&mut self, sp: u64) {
+        self.rsp = sp;
+    }
+
+    fn frame_pointer(&self) -> u64 {
+        self.rbp
+    }
+
+    fn return_value(&self) -> u64 {
+        self.rax
+    }
+
+    fn set_return_value(&mut self, value: u64) {
+        self.rax = value;
+    }
+
+    fn syscall_number(&self) -> u64 {
+        self.orig_rax
+    }
+
+    fn cancel_syscall_restart(&mut self) {
+        self.orig_rax = u64::MAX;
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Registers for user_regs_struct {
+    fn pc(&self) -> u64 {
+        self.pc
+    }
+
+    fn set_pc(&mut self, pc: u64) {
+        self.pc = pc;
+    }
+
+    fn sp(&self) -> u64 {
+        self.sp
+    }
+
+    fn set_sp(&mut self, sp: u64) {
+        self.sp = sp;
+    }
+
+    fn frame_pointer(&self) -> u64 {
+        self.regs[29]
+    }
+
+    fn return_value(&self) -> u64 {
+        self.regs[0]
+    }
+
+    fn set_return_value(&mut self, value: u64) {
+        self.regs[0] = value;
+    }
+
+    fn syscall_number(&self) -> u64 {
+        self.regs[8]
+    }
+
+    fn cancel_syscall_restart(&mut self) {}
+}
+
+/// Read the general purpose registers of a thread, with `PTRACE_GETREGS`.
+///
+/// # Errors
+///
+/// Returns the errno of ptrace.
+#[cfg(target_arch = "x86_64")]
+pub fn get_registers(tid: Pid) -> Result<user_regs_struct, Errno> {
+    nix::sys::ptrace::getregs(tid)
+}
+
+/// Write the general purpose registers of a thread, with `PTRACE_SETREGS`.
+///
+/// # Errors
+///
+/// Returns the errno of ptrace.
+#[cfg(target_arch = "x86_64")]
+pub fn set_registers(tid: Pid, regs: user_regs_struct) -> Result<(), Errno> {
+    nix::sys::ptrace::setregs(tid, regs)
+}
+
+/// Read the general purpose registers of a thread, with `PTRACE_GETREGSET` and `NT_PRSTATUS`:
+/// aarch64 has no `PTRACE_GETREGS`.
+///
+/// # Errors
+///
+/// Returns the errno of ptrace.
+#[cfg(target_arch = "aarch64")]
+pub fn get_registers(tid: Pid) -> Result<user_regs_struct, Errno> {
+    let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+    let mut iovec = libc::iovec { iov_base: (&mut regs as *mut user_regs_struct).cast(), iov_len: std::mem::size_of::<user_regs_struct>() };
+    let result = unsafe { libc::ptrace(libc::PTRACE_GETREGSET, tid.as_raw(), libc::NT_PRSTATUS, &mut iovec as *mut libc::iovec) };
+    Errno::result(result).map(|_| regs)
+}
+
+/// Write the general purpose registers of a thread, with `PTRACE_SETREGSET` and `NT_PRSTATUS`.
+///
+/// # Errors
+///
+/// Returns the errno of ptrace.
+#[cfg(target_arch = "aarch64")]
+pub fn set_registers(tid: Pid, mut regs: user_regs_struct) -> Result<(), Errno> {
+    let mut iovec = libc::iovec { iov_base: (&mut regs as *mut user_regs_struct).cast(), iov_len: std::mem::size_of::<user_regs_struct>() };
+    let result = unsafe { libc::ptrace(libc::PTRACE_SETREGSET, tid.as_raw(), libc::NT_PRSTATUS, &mut iovec as *mut libc::iovec) };
+    Errno::result(result).map(drop)
+}
+
+/// The `NT_ARM_TLS` register set of aarch64, the thread pointer `tpidr_el0`.
+#[cfg(target_arch = "aarch64")]
+const NT_ARM_TLS: libc::c_int = 0x401;
+
+/// Read the thread pointer of a thread on aarch64, `tpidr_el0`, the base of its thread-local
+/// storage, with `PTRACE_GETREGSET` and `NT_ARM_TLS`. On x86_64, it is `fs_base`.
+///
+/// # Errors
+///
+/// Returns the errno of ptrace.
+#[cfg(target_arch = "aarch64")]
+pub fn thread_pointer(tid: Pid) -> Result<u64, Errno> {
+    let mut pointer = 0u64;
+    let mut iovec = libc::iovec { iov_base: (&mut pointer as *mut u64).cast(), iov_len: std::mem::size_of::<u64>() };
+    let result = unsafe { libc::ptrace(libc::PTRACE_GETREGSET, tid.as_raw(), NT_ARM_TLS, &mut iovec as *mut libc::iovec) };
+    Errno::result(result).map(|_| pointer)
+}
+
+/// The floating point registers of a thread: the x87 and SSE ones on x86_64.
+#[cfg(target_arch = "x86_64")]
+pub type FpRegisters = libc::user_fpregs_struct;
+/// The floating point registers of a thread: the SIMD ones, `v0` to `v31`, on aarch64.
+#[cfg(target_arch = "aarch64")]
+pub type FpRegisters = libc::user_fpsimd_struct;
+
+/// Read the floating point registers of a thread, with `PTRACE_GETREGSET` and `NT_PRFPREG`.
+///
+/// # Errors
+///
+/// Returns the errno of ptrace.
+pub fn get_fp_registers(tid: Pid) -> Result<FpRegisters, Errno> {
+    let mut regs: FpRegisters = unsafe { std::mem::zeroed() };
+    let mut iovec = libc::iovec { iov_base: (&mut regs as *mut FpRegisters).cast(), iov_len: std::mem::size_of::<FpRegisters>() };
+    let result = unsafe { libc::ptrace(libc::PTRACE_GETREGSET, tid.as_raw(), libc::NT_PRFPREG, &mut iovec as *mut libc::iovec) };
+    Errno::result(result).map(|_| regs)
+}
+
+/// Write the floating point registers of a thread, with `PTRACE_SETREGSET` and `NT_PRFPREG`.
+///
+/// # Errors
+///
+/// Returns the errno of ptrace.
+pub fn set_fp_registers(tid: Pid, regs: &FpRegisters) -> Result<(), Errno> {
+    let mut regs = *regs;
+    let mut iovec = libc::iovec { iov_base: (&mut regs as *mut FpRegisters).cast(), iov_len: std::mem::size_of::<FpRegisters>() };
+    let result = unsafe { libc::ptrace(libc::PTRACE_SETREGSET, tid.as_raw(), libc::NT_PRFPREG, &mut iovec as *mut libc::iovec) };
+    Errno::result(result).map(drop)
+}
+
+/// The machine of the ELF files of the architecture, `EM_X86_64`, written in the core files and
+/// expected in the ones read.
+#[cfg(target_arch = "x86_64")]
+pub const ELF_MACHINE: u16 = 62;
+/// The machine of the ELF files of the architecture, `EM_AARCH64`.
+#[cfg(target_arch = "aarch64")]
+pub const ELF_MACHINE: u16 = 183;
+
+/// The architecture of the debugged program. A 32-bit program runs on the x86_64 kernel in its
+/// compatibility mode: ptrace gives its registers in the 64-bit layout, with the 32-bit registers
+/// in the low halves and `r8` to `r15` unused. On an aarch64 host, the program is an aarch64 one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arch {
+    #[cfg(target_arch = "x86_64")]
+    #[default]
+    X86_64,
+    /// A 32-bit program: 4-byte pointers, the i386 system calls, and the arguments of the calls
+    /// on the stack (cdecl).
+    #[cfg(target_arch = "x86_64")]
+    I386,
+    /// A program of an aarch64 host: the registers `x0` to `x30`, `sp` and `pc`, the system call
+    /// number in `x8` and its arguments in `x0` to `x5`.
+    #[cfg(target_arch = "aarch64")]
+    #[default]
+    Aarch64,
+}
+
+impl Arch {
+    /// The architecture of the ELF executable at `path`, x86_64 if it can't be read.
+    #[cfg(target_arch = "x86_64")]
+    pub fn of_executable(path: &str) -> Arch {
+        let mut header = [0; 20];
+        let read = File::open(path).and_then(|mut file| file.read_exact(&mut header));
+        // ELFCLASS32 and EM_386.
+        if read.is_ok() && header[..4] == *b"\x7fELF" && header[4] == 1 && u16::from_le_bytes([header[18], header[19]]) == 3 {
+            Arch::I386
+        } else {
+            Arch::X86_64
+        }
+    }
+
+    /// The architecture of the ELF executable at `path`: aarch64, the only one an aarch64 host
+    /// debugs.
+    #[cfg(target_arch = "aarch64")]
+    pub fn of_executable(_path: &str) -> Arch {
+        Arch::Aarch64
+    }
+
+    /// Returns true for a 32-bit (i386) program, whose pointers, system calls and call arguments
+    /// differ.
+    pub fn is_i386(self) -> bool {
+        #[cfg(target_arch = "x86_64")]
+        return self == Arch::I386;
+        #[cfg(target_arch = "aarch64")]
+        return false;
+    }
+
+    /// The size of a pointer, and of a slot of the stack.
+    pub fn pointer_size(self) -> u64 {
+        if self.is_i386() {
+            4
+        } else {
+            8
+        }
+    }
+
+    /// The bitness of the instructions, for the decoder.
+    pub fn bitness(self) -> u32 {
+        if self.is_i386() {
+            32
+        } else {
+            64
+        }
+    }
+
+    /// Read the pointer at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::Memory`] if it can't be read.
+    pub fn read_pointer(self, tracee: &(impl Tracee + ?Sized), address: u64) -> Result<u64, DbgError> {
+        if !self.is_i386() {
+            return read_u64(tracee, address);
+        }
+        let bytes = read_bytes(tracee, address, 4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as u64)
+    }
+
+    /// The general purpose registers shown by `r`, by their names for the architecture.
+    pub fn registers(self, regs: &user_regs_struct) -> Vec<(&'static str, u64)> {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Arch::X86_64 => vec![
+                ("rax", regs.rax),
+                ("rbx", regs.rbx),
+                ("rcx", regs.rcx),
+                ("rdx", regs.rdx),
+                ("rsi", regs.rsi),
+                ("rdi", regs.rdi),
+                ("rsp", regs.rsp),
+                ("rip", regs.rip),
+                ("rbp", regs.rbp),
+                ("r8 ", regs.r8),
+                ("r9 ", regs.r9),
+                ("r10", regs.r10),
+                ("r11", regs.r11),
+                ("r12", regs.r12),
+                ("r13", regs.r13),
+                ("r14", regs.r14),
+                ("r15", regs.r15),
+            ],
+            #[cfg(target_arch = "x86_64")]
+            Arch::I386 => [
+                ("eax", regs.rax),
+                ("ebx", regs.rbx),
+                ("ecx", regs.rcx),
+                ("edx", regs.rdx),
+                ("esi", regs.rsi),
+                ("edi", regs.rdi),
+                ("esp", regs.rsp),
+                ("eip", regs.rip),
+                ("ebp", regs.rbp),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name, value & 0xffff_ffff))
+            .collect(),
+            #[cfg(target_arch = "aarch64")]
+            Arch::Aarch64 => {
+                let mut registers: Vec<(&'static str, u64)> = X_REGISTERS.into_iter().zip(regs.regs).collect();
+                registers.extend([("sp", regs.sp), ("pc", regs.pc), ("pstate", regs.pstate)]);
+                registers
+            }
+        }
+    }
+
+    /// The register of `regs` called `name`, as [`Arch::registers`] names them, with `pc`, `sp` and
+    /// `fp` for the program counter, the stack and the frame pointers. On x86_64, `eflags`,
+    /// `orig_rax`, `fs_base` and `gs_base` too.
+    pub fn register_mut<'a>(self, regs: &'a mut user_regs_struct, name: &str) -> Option<&'a mut u64> {
+        #[cfg(target_arch = "x86_64")]
+        return Some(match name {
+            "rax" => &mut regs.rax,
+            "rbx" => &mut regs.rbx,
+            "rcx" => &mut regs.rcx,
+            "rdx" => &mut regs.rdx,
+            "rsi" => &mut regs.rsi,
+            "rdi" => &mut regs.rdi,
+            "rbp" | "fp" => &mut regs.rbp,
+            "rsp" | "sp" => &mut regs.rsp,
+            "r8" => &mut regs.r8,
+            "r9" => &mut regs.r9,
+            "r10" => &mut regs.r10,
+            "r11" => &mut regs.r11,
+            "r12" => &mut regs.r12,
+            "r13" => &mut regs.r13,
+            "r14" => &mut regs.r14,
+            "r15" => &mut regs.r15,
+            "rip" | "pc" => &mut regs.rip,
+            "eflags" => &mut regs.eflags,
+            "orig_rax" => &mut regs.orig_rax,
+            "fs_base" => &mut regs.fs_base,
+            "gs_base" => &mut regs.gs_base,
+            _ => return None,
+        });
+        #[cfg(target_arch = "aarch64")]
+        return match name {
+            "sp" => Some(&mut regs.sp),
+            "pc" => Some(&mut regs.pc),
+            "pstate" => Some(&mut regs.pstate),
+            "fp" => Some(&mut regs.regs[29]),
+            "lr" => Some(&mut regs.regs[30]),
+            _ => X_REGISTERS.iter().position(|register| *register == name).map(|index| &mut regs.regs[index]),
+        };
+    }
+
+    /// The name of the system call `number`.
+    pub fn syscall_name(self, number: u64) -> &'static str {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Arch::X86_64 => syscall::syscall_name(number),
+            #[cfg(target_arch = "x86_64")]
+            Arch::I386 => syscall::syscall_name_i386(number),
+            #[cfg(target_arch = "aarch64")]
+            Arch::Aarch64 => syscall::syscall_name_aarch64(number),
+        }
+    }
+
+    /// The value a system call returned: `rax`, or `eax` for a 32-bit program.
+    pub fn return_value(self, rax: u64) -> u64 {
+        if self.is_i386() {
+            rax & 0xffff_ffff
+        } else {
+            rax
+        }
+    }
+
+    /// The six arguments of the system call a thread is stopped at the entry of: `rdi`, `rsi`,
+    /// `rdx`, `r10`, `r8` and `r9` on x86_64, `ebx` to `ebp` for a 32-bit program, `x0` to `x5`
+    /// on aarch64.
+    pub fn syscall_arguments(self, regs: &user_regs_struct) -> [u64; 6] {
+        let mut regs = *regs;
+        self.syscall_argument_registers(&mut regs).map(|register| *register)
+    }
+
+    /// The registers of the six arguments of a system call, as [`Arch::syscall_arguments`] reads
+    /// them, to set them before running one.
+    pub fn syscall_argument_registers(self, regs: &mut user_regs_struct) -> [&mut u64; 6] {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Arch::X86_64 => [&mut regs.rdi, &mut regs.rsi, &mut regs.rdx, &mut regs.r10, &mut regs.r8, &mut regs.r9],
+            #[cfg(target_arch = "x86_64")]
+            Arch::I386 => [&mut regs.rbx, &mut regs.rcx, &mut regs.rdx, &mut regs.rsi, &mut regs.rdi, &mut regs.rbp],
+            #[cfg(target_arch = "aarch64")]
+            Arch::Aarch64 => {
+                let [x0, x1, x2, x3, x4, x5, ..] = &mut regs.regs;
+                [x0, x1, x2, x3, x4, x5]
+            }
+        }
+    }
+
+    /// Set the number of the system call run by the next system call instruction: `rax`, or `x8`
+    /// on aarch64.
+    pub fn set_syscall_number(self, regs: &mut user_regs_struct, number: u64) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            regs.rax = number;
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            regs.regs[8] = number;
+        }
+    }
+
+    /// The first `count` integer arguments of a call, from the registers on x86_64 and aarch64, from
+    /// the stack for a 32-bit program, the first one at `arguments` (above the return address).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::Memory`] if the stack can't be read.
+    pub fn call_arguments(self, tracee: &(impl Tracee + ?Sized), regs: &user_regs_struct, arguments: u64, count: usize) -> Result<Vec<u64>, DbgError> {
+        if self.is_i386() {
+            return (0..count as u64).map(|index| self.read_pointer(tracee, arguments + index * 4)).collect();
+        }
+        let mut regs = *regs;
+        Ok(call_argument_registers(&mut regs).into_iter().map(|register| *register).take(count).collect())
+    }
+}
+
+/// The registers of the integer arguments of a call: `rdi`, `rsi`, `rdx`, `rcx`, `r8` and `r9` on
+/// x86_64, `x0` to `x7` on aarch64.
+pub fn call_argument_registers(regs: &mut user_regs_struct) -> Vec<&mut u64> {
+    #[cfg(target_arch = "x86_64")]
+    return vec![&mut regs.rdi, &mut regs.rsi, &mut regs.rdx, &mut regs.rcx, &mut regs.r8, &mut regs.r9];
+    #[cfg(target_arch = "aarch64")]
+    return regs.regs[..8].iter_mut().collect();
+}
+
+/// Refuse what decodes x86 code or uses registers of x86_64 only, as `disas` or the debug
+/// registers, when the debugger runs on another architecture.
+///
+/// # Errors
+///
+/// Returns [`DbgError::X86Only`] with `what` on aarch64.
+pub fn require_x86(what: &'static str) -> Result<(), DbgError> {
+    if cfg!(target_arch = "x86_64") {
+        Ok(())
+    } else {
+        Err(DbgError::X86Only(what))
+    }
+}
+
+/// The bits of `eflags` named by [`eflags_names`], from the lowest.
+const EFLAGS: &[(u32, &str)] = &[(0, "CF"), (2, "PF"), (4, "AF"), (6, "ZF"), (7, "SF"), (8, "TF"), (9, "IF"), (10, "DF"), (11, "OF")];
+
+/// The names of the flags set in `eflags`, from the lowest bit, as in `["ZF", "IF"]`.
+pub fn eflags_names(eflags: u64) -> Vec<&'static str> {
+    EFLAGS.iter().filter(|(bit, _)| eflags & (1 << bit) != 0).map(|(_, name)| *name).collect()
+}
+
+/// Decode `eflags` as gdb shows it: `[ ZF IF ]`, `[ ]` when no flag is set.
+pub fn describe_eflags(eflags: u64) -> String {
+    let names = eflags_names(eflags);
+    if names.is_empty() {
+        "[ ]".to_string()
+    } else {
+        format!("[ {} ]", names.join(" "))
+    }
+}
@@ -0,0 +1,191 @@
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::location;
+use serde_json::json;
+use std::cmp::Ordering;
+
+/// A comparison of `assert`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    /// The operators, the two-character ones before the ones they start with.
+    const ALL: [(&'static str, Operator); 6] = [
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LessOrEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("<", Operator::Less),
+        (">", Operator::Greater),
+    ];
+
+    /// Whether two values ordered as `ordering` satisfy the operator.
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Equal => ordering == Ordering::Equal,
+            Operator::NotEqual => ordering != Ordering::Equal,
+            Operator::Less => ordering == Ordering::Less,
+            Operator::LessOrEqual => ordering != Ordering::Greater,
+            Operator::Greater => ordering == Ordering::Greater,
+            Operator::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A value of an assertion, with its type as C would give it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    /// The bits of the value, the low ones for a 32-bit value.
+    pub value: u64,
+    /// Whether it is signed, as a register, a memory read or a number up to `0x7fffffffffffffff`,
+    /// or unsigned, as an address.
+    pub signed: bool,
+    /// Its width, 64 bits, or 32 for the registers of i386 (`$eax`) and the memory reads of
+    /// 32-bit programs.
+    pub bits: u32,
+}
+
+impl Operand {
+    /// A signed 64-bit value.
+    pub fn signed(value: i64) -> Operand {
+        Operand { value: value as u64, signed: true, bits: 64 }
+    }
+
+    /// An unsigned 64-bit value.
+    pub fn unsigned(value: u64) -> Operand {
+        Operand { value, signed: false, bits: 64 }
+    }
+
+    /// The value sign-extended to 64 bits if it is signed.
+    pub fn as_i64(self) -> i64 {
+        match (self.signed, self.bits) {
+            (true, 32) => self.value as u32 as i32 as i64,
+            (false, 32) => self.value as u32 as i64,
+            _ => self.value as i64,
+        }
+    }
+}
+
+/// Compare two values with the usual conversions of C: as signed 64-bit integers, unless one of
+/// them is unsigned, when both are converted to unsigned 64-bit integers, so that `-1` is the
+/// largest one.
+pub fn compare(left: Operand, operator: Operator, right: Operand) -> bool {
+    let ordering = if left.signed && right.signed {
+        left.as_i64().cmp(&right.as_i64())
+    } else {
+        (left.as_i64() as u64).cmp(&(right.as_i64() as u64))
+    };
+    operator.holds(ordering)
+}
+
+/// Split the expression given to `assert` into its operands and comparison: `<value>` alone,
+/// true if not 0, or `<value> <operator> <value>`.
+///
+/// # Errors
+///
+/// Returns a message if an operand is missing, or the operator is unknown.
+pub fn parse_assertion(text: &str) -> Result<(String, Option<(Operator, String)>), String> {
+    let text = text.trim();
+    let Some(start) = text.find(['=', '!', '<', '>']) else {
+        return Ok((text.to_string(), None));
+    };
+    let Some((symbol, operator)) = Operator::ALL.iter().find(|(symbol, _)| text[start..].starts_with(symbol)) else {
+        return Err(format!("Unknown comparison in \"{}\", expected ==, !=, <, <=, > or >=", text));
+    };
+    let (left, right) = (text[..start].trim(), text[start + symbol.len()..].trim());
+    if left.is_empty() || right.is_empty() {
+        return Err(format!("Missing a value on a side of {} in \"{}\"", symbol, text));
+    }
+    Ok((left.to_string(), Some((*operator, right.to_string()))))
+}
+
+/// Evaluate an operand of an assertion: a value as addresses are read (numbers, registers,
+/// convenience variables and symbols, with an offset), `*<operand>` for the pointer-sized integer
+/// in memory at an address, or `-<operand>` for its negation.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Parse`] if the value can't be read, or the error of reading the memory.
+pub fn evaluate(debugger: &mut Debugger, text: &str) -> Result<Operand, DbgError> {
+    let text = text.trim();
+    if let Some(rest) = text.strip_prefix('-') {
+        return Ok(Operand::signed(evaluate(debugger, rest)?.as_i64().wrapping_neg()));
+    }
+    if let Some(rest) = text.strip_prefix('*') {
+        let address = evaluate(debugger, rest)?.as_i64() as u64;
+        let size = debugger.arch.pointer_size();
+        let bytes = debugger.read_memory(address, size as usize)?;
+        let value = bytes.iter().rev().fold(0, |value, byte| (value << 8) | u64::from(*byte));
+        return Ok(Operand { value, signed: true, bits: size as u32 * 8 });
+    }
+    let value = location::parse_value(debugger, text, "value")?;
+    if text.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(Operand { value, signed: value <= i64::MAX as u64, bits: 64 });
+    }
+    // A register or a convenience variable, or an address: a symbol, or anything plus an offset.
+    match text.strip_prefix('$').filter(|_| !text.contains('+')) {
+        Some(name) if location::is_half_register(name) => Ok(Operand { value, signed: true, bits: 32 }),
+        Some(_) => Ok(Operand::signed(value as i64)),
+        None => Ok(Operand::unsigned(value)),
+    }
+}
+
+/// Check an assertion (`assert <expression>`), and print whether it holds. In batch mode, a
+/// failed assertion ends the session: the program is killed, and the debugger exits with a status
+/// of 3.
+///
+/// # Returns
+///
+/// False if the expression is invalid or the assertion failed.
+pub fn assert(debugger: &mut Debugger, text: &str) -> bool {
+    let checked = parse_assertion(text).and_then(|(left, comparison)| {
+        let evaluated = |debugger: &mut Debugger, text: &str| evaluate(debugger, text).map_err(|err| err.to_string());
+        let left_value = evaluated(debugger, &left)?;
+        match comparison {
+            None => Ok((compare(left_value, Operator::NotEqual, Operand::signed(0)), vec![(left, left_value)])),
+            Some((operator, right)) => {
+                let right_value = evaluated(debugger, &right)?;
+                Ok((compare(left_value, operator, right_value), vec![(left, left_value), (right, right_value)]))
+            }
+        }
+    });
+    let (passed, operands) = match checked {
+        Ok(checked) => checked,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let values: Vec<String> = operands
+        .iter()
+        .filter(|(text, _)| text.parse::<i64>().is_err() && !text.starts_with("0x"))
+        .map(|(text, operand)| format!("{} = {:#x} ({})", text, operand.as_i64(), operand.as_i64()))
+        .collect();
+    json::set_data(debugger, |_| {
+        let operands: Vec<_> = operands.iter().map(|(text, operand)| json!({ "expression": text, "value": operand.as_i64() })).collect();
+        json!({ "assertion": text.trim(), "passed": passed, "operands": operands })
+    });
+    if passed {
+        outln!(debugger, "Assertion passed: {}", text.trim());
+        return true;
+    }
+    if values.is_empty() {
+        errln!(debugger, "Assertion failed: {}", text.trim());
+    } else {
+        errln!(debugger, "Assertion failed: {}, with {}", text.trim(), values.join(", "));
+    }
+    if debugger.batch {
+        crate::launch::release(debugger);
+        debugger.assertion_failed = true;
+        debugger.quit_requested = true;
+    }
+    false
+}
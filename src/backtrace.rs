@@ -0,0 +1,373 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::maps::{self, MapEntry};
+use crate::memory::read_bytes;
+use crate::tracee::Tracee;
+use crate::unwind::{self, CfiStep, FrameRules, UnwindRegisters, FP, RETURN_ADDRESS, SP};
+
+/// Default maximum number of frames shown by `bt`.
+pub const DEFAULT_BACKTRACE_LIMIT: usize = 32;
+/// Default maximum number of inlined functions shown for each stack frame.
+pub const DEFAULT_INLINE_DEPTH: usize = 8;
+
+/// A stack frame recovered by the unwinder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Program counter: `rip` for the innermost frame, the return address for the others.
+    pub pc: u64,
+    /// Stack pointer of the frame.
+    pub sp: u64,
+    /// Frame pointer of the frame, if it could be recovered.
+    pub bp: Option<u64>,
+    /// Every register value recovered for the frame.
+    pub registers: UnwindRegisters,
+}
+
+impl Frame {
+    fn new(registers: UnwindRegisters) -> Option<Frame> {
+        Some(Frame { pc: registers.pc()?, sp: registers.sp()?, bp: registers.bp(), registers })
+    }
+}
+
+/// Where the innermost function is in its `push rbp; mov rbp, rsp` prologue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prologue {
+    /// Nothing has been pushed yet, the return address is at `[rsp]`.
+    BeforePush,
+    /// `rbp` has been pushed but not set, the return address is above it, at `[rsp + 8]`.
+    AfterPush,
+    /// `rbp` is set up, the return address is at `[rbp + 8]`.
+    Body,
+}
+
+fn prologue_state(tracee: &dyn Tracee, function_start: u64, offset: u64) -> Prologue {
+    let Ok(bytes) = read_bytes(tracee, function_start, 8) else {
+        return Prologue::Body;
+    };
+    // Skip the endbr64 (endbr32) emitted with -fcf-protection.
+    let push = if bytes[..3] == [0xf3, 0x0f, 0x1e] && matches!(bytes[3], 0xfa | 0xfb) { 4 } else { 0 };
+    if offset <= push as u64 {
+        return Prologue::BeforePush;
+    }
+    if bytes[push] == 0x55 && offset == push as u64 + 1 {
+        return Prologue::AfterPush;
+    }
+    Prologue::Body
+}
+
+/// Unwind one frame by following the saved frame pointer.
+///
+/// The innermost frame is special-cased when it is stopped in its prologue, before `rbp` is set up.
+///
+/// # Arguments
+///
+/// * `tracee` - The thread being debugged, or the thread of the core file.
+/// * `frame` - The frame to unwind.
+/// * `innermost` - True for frame #0.
+/// * `stack` - The stack mapping, saved frame pointers outside of it end the chain.
+/// * `function_offset` - Returns the offset of an address inside its function, if known.
+/// * `arch` - The architecture of the program, whose stack slots are 8 or 4 bytes.
+pub fn frame_pointer_step(
+    tracee: &dyn Tracee,
+    frame: &Frame,
+    innermost: bool,
+    stack: &MapEntry,
+    function_offset: &mut dyn FnMut(u64) -> Option<u64>,
+    arch: Arch,
+) -> Option<UnwindRegisters> {
+    let slot = arch.pointer_size();
+    let (return_slot, bp_slot) = frame_pointer_slots(tracee, frame, innermost, function_offset, arch)?;
+    let caller_bp = match bp_slot {
+        // Saved frame pointers must be on the stack, above the frame.
+        Some(bp) if !stack.contains(bp) || bp < frame.sp => return None,
+        Some(bp) => arch.read_pointer(tracee, bp).ok(),
+        None => frame.bp,
+    };
+    if !stack.contains(return_slot) {
+        return None;
+    }
+    let return_address = arch.read_pointer(tracee, return_slot).ok()?;
+    Some(frame.registers.caller(return_address, return_slot + slot, caller_bp))
+}
+
+/// Find the stack slots of the return address of a frame and of the frame pointer it saved, if
+/// it pushed one yet, following the frame pointer chain as [`frame_pointer_step`] does.
+fn frame_pointer_slots(
+    tracee: &dyn Tracee,
+    frame: &Frame,
+    innermost: bool,
+    function_offset: &mut dyn FnMut(u64) -> Option<u64>,
+    arch: Arch,
+) -> Option<(u64, Option<u64>)> {
+    let slot = arch.pointer_size();
+    let prologue = match function_offset(frame.pc) {
+        Some(offset) if innermost => prologue_state(tracee, frame.pc - offset, offset),
+        _ => Prologue::Body,
+    };
+    match prologue {
+        Prologue::BeforePush => Some((frame.sp, None)),
+        Prologue::AfterPush => Some((frame.sp + slot, Some(frame.sp))),
+        Prologue::Body => frame.bp.map(|bp| (bp + slot, Some(bp))),
+    }
+}
+
+/// Find how the caller of a frame is recovered, as [`backtrace`] does: with the call frame
+/// information of its module, or else by following the frame pointer chain.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `index` - The number of the frame, 0 for the innermost.
+/// * `frame` - The frame.
+///
+/// # Returns
+///
+/// None if neither finds where the frame saved its return address.
+pub fn frame_rules(debugger: &mut Debugger, index: usize, frame: &Frame) -> Option<FrameRules> {
+    let tracee = debugger.tracee();
+    let arch = debugger.arch;
+    let lookup = if index == 0 { frame.pc } else { frame.pc - 1 };
+    let module = debugger.modules().into_iter().find(|module| frame.pc >= module.start && frame.pc < module.end);
+    if let Some(module) = module.filter(|_| !arch.is_i386()) {
+        if let (Some(cfi), Some(bias)) = (debugger.cfi(&module.path), module.bias) {
+            if let Some(rules) = cfi.rules(&frame.registers, lookup, bias) {
+                return Some(rules);
+            }
+        }
+    }
+    let slot = arch.pointer_size();
+    let mut function_offset = |address: u64| debugger.symbolize(address).map(|(_, offset, _)| offset);
+    let (return_slot, bp_slot) = frame_pointer_slots(tracee.as_ref(), frame, index == 0, &mut function_offset, arch)?;
+    // The slots must be on the stack, above the frame, as frame_pointer_step checks.
+    let mappings = debugger.mappings.clone();
+    let stack = maps::find_mapping(&mappings, frame.sp)?;
+    let on_stack = |slot: u64| stack.contains(slot) && slot >= frame.sp;
+    if !on_stack(return_slot) || bp_slot.is_some_and(|bp| !on_stack(bp)) {
+        return None;
+    }
+    let cfa = return_slot + slot;
+    let cfa_rule = match bp_slot {
+        Some(bp) if Some(bp) == frame.bp => (FP, 2 * slot as i64),
+        _ => (SP, cfa.wrapping_sub(frame.sp) as i64),
+    };
+    let mut saved: Vec<(usize, u64)> = bp_slot.map(|bp| (FP, bp)).into_iter().collect();
+    saved.push((RETURN_ADDRESS, return_slot));
+    Some(FrameRules { cfi: false, cfa_rule, cfa, saved })
+}
+
+/// Describe the selected frame (`info frame`): its canonical frame address, where its return
+/// address and the registers of its caller are saved, found by the call frame information or the
+/// frame pointer chain, with their values, and its size.
+///
+/// # Returns
+///
+/// False if the program is not running or its stack can't be read.
+pub fn info_frame(debugger: &mut Debugger) -> bool {
+    if !debugger.state.is_alive() {
+        errln!(debugger, "The program is not being run.");
+        return false;
+    }
+    let index = debugger.selected_frame;
+    let frames = match backtrace(debugger, index + 2) {
+        Ok(frames) if index < frames.len() => frames,
+        Ok(frames) => {
+            errln!(debugger, "No frame at level {} (the stack has {} frames).", index, frames.len());
+            return false;
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let frame = frames[index];
+    let rules = frame_rules(debugger, index, &frame);
+    let tracee = debugger.tracee();
+    let arch = debugger.arch;
+    let cfa = rules.as_ref().map_or(frame.sp, |rules| rules.cfa);
+    outln!(debugger, "Stack level {}, frame at {:#x}:", index, cfa);
+    print_frame(debugger, index, &frame);
+    if index > 0 {
+        outln!(debugger, "Caller of the frame at level {}.", index - 1);
+    }
+    if index + 1 < frames.len() {
+        outln!(debugger, "Called by the frame at level {}.", index + 1);
+    }
+    let Some(rules) = rules else {
+        outln!(debugger, "No call frame information nor frame pointer for this frame, it is the outermost.");
+        json::set_data(debugger, |debugger| serde_json::json!({ "frame": frame_data(debugger, index, &frame) }));
+        return true;
+    };
+    let (register, offset) = rules.cfa_rule;
+    let unwinder = if rules.cfi { "the call frame information" } else { "the frame pointer chain" };
+    outln!(debugger, "Unwound with {}: CFA = {}{:+}", unwinder, unwind::register_name(register), offset);
+    outln!(debugger, "Frame size: {} bytes", rules.cfa.wrapping_sub(frame.sp));
+    let saved: Vec<(usize, u64, Option<u64>)> = rules
+        .saved
+        .iter()
+        .map(|&(register, slot)| (register, slot, arch.read_pointer(tracee.as_ref(), slot).ok()))
+        .collect();
+    let value = |value: Option<u64>| value.map_or("<unreadable>".to_string(), |value| format!("{:#x}", value));
+    match saved.iter().find(|(register, _, _)| *register == RETURN_ADDRESS) {
+        Some(&(_, slot, return_address)) => outln!(debugger, "Saved return address: {} at {:#x}", value(return_address), slot),
+        None => outln!(debugger, "No saved return address, the frame is the outermost."),
+    }
+    let registers: Vec<_> = saved.iter().filter(|(register, _, _)| *register != RETURN_ADDRESS).collect();
+    if registers.is_empty() {
+        outln!(debugger, "No saved registers.");
+    } else {
+        outln!(debugger, "Saved registers:");
+        for &&(register, slot, saved_value) in &registers {
+            outln!(debugger, "  {:<4} at {:#x}: {}", unwind::register_name(register), slot, value(saved_value));
+        }
+    }
+    json::set_data(debugger, |debugger| {
+        let saved: Vec<serde_json::Value> = saved
+            .iter()
+            .map(|&(register, slot, value)| {
+                serde_json::json!({
+                    "register": unwind::register_name(register),
+                    "address": json::address(slot),
+                    "value": value.map(json::address),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "frame": frame_data(debugger, index, &frame),
+            "cfa": json::address(rules.cfa),
+            "unwinder": if rules.cfi { "cfi" } else { "frame-pointer" },
+            "cfa_rule": format!("{}{:+}", unwind::register_name(register), offset),
+            "size": rules.cfa.wrapping_sub(frame.sp),
+            "saved": saved,
+        })
+    });
+    true
+}
+
+/// Print the lines describing a frame: number, pc, symbol+offset and file:line when available.
+///
+/// Functions inlined at the pc are listed first, marked "(inlined)", each with its own location.
+/// They share the registers of the frame, so only the first line carries the frame number.
+pub fn print_frame(debugger: &mut Debugger, index: usize, frame: &Frame) {
+    // Return addresses point after the call, look up the call itself.
+    let lookup = if index == 0 { frame.pc } else { frame.pc - 1 };
+    let functions = debugger.function_frames(lookup);
+    let mut prefix = format!("#{:<3} ", index);
+    for function in functions.iter().filter(|function| function.inlined) {
+        let mut line = format!("{}{:#018x} in {} (inlined)", prefix, frame.pc, function.function.as_deref().unwrap_or("??"));
+        if let Some(location) = &function.location {
+            line.push_str(&format!(" at {}:{}", location.file, location.line));
+        }
+        outln!(debugger, "{}", line);
+        prefix = " ".repeat(prefix.len());
+    }
+    let mut line = format!("{}{:#018x} in ", prefix, frame.pc);
+    match debugger.symbolize(frame.pc) {
+        Some((name, 0, _)) => line.push_str(&name),
+        Some((name, offset, _)) => line.push_str(&format!("{}+{:#x}", name, offset)),
+        None => line.push_str("??"),
+    }
+    let location = match functions.last() {
+        Some(function) => function.location.clone(),
+        None => debugger.source_location(lookup),
+    };
+    if let Some(location) = location {
+        line.push_str(&format!(" at {}:{}", location.file, location.line));
+    }
+    outln!(debugger, "{}", line);
+}
+
+/// Describe a frame for the JSON record of `bt`: its number, pc, function and source line,
+/// with the functions inlined at the pc, innermost first.
+pub fn frame_data(debugger: &mut Debugger, index: usize, frame: &Frame) -> serde_json::Value {
+    let lookup = if index == 0 { frame.pc } else { frame.pc - 1 };
+    let functions = debugger.function_frames(lookup);
+    let inlined: Vec<serde_json::Value> = functions
+        .iter()
+        .filter(|function| function.inlined)
+        .map(|function| {
+            let location = function.location.as_ref();
+            serde_json::json!({
+                "function": function.function,
+                "file": location.map(|location| &location.file),
+                "line": location.map(|location| location.line),
+            })
+        })
+        .collect();
+    let (function, offset) = match debugger.symbolize(frame.pc) {
+        Some((name, offset, _)) => (Some(name), Some(offset)),
+        None => (None, None),
+    };
+    let location = match functions.last() {
+        Some(function) => function.location.clone(),
+        None => debugger.source_location(lookup),
+    };
+    serde_json::json!({
+        "level": index,
+        "pc": crate::json::address(frame.pc),
+        "function": function,
+        "offset": offset,
+        "file": location.as_ref().map(|location| &location.file),
+        "line": location.as_ref().map(|location| location.line),
+        "inlined": inlined,
+    })
+}
+
+/// Compute the backtrace of the child.
+///
+/// Each frame is unwound with the call frame information (`.eh_frame`/`.debug_frame`) of the
+/// module containing it, falling back to the frame pointer chain when there is none. The frames of
+/// a 32-bit program follow the frame pointer chain only, its call frame information numbering the
+/// registers of i386.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `limit` - The maximum number of frames to return.
+pub fn backtrace(debugger: &mut Debugger, limit: usize) -> Result<Vec<Frame>, DbgError> {
+    let tracee = debugger.tracee();
+    let regs = tracee.getregs().map_err(DbgError::ptrace("read the registers"))?;
+    let mappings = debugger.mappings.clone();
+    let modules = debugger.modules();
+    let arch = debugger.arch;
+    let mut frames = Vec::new();
+    frames.extend(Frame::new(UnwindRegisters::from_regs(&regs)));
+    let Some(stack) = maps::find_mapping(&mappings, regs.sp()) else {
+        return Ok(frames);
+    };
+    while frames.len() < limit {
+        let frame = frames[frames.len() - 1];
+        let innermost = frames.len() == 1;
+        // Return addresses point after the call, look up the call itself.
+        let lookup = if innermost { frame.pc } else { frame.pc - 1 };
+        let step = match modules.iter().find(|module| frame.pc >= module.start && frame.pc < module.end) {
+            Some(_) if arch.is_i386() => CfiStep::NoInfo,
+            Some(module) => match (debugger.cfi(&module.path), module.bias) {
+                (Some(cfi), Some(bias)) => cfi.unwind(tracee.as_ref(), &frame.registers, lookup, bias),
+                _ => CfiStep::NoInfo,
+            },
+            None => CfiStep::NoInfo,
+        };
+        let caller = match step {
+            CfiStep::Caller(registers) => *registers,
+            CfiStep::Outermost => break,
+            CfiStep::NoInfo => {
+                let mut function_offset = |address: u64| debugger.symbolize(address).map(|(_, offset, _)| offset);
+                match frame_pointer_step(tracee.as_ref(), &frame, innermost, stack, &mut function_offset, arch) {
+                    Some(registers) => registers,
+                    None => break,
+                }
+            }
+        };
+        let Some(caller) = Frame::new(caller) else {
+            break;
+        };
+        let executable = maps::find_mapping(&mappings, caller.pc).is_some_and(|mapping| mapping.is_executable());
+        if !executable || caller.sp <= frame.sp {
+            break;
+        }
+        frames.push(caller);
+    }
+    Ok(frames)
+}
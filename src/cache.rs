@@ -0,0 +1,144 @@
+use crate::tracee::Tracee;
+use nix::errno::Errno;
+use nix::libc::user_regs_struct;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The size of the pages of memory kept.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// The pages kept at most, the cache starting over past them.
+const MAX_PAGES: usize = 64;
+
+thread_local! {
+    /// Changed by every operation that can change the registers or the memory of the process:
+    /// writing them, resuming it and waiting for it. What was read at another generation is stale.
+    static GENERATION: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Forget the registers and memory read so far, as they may have changed: the process was
+/// resumed, or written to.
+pub fn invalidate() {
+    GENERATION.set(GENERATION.get().wrapping_add(1));
+}
+
+/// The registers and memory of the process read during a stop, so that the prompt, the displays
+/// and the commands read them once: the registers of each thread, and the pages of memory read.
+/// Forgotten at the next [`invalidate`].
+#[derive(Default)]
+pub struct StopCache {
+    /// The generation the registers and pages were read at.
+    generation: Cell<u64>,
+    registers: RefCell<HashMap<Pid, user_regs_struct>>,
+    /// The pages by their address.
+    pages: RefCell<HashMap<u64, Box<[u8]>>>,
+}
+
+impl StopCache {
+    /// Forget what was read at an older generation.
+    fn refresh(&self) {
+        let generation = GENERATION.get();
+        if self.generation.replace(generation) != generation {
+            self.registers.borrow_mut().clear();
+            self.pages.borrow_mut().clear();
+        }
+    }
+
+    /// The registers of the thread `tid`, read from `tracee` once per stop.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errno of reading them, which is not kept.
+    pub fn registers(&self, tid: Pid, tracee: &(impl Tracee + ?Sized)) -> Result<user_regs_struct, Errno> {
+        self.refresh();
+        if let Some(regs) = self.registers.borrow().get(&tid) {
+            return Ok(*regs);
+        }
+        let regs = tracee.getregs()?;
+        self.registers.borrow_mut().insert(tid, regs);
+        Ok(regs)
+    }
+
+    /// The 8 bytes at `address`, from the page around it read from `tracee` once per stop. A word
+    /// across two pages, or in a page that can't be read whole, is read from `tracee` each time.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errno of reading the word.
+    pub fn read_word(&self, address: u64, tracee: &(impl Tracee + ?Sized)) -> Result<i64, Errno> {
+        self.refresh();
+        let page = address & !(PAGE_SIZE - 1);
+        let offset = (address - page) as usize;
+        if offset + 8 > PAGE_SIZE as usize {
+            return tracee.read_word(address);
+        }
+        let mut pages = self.pages.borrow_mut();
+        if !pages.contains_key(&page) {
+            let mut bytes = vec![0; PAGE_SIZE as usize].into_boxed_slice();
+            if tracee.read_page(page, &mut bytes).is_err() {
+                return tracee.read_word(address);
+            }
+            if pages.len() >= MAX_PAGES {
+                pages.clear();
+            }
+            pages.insert(page, bytes);
+        }
+        Ok(i64::from_le_bytes(pages[&page][offset..offset + 8].try_into().unwrap()))
+    }
+}
+
+/// A thread of the process, whose registers and memory are read through the [`StopCache`] of the
+/// session.
+pub struct CachedTracee<T: Tracee> {
+    pub tid: Pid,
+    pub tracee: T,
+    pub cache: Rc<StopCache>,
+}
+
+impl<T: Tracee> Tracee for CachedTracee<T> {
+    fn read_word(&self, address: u64) -> Result<i64, Errno> {
+        self.cache.read_word(address, &self.tracee)
+    }
+
+    fn read_page(&self, address: u64, page: &mut [u8]) -> Result<(), Errno> {
+        self.tracee.read_page(address, page)
+    }
+
+    fn write_word(&mut self, address: u64, word: i64) -> Result<(), Errno> {
+        invalidate();
+        self.tracee.write_word(address, word)
+    }
+
+    fn getregs(&self) -> Result<user_regs_struct, Errno> {
+        self.cache.registers(self.tid, &self.tracee)
+    }
+
+    fn setregs(&mut self, regs: user_regs_struct) -> Result<(), Errno> {
+        invalidate();
+        self.tracee.setregs(regs)
+    }
+
+    fn cont(&mut self) -> Result<(), Errno> {
+        invalidate();
+        self.tracee.cont()
+    }
+
+    fn step(&mut self) -> Result<(), Errno> {
+        invalidate();
+        self.tracee.step()
+    }
+
+    fn syscall(&mut self) -> Result<(), Errno> {
+        invalidate();
+        self.tracee.syscall()
+    }
+
+    fn wait(&mut self) -> Result<WaitStatus, Errno> {
+        let status = self.tracee.wait();
+        invalidate();
+        status
+    }
+}
@@ -0,0 +1,260 @@
+use crate::arch::{call_argument_registers, get_fp_registers, set_fp_registers, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::location;
+use crate::memory::write_bytes;
+use crate::printf::unescape;
+use crate::step::{self, StepStop};
+use crate::tracee::Tracee;
+use crate::working::{armed_breakpoints, remove_breakpoint, set_breakpoint};
+use serde_json::json;
+
+/// The most arguments passed, those that fit in the argument registers of the System V ABI:
+/// `rdi`, `rsi`, `rdx`, `rcx`, `r8` and `r9` (`x0` to `x5` on aarch64).
+pub const MAX_ARGUMENTS: usize = 6;
+/// The bytes below the stack pointer a function may use without moving it, left untouched by a call.
+const RED_ZONE: u64 = 128;
+
+/// An argument of `call`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallArgument {
+    /// A value, read as addresses are (numbers, registers, convenience variables and symbols, with
+    /// an offset), or a negative number.
+    Value(String),
+    /// A string literal, with its escapes replaced. It is written to the stack of the program with
+    /// a terminating NUL, and passed by its address.
+    Text(String),
+}
+
+/// How a function called by the debugger ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallOutcome {
+    /// The function returned, with this value of `rax` (`x0` on aarch64).
+    Returned(u64),
+    /// The program stopped in the function, by a signal or a breakpoint instruction. The call was
+    /// abandoned and the program put back as it was.
+    Abandoned(StepStop),
+    /// The program terminated during the call.
+    Terminated(StepStop),
+}
+
+/// Split the text given to `call` into the function and its arguments: `function(argument, ...)`,
+/// the parentheses being optional without arguments.
+///
+/// # Errors
+///
+/// Returns a message if the parentheses or the quotes of a string are not closed, or an argument
+/// is empty.
+pub fn parse_call(text: &str) -> Result<(String, Vec<CallArgument>), String> {
+    let text = text.trim();
+    let Some((function, rest)) = text.split_once('(') else {
+        return Ok((text.to_string(), Vec::new()));
+    };
+    let Some(inside) = rest.trim_end().strip_suffix(')') else {
+        return Err(format!("Missing the ) closing the arguments: {}", text));
+    };
+    let mut arguments = Vec::new();
+    let mut chars = inside.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None if arguments.is_empty() => break,
+            None => return Err(format!("Missing an argument after the last comma: {}", text)),
+            Some('"') => {
+                chars.next();
+                let mut literal = String::new();
+                let mut escaped = false;
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("Missing the \" closing a string: {}", text)),
+                        Some('"') if !escaped => break,
+                        Some(c) => {
+                            escaped = c == '\\' && !escaped;
+                            literal.push(c);
+                        }
+                    }
+                }
+                arguments.push(CallArgument::Text(unescape(&literal)));
+            }
+            Some(_) => {
+                let mut value = String::new();
+                while let Some(c) = chars.next_if(|c| *c != ',') {
+                    value.push(c);
+                }
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err(format!("Empty argument: {}", text));
+                }
+                arguments.push(CallArgument::Value(value.to_string()));
+            }
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None => break,
+            Some(',') => {}
+            Some(c) => return Err(format!("Unexpected '{}' after a string, arguments are separated by commas: {}", c, text)),
+        }
+    }
+    Ok((function.trim().to_string(), arguments))
+}
+
+/// Call a function of the program from the current thread, with the System V calling convention:
+/// the integer and pointer arguments in registers, the strings written below the red zone of the
+/// stack, and the entry point of the program as the return address (pushed, or in `x30` on
+/// aarch64), where a breakpoint
+/// instruction catches the return. The breakpoints are disarmed during the call, and the other
+/// threads stay stopped. The registers, general purpose and floating point, are restored
+/// afterwards, unless the program terminated.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `function` - The address of the function.
+/// * `arguments` - Its arguments, at most [`MAX_ARGUMENTS`].
+///
+/// # Errors
+///
+/// Returns [`DbgError::Parse`] if an argument can't be read, [`DbgError::NoEntryPoint`] if the
+/// entry point of the program is not known, or the error of the operation that failed.
+pub fn call_function(debugger: &mut Debugger, function: u64, arguments: &[CallArgument]) -> Result<CallOutcome, DbgError> {
+    let Some(executable) = debugger.executable().cloned() else {
+        return Err(DbgError::NoEntryPoint);
+    };
+    let return_address = executable.header.entry.wrapping_add(executable.bias.unwrap_or_default());
+    // The values are read before anything is changed, in the selected frame.
+    let mut values = Vec::new();
+    for argument in arguments {
+        values.push(match argument {
+            CallArgument::Value(text) => match text.strip_prefix('-') {
+                Some(text) => Some(location::parse_value(debugger, text, "argument")?.wrapping_neg()),
+                None => Some(location::parse_value(debugger, text, "argument")?),
+            },
+            CallArgument::Text(_) => None,
+        });
+    }
+    let mut thread = debugger.thread;
+    let saved = thread.getregs().map_err(DbgError::ptrace("read the registers"))?;
+    let saved_fpregs = get_fp_registers(thread).ok();
+    let mut sp = saved.sp() - RED_ZONE;
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        if let CallArgument::Text(text) = argument {
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.push(0);
+            sp -= bytes.len() as u64;
+            write_bytes(thread, sp, &bytes)?;
+            *value = Some(sp);
+        }
+    }
+    let mut regs = saved;
+    for (register, value) in call_argument_registers(&mut regs).into_iter().zip(values) {
+        *register = value.unwrap_or_default();
+    }
+    #[cfg(target_arch = "x86_64")]
+    let frame_sp = {
+        // The stack is aligned on 16 bytes at the call, before the return address is pushed.
+        sp = (sp & !0xf) - 8;
+        write_bytes(thread, sp, &return_address.to_le_bytes())?;
+        // No vector registers for variadic functions.
+        regs.rax = 0;
+        sp + 8
+    };
+    #[cfg(target_arch = "aarch64")]
+    let frame_sp = {
+        // The stack is aligned on 16 bytes, the return address is in the link register.
+        sp &= !0xf;
+        regs.regs[30] = return_address;
+        sp
+    };
+    regs.set_sp(sp);
+    regs.set_pc(function);
+    // Not in a system call anymore, which the kernel would restart.
+    regs.cancel_syscall_restart();
+    crate::cache::invalidate();
+    thread.setregs(regs).map_err(DbgError::ptrace("write the registers"))?;
+    let breakpoints = armed_breakpoints(&debugger.breakpoint_table);
+    for address in &breakpoints {
+        remove_breakpoint(&mut debugger.breakpoint_table, thread, *address)?;
+    }
+    log::debug!("Calling {:#x} with the return address {:#x} at {:#x}", function, return_address, sp);
+    let stop = step::run_until_return(debugger, return_address, frame_sp);
+    if let Ok(stop @ (StepStop::Exited(_) | StepStop::Killed(_))) = stop {
+        return Ok(CallOutcome::Terminated(stop));
+    }
+    let returned = thread.getregs().map(|regs| regs.return_value());
+    crate::cache::invalidate();
+    thread.setregs(saved).map_err(DbgError::ptrace("write the registers"))?;
+    if let Some(fpregs) = &saved_fpregs {
+        set_fp_registers(thread, fpregs).map_err(DbgError::ptrace("write the floating point registers"))?;
+    }
+    for address in breakpoints {
+        set_breakpoint(&mut debugger.breakpoint_table, thread, address)?;
+    }
+    match stop? {
+        StepStop::Done => Ok(CallOutcome::Returned(returned.map_err(DbgError::ptrace("read the registers"))?)),
+        stop => Ok(CallOutcome::Abandoned(stop)),
+    }
+}
+
+/// Call a function of the program and print the value it returned (`call`), as
+/// [`call_function`] does.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `text` - The call, `function(argument, ...)`.
+///
+/// # Returns
+///
+/// False if the call can't be made, or the function did not return.
+pub fn call(debugger: &mut Debugger, text: &str) -> bool {
+    let (name, arguments) = match parse_call(text) {
+        Ok(call) => call,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    if debugger.arch.is_i386() {
+        errln!(debugger, "Functions can only be called in 64-bit programs, the arguments of i386 are on the stack.");
+        return false;
+    }
+    if arguments.len() > MAX_ARGUMENTS {
+        errln!(debugger, "At most {} arguments can be passed, in registers.", MAX_ARGUMENTS);
+        return false;
+    }
+    let function = match location::parse_value(debugger, &name, "function") {
+        Ok(function) => function,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    match call_function(debugger, function, &arguments) {
+        Ok(CallOutcome::Returned(value)) => {
+            outln!(debugger, "{} returned {:#x} ({})", name, value, value as i64);
+            json::set_data(debugger, |_| {
+                json!({ "function": name, "address": json::address(function), "returned": json::address(value), "value": value as i64 })
+            });
+            true
+        }
+        Ok(CallOutcome::Abandoned(stop)) => {
+            let reason = match stop {
+                StepStop::Signal(signal) => format!("received {:?}", signal),
+                StepStop::Breakpoint(address) => format!("hit a breakpoint instruction at {:#x}", address),
+                stop => format!("stopped ({:?})", stop),
+            };
+            errln!(debugger, "The program {} in {}, called from the debugger. The call was abandoned and the registers restored.", reason, name);
+            false
+        }
+        Ok(CallOutcome::Terminated(stop)) => {
+            errln!(debugger, "The program terminated during the call of {}.", name);
+            step::report_stop(debugger, &stop);
+            false
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
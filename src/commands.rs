@@ -0,0 +1,2610 @@
+use crate::alias;
+use crate::antidebug;
+use crate::assertion;
+use crate::backtrace;
+use crate::call;
+use crate::debugger::Debugger;
+use crate::define;
+use crate::debuginfo;
+use crate::disasm;
+use crate::display;
+use crate::error::DbgError;
+use crate::inferior::{self, InferiorTty};
+use crate::inject;
+use crate::location::{self, Location};
+use crate::json;
+use crate::examine;
+use crate::label;
+use crate::launch::{self, LaunchSpec};
+use crate::ltrace;
+use crate::maps;
+use crate::memory;
+use crate::nonstop;
+use crate::pagewatch;
+use crate::printf;
+use crate::coredump;
+use crate::coverage;
+use crate::procfs;
+use crate::record;
+use crate::siginfo;
+use crate::snapshot;
+use crate::repl::confirm;
+use crate::script;
+use crate::sandbox;
+use crate::scripting;
+use crate::session;
+use crate::settings::{self, SETTINGS};
+use crate::sharedlib;
+use crate::state::{InferiorState, StopReason, ValidIn};
+use crate::step;
+use crate::summary;
+use crate::runstatus;
+use crate::swatch::{self, WatchStop};
+use crate::syscall;
+use crate::syscallinfo;
+use crate::threads;
+use crate::variables::{self, FormatLimits};
+use crate::watch;
+use crate::working::{self, clear_breakpoints, help_commands, show_registers};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::os::unix::process::ExitStatusExt;
+
+/// Runs a command, given its arguments (the first one being the name it was called with).
+/// Returns false if the command failed.
+pub type CommandHandler = fn(&mut Debugger, &[&str]) -> bool;
+
+/// A command of the debugger: the names it is called with, its documentation and its
+/// implementation. `run_command` dispatches to the handlers of [`COMMANDS`] and `help` shows their
+/// documentation, so every command is documented.
+pub struct CommandSpec {
+    /// The names of the command, the short ones first (`b`, `breakpoint`).
+    pub names: &'static [&'static str],
+    /// The arguments of the command, as in `<address|function|file:line>`.
+    pub arguments: &'static str,
+    /// One line describing the command, shown by `help`.
+    pub summary: &'static str,
+    /// The details shown by `help <command>`: every form of the command, with examples.
+    pub long_help: &'static str,
+    /// The states of the program in which the command can run, checked before its handler.
+    pub valid_in: ValidIn,
+    /// Runs the command.
+    pub handler: CommandHandler,
+}
+
+impl CommandSpec {
+    /// The longest name of the command, completed at the prompt.
+    pub fn name(&self) -> &'static str {
+        self.names.iter().max_by_key(|name| name.len()).copied().unwrap_or_default()
+    }
+
+    /// The names and arguments of the command, as in `b or breakpoint <address|function|file:line>`.
+    pub fn synopsis(&self) -> String {
+        let names = self.names.join(" or ");
+        if self.arguments.is_empty() {
+            names
+        } else {
+            format!("{} {}", names, self.arguments)
+        }
+    }
+}
+
+/// Every command of the debugger, in the order `help` lists them.
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        names: &["run"],
+        arguments: "[argument...] [< file]",
+        summary: "Start the program",
+        long_help: "The program is stopped before its first instruction until it is resumed. run continues it until \
+it terminates, crashes or hits a breakpoint. c and the stepping commands start it too.
+
+With arguments, or with < file to read a file as its standard input, the program is started again with them \
+first, keeping the breakpoints. They are for this run only: set args sets the ones of the next runs and restart, \
+show args shows them, and set cwd the directory the program starts in. Without < file, the standard input of a \
+captured program is a pipe written by input.
+
+Examples:
+  run -v input.txt
+  run < commands.txt",
+        valid_in: ValidIn::NotStarted,
+        handler: run,
+    },
+    CommandSpec {
+        names: &["start"],
+        arguments: "[argument...] [< file]",
+        summary: "Start the program and stop in its main function",
+        long_help: "Start the program as run does, taking the same arguments, and stop it at the first line of its \
+main function with a temporary breakpoint, removed at the next stop. The main function is the one marked as such \
+by the debugging information, as rustc marks the main of the crate, then the main of a Rust program found from \
+its mangled symbol (the user main run by std::rt::lang_start, not the main of the C library), then the main \
+symbol, then the entry point of a stripped program. start tells which one it found; --stop-at-main does the same \
+at startup.
+
+Examples:
+  start
+  start -v input.txt",
+        valid_in: ValidIn::NotStarted,
+        handler: start,
+    },
+    CommandSpec {
+        names: &["restart"],
+        arguments: "",
+        summary: "Kill the program and start it again, keeping the breakpoints",
+        long_help: "Kill the program and start it again, stopped before its first instruction. Its symbols are \
+loaded again, from the new build after rebuild, and the breakpoints are set again at the locations given to b. \
+When the program is running, restart asks first unless set confirm is off; restart! does not ask.",
+        valid_in: ValidIn::Any,
+        handler: restart,
+    },
+    CommandSpec {
+        names: &["input"],
+        arguments: "[text] | --eof",
+        summary: "Write a line to the standard input of the program, or close it",
+        long_help: "Write text and a newline to the standard input of the program, which it reads once it is \
+resumed, to drive an interactive program from the session. input alone writes an empty line, and input --eof \
+closes the standard input, so that the program reads the end of the file. The standard input is a pipe of the \
+debugger when the output is captured (set inferior-tty capture, the default) and run was not given < file. A \
+program that does not read fills the pipe: continue it before writing more.
+
+Examples:
+  input 2 + 3
+  c
+  input --eof",
+        valid_in: ValidIn::Live,
+        handler: input,
+    },
+    CommandSpec {
+        names: &["tty"],
+        arguments: "<path>",
+        summary: "Give the program another terminal for its input and output, from the next restart",
+        long_help: "Start the program with the terminal at path as its standard input, output and error, as the \
+terminal of another window found with the tty command of its shell, instead of capturing its output. The program \
+already started keeps its streams: it gets the terminal when restart starts it again. set inferior-tty capture \
+captures its output again, its lines prefixed with [out] and [err]; inherit shares the terminal of the debugger.
+
+Example:
+  tty /dev/pts/3
+  restart",
+        valid_in: ValidIn::Any,
+        handler: tty,
+    },
+    CommandSpec {
+        names: &["c", "continue"],
+        arguments: "[-a]",
+        summary: "Continue the process until completion (or the next breakpoint), reporting crashes",
+        long_help: "Continue the program until it terminates, hits a breakpoint or receives a signal.
+
+When the program crashes (SIGSEGV, SIGBUS, SIGILL, SIGFPE), a crash report shows the faulting address and
+instruction, the memory access at fault, the registers and a backtrace. With ltrace on, the library calls
+made on the way are printed. set run-status interval <seconds> shows what the program does every that many seconds
+while it runs. An empty line continues again.
+
+In non-stop mode (set non-stop on), c continues the current thread only, the one selected with thread, and waits for
+the next stop of any thread, which becomes the current one. c -a continues every stopped thread.",
+        valid_in: ValidIn::Live,
+        handler: continue_program,
+    },
+    CommandSpec {
+        names: &["interrupt"],
+        arguments: "",
+        summary: "Stop the threads left running in non-stop mode",
+        long_help: "Stop the threads left running in non-stop mode (set non-stop on), showing where each one stopped. \
+They can then be selected with thread, and resumed with c -a.",
+        valid_in: ValidIn::Live,
+        handler: nonstop::interrupt_command,
+    },
+    CommandSpec {
+        names: &["s", "syscall"],
+        arguments: "",
+        summary: "Continue the process until the next syscall (or end of syscall)",
+        long_help: "Run until the program enters or leaves a system call, showing its name and number, and its \
+return value when it leaves it.
+
+A ptrace system call, or the opening of /proc/self/status to read its TracerPid, is reported as a likely check of
+the program for a debugger. With set anti-anti-debug on, a failing ptrace(PTRACE_TRACEME) returns 0, and c watches
+the system calls too.
+
+Example:
+  s    Caught syscall write (1) (entry)
+  s    Caught syscall write (1) (exit), returned 0xd",
+        valid_in: ValidIn::Live,
+        handler: continue_to_syscall,
+    },
+    CommandSpec {
+        names: &["n", "next"],
+        arguments: "",
+        summary: "Continue to the next source line, stepping over function calls",
+        long_help: "Run until the next source line of the current function, or of its caller when it returns. \
+Called functions run until they return, unless they hit a breakpoint. Needs the line information of the program \
+(-g). An empty line steps again.",
+        valid_in: ValidIn::Live,
+        handler: step_line,
+    },
+    CommandSpec {
+        names: &["step"],
+        arguments: "",
+        summary: "Continue to the next source line, entering called functions",
+        long_help: "Run until the next source line, stopping at the first line of the functions called on the \
+way when they have line information. An empty line steps again.",
+        valid_in: ValidIn::Live,
+        handler: step_line,
+    },
+    CommandSpec {
+        names: &["si", "stepi"],
+        arguments: "",
+        summary: "Make a single step in the process (Continue to next instruction (single-step))",
+        long_help: "Execute a single instruction, then show the disassembled instruction at the new pc. An empty \
+line steps again.",
+        valid_in: ValidIn::Live,
+        handler: step_instruction,
+    },
+    CommandSpec {
+        names: &["swatch"],
+        arguments: "<address> <len> [function...]",
+        summary: "Single-step the program until bytes of memory change (software watchpoint)",
+        long_help: "Watch len bytes at an address by single-stepping the current thread and comparing them after \
+each instruction, until they change. The instruction that changed them is shown with the old and new bytes. The \
+program runs thousands of times slower than with c: Ctrl-C interrupts the scan, and the functions listed after \
+the length run at full speed until they return when they are called, a change they make being reported at the \
+call. A breakpoint hit, a signal or the end of the program stop the scan too.
+
+Examples:
+  swatch total 4
+  swatch 0x555555558010 8 printf malloc",
+        valid_in: ValidIn::Live,
+        handler: software_watchpoint,
+    },
+    CommandSpec {
+        names: &["watch"],
+        arguments: "[variable]",
+        summary: "Stop the program when a global or static variable changes",
+        long_help: "Watch a global or static variable by name: the program stops when it changes, showing its old \
+and new values with its type. A variable of 1, 2, 4 or 8 bytes aligned on its size is watched with a debug register \
+of the processor, at full speed, 4 at most. A larger one is watched by single-stepping the program when c continues \
+it, thousands of times slower, and alone. Writes leaving the value unchanged don't stop the program. Without a \
+variable, list the watchpoints with their values. unwatch deletes one, restart watches them again.
+
+Examples:
+  watch total
+  watch app::state::COUNTER",
+        valid_in: ValidIn::Live,
+        handler: watch_variable,
+    },
+    CommandSpec {
+        names: &["awatch"],
+        arguments: "<variable>",
+        summary: "Stop the program when a global or static variable is read or written",
+        long_help: "Watch the reads and writes of a global or static variable with a debug register: the program \
+stops after each access, showing the value. The variable must have 1, 2, 4 or 8 bytes aligned on its size.
+
+Example:
+  awatch total",
+        valid_in: ValidIn::Live,
+        handler: watch_variable,
+    },
+    CommandSpec {
+        names: &["unwatch"],
+        arguments: "<variable>",
+        summary: "Delete the watchpoint on a variable",
+        long_help: "Stop watching a variable watched with watch or awatch, named as it was given or by its full path.
+
+Example:
+  unwatch total",
+        valid_in: ValidIn::Any,
+        handler: unwatch_variable,
+    },
+    CommandSpec {
+        names: &["watch-region"],
+        arguments: "[<address> <len>]",
+        summary: "Stop the program when it writes to a region of memory, write-protecting its pages",
+        long_help: "Watch the writes to len bytes at an address, of any size, at full speed: the pages containing \
+them are made read-only with an mprotect run in the program, and a write to them faults. A write to the region \
+stops the program, showing the address written, the instruction that wrote and the old and new bytes. A write to \
+the pages outside of the region is done by single-stepping it with the pages writable again, and the program goes \
+on. Without arguments, list the regions with the writes caught. unwatch-region deletes one, giving its pages \
+back their protection, restart watches them again at the same addresses.
+
+The pages stay read-only for the kernel too: a system call writing to them, as a read into a buffer in them, \
+fails with EFAULT instead of stopping the program. The children it forks inherit the read-only pages without \
+being traced, and the writes of the other threads running while a write is single-stepped are not caught.
+
+Examples:
+  watch-region buffer 4096
+  watch-region 0x555555559000 0x100",
+        valid_in: ValidIn::Live,
+        handler: pagewatch::watch_region_command,
+    },
+    CommandSpec {
+        names: &["unwatch-region"],
+        arguments: "<number>",
+        summary: "Delete a region watchpoint, making its pages writable again",
+        long_help: "Stop watching a region watched with watch-region, by its number, giving its pages back the \
+protection they had.
+
+Example:
+  unwatch-region 1",
+        valid_in: ValidIn::Any,
+        handler: pagewatch::unwatch_region_command,
+    },
+    CommandSpec {
+        names: &["snapshot"],
+        arguments: "",
+        summary: "Save the registers and the writable memory of the program, to go back to with reverse-continue",
+        long_help: "Save the registers of every thread and a full copy of the private writable memory of the \
+program (data, bss, heap, stacks). set snapshot-interval <n> takes one every n stops. The last 16 are kept, \
+info snapshots lists them.
+
+A snapshot does NOT save anything outside the program: see help reverse-continue.",
+        valid_in: ValidIn::Live,
+        handler: snapshot::snapshot_command,
+    },
+    CommandSpec {
+        names: &["rc", "reverse-continue"],
+        arguments: "",
+        summary: "Go back to the previous breakpoint hit, from the last snapshot before it",
+        long_help: "Go back to the last breakpoint hit before the current state: the latest snapshot before it is \
+restored, and the program executed again until it hits that breakpoint in the same state.
+
+WARNING: only the registers and the private writable memory of the program go back. Everything else stays as it \
+is: the files written or removed, the output printed, the data sent or received on sockets and pipes, the shared \
+memory, the other processes, the time, and what the kernel keeps for the program (open files and their offsets, \
+the program break, the memory mapped since, pending signals, timers). The code executed again makes its system \
+calls again: its output is written twice, its files written again, its input read again. When the program does \
+not take the same path, the command fails and leaves the program where it stopped.",
+        valid_in: ValidIn::Live,
+        handler: reverse_continue,
+    },
+    CommandSpec {
+        names: &["rsi", "reverse-stepi"],
+        arguments: "",
+        summary: "Go back one instruction, from the last snapshot before it",
+        long_help: "Go back one instruction of the current thread: the latest snapshot before the current state is \
+restored, the instructions executed since it counted, and the program executed again from it up to the previous \
+instruction. The longer since the snapshot, the slower.
+
+WARNING: the same caveats as reverse-continue apply, see help reverse-continue.",
+        valid_in: ValidIn::Live,
+        handler: reverse_stepi,
+    },
+    CommandSpec {
+        names: &["r", "registers", "regs"],
+        arguments: "[diff <snapshot> [<snapshot> | live]]",
+        summary: "Show the register states of the process, or compare them between snapshots",
+        long_help: "Show the general purpose registers of the program, in hexadecimal.
+
+r diff compares the registers of the current thread saved by two snapshots, given by their numbers in info \
+snapshots, or of a snapshot and the program (live, the default). Only the registers that differ are printed, with \
+both values, their XOR to spot the flipped bits and their bytes as ASCII. When eflags differs, its flags are \
+decoded on both sides, as in [ ZF IF ], with the ones that flipped.
+
+Examples:
+  regs diff 1
+  regs diff 1 2",
+        valid_in: ValidIn::Stopped,
+        handler: print_registers,
+    },
+    CommandSpec {
+        names: &["m", "memory"],
+        arguments: "<address>",
+        summary: "Show the content of a memory address",
+        long_help: "Show the 8 bytes at an address, as a 64-bit hexadecimal number, or the 4 bytes of a pointer of a 32-bit
+program. The address is a value: a hexadecimal number with 0x or a decimal one, a register of the selected frame
+($sp), a convenience variable ($base), a label, a symbol, or one of them plus an offset.
+
+Examples:
+  m 0x7ffc3a2b1e40
+  m $sp+8                                   The word above the top of the stack.
+  m counter                                 The static variable counter.
+  m $base+0x1234                            At an offset from a convenience variable.",
+        valid_in: ValidIn::Stopped,
+        handler: memory,
+    },
+    CommandSpec {
+        names: &["x"],
+        arguments: "[/<count><format><size>] <address>",
+        summary: "Examine memory as units, strings or instructions, with the formats of gdb",
+        long_help: "Show memory from an address as gdb's x does. After the /, an optional count (1), then a format \
+letter and a size letter, each optional:
+
+Formats:
+  x    Hexadecimal (the default).
+  d    Signed decimal.
+  u    Unsigned decimal.
+  t    Binary.
+  c    The value and its character, one byte by default.
+  s    NUL terminated strings, one after the other.
+  i    Instructions, listed as disas lists them.
+
+Sizes, of the units of x, d, u, t and c:
+  b    1 byte.
+  h    2 bytes.
+  w    4 bytes (the default).
+  g    8 bytes.
+
+Examples:
+  x/16xb $sp
+  x/4gx 0x7ffc3a2b1e40
+  x/s $rdi
+  x/5i $pc",
+        valid_in: ValidIn::Stopped,
+        handler: examine::examine_command,
+    },
+    CommandSpec {
+        names: &["gcore"],
+        arguments: "[path]",
+        summary: "Save a core file of the program",
+        long_help: "Write an ELF core file of the program to path, core.<pid> by default, which gdb <program> <path> \
+loads. It holds the registers of every thread and the memory of the program, the mappings the kernel would leave out of \
+a core dump (see /proc/<pid>/coredump_filter) being empty.",
+        valid_in: ValidIn::Live,
+        handler: gcore,
+    },
+    CommandSpec {
+        names: &["b", "breakpoint"],
+        arguments: "<address|function|file:line>",
+        summary: "Set a breakpoint at an address, a function name (shows its arguments when hit) or a source line",
+        long_help: "Set a breakpoint, numbered in the order breakpoints are set. A breakpoint stops the program \
+the first time it is hit, then it is removed. info breakpoints lists the armed ones.
+
+Forms:
+  b <address>       At an address, starting with 0x. An address inside an instruction corrupts it, info
+                    breakpoints points this out.
+  b <function>      After the prologue of a function, so its arguments are shown when it is hit. Rust functions
+                    match by their path or its last components (update, state::update). The shared library
+                    defining the function is loaded if needed.
+  b <file:line>     At the first instruction of a source line of the program, or of the next line with code.
+
+Examples:
+  b main
+  b app::state::update
+  b 0x555555555149
+  b hello.c:12",
+        valid_in: ValidIn::Live,
+        handler: breakpoint,
+    },
+    CommandSpec {
+        names: &["rbreak"],
+        arguments: "<regex>",
+        summary: "Set a breakpoint on every function matching a regular expression, as one group",
+        long_help: "Set a breakpoint after the prologue of every function of the program and of the loaded \
+libraries whose name matches a regular expression, mangled or demangled, to see which of them are called. \
+rbreak asks first when more than 50 functions match. The breakpoints form a group, numbered in the order the \
+groups are made, that delete group removes at once.
+
+Examples:
+  rbreak ^parse_          The functions starting with parse_.
+  rbreak ^app::state::    The functions of a Rust module.",
+        valid_in: ValidIn::Live,
+        handler: rbreak,
+    },
+    CommandSpec {
+        names: &["delete"],
+        arguments: "<number> | group <id>",
+        summary: "Remove a breakpoint, or the breakpoints of a group set by rbreak",
+        long_help: "Remove the breakpoint numbered as in info breakpoints, or every breakpoint of a group set by \
+rbreak that is still armed. restart does not set them again.
+
+Examples:
+  delete 3
+  delete group 1",
+        valid_in: ValidIn::Live,
+        handler: delete,
+    },
+    CommandSpec {
+        names: &["sym", "symbol"],
+        arguments: "<address>",
+        summary: "Show the symbol containing an address",
+        long_help: "Show the symbol containing an address, with the offset of the address in it and the object \
+defining it.
+
+Example:
+  sym 0x555555555149    0x555555555149 is main+0x4 in /tmp/hello",
+        valid_in: ValidIn::Any,
+        handler: symbol,
+    },
+    CommandSpec {
+        names: &["ltrace"],
+        arguments: "[on [function,...] | off]",
+        summary: "Trace the library calls of the program while continuing, ltrace alone counts them",
+        long_help: "Trace the calls the program makes through its PLT stubs while c continues it, showing the \
+first arguments of each call.
+
+Forms:
+  ltrace on                     Trace every library function.
+  ltrace on malloc,free         Trace the listed functions.
+  ltrace off                    Stop tracing.
+  ltrace                        Show how many times each function was called.",
+        valid_in: ValidIn::Live,
+        handler: |debugger, args| {
+            ltrace::ltrace_command(debugger, &args[1..]);
+            true
+        },
+    },
+    CommandSpec {
+        names: &["cover"],
+        arguments: "start [step] | stop | report | save [--drcov] <file>",
+        summary: "Record the code the program executes, for coverage viewers",
+        long_help: "Record the addresses of the code executed while the program runs.
+
+cover start finds the basic blocks of the functions of the program from their disassembly: their start, the \
+targets of their jumps and the instructions after a jump, a call or a return. A breakpoint on each block records \
+it the first time it is entered, then it is removed, so the program soon runs at full speed. The libraries are \
+not recorded. cover start step instead single-steps the current thread when c continues it, recording every \
+instruction, in the libraries too, which is very slow.
+
+Forms:
+  cover start                   Record the basic blocks entered.
+  cover start step              Record every instruction executed, by single-stepping.
+  cover stop                    Stop recording, keeping what was recorded.
+  cover report                  Show the number of addresses executed in each function, and its blocks.
+  cover save <file>             Write the addresses executed, one per line.
+  cover save --drcov <file>     Write them in the drcov format, read by lighthouse and other viewers.",
+        valid_in: ValidIn::Any,
+        handler: coverage::cover_command,
+    },
+    CommandSpec {
+        names: &["record"],
+        arguments: "on <file> | off | stats",
+        summary: "Record the instructions stepped and the registers they change to a file",
+        long_help: "Append a JSON line to the file for each instruction stepped by si, step and next, with the pc \
+of the instruction and the registers it changed, as {\"pc\":\"0x401126\",\"rax\":\"0x1\"}. The first line, and \
+the first one after the program ran unrecorded (c, or a function run over by next), has every register. Diffing the \
+records of two runs shows where they diverge.
+
+Forms:
+  record on trace.jsonl         Start recording to trace.jsonl.
+  record off                    Stop recording.
+  record stats                  Show the instructions recorded and the size of the file.",
+        valid_in: ValidIn::Any,
+        handler: |debugger, args| record::record_command(debugger, &args[1..]),
+    },
+    CommandSpec {
+        names: &["script"],
+        arguments: "eval '<code>' | file <path> | clear",
+        summary: "Run a Rhai script, with bindings to read and change the program and hooks on its stops",
+        long_help: "Run a script of the Rhai language (https://rhai.rs), given on the line or read from a file, to \
+automate an analysis without changing the debugger. Quote the code given to eval, the ; inside the quotes being part \
+of it. Functions registered with on_stop are called at each stop of the program until script clear, with an event \
+map: reason (breakpoint, signal, step, exited or killed), pc, thread, and the address of the breakpoint, the signal \
+or the exit code. Numbers are 64-bit, addresses included.
+
+Bindings:
+  reg(name)                     The value of a register of the current thread (rax, rip, pc, sp, eflags...).
+  set_reg(name, value)          Change a register of the current thread.
+  read_memory(address, len)     Read len bytes of memory, as a blob.
+  read_u64(address)             Read the 8 bytes at an address, as a number.
+  write_memory(address, blob)   Write bytes to memory.
+  write_u64(address, value)     Write 8 bytes to memory.
+  resolve(symbol)               The address of a symbol.
+  breakpoint(location)          Set a breakpoint, as b does, returning its address.
+  cont()                        Continue the program, as c does, returning the stop event.
+  stepi()                       Execute one instruction, as si does, returning the stop event.
+  command(line)                 Run a command of the debugger, returning what it printed.
+  on_stop(function)             Call function(event) at each stop of the program.
+  print(value)                  Print a value.
+
+Example, logging the size of each allocation:
+  script eval 'let malloc = breakpoint(\"malloc\"); on_stop(|event| if event.address == malloc { print(reg(\"rdi\")) })'
+
+Forms:
+  script eval '<code>'          Run the code.
+  script file <path>            Run the script in a file.
+  script clear                  Remove the stop hooks.",
+        valid_in: ValidIn::Any,
+        handler: |debugger, args| scripting::script_command(debugger, &args[1..]),
+    },
+    CommandSpec {
+        names: &["disas", "disassemble"],
+        arguments: "[--raw] [address|function] [count|end|+len] [> file]",
+        summary: "Disassemble instructions around the pc, at an address, a whole function or a range",
+        long_help: "Disassemble instructions. => marks the current instruction, in the innermost frame, and B<n> the instructions where \
+breakpoint n is armed. Calls and jumps show the symbol of their destination, rip-relative operands the data they \
+designate, and a conditional jump at the pc whether it will be taken.
+
+Forms:
+  disas                         10 instructions around the pc of the selected frame, the call of a caller.
+  disas <address> [count]       count instructions (10) from an address.
+  disas <function>              The whole function.
+  disas <address> <end>         The instructions up to the address end.
+  disas <address> +<len>        The instructions in the next len bytes.
+  disas ... > <file>            Write the listing to a file.
+  disas --raw ...               Decode the bytes as they are in memory, with the breakpoint instructions.
+
+Examples:
+  disas main
+  disas 0x401126 20
+  disas main > main.s",
+        valid_in: ValidIn::Stopped,
+        handler: |debugger, args| {
+            disasm::disassemble_command(debugger, &args[1..]);
+            true
+        },
+    },
+    CommandSpec {
+        names: &["l", "list"],
+        arguments: "[file:line|function]",
+        summary: "Show the source lines around the current line or a location",
+        long_help: "Show the source lines around the line of the selected frame, around a line of a file, or \
+around the start of a function. An empty line lists again.
+
+Examples:
+  list
+  list hello.c:12
+  list main",
+        valid_in: ValidIn::Any,
+        handler: list,
+    },
+    CommandSpec {
+        names: &["bt", "backtrace"],
+        arguments: "[count]",
+        summary: "Show the call stack, including inlined functions",
+        long_help: "Show the call stack, innermost frame first, with the function and the source line of each \
+frame. Inlined functions are marked (inlined). At most count frames are shown, set backtrace limit changes the \
+default (32).",
+        valid_in: ValidIn::Stopped,
+        handler: print_backtrace,
+    },
+    CommandSpec {
+        names: &["where"],
+        arguments: "",
+        summary: "Show where the program is stopped and why, in one line",
+        long_help: "Show in one line the pc of the current thread with its function and offset, its source line when \
+known, the mapping containing it with its permissions, why the program stopped (the breakpoint number, a step, the \
+signal...) and the thread. The same line is shown at each stop, unless set stop-summary off.
+
+Example:
+  where    0x000055d1c2f0e140 in count+0x7 at loop.c:6 [loop r-xp], breakpoint 1, thread 1 (tid 1234)",
+        valid_in: ValidIn::Stopped,
+        handler: where_stopped,
+    },
+    CommandSpec {
+        names: &["f", "frame"],
+        arguments: "[number]",
+        summary: "Select a stack frame, or describe the selected one",
+        long_help: "Select the stack frame with the given number (as shown by bt), 0 being the innermost one. \
+info locals, info args, print and list then apply to that frame. Without a number, describe the selected frame.",
+        valid_in: ValidIn::Stopped,
+        handler: select_frame,
+    },
+    CommandSpec {
+        names: &["up"],
+        arguments: "[count]",
+        summary: "Select the caller of the selected frame",
+        long_help: "Select the frame count levels (1) above the selected one, towards the outermost frame.",
+        valid_in: ValidIn::Stopped,
+        handler: select_frame,
+    },
+    CommandSpec {
+        names: &["down"],
+        arguments: "[count]",
+        summary: "Select the callee of the selected frame",
+        long_help: "Select the frame count levels (1) below the selected one, towards the innermost frame.",
+        valid_in: ValidIn::Stopped,
+        handler: select_frame,
+    },
+    CommandSpec {
+        names: &["thread"],
+        arguments: "[number]",
+        summary: "Select a thread, or describe the current one",
+        long_help: "Select the thread with the given number (as shown by info threads), 1 being the main thread. \
+r, bt, print, si, n and step then apply to that thread: it is stepped alone, the other threads staying stopped, and c \
+resumes every thread. Without a number, describe the current thread. The thread stopping the program becomes the \
+current one.
+
+In non-stop mode (set non-stop on), each thread stops alone and the others keep running: info threads shows which \
+ones run, and only a stopped thread can be selected, showing again why it stopped. interrupt stops the others.",
+        valid_in: ValidIn::Stopped,
+        handler: select_thread,
+    },
+    CommandSpec {
+        names: &["set"],
+        arguments: "<setting> <value> | $<variable> = <value> | mem <address> = <byte>...",
+        summary: "Change a setting of the debugger, see show for the settings, or a convenience variable",
+        long_help: "Change a setting of the debugger. show lists the settings with their values, and
+show <setting> describes one with the values it accepts.
+
+set $<variable> = <value> keeps a value in a convenience variable, usable as $<variable> wherever a value or an
+address is expected, plus an offset as in $base+0x1234. info convenience lists them.
+
+set mem <address> = <byte>... writes bytes to the memory of the program. A write to a read-only or executable
+mapping, as the code of the program, asks first (see set confirm): ptrace writes bypass the page protections.
+set! mem writes without asking, with a warning.
+
+Examples:
+  set backtrace limit 8                     Show 8 frames with bt.
+  set disassembly-flavor att                Disassemble in the AT&T syntax.
+  set prompt \"(dbg) \"                       A plain prompt, quoted to keep its trailing space.
+  set logging on                            Copy the session to rustdbg.log.
+  set $base = 0x555555554000                Then m $base+0x1234 reads 0x555555555234.
+  set mem $sp+8 = 0x2a 0                    Write two bytes to the stack.
+  set! mem $pc = 0x90                       Replace the first byte of the next instruction with an x86 nop.",
+        valid_in: ValidIn::Any,
+        handler: set,
+    },
+    CommandSpec {
+        names: &["show"],
+        arguments: "[setting]",
+        summary: "Show the settings of the debugger and their values",
+        long_help: "Show the value of every setting, or describe one setting: its value, what it changes, its
+default value and the values set accepts. show rlimits lists the resource limits the program gets
+from the next run, the ones of set rlimit and the ones it inherits from the debugger.
+
+Examples:
+  show                                      Every setting with its value.
+  show backtrace limit                      The number of frames shown by bt.
+  show rlimits                              The resource limits of the next run.",
+        valid_in: ValidIn::Any,
+        handler: show,
+    },
+    CommandSpec {
+        names: &["i", "info"],
+        arguments: "<topic>",
+        summary: "Show information about the program or the debugger, see help info",
+        long_help: "Show information about the program or the debugger.
+
+Topics:
+  info breakpoints                          The armed breakpoints and the instruction they are set on.
+  info frame                                The selected frame: its CFA, saved return address and registers, size.
+  info locals                               The local variables of the selected frame.
+  info args                                 The arguments of the selected frame.
+  info line <address|function|file:line>    Translate between addresses and source lines.
+  info address <symbol>                     Where a symbol lives: section, file and runtime address, object.
+  info symbol <regex>                       Search the symbols of every loaded object.
+  info variables [regex]                    The global and static data symbols matching a regular expression.
+  info file                                 The ELF header of the program: type, machine, entry point.
+  info sections                             The sections of the program, * marks the mapped ones.
+  info sharedlibrary                        The shared libraries loaded by the program.
+  info dl                                   The dynamic loader: interpreter, AT_BASE, AT_ENTRY, r_debug.
+  info threads                              The threads of the program, * marks the current one.
+  info proc                                 The state, parent, threads and memory size of the process.
+  info proc cwd | exe                       Its current directory, or its executable.
+  info proc fd                              Its open file descriptors with their flags, offset and target.
+  info signal                               The code, address and sender of the signal the program stopped by.
+  info syscall                              The system call the program is in, decoded as strace does, as when interrupted.
+  info maps                                 The memory mappings of the program: range, size, offset, permissions and file.
+  info snapshots                            The snapshots taken, for reverse-continue and reverse-stepi.
+  info alias                                The aliases and the commands they run.
+  info labels                               The addresses named with label, with their length.
+  info display                              The expressions shown at every stop, with their last values.
+  info convenience                          The convenience variables set with set $<variable> = <value>.",
+        valid_in: ValidIn::Any,
+        handler: info,
+    },
+    CommandSpec {
+        names: &["p", "print"],
+        arguments: "[/full] <variable>",
+        summary: "Show the value of a local variable, parameter or static (including thread-locals)",
+        long_help: "Show the value of a variable of the selected frame, or of a static. Rust statics match by \
+their path or its last components, thread-local statics are read for the traced thread.
+
+The value is shown according to its DWARF type. Structs show their fields by name, and the structs in them one \
+level deep, the deeper ones being {...}: print/full shows every level. Arrays show their first 16 elements, and \
+arrays of C chars their text. Rust enums show their active variant, as Some(5), Err(\"bad\") or \
+Shape::Circle { r: 2 }. Pointers show their address and the value they point to, or the text of a C string.
+
+Examples:
+  print count
+  print/full config
+  print app::state::COUNTER",
+        valid_in: ValidIn::Stopped,
+        handler: print_variable,
+    },
+    CommandSpec {
+        names: &["display"],
+        arguments: "[<expression>]",
+        summary: "Show an expression at every stop, display alone shows them all",
+        long_help: "Add an expression shown, numbered, every time the program stops, and right away when it is \
+stopped. A variable is shown as print shows it; the other expressions are values read as assert reads them \
+(registers, convenience variables, numbers and symbols with an offset), or *<value> for the integer in memory at an \
+address, shown in hexadecimal and decimal. An expression that can't be evaluated, as an address no longer mapped, \
+shows its error in place of its value, and the others are shown still. undisplay removes one, info display lists them \
+with the value shown at the last stop.
+
+Examples:
+  display $rax
+  display *0x601040
+  display counter",
+        valid_in: ValidIn::Any,
+        handler: display::display_command,
+    },
+    CommandSpec {
+        names: &["undisplay"],
+        arguments: "<number>...",
+        summary: "Remove expressions shown at every stop",
+        long_help: "Remove the expressions added with display, by the number info display lists.
+
+Example:
+  undisplay 2",
+        valid_in: ValidIn::Any,
+        handler: display::undisplay_command,
+    },
+    CommandSpec {
+        names: &["call"],
+        arguments: "<function>(<argument>, ...)",
+        summary: "Call a function of the program and show the value it returns",
+        long_help: "Call a function of the program in the current thread and show the value it returns in rax, then \
+put the registers back as they were. At most 6 integer or pointer arguments are passed, in registers: values are \
+read as addresses are (numbers, registers, convenience variables and symbols, with an offset), and string literals \
+are written to the stack and passed by address. The breakpoints are disarmed during the call and the other threads \
+stay stopped. If the program receives a signal in the function, the call is abandoned and the registers restored. \
+64-bit programs only.
+
+Examples:
+  call malloc_stats()
+  call dump_state($rdi, \"after the loop\")",
+        valid_in: ValidIn::Live,
+        handler: call_function,
+    },
+    CommandSpec {
+        names: &["alloc"],
+        arguments: "<size>",
+        summary: "Map readable and writable memory in the program and show its address",
+        long_help: "Map <size> bytes of anonymous, readable and writable memory in the program, rounded up to whole \
+pages, and show its address. The current thread makes the mmap system call: its instruction is written over the \
+one at the pc and stepped, then the code and the registers are put back. The memory stays until dealloc, scratch \
+space for set mem or the arguments of call. Not while the program is stopped in a system call.
+
+Example:
+  alloc 256
+  set mem 0x7f0000000000 = 0x68 0x69 0x00",
+        valid_in: ValidIn::Live,
+        handler: alloc,
+    },
+    CommandSpec {
+        names: &["dealloc"],
+        arguments: "<address> <size>",
+        summary: "Unmap memory of the program, as mapped by alloc",
+        long_help: "Unmap <size> bytes of memory of the program at <address>, which must be aligned on a page, with a \
+munmap system call made as alloc makes its mmap.",
+        valid_in: ValidIn::Live,
+        handler: dealloc,
+    },
+    CommandSpec {
+        names: &["sharedlibrary"],
+        arguments: "<name>",
+        summary: "Load the symbols of the shared libraries matching a name",
+        long_help: "Load the symbols of the shared libraries whose path contains name. Symbols of libraries are \
+otherwise loaded when a command needs them.
+
+Example:
+  sharedlibrary libc",
+        valid_in: ValidIn::Stopped,
+        handler: sharedlibrary,
+    },
+    CommandSpec {
+        names: &["source"],
+        arguments: "<file>",
+        summary: "Run the commands of a script file (blank lines and # comments are skipped)",
+        long_help: "Run the commands of a script file in order, printing each command before its output. Blank \
+lines and lines starting with # are skipped. A failed command stops the script, unless set script-on-error \
+continue. Scripts can source other scripts.",
+        valid_in: ValidIn::Any,
+        handler: source,
+    },
+    CommandSpec {
+        names: &["session"],
+        arguments: "save <file> | replay <file>",
+        summary: "Save the commands entered to a file, or replay a saved session on the same program",
+        long_help: "Save the commands typed or given with -ex since the start, with the program, its arguments \
+and whether --no-aslr was given, to reproduce the session later, as in a bug report. The commands of scripts and \
+user commands run again with the command running them, and help, apropos and session are not saved. The file is \
+a script: the header, the commands that failed and the regions of the addresses written in the commands are # \
+comments.
+
+replay starts the program again with the recorded arguments and setting, then runs the commands like source, \
+warning when one does not succeed or fail as it did. Before a command with an address, the file or the [stack] \
+or [heap] it was in must be loaded at the same address, or the replay stops: record the session with --no-aslr \
+to replay its addresses. Replay a session in a new debugger, dbg_rust -ex 'session replay bug.rdbg' <program>, \
+as the breakpoints set before are kept.
+
+Forms:
+  session save bug.rdbg         Save the session to bug.rdbg.
+  session replay bug.rdbg       Start the program again and replay bug.rdbg.",
+        valid_in: ValidIn::Any,
+        handler: |debugger, args| session::session_command(debugger, &args[1..]),
+    },
+    CommandSpec {
+        names: &["alias"],
+        arguments: "[<name> = \"<command>[; <command>...]\"]",
+        summary: "Define a command running other commands, alias alone lists the aliases",
+        long_help: "Define an alias: a new command running one or more commands separated by ;. Quote the \
+commands so the ; belongs to the alias. $1, $2... are replaced with the arguments of the alias, $* with all of \
+them and $$ with a $. Without any $n, the arguments are appended to the commands. Aliases can use other aliases, \
+but not the names of the commands. info alias lists them, unalias removes one.
+
+Examples:
+  alias ll = \"disas 0x401126 20\"
+  alias bm = \"b $1; c\"        then bm main breaks on main and continues to it
+  alias w = bt",
+        valid_in: ValidIn::Any,
+        handler: define_alias,
+    },
+    CommandSpec {
+        names: &["unalias"],
+        arguments: "<name>",
+        summary: "Remove an alias",
+        long_help: "Remove an alias defined with alias.
+
+Example:
+  unalias bm",
+        valid_in: ValidIn::Any,
+        handler: unalias,
+    },
+    CommandSpec {
+        names: &["label"],
+        arguments: "[<address> <name> [length] | --save <file>]",
+        summary: "Name an address, label alone lists the labels",
+        long_help: "Give a name to an address, and to the length bytes from it when a length is given. Wherever an \
+address is described, as the operands of disas and info line, an address the label covers is shown by it, as in \
+<input_buf+16>; among overlapping labels, the one starting the closest before the address. The name is a value \
+too, before the symbols of the same name: m input_buf+8, b handler. info labels lists them, unlabel removes one, \
+and label --save <file> writes them as label commands, which source reads back in another session.
+
+Examples:
+  label $sp+0x20 input_buf 64
+  label 0x7ffd4c00 header
+  label --save labels.gdb",
+        valid_in: ValidIn::Any,
+        handler: label::label_command,
+    },
+    CommandSpec {
+        names: &["unlabel"],
+        arguments: "<name>",
+        summary: "Remove a label",
+        long_help: "Remove a label given with label.
+
+Example:
+  unlabel input_buf",
+        valid_in: ValidIn::Any,
+        handler: label::unlabel_command,
+    },
+    CommandSpec {
+        names: &["define"],
+        arguments: "<name>",
+        summary: "Define a command from the lines that follow, up to end",
+        long_help: "Define a user command running the commands of the following lines, up to a line saying just \
+end. In the commands, $arg0, $arg1... are replaced with the arguments of the user command and $argc with their \
+number. A user command stops at the first of its commands that fails, and can run other user commands, 16 deep at \
+most. Defining it again replaces it, undefine removes it and help user-defined lists them.
+
+Example:
+  define trace_alloc
+  b $arg0
+  c
+  bt 3
+  end
+  trace_alloc malloc",
+        valid_in: ValidIn::Any,
+        handler: define,
+    },
+    CommandSpec {
+        names: &["undefine"],
+        arguments: "<name>",
+        summary: "Remove a command defined with define",
+        long_help: "Remove a user command defined with define.
+
+Example:
+  undefine trace_alloc",
+        valid_in: ValidIn::Any,
+        handler: undefine,
+    },
+    CommandSpec {
+        names: &["echo"],
+        arguments: "<text>",
+        summary: "Print a line of text, for scripts",
+        long_help: "Print text on a line of its own. \\n starts a new line, \\t is a tab, \\\\ a backslash.
+
+Example:
+  echo Breakpoints set, continuing",
+        valid_in: ValidIn::Any,
+        handler: echo,
+    },
+    CommandSpec {
+        names: &["printf"],
+        arguments: "\"<format>\" [value, ...]",
+        summary: "Print values with a C format, for scripts",
+        long_help: "Print values with a format in the style of C. Values are read as addresses are: numbers, \
+registers, convenience variables and symbols, with an offset. The conversions are %x, %d, %u, %p, %c and %s, which \
+reads the string the value points to in the program, with the - and 0 flags and a width. The escapes are those \
+of echo. The output ends with a line, even without \\n.
+
+Examples:
+  printf \"rax=%x\\n\" $rax
+  printf \"%s at %p\\n\", $rdi, $rdi",
+        valid_in: ValidIn::Any,
+        handler: printf,
+    },
+    CommandSpec {
+        names: &["assert"],
+        arguments: "<value> [==|!=|<|<=|>|>= <value>]",
+        summary: "Check a comparison of values, ending a batch session with the status 3 when it fails",
+        long_help: "Check that a comparison holds, or that a value alone is not 0, for scripts and test suites. Values \
+are read as addresses are: numbers, registers, convenience variables and symbols, with an offset. *<value> reads the \
+integer of the size of a pointer in memory at an address, and -<value> negates.
+
+Values compare as the signed 64-bit integers of C: $rax == -1 and $rax < 0 hold for a negative rax. The 32-bit \
+registers of i386 ($eax) are signed 32-bit integers. Symbols, values with an offset and numbers above \
+0x7fffffffffffffff are unsigned, and a comparison with one of them is unsigned, -1 being the largest value.
+
+A failed assertion shows the values compared. In batch mode, it kills the program and the debugger exits at once \
+with the status 3, the commands left not run.
+
+Examples:
+  assert $rax == 0x5
+  assert *counter >= 10
+  assert $sp != 0",
+        valid_in: ValidIn::Any,
+        handler: check_assertion,
+    },
+    CommandSpec {
+        names: &["shell"],
+        arguments: "<command line>",
+        summary: "Run a command of the shell, also written !<command line>",
+        long_help: "Run a command line with $SHELL (or /bin/sh), on the terminal of the debugger, and report its exit \
+status when it fails. The rest of the line goes to the shell as typed, ; included. !<command line> is the short form.
+
+Examples:
+  shell grep -n panic app.log
+  !ls -l target/debug",
+        valid_in: ValidIn::Any,
+        handler: shell,
+    },
+    CommandSpec {
+        names: &["rebuild"],
+        arguments: "",
+        summary: "Run the build command (set build-command), then restart loads the new program",
+        long_help: "Run the command line given to set build-command with the shell, to rebuild the program without \
+leaving the debugger. The symbols loaded are then stale: restart loads the new program, its symbols and sets the \
+breakpoints again.
+
+Example:
+  set build-command cargo build
+  rebuild",
+        valid_in: ValidIn::Any,
+        handler: rebuild,
+    },
+    CommandSpec {
+        names: &["h", "help"],
+        arguments: "[command]",
+        summary: "List the commands, or show the details of one",
+        long_help: "List the commands with a summary of each, or show every form of a command with examples.
+
+Example:
+  help b",
+        valid_in: ValidIn::Any,
+        handler: help,
+    },
+    CommandSpec {
+        names: &["q", "quit"],
+        arguments: "",
+        summary: "Kill the process and exit the debugger (also at the end of the input)",
+        long_help: "Kill the program and exit the debugger. The end of the input (Ctrl-D) quits too. When the \
+program is running, quit asks first unless set confirm is off; quit! does not ask.",
+        valid_in: ValidIn::Any,
+        handler: quit,
+    },
+];
+
+/// Find the command called `name`, exactly.
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.names.contains(&name))
+}
+
+/// What a command name typed by the user designates.
+#[derive(Clone, Copy)]
+pub enum CommandMatch {
+    /// One command, by one of its names or an abbreviation.
+    Found(&'static CommandSpec),
+    /// An abbreviation of several commands.
+    Ambiguous,
+    /// No command.
+    Unknown,
+}
+
+/// Find the command called `name` or abbreviated as `name`. A name of a command wins over the
+/// abbreviations, so `s` is `syscall` even though `step`, `set` and others start with it.
+pub fn lookup_command(name: &str) -> CommandMatch {
+    if let Some(spec) = find_command(name) {
+        return CommandMatch::Found(spec);
+    }
+    let mut matches = COMMANDS.iter().filter(|spec| spec.names.iter().any(|candidate| candidate.starts_with(name)));
+    match (matches.next(), matches.next()) {
+        (Some(spec), None) if !name.is_empty() => CommandMatch::Found(spec),
+        (Some(_), Some(_)) if !name.is_empty() => CommandMatch::Ambiguous,
+        _ => CommandMatch::Unknown,
+    }
+}
+
+/// The names of the commands starting with `prefix`, sorted.
+pub fn commands_starting_with(prefix: &str) -> Vec<&'static str> {
+    let mut names: Vec<&str> =
+        COMMANDS.iter().flat_map(|spec| spec.names.iter().copied()).filter(|name| name.starts_with(prefix)).collect();
+    names.sort_unstable();
+    names
+}
+
+/// Report an abbreviation matching several commands, with their names.
+pub fn print_ambiguous_command(debugger: &mut Debugger, name: &str) {
+    errln!(debugger, "Ambiguous command \"{}\": {}.", name, commands_starting_with(name).join(", "));
+}
+
+/// The number of single character edits (insertions, deletions, substitutions and swaps of
+/// adjacent characters) turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    distances[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution.min(distances[i - 1][j] + 1).min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// The names of `candidates` close to `name`: starting with it, a couple of typos away, or
+/// starting with a misspelling of it (`brek` for `breakpoint`).
+pub fn suggestions<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let typos = 2.min(name.len() / 3);
+    let mut close: Vec<&str> = candidates
+        .into_iter()
+        .filter(|candidate| {
+            let prefix: String = candidate.chars().take(name.chars().count()).collect();
+            candidate.starts_with(name)
+                || edit_distance(name, candidate) <= typos
+                || (name.len() >= 3 && edit_distance(name, &prefix) <= 1)
+        })
+        .collect();
+    close.sort_unstable();
+    close.dedup();
+    close
+}
+
+/// Print the usage of the command called `args[0]`.
+///
+/// # Returns
+///
+/// False, the command failed.
+fn usage(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if let CommandMatch::Found(spec) = lookup_command(args[0]) {
+        errln!(debugger, "Usage: {} {}", args[0], spec.arguments);
+    }
+    false
+}
+
+/// Start the program with `continue`. It runs before the program is started only. With arguments
+/// or `< file`, or after `set args`, the program is started again with them first.
+fn run(debugger: &mut Debugger, args: &[&str]) -> bool {
+    apply_run_arguments(debugger, args) && continue_program(debugger, &["continue"])
+}
+
+/// Start the program and stop it in its main function, as `--stop-at-main` does, taking the
+/// arguments of `run`.
+fn start(debugger: &mut Debugger, args: &[&str]) -> bool {
+    apply_run_arguments(debugger, args) && launch::run_to_main(debugger)
+}
+
+/// Take the arguments and `< file` given to `run` or `start`, starting the program again with
+/// them for this run only, or with the ones of `set args` and `set cwd` if they changed.
+fn apply_run_arguments(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() > 1 {
+        let mut spec = debugger.launch_spec();
+        match inferior::parse_arguments(&args[1..]) {
+            Ok((arguments, stdin_file)) => {
+                spec.args = arguments;
+                spec.stdin_file = stdin_file;
+            }
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return false;
+            }
+        }
+        return start_again(debugger, &spec, "Starting");
+    }
+    if debugger.launch_changed {
+        return start_again(debugger, &debugger.launch_spec(), "Starting");
+    }
+    true
+}
+
+/// Write a line to the standard input of the program, or close it with `--eof`.
+fn input(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args[1..] == ["--eof"] {
+        if debugger.program_input.take().is_none() {
+            errln!(debugger, "The standard input of the program is not a pipe of the debugger, or is already closed.");
+            return false;
+        }
+        outln!(debugger, "Closed the standard input of the program.");
+        return true;
+    }
+    let line = format!("{}\n", args[1..].join(" "));
+    match inferior::write_input(debugger, &line) {
+        Ok(()) => true,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// Continue the program until it stops, tracing its library calls when `ltrace` is on.
+fn continue_program(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let cont: fn(&mut Debugger) -> Result<step::StepStop, DbgError> = match args.get(1..).unwrap_or_default() {
+        [] => Debugger::cont,
+        ["-a"] => Debugger::cont_all,
+        _ => return usage(debugger, args),
+    };
+    outln!(debugger, "Continuing execution...");
+    match runstatus::while_running(debugger, cont) {
+        Ok(stop) => step::report_stop(debugger, &stop),
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    true
+}
+
+/// Run until the next system call entry or exit.
+fn continue_to_syscall(debugger: &mut Debugger, _: &[&str]) -> bool {
+    // Resuming forgets whether the program is in a system call, which only this command knows.
+    let mut in_syscall = debugger.in_syscall;
+    debugger.resume();
+    let stop = syscall::run_to_syscall(&mut debugger.breakpoint_table, debugger.thread, &mut in_syscall);
+    debugger.in_syscall = in_syscall;
+    match stop {
+        Ok(syscall::SyscallStop::Entry { number }) => {
+            debugger.state = InferiorState::Stopped { reason: StopReason::Syscall { number, entry: true } };
+            json::stop_event(debugger);
+            let banner = summary::banner(debugger).unwrap_or_default();
+            outln!(debugger, "{}", banner);
+            procfs::report_fd_changes(debugger);
+            // The anti-debugging checks of the program are caught on the way.
+            if let Err(err) = antidebug::syscall_entry(debugger, debugger.thread) {
+                errln!(debugger, "{}", err);
+            }
+            display::show_displays(debugger);
+        }
+        Ok(syscall::SyscallStop::Exit { number, value }) => {
+            debugger.state = InferiorState::Stopped { reason: StopReason::Syscall { number, entry: false } };
+            json::stop_event(debugger);
+            let value = match antidebug::syscall_exit(debugger, debugger.thread) {
+                Ok(rewritten) => rewritten.unwrap_or(debugger.arch.return_value(value)),
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    debugger.arch.return_value(value)
+                }
+            };
+            let banner = summary::banner(debugger).unwrap_or_default();
+            outln!(debugger, "{}, returned {:#x}", banner, value);
+            procfs::report_fd_changes(debugger);
+            display::show_displays(debugger);
+        }
+        Ok(syscall::SyscallStop::Other(stop)) => step::report_stop(debugger, &stop),
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    true
+}
+
+/// Step to the next source line, over calls for `next` and into them for `step`.
+fn step_line(debugger: &mut Debugger, args: &[&str]) -> bool {
+    debugger.resume();
+    let over_calls = args[0] != "step";
+    match step::step_line(debugger, over_calls) {
+        Ok(stop) => step::report_stop(debugger, &stop),
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    true
+}
+
+/// Execute a single instruction.
+fn step_instruction(debugger: &mut Debugger, _: &[&str]) -> bool {
+    outln!(debugger, "Taking a single step...");
+    match debugger.step() {
+        Ok(stop) => step::report_stop(debugger, &stop),
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    true
+}
+
+/// Single-step until the watched bytes change.
+fn software_watchpoint(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() < 3 {
+        return usage(debugger, args);
+    }
+    let watched = location::parse_value(debugger, args[1], "address").and_then(|address| Ok((address, location::parse_number(args[2], "length")?)));
+    let (address, len) = match watched {
+        Ok((_, 0)) => {
+            errln!(debugger, "The length must be at least 1.");
+            return false;
+        }
+        Ok(watched) => watched,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    warnln!(debugger, "Single-stepping the program, which is very slow: press Ctrl-C to interrupt.");
+    debugger.resume();
+    let (stop, steps) = match swatch::watch(debugger, address, len as usize, &args[3..]) {
+        Ok(watched) => watched,
+        Err(err) => {
+            // Stopped where the scan failed.
+            debugger.record_stop(&step::StepStop::Done);
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    match stop {
+        WatchStop::Changed { pc, old, new } => {
+            json::set_data(debugger, |_| {
+                json!({ "address": json::address(address), "len": len, "pc": json::address(pc), "old": old, "new": new, "steps": steps })
+            });
+            outln!(debugger, "Software watchpoint: {} bytes at {:#x} changed after {} instructions, by:", len, address, steps);
+            match disasm::instruction_line(debugger, pc, None) {
+                Some(line) => outln!(debugger, "{}", line),
+                None => outln!(debugger, "{:#x}", pc),
+            }
+            outln!(debugger, "Old value: {}", swatch::format_bytes(&old));
+            outln!(debugger, "New value: {}", swatch::format_bytes(&new));
+            step::report_stop(debugger, &step::StepStop::Done);
+        }
+        WatchStop::Interrupted => {
+            outln!(debugger, "Interrupted after {} instructions, the watched bytes unchanged.", steps);
+            step::report_stop(debugger, &step::StepStop::Done);
+        }
+        WatchStop::Stopped(stop) => step::report_stop(debugger, &stop),
+    }
+    true
+}
+
+/// Watch a variable (`watch`, `awatch`), or list the watchpoints.
+fn watch_variable(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let kind = if args[0] == "awatch" { watch::WatchKind::Access } else { watch::WatchKind::Write };
+    match args {
+        [_] if kind == watch::WatchKind::Write => {
+            watch::list(debugger);
+            true
+        }
+        [_, name] => watch::watch(debugger, name, kind),
+        _ => usage(debugger, args),
+    }
+}
+
+/// Delete a watchpoint (`unwatch`).
+fn unwatch_variable(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match args {
+        [_, name] => watch::unwatch(debugger, name),
+        _ => usage(debugger, args),
+    }
+}
+
+/// Go back to the previous breakpoint hit.
+fn reverse_continue(debugger: &mut Debugger, _: &[&str]) -> bool {
+    outln!(debugger, "Going back to the previous breakpoint hit...");
+    if let Err(err) = snapshot::reverse_continue(debugger) {
+        errln!(debugger, "{}", err);
+        return false;
+    }
+    true
+}
+
+/// Go back one instruction.
+fn reverse_stepi(debugger: &mut Debugger, _: &[&str]) -> bool {
+    outln!(debugger, "Taking a step back...");
+    if let Err(err) = snapshot::reverse_stepi(debugger) {
+        errln!(debugger, "{}", err);
+        return false;
+    }
+    true
+}
+
+/// Show the general purpose registers, or compare them between snapshots with `r diff`.
+fn print_registers(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match args.get(1) {
+        Some(&"diff") => return snapshot::registers_diff(debugger, &args[2..]),
+        Some(_) => return usage(debugger, args),
+        None => {}
+    }
+    outln!(debugger, "Showing register states...");
+    match show_registers(debugger) {
+        Ok(()) => true,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// Save a core file of the program.
+fn gcore(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() > 2 {
+        return usage(debugger, args);
+    }
+    coredump::gcore(debugger, args.get(1).copied())
+}
+
+/// Show the 8 bytes at an address, or the 4 bytes of a pointer of a 32-bit program.
+fn memory(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    let address = match location::parse_value(debugger, args[1], "address") {
+        Ok(address) => address,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let size = debugger.arch.pointer_size() as usize;
+    match debugger.arch.read_pointer(debugger.tracee().as_ref(), address) {
+        Ok(value) => {
+            json::set_data(debugger, |_| {
+                json!({ "address": json::address(address), "value": json::address(value), "bytes": value.to_le_bytes()[..size] })
+            });
+            outln!(debugger, "{:#0width$x}", value, width = size * 2 + 2);
+            true
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// Set a breakpoint at an address, a function or a source line.
+fn breakpoint(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    let address = match debugger.set_breakpoint(args[1]) {
+        Ok(address) => address,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let number = debugger.breakpoints.len();
+    let function = debugger.function_breakpoints.get(&address).cloned();
+    json::set_data(debugger, |_| json!({ "number": number, "address": json::address(address), "function": function }));
+    outln!(debugger, "Breakpoint {} set at {:#x}", number, address);
+    true
+}
+
+/// `rbreak` asks before setting more breakpoints than this.
+const RBREAK_CONFIRM_THRESHOLD: usize = 50;
+
+/// Set a breakpoint on every function of the loaded objects matching a regular expression, in a
+/// new group of breakpoints.
+fn rbreak(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    let filter = match regex::Regex::new(args[1]) {
+        Ok(filter) => filter,
+        Err(err) => {
+            errln!(debugger, "Invalid regular expression: {}", err);
+            return false;
+        }
+    };
+    debugger.refresh_executable_bias();
+    debugger.load_all_symbols();
+    let mut functions: Vec<(u64, String)> = Vec::new();
+    for object in &debugger.symbols.objects {
+        let Some(bias) = object.bias else {
+            continue;
+        };
+        let matching = object
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.is_function && symbol.address != 0)
+            .filter(|symbol| filter.is_match(&symbol.demangled) || filter.is_match(&symbol.name));
+        functions.extend(matching.map(|symbol| (symbol.address.wrapping_add(bias), symbol.demangled.clone())));
+    }
+    functions.sort();
+    // The aliases of a function share its address.
+    functions.dedup_by_key(|(address, _)| *address);
+    if functions.is_empty() {
+        errln!(debugger, "No function matches \"{}\".", args[1]);
+        return false;
+    }
+    if functions.len() > RBREAK_CONFIRM_THRESHOLD
+        && !confirm(debugger, &format!("Set {} breakpoints on the functions matching \"{}\"?", functions.len(), args[1]))
+    {
+        return false;
+    }
+    let group = debugger.breakpoint_groups.len() + 1;
+    let mut numbers = Vec::new();
+    let mut data = Vec::new();
+    for (entry, name) in functions {
+        // The name sets the breakpoint again after restart, where the address may change, unless
+        // it designates another function of the same name.
+        let location = match location::parse_location(debugger, &name) {
+            Ok(Location::Function(_, address)) if address == entry => name.clone(),
+            _ => format!("{:#x}", entry),
+        };
+        match debugger.set_breakpoint(&location) {
+            Ok(address) => {
+                let number = debugger.breakpoints.len();
+                outln!(debugger, "Breakpoint {} set at {:#x}: {}", number, address, name);
+                data.push(json!({ "number": number, "address": json::address(address), "function": name }));
+                numbers.push(number);
+            }
+            Err(err) => warnln!(debugger, "No breakpoint on {}: {}", name, err),
+        }
+    }
+    if numbers.is_empty() {
+        return false;
+    }
+    let count = numbers.len();
+    debugger.breakpoint_groups.push(numbers);
+    json::set_data(debugger, |_| json!({ "group": group, "breakpoints": data }));
+    outln!(debugger, "Set {} breakpoints as group {}, delete group {} removes them.", count, group, group);
+    true
+}
+
+/// Remove the breakpoints of a group set by `rbreak`, `delete group <id>`.
+fn delete(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let id = match args {
+        [_, "group", id] => id,
+        [_, number] => {
+            let number = match location::parse_count(number, "breakpoint") {
+                Ok(number) => number,
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    return false;
+                }
+            };
+            let armed = number.checked_sub(1).and_then(|index| debugger.breakpoints.get(index)).is_some_and(|address| debugger.breakpoint_number(*address) == Some(number));
+            if !armed && !debugger.pending_breakpoints.contains(&number) {
+                errln!(debugger, "No breakpoint number {}.", number);
+                return false;
+            }
+            if !delete_breakpoint(debugger, number) {
+                return false;
+            }
+            outln!(debugger, "Deleted breakpoint {}.", number);
+            return true;
+        }
+        _ => return usage(debugger, args),
+    };
+    let id = match location::parse_count(id, "group") {
+        Ok(id) => id,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let numbers = match id.checked_sub(1).and_then(|index| debugger.breakpoint_groups.get_mut(index)) {
+        Some(group) if !group.is_empty() => std::mem::take(group),
+        _ => {
+            errln!(debugger, "No breakpoint group {}.", id);
+            return false;
+        }
+    };
+    let mut succeeded = true;
+    for &number in &numbers {
+        succeeded &= delete_breakpoint(debugger, number);
+    }
+    outln!(debugger, "Deleted the {} breakpoints of group {}.", numbers.len(), id);
+    succeeded
+}
+
+/// Remove the breakpoint numbered `number` if it is armed, and keep `restart` and exec from
+/// setting it again.
+fn delete_breakpoint(debugger: &mut Debugger, number: usize) -> bool {
+    let mut succeeded = true;
+    let address = debugger.breakpoints[number - 1];
+    if debugger.breakpoint_number(address) == Some(number) {
+        if let Err(err) = working::remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, address) {
+            errln!(debugger, "{}", err);
+            succeeded = false;
+        }
+        debugger.function_breakpoints.remove(&address);
+    }
+    debugger.deleted_breakpoints.insert(number);
+    debugger.pending_breakpoints.remove(&number);
+    succeeded
+}
+
+/// Show the source lines around the selected frame or a location.
+fn list(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let location = match args.get(1) {
+        None => debugger
+            .selected_frame_lookup_pc()
+            .and_then(|pc| debugger.source_location(pc)),
+        Some(target) => match location::parse_location(debugger, target) {
+            Ok(Location::Line(file, line)) => Some(debuginfo::SourceLocation { file, line }),
+            Ok(Location::Address(address) | Location::Function(_, address)) => debugger.source_location(address),
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return false;
+            }
+        },
+    };
+    match location {
+        Some(location) => debugger.list_source(&location),
+        None => outln!(debugger, "No line number information available."),
+    }
+    true
+}
+
+/// Show the call stack.
+fn print_backtrace(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let limit = match args.get(1).map(|count| location::parse_count(count, "count")) {
+        None => debugger.backtrace_limit,
+        Some(Ok(count)) => count,
+        Some(Err(err)) => {
+            errln!(debugger, "{}", err);
+            return usage(debugger, args);
+        }
+    };
+    match backtrace::backtrace(debugger, limit) {
+        Ok(frames) => {
+            json::set_data(debugger, |debugger| {
+                let frames: Vec<Value> =
+                    frames.iter().enumerate().map(|(index, frame)| backtrace::frame_data(debugger, index, frame)).collect();
+                json!({ "frames": frames })
+            });
+            for (index, frame) in frames.iter().enumerate() {
+                backtrace::print_frame(debugger, index, frame);
+            }
+            if frames.len() == limit {
+                outln!(debugger, "(More stack frames follow...)");
+            }
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    true
+}
+
+/// Select a stack frame (`frame`), its caller (`up`) or its callee (`down`).
+fn select_frame(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let count = match args.get(1).map(|count| location::parse_count(count, "count")) {
+        None => None,
+        Some(Ok(count)) => Some(count),
+        Some(Err(err)) => {
+            errln!(debugger, "{}", err);
+            return usage(debugger, args);
+        }
+    };
+    let target = match (args[0], count) {
+        ("up", count) => debugger.selected_frame + count.unwrap_or(1),
+        ("down", count) => match debugger.selected_frame.checked_sub(count.unwrap_or(1)) {
+            Some(target) => target,
+            None => {
+                errln!(debugger, "Bottom (innermost) frame selected; you cannot go down.");
+                return false;
+            }
+        },
+        (_, Some(number)) => number,
+        (_, None) => debugger.selected_frame,
+    };
+    match backtrace::backtrace(debugger, target + 1) {
+        Ok(frames) if target < frames.len() => {
+            debugger.selected_frame = target;
+            backtrace::print_frame(debugger, target, &frames[target]);
+            let lookup = if target == 0 { frames[0].pc } else { frames[target].pc - 1 };
+            if let Some(location) = debugger.source_location(lookup) {
+                debugger.print_source_line(&location);
+            }
+        }
+        Ok(_) if args[0] == "up" => errln!(debugger, "Initial frame selected; you cannot go up."),
+        Ok(frames) => errln!(debugger, "No frame at level {} (the stack has {} frames).", target, frames.len()),
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    true
+}
+
+/// Select a thread by number, or describe the current one, showing its innermost frame.
+fn select_thread(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let number = match args.get(1).map(|number| location::parse_count(number, "thread number")) {
+        None => threads::number(debugger, debugger.thread),
+        Some(Ok(number)) => number,
+        Some(Err(err)) => {
+            errln!(debugger, "{}", err);
+            return usage(debugger, args);
+        }
+    };
+    let tid = match threads::select_thread(debugger, number) {
+        Ok(tid) => tid,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    json::set_data(debugger, |_| json!({ "number": number, "tid": tid.as_raw() }));
+    outln!(debugger, "[Current thread is {} (tid {})]", number, tid);
+    // In non-stop mode, why the thread stopped.
+    let stopped = debugger.threads.iter().any(|thread| thread.tid == tid && thread.stop.is_some());
+    if debugger.non_stop && stopped {
+        if let Some(banner) = summary::banner(debugger) {
+            outln!(debugger, "{}", banner);
+        }
+    }
+    match backtrace::backtrace(debugger, 1) {
+        Ok(frames) if !frames.is_empty() => {
+            backtrace::print_frame(debugger, 0, &frames[0]);
+            if let Some(location) = debugger.source_location(frames[0].pc) {
+                debugger.print_source_line(&location);
+            }
+        }
+        Ok(_) => {}
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    true
+}
+
+/// Change a setting of the debugger.
+fn set(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() < 2 {
+        return usage(debugger, args);
+    }
+    if args[1].starts_with('$') {
+        return set_variable(debugger, &args[1..].join(" "));
+    }
+    if args[1] == "mem" {
+        return set_memory(debugger, &args[2..].join(" "));
+    }
+    match settings::apply(debugger, &args[1..]) {
+        Ok(()) => true,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// Show the settings of the debugger, or one of them.
+fn show(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() == 1 {
+        let mut data = serde_json::Map::new();
+        for spec in SETTINGS {
+            let value = (spec.get)(debugger);
+            outln!(debugger, "{} = {}", spec.name, value);
+            data.insert(spec.name.to_string(), json!(value));
+        }
+        json::set_data(debugger, |_| json!({ "settings": data }));
+        return true;
+    }
+    if args[1..] == ["rlimits"] {
+        sandbox::show_rlimits(debugger);
+        return true;
+    }
+    match settings::find_setting(&args[1..]) {
+        Some((spec, count)) if count == args.len() - 1 => {
+            let value = (spec.get)(debugger);
+            outln!(debugger, "{} = {}", spec.name, value);
+            outln!(debugger, "{}", spec.doc);
+            let default = match spec.kind {
+                _ if spec.default.is_empty() => "none".to_string(),
+                settings::SettingKind::String => format!("\"{}\"", spec.default),
+                _ => spec.default.to_string(),
+            };
+            outln!(debugger, "Usage: {} (default {})", spec.synopsis(), default);
+            true
+        }
+        _ => {
+            errln!(debugger, "{}", settings::unknown_setting(&args[1..].join(" ")));
+            false
+        }
+    }
+}
+
+/// Show the address range of the line `line` of `file` (`info line file:line`).
+fn print_line_address(debugger: &mut Debugger, file: &str, line: u64) -> bool {
+    match debugger.find_line(file, line) {
+        Some((found, address)) if found == line => {
+            let end = debugger.line_range(address).map_or(address, |(_, _, end)| end);
+            let start = debugger.describe_address(address);
+            let end = debugger.describe_address(end);
+            outln!(debugger, "Line {} of \"{}\" starts at address {} and ends at {}.", line, file, start, end);
+        }
+        Some((_, address)) => {
+            let address = debugger.describe_address(address);
+            outln!(debugger, "Line {} of \"{}\" is at address {} but contains no code.", line, file, address);
+        }
+        None => {
+            errln!(debugger, "{}", DbgError::LineOutOfRange { file: file.to_string(), line });
+            return false;
+        }
+    }
+    true
+}
+
+/// Show information about the program or the debugger.
+fn info(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match args.get(1) {
+        Some(&"sharedlibrary" | &"shared") => {
+            let libraries = debugger.shared_libraries();
+            if libraries.is_empty() {
+                outln!(debugger, "No shared libraries loaded at this time.");
+                return true;
+            }
+            outln!(
+                debugger,
+                "{:<18}  {:<18}  {:<9}  {:<10}  Shared Object Library",
+                "Base", "End", "Syms Read", "Debug Info"
+            );
+            for library in libraries {
+                let base = match library.bias {
+                    Some(bias) => format!("{:#018x}", bias),
+                    None => "?".to_string(),
+                };
+                let (loaded, debug_info) = match debugger.symbols.object_mut(&library.path) {
+                    Some(object) if object.has_debug_info() => ("Yes", "Yes"),
+                    Some(_) => ("Yes", "No"),
+                    None => ("No", "?"),
+                };
+                outln!(debugger, "{:<18}  {:#018x}  {:<9}  {:<10}  {}", base, library.end, loaded, debug_info, library.path);
+            }
+        }
+        Some(&"breakpoints" | &"break" | &"b") => {
+            let armed: Vec<(usize, u64)> = (1..=debugger.breakpoints.len())
+                .map(|number| (number, debugger.breakpoints[number - 1]))
+                .filter(|(number, address)| debugger.breakpoint_number(*address) == Some(*number))
+                .collect();
+            let mut data = Vec::new();
+            let pending: Vec<usize> = debugger.pending_breakpoints.iter().copied().collect();
+            if armed.is_empty() && pending.is_empty() {
+                json::set_data(debugger, |_| json!({ "breakpoints": data }));
+                outln!(debugger, "No breakpoints.");
+                return true;
+            }
+            outln!(debugger, "{:<4}  {:<18}  {:<24}  Instruction", "Num", "Address", "What");
+            for (number, address) in armed {
+                let what = match debugger.symbolize(address) {
+                    Some((name, 0, _)) => name,
+                    Some((name, offset, _)) => format!("{}+{}", name, offset),
+                    None => "?".to_string(),
+                };
+                let group = debugger.breakpoint_group(number);
+                let instruction = match disasm::containing_instruction(debugger, address) {
+                    Some(instruction) if instruction.address == address => instruction.text,
+                    Some(instruction) => format!(
+                        "inside `{}` at {:#x}, the breakpoint corrupts it",
+                        instruction.text, instruction.address
+                    ),
+                    None => "<cannot decode>".to_string(),
+                };
+                match group {
+                    Some(group) => outln!(debugger, "{:<4}  {:#018x}  {:<24}  {}  (group {})", number, address, what, instruction, group),
+                    None => outln!(debugger, "{:<4}  {:#018x}  {:<24}  {}", number, address, what, instruction),
+                }
+                data.push(json!({ "number": number, "address": json::address(address), "what": what, "instruction": instruction, "group": group }));
+            }
+            // Not in the program executed, set again by the next exec or restart.
+            for number in pending {
+                let location = debugger.breakpoint_locations[number - 1].clone();
+                outln!(debugger, "{:<4}  {:<18}  {:<24}  not in {}", number, "<pending>", location, debugger.program_path);
+                data.push(json!({ "number": number, "address": null, "what": location, "pending": true, "group": debugger.breakpoint_group(number) }));
+            }
+            json::set_data(debugger, |_| json!({ "breakpoints": data }));
+        }
+        Some(&"dl") => return sharedlib::info_dl(debugger),
+        Some(&"threads") => threads::info_threads(debugger),
+        Some(&"proc") => return procfs::info_proc(debugger, args.get(2).copied()),
+        Some(&"signal") => return siginfo::info_signal(debugger),
+        Some(&"syscall") => return syscallinfo::info_syscall(debugger),
+        Some(&"display") => display::info_display(debugger),
+        Some(&"maps") => return maps::info_maps(debugger),
+        Some(&"snapshots") => snapshot::info_snapshots(debugger),
+        Some(&"file" | &"files") => {
+            let Some(executable) = debugger.executable().cloned() else {
+                errln!(debugger, "No executable file loaded.");
+                return false;
+            };
+            let header = &executable.header;
+            outln!(debugger, "Symbols from \"{}\".", executable.path);
+            outln!(debugger, "Type: {}", header.kind);
+            outln!(debugger, "Machine: {}", header.machine);
+            match executable.bias {
+                Some(bias) => outln!(debugger, "Entry point: {:#x}", header.entry.wrapping_add(bias)),
+                None => outln!(debugger, "Entry point: {:#x} (file address)", header.entry),
+            }
+            if let Some(interpreter) = &header.interpreter {
+                outln!(debugger, "Interpreter: {}", interpreter);
+            }
+            if let Some(bias) = executable.bias {
+                outln!(debugger, "Load bias: {:#x}", bias);
+            }
+        }
+        Some(&"sections") => {
+            let mappings = debugger.mappings.clone();
+            let Some(executable) = debugger.executable().cloned() else {
+                errln!(debugger, "No executable file loaded.");
+                return false;
+            };
+            outln!(debugger, "  [Nr] {:<24} {:<18} {:<10} Flags", "Name", "Address", "Size");
+            for section in executable.sections.iter().filter(|section| !section.name.is_empty()) {
+                let runtime = match executable.bias {
+                    Some(bias) if section.is_alloc() => Some(section.address.wrapping_add(bias)),
+                    _ => None,
+                };
+                let mapped = runtime.is_some_and(|address| maps::find_mapping(&mappings, address).is_some());
+                let address = match runtime {
+                    Some(address) => format!("{:#018x}", address),
+                    None => "-".to_string(),
+                };
+                outln!(
+                    debugger,
+                    "{} [{:>2}] {:<24} {:<18} {:<#10x} {}",
+                    if mapped { "*" } else { " " },
+                    section.index,
+                    section.name,
+                    address,
+                    section.size,
+                    section.flag_letters()
+                );
+            }
+        }
+        Some(&"line") => {
+            let Some(target) = args.get(2) else {
+                errln!(debugger, "Usage: info line <address|function|file:line>");
+                return false;
+            };
+            let address = match location::parse_location(debugger, target) {
+                Ok(Location::Address(address) | Location::Function(_, address)) => address,
+                Ok(Location::Line(file, line)) => return print_line_address(debugger, &file, line),
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    return false;
+                }
+            };
+            match debugger.line_range(address) {
+                Some((location, start, end)) => {
+                    let start = debugger.describe_address(start);
+                    let end = debugger.describe_address(end);
+                    outln!(
+                        debugger,
+                        "Line {} of \"{}\" starts at address {} and ends at {}.",
+                        location.line, location.file, start, end
+                    );
+                    if let Some(function) = debugger.inlined_function(address) {
+                        outln!(debugger, "The address is in {}.", function);
+                    }
+                }
+                None => {
+                    let address = debugger.describe_address(address);
+                    outln!(debugger, "No line number information available for address {}", address);
+                }
+            }
+        }
+        Some(&"address") => {
+            let Some(name) = args.get(2) else {
+                errln!(debugger, "Usage: info address <symbol>");
+                return false;
+            };
+            // Loads the shared library defining the symbol if needed.
+            debugger.resolve_symbol(name);
+            let found = debugger.symbols.find(name);
+            if found.is_empty() {
+                errln!(debugger, "No symbol \"{}\" in loaded objects.", name);
+            }
+            for (object, symbol) in found {
+                let section = object.section_name(symbol).unwrap_or("?");
+                let runtime = match object.bias {
+                    Some(bias) => format!("{:#x}", symbol.address.wrapping_add(bias)),
+                    None => "unknown".to_string(),
+                };
+                outln!(
+                    debugger,
+                    "Symbol \"{}\" is at {} (file address {:#x}) in section {} of {}",
+                    symbol.demangled, runtime, symbol.address, section, object.path
+                );
+            }
+        }
+        Some(&"symbol") => {
+            let Some(pattern) = args.get(2) else {
+                errln!(debugger, "Usage: info symbol <regex>");
+                return false;
+            };
+            let filter = match regex::Regex::new(pattern) {
+                Ok(filter) => filter,
+                Err(err) => {
+                    errln!(debugger, "Invalid regular expression: {}", err);
+                    return false;
+                }
+            };
+            debugger.load_all_symbols();
+            let mut total = 0;
+            for object in &debugger.symbols.objects {
+                let matching: Vec<_> = object
+                    .symbols
+                    .iter()
+                    .filter(|symbol| filter.is_match(&symbol.demangled) || filter.is_match(&symbol.name))
+                    .collect();
+                if matching.is_empty() {
+                    continue;
+                }
+                outln!(debugger, "Symbols matching \"{}\" in {}:", pattern, object.path);
+                for symbol in &matching {
+                    let address = symbol.address.wrapping_add(object.bias.unwrap_or(0));
+                    let kind = if symbol.is_function { 'T' } else { 'D' };
+                    outln!(debugger, "{:#018x}  {}  {}", address, kind, symbol.demangled);
+                }
+                total += matching.len();
+            }
+            if total == 0 {
+                errln!(debugger, "No symbol matches \"{}\".", pattern);
+            }
+        }
+        Some(&"variables") => {
+            let filter = match args.get(2).map(|pattern| regex::Regex::new(pattern)) {
+                None => None,
+                Some(Ok(filter)) => Some(filter),
+                Some(Err(err)) => {
+                    errln!(debugger, "Invalid regular expression: {}", err);
+                    return false;
+                }
+            };
+            debugger.refresh_executable_bias();
+            for object in &debugger.symbols.objects {
+                let matching: Vec<_> = object
+                    .symbols
+                    .iter()
+                    .filter(|symbol| !symbol.is_function)
+                    .filter(|symbol| {
+                        filter.as_ref().is_none_or(|filter| filter.is_match(&symbol.demangled) || filter.is_match(&symbol.name))
+                    })
+                    .collect();
+                if matching.is_empty() {
+                    continue;
+                }
+                outln!(debugger, "Data symbols in {}:", object.path);
+                for symbol in matching {
+                    let address = symbol.address.wrapping_add(object.bias.unwrap_or(0));
+                    outln!(debugger, "{:#018x}  {:>6}  {}", address, symbol.size, symbol.demangled);
+                }
+            }
+        }
+        Some(&"alias" | &"aliases") => print_aliases(debugger),
+        Some(&"labels") => label::info_labels(debugger),
+        Some(&"convenience" | &"conv") => print_variables(debugger),
+        Some(&"locals") => variables::print_locals(debugger),
+        Some(&"args") => variables::print_arguments(debugger),
+        Some(&"frame" | &"f") => return backtrace::info_frame(debugger),
+        _ => {
+            errln!(debugger, "Usage: info breakpoints | info sharedlibrary | info dl | info threads | info proc [cwd|exe|fd] | info signal | info syscall | info maps | info snapshots | info frame | info locals | info args | info file | info sections | info line <location> | info variables [regex] | info address <symbol> | info symbol <regex> | info alias | info labels | info display | info convenience");
+            return false;
+        },
+    }
+    true
+}
+
+/// Show where the program is stopped, in one line.
+fn where_stopped(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 1 {
+        return usage(debugger, args);
+    }
+    summary::print_summary(debugger)
+}
+
+/// Call a function of the program.
+fn call_function(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() < 2 {
+        return usage(debugger, args);
+    }
+    call::call(debugger, &args[1..].join(" "))
+}
+
+/// Map memory in the program.
+fn alloc(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    inject::alloc(debugger, args[1])
+}
+
+/// Unmap memory of the program.
+fn dealloc(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 3 {
+        return usage(debugger, args);
+    }
+    inject::dealloc(debugger, args[1], args[2])
+}
+
+/// Show the value of a variable.
+fn print_variable(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    let limits = match args[0].split_once('/') {
+        None => FormatLimits::DEFAULT,
+        Some((_, "full")) => FormatLimits::FULL,
+        Some((_, format)) => {
+            errln!(debugger, "Unknown format /{}, print takes /full.", format);
+            return false;
+        }
+    };
+    variables::print_variable(debugger, args[1], limits);
+    true
+}
+
+/// Show the symbol containing an address.
+fn symbol(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    let address = match location::parse_value(debugger, args[1], "address") {
+        Ok(address) => address,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    match debugger.symbolize(address) {
+        Some((name, 0, path)) => outln!(debugger, "{:#x} is {} in {}", address, name, path),
+        Some((name, offset, path)) => outln!(debugger, "{:#x} is {}+{:#x} in {}", address, name, offset, path),
+        None => errln!(debugger, "No symbol matches {:#x}.", address),
+    }
+    true
+}
+
+/// Load the symbols of the shared libraries matching a name.
+fn sharedlibrary(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    let results = debugger.load_library_symbols(args[1]);
+    if results.is_empty() {
+        errln!(debugger, "No loaded shared library matches \"{}\".", args[1]);
+    }
+    for result in results {
+        match result {
+            Ok((path, count)) => outln!(debugger, "Loaded {} symbols for {}", count, path),
+            Err(err) => errln!(debugger, "Failed to load symbols: {}", err),
+        }
+    }
+    true
+}
+
+/// Run the commands of a script file.
+fn source(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    script::source(debugger, args[1], crate::run_command)
+}
+
+/// Define an alias, or list them.
+fn define_alias(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() == 1 {
+        print_aliases(debugger);
+        return true;
+    }
+    let (name, body) = match alias::parse_definition(&args[1..].join(" ")) {
+        Ok(definition) => definition,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    if find_command(&name).is_some() {
+        errln!(debugger, "\"{}\" is a command, an alias can't replace it.", name);
+        return false;
+    }
+    if debugger.user_commands.contains_key(&name) {
+        errln!(debugger, "\"{}\" is a user command, remove it with undefine first.", name);
+        return false;
+    }
+    debugger.aliases.insert(name, body);
+    true
+}
+
+/// Remove an alias.
+fn unalias(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    if debugger.aliases.remove(args[1]).is_none() {
+        errln!(debugger, "No alias named \"{}\".", args[1]);
+        return false;
+    }
+    true
+}
+
+/// Start the definition of a user command, whose lines are collected until `end`.
+fn define(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    let name = args[1];
+    if !define::is_valid_name(name) {
+        errln!(debugger, "Invalid command name \"{}\": use letters, digits, - and _", name);
+        return false;
+    }
+    if find_command(name).is_some() {
+        errln!(debugger, "\"{}\" is a command, a user command can't replace it.", name);
+        return false;
+    }
+    if debugger.aliases.contains_key(name) {
+        errln!(debugger, "\"{}\" is an alias, remove it with unalias first.", name);
+        return false;
+    }
+    if debugger.user_commands.contains_key(name) && !confirm(debugger, &format!("Redefine command \"{}\"?", name)) {
+        return false;
+    }
+    if debugger.script_depth == 0 && !debugger.batch {
+        outln!(debugger, "Type commands for definition of \"{}\".", name);
+        outln!(debugger, "End with a line saying just \"end\".");
+    }
+    debugger.definition = Some(define::Definition { name: name.to_string(), lines: Vec::new(), nested: 0 });
+    true
+}
+
+/// Remove a user command.
+fn undefine(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    if debugger.user_commands.remove(args[1]).is_none() {
+        errln!(debugger, "No user command named \"{}\".", args[1]);
+        return false;
+    }
+    true
+}
+
+/// List the aliases with their commands.
+fn print_aliases(debugger: &mut Debugger) {
+    if debugger.aliases.is_empty() {
+        outln!(debugger, "No aliases.");
+        return;
+    }
+    for (name, body) in &debugger.aliases {
+        outln!(debugger, "{} = \"{}\"", name, body);
+    }
+}
+
+/// Set a convenience variable, from `$<name> = <value>`.
+fn set_variable(debugger: &mut Debugger, assignment: &str) -> bool {
+    let Some((name, value)) = assignment.split_once('=') else {
+        errln!(debugger, "Usage: set $<variable> = <value>");
+        return false;
+    };
+    let name = name.trim().trim_start_matches('$');
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        errln!(debugger, "Invalid variable name \"${}\": letters, digits and _, not starting with a digit.", name);
+        return false;
+    }
+    if location::is_register(name) {
+        errln!(debugger, "${} is a register, choose another name for the variable.", name);
+        return false;
+    }
+    match location::parse_value(debugger, value.trim(), "value") {
+        Ok(value) => {
+            debugger.variables.insert(name.to_string(), value);
+            true
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// Write bytes to the memory of the program, from `<address> = <byte>...`.
+///
+/// A write to a read-only or executable mapping asks first, see [`confirm`], unless the command is
+/// forced with `set! mem`: ptrace writes bypass the page protections, and corrupted code is hard
+/// to diagnose.
+fn set_memory(debugger: &mut Debugger, assignment: &str) -> bool {
+    if debugger.core.is_some() {
+        errln!(debugger, "{}", DbgError::NotSupportedOnCore);
+        return false;
+    }
+    let Some((address, values)) = assignment.split_once('=') else {
+        errln!(debugger, "Usage: set mem <address> = <byte> [<byte>...]");
+        return false;
+    };
+    let address = match location::parse_value(debugger, address.trim(), "address") {
+        Ok(address) => address,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let mut bytes = Vec::new();
+    for value in values.split(|c: char| c == ',' || c.is_whitespace()).filter(|value| !value.is_empty()) {
+        match location::parse_value(debugger, value, "byte") {
+            Ok(byte) if byte <= 0xff => bytes.push(byte as u8),
+            Ok(byte) => {
+                errln!(debugger, "{:#x} does not fit in a byte.", byte);
+                return false;
+            }
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return false;
+            }
+        }
+    }
+    if bytes.is_empty() {
+        errln!(debugger, "Usage: set mem <address> = <byte> [<byte>...]");
+        return false;
+    }
+    let protected: Vec<(String, &str)> = match maps::protected_mappings(&debugger.mappings, address, bytes.len() as u64) {
+        Ok(protected) => protected
+            .into_iter()
+            .map(|mapping| {
+                let kind = match (mapping.is_writable(), mapping.is_executable()) {
+                    (false, true) => "read-only and executable",
+                    (true, true) => "executable",
+                    _ => "read-only",
+                };
+                (mapping.describe(), kind)
+            })
+            .collect(),
+        Err(unmapped) => {
+            errln!(debugger, "{}", DbgError::BadAddress(unmapped));
+            return false;
+        }
+    };
+    for (mapping, kind) in &protected {
+        let question = format!(
+            "The mapping {} of {:#x} is {}, and ptrace writes bypass the page protections. Write to it anyway?",
+            mapping, address, kind
+        );
+        if !confirm(debugger, &question) {
+            return false;
+        }
+        warnln!(debugger, "Writing to the {} mapping {}.", kind, mapping);
+    }
+    working::keep_breakpoints(&mut debugger.breakpoint_table, address, &mut bytes);
+    match memory::write_bytes(debugger.thread, address, &bytes) {
+        Ok(()) => true,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// List the convenience variables with their values.
+fn print_variables(debugger: &mut Debugger) {
+    json::set_data(debugger, |debugger| {
+        let variables: serde_json::Map<String, Value> =
+            debugger.variables.iter().map(|(name, value)| (name.clone(), json::address(*value))).collect();
+        json!({ "variables": variables })
+    });
+    if debugger.variables.is_empty() {
+        outln!(debugger, "No convenience variables, set one with set $<variable> = <value>.");
+        return;
+    }
+    for (name, value) in &debugger.variables {
+        outln!(debugger, "${} = {:#x} ({})", name, value, value);
+    }
+}
+
+/// Print a line of text.
+fn echo(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let text = printf::unescape(&args[1..].join(" "));
+    for line in text.split('\n') {
+        outln!(debugger, "{}", line);
+    }
+    true
+}
+
+/// Print values with a C format.
+/// Check a comparison of values (`assert`).
+fn check_assertion(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() < 2 {
+        return usage(debugger, args);
+    }
+    assertion::assert(debugger, &args[1..].join(" "))
+}
+
+fn printf(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() < 2 {
+        return usage(debugger, args);
+    }
+    let text = args[1..].join(" ");
+    let formatted = printf::split_arguments(&text).and_then(|(format, arguments)| printf::format(debugger, &format, &arguments));
+    match formatted {
+        Ok(formatted) => {
+            for line in formatted.strip_suffix('\n').unwrap_or(&formatted).split('\n') {
+                outln!(debugger, "{}", line);
+            }
+            true
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// Give the program another terminal, when it is started again.
+fn tty(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        return usage(debugger, args);
+    }
+    if let Err(err) = std::fs::metadata(args[1]) {
+        errln!(debugger, "Cannot use {} as the terminal of the program: {}", args[1], err);
+        return false;
+    }
+    debugger.inferior_tty = InferiorTty::Path(args[1].to_string());
+    outln!(debugger, "The program gets the terminal {} from the next restart.", args[1]);
+    true
+}
+
+/// Kill the program and start it again, setting the breakpoints again.
+fn restart(debugger: &mut Debugger, _: &[&str]) -> bool {
+    if debugger.core.is_some() {
+        errln!(debugger, "{}", DbgError::NotSupportedOnCore);
+        return false;
+    }
+    if debugger.has_live_process() && !confirm(debugger, &format!("The program is running (pid {}). Kill it and start it again?", debugger.child)) {
+        return false;
+    }
+    start_again(debugger, &debugger.launch_spec(), "Restarted")
+}
+
+/// Kill the program and start it again as `spec` says, setting the breakpoints and the
+/// watchpoints again, and say so starting with `announce`.
+fn start_again(debugger: &mut Debugger, spec: &LaunchSpec, announce: &str) -> bool {
+    launch::release(debugger);
+    inferior::flush(debugger);
+    clear_breakpoints(&mut debugger.breakpoint_table);
+    let (child, stdio) = match launch::start(spec, &debugger.inferior_tty) {
+        Ok(started) => started,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    if debugger.symbols_stale {
+        outln!(debugger, "Loading the symbols of the rebuilt {}.", debugger.program_path);
+    }
+    debugger.start_process(child);
+    inferior::capture(debugger, stdio);
+    outln!(debugger, "{} {} (pid {}).", announce, debugger.program_path, child);
+    debugger.breakpoints.clear();
+    debugger.function_breakpoints.clear();
+    let deleted = std::mem::take(&mut debugger.deleted_breakpoints);
+    debugger.pending_breakpoints.clear();
+    // The breakpoints are numbered again, the groups follow their new numbers.
+    let mut numbers = HashMap::new();
+    let mut succeeded = true;
+    for (index, location) in std::mem::take(&mut debugger.breakpoint_locations).into_iter().enumerate() {
+        if deleted.contains(&(index + 1)) {
+            continue;
+        }
+        if breakpoint(debugger, &["b", &location]) {
+            numbers.insert(index + 1, debugger.breakpoints.len());
+        } else {
+            succeeded = false;
+        }
+    }
+    for group in &mut debugger.breakpoint_groups {
+        *group = group.iter().filter_map(|number| numbers.get(number).copied()).collect();
+    }
+    succeeded &= watch::rewatch(debugger);
+    succeeded &= pagewatch::rewatch(debugger);
+    succeeded
+}
+
+/// The commands taking a format after their name, as `print/full`.
+const FORMAT_COMMANDS: &[&str] = &["print", "x"];
+
+/// Returns true if the command takes a format after its name, as `print/full`.
+pub fn takes_format(spec: &CommandSpec) -> bool {
+    FORMAT_COMMANDS.contains(&spec.name())
+}
+
+/// Returns the command line of `shell <command line>` or `!<command line>`, which goes to the
+/// shell as typed.
+pub fn shell_command_line(command: &str) -> Option<&str> {
+    let command = command.trim();
+    if let Some(line) = command.strip_prefix('!') {
+        return Some(line.trim_start());
+    }
+    let name = command.split_whitespace().next()?;
+    match lookup_command(name) {
+        CommandMatch::Found(spec) if spec.name() == "shell" => Some(command[name.len()..].trim_start()),
+        _ => None,
+    }
+}
+
+/// Run a command line with the shell of the user, on the terminal of the debugger.
+///
+/// # Returns
+///
+/// True if the command line ran and exited with status 0. Other statuses are reported.
+pub fn run_shell(debugger: &mut Debugger, line: &str) -> bool {
+    let shell = std::env::var("SHELL").ok().filter(|shell| !shell.is_empty()).unwrap_or_else(|| "/bin/sh".to_string());
+    let mut command = std::process::Command::new(&shell);
+    if !line.is_empty() {
+        command.arg("-c").arg(line);
+    }
+    // The output of the debugger comes first.
+    std::io::stdout().flush().ok();
+    let status = match command.status() {
+        Ok(status) => status,
+        Err(err) => {
+            errln!(debugger, "Could not run {}: {}", shell, err);
+            return false;
+        }
+    };
+    json::set_data(debugger, |_| json!({ "status": status.code(), "signal": status.signal() }));
+    match (status.code(), status.signal()) {
+        (Some(0), _) => true,
+        (Some(code), _) => {
+            errln!(debugger, "Shell command exited with status {}.", code);
+            false
+        }
+        (None, signal) => {
+            errln!(debugger, "Shell command was killed by signal {}.", signal.unwrap_or_default());
+            false
+        }
+    }
+}
+
+/// Run a command line with the shell (`shell` and `!`).
+fn shell(debugger: &mut Debugger, args: &[&str]) -> bool {
+    run_shell(debugger, &args[1..].join(" "))
+}
+
+/// Run the build command, after which the symbols are stale until `restart`.
+fn rebuild(debugger: &mut Debugger, _: &[&str]) -> bool {
+    if debugger.build_command.is_empty() {
+        errln!(debugger, "No build command, give one with set build-command <command line>.");
+        return false;
+    }
+    let command = debugger.build_command.clone();
+    outln!(debugger, "Running {}", command);
+    if !run_shell(debugger, &command) {
+        return false;
+    }
+    debugger.symbols_stale = true;
+    warnln!(debugger, "{} was rebuilt, the symbols loaded are stale: restart runs the new program with its symbols and sets the breakpoints again.", debugger.program_path);
+    true
+}
+
+/// List the commands, or show the details of one.
+fn help(debugger: &mut Debugger, args: &[&str]) -> bool {
+    help_commands(debugger, args.get(1).copied())
+}
+
+/// Kill the program and end the session: the caller exits the debugger.
+fn quit(debugger: &mut Debugger, _: &[&str]) -> bool {
+    // An attached process is detached, and keeps running.
+    let kills = debugger.has_live_process() && !debugger.attached;
+    if kills && !confirm(debugger, &format!("The program is running (pid {}). Kill it and quit?", debugger.child)) {
+        return false;
+    }
+    // Don't leave the program stopped behind.
+    launch::release(debugger);
+    if debugger.attached {
+        outln!(debugger, "Detached from process {}.", debugger.child);
+    }
+    outln!(debugger, "Exiting the debugger !");
+    debugger.quit_requested = true;
+    true
+}
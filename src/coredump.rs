@@ -0,0 +1,372 @@
+use crate::arch::{get_fp_registers, ELF_MACHINE};
+use crate::debugger::Debugger;
+use crate::json;
+use crate::maps::{self, MapEntry};
+use crate::memory::read_bulk;
+use crate::procfs;
+use crate::working::{restore_original_bytes, BreakpointTable};
+use nix::libc::{self, user_regs_struct};
+use nix::unistd::Pid;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+
+/// The page size of x86_64, the alignment of the segments.
+const PAGE_SIZE: u64 = 4096;
+/// The size of the ELF header of a 64-bit file.
+const EHDR_SIZE: u64 = 64;
+/// The size of a program header of a 64-bit file.
+const PHDR_SIZE: u64 = 56;
+/// The most memory read at once while copying a mapping.
+const CHUNK_SIZE: u64 = 1 << 20;
+
+const ET_CORE: u16 = 4;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRFPREG: u32 = 2;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_FILE: u32 = 0x4649_4c45;
+
+// The bits of /proc/<pid>/coredump_filter, as documented in core(5).
+const FILTER_ANON_PRIVATE: u32 = 1 << 0;
+const FILTER_ANON_SHARED: u32 = 1 << 1;
+const FILTER_MAPPED_PRIVATE: u32 = 1 << 2;
+const FILTER_MAPPED_SHARED: u32 = 1 << 3;
+const FILTER_ELF_HEADERS: u32 = 1 << 4;
+const FILTER_HUGETLB_PRIVATE: u32 = 1 << 5;
+const FILTER_HUGETLB_SHARED: u32 = 1 << 6;
+/// The filter of the kernel when the process did not change it.
+const DEFAULT_FILTER: u32 = 0x33;
+
+/// How much of a mapping goes into the core file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dump {
+    /// The whole mapping.
+    Whole,
+    /// Its first page only, the ELF header of a file mapped with it.
+    Header,
+    /// Nothing, only its place in memory.
+    Skip,
+}
+
+/// What `/proc/<pid>/smaps` tells about a mapping, beyond `/proc/<pid>/maps`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmapsEntry {
+    /// The start address of the mapping.
+    pub start: u64,
+    /// The kernel flags of the mapping, as `dd` (do not dump), `io` (device memory) or `ht` (huge
+    /// pages).
+    pub vm_flags: Vec<String>,
+    /// The anonymous memory in kB: for a private file mapping, the pages written to.
+    pub anonymous: u64,
+}
+
+/// What went into a core file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreStats {
+    /// The number of threads, each with its registers.
+    pub threads: usize,
+    /// The number of load segments, one per mapping.
+    pub segments: usize,
+    /// The number of bytes of memory copied.
+    pub memory: u64,
+}
+
+/// Decide how much of a mapping a core dump contains, with the rules of the kernel
+/// (`vma_dump_size` in `fs/binfmt_elf.c`).
+///
+/// # Arguments
+///
+/// * `entry` - The mapping.
+/// * `smaps` - Its flags and anonymous memory, from `/proc/<pid>/smaps`.
+/// * `filter` - The value of `/proc/<pid>/coredump_filter`.
+pub fn dump_rule(entry: &MapEntry, smaps: &SmapsEntry, filter: u32) -> Dump {
+    let has_flag = |flag: &str| smaps.vm_flags.iter().any(|name| name == flag);
+    let whole_if = |bit: u32| if filter & bit != 0 { Dump::Whole } else { Dump::Skip };
+    let shared = entry.perms.as_bytes().get(3) == Some(&b's');
+    // The vDSO is needed to unwind through signal handlers.
+    if entry.pathname.as_deref() == Some("[vdso]") {
+        return Dump::Whole;
+    }
+    if has_flag("dd") || has_flag("io") || has_flag("pf") {
+        return Dump::Skip;
+    }
+    if entry.perms.as_bytes().first() != Some(&b'r') {
+        return Dump::Skip;
+    }
+    if has_flag("ht") {
+        return whole_if(if shared { FILTER_HUGETLB_SHARED } else { FILTER_HUGETLB_PRIVATE });
+    }
+    let file_backed = entry.inode != 0;
+    if shared {
+        // Shared anonymous memory is an unlinked file, as `/dev/zero (deleted)` or `/SYSV00000000 (deleted)`.
+        let unlinked = entry.pathname.as_deref().is_some_and(|path| path.ends_with("(deleted)"));
+        return whole_if(if file_backed && !unlinked { FILTER_MAPPED_SHARED } else { FILTER_ANON_SHARED });
+    }
+    // A private file mapping written to, as relocated data, has anonymous pages.
+    if !file_backed || smaps.anonymous > 0 {
+        if filter & FILTER_ANON_PRIVATE != 0 {
+            return Dump::Whole;
+        }
+        if !file_backed {
+            return Dump::Skip;
+        }
+    }
+    if filter & FILTER_MAPPED_PRIVATE != 0 {
+        return Dump::Whole;
+    }
+    if filter & FILTER_ELF_HEADERS != 0 && entry.offset == 0 {
+        return Dump::Header;
+    }
+    Dump::Skip
+}
+
+/// Parse the flags and the anonymous memory of each mapping of a `/proc/<pid>/smaps` file, whose
+/// mappings are lines of `/proc/<pid>/maps` followed by their fields.
+pub fn parse_smaps(content: &str) -> Vec<SmapsEntry> {
+    let mut entries: Vec<SmapsEntry> = Vec::new();
+    for line in content.lines() {
+        if let [entry] = maps::parse_maps(line).as_slice() {
+            entries.push(SmapsEntry { start: entry.start, ..SmapsEntry::default() });
+            continue;
+        }
+        let (Some(last), Some((key, value))) = (entries.last_mut(), line.split_once(':')) else {
+            continue;
+        };
+        match key {
+            "VmFlags" => last.vm_flags = value.split_whitespace().map(str::to_string).collect(),
+            "Anonymous" => last.anonymous = value.trim().trim_end_matches("kB").trim().parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Read `/proc/<pid>/coredump_filter`, or the default filter of the kernel.
+fn read_filter(pid: Pid) -> u32 {
+    fs::read_to_string(format!("/proc/{}/coredump_filter", pid))
+        .ok()
+        .and_then(|content| u32::from_str_radix(content.trim(), 16).ok())
+        .unwrap_or(DEFAULT_FILTER)
+}
+
+/// Append an ELF note, its name and descriptor padded to 4 bytes.
+fn push_note(notes: &mut Vec<u8>, kind: u32, descriptor: &[u8]) {
+    let name = b"CORE\0";
+    notes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&(descriptor.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&kind.to_le_bytes());
+    for bytes in [&name[..], descriptor] {
+        notes.extend_from_slice(bytes);
+        notes.resize(notes.len().next_multiple_of(4), 0);
+    }
+}
+
+/// The bytes of a C structure, as the kernel writes it in the notes.
+fn struct_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) }
+}
+
+/// The `elf_prstatus` of a thread: its signal, ids and registers.
+fn prstatus(tid: Pid, pid: Pid, signal: i32, regs: &user_regs_struct) -> Vec<u8> {
+    let regs_size = std::mem::size_of::<user_regs_struct>();
+    let mut status = vec![0; (112 + regs_size + 4).next_multiple_of(8)];
+    status[0..4].copy_from_slice(&signal.to_le_bytes());
+    status[12..14].copy_from_slice(&(signal as i16).to_le_bytes());
+    let ppid = procfs::read_status(pid).ok().and_then(|status| status.ppid).unwrap_or(0);
+    let (pgrp, sid) = unsafe { (libc::getpgid(pid.as_raw()), libc::getsid(pid.as_raw())) };
+    for (offset, value) in [(32, tid.as_raw()), (36, ppid), (40, pgrp), (44, sid)] {
+        status[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    status[112..112 + regs_size].copy_from_slice(struct_bytes(regs));
+    // pr_fpvalid: a NT_PRFPREG note follows.
+    status[112 + regs_size..116 + regs_size].copy_from_slice(&1i32.to_le_bytes());
+    status
+}
+
+/// The `elf_prpsinfo` of the process: its state, owner, name and command line.
+fn prpsinfo(pid: Pid) -> Vec<u8> {
+    let mut info = vec![0; 136];
+    let status = procfs::read_status(pid).unwrap_or_default();
+    let state = status.state.bytes().next().unwrap_or(b'R');
+    info[1] = state;
+    info[0] = b"RSDTZW".iter().position(|letter| *letter == state).unwrap_or(0) as u8;
+    if let Ok(metadata) = fs::metadata(format!("/proc/{}", pid)) {
+        info[16..20].copy_from_slice(&metadata.uid().to_le_bytes());
+        info[20..24].copy_from_slice(&metadata.gid().to_le_bytes());
+    }
+    info[24..28].copy_from_slice(&pid.as_raw().to_le_bytes());
+    info[28..32].copy_from_slice(&status.ppid.unwrap_or(0).to_le_bytes());
+    let name = status.name.as_bytes();
+    info[40..40 + name.len().min(15)].copy_from_slice(&name[..name.len().min(15)]);
+    // The arguments are separated by NULs.
+    let arguments = fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+    let arguments: Vec<u8> = arguments.iter().map(|byte| if *byte == 0 { b' ' } else { *byte }).collect();
+    let arguments = arguments.trim_ascii_end();
+    info[56..56 + arguments.len().min(79)].copy_from_slice(&arguments[..arguments.len().min(79)]);
+    info
+}
+
+/// The `NT_FILE` note: the files mapped, so that a debugger finds the shared libraries.
+fn mapped_files_note(mappings: &[MapEntry]) -> Vec<u8> {
+    let files: Vec<&MapEntry> = mappings.iter().filter(|entry| entry.is_file_backed()).collect();
+    let mut note = Vec::new();
+    note.extend_from_slice(&(files.len() as u64).to_le_bytes());
+    note.extend_from_slice(&PAGE_SIZE.to_le_bytes());
+    for entry in &files {
+        for value in [entry.start, entry.end, entry.offset / PAGE_SIZE] {
+            note.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for entry in &files {
+        note.extend_from_slice(entry.pathname.as_deref().unwrap_or_default().as_bytes());
+        note.push(0);
+    }
+    note
+}
+
+/// Copy `len` bytes of memory from `address` to `out`, without the breakpoints. A chunk that
+/// can't be read is read page by page, the pages that can't be read being written as zeros.
+fn copy_memory(out: &mut impl Write, breakpoints: &BreakpointTable, pid: Pid, address: u64, len: u64) -> io::Result<()> {
+    let mut current = address;
+    while current < address + len {
+        let size = CHUNK_SIZE.min(address + len - current);
+        let mut bytes = read_bulk(pid, current, size as usize).unwrap_or_else(|_| {
+            let pages = (current..current + size).step_by(PAGE_SIZE as usize);
+            pages.flat_map(|page| {
+                let page_size = PAGE_SIZE.min(current + size - page) as usize;
+                read_bulk(pid, page, page_size).unwrap_or_else(|_| vec![0; page_size])
+            })
+            .collect()
+        });
+        restore_original_bytes(breakpoints, current, &mut bytes);
+        out.write_all(&bytes)?;
+        current += size;
+    }
+    Ok(())
+}
+
+/// Write a core file of the program, which `gdb <program> <core>` loads: the registers of every
+/// thread in `NT_PRSTATUS` and `NT_PRFPREG` notes, the current thread first, and a load segment
+/// per mapping, with the memory the kernel would dump (see [`dump_rule`]).
+///
+/// # Errors
+///
+/// Returns an error if the registers or the mappings can't be read, or the file can't be written.
+pub fn write_core(debugger: &Debugger, path: &str) -> io::Result<CoreStats> {
+    let pid = debugger.child;
+    let mappings = maps::read_maps(pid)?;
+    let smaps = fs::read_to_string(format!("/proc/{}/smaps", pid)).map(|content| parse_smaps(&content)).unwrap_or_default();
+    let filter = read_filter(pid);
+    let rules: Vec<Dump> = mappings
+        .iter()
+        .map(|entry| {
+            let smaps = smaps.iter().find(|smaps| smaps.start == entry.start).cloned().unwrap_or_default();
+            dump_rule(entry, &smaps, filter)
+        })
+        .collect();
+
+    let mut notes = Vec::new();
+    push_note(&mut notes, NT_PRPSINFO, &prpsinfo(pid));
+    let mut tids: Vec<Pid> = debugger.threads.iter().map(|thread| thread.tid).collect();
+    tids.sort_by_key(|tid| *tid != debugger.thread);
+    for tid in &tids {
+        let regs = crate::arch::get_registers(*tid).map_err(io::Error::from)?;
+        let fpregs = get_fp_registers(*tid).map_err(io::Error::from)?;
+        let signal = match debugger.last_signal {
+            Some(info) if *tid == debugger.thread => info.signo,
+            _ => 0,
+        };
+        push_note(&mut notes, NT_PRSTATUS, &prstatus(*tid, pid, signal, &regs));
+        push_note(&mut notes, NT_PRFPREG, struct_bytes(&fpregs));
+    }
+    if let Ok(auxv) = fs::read(format!("/proc/{}/auxv", pid)) {
+        push_note(&mut notes, NT_AUXV, &auxv);
+    }
+    push_note(&mut notes, NT_FILE, &mapped_files_note(&mappings));
+
+    // The headers, the notes, then the memory of each segment from the next page.
+    let program_headers = 1 + mappings.len() as u64;
+    let notes_offset = EHDR_SIZE + program_headers * PHDR_SIZE;
+    let mut offset = (notes_offset + notes.len() as u64).next_multiple_of(PAGE_SIZE);
+    let mut header = Vec::with_capacity(notes_offset as usize);
+    header.extend_from_slice(b"\x7fELF\x02\x01\x01");
+    header.resize(16, 0);
+    header.extend_from_slice(&ET_CORE.to_le_bytes());
+    header.extend_from_slice(&ELF_MACHINE.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes());
+    // e_entry, e_phoff, e_shoff, e_flags.
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&EHDR_SIZE.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    for size in [EHDR_SIZE as u16, PHDR_SIZE as u16, program_headers as u16, 64, 0, 0] {
+        header.extend_from_slice(&size.to_le_bytes());
+    }
+    let mut program_header = |kind: u32, flags: u32, offset: u64, address: u64, file_size: u64, memory_size: u64, align: u64| {
+        header.extend_from_slice(&kind.to_le_bytes());
+        header.extend_from_slice(&flags.to_le_bytes());
+        for value in [offset, address, 0, file_size, memory_size, align] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+    };
+    program_header(PT_NOTE, 0, notes_offset, 0, notes.len() as u64, 0, 4);
+    let mut segments = Vec::new();
+    for (entry, rule) in mappings.iter().zip(&rules) {
+        let size = entry.end - entry.start;
+        let file_size = match rule {
+            Dump::Whole => size,
+            Dump::Header => PAGE_SIZE.min(size),
+            Dump::Skip => 0,
+        };
+        let perms = entry.perms.as_bytes();
+        let flags = [(0, 4), (1, 2), (2, 1)].iter().filter(|(index, _)| perms.get(*index).is_some_and(|perm| *perm != b'-')).map(|(_, flag)| flag).sum();
+        program_header(PT_LOAD, flags, offset, entry.start, file_size, size, PAGE_SIZE);
+        segments.push((entry.start, offset, file_size));
+        offset += file_size;
+    }
+
+    let write = |out: &mut BufWriter<File>| -> io::Result<u64> {
+        out.write_all(&header)?;
+        out.write_all(&notes)?;
+        let mut written = notes_offset + notes.len() as u64;
+        for (address, offset, file_size) in segments.iter().filter(|(_, _, file_size)| *file_size > 0) {
+            out.write_all(&vec![0; (offset - written) as usize])?;
+            copy_memory(out, &debugger.breakpoint_table, pid, *address, *file_size)?;
+            written = offset + file_size;
+        }
+        out.flush()?;
+        Ok(segments.iter().map(|(_, _, file_size)| file_size).sum())
+    };
+    let mut out = BufWriter::new(File::create(path)?);
+    match write(&mut out) {
+        Ok(memory) => Ok(CoreStats { threads: tids.len(), segments: mappings.len(), memory }),
+        Err(err) => {
+            // A core file without all the memory would mislead.
+            fs::remove_file(path).ok();
+            Err(err)
+        }
+    }
+}
+
+/// Save a core file of the program (`gcore`), to `core.<pid>` by default.
+///
+/// # Returns
+///
+/// False if the core file could not be written.
+pub fn gcore(debugger: &mut Debugger, path: Option<&str>) -> bool {
+    let path = path.map(str::to_string).unwrap_or_else(|| format!("core.{}", debugger.child));
+    let stats = match write_core(debugger, &path) {
+        Ok(stats) => stats,
+        Err(err) => {
+            errln!(debugger, "Could not write the core file {}: {}", path, err);
+            return false;
+        }
+    };
+    json::set_data(debugger, |_| json!({ "path": path, "threads": stats.threads, "segments": stats.segments, "memory": stats.memory }));
+    outln!(debugger, "Saved corefile {} ({} threads, {} segments, {} bytes of memory).", path, stats.threads, stats.segments, stats.memory);
+    true
+}
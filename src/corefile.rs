@@ -0,0 +1,317 @@
+use crate::arch::ELF_MACHINE;
+use crate::backtrace;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::maps::MapEntry;
+use crate::siginfo;
+use crate::tracee::Tracee;
+use nix::errno::Errno;
+use nix::libc::user_regs_struct;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::rc::Rc;
+
+const ET_CORE: u16 = 4;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+const NT_FILE: u32 = 0x4649_4c45;
+/// The most notes read, against a corrupted file.
+const MAX_NOTES_SIZE: u64 = 64 << 20;
+
+/// A thread saved in a core file.
+#[derive(Debug, Clone, Copy)]
+pub struct CoreThread {
+    pub tid: Pid,
+    /// The signal it was stopped by, 0 for none.
+    pub signal: i32,
+    /// Its general purpose registers.
+    pub regs: user_regs_struct,
+}
+
+/// A load segment: memory of the program, part of it in the core file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    start: u64,
+    end: u64,
+    /// Where its bytes are in the core file.
+    offset: u64,
+    /// How many bytes are there, the others were left out of the dump.
+    file_size: u64,
+}
+
+/// A core file, whose registers and memory the commands read instead of a process: the
+/// post-mortem mode of `--core`.
+#[derive(Debug)]
+pub struct CoreFile {
+    pub path: String,
+    /// The process the core was taken from.
+    pub pid: Pid,
+    /// Its command line, as in `ps`.
+    pub command: String,
+    /// The threads, the one that dumped the core first.
+    pub threads: Vec<CoreThread>,
+    /// The mappings of the process, rebuilt from the load segments and the files they map.
+    pub mappings: Vec<MapEntry>,
+    segments: Vec<Segment>,
+    file: File,
+    /// The files mapped by the process, opened on first use: the memory left out of the dump, as
+    /// code, is read from them.
+    mapped_files: RefCell<HashMap<String, Option<File>>>,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// A NUL terminated string of a fixed size field.
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim_end().to_string()
+}
+
+/// Split the notes of a `PT_NOTE` segment into their owner, type and descriptor.
+fn parse_notes(bytes: &[u8]) -> Vec<(String, u32, &[u8])> {
+    let mut notes = Vec::new();
+    let mut offset = 0;
+    while let (Some(name_size), Some(descriptor_size), Some(kind)) = (u32_at(bytes, offset), u32_at(bytes, offset + 4), u32_at(bytes, offset + 8)) {
+        let name_start = offset + 12;
+        let descriptor_start = name_start + (name_size as usize).next_multiple_of(4);
+        let end = descriptor_start + descriptor_size as usize;
+        let (Some(name), Some(descriptor)) = (bytes.get(name_start..name_start + name_size as usize), bytes.get(descriptor_start..end)) else {
+            break;
+        };
+        notes.push((c_string(name), kind, descriptor));
+        offset = end.next_multiple_of(4);
+    }
+    notes
+}
+
+/// Read a thread from its `elf_prstatus`.
+fn parse_prstatus(descriptor: &[u8]) -> Option<CoreThread> {
+    let regs = descriptor.get(112..112 + std::mem::size_of::<user_regs_struct>())?;
+    Some(CoreThread {
+        tid: Pid::from_raw(u32_at(descriptor, 32)? as i32),
+        signal: u16_at(descriptor, 12)? as i16 as i32,
+        // Plain integers, valid for any bytes.
+        regs: unsafe { std::ptr::read_unaligned(regs.as_ptr() as *const user_regs_struct) },
+    })
+}
+
+/// Read the `NT_FILE` note: the start, end, file offset and path of each file mapping.
+fn parse_mapped_files(descriptor: &[u8]) -> Vec<(u64, u64, u64, String)> {
+    let (Some(count), Some(page_size)) = (u64_at(descriptor, 0), u64_at(descriptor, 8)) else {
+        return Vec::new();
+    };
+    let names_start = 16 + 24 * count as usize;
+    let Some(names) = descriptor.get(names_start..) else {
+        return Vec::new();
+    };
+    names
+        .split(|byte| *byte == 0)
+        .zip(0..count as usize)
+        .filter_map(|(name, index)| {
+            let entry = 16 + 24 * index;
+            let (start, end, page) = (u64_at(descriptor, entry)?, u64_at(descriptor, entry + 8)?, u64_at(descriptor, entry + 16)?);
+            Some((start, end, page * page_size, String::from_utf8_lossy(name).into_owned()))
+        })
+        .collect()
+}
+
+impl CoreFile {
+    /// Open an x86_64 core file, reading its threads and mappings.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::Core`] if the file can't be read or is not an x86_64 core file.
+    pub fn open(path: &str) -> Result<CoreFile, DbgError> {
+        let invalid = |reason: &str| DbgError::Core { path: path.to_string(), reason: reason.to_string() };
+        let file = File::open(path).map_err(|err| invalid(&err.to_string()))?;
+        let read = |offset: u64, len: u64| -> Result<Vec<u8>, DbgError> {
+            let mut bytes = vec![0; len as usize];
+            file.read_exact_at(&mut bytes, offset).map_err(|_| invalid("truncated file"))?;
+            Ok(bytes)
+        };
+        let header = read(0, 64)?;
+        if header[..4] != *b"\x7fELF" || header[4] != 2 || header[5] != 1 {
+            return Err(invalid("not a 64-bit little endian ELF file"));
+        }
+        if u16_at(&header, 16) != Some(ET_CORE) || u16_at(&header, 18) != Some(ELF_MACHINE) {
+            return Err(invalid(&format!("not an {} core file", std::env::consts::ARCH)));
+        }
+        let (program_headers, entry_size, count) = (u64_at(&header, 32).unwrap_or(0), u16_at(&header, 54).unwrap_or(0), u16_at(&header, 56).unwrap_or(0));
+        if entry_size < 56 {
+            return Err(invalid("no program headers"));
+        }
+        let table = read(program_headers, entry_size as u64 * count as u64)?;
+        let mut segments = Vec::new();
+        let mut permissions = Vec::new();
+        let mut notes = Vec::new();
+        for entry in table.chunks(entry_size as usize) {
+            let (kind, flags) = (u32_at(entry, 0).unwrap_or(0), u32_at(entry, 4).unwrap_or(0));
+            let (offset, address, file_size, memory_size) =
+                (u64_at(entry, 8).unwrap_or(0), u64_at(entry, 16).unwrap_or(0), u64_at(entry, 32).unwrap_or(0), u64_at(entry, 40).unwrap_or(0));
+            match kind {
+                PT_LOAD => {
+                    segments.push(Segment { start: address, end: address + memory_size, offset, file_size: file_size.min(memory_size) });
+                    let letter = |flag: u32, letter: char| if flags & flag != 0 { letter } else { '-' };
+                    permissions.push(format!("{}{}{}p", letter(4, 'r'), letter(2, 'w'), letter(1, 'x')));
+                }
+                PT_NOTE if file_size <= MAX_NOTES_SIZE => notes.extend(read(offset, file_size)?),
+                _ => {}
+            }
+        }
+        let (mut threads, mut pid, mut command, mut files) = (Vec::new(), None, String::new(), Vec::new());
+        for (owner, kind, descriptor) in parse_notes(&notes) {
+            match (owner.as_str(), kind) {
+                ("CORE", NT_PRSTATUS) => threads.extend(parse_prstatus(descriptor)),
+                ("CORE", NT_PRPSINFO) if descriptor.len() >= 136 => {
+                    pid = u32_at(descriptor, 24).map(|pid| Pid::from_raw(pid as i32));
+                    command = c_string(&descriptor[56..136]);
+                }
+                ("CORE", NT_FILE) => files = parse_mapped_files(descriptor),
+                _ => {}
+            }
+        }
+        let Some(first) = threads.first() else {
+            return Err(invalid("no thread in the core file"));
+        };
+        let mappings = segments
+            .iter()
+            .zip(permissions)
+            .map(|(segment, perms)| {
+                let file = files.iter().find(|(start, end, _, _)| *start == segment.start && *end == segment.end);
+                MapEntry {
+                    start: segment.start,
+                    end: segment.end,
+                    perms,
+                    offset: file.map_or(0, |(_, _, offset, _)| *offset),
+                    // The core file does not keep the inodes, any nonzero value marks the file mappings.
+                    inode: file.map_or(0, |_| 1),
+                    pathname: file.map(|(_, _, _, path)| path.clone()),
+                }
+            })
+            .collect();
+        Ok(CoreFile {
+            path: path.to_string(),
+            pid: pid.unwrap_or(first.tid),
+            command,
+            threads,
+            mappings,
+            segments,
+            file,
+            mapped_files: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// The signal that ended the program, from the thread that dumped the core, 0 for none as
+    /// with `gcore`.
+    pub fn signal(&self) -> i32 {
+        self.threads.first().map_or(0, |thread| thread.signal)
+    }
+
+    /// Read the memory of the program at `address`: from the dump, or else from the file mapped
+    /// there, as the code left out of the dump.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EIO` if nothing is mapped at `address`, or its memory was not dumped and does not
+    /// come from a file.
+    pub fn read(&self, address: u64, bytes: &mut [u8]) -> Result<(), Errno> {
+        let end = address.checked_add(bytes.len() as u64).ok_or(Errno::EIO)?;
+        let index = self.segments.iter().position(|segment| address >= segment.start && end <= segment.end).ok_or(Errno::EIO)?;
+        let segment = self.segments[index];
+        let offset = address - segment.start;
+        if offset + bytes.len() as u64 <= segment.file_size {
+            return self.file.read_exact_at(bytes, segment.offset + offset).map_err(|_| Errno::EIO);
+        }
+        let mapping = &self.mappings[index];
+        let path = mapping.pathname.as_ref().filter(|_| mapping.is_file_backed()).ok_or(Errno::EIO)?;
+        let mut files = self.mapped_files.borrow_mut();
+        let file = files.entry(path.clone()).or_insert_with(|| File::open(path).ok()).as_ref().ok_or(Errno::EIO)?;
+        file.read_exact_at(bytes, mapping.offset + offset).map_err(|_| Errno::EIO)
+    }
+
+    /// The thread with this tid.
+    pub fn thread(&self, tid: Pid) -> Option<&CoreThread> {
+        self.threads.iter().find(|thread| thread.tid == tid)
+    }
+}
+
+/// A thread of a core file, read like a stopped process but never resumed nor written: the
+/// tracee of the commands in post-mortem mode.
+pub struct CoreTracee {
+    pub core: Rc<CoreFile>,
+    pub tid: Pid,
+}
+
+impl Tracee for CoreTracee {
+    fn read_word(&self, address: u64) -> Result<i64, Errno> {
+        let mut bytes = [0; 8];
+        self.core.read(address, &mut bytes)?;
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    fn write_word(&mut self, _: u64, _: i64) -> Result<(), Errno> {
+        Err(Errno::EROFS)
+    }
+
+    fn getregs(&self) -> Result<user_regs_struct, Errno> {
+        self.core.thread(self.tid).map(|thread| thread.regs).ok_or(Errno::ESRCH)
+    }
+
+    fn setregs(&mut self, _: user_regs_struct) -> Result<(), Errno> {
+        Err(Errno::EROFS)
+    }
+
+    fn cont(&mut self) -> Result<(), Errno> {
+        Err(Errno::ENOTSUP)
+    }
+
+    fn step(&mut self) -> Result<(), Errno> {
+        Err(Errno::ENOTSUP)
+    }
+
+    fn syscall(&mut self) -> Result<(), Errno> {
+        Err(Errno::ENOTSUP)
+    }
+
+    fn wait(&mut self) -> Result<WaitStatus, Errno> {
+        Err(Errno::ECHILD)
+    }
+}
+
+/// Tell where the program of a core file was: its command line, the signal that ended it, and
+/// the innermost frame of the thread that dumped the core.
+pub fn report_core(debugger: &mut Debugger) {
+    let Some(core) = debugger.core.clone() else {
+        return;
+    };
+    crate::json::stop_event(debugger);
+    outln!(debugger, "Core was generated by `{}'.", core.command);
+    match core.signal() {
+        0 => {}
+        signal => outln!(debugger, "Program terminated with signal {}.", siginfo::signal_name(signal)),
+    }
+    if let Ok(frames) = backtrace::backtrace(debugger, 1) {
+        if let Some(frame) = frames.first() {
+            backtrace::print_frame(debugger, 0, frame);
+            if let Some(location) = debugger.source_location(frame.pc) {
+                debugger.print_source_line(&location);
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::maps::{self, MappedFile};
+use crate::memory::read_bulk;
+use crate::step::{self, StepStop};
+use crate::threads::{self, ThreadState};
+use crate::working::{handle_breakpoint, is_breakpoint, remove_breakpoint, restore_original_bytes, set_breakpoint, trap_address};
+use iced_x86::{Decoder, DecoderOptions, FlowControl, OpKind};
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// How `cover start` records the code executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverMode {
+    /// A breakpoint on the start of each basic block of the functions of the program, removed when
+    /// hit: the program runs at full speed once a block has been seen.
+    Blocks,
+    /// Single-step the current thread when continuing, recording every instruction, in the
+    /// libraries too.
+    Step,
+}
+
+/// The coverage recorded by `cover start`.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    /// How the coverage is recorded, `None` when it is not.
+    pub mode: Option<CoverMode>,
+    /// The basic blocks found in the functions of the program: their size and function, by
+    /// runtime address of their start.
+    pub blocks: HashMap<u64, (u64, String)>,
+    /// The blocks whose breakpoint is armed, not entered yet.
+    pub pending: HashSet<u64>,
+    /// The addresses executed: the start of the blocks entered, or each instruction stepped.
+    pub visited: BTreeSet<u64>,
+    /// The files mapped in the program when last seen, for the module table of drcov files.
+    pub modules: Vec<MappedFile>,
+}
+
+/// Find the basic blocks of the code `bytes` of a function at `address`: its start, the targets of
+/// its jumps inside it, and the instructions following a jump, a call or a return. The decoding
+/// stops at the first invalid instruction.
+///
+/// # Returns
+///
+/// The start and size of each block, in address order.
+pub fn basic_blocks(bytes: &[u8], address: u64, arch: Arch) -> Vec<(u64, u64)> {
+    let end = address + bytes.len() as u64;
+    let mut decoder = Decoder::with_ip(arch.bitness(), bytes, address, DecoderOptions::NONE);
+    let mut starts = BTreeSet::from([address]);
+    let mut instructions = HashSet::new();
+    let mut decoded_end = address;
+    for instruction in decoder.iter() {
+        if instruction.is_invalid() {
+            break;
+        }
+        instructions.insert(instruction.ip());
+        decoded_end = instruction.next_ip();
+        match instruction.flow_control() {
+            FlowControl::Next => continue,
+            FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch
+                if matches!(instruction.op0_kind(), OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64) && (address..end).contains(&instruction.near_branch_target()) =>
+            {
+                starts.insert(instruction.near_branch_target());
+            }
+            _ => {}
+        }
+        starts.insert(instruction.next_ip());
+    }
+    // A jump into the middle of an instruction, or past the code decoded, is not a block.
+    let starts: Vec<u64> = starts.into_iter().filter(|start| instructions.contains(start)).collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, start)| (*start, starts.get(index + 1).copied().unwrap_or(decoded_end) - start))
+        .collect()
+}
+
+/// Start recording the coverage, forgetting the one recorded before. With [`CoverMode::Blocks`],
+/// a breakpoint is armed at the start of each basic block of the functions of the program, but
+/// where a breakpoint already is and at the pc, which is recorded as executed.
+///
+/// # Returns
+///
+/// The number of blocks found, none with [`CoverMode::Step`].
+///
+/// # Errors
+///
+/// Returns a message if the program has no function with a size or isn't mapped yet, or a block
+/// can't be armed.
+pub fn start(debugger: &mut Debugger, mode: CoverMode) -> Result<usize, String> {
+    stop(debugger);
+    debugger.coverage = Coverage { mode: Some(mode), ..Coverage::default() };
+    update_modules(debugger);
+    let pc = debugger.registers().map_err(|err| err.to_string())?.pc();
+    if mode == CoverMode::Step {
+        return Ok(0);
+    }
+    let executable = debugger.executable().ok_or("The symbols of the program are not loaded.")?;
+    let bias = executable.bias.ok_or("The program is not mapped yet.")?;
+    let mut functions: Vec<(u64, u64, String)> = executable
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.is_function && symbol.size > 0)
+        .map(|symbol| (symbol.address.wrapping_add(bias), symbol.size, symbol.demangled.clone()))
+        .collect();
+    functions.sort();
+    functions.dedup_by_key(|(address, ..)| *address);
+    if functions.is_empty() {
+        debugger.coverage.mode = None;
+        return Err("The program has no function with a size in its symbol table.".to_string());
+    }
+    for (address, size, name) in functions {
+        let Ok(mut bytes) = read_bulk(debugger.child, address, size as usize) else {
+            continue;
+        };
+        restore_original_bytes(&debugger.breakpoint_table, address, &mut bytes);
+        for (block, size) in basic_blocks(&bytes, address, debugger.arch) {
+            debugger.coverage.blocks.insert(block, (size, name.clone()));
+        }
+    }
+    let mut blocks: Vec<u64> = debugger.coverage.blocks.keys().copied().collect();
+    blocks.sort();
+    for block in blocks {
+        if block == pc {
+            debugger.coverage.visited.insert(block);
+        } else if !is_breakpoint(&debugger.breakpoint_table, block) {
+            set_breakpoint(&mut debugger.breakpoint_table, debugger.thread, block).map_err(|err| format!("Could not arm the block at {:#x}: {}", block, err))?;
+            debugger.coverage.pending.insert(block);
+        }
+    }
+    Ok(debugger.coverage.blocks.len())
+}
+
+/// Stop recording the coverage, removing the breakpoints of the blocks not entered. The coverage
+/// recorded is kept for `cover report` and `cover save`.
+pub fn stop(debugger: &mut Debugger) {
+    for address in debugger.coverage.pending.drain() {
+        // Gone with the process when it has terminated.
+        remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, address).ok();
+    }
+    debugger.coverage.mode = None;
+    update_modules(debugger);
+}
+
+/// Keep the files mapped in the program while it runs, for [`save_drcov`].
+fn update_modules(debugger: &mut Debugger) {
+    if let Ok(mappings) = debugger.read_mappings() {
+        debugger.coverage.modules = maps::mapped_files(&mappings);
+    }
+}
+
+/// Record the block at `address` as entered if its breakpoint is armed, removing it.
+///
+/// # Returns
+///
+/// True if `address` was the breakpoint of a block, which the stepping commands then step over
+/// instead of stopping there.
+pub fn reached(debugger: &mut Debugger, address: u64) -> bool {
+    if !debugger.coverage.pending.remove(&address) {
+        return false;
+    }
+    debugger.coverage.visited.insert(address);
+    remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, address).is_ok()
+}
+
+/// Record the block a thread stopped at, when its breakpoint was hit while the program is
+/// continued, and resume the thread. A breakpoint of the user at the start of a block records it
+/// too, and stops the program.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `tid` - The thread that stopped.
+/// * `status` - How it stopped.
+///
+/// # Returns
+///
+/// `None` if the thread was at the breakpoint of a block and runs again, `status` otherwise.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] or [`DbgError::Memory`] if the thread can't be rewound to the
+/// block and resumed.
+pub fn block_hit(debugger: &mut Debugger, tid: Pid, status: WaitStatus) -> Result<Option<WaitStatus>, DbgError> {
+    if status != WaitStatus::Stopped(tid, Signal::SIGTRAP) || debugger.coverage.mode != Some(CoverMode::Blocks) {
+        return Ok(Some(status));
+    }
+    let mut regs = crate::arch::get_registers(tid).map_err(DbgError::ptrace("read the registers"))?;
+    let address = trap_address(regs.pc());
+    if !debugger.coverage.pending.remove(&address) {
+        if debugger.coverage.blocks.contains_key(&address) && is_breakpoint(&debugger.breakpoint_table, address) {
+            debugger.coverage.visited.insert(address);
+        }
+        return Ok(Some(status));
+    }
+    debugger.coverage.visited.insert(address);
+    remove_breakpoint(&mut debugger.breakpoint_table, tid, address)?;
+    regs.set_pc(address);
+    crate::cache::invalidate();
+    crate::arch::set_registers(tid, regs).map_err(DbgError::ptrace("write the registers"))?;
+    log::debug!("PTRACE_CONT {} after the block at {:#x}", tid, address);
+    crate::cache::invalidate();
+    ptrace::cont(tid, None).map_err(DbgError::ptrace("continue execution"))?;
+    threads::set_state(debugger, tid, ThreadState::Running);
+    Ok(None)
+}
+
+/// Continue the current thread by single-stepping it with [`CoverMode::Step`], recording each
+/// instruction, until it stops at a breakpoint, with a signal, or terminates.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the thread can't be stepped.
+pub fn step_until_stop(debugger: &mut Debugger) -> Result<StepStop, DbgError> {
+    let stop = loop {
+        let pc = crate::arch::get_registers(debugger.thread).map_err(DbgError::ptrace("read the registers"))?.pc();
+        debugger.coverage.visited.insert(pc);
+        let stop = step::single_step(debugger)?;
+        if stop != StepStop::Done {
+            break stop;
+        }
+        let rip = crate::arch::get_registers(debugger.thread).map_err(DbgError::ptrace("read the registers"))?.pc();
+        if is_breakpoint(&debugger.breakpoint_table, rip) {
+            handle_breakpoint(debugger, rip);
+            break StepStop::Breakpoint(rip);
+        }
+    };
+    update_modules(debugger);
+    Ok(stop)
+}
+
+/// The number of addresses executed in each function, with the number of its blocks when they are
+/// known, sorted by function name.
+pub fn function_hits(debugger: &mut Debugger) -> Vec<(String, usize, Option<usize>)> {
+    let mut functions: BTreeMap<String, (usize, Option<usize>)> = BTreeMap::new();
+    for (_, name) in debugger.coverage.blocks.values() {
+        *functions.entry(name.clone()).or_default().1.get_or_insert(0) += 1;
+    }
+    let visited: Vec<u64> = debugger.coverage.visited.iter().copied().collect();
+    for address in visited {
+        let name = match debugger.coverage.blocks.get(&address) {
+            Some((_, name)) => name.clone(),
+            None => match debugger.symbolize(address) {
+                Some((name, ..)) => name,
+                // Outside of any symbol, by the file it is in.
+                None => match debugger.coverage.modules.iter().find(|module| (module.start..module.end).contains(&address)) {
+                    Some(module) => format!("?? in {}", module.path.rsplit('/').next().unwrap_or_default()),
+                    None => "??".to_string(),
+                },
+            },
+        };
+        functions.entry(name).or_default().0 += 1;
+    }
+    functions.into_iter().map(|(name, (hits, blocks))| (name, hits, blocks)).collect()
+}
+
+/// Write the addresses executed to `path`, one per line in hexadecimal.
+///
+/// # Errors
+///
+/// Returns the error of writing the file.
+pub fn save_addresses(coverage: &Coverage, path: &str) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for address in &coverage.visited {
+        writeln!(file, "{:#x}", address)?;
+    }
+    file.flush()
+}
+
+/// Write the coverage to `path` in the drcov format of DynamoRIO, read by coverage viewers such
+/// as lighthouse: the table of the files mapped, then each block entered (or instruction stepped)
+/// as its offset in its file, its size and the index of its file.
+///
+/// # Errors
+///
+/// Returns the error of writing the file.
+pub fn save_drcov(coverage: &Coverage, path: &str) -> std::io::Result<()> {
+    let mut entries = Vec::new();
+    for address in &coverage.visited {
+        let Some(module) = coverage.modules.iter().position(|module| (module.start..module.end).contains(address)) else {
+            continue;
+        };
+        let base = module_base(&coverage.modules[module]);
+        // The size of an instruction stepped is not kept, 1 marks its first byte.
+        let size = coverage.blocks.get(address).map_or(1, |(size, _)| *size);
+        entries.push(((address - base) as u32, size.min(u16::MAX as u64) as u16, module as u16));
+    }
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "DRCOV VERSION: 2")?;
+    writeln!(file, "DRCOV FLAVOR: rustdbg")?;
+    writeln!(file, "Module Table: version 2, count {}", coverage.modules.len())?;
+    writeln!(file, "Columns: id, base, end, entry, checksum, timestamp, path")?;
+    for (id, module) in coverage.modules.iter().enumerate() {
+        writeln!(file, "{:3}, {:#018x}, {:#018x}, 0x0000000000000000, 0x00000000, 0x00000000, {}", id, module_base(module), module.end, module.path)?;
+    }
+    writeln!(file, "BB Table: {} bbs", entries.len())?;
+    for (offset, size, module) in entries {
+        file.write_all(&offset.to_le_bytes())?;
+        file.write_all(&size.to_le_bytes())?;
+        file.write_all(&module.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+/// The address the file is mapped at, that the offsets of its blocks are relative to.
+fn module_base(module: &MappedFile) -> u64 {
+    module.base.unwrap_or(module.start)
+}
+
+/// Run the `cover start [step]`, `cover stop`, `cover report` and `cover save [--drcov] <file>`
+/// commands.
+///
+/// # Returns
+///
+/// False if the command failed.
+pub fn cover_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match &args[1..] {
+        ["start"] | ["start", "step"] => {
+            let mode = if args.len() == 3 { CoverMode::Step } else { CoverMode::Blocks };
+            if let Err(err) = debugger.check_live() {
+                errln!(debugger, "{}", err);
+                return false;
+            }
+            match start(debugger, mode) {
+                Ok(_) if mode == CoverMode::Step => {
+                    warnln!(debugger, "c single-steps the current thread while recording, which is very slow.");
+                    outln!(debugger, "Recording every instruction executed.");
+                }
+                Ok(count) => {
+                    let armed = debugger.coverage.pending.len();
+                    json::set_data(debugger, |_| json!({ "blocks": count, "armed": armed }));
+                    outln!(debugger, "Recording the {} basic blocks of the program.", count);
+                }
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    return false;
+                }
+            }
+        }
+        ["stop"] => {
+            stop(debugger);
+            let count = debugger.coverage.visited.len();
+            json::set_data(debugger, |_| json!({ "visited": count }));
+            outln!(debugger, "Coverage recording stopped, {} addresses executed.", count);
+        }
+        ["report"] => {
+            let functions = function_hits(debugger);
+            json::set_data(debugger, |_| {
+                let functions: Vec<_> = functions.iter().map(|(name, hits, blocks)| json!({ "function": name, "hits": hits, "blocks": blocks })).collect();
+                json!({ "functions": functions })
+            });
+            if functions.is_empty() {
+                outln!(debugger, "No coverage recorded.");
+                return true;
+            }
+            outln!(debugger, "{:<8}  {:<8}  Function", "Hits", "Blocks");
+            for (name, hits, blocks) in functions {
+                let blocks = blocks.map_or_else(|| "-".to_string(), |blocks| blocks.to_string());
+                outln!(debugger, "{:<8}  {:<8}  {}", hits, blocks, name);
+            }
+        }
+        ["save", path] | ["save", "--drcov", path] => {
+            if debugger.coverage.mode.is_some() {
+                update_modules(debugger);
+            }
+            let drcov = args.len() == 4;
+            let saved = if drcov { save_drcov(&debugger.coverage, path) } else { save_addresses(&debugger.coverage, path) };
+            if let Err(err) = saved {
+                errln!(debugger, "Could not write {}: {}", path, err);
+                return false;
+            }
+            outln!(debugger, "{} addresses written to {}.", debugger.coverage.visited.len(), path);
+        }
+        _ => {
+            errln!(debugger, "Usage: cover start [step] | cover stop | cover report | cover save [--drcov] <file>");
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,255 @@
+use crate::arch::{Arch, Registers};
+use crate::backtrace;
+use crate::debugger::Debugger;
+use crate::disasm::{current_instruction, read_code};
+use crate::siginfo::{self, SI_KERNEL};
+use crate::working::show_registers;
+#[cfg(target_arch = "x86_64")]
+use iced_x86::{Decoder, DecoderOptions, Instruction, InstructionInfoFactory, OpAccess, Register, UsedMemory};
+use nix::libc::user_regs_struct;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+
+/// Longest possible x86_64 instruction.
+const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// A memory access made by an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The memory operand, as in `[rax+0x8]`.
+    pub operand: String,
+    /// "read", "write" or "read/write".
+    pub kind: &'static str,
+    /// The address accessed with the current register values.
+    pub address: u64,
+    /// The number of bytes accessed.
+    pub size: u64,
+    /// The registers used to compute the address, with their values.
+    pub registers: Vec<(String, u64)>,
+}
+
+/// Returns true for the signals raised by a faulting instruction.
+pub fn is_fault(signal: Signal) -> bool {
+    matches!(signal, Signal::SIGSEGV | Signal::SIGBUS | Signal::SIGILL | Signal::SIGFPE)
+}
+
+fn signal_description(signal: Signal) -> &'static str {
+    match signal {
+        Signal::SIGSEGV => "Segmentation fault",
+        Signal::SIGBUS => "Bus error",
+        Signal::SIGILL => "Illegal instruction",
+        Signal::SIGFPE => "Arithmetic exception",
+        _ => "Fatal signal",
+    }
+}
+
+/// Describe the `si_code` of a fault, as documented in sigaction(2).
+fn code_description(signal: Signal, code: i32) -> Option<&'static str> {
+    match code {
+        SI_KERNEL => Some("general protection fault, the kernel does not report the address"),
+        _ => siginfo::describe_code(signal as i32, code).map(|(_, description)| description),
+    }
+}
+
+/// The value of a general purpose register, or of a sub-register like `eax`.
+#[cfg(target_arch = "x86_64")]
+fn register_value(regs: &user_regs_struct, register: Register) -> Option<u64> {
+    let value = match register.full_register() {
+        Register::RAX => regs.rax,
+        Register::RBX => regs.rbx,
+        Register::RCX => regs.rcx,
+        Register::RDX => regs.rdx,
+        Register::RSI => regs.rsi,
+        Register::RDI => regs.rdi,
+        Register::RSP => regs.sp(),
+        Register::RBP => regs.frame_pointer(),
+        Register::R8 => regs.r8,
+        Register::R9 => regs.r9,
+        Register::R10 => regs.r10,
+        Register::R11 => regs.r11,
+        Register::R12 => regs.r12,
+        Register::R13 => regs.r13,
+        Register::R14 => regs.r14,
+        Register::R15 => regs.r15,
+        Register::RIP => regs.pc(),
+        _ => return None,
+    };
+    match register.size() {
+        8 => Some(value),
+        size => Some(value & ((1 << (size * 8)) - 1)),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn register_name(register: Register) -> String {
+    format!("{:?}", register).to_lowercase()
+}
+
+#[cfg(target_arch = "x86_64")]
+fn operand_text(memory: &UsedMemory) -> String {
+    let mut text = String::new();
+    if matches!(memory.segment(), Register::FS | Register::GS) {
+        text.push_str(&format!("{}:", register_name(memory.segment())));
+    }
+    let mut terms = Vec::new();
+    if memory.base() != Register::None && memory.base() != Register::RIP {
+        terms.push(register_name(memory.base()));
+    }
+    if memory.index() != Register::None {
+        terms.push(match memory.scale() {
+            1 => register_name(memory.index()),
+            scale => format!("{}*{}", register_name(memory.index()), scale),
+        });
+    }
+    let displacement = memory.displacement() as i64;
+    let mut inner = terms.join("+");
+    if terms.is_empty() {
+        // An absolute address, or a rip-relative one which is already resolved.
+        inner = format!("{:#x}", memory.displacement());
+    } else if displacement < 0 {
+        inner.push_str(&format!("-{:#x}", displacement.unsigned_abs()));
+    } else if displacement > 0 {
+        inner.push_str(&format!("+{:#x}", displacement));
+    }
+    text.push_str(&format!("[{}]", inner));
+    text
+}
+
+/// Decode the instruction in `bytes` and compute the memory it accesses with the registers `regs`.
+///
+/// Implicit accesses are included, such as the stack slot written by a `push` or a `call`.
+///
+/// # Arguments
+///
+/// * `bytes` - The code of the instruction.
+/// * `regs` - The registers of the thread executing it, `rip` being its address.
+/// * `arch` - The architecture of the code, 64-bit or 32-bit.
+#[cfg(target_arch = "x86_64")]
+pub fn memory_accesses(bytes: &[u8], regs: &user_regs_struct, arch: Arch) -> Vec<MemoryAccess> {
+    let mut decoder = Decoder::with_ip(arch.bitness(), bytes, regs.pc(), DecoderOptions::NONE);
+    let mut instruction = Instruction::default();
+    decoder.decode_out(&mut instruction);
+    if instruction.is_invalid() {
+        return Vec::new();
+    }
+    let mut factory = InstructionInfoFactory::new();
+    let info = factory.info(&instruction);
+    let mut accesses = Vec::new();
+    for memory in info.used_memory() {
+        let kind = match memory.access() {
+            OpAccess::Read | OpAccess::CondRead => "read",
+            OpAccess::Write | OpAccess::CondWrite => "write",
+            OpAccess::ReadWrite | OpAccess::ReadCondWrite => "read/write",
+            _ => continue,
+        };
+        let address = memory.virtual_address(0, |register, _, _| match register {
+            Register::FS => Some(regs.fs_base),
+            Register::GS => Some(regs.gs_base),
+            Register::ES | Register::CS | Register::SS | Register::DS => Some(0),
+            // The displacement of a rip-relative operand is already the absolute address.
+            Register::RIP => Some(0),
+            _ => register_value(regs, register),
+        });
+        let Some(address) = address else {
+            continue;
+        };
+        let registers = [memory.base(), memory.index()]
+            .into_iter()
+            .filter(|register| *register != Register::None && *register != Register::RIP)
+            .filter_map(|register| Some((register_name(register), register_value(regs, register)?)))
+            .collect();
+        accesses.push(MemoryAccess {
+            operand: operand_text(memory),
+            kind,
+            address,
+            size: memory.memory_size().size() as u64,
+            registers,
+        });
+    }
+    accesses
+}
+
+/// The memory accesses of an instruction, none on aarch64, whose code is not decoded.
+#[cfg(target_arch = "aarch64")]
+pub fn memory_accesses(_bytes: &[u8], _regs: &user_regs_struct, _arch: Arch) -> Vec<MemoryAccess> {
+    Vec::new()
+}
+
+/// Print a crash report if the child is stopped by a fault (SIGSEGV, SIGBUS, SIGILL, SIGFPE).
+///
+/// The report shows the faulting address given by the kernel, the faulting instruction, the memory
+/// it accesses (marking the access to the faulting address), the registers and a backtrace. A
+/// crash is reported once: stopping again at the same pc for the same fault, after `c` retried
+/// the instruction, prints nothing.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `signal` - The signal the child is stopped with.
+///
+/// # Returns
+///
+/// True if a report was printed.
+pub fn report_crash(debugger: &mut Debugger, signal: Signal) -> bool {
+    if !is_fault(signal) {
+        return false;
+    }
+    let child = debugger.thread;
+    let (Ok(siginfo), Ok(regs)) = (ptrace::getsiginfo(child), crate::arch::get_registers(child)) else {
+        return false;
+    };
+    let fault = unsafe { siginfo.si_addr() } as u64;
+    let code = siginfo.si_code;
+    if debugger.last_crash == Some((signal, fault, regs.pc())) {
+        return false;
+    }
+    debugger.last_crash = Some((signal, fault, regs.pc()));
+
+    outln!(debugger, "Crash report: {:?} ({})", signal, signal_description(signal));
+    match code_description(signal, code) {
+        Some(description) if code == SI_KERNEL => outln!(debugger, "Cause: {}", description),
+        Some(description) => outln!(debugger, "Faulting address: {:#x} ({})", fault, description),
+        None => outln!(debugger, "Faulting address: {:#x} (si_code {})", fault, code),
+    }
+    if debugger.source_location(regs.pc()).is_some() {
+        debugger.print_stop_location(regs.pc());
+    }
+    match current_instruction(debugger, regs.pc()) {
+        Some(line) => outln!(debugger, "Faulting instruction:\n{}", line),
+        None => outln!(debugger, "Faulting instruction: <cannot read the code at {:#x}>", regs.pc()),
+    }
+    if matches!(signal, Signal::SIGSEGV | Signal::SIGBUS) {
+        let bytes = read_code(&child, &debugger.breakpoint_table, regs.pc(), MAX_INSTRUCTION_LEN).unwrap_or_default();
+        let accesses = memory_accesses(&bytes, &regs, debugger.arch);
+        // Without an address from the kernel, a single access is the culprit.
+        let culprit = accesses
+            .iter()
+            .position(|access| fault >= access.address && fault < access.address + access.size.max(1))
+            .or_else(|| (code == SI_KERNEL && accesses.len() == 1).then_some(0));
+        for (index, access) in accesses.iter().enumerate() {
+            let registers: Vec<String> = access.registers.iter().map(|(name, value)| format!("{} = {:#x}", name, value)).collect();
+            let mut line = format!("  {} {} = {:#x}", access.kind, access.operand, access.address);
+            if !registers.is_empty() {
+                line.push_str(&format!(" ({})", registers.join(", ")));
+            }
+            if Some(index) == culprit {
+                line.push_str(" <- faulting access");
+            }
+            outln!(debugger, "{}", line);
+        }
+    }
+    if let Err(err) = show_registers(debugger) {
+        errln!(debugger, "{}", err);
+    }
+    outln!(debugger, "Backtrace:");
+    let limit = debugger.backtrace_limit;
+    match backtrace::backtrace(debugger, limit) {
+        Ok(frames) => {
+            for (index, frame) in frames.iter().enumerate() {
+                backtrace::print_frame(debugger, index, frame);
+            }
+        }
+        Err(err) => errln!(debugger, "{}", err),
+    }
+    true
+}
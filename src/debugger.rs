@@ -0,0 +1,1138 @@
+use crate::arch::Arch;
+use crate::backtrace::{self, Frame};
+use crate::cache::{self, CachedTracee, StopCache};
+use crate::corefile::{CoreFile, CoreTracee};
+use crate::define::Definition;
+use crate::debuginfo::{DebugInfo, FunctionFrame, SourceLocation};
+use crate::disasm::DisassemblyFlavor;
+use crate::error::DbgError;
+use crate::inferior::{self, Capture, ChildStdio, InferiorTty};
+use crate::inject;
+use crate::json::{self, JsonState};
+use crate::display::Display;
+use crate::label::{self, Label};
+use crate::launch;
+use crate::location::{self, Location};
+use crate::coverage::{self, CoverMode, Coverage};
+use crate::antidebug::{self, AntiDebug};
+use crate::ltrace::{self, LibraryTracer};
+use crate::pagewatch::RegionWatches;
+use crate::watch::{self, Watchpoints};
+use crate::options::Config;
+use crate::maps::{self, MapEntry};
+use crate::memory;
+use crate::output::{Output, Style, Terminal};
+use crate::procfs::{self, FdTracking};
+use crate::prompt;
+use crate::record::InstructionRecord;
+use crate::sandbox;
+use crate::scripting::Scripting;
+use crate::snapshot::SnapshotStore;
+use crate::script::{AutoLoadLocal, ScriptOnError};
+use crate::session::Session;
+use crate::sharedlib::{self, SharedLibrary};
+use crate::siginfo::{self, SigInfo};
+use crate::state::{InferiorState, StopReason};
+use crate::source;
+use crate::symbols::{same_file, ObjectSymbols, SymbolError, SymbolTable};
+use crate::tracee::Tracee;
+use crate::threads::{self, Thread, ThreadState};
+use crate::transcript::Transcript;
+use crate::unwind::{CfiModule, UnwindRegisters};
+use crate::step::{self, StepStop};
+use crate::working::{self, is_breakpoint, BreakpointTable};
+use nix::libc::user_regs_struct;
+use nix::sys::signal::Signal;
+use nix::unistd;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::rc::Rc;
+
+/// State of a debugging session.
+pub struct Debugger {
+    /// The process ID (Pid) of the child being debugged.
+    pub child: unistd::Pid,
+    /// The current thread: the one that stopped last, whose registers the commands read and which
+    /// the stepping commands step. The main thread, whose tid is the pid, at first.
+    pub thread: unistd::Pid,
+    /// The threads of the child, in the order they were created.
+    pub threads: Vec<Thread>,
+    /// The number of the next thread created.
+    pub next_thread_number: usize,
+    /// `set non-stop`: a thread stopping stops alone, the others keep running, and `c` resumes the
+    /// current thread only.
+    pub non_stop: bool,
+    /// Path of the debugged program, as given on the command line.
+    pub program_path: String,
+    /// The architecture of the program, read from its executable when it is started.
+    pub arch: Arch,
+    /// The arguments the program is started with, again by `restart`.
+    pub program_args: Vec<String>,
+    /// `--no-aslr`: the program is started with address space randomization off.
+    pub no_aslr: bool,
+    /// `set inferior-tty` and `tty`: where the standard streams of the program go when it is
+    /// started again.
+    pub inferior_tty: InferiorTty,
+    /// The standard output and error of the program, when they are captured.
+    pub capture: Option<Capture>,
+    /// `run < file` and `set args`: the file the program reads as its standard input.
+    pub stdin_file: Option<String>,
+    /// The end of the standard input of the program written by `input`, when it is a pipe.
+    pub program_input: Option<File>,
+    /// `set cwd`: the directory the program is started in, the one of the debugger when `None`.
+    pub cwd: Option<String>,
+    /// `set env`: the environment the program is started with.
+    pub environment: sandbox::Environment,
+    /// `set rlimit`: the resource limits the program is started with, in the order of
+    /// [`sandbox::RESOURCES`].
+    pub rlimits: Vec<sandbox::ResourceLimit>,
+    /// Set by `set args`, `set cwd`, `set env` and `set rlimit` once the program is started:
+    /// `run` starts it again with them.
+    pub launch_changed: bool,
+    /// True if the process was attached with `--pid`: it is detached instead of killed.
+    pub attached: bool,
+    /// Symbols of the main executable and of the shared libraries loaded so far.
+    pub symbols: SymbolTable,
+    /// DWARF line information of the main executable, if it has some.
+    pub debug_info: Option<DebugInfo>,
+    /// `set substitute-path` rules used to find source files of programs built elsewhere.
+    pub substitute_paths: Vec<(String, String)>,
+    /// Maximum number of frames shown by `bt`.
+    pub backtrace_limit: usize,
+    /// Maximum number of inlined functions shown for each stack frame.
+    pub inline_depth: usize,
+    /// `set show-instruction`: show the instruction at the pc after breakpoint hits too, not only
+    /// after steps.
+    pub show_instruction: bool,
+    /// `set stop-summary`: print the line of `where` at each stop.
+    pub stop_summary: bool,
+    /// `set run-status interval`: the seconds between the lines showing what the program does
+    /// while `c` waits for it, 0 for none.
+    pub run_status_interval: u64,
+    /// The anti-debugging checks watched for, and `set anti-anti-debug`.
+    pub anti_debug: AntiDebug,
+    /// `set track-fds`: the descriptors of the program compared from stop to stop.
+    pub fd_tracking: FdTracking,
+    /// The variables watched with `watch` and `awatch`.
+    pub watchpoints: Watchpoints,
+    /// The regions of memory watched with `watch-region`.
+    pub region_watches: RegionWatches,
+    /// Syntax of the disassembled instructions.
+    pub disassembly_flavor: DisassemblyFlavor,
+    /// Stack frame selected with `frame`, `up` and `down`, 0 being the innermost one.
+    pub selected_frame: usize,
+    /// Address of each breakpoint set with `b`, breakpoint `n` being at index `n - 1`.
+    pub breakpoints: Vec<u64>,
+    /// The breakpoints armed in the process, of the user or of the debugger, with the original
+    /// bytes they replaced.
+    pub breakpoint_table: BreakpointTable,
+    /// The location given to `b` for each breakpoint, to set them again after `restart`.
+    pub breakpoint_locations: Vec<String>,
+    /// The breakpoints set by each `rbreak`, by number, group `n` being at index `n - 1`.
+    pub breakpoint_groups: Vec<Vec<usize>>,
+    /// The breakpoints removed with `delete`, or dropped by an exec as their address was in
+    /// the previous program, which `restart` does not set again.
+    pub deleted_breakpoints: BTreeSet<usize>,
+    /// The breakpoints whose function or line is not in the program executed by the program, set
+    /// again by the next exec or `restart`.
+    pub pending_breakpoints: BTreeSet<usize>,
+    /// Breakpoints set on a function name, with that name, to show the arguments when one is hit.
+    pub function_breakpoints: HashMap<u64, String>,
+    /// The breakpoint `start` runs to, on the main function, removed at the next stop.
+    pub temporary_breakpoint: Option<u64>,
+    /// Library calls traced with `ltrace on`.
+    pub ltrace: LibraryTracer,
+    /// The code executed, recorded with `cover start`.
+    pub coverage: Coverage,
+    /// The instructions stepped, recorded with `record on`.
+    pub record: InstructionRecord,
+    /// The snapshots taken with `snapshot` and `set snapshot-interval`, and the stops since them.
+    pub snapshots: SnapshotStore,
+    /// The engine of `script eval` and `script file`, and the stop hooks of the scripts.
+    pub scripting: Scripting,
+    /// `set script-on-error`: whether a failed command stops the script running it.
+    pub script_on_error: ScriptOnError,
+    /// `set auto-load-local`: whether the `.rustdbginit` of the current directory is run at startup.
+    pub auto_load_local: AutoLoadLocal,
+    /// Number of scripts being run, sourced from each other.
+    pub script_depth: usize,
+    /// The commands of each alias defined with `alias`, by name.
+    pub aliases: BTreeMap<String, String>,
+    /// The commands defined with `define`, with the lines of their bodies, by name.
+    pub user_commands: BTreeMap<String, Vec<String>>,
+    /// The user command being defined, collecting the lines until `end`.
+    pub definition: Option<Definition>,
+    /// How many user commands are running, each inside the previous one.
+    pub user_command_depth: usize,
+    /// The convenience variables set with `set $<name> = <value>`, by name without the `$`.
+    pub variables: BTreeMap<String, u64>,
+    /// The addresses named with `label`, by name.
+    pub labels: BTreeMap<String, Label>,
+    /// The expressions shown at every stop, added with `display`, by number.
+    pub displays: BTreeMap<usize, Display>,
+    /// The number of displays added, the last number given.
+    pub display_count: usize,
+    /// The commands entered since the start, for `session save`.
+    pub session: Session,
+    /// `--batch`: no prompt nor echo of the commands run, only their output.
+    pub batch: bool,
+    /// `set confirm`: whether `quit`, `restart`, `define`, `rbreak` and `set mem` ask first.
+    pub confirm: bool,
+    /// True while a command given with a `!` suffix (`quit!`) runs, which then does not ask.
+    pub forced: bool,
+    /// True while the program is stopped at the entry of a system call by `syscall`, so that the
+    /// next system call stop is its exit.
+    pub in_syscall: bool,
+    /// Set by `quit`: the session is over, the program killed or detached, and the caller exits.
+    pub quit_requested: bool,
+    /// Set with `quit_requested` by an `assert` failing in batch mode, for the caller to exit with
+    /// the status 3.
+    pub assertion_failed: bool,
+    /// Whether the program is stopped, running or terminated, updated as it is resumed and stops.
+    pub state: InferiorState,
+    /// `set build-command`: the command run by `rebuild`.
+    pub build_command: String,
+    /// True once `rebuild` changed the program, until `restart` loads its symbols again.
+    pub symbols_stale: bool,
+    /// Where the commands print their output.
+    pub output: Box<dyn Output>,
+    /// The copy of the commands and their output to a file (`set logging`).
+    pub transcript: Transcript,
+    /// The records of `--interpreter json`, `None` in the usual text mode.
+    pub json: Option<JsonState>,
+    /// The template of the prompt (`set prompt`).
+    pub prompt: String,
+    /// The values of the registers last shown by `r`, to highlight the ones that changed.
+    pub shown_registers: Option<Vec<u64>>,
+    /// The signal, faulting address and pc of the last crash reported, to report each crash once.
+    pub last_crash: Option<(Signal, u64, u64)>,
+    /// The details of the signal the program last stopped by, shown by `info signal`.
+    pub last_signal: Option<SigInfo>,
+    /// The memory mappings of the child, read again at each stop since they change as the program
+    /// maps and unmaps memory.
+    pub mappings: Vec<MapEntry>,
+    /// The registers and memory read during the current stop.
+    pub(crate) cache: Rc<StopCache>,
+    /// Call frame information of each module, parsed on first use.
+    cfi_modules: HashMap<String, Option<CfiModule>>,
+    /// The core file read with `--core`, instead of a process: `child` is then the process it was
+    /// taken from, which is not traced.
+    pub(crate) core: Option<Rc<CoreFile>>,
+}
+
+impl Debugger {
+    /// Create a session for `child`, started or attached as `config` says, loading the symbols of
+    /// the main executable.
+    ///
+    /// # Arguments
+    ///
+    /// * `child` - The process ID (Pid) of the child being debugged.
+    /// * `config` - The options of the session. The startup actions are run by the caller.
+    pub fn new(child: unistd::Pid, config: &Config) -> Debugger {
+        Debugger::create(child, config, None)
+    }
+
+    /// Create a session reading the core file of `config`, whose threads and memory are read
+    /// instead of a process: it can't be resumed nor changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::Core`] if the core file can't be read.
+    pub fn open_core(config: &Config) -> Result<Debugger, DbgError> {
+        let path = config.core.as_deref().ok_or(DbgError::Core { path: String::new(), reason: "no core file given".to_string() })?;
+        let core = CoreFile::open(path)?;
+        Ok(Debugger::create(core.pid, config, Some(Rc::new(core))))
+    }
+
+    fn create(child: unistd::Pid, config: &Config, core: Option<Rc<CoreFile>>) -> Debugger {
+        let program_path = config.program_path.as_str();
+        let mut output: Box<dyn Output> = Box::new(Terminal::new(true));
+        let mut symbols = SymbolTable::default();
+        if let Err(err) = symbols.load(program_path, None) {
+            output.write_styled_line(&format!("Could not load symbols: {}", err), Style::Error);
+        }
+        let debug_info = executable_debug_info(&symbols, program_path);
+        let tids = match &core {
+            Some(core) => core.threads.iter().map(|thread| thread.tid).collect(),
+            None => threads::tasks(child),
+        };
+        for tid in tids.iter().filter(|_| core.is_none()) {
+            if let Err(err) = threads::trace_events(*tid) {
+                output.write_styled_line(&format!("Could not trace the threads: {}", err), Style::Error);
+            }
+        }
+        // The thread that dumped the core comes first.
+        let thread = tids.first().copied().filter(|_| core.is_some()).unwrap_or(child);
+        let state = match (&core, config.pid) {
+            (Some(_), _) => InferiorState::Stopped { reason: StopReason::Core },
+            // An attached process was already running.
+            (None, Some(_)) => InferiorState::Stopped { reason: StopReason::Attached },
+            (None, None) => InferiorState::NotStarted,
+        };
+        let mappings = match &core {
+            Some(core) => core.mappings.clone(),
+            None => maps::read_maps(child).unwrap_or_default(),
+        };
+        Debugger {
+            child,
+            thread,
+            next_thread_number: tids.len() + 1,
+            non_stop: false,
+            threads: (1..).zip(tids).map(|(number, tid)| Thread::stopped(number, tid)).collect(),
+            program_path: program_path.to_string(),
+            arch: Arch::of_executable(program_path),
+            program_args: config.program_args.clone(),
+            no_aslr: config.no_aslr,
+            inferior_tty: config.inferior_tty.clone(),
+            capture: None,
+            stdin_file: None,
+            program_input: None,
+            cwd: None,
+            environment: sandbox::Environment::default(),
+            rlimits: Vec::new(),
+            launch_changed: false,
+            attached: config.pid.is_some(),
+            symbols,
+            debug_info,
+            substitute_paths: Vec::new(),
+            backtrace_limit: backtrace::DEFAULT_BACKTRACE_LIMIT,
+            inline_depth: backtrace::DEFAULT_INLINE_DEPTH,
+            show_instruction: false,
+            stop_summary: true,
+            run_status_interval: 0,
+            anti_debug: AntiDebug::default(),
+            fd_tracking: FdTracking::default(),
+            watchpoints: Watchpoints::default(),
+            region_watches: RegionWatches::default(),
+            disassembly_flavor: DisassemblyFlavor::default(),
+            selected_frame: 0,
+            breakpoints: Vec::new(),
+            breakpoint_table: BreakpointTable::default(),
+            breakpoint_locations: Vec::new(),
+            breakpoint_groups: Vec::new(),
+            deleted_breakpoints: BTreeSet::new(),
+            pending_breakpoints: BTreeSet::new(),
+            function_breakpoints: HashMap::new(),
+            temporary_breakpoint: None,
+            ltrace: LibraryTracer::default(),
+            coverage: Coverage::default(),
+            record: InstructionRecord::default(),
+            snapshots: SnapshotStore::default(),
+            scripting: Scripting::default(),
+            script_on_error: ScriptOnError::default(),
+            auto_load_local: AutoLoadLocal::default(),
+            script_depth: 0,
+            aliases: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            displays: BTreeMap::new(),
+            display_count: 0,
+            session: Session { program: program_path.to_string(), args: config.program_args.clone(), no_aslr: config.no_aslr, entries: Vec::new() },
+            user_commands: BTreeMap::new(),
+            definition: None,
+            user_command_depth: 0,
+            batch: config.batch,
+            confirm: true,
+            forced: false,
+            in_syscall: false,
+            quit_requested: false,
+            assertion_failed: false,
+            state,
+            build_command: String::new(),
+            symbols_stale: false,
+            output,
+            transcript: Transcript::default(),
+            json: None,
+            prompt: prompt::DEFAULT_PROMPT_TEMPLATE.to_string(),
+            shown_registers: None,
+            last_crash: None,
+            last_signal: None,
+            mappings,
+            cache: Rc::default(),
+            cfi_modules: HashMap::new(),
+            core,
+        }
+    }
+
+    /// Start `program_path` with `args`, traced and stopped before its first instruction, and
+    /// create a session debugging it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::Exec`] if the program cannot be executed, or another error if it cannot
+    /// be started.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let mut debugger = rustdbg::debugger::Debugger::launch("/bin/ls", &["-l".to_string()])?;
+    /// debugger.cont()?;
+    /// # Ok::<(), rustdbg::error::DbgError>(())
+    /// ```
+    pub fn launch(program_path: &str, args: &[String]) -> Result<Debugger, DbgError> {
+        // The program writes to the standard streams of the caller.
+        let spec = launch::LaunchSpec { program_path: program_path.to_string(), args: args.to_vec(), ..launch::LaunchSpec::default() };
+        let child = launch::launch(&spec, &ChildStdio::default())?;
+        let config = Config { program_path: program_path.to_string(), program_args: args.to_vec(), inferior_tty: InferiorTty::Inherit, ..Config::default() };
+        Ok(Debugger::new(child, &config))
+    }
+
+    /// Attach to the running process `pid`, stopped until it is resumed, and create a session
+    /// debugging it. [`launch::release`] detaches from it when done.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::NoProcess`] if there is no such process, or another error if it cannot
+    /// be traced.
+    pub fn attach(pid: i32) -> Result<Debugger, DbgError> {
+        let (child, program_path) = launch::attach(pid)?;
+        let config = Config { program_path, pid: Some(pid), ..Config::default() };
+        Ok(Debugger::new(child, &config))
+    }
+
+    /// Exit the debugger with `status`, closing the transcript and the JSON records first.
+    pub fn exit(&mut self, status: i32) -> ! {
+        inferior::flush(self);
+        json::exit(self);
+        self.transcript.stop();
+        std::process::exit(status);
+    }
+
+    /// Returns true if the program has been started and has not terminated, so that killing it
+    /// loses its state.
+    pub fn has_live_process(&self) -> bool {
+        self.core.is_none() && self.state != InferiorState::NotStarted && self.state.is_alive()
+    }
+
+    /// The current thread, whose registers and memory the commands read: the thread of the process,
+    /// or the thread saved in the core file.
+    pub(crate) fn tracee(&self) -> Rc<dyn Tracee> {
+        self.tracee_of(self.thread)
+    }
+
+    /// The thread `tid` of the process, its registers and memory read once per stop, or the thread
+    /// of the core file.
+    pub(crate) fn tracee_of(&self, tid: unistd::Pid) -> Rc<dyn Tracee> {
+        match &self.core {
+            Some(core) => Rc::new(CoreTracee { core: core.clone(), tid }),
+            None => Rc::new(CachedTracee { tid, tracee: tid, cache: self.cache.clone() }),
+        }
+    }
+
+    /// Read the memory mappings of the process, or the ones saved in the core file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `/proc/<pid>/maps` can't be read, as once the process is gone.
+    pub fn read_mappings(&self) -> std::io::Result<Vec<MapEntry>> {
+        match &self.core {
+            Some(core) => Ok(core.mappings.clone()),
+            None => maps::read_maps(self.child),
+        }
+    }
+
+    /// The exit status of the program once it has terminated, 128 + the signal if it was killed.
+    pub fn exit_status(&self) -> Option<i32> {
+        self.state.exit_status()
+    }
+
+    /// How `restart` starts the program: with the arguments, standard input, directory,
+    /// environment and resource limits of the settings.
+    pub fn launch_spec(&self) -> launch::LaunchSpec {
+        launch::LaunchSpec {
+            program_path: self.program_path.clone(),
+            args: self.program_args.clone(),
+            stdin_file: self.stdin_file.clone(),
+            cwd: self.cwd.clone(),
+            no_aslr: self.no_aslr,
+            env: self.environment.clone(),
+            rlimits: self.rlimits.clone(),
+        }
+    }
+
+    /// Debug a new process of the program, started by `restart`. Its objects are mapped at new
+    /// addresses, so their symbols are loaded again, and the state of the previous process is
+    /// forgotten.
+    pub fn start_process(&mut self, child: unistd::Pid) {
+        self.child = child;
+        self.thread = child;
+        self.threads = vec![Thread::stopped(1, child)];
+        self.next_thread_number = 2;
+        if let Err(err) = threads::trace_events(child) {
+            errln!(self, "Could not trace the threads: {}", err);
+        }
+        self.attached = false;
+        self.launch_changed = false;
+        self.state = InferiorState::NotStarted;
+        self.forget_image();
+        self.load_program();
+    }
+
+    /// Debug the new program the process executed (`execve`), at `path`, which `restart` starts
+    /// from then on. The thread that executed it is the only one left, with the pid of the process.
+    pub fn exec_process(&mut self, path: &str) {
+        self.program_path = path.to_string();
+        self.thread = self.child;
+        self.threads = vec![Thread::stopped(1, self.child)];
+        self.next_thread_number = 2;
+        self.temporary_breakpoint = None;
+        self.forget_image();
+        self.load_program();
+    }
+
+    /// Forget what was known of the memory image of the process: the stop, the library calls
+    /// traced, the blocks covered and the snapshots.
+    fn forget_image(&mut self) {
+        self.in_syscall = false;
+        self.selected_frame = 0;
+        self.shown_registers = None;
+        self.last_crash = None;
+        self.ltrace = LibraryTracer::default();
+        self.coverage = Coverage::default();
+        self.snapshots.clear();
+        self.cfi_modules.clear();
+    }
+
+    /// Load the architecture, the symbols and the line information of the program.
+    fn load_program(&mut self) {
+        // The program may have been rebuilt for the other architecture.
+        self.arch = Arch::of_executable(&self.program_path);
+        self.symbols.clear();
+        if let Err(err) = self.symbols.load(&self.program_path.clone(), None) {
+            errln!(self, "Could not load symbols: {}", err);
+        }
+        self.debug_info = executable_debug_info(&self.symbols, &self.program_path);
+        self.symbols_stale = false;
+        self.refresh_mappings();
+    }
+
+    /// Read the memory mappings of the child again, after it ran. They are empty once it has
+    /// terminated.
+    pub fn refresh_mappings(&mut self) {
+        self.mappings = self.read_mappings().unwrap_or_default();
+    }
+
+    /// Change the directories searched for separate debug files, and reload the symbols of the
+    /// objects already loaded so they pick up the debug files found there.
+    pub fn set_debug_file_directories(&mut self, directories: Vec<String>) {
+        self.symbols.debug_file_directories = directories;
+        // The objects read from memory have no debug file.
+        let loaded: Vec<(String, Option<u64>)> = self
+            .symbols
+            .objects
+            .iter()
+            .filter(|object| !sharedlib::in_memory(&object.path))
+            .map(|object| (object.path.clone(), object.bias))
+            .collect();
+        for (path, bias) in loaded {
+            if let Err(err) = self.symbols.load(&path, bias) {
+                errln!(self, "Could not load symbols: {}", err);
+            }
+        }
+        self.debug_info = executable_debug_info(&self.symbols, &self.program_path);
+    }
+
+    /// Compute the load bias of the main executable once it is mapped into the child.
+    ///
+    /// Position independent executables are only rebased after `execve`, so this is retried
+    /// until it succeeds.
+    pub fn refresh_executable_bias(&mut self) {
+        if self.symbols.object_mut(&self.program_path).is_none_or(|executable| executable.bias.is_some()) {
+            return;
+        }
+        let Ok(entries) = self.read_mappings() else {
+            return;
+        };
+        let Some(executable) = self.symbols.object_mut(&self.program_path) else {
+            return;
+        };
+        if let Some(base) = maps::mapped_files(&entries)
+            .iter()
+            .find(|file| same_file(&file.path, &self.program_path))
+            .and_then(|file| file.base)
+        {
+            executable.bias = Some(executable.bias_from_base(base));
+        }
+    }
+
+    /// List the shared libraries currently mapped into the child, the vDSO last.
+    pub fn shared_libraries(&mut self) -> Vec<SharedLibrary> {
+        self.refresh_executable_bias();
+        let executable = self.symbols.objects.iter().find(|object| same_file(&object.path, &self.program_path));
+        let mappings = self.read_mappings().unwrap_or_default();
+        let mut libraries = sharedlib::list_shared_libraries(self.tracee().as_ref(), self.arch, &mappings, executable);
+        libraries.extend(self.vdso(&mappings));
+        libraries
+    }
+
+    /// Find the vDSO of the child, loading its symbols from its memory on first use: they are
+    /// needed to know its load bias.
+    fn vdso(&mut self, mappings: &[MapEntry]) -> Option<SharedLibrary> {
+        let auxv = procfs::read_auxv(self.child, self.arch.pointer_size() as usize).unwrap_or_default();
+        let (start, end) = sharedlib::find_vdso(&auxv, mappings)?;
+        if !self.symbols.is_loaded(sharedlib::VDSO) {
+            match sharedlib::read_memory_object(self.tracee().as_ref(), sharedlib::VDSO, start, end) {
+                Ok(object) => {
+                    self.symbols.add(object);
+                }
+                Err(err) => log::debug!("Could not load the symbols of the vDSO: {}", err),
+            }
+        }
+        let bias = self.symbols.object_mut(sharedlib::VDSO).and_then(|object| object.bias);
+        Some(SharedLibrary { path: sharedlib::VDSO.to_string(), start, end, bias })
+    }
+
+    /// Load the symbols of a shared library, from its file or, for the vDSO, from the memory of
+    /// the child.
+    ///
+    /// # Returns
+    ///
+    /// The number of symbols loaded.
+    pub fn load_library(&mut self, library: &SharedLibrary) -> Result<usize, SymbolError> {
+        if sharedlib::in_memory(&library.path) {
+            let object = sharedlib::read_memory_object(self.tracee().as_ref(), &library.path, library.start, library.end)?;
+            return Ok(self.symbols.add(object));
+        }
+        self.symbols.load(&library.path, library.bias)
+    }
+
+    /// List every module mapped into the child: the main executable first, then the shared libraries.
+    pub fn modules(&mut self) -> Vec<SharedLibrary> {
+        let mut modules = Vec::new();
+        let bias = self.executable_bias();
+        if let Ok(entries) = self.read_mappings() {
+            let files = maps::mapped_files(&entries);
+            let executable = files.iter().filter(|file| same_file(&file.path, &self.program_path));
+            modules.extend(executable.map(|file| SharedLibrary {
+                path: file.path.clone(),
+                start: file.start,
+                end: file.end,
+                bias,
+            }));
+        }
+        modules.extend(self.shared_libraries());
+        modules
+    }
+
+    /// Returns the call frame information of the module at `path`, parsing it on first use.
+    pub fn cfi(&mut self, path: &str) -> Option<&CfiModule> {
+        self.cfi_modules
+            .entry(path.to_string())
+            .or_insert_with(|| CfiModule::load(path))
+            .as_ref()
+    }
+
+    /// Returns the canonical frame address of a frame of the main executable.
+    ///
+    /// # Arguments
+    ///
+    /// * `registers` - The registers of the frame.
+    /// * `lookup` - The pc of the frame, minus one for the callers.
+    pub fn frame_cfa(&mut self, registers: &UnwindRegisters, lookup: u64) -> Option<u64> {
+        let bias = self.executable_bias()?;
+        let path = self.program_path.clone();
+        self.cfi(&path)?.cfa(registers, lookup, bias)
+    }
+
+    /// Load the symbols of every shared library whose path contains `name`.
+    ///
+    /// # Returns
+    ///
+    /// The path and number of symbols of each library loaded.
+    pub fn load_library_symbols(&mut self, name: &str) -> Vec<Result<(String, usize), SymbolError>> {
+        self.shared_libraries()
+            .into_iter()
+            .filter(|library| library.path.contains(name))
+            .map(|library| {
+                let count = self.load_library(&library)?;
+                Ok((library.path, count))
+            })
+            .collect()
+    }
+
+    /// Load the symbols of every mapped shared library not loaded yet.
+    pub fn load_all_symbols(&mut self) {
+        for library in self.shared_libraries() {
+            if !self.symbols.is_loaded(&library.path) && library.bias.is_some() {
+                if let Err(err) = self.load_library(&library) {
+                    errln!(self, "Could not load symbols: {}", err);
+                }
+            }
+        }
+    }
+
+    /// Find the runtime address of a symbol.
+    ///
+    /// Objects whose symbols are already loaded are searched first (the main executable comes
+    /// first), then the symbols of the remaining shared libraries are loaded one by one, in the
+    /// dynamic linker's load order, until the symbol is found.
+    pub fn resolve_symbol(&mut self, name: &str) -> Option<u64> {
+        self.refresh_executable_bias();
+        if let Some(address) = self.symbols.lookup(name) {
+            return Some(address);
+        }
+        // Listing the libraries loads the symbols of the vDSO.
+        for library in self.shared_libraries() {
+            if library.bias.is_none() || !self.symbols.is_loaded(&library.path) && self.load_library(&library).is_err() {
+                continue;
+            }
+            if let Some(address) = self.symbols.object_mut(&library.path).and_then(|object| object.lookup(name)) {
+                return Some(address);
+            }
+        }
+        None
+    }
+
+    /// Find where to break on a function: after its prologue when the main executable has line
+    /// information for it, so its parameters can be read from their DWARF locations, on its first
+    /// instruction otherwise.
+    pub fn function_breakpoint_address(&mut self, name: &str) -> Option<u64> {
+        let entry = self.resolve_symbol(name)?;
+        Some(self.after_prologue(entry))
+    }
+
+    /// Find the first line of the body of the function starting at `entry`, when the main
+    /// executable has line information for it.
+    ///
+    /// # Returns
+    ///
+    /// The address after the prologue, or `entry` itself.
+    pub fn after_prologue(&mut self, entry: u64) -> u64 {
+        let function = self.symbolize(entry);
+        let in_executable = function.as_ref().is_some_and(|(_, _, path)| same_file(path, &self.program_path));
+        let body = match (self.executable_bias(), &self.debug_info) {
+            (Some(bias), Some(debug_info)) if in_executable => {
+                debug_info.prologue_end(entry.wrapping_sub(bias)).map(|address| address.wrapping_add(bias))
+            }
+            _ => None,
+        };
+        match (body, function) {
+            (Some(address), Some((name, _, _))) if self.symbolize(address).is_some_and(|(symbol, _, _)| symbol == name) => address,
+            _ => entry,
+        }
+    }
+
+    /// Describe a runtime address as `symbol+offset`, loading the symbols of the shared library
+    /// containing it if needed.
+    ///
+    /// # Returns
+    ///
+    /// The symbol name, the offset inside the symbol and the path of the object defining it.
+    pub fn symbolize(&mut self, address: u64) -> Option<(String, u64, String)> {
+        self.refresh_executable_bias();
+        if self.symbols.symbolize(address).is_none() {
+            let library = self
+                .shared_libraries()
+                .into_iter()
+                .find(|library| address >= library.start && address < library.end)?;
+            if !self.symbols.is_loaded(&library.path) {
+                self.load_library(&library).ok()?;
+            }
+        }
+        self.symbols
+            .symbolize(address)
+            .map(|(object, symbol, offset)| (symbol.name.clone(), offset, object.path.clone()))
+    }
+
+    /// Returns the symbols, sections and header of the main executable.
+    pub fn executable(&mut self) -> Option<&ObjectSymbols> {
+        self.refresh_executable_bias();
+        self.symbols.objects.iter().find(|object| same_file(&object.path, &self.program_path))
+    }
+
+    /// Returns the load bias of the main executable, if it is known yet.
+    pub fn executable_bias(&mut self) -> Option<u64> {
+        self.refresh_executable_bias();
+        self.symbols.object_mut(&self.program_path)?.bias
+    }
+
+    /// Find the source location of a runtime address of the main executable.
+    pub fn source_location(&mut self, address: u64) -> Option<SourceLocation> {
+        let bias = self.executable_bias()?;
+        self.debug_info.as_ref()?.find_location(address.wrapping_sub(bias))
+    }
+
+    /// Find the functions active at a runtime address of the main executable, innermost first.
+    ///
+    /// At most `inline_depth` inlined functions are kept, the innermost ones are dropped first.
+    pub fn function_frames(&mut self, address: u64) -> Vec<FunctionFrame> {
+        let Some(bias) = self.executable_bias() else {
+            return Vec::new();
+        };
+        let Some(debug_info) = self.debug_info.as_ref() else {
+            return Vec::new();
+        };
+        let mut frames = debug_info.find_frames(address.wrapping_sub(bias));
+        let hidden = frames.len().saturating_sub(1).saturating_sub(self.inline_depth);
+        frames.drain(..hidden);
+        frames
+    }
+
+    /// Describe the inlined function containing a runtime address, as in `square (inlined into main)`.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the address is not in inlined code.
+    pub fn inlined_function(&mut self, address: u64) -> Option<String> {
+        let frames = self.function_frames(address);
+        let innermost = frames.first().filter(|frame| frame.inlined)?;
+        let caller = frames.last()?.function.as_deref().unwrap_or("??");
+        Some(format!("{} (inlined into {})", innermost.function.as_deref().unwrap_or("??"), caller))
+    }
+
+    /// Find the line containing a runtime address of the main executable and the runtime range of
+    /// addresses generated for it.
+    pub fn line_range(&mut self, address: u64) -> Option<(SourceLocation, u64, u64)> {
+        let bias = self.executable_bias()?;
+        let (location, start, end) = self.debug_info.as_ref()?.line_range(address.wrapping_sub(bias))?;
+        Some((location, start.wrapping_add(bias), end.wrapping_add(bias)))
+    }
+
+    /// Find the runtime address of a source line of the main executable.
+    ///
+    /// # Returns
+    ///
+    /// The line actually found (the next line with code when `line` has none) and its address.
+    pub fn find_line(&mut self, file: &str, line: u64) -> Option<(u64, u64)> {
+        let bias = self.executable_bias()?;
+        let (line, address) = self.debug_info.as_ref()?.find_line(file, line)?;
+        Some((line, address.wrapping_add(bias)))
+    }
+
+    /// The number of the breakpoint set with `b` that is still armed at `address`.
+    pub fn breakpoint_number(&self, address: u64) -> Option<usize> {
+        if !is_breakpoint(&self.breakpoint_table, address) {
+            return None;
+        }
+        self.breakpoints.iter().rposition(|breakpoint| *breakpoint == address).map(|index| index + 1)
+    }
+
+    /// The group of the breakpoint numbered `number`, if `rbreak` set it.
+    pub fn breakpoint_group(&self, number: usize) -> Option<usize> {
+        self.breakpoint_groups.iter().position(|group| group.contains(&number)).map(|index| index + 1)
+    }
+
+    /// Format an address followed by the label or the symbol containing it, as in
+    /// `0x401126 <main+4>`.
+    pub fn describe_address(&mut self, address: u64) -> String {
+        if let Some(label) = self.label_at(address) {
+            return format!("{:#x} <{}>", address, label);
+        }
+        match self.symbolize(address) {
+            Some((name, 0, _)) => format!("{:#x} <{}>", address, name),
+            Some((name, offset, _)) => format!("{:#x} <{}+{}>", address, name, offset),
+            None => format!("{:#x}", address),
+        }
+    }
+
+    /// Name an address by the label covering it, as in `input_buf+16`.
+    pub fn label_at(&self, address: u64) -> Option<String> {
+        match label::find(&self.labels, address)? {
+            (name, 0) => Some(name.to_string()),
+            (name, offset) => Some(format!("{}+{}", name, offset)),
+        }
+    }
+
+    /// Find the full path of a source file of the main executable.
+    pub fn source_file(&self, file: &str) -> Option<String> {
+        self.debug_info.as_ref()?.find_file(file)
+    }
+
+    /// Read a source file, applying the `set substitute-path` rules to its path.
+    pub fn read_source(&mut self, file: &str) -> Option<Vec<String>> {
+        let path = source::substitute_path(file, &self.substitute_paths);
+        match source::read_source_lines(&path) {
+            Ok(lines) => Some(lines),
+            Err(err) => {
+                errln!(self, "Could not read {}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    /// Print the source lines around `location`.
+    pub fn list_source(&mut self, location: &SourceLocation) {
+        if let Some(lines) = self.read_source(&location.file) {
+            let (first, last) = source::centered_range(location.line, lines.len() as u64);
+            source::print_lines(self, &lines, first, last);
+        }
+    }
+
+    /// Print the `file:line` and source line of a stop address, when line information is available.
+    pub fn print_stop_location(&mut self, address: u64) {
+        let Some(location) = self.source_location(address) else {
+            return;
+        };
+        match self.inlined_function(address) {
+            Some(function) => outln!(self, "{} at {}:{}", function, location.file, location.line),
+            None => outln!(self, "at {}:{}", location.file, location.line),
+        }
+        self.print_source_line(&location);
+    }
+
+    /// Print the source line of a location, prefixed with its line number.
+    pub fn print_source_line(&mut self, location: &SourceLocation) {
+        if let Some(lines) = self.read_source(&location.file) {
+            source::print_lines(self, &lines, location.line, location.line);
+        }
+    }
+
+    /// Set a breakpoint at an address, a function (after its prologue) or a source line, as `b`
+    /// does. `restart` sets it again at the same location.
+    ///
+    /// # Returns
+    ///
+    /// The address of the breakpoint. Its number is the number of breakpoints set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::Parse`] if the location is invalid, [`DbgError::LineOutOfRange`] if a
+    /// line has no code, [`DbgError::Memory`] if the breakpoint cannot be written, or
+    /// [`DbgError::NotSupportedOnCore`] on a core file.
+    pub fn set_breakpoint(&mut self, location: &str) -> Result<u64, DbgError> {
+        self.check_live()?;
+        let (address, function) = self.resolve_breakpoint(location)?;
+        working::set_breakpoint(&mut self.breakpoint_table, self.thread, address)?;
+        // The breakpoint of a block becomes the user's, stopping the program when hit.
+        self.coverage.pending.remove(&address);
+        self.breakpoints.push(address);
+        self.breakpoint_locations.push(location.to_string());
+        if let Some(function) = function {
+            self.function_breakpoints.insert(address, function);
+        }
+        Ok(address)
+    }
+
+    /// Find the address of a breakpoint location, as [`Debugger::set_breakpoint`] does.
+    ///
+    /// # Returns
+    ///
+    /// The address, with the name of the function when the location is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::Parse`] if the location is invalid, or [`DbgError::LineOutOfRange`] if a
+    /// line has no code.
+    pub fn resolve_breakpoint(&mut self, location: &str) -> Result<(u64, Option<String>), DbgError> {
+        Ok(match location::parse_location(self, location)? {
+            Location::Address(address) => (address, None),
+            Location::Function(name, entry) => (self.function_breakpoint_address(&name).unwrap_or(entry), Some(name)),
+            Location::Line(file, line) => match self.find_line(&file, line) {
+                Some((_, address)) => (address, None),
+                None => return Err(DbgError::LineOutOfRange { file, line }),
+            },
+        })
+    }
+
+    /// Mark the program as running when resuming it, which invalidates the frames of the current
+    /// stop.
+    pub fn resume(&mut self) {
+        cache::invalidate();
+        self.selected_frame = 0;
+        self.in_syscall = false;
+        self.state = InferiorState::Running;
+    }
+
+    /// Continue the program until it stops, as `c` does, tracing its library calls when `ltrace` is
+    /// on. In non-stop mode, only the current thread is continued.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::ProcessExited`] if the program has terminated,
+    /// [`DbgError::NotSupportedOnCore`] on a core file, or the error of the ptrace operation that
+    /// failed.
+    pub fn cont(&mut self) -> Result<StepStop, DbgError> {
+        self.cont_with_signal(None)
+    }
+
+    /// Continue the program as [`Debugger::cont`] does, delivering `signal` to the current thread,
+    /// as the signal it stopped with, instead of discarding it.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Debugger::cont`].
+    pub fn cont_with_signal(&mut self, signal: Option<Signal>) -> Result<StepStop, DbgError> {
+        self.continue_threads(signal, !self.non_stop)
+    }
+
+    /// Continue every thread of the program, as `c -a` does in non-stop mode, where [`Debugger::cont`]
+    /// continues the current thread only.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Debugger::cont`].
+    pub fn cont_all(&mut self) -> Result<StepStop, DbgError> {
+        self.continue_threads(None, true)
+    }
+
+    /// Continue the current thread, and the other stopped threads with `all`, until a thread stops.
+    fn continue_threads(&mut self, signal: Option<Signal>, all: bool) -> Result<StepStop, DbgError> {
+        self.check_live()?;
+        let in_syscall = self.in_syscall;
+        self.resume();
+        if self.coverage.mode == Some(CoverMode::Step) {
+            // Only the current thread runs, one instruction at a time.
+            let stop = coverage::step_until_stop(self)?;
+            self.record_stop(&stop);
+            return Ok(stop);
+        }
+        if let Some(stop) = watch::continue_software(self)? {
+            self.record_stop(&stop);
+            return Ok(stop);
+        }
+        ltrace::arm_tracepoints(self)?;
+        watch::arm(self)?;
+        antidebug::start_watch(self, in_syscall);
+        if all {
+            threads::resume_others(self)?;
+        }
+        let thread = self.thread;
+        antidebug::resume(self.anti_debug.enabled, thread, signal)?;
+        threads::set_state(self, thread, ThreadState::Running);
+        self.wait_event()
+    }
+
+    /// Execute a single instruction, as `si` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::ProcessExited`] if the program has terminated,
+    /// [`DbgError::NotSupportedOnCore`] on a core file, or the error of the ptrace operation that
+    /// failed.
+    pub fn step(&mut self) -> Result<StepStop, DbgError> {
+        self.check_live()?;
+        self.resume();
+        let stop = step::single_step(self)?;
+        self.record_stop(&stop);
+        Ok(stop)
+    }
+
+    /// Wait until the resumed program stops: at a breakpoint, which is then removed, with a signal,
+    /// or because it terminated. The thread that stopped becomes the current thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::ProcessExited`] if the program has already terminated, or
+    /// [`DbgError::Ptrace`] if it cannot be waited for.
+    pub fn wait_event(&mut self) -> Result<StepStop, DbgError> {
+        let stop = working::prettier(self)?;
+        self.record_stop(&stop);
+        Ok(stop)
+    }
+
+    /// Returns [`DbgError::ProcessExited`] once the program has terminated.
+    fn check_alive(&self) -> Result<(), DbgError> {
+        if self.state.has_terminated() {
+            return Err(DbgError::ProcessExited);
+        }
+        Ok(())
+    }
+
+    /// Returns [`DbgError::NotSupportedOnCore`] on a core file, which can't be resumed nor
+    /// changed, and [`DbgError::ProcessExited`] once the program has terminated.
+    pub(crate) fn check_live(&self) -> Result<(), DbgError> {
+        if self.core.is_some() {
+            return Err(DbgError::NotSupportedOnCore);
+        }
+        self.check_alive()
+    }
+
+    /// Keep what a stop changed: the state of the program and the stop of the current thread, the
+    /// details of the signal it stopped by, and its mappings.
+    pub fn record_stop(&mut self, stop: &StepStop) {
+        self.state = InferiorState::from_stop(stop);
+        let current = self.thread;
+        if let Some(thread) = self.threads.iter_mut().find(|thread| thread.tid == current) {
+            thread.stop = Some(stop.clone());
+        }
+        self.last_signal = match stop {
+            StepStop::Signal(_) => siginfo::read(self.thread).ok(),
+            _ => None,
+        };
+        self.refresh_mappings();
+    }
+
+    /// Returns the registers of the current thread of the program, as `r` shows them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::ProcessExited`] if the program has terminated, or [`DbgError::Ptrace`]
+    /// if it is not stopped.
+    pub fn registers(&self) -> Result<user_regs_struct, DbgError> {
+        self.check_alive()?;
+        self.tracee().getregs().map_err(DbgError::ptrace("read the registers"))
+    }
+
+    /// Read `len` bytes of the memory of the program at `address`, with the original bytes of the
+    /// program where breakpoints are armed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::ProcessExited`] if the program has terminated, or
+    /// [`DbgError::Memory`] if part of the range cannot be read.
+    pub fn read_memory(&self, address: u64, len: usize) -> Result<Vec<u8>, DbgError> {
+        self.check_alive()?;
+        let mut bytes = memory::read_bytes(self.tracee().as_ref(), address, len)?;
+        working::restore_original_bytes(&self.breakpoint_table, address, &mut bytes);
+        Ok(bytes)
+    }
+
+    /// Map `size` bytes of anonymous, readable and writable memory in the program, as `alloc` does,
+    /// with an `mmap` system call made by the current thread. The memory is kept until
+    /// [`Debugger::deallocate`], scratch space for the data given to the program.
+    ///
+    /// # Returns
+    ///
+    /// The address of the memory, aligned on a page.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbgError::NotSupportedOnCore`] on a core file, [`DbgError::ProcessExited`] if the
+    /// program has terminated, or [`DbgError::Syscall`] if `mmap` fails or the thread is stopped in
+    /// a system call.
+    pub fn allocate(&mut self, size: u64) -> Result<u64, DbgError> {
+        self.check_live()?;
+        let address = inject::allocate(self, size)?;
+        self.refresh_mappings();
+        Ok(address)
+    }
+
+    /// Unmap `size` bytes of memory of the program at `address`, as `dealloc` does, with a
+    /// `munmap` system call made by the current thread.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Debugger::allocate`], with `munmap`.
+    pub fn deallocate(&mut self, address: u64, size: u64) -> Result<(), DbgError> {
+        self.check_live()?;
+        inject::deallocate(self, address, size)?;
+        self.refresh_mappings();
+        Ok(())
+    }
+
+    /// Returns the selected stack frame, with the registers recovered for it.
+    pub fn selected_frame(&mut self) -> Option<Frame> {
+        let target = self.selected_frame;
+        backtrace::backtrace(self, target + 1).ok()?.get(target).copied()
+    }
+
+    /// Returns the address to use to look up the code of the selected frame: its pc for the
+    /// innermost frame, the call instruction (pc - 1) for the callers.
+    pub fn selected_frame_lookup_pc(&mut self) -> Option<u64> {
+        let frame = self.selected_frame()?;
+        Some(if self.selected_frame == 0 { frame.pc } else { frame.pc - 1 })
+    }
+}
+
+/// Load the line information of the main executable, from its separate debug file if it has one.
+fn executable_debug_info(symbols: &SymbolTable, program_path: &str) -> Option<DebugInfo> {
+    let debug_file = symbols
+        .objects
+        .iter()
+        .find(|object| same_file(&object.path, program_path))
+        .and_then(|object| object.debug_file.clone());
+    match debug_file {
+        Some(debug_file) => DebugInfo::load(&debug_file),
+        None => DebugInfo::load(program_path),
+    }
+}
@@ -0,0 +1,247 @@
+use crate::symbols::demangle;
+use gimli::{EndianArcSlice, Reader as _, RunTimeEndian};
+use object::{Object, ObjectSection};
+use std::borrow::Cow;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The `gimli` reader used for every DWARF section.
+pub type Reader = EndianArcSlice<RunTimeEndian>;
+
+/// A row of the line table: the code starting at `address` belongs to `file:line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineRow {
+    pub address: u64,
+    pub file: String,
+    pub line: u64,
+}
+
+/// A source location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u64,
+}
+
+/// A function active at an address: the out-of-line function containing it or one of the
+/// functions inlined into it (`DW_TAG_inlined_subroutine`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionFrame {
+    /// The demangled name of the function, if known.
+    pub function: Option<String>,
+    /// For the innermost function, the location of the address. For the others, the location
+    /// of the call that was inlined.
+    pub location: Option<SourceLocation>,
+    /// True if the function was inlined into its caller, so it shares the caller's stack frame.
+    pub inlined: bool,
+}
+
+/// DWARF debugging information of an ELF file. Addresses are relative to the file (not rebased).
+pub struct DebugInfo {
+    dwarf: Arc<gimli::Dwarf<Reader>>,
+    context: addr2line::Context<Reader>,
+    /// Statement rows of every line program, sorted by address.
+    rows: Vec<LineRow>,
+}
+
+impl DebugInfo {
+    /// Load the DWARF sections of the ELF file at `path`.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the file can't be parsed or has no line information.
+    pub fn load(path: &str) -> Option<DebugInfo> {
+        let data = fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        file.section_by_name(".debug_line")?;
+        let endian = if file.is_little_endian() { RunTimeEndian::Little } else { RunTimeEndian::Big };
+        let load_section = |id: gimli::SectionId| -> Result<Reader, gimli::Error> {
+            let bytes = file
+                .section_by_name(id.name())
+                .and_then(|section| section.uncompressed_data().ok())
+                .unwrap_or(Cow::Borrowed(&[]));
+            Ok(EndianArcSlice::new(Arc::from(&*bytes), endian))
+        };
+        let dwarf = Arc::new(gimli::Dwarf::load(load_section).ok()?);
+        let rows = line_rows(&dwarf).ok()?;
+        let context = addr2line::Context::from_arc_dwarf(dwarf.clone()).ok()?;
+        Some(DebugInfo { dwarf, context, rows })
+    }
+
+    /// Find the source location of a file address.
+    pub fn find_location(&self, address: u64) -> Option<SourceLocation> {
+        let location = self.context.find_location(address).ok()??;
+        Some(SourceLocation { file: location.file?.to_string(), line: location.line? as u64 })
+    }
+
+    /// Find the functions active at a file address, the innermost (inlined) one first and the
+    /// out-of-line function containing the address last.
+    pub fn find_frames(&self, address: u64) -> Vec<FunctionFrame> {
+        let mut frames = Vec::new();
+        let Ok(mut iter) = self.context.find_frames(address).skip_all_loads() else {
+            return frames;
+        };
+        while let Ok(Some(frame)) = iter.next() {
+            frames.push(FunctionFrame {
+                function: frame.function.and_then(|name| name.raw_name().ok().map(|name| demangle(&name))),
+                location: frame.location.and_then(|location| {
+                    Some(SourceLocation { file: location.file?.to_string(), line: location.line? as u64 })
+                }),
+                inlined: true,
+            });
+        }
+        if let Some(outermost) = frames.last_mut() {
+            outermost.inlined = false;
+        }
+        frames
+    }
+
+    /// Find the line containing a file address and the range of addresses generated for it.
+    ///
+    /// Consecutive line table rows of the same line are merged, so the range covers the whole
+    /// statement rather than one of its columns.
+    ///
+    /// # Returns
+    ///
+    /// The location and the `[start, end)` range of the line, `None` if there is no line
+    /// information for the address.
+    pub fn line_range(&self, address: u64) -> Option<(SourceLocation, u64, u64)> {
+        let row_at = |address: u64| -> Option<(SourceLocation, u64, u64)> {
+            let (start, len, location) = self.context.find_location_range(address, address + 1).ok()?.next()?;
+            let location = SourceLocation { file: location.file?.to_string(), line: location.line? as u64 };
+            Some((location, start, start + len))
+        };
+        let (location, mut start, mut end) = row_at(address)?;
+        while let Some((previous, previous_start, previous_end)) = start.checked_sub(1).and_then(row_at) {
+            if previous != location || previous_end != start {
+                break;
+            }
+            start = previous_start;
+        }
+        while let Some((next, next_start, next_end)) = row_at(end) {
+            if next != location || next_start != end {
+                break;
+            }
+            end = next_end;
+        }
+        Some((location, start, end))
+    }
+
+    /// Find the code of a source line.
+    ///
+    /// # Returns
+    ///
+    /// The lowest address of the line. When the line has no code, the lowest address of the next
+    /// line of the same file that has some, with that line number. `None` if no line at or after
+    /// `line` has code.
+    pub fn find_line(&self, file: &str, line: u64) -> Option<(u64, u64)> {
+        self.rows
+            .iter()
+            .filter(|row| row.line >= line && path_matches(&row.file, file))
+            .min_by_key(|row| (row.line, row.address))
+            .map(|row| (row.line, row.address))
+    }
+
+    /// The DWARF sections of the file.
+    pub fn dwarf(&self) -> &gimli::Dwarf<Reader> {
+        &self.dwarf
+    }
+
+    /// Find the compilation unit containing the code at a file address.
+    pub fn unit_at(&self, address: u64) -> Option<gimli::UnitRef<'_, Reader>> {
+        self.context.find_dwarf_and_unit(address).skip_all_loads()
+    }
+
+    /// Find the first line of a function body, after the prologue that sets up its frame.
+    ///
+    /// This is the second line table row of the function, the caller checks it is still inside
+    /// the function.
+    ///
+    /// # Returns
+    ///
+    /// `None` if there is no line information for the first instruction of the function.
+    pub fn prologue_end(&self, function_start: u64) -> Option<u64> {
+        let index = self.rows.partition_point(|row| row.address < function_start);
+        let first = self.rows.get(index).filter(|row| row.address == function_start)?;
+        self.rows[index..]
+            .iter()
+            .find(|row| row.address > function_start && row.line != first.line)
+            .map(|row| row.address)
+    }
+
+    /// Find the function marked as the main function of the program (`DW_AT_main_subprogram`),
+    /// as rustc marks the `main` of the crate.
+    ///
+    /// # Returns
+    ///
+    /// The file address of its first instruction.
+    pub fn main_subprogram(&self) -> Option<u64> {
+        let dwarf = self.dwarf();
+        let mut units = dwarf.units();
+        while let Ok(Some(header)) = units.next() {
+            let Ok(unit) = dwarf.unit(header) else {
+                continue;
+            };
+            let mut entries = unit.entries();
+            while let Ok(Some((_, entry))) = entries.next_dfs() {
+                if entry.tag() != gimli::DW_TAG_subprogram
+                    || !matches!(entry.attr_value(gimli::DW_AT_main_subprogram), Ok(Some(gimli::AttributeValue::Flag(true))))
+                {
+                    continue;
+                }
+                if let Ok(Some(low_pc)) = entry.attr_value(gimli::DW_AT_low_pc) {
+                    if let Ok(Some(address)) = dwarf.attr_address(&unit, low_pc) {
+                        return Some(address);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Find the full path of a source file designated by the user (`main.c`, `src/lib.rs`, ...).
+    pub fn find_file(&self, file: &str) -> Option<String> {
+        self.rows.iter().find(|row| path_matches(&row.file, file)).map(|row| row.file.clone())
+    }
+}
+
+/// Returns true if the source path `path` is the file designated by the user as `file`.
+pub fn path_matches(path: &str, file: &str) -> bool {
+    path == file || path.ends_with(&format!("/{}", file.trim_start_matches("./")))
+}
+
+fn line_rows(dwarf: &gimli::Dwarf<Reader>) -> Result<Vec<LineRow>, gimli::Error> {
+    let mut rows = Vec::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let Some(program) = unit.line_program.clone() else {
+            continue;
+        };
+        let comp_dir = match unit.comp_dir.as_ref() {
+            Some(dir) => Some(dir.to_string_lossy()?.into_owned()),
+            None => None,
+        };
+        let mut program_rows = program.rows();
+        while let Some((header, row)) = program_rows.next_row()? {
+            if row.end_sequence() || !row.is_stmt() {
+                continue;
+            }
+            let (Some(line), Some(file)) = (row.line(), row.file(header)) else {
+                continue;
+            };
+            let mut path = PathBuf::new();
+            if let Some(dir) = &comp_dir {
+                path.push(dir);
+            }
+            if let Some(directory) = file.directory(header) {
+                path.push(dwarf.attr_string(&unit, directory)?.to_string_lossy()?.as_ref());
+            }
+            path.push(dwarf.attr_string(&unit, file.path_name())?.to_string_lossy()?.as_ref());
+            rows.push(LineRow { address: row.address(), file: path.to_string_lossy().into_owned(), line: line.get() });
+        }
+    }
+    rows.sort_by_key(|row| row.address);
+    Ok(rows)
+}
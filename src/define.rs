@@ -0,0 +1,136 @@
+use crate::alias::tokenize;
+use crate::debugger::Debugger;
+
+/// User commands running user commands are nested at most this deep, to stop a user command
+/// calling itself endlessly.
+pub const MAX_USER_COMMAND_DEPTH: usize = 16;
+
+/// A user command being defined with `define`, collecting the lines of its body until `end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    /// The name of the command.
+    pub name: String,
+    /// The commands of the body so far.
+    pub lines: Vec<String>,
+    /// The `define` lines of the body not closed by an `end` yet, which belong to the body.
+    pub nested: usize,
+}
+
+/// Returns true if `name` can name a user command: letters, digits, `-` and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Add a line to the body of the command being defined, or define the command at `end`.
+///
+/// # Returns
+///
+/// False if there is no definition in progress.
+pub fn collect_line(line: &str, debugger: &mut Debugger) -> bool {
+    let Some(definition) = &mut debugger.definition else {
+        return false;
+    };
+    let line = line.trim();
+    let first = line.split_whitespace().next().unwrap_or_default();
+    if first == "end" && definition.nested == 0 {
+        let Some(definition) = debugger.definition.take() else {
+            return false;
+        };
+        debugger.user_commands.insert(definition.name, definition.lines);
+        return true;
+    }
+    match first {
+        "define" => definition.nested += 1,
+        "end" => definition.nested -= 1,
+        _ => {}
+    }
+    if !line.is_empty() {
+        definition.lines.push(line.to_string());
+    }
+    true
+}
+
+/// Replace the arguments of a user command in a line of its body: `$arg0` to `$argN` with its
+/// arguments, and `$argc` with their number. Other `$` are kept, as in `$rip`.
+///
+/// # Errors
+///
+/// Returns a message if an argument is missing.
+pub fn substitute_arguments(line: &str, args: &[String]) -> Result<String, String> {
+    let mut substituted = String::new();
+    let mut rest = line;
+    while let Some(index) = rest.find("$arg") {
+        substituted.push_str(&rest[..index]);
+        let after = &rest[index + 4..];
+        if let Some(after) = after.strip_prefix('c').filter(|after| !after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')) {
+            substituted.push_str(&args.len().to_string());
+            rest = after;
+            continue;
+        }
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            substituted.push_str("$arg");
+            rest = after;
+            continue;
+        }
+        let number: usize = after[..digits].parse().map_err(|_| format!("Invalid argument $arg{}", &after[..digits]))?;
+        match args.get(number) {
+            Some(arg) => substituted.push_str(arg),
+            None => return Err(format!("Missing argument $arg{}: {} given", number, args.len())),
+        }
+        rest = &after[digits..];
+    }
+    substituted.push_str(rest);
+    Ok(substituted)
+}
+
+/// Run a user command, one line of its body after the other, stopping at the first that fails.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `command` - The command line, starting with the name of the user command.
+/// * `run_command` - Runs one line of the body, returning false if it failed.
+///
+/// # Returns
+///
+/// False if a line failed, an argument is missing, or user commands are nested too deeply.
+pub fn run(debugger: &mut Debugger, command: &str, run_command: fn(&str, &mut Debugger) -> bool) -> bool {
+    let command = command.trim_start();
+    let name = command.split_whitespace().next().unwrap_or_default();
+    let Some(body) = debugger.user_commands.get(name).cloned() else {
+        return false;
+    };
+    let args = match tokenize(&command[name.len()..]) {
+        Ok(args) => args,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    if debugger.user_command_depth >= MAX_USER_COMMAND_DEPTH {
+        errln!(debugger, "User commands are nested too deeply running \"{}\", is it recursive?", name);
+        return false;
+    }
+    debugger.user_command_depth += 1;
+    let mut succeeded = true;
+    for line in body {
+        let line = match substitute_arguments(&line, &args) {
+            Ok(line) => line,
+            Err(err) => {
+                errln!(debugger, "{}: {}", name, err);
+                succeeded = false;
+                break;
+            }
+        };
+        if !run_command(&line, debugger) {
+            succeeded = false;
+            break;
+        }
+        if debugger.quit_requested {
+            break;
+        }
+    }
+    debugger.user_command_depth -= 1;
+    succeeded
+}
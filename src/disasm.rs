@@ -0,0 +1,551 @@
+use crate::arch::{require_x86, Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::location::{self, Location};
+use crate::memory::{read_bytes, read_c_string, read_u64};
+use crate::tracee::Tracee;
+use crate::variables::is_printable;
+use crate::working::{restore_original_bytes, BreakpointTable};
+use iced_x86::{ConditionCode, Decoder, DecoderOptions, Formatter, GasFormatter, Instruction, IntelFormatter, OpKind};
+
+/// Number of instructions shown by `disas` when no count is given.
+pub const DEFAULT_INSTRUCTION_COUNT: usize = 10;
+/// Longest possible x86_64 instruction.
+pub const MAX_INSTRUCTION_LEN: usize = 15;
+/// Largest function or range disassembled at once, larger ones are truncated.
+const MAX_DISASSEMBLY_LEN: u64 = 0x10000;
+/// How far before the pc the start of its function may be to disassemble around the pc.
+const MAX_RESYNC_DISTANCE: u64 = 0x1000;
+
+/// The assembly syntax used to show instructions (`set disassembly-flavor`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisassemblyFlavor {
+    /// `mov rax, 1`
+    #[default]
+    Intel,
+    /// `movq $1, %rax`
+    Att,
+}
+
+impl DisassemblyFlavor {
+    /// Parse the name used by `set disassembly-flavor`.
+    pub fn parse(name: &str) -> Option<DisassemblyFlavor> {
+        match name {
+            "intel" => Some(DisassemblyFlavor::Intel),
+            "att" => Some(DisassemblyFlavor::Att),
+            _ => None,
+        }
+    }
+
+    fn formatter(self) -> Box<dyn Formatter> {
+        let mut formatter: Box<dyn Formatter> = match self {
+            DisassemblyFlavor::Intel => Box::new(IntelFormatter::new()),
+            DisassemblyFlavor::Att => Box::new(GasFormatter::new()),
+        };
+        let options = formatter.options_mut();
+        options.set_hex_prefix("0x");
+        options.set_hex_suffix("");
+        options.set_uppercase_hex(false);
+        options.set_space_after_operand_separator(true);
+        options.set_branch_leading_zeros(false);
+        options.set_rip_relative_addresses(true);
+        options.set_gas_show_mnemonic_size_suffix(true);
+        formatter
+    }
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    pub address: u64,
+    pub bytes: Vec<u8>,
+    /// Mnemonic and operands, in the requested syntax.
+    pub text: String,
+    /// Destination of a direct call or jump.
+    pub branch_target: Option<u64>,
+    /// Absolute address of a `rip`-relative memory operand.
+    pub memory_address: Option<u64>,
+    /// The condition of a conditional jump (`Jcc`).
+    pub condition: Option<ConditionCode>,
+}
+
+/// Read code from the child, showing the original bytes where breakpoints are armed.
+///
+/// # Arguments
+///
+/// * `tracee` - The thread being debugged, or the thread of the core file.
+/// * `breakpoints` - The breakpoints armed in it.
+/// * `address` - The first address to read.
+/// * `len` - The number of bytes to read.
+///
+/// # Errors
+///
+/// Returns an error if the first bytes can't be read. A range crossing the end of a mapping is
+/// truncated to the readable part.
+pub fn read_code(tracee: &dyn Tracee, breakpoints: &BreakpointTable, address: u64, len: usize) -> Result<Vec<u8>, DbgError> {
+    let mut bytes = read_raw_code(tracee, address, len)?;
+    restore_original_bytes(breakpoints, address, &mut bytes);
+    Ok(bytes)
+}
+
+/// Read code for a listing: as it is in memory with `raw`, with the original bytes where
+/// breakpoints are armed otherwise.
+fn read_listing_code(debugger: &Debugger, address: u64, len: usize, raw: bool) -> Result<Vec<u8>, DbgError> {
+    let tracee = debugger.tracee();
+    match raw {
+        true => read_raw_code(tracee.as_ref(), address, len),
+        false => read_code(tracee.as_ref(), &debugger.breakpoint_table, address, len),
+    }
+}
+
+/// Read code from the child as it is in memory, including the `int3` of armed breakpoints.
+///
+/// # Errors
+///
+/// Same as [`read_code`].
+pub fn read_raw_code(tracee: &dyn Tracee, address: u64, len: usize) -> Result<Vec<u8>, DbgError> {
+    match read_bytes(tracee, address, len) {
+        Ok(bytes) => Ok(bytes),
+        Err(err) => {
+            // Stop at the end of the mapping instead of failing the whole read.
+            let readable = (address | 0xfff) + 1 - address;
+            if readable as usize >= len {
+                return Err(err);
+            }
+            read_bytes(tracee, address, readable as usize)
+        }
+    }
+}
+
+/// Decode up to `count` instructions from `bytes`, located at `address` in the child.
+///
+/// Bytes that don't start a valid instruction are listed one by one as `.byte 0x..`, decoding
+/// resumes at the next byte. Decoding stops early when the bytes run out in the middle of an
+/// instruction.
+///
+/// # Arguments
+///
+/// * `bytes` - The code to decode.
+/// * `address` - The address of the first byte in the child.
+/// * `count` - The maximum number of instructions to decode.
+/// * `flavor` - The syntax of the text of the instructions.
+/// * `arch` - The architecture of the code, 64-bit or 32-bit. Nothing is decoded on aarch64.
+pub fn disassemble(bytes: &[u8], address: u64, count: usize, flavor: DisassemblyFlavor, arch: Arch) -> Vec<DisassembledInstruction> {
+    if require_x86("Disassembly").is_err() {
+        return Vec::new();
+    }
+    let mut decoder = Decoder::with_ip(arch.bitness(), bytes, address, DecoderOptions::NONE);
+    let mut formatter = flavor.formatter();
+    let mut instruction = Instruction::default();
+    let mut instructions = Vec::new();
+    while decoder.can_decode() && instructions.len() < count {
+        let offset = decoder.position();
+        decoder.decode_out(&mut instruction);
+        if instruction.is_invalid() && decoder.last_error() == iced_x86::DecoderError::NoMoreBytes {
+            break;
+        }
+        if instruction.is_invalid() {
+            instructions.push(DisassembledInstruction {
+                address: address + offset as u64,
+                bytes: vec![bytes[offset]],
+                text: format!(".byte {:#04x}", bytes[offset]),
+                branch_target: None,
+                memory_address: None,
+                condition: None,
+            });
+            decoder.set_position(offset + 1).expect("the position is inside the bytes");
+            decoder.set_ip(address + offset as u64 + 1);
+            continue;
+        }
+        let mut text = String::new();
+        formatter.format(&instruction, &mut text);
+        instructions.push(DisassembledInstruction {
+            address: instruction.ip(),
+            bytes: bytes[offset..offset + instruction.len()].to_vec(),
+            text,
+            branch_target: matches!(instruction.op0_kind(), OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64)
+                .then(|| instruction.near_branch_target()),
+            memory_address: instruction.is_ip_rel_memory_operand().then(|| instruction.ip_rel_memory_address()),
+            condition: instruction.is_jcc_short_or_near().then(|| instruction.condition_code()),
+        });
+    }
+    instructions
+}
+
+/// Decode the instructions around `pc`: a few before it, when the start of its function is known
+/// so decoding can start on an instruction boundary, and the rest after it.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `pc` - The address to disassemble around.
+/// * `count` - The number of instructions to decode.
+/// * `raw` - True to decode the bytes in memory, including the `int3` of breakpoints.
+pub fn disassemble_around(debugger: &mut Debugger, pc: u64, count: usize, raw: bool) -> Result<Vec<DisassembledInstruction>, DbgError> {
+    let (flavor, arch) = (debugger.disassembly_flavor, debugger.arch);
+    let start = match debugger.symbolize(pc) {
+        Some((_, offset, _)) if offset > 0 && offset <= MAX_RESYNC_DISTANCE => Some(pc - offset),
+        _ => None,
+    };
+    let after = || read_listing_code(debugger, pc, count * MAX_INSTRUCTION_LEN, raw).map(|bytes| disassemble(&bytes, pc, count, flavor, arch));
+    let Some(start) = start else {
+        return after();
+    };
+    let bytes = read_listing_code(debugger, start, (pc - start) as usize + count * MAX_INSTRUCTION_LEN, raw)?;
+    let instructions = disassemble(&bytes, start, usize::MAX, flavor, arch);
+    let Some(index) = instructions.iter().position(|instruction| instruction.address == pc) else {
+        // The pc is not on an instruction boundary of the function (data in code, ...).
+        return after();
+    };
+    let first = index.saturating_sub(count / 2);
+    Ok(instructions.into_iter().skip(first).take(count).collect())
+}
+
+/// Decode the instruction containing `address`, which may be in the middle of it.
+///
+/// The function containing `address` is decoded from its start to find the instruction
+/// boundaries. Without a symbol, `address` is assumed to start an instruction.
+///
+/// # Returns
+///
+/// `None` if the code can't be read or decoded.
+pub fn containing_instruction(debugger: &mut Debugger, address: u64) -> Option<DisassembledInstruction> {
+    let start = match debugger.symbolize(address) {
+        Some((_, offset, _)) if offset <= MAX_RESYNC_DISTANCE => address - offset,
+        _ => address,
+    };
+    let bytes = read_code(debugger.tracee().as_ref(), &debugger.breakpoint_table, start, (address - start) as usize + MAX_INSTRUCTION_LEN).ok()?;
+    disassemble(&bytes, start, usize::MAX, debugger.disassembly_flavor, debugger.arch)
+        .into_iter()
+        .find(|instruction| address < instruction.address + instruction.bytes.len() as u64)
+        .filter(|instruction| instruction.address <= address)
+}
+
+/// Bits of `eflags` tested by the conditional jumps.
+#[cfg(target_arch = "x86_64")]
+const CARRY_FLAG: u64 = 1 << 0;
+#[cfg(target_arch = "x86_64")]
+const PARITY_FLAG: u64 = 1 << 2;
+#[cfg(target_arch = "x86_64")]
+const ZERO_FLAG: u64 = 1 << 6;
+#[cfg(target_arch = "x86_64")]
+const SIGN_FLAG: u64 = 1 << 7;
+#[cfg(target_arch = "x86_64")]
+const OVERFLOW_FLAG: u64 = 1 << 11;
+
+/// Evaluate the condition of a conditional jump.
+///
+/// # Arguments
+///
+/// * `condition` - The condition code of the jump.
+/// * `eflags` - The flags register.
+///
+/// # Returns
+///
+/// Whether the jump will be taken, and the value of the flags it depends on (`ZF=0, SF=1`).
+/// `None` for instructions without a condition.
+#[cfg(target_arch = "x86_64")]
+pub fn branch_taken(condition: ConditionCode, eflags: u64) -> Option<(bool, String)> {
+    let flag = |bit: u64| eflags & bit != 0;
+    let (cf, pf, zf, sf, of) = (flag(CARRY_FLAG), flag(PARITY_FLAG), flag(ZERO_FLAG), flag(SIGN_FLAG), flag(OVERFLOW_FLAG));
+    let (taken, flags): (bool, &[(&str, bool)]) = match condition {
+        ConditionCode::o => (of, &[("OF", of)]),
+        ConditionCode::no => (!of, &[("OF", of)]),
+        ConditionCode::b => (cf, &[("CF", cf)]),
+        ConditionCode::ae => (!cf, &[("CF", cf)]),
+        ConditionCode::e => (zf, &[("ZF", zf)]),
+        ConditionCode::ne => (!zf, &[("ZF", zf)]),
+        ConditionCode::be => (cf || zf, &[("CF", cf), ("ZF", zf)]),
+        ConditionCode::a => (!cf && !zf, &[("CF", cf), ("ZF", zf)]),
+        ConditionCode::s => (sf, &[("SF", sf)]),
+        ConditionCode::ns => (!sf, &[("SF", sf)]),
+        ConditionCode::p => (pf, &[("PF", pf)]),
+        ConditionCode::np => (!pf, &[("PF", pf)]),
+        ConditionCode::l => (sf != of, &[("SF", sf), ("OF", of)]),
+        ConditionCode::ge => (sf == of, &[("SF", sf), ("OF", of)]),
+        ConditionCode::le => (zf || sf != of, &[("ZF", zf), ("SF", sf), ("OF", of)]),
+        ConditionCode::g => (!zf && sf == of, &[("ZF", zf), ("SF", sf), ("OF", of)]),
+        _ => return None,
+    };
+    let flags: Vec<String> = flags.iter().map(|(name, value)| format!("{}={}", name, *value as u8)).collect();
+    Some((taken, flags.join(", ")))
+}
+
+/// Longest string shown after a `rip`-relative operand pointing at text.
+const MAX_PREVIEW_LEN: usize = 32;
+
+/// The label or the symbol containing an address, as in ` <main+4>`. Symbols without a size only
+/// match their first byte, so the end of the preceding section is not named after them.
+fn symbol_annotation(debugger: &mut Debugger, address: u64) -> String {
+    if let Some(label) = debugger.label_at(address) {
+        return format!(" <{}>", label);
+    }
+    if debugger.symbolize(address).is_none() {
+        return String::new();
+    }
+    match debugger.symbols.symbolize(address) {
+        Some((_, symbol, 0)) => format!(" <{}>", symbol.demangled),
+        Some((_, symbol, offset)) if symbol.size > 0 => format!(" <{}+{}>", symbol.demangled, offset),
+        _ => String::new(),
+    }
+}
+
+/// Describe the destination of a branch: its symbol, and how many lines away it is when it is
+/// part of the listing (`[-> +3]` for three lines below).
+fn branch_annotation(debugger: &mut Debugger, instructions: &[DisassembledInstruction], index: usize, target: u64) -> String {
+    let mut annotation = symbol_annotation(debugger, target);
+    if let Some(position) = instructions.iter().position(|instruction| instruction.address == target) {
+        annotation.push_str(&format!(" [-> {:+}]", position as i64 - index as i64));
+    }
+    annotation
+}
+
+/// Describe the data a `rip`-relative operand points to: its symbol and a preview of the value,
+/// the text when it is a printable string, the 8-byte value otherwise.
+fn memory_annotation(debugger: &mut Debugger, address: u64) -> String {
+    let mut annotation = format!("  # {:#x}{}", address, symbol_annotation(debugger, address));
+    match read_c_string(debugger.tracee().as_ref(), address, MAX_PREVIEW_LEN) {
+        Ok(text) if text.len() >= 2 && text.chars().all(|c| is_printable(c) || c == '\n' || c == '\t') => annotation.push_str(&format!(" {:?}", text)),
+        _ => {
+            if let Ok(value) = read_u64(debugger.tracee().as_ref(), address) {
+                // Pointers, such as GOT entries read by indirect calls, are named too.
+                annotation.push_str(&format!(" = {:#x}{}", value, symbol_annotation(debugger, value)));
+            }
+        }
+    }
+    annotation
+}
+
+/// Format one line of a listing, marking the instruction at `rip` with `=>` and the instructions
+/// where breakpoint `n` is armed with `B<n>`.
+///
+/// A breakpoint armed in the middle of the instruction is pointed out, it would corrupt it. Branch destinations are followed by their symbol, and `rip`-relative operands by the address
+/// they designate with a preview of the data there. When the instruction at `rip` is a
+/// conditional jump, whether it will be taken is evaluated from the current flags.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `instructions` - The listing, used to locate the destinations of jumps.
+/// * `index` - The position in the listing of the instruction to format.
+/// * `rip` - The current pc, if known.
+pub fn format_instruction(debugger: &mut Debugger, instructions: &[DisassembledInstruction], index: usize, rip: Option<u64>) -> String {
+    let instruction = &instructions[index];
+    let end = instruction.address + instruction.bytes.len() as u64;
+    let mut marker = match debugger.breakpoint_number(instruction.address) {
+        Some(number) => format!("B{}", number),
+        None => String::new(),
+    };
+    marker.push_str(match (Some(instruction.address) == rip, marker.is_empty()) {
+        (true, _) => "=>",
+        (false, true) => "  ",
+        (false, false) => "",
+    });
+    let location = symbol_annotation(debugger, instruction.address);
+    let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let mut text = instruction.text.clone();
+    if let Some(target) = instruction.branch_target {
+        text.push_str(&branch_annotation(debugger, instructions, index, target));
+    }
+    if let Some(address) = instruction.memory_address {
+        text.push_str(&memory_annotation(debugger, address));
+    }
+    #[cfg(target_arch = "x86_64")]
+    if let (Some(condition), true) = (instruction.condition, Some(instruction.address) == rip) {
+        let eflags = debugger.tracee().getregs().map(|regs| regs.eflags);
+        match eflags.ok().and_then(|eflags| branch_taken(condition, eflags)) {
+            Some((true, flags)) => text.push_str(&format!(" \u{2014} will be TAKEN ({})", flags)),
+            Some((false, flags)) => text.push_str(&format!(" \u{2014} will NOT be taken ({})", flags)),
+            None => {}
+        }
+    }
+    for address in instruction.address + 1..end {
+        if let Some(number) = debugger.breakpoint_number(address) {
+            text.push_str(&format!(" <- breakpoint B{} at {:#x} is inside this instruction", number, address));
+        }
+    }
+    format!("{} {:#018x}{}:\t{:<24} {}", marker, instruction.address, location, bytes.join(" "), text)
+}
+
+/// Print a listing, marking the instruction at `rip` with `=>`.
+pub fn print_instructions(debugger: &mut Debugger, instructions: &[DisassembledInstruction], rip: Option<u64>) {
+    for index in 0..instructions.len() {
+        outln!(debugger, "{}", format_instruction(debugger, instructions, index, rip));
+    }
+}
+
+/// Format the listing line of the instruction at `rip`.
+///
+/// # Returns
+///
+/// `None` if the code at `rip` can't be read or decoded.
+pub fn current_instruction(debugger: &mut Debugger, rip: u64) -> Option<String> {
+    instruction_line(debugger, rip, Some(rip))
+}
+
+/// Format the listing line of the instruction at `address`, marked with `=>` if it is at `current`.
+///
+/// # Returns
+///
+/// `None` if the code at `address` can't be read or decoded.
+pub fn instruction_line(debugger: &mut Debugger, address: u64, current: Option<u64>) -> Option<String> {
+    let bytes = read_code(debugger.tracee().as_ref(), &debugger.breakpoint_table, address, MAX_INSTRUCTION_LEN).ok()?;
+    let instructions = disassemble(&bytes, address, 1, debugger.disassembly_flavor, debugger.arch);
+    if instructions.is_empty() {
+        return None;
+    }
+    Some(format_instruction(debugger, &instructions, 0, current))
+}
+
+/// Disassemble for the `disas [--raw] [location] [count|end|+len] [> file]` command.
+///
+/// With `--raw`, the bytes are decoded as they are in memory, with the `int3` of breakpoints
+/// instead of the original bytes. Without a location, `count` instructions around the pc are shown. A function name shows the
+/// whole function (or its first `count` instructions), an address shows `count` instructions
+/// starting there. A second argument that is not a decimal count (an `0x` address, a register, a
+/// symbol) is the end of a raw range to decode, as is a `+len` length, which also works for code
+/// without symbols. With `> file`, the listing is written to the file.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `args` - The arguments of the command.
+pub fn disassemble_command(debugger: &mut Debugger, args: &[&str]) {
+    const USAGE: &str = "Usage: disas [--raw] [address|function] [count|end|+len] [> file]";
+    let (raw, args) = match args.first() {
+        Some(&"--raw") => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let (args, output) = match args.iter().position(|arg| arg.starts_with('>')) {
+        Some(index) => {
+            let file = match args[index].strip_prefix('>').filter(|file| !file.is_empty()) {
+                Some(file) => Some(file),
+                None => args.get(index + 1).copied(),
+            };
+            match file {
+                Some(file) if args.len() <= index + 2 => (&args[..index], Some(file)),
+                _ => {
+                    errln!(debugger, "{}", USAGE);
+                    return;
+                }
+            }
+        }
+        None => (args, None),
+    };
+    if args.len() > 2 {
+        errln!(debugger, "{}", USAGE);
+        return;
+    }
+    if let Err(err) = require_x86("disas") {
+        errln!(debugger, "{}", err);
+        return;
+    }
+    // The current instruction is only marked in the innermost frame, the callers are at a call.
+    let rip = debugger.tracee().getregs().ok().map(|regs| regs.pc()).filter(|_| debugger.selected_frame == 0);
+    let (flavor, arch) = (debugger.disassembly_flavor, debugger.arch);
+    let instructions = match args.first() {
+        None => {
+            let Some(pc) = debugger.selected_frame_lookup_pc() else {
+                errln!(debugger, "No registers.");
+                return;
+            };
+            // The lookup pc of a caller is inside its call instruction: center on the call.
+            let pc = match debugger.selected_frame {
+                0 => pc,
+                _ => containing_instruction(debugger, pc).map_or(pc + 1, |instruction| instruction.address),
+            };
+            disassemble_around(debugger, pc, DEFAULT_INSTRUCTION_COUNT, raw)
+        }
+        Some(location) => {
+            let (address, size) = match location::parse_location(debugger, location) {
+                Ok(Location::Function(_, address)) => {
+                    let size = debugger.symbols.symbolize(address).map(|(_, symbol, _)| symbol.size).filter(|size| *size > 0);
+                    (address, size)
+                }
+                Ok(Location::Address(address)) => (address, None),
+                Ok(Location::Line(file, line)) => match debugger.find_line(&file, line) {
+                    Some((_, address)) => (address, None),
+                    None => {
+                        errln!(debugger, "{}", DbgError::LineOutOfRange { file, line });
+                        return;
+                    }
+                },
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    return;
+                }
+            };
+            let (len, count) = match args.get(1) {
+                Some(length) if length.starts_with('+') => match location::parse_number(&length[1..], "length") {
+                    Ok(len) if len > 0 => (len, usize::MAX),
+                    Ok(_) => {
+                        errln!(debugger, "Invalid length {}", length);
+                        return;
+                    }
+                    Err(err) => {
+                        errln!(debugger, "{}", err);
+                        return;
+                    }
+                },
+                // A decimal (or binary) number is a count of instructions, anything else the end of the range.
+                Some(count) if count.starts_with(|c: char| c.is_ascii_digit()) && !count.starts_with("0x") => {
+                    match location::parse_count(count, "count") {
+                        Ok(count) if count > 0 => (size.unwrap_or((count * MAX_INSTRUCTION_LEN) as u64), count),
+                        Ok(_) => {
+                            errln!(debugger, "{}", USAGE);
+                            return;
+                        }
+                        Err(err) => {
+                            errln!(debugger, "{}", err);
+                            return;
+                        }
+                    }
+                }
+                Some(end) => match location::parse_value(debugger, end, "end address") {
+                    Ok(end) if end > address => (end - address, usize::MAX),
+                    Ok(_) => {
+                        errln!(debugger, "The end of the range must be an address after {:#x}.", address);
+                        return;
+                    }
+                    Err(err) => {
+                        errln!(debugger, "{}", err);
+                        return;
+                    }
+                },
+                None => match size {
+                    Some(size) => (size, usize::MAX),
+                    None => ((DEFAULT_INSTRUCTION_COUNT * MAX_INSTRUCTION_LEN) as u64, DEFAULT_INSTRUCTION_COUNT),
+                },
+            };
+            if len > MAX_DISASSEMBLY_LEN {
+                errln!(debugger, "Only the first {:#x} bytes of the range are disassembled.", MAX_DISASSEMBLY_LEN);
+            }
+            let len = len.min(MAX_DISASSEMBLY_LEN) as usize;
+            read_listing_code(debugger, address, len, raw).map(|bytes| disassemble(&bytes, address, count, flavor, arch))
+        }
+    };
+    let instructions = match instructions {
+        Ok(instructions) if instructions.is_empty() => {
+            outln!(debugger, "No instructions decoded.");
+            return;
+        }
+        Ok(instructions) => instructions,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return;
+        }
+    };
+    let Some(file) = output else {
+        print_instructions(debugger, &instructions, rip);
+        return;
+    };
+    let mut listing = String::new();
+    for index in 0..instructions.len() {
+        listing.push_str(&format_instruction(debugger, &instructions, index, rip));
+        listing.push('\n');
+    }
+    match std::fs::write(file, listing) {
+        Ok(()) => outln!(debugger, "Wrote {} instructions to {}.", instructions.len(), file),
+        Err(err) => errln!(debugger, "Could not write {}: {}", file, err),
+    }
+}
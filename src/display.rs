@@ -0,0 +1,151 @@
+use crate::assertion::{self, Operand};
+use crate::debugger::Debugger;
+use crate::json;
+use crate::state::InferiorState;
+use crate::variables::{self, FormatLimits};
+use serde_json::json;
+
+/// An expression shown at every stop, added with `display`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    pub expression: String,
+    /// What was shown at the last stop: the value, or the error evaluating it.
+    pub last: Option<String>,
+}
+
+/// Returns true if an expression names a variable, as `count` or `app::state::COUNTER`, shown as
+/// `print` shows it. The others are values read as `assert` reads them: `$rax`, `*0x601040`.
+pub fn is_variable(expression: &str) -> bool {
+    expression.starts_with(|c: char| c.is_alphabetic() || c == '_') && expression.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Format a value read as `assert` reads it, in hexadecimal then in decimal, as in `0x2a (42)`.
+pub fn format_operand(operand: Operand) -> String {
+    let decimal = if operand.signed { operand.as_i64().to_string() } else { operand.value.to_string() };
+    format!("{:#x} ({})", operand.value, decimal)
+}
+
+/// Evaluate an expression of `display`: a variable as `print` shows it, or a value as `assert`
+/// reads it (registers, convenience variables, numbers and symbols with an offset, `*<value>`
+/// for the integer in memory at an address).
+///
+/// # Errors
+///
+/// Returns the message of the error: no such variable, an unmapped address...
+pub fn evaluate(debugger: &mut Debugger, expression: &str) -> Result<String, String> {
+    if is_variable(expression) && !debugger.labels.contains_key(expression) {
+        return variables::variable_value(debugger, expression, FormatLimits::DEFAULT).map(|(_, value)| value).map_err(|err| err.to_string());
+    }
+    assertion::evaluate(debugger, expression).map(format_operand).map_err(|err| err.to_string())
+}
+
+/// The line showing a display, as `1: count = 3`, or `2: *0x10 = <error: ...>` when it can't be
+/// evaluated.
+fn format_line(number: usize, expression: &str, value: &Result<String, String>) -> String {
+    match value {
+        Ok(value) => format!("{}: {} = {}", number, expression, value),
+        Err(err) => format!("{}: {} = <error: {}>", number, expression, err),
+    }
+}
+
+/// Evaluate and show a display, keeping what it showed for `info display`.
+fn show(debugger: &mut Debugger, number: usize) -> Result<String, String> {
+    let expression = debugger.displays[&number].expression.clone();
+    let value = evaluate(debugger, &expression);
+    let line = format_line(number, &expression, &value);
+    outln!(debugger, "{}", line);
+    if let Some(display) = debugger.displays.get_mut(&number) {
+        display.last = Some(value.clone().unwrap_or_else(|err| format!("<error: {}>", err)));
+    }
+    value
+}
+
+/// Show every display, at a stop of the program: one failing to evaluate shows its error and
+/// the others go on.
+pub fn show_displays(debugger: &mut Debugger) {
+    if !matches!(debugger.state, InferiorState::Stopped { .. }) {
+        return;
+    }
+    let numbers: Vec<usize> = debugger.displays.keys().copied().collect();
+    for number in numbers {
+        show(debugger, number).ok();
+    }
+}
+
+/// Add an expression shown at every stop, `display <expression>`, shown right away when the
+/// program is stopped. `display` alone shows them all.
+///
+/// # Returns
+///
+/// False without a program stopped to show the displays on.
+pub fn display_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() < 2 {
+        if !matches!(debugger.state, InferiorState::Stopped { .. }) {
+            errln!(debugger, "The program is not stopped, the displays are shown at its next stop.");
+            return false;
+        }
+        show_displays(debugger);
+        return true;
+    }
+    let expression = args[1..].join(" ");
+    debugger.display_count += 1;
+    let number = debugger.display_count;
+    debugger.displays.insert(number, Display { expression: expression.clone(), last: None });
+    let value = matches!(debugger.state, InferiorState::Stopped { .. }).then(|| show(debugger, number));
+    json::set_data(debugger, |_| {
+        let (value, error) = match value {
+            Some(Ok(value)) => (Some(value), None),
+            Some(Err(err)) => (None, Some(err)),
+            None => (None, None),
+        };
+        json!({ "number": number, "expression": expression, "value": value, "error": error })
+    });
+    true
+}
+
+/// Remove displays, `undisplay <number>...`.
+///
+/// # Returns
+///
+/// False if a number is not a display's: the others are removed.
+pub fn undisplay_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() < 2 {
+        errln!(debugger, "Usage: undisplay <number>...");
+        return false;
+    }
+    let mut removed = true;
+    for arg in &args[1..] {
+        match arg.parse::<usize>().ok().and_then(|number| debugger.displays.remove(&number)) {
+            Some(_) => {}
+            None => {
+                errln!(debugger, "No display number {}.", arg);
+                removed = false;
+            }
+        }
+    }
+    removed
+}
+
+/// List the displays with their number and the value they showed at the last stop (`info
+/// display`).
+pub fn info_display(debugger: &mut Debugger) {
+    let displays: Vec<(usize, Display)> = debugger.displays.iter().map(|(number, display)| (*number, display.clone())).collect();
+    json::set_data(debugger, |_| {
+        let displays: Vec<_> = displays
+            .iter()
+            .map(|(number, display)| json!({ "number": number, "expression": display.expression, "last": display.last }))
+            .collect();
+        json!({ "displays": displays })
+    });
+    if displays.is_empty() {
+        outln!(debugger, "No displays, add one with display <expression>.");
+        return;
+    }
+    outln!(debugger, "Expressions to display when the program stops:");
+    for (number, display) in displays {
+        match display.last {
+            Some(last) => outln!(debugger, "{}: {} = {}", number, display.expression, last),
+            None => outln!(debugger, "{}: {} (not shown yet)", number, display.expression),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::location::ParseError;
+use crate::output::Recording;
+use nix::errno::Errno;
+use nix::sys::wait::WaitStatus;
+use thiserror::Error;
+
+/// Why an operation on the debugged process failed. The commands print it, the library returns it
+/// so callers can tell the failures apart.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DbgError {
+    /// A ptrace or wait operation failed, `op` saying what it was doing.
+    #[error("Failed to {op}: {errno}")]
+    Ptrace { op: &'static str, errno: Errno },
+    /// The memory of the program cannot be read or written at `address`.
+    #[error("Cannot access memory at address {address:#x}: {errno}")]
+    Memory { address: u64, errno: Errno },
+    /// Nothing is mapped at this address in the program.
+    #[error("Cannot access memory at address {0:#x}: the address is not mapped")]
+    BadAddress(u64),
+    /// No breakpoint is armed at this address.
+    #[error("No breakpoint at address {0:#x}")]
+    NoSuchBreakpoint(u64),
+    /// The program has terminated, so it cannot be controlled anymore.
+    #[error("The program has terminated.")]
+    ProcessExited,
+    /// An argument could not be parsed.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    /// A source line has no code.
+    #[error("Line {line} is out of range for \"{file}\".")]
+    LineOutOfRange { file: String, line: u64 },
+    /// A path or an argument of the program cannot be given to exec, as when it contains a NUL.
+    #[error("Invalid {what} {value:?}")]
+    InvalidArgument { what: &'static str, value: String },
+    /// The debugger could not fork to start the program.
+    #[error("Failed to fork: {0}")]
+    Fork(Errno),
+    /// The program could not be executed, as when it does not exist or is not executable.
+    #[error("Failed to execute {path}: {errno}")]
+    Exec { path: String, errno: Errno },
+    /// The directory the program is started in, given with `set cwd`, can't be entered.
+    #[error("Cannot start the program in {path}: {errno}")]
+    Cwd { path: String, errno: Errno },
+    /// A resource limit of `set rlimit` can't be given to the program.
+    #[error("Cannot limit the program: {0}")]
+    Rlimit(String),
+    /// The program did not stop before its first instruction as expected.
+    #[error("Failed to start {path}: {status:?}")]
+    Start { path: String, status: WaitStatus },
+    /// The terminal or the pipes given to the program as its standard streams can't be opened.
+    #[error("Cannot open {tty} for the program: {errno}")]
+    InferiorTty { tty: String, errno: Errno },
+    /// There is no process to attach to.
+    #[error("No process {pid} to attach to: {reason}")]
+    NoProcess { pid: i32, reason: String },
+    /// The process cannot be traced by this user.
+    #[error(
+        "Not allowed to attach to process {0}: it belongs to another user, is already traced, or \
+         /proc/sys/kernel/yama/ptrace_scope only allows tracing children"
+    )]
+    AttachDenied(i32),
+    /// The entry point of the program is not known.
+    #[error("The entry point is not known, no executable file loaded")]
+    NoEntryPoint,
+    /// The program stopped for another reason before reaching its entry point.
+    #[error("The program did not reach its entry point: {0:?}")]
+    EntryNotReached(WaitStatus),
+    /// The core file cannot be read, or is not the core file of a program of the host architecture.
+    #[error("Cannot read the core file {path}: {reason}")]
+    Core { path: String, reason: String },
+    /// The operation needs a running process, and the session reads a core file.
+    #[error("Not supported on core files.")]
+    NotSupportedOnCore,
+    /// The command decodes x86 code or uses registers of x86_64, as `disas`, and the debugger runs
+    /// on aarch64.
+    #[error("{0} is only supported on x86_64.")]
+    X86Only(&'static str),
+    /// The connection of gdb to `--gdbserver` failed, or could not be set up.
+    #[error("Remote connection on {address} failed: {reason}")]
+    Remote { address: String, reason: String },
+    /// `reverse-continue` or `reverse-stepi` cannot go back.
+    #[error("Cannot go back: {0}")]
+    Reverse(String),
+    /// `watch-region` cannot write-protect the pages of a region.
+    #[error("Cannot watch the region: {0}")]
+    Region(String),
+    /// A system call made by the debugger in the program, as the `mmap` of `alloc`, failed.
+    #[error("The {name} system call of the program failed: {reason}")]
+    Syscall { name: &'static str, reason: String },
+    /// A command run by [`dispatch`](crate::dispatch) failed. Its output, error messages
+    /// included, is in `output`.
+    #[error("{}", failure_message(command, output))]
+    Command { command: String, output: Recording },
+}
+
+/// The message of a failed command: its error messages, or the command when it printed none.
+fn failure_message(command: &str, output: &Recording) -> String {
+    let errors = output.errors();
+    if errors.is_empty() {
+        format!("\"{}\" failed", command)
+    } else {
+        errors.join("\n")
+    }
+}
+
+impl DbgError {
+    /// Returns a function making the error of the ptrace or wait operation `op`, for `map_err`, as
+    /// in `ptrace::cont(child, None).map_err(DbgError::ptrace("continue execution"))`.
+    pub fn ptrace(op: &'static str) -> impl Fn(Errno) -> DbgError {
+        move |errno| DbgError::Ptrace { op, errno }
+    }
+
+    /// Returns a function making the error of a memory access at `address`, for `map_err`.
+    pub fn memory(address: u64) -> impl Fn(Errno) -> DbgError {
+        move |errno| DbgError::Memory { address, errno }
+    }
+}
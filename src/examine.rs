@@ -0,0 +1,291 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::disasm::{self, MAX_INSTRUCTION_LEN};
+use crate::json;
+use crate::location;
+use crate::memory;
+use serde_json::json;
+
+/// The letters of the formats and sizes, listed when an unknown one is given.
+const FORMAT_LETTERS: &str = "the formats x (hex), d (decimal), u (unsigned), t (binary), c (char), s (string) and i \
+(instruction), and the sizes b (1 byte), h (2), w (4) and g (8)";
+
+/// `x/s` gives up on a string without its NUL after this many bytes.
+const MAX_STRING_LEN: usize = 4096;
+
+/// `x` reads at most this many bytes of units at once.
+const MAX_EXAMINE_LEN: usize = 0x10000;
+
+/// How `x` shows the memory, the letter after the count in `x/16xb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Hex,
+    Decimal,
+    Unsigned,
+    Binary,
+    Char,
+    String,
+    Instruction,
+}
+
+impl Format {
+    fn from_letter(letter: char) -> Option<Format> {
+        Some(match letter {
+            'x' => Format::Hex,
+            'd' => Format::Decimal,
+            'u' => Format::Unsigned,
+            't' => Format::Binary,
+            'c' => Format::Char,
+            's' => Format::String,
+            'i' => Format::Instruction,
+            _ => return None,
+        })
+    }
+
+    /// The name of the format in the JSON data.
+    fn name(self) -> &'static str {
+        match self {
+            Format::Hex => "hex",
+            Format::Decimal => "decimal",
+            Format::Unsigned => "unsigned",
+            Format::Binary => "binary",
+            Format::Char => "char",
+            Format::String => "string",
+            Format::Instruction => "instruction",
+        }
+    }
+}
+
+/// What `x/<count><format><size>` examines: `count` units of `size` bytes shown in `format`, or
+/// `count` strings or instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Examine {
+    pub count: usize,
+    pub format: Format,
+    /// 1, 2, 4 or 8 bytes, ignored by strings and instructions.
+    pub size: usize,
+}
+
+fn size_from_letter(letter: char) -> Option<usize> {
+    Some(match letter {
+        'b' => 1,
+        'h' => 2,
+        'w' => 4,
+        'g' => 8,
+        _ => return None,
+    })
+}
+
+/// Parse the format of `x`, what follows the `/` of `x/16xb`: an optional count, then a format
+/// and a size letter in any order, each optional. The count is 1 by default, the format x, and
+/// the size w, or b for characters.
+///
+/// # Errors
+///
+/// Returns an error listing the supported letters for an unknown letter, and an error for a
+/// zero count, two formats or two sizes, or a size given to strings or instructions.
+pub fn parse_format(text: &str) -> Result<Examine, String> {
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let count = match &text[..digits] {
+        "" => 1,
+        count => match count.parse::<usize>() {
+            Ok(0) => return Err(format!("The count of /{} must be at least 1.", text)),
+            Ok(count) => count,
+            Err(_) => return Err(format!("The count of /{} is too large.", text)),
+        },
+    };
+    let (mut format, mut size) = (None, None);
+    for letter in text[digits..].chars() {
+        if let Some(letter_format) = Format::from_letter(letter) {
+            if format.replace(letter_format).is_some() {
+                return Err(format!("/{} has two formats, give one of x, d, u, t, c, s or i.", text));
+            }
+        } else if let Some(letter_size) = size_from_letter(letter) {
+            if size.replace(letter_size).is_some() {
+                return Err(format!("/{} has two sizes, give one of b, h, w or g.", text));
+            }
+        } else if letter.is_ascii_digit() {
+            return Err(format!("The count of /{} must come first.", text));
+        } else {
+            return Err(format!("Unknown format letter '{}' in /{}, x takes {}.", letter, text, FORMAT_LETTERS));
+        }
+    }
+    let format = format.unwrap_or(Format::Hex);
+    let size = match (format, size) {
+        (Format::String | Format::Instruction, Some(_)) => {
+            return Err(format!("/{} gives a size to {}s, which have none.", text, format.name()));
+        }
+        (Format::Char, None) => 1,
+        (_, size) => size.unwrap_or(4),
+    };
+    Ok(Examine { count, format, size })
+}
+
+/// The number of units of `size` bytes on a line of `x`, 16 bytes of bytes and half-words, 4
+/// words and 2 giant words.
+fn units_per_line(size: usize) -> usize {
+    match size {
+        1 | 2 => 8,
+        4 => 4,
+        _ => 2,
+    }
+}
+
+/// Format a unit read in memory, little-endian in `bytes`.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes of the unit, its size.
+/// * `format` - A format of units: not a string nor an instruction.
+pub fn format_unit(bytes: &[u8], format: Format) -> String {
+    let size = bytes.len();
+    let mut buffer = [0u8; 8];
+    buffer[..size].copy_from_slice(bytes);
+    let value = u64::from_le_bytes(buffer);
+    // Sign-extended from the size of the unit.
+    let signed = (value << (64 - size * 8)) as i64 >> (64 - size * 8);
+    match format {
+        Format::Hex => format!("{:#0width$x}", value, width = size * 2 + 2),
+        Format::Decimal => signed.to_string(),
+        Format::Binary => format!("{:0width$b}", value, width = size * 8),
+        Format::Char => format!("{} {}", signed, char_literal(value as u8)),
+        Format::Unsigned | Format::String | Format::Instruction => value.to_string(),
+    }
+}
+
+/// A byte as a C character literal, as `'A'`, `'\n'` or `'\377'`.
+fn char_literal(byte: u8) -> String {
+    match byte {
+        b'\n' => "'\\n'".to_string(),
+        b'\t' => "'\\t'".to_string(),
+        b'\r' => "'\\r'".to_string(),
+        0 => "'\\000'".to_string(),
+        b'\'' | b'\\' => format!("'\\{}'", byte as char),
+        0x20..=0x7e => format!("'{}'", byte as char),
+        _ => format!("'\\{:03o}'", byte),
+    }
+}
+
+/// Format units read from `address` as the lines of `x`, each starting with the address of its
+/// first unit.
+///
+/// # Arguments
+///
+/// * `bytes` - The memory read, a multiple of the size.
+/// * `address` - The address of the first byte.
+/// * `format` - A format of units: not a string nor an instruction.
+/// * `size` - The size of the units.
+pub fn format_units(bytes: &[u8], address: u64, format: Format, size: usize) -> Vec<String> {
+    bytes
+        .chunks(size * units_per_line(size))
+        .enumerate()
+        .map(|(index, line)| {
+            let units: Vec<String> = line.chunks(size).map(|unit| format_unit(unit, format)).collect();
+            format!("{:#x}:\t{}", address + (index * size * units_per_line(size)) as u64, units.join("\t"))
+        })
+        .collect()
+}
+
+/// Examine memory, `x[/<count><format><size>] <address>`, in the format of gdb: units of memory
+/// in hexadecimal, decimal, binary or as characters, NUL terminated strings, or instructions.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `args` - The arguments of the command, the first being `x` or `x/<format>`.
+pub fn examine_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    if args.len() != 2 {
+        errln!(debugger, "Usage: x[/<count><format><size>] <address>");
+        return false;
+    }
+    let examine = match parse_format(args[0].split_once('/').map_or("", |(_, format)| format)) {
+        Ok(examine) => examine,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let address = match location::parse_value(debugger, args[1], "address") {
+        Ok(address) => address,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    match examine.format {
+        Format::String => examine_strings(debugger, address, examine.count),
+        Format::Instruction => examine_instructions(debugger, address, examine.count),
+        format => examine_units(debugger, address, examine, format),
+    }
+}
+
+fn examine_units(debugger: &mut Debugger, address: u64, examine: Examine, format: Format) -> bool {
+    let Some(len) = examine.count.checked_mul(examine.size).filter(|len| *len <= MAX_EXAMINE_LEN) else {
+        errln!(debugger, "x reads at most {:#x} bytes at once.", MAX_EXAMINE_LEN);
+        return false;
+    };
+    let bytes = match memory::read_bytes(debugger.tracee().as_ref(), address, len) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    json::set_data(debugger, |_| {
+        let values: Vec<String> = bytes.chunks(examine.size).map(|unit| format_unit(unit, format)).collect();
+        json!({ "address": json::address(address), "format": format.name(), "size": examine.size, "values": values })
+    });
+    for line in format_units(&bytes, address, format, examine.size) {
+        outln!(debugger, "{}", line);
+    }
+    true
+}
+
+/// Show `count` NUL terminated strings, one after the other from `address`.
+fn examine_strings(debugger: &mut Debugger, address: u64, count: usize) -> bool {
+    let mut strings = Vec::new();
+    let mut current = address;
+    for _ in 0..count {
+        let bytes = match memory::read_c_bytes(debugger.tracee().as_ref(), current, MAX_STRING_LEN) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return false;
+            }
+        };
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        let truncated = bytes.len() == MAX_STRING_LEN;
+        outln!(debugger, "{:#x}:\t{:?}{}", current, text, if truncated { "..." } else { "" });
+        strings.push(json!({ "address": json::address(current), "text": text, "truncated": truncated }));
+        current += bytes.len() as u64 + 1;
+    }
+    json::set_data(debugger, |_| json!({ "address": json::address(address), "format": "string", "strings": strings }));
+    true
+}
+
+/// Disassemble `count` instructions from `address`, listed as `disas` lists them.
+fn examine_instructions(debugger: &mut Debugger, address: u64, count: usize) -> bool {
+    if let Err(err) = crate::arch::require_x86("x/i") {
+        errln!(debugger, "{}", err);
+        return false;
+    }
+    let bytes = match disasm::read_code(debugger.tracee().as_ref(), &debugger.breakpoint_table, address, count.saturating_mul(MAX_INSTRUCTION_LEN).min(MAX_EXAMINE_LEN)) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let instructions = disasm::disassemble(&bytes, address, count, debugger.disassembly_flavor, debugger.arch);
+    if instructions.is_empty() {
+        errln!(debugger, "No instructions decoded at {:#x}.", address);
+        return false;
+    }
+    let rip = debugger.tracee().getregs().ok().map(|regs| regs.pc());
+    json::set_data(debugger, |_| {
+        let listing: Vec<_> = instructions.iter().map(|instruction| json!({ "address": json::address(instruction.address), "text": instruction.text })).collect();
+        json!({ "address": json::address(address), "format": "instruction", "instructions": listing })
+    });
+    disasm::print_instructions(debugger, &instructions, rip);
+    true
+}
@@ -0,0 +1,77 @@
+use crate::debugger::Debugger;
+use crate::watch;
+use crate::working;
+use nix::sys::ptrace;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+
+/// Returns true if `status` is the stop of the program executing a new program, the
+/// `PTRACE_EVENT_EXEC` stop of `PTRACE_O_TRACEEXEC`.
+pub fn is_exec(status: &WaitStatus) -> bool {
+    matches!(*status, WaitStatus::PtraceEvent(_, _, event) if event == ptrace::Event::PTRACE_EVENT_EXEC as i32)
+}
+
+/// Returns true if a breakpoint location is an address of the program (`0x401126`, `$rip+4`, a
+/// label), which means nothing in another program.
+fn is_address(debugger: &Debugger, location: &str) -> bool {
+    let name = location.split('+').next().unwrap_or_default();
+    location.starts_with(|c: char| c.is_ascii_digit() || c == '$') || debugger.labels.contains_key(name)
+}
+
+/// Follow the program into the new program it executed: the breakpoints written in the memory
+/// of the previous one are gone with it, so its symbols are loaded, and the breakpoints set
+/// again on their function or line in the new program. The breakpoints on an address are
+/// dropped, and the ones on a function or line it doesn't have are pending until the next exec or
+/// `restart`.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `pid` - The process, stopped by its `PTRACE_EVENT_EXEC` event.
+pub fn follow_exec(debugger: &mut Debugger, pid: Pid) {
+    let path = match std::fs::read_link(format!("/proc/{}/exe", pid)) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(err) => {
+            warnln!(debugger, "Could not read the program executed by process {}: {}", pid, err);
+            return;
+        }
+    };
+    outln!(debugger, "Process {} is executing a new program: {}", pid, path);
+    working::clear_breakpoints(&mut debugger.breakpoint_table);
+    debugger.function_breakpoints.clear();
+    debugger.exec_process(&path);
+    for index in 0..debugger.breakpoint_locations.len() {
+        let number = index + 1;
+        if debugger.deleted_breakpoints.contains(&number) {
+            continue;
+        }
+        let location = debugger.breakpoint_locations[index].clone();
+        if is_address(debugger, &location) {
+            debugger.deleted_breakpoints.insert(number);
+            debugger.pending_breakpoints.remove(&number);
+            warnln!(debugger, "Breakpoint {} ({}) dropped: its address was in the previous program.", number, location);
+            continue;
+        }
+        let set = debugger.resolve_breakpoint(&location).and_then(|(address, function)| {
+            working::set_breakpoint(&mut debugger.breakpoint_table, debugger.child, address)?;
+            Ok((address, function))
+        });
+        match set {
+            Ok((address, function)) => {
+                debugger.breakpoints[index] = address;
+                if let Some(function) = function {
+                    debugger.function_breakpoints.insert(address, function);
+                }
+                debugger.pending_breakpoints.remove(&number);
+                outln!(debugger, "Breakpoint {} ({}) set again at {:#x}.", number, location, address);
+            }
+            Err(err) => {
+                log::debug!("Breakpoint {} ({}) not set again: {}", number, location, err);
+                debugger.pending_breakpoints.insert(number);
+                warnln!(debugger, "Breakpoint {} ({}) is pending, it is not in {}.", number, location, path);
+            }
+        }
+    }
+    watch::rewatch(debugger);
+    crate::pagewatch::forget(debugger);
+}
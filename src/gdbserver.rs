@@ -0,0 +1,605 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::disasm::read_code;
+use crate::error::DbgError;
+use crate::launch;
+use crate::memory;
+use crate::step::StepStop;
+use crate::tracee::Tracee;
+use crate::working::{self, keep_breakpoints};
+use nix::libc::user_regs_struct;
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// The largest packet accepted, announced in `qSupported`.
+const PACKET_SIZE: usize = 0x4000;
+/// The byte gdb sends, outside of any packet, to interrupt the running program (Ctrl-C).
+const INTERRUPT: u8 = 0x03;
+/// How often the interrupts are looked for while the program runs.
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
+/// The number gdb gives to a signal it does not know.
+const GDB_SIGNAL_UNKNOWN: u8 = 143;
+/// The size of the general registers in a `g` packet: rax to r15 and rip on 8 bytes, then eflags
+/// and the six segment registers on 4 bytes, in the order of gdb for amd64.
+#[cfg(target_arch = "x86_64")]
+pub const REGISTERS_SIZE: usize = 17 * 8 + 7 * 4;
+/// The size of the general registers in a `g` packet: x0 to x30, sp and pc on 8 bytes, then cpsr
+/// on 4 bytes, in the order of gdb for aarch64.
+#[cfg(target_arch = "aarch64")]
+pub const REGISTERS_SIZE: usize = 33 * 8 + 4;
+
+/// The Linux signals with their number in the remote protocol, which follows gdb's own numbering
+/// (`gdb/signals.def`) rather than the one of the system.
+const SIGNALS: &[(Signal, u8)] = &[
+    (Signal::SIGHUP, 1),
+    (Signal::SIGINT, 2),
+    (Signal::SIGQUIT, 3),
+    (Signal::SIGILL, 4),
+    (Signal::SIGTRAP, 5),
+    (Signal::SIGABRT, 6),
+    (Signal::SIGFPE, 8),
+    (Signal::SIGKILL, 9),
+    (Signal::SIGBUS, 10),
+    (Signal::SIGSEGV, 11),
+    (Signal::SIGSYS, 12),
+    (Signal::SIGPIPE, 13),
+    (Signal::SIGALRM, 14),
+    (Signal::SIGTERM, 15),
+    (Signal::SIGURG, 16),
+    (Signal::SIGSTOP, 17),
+    (Signal::SIGTSTP, 18),
+    (Signal::SIGCONT, 19),
+    (Signal::SIGCHLD, 20),
+    (Signal::SIGTTIN, 21),
+    (Signal::SIGTTOU, 22),
+    (Signal::SIGIO, 23),
+    (Signal::SIGXCPU, 24),
+    (Signal::SIGXFSZ, 25),
+    (Signal::SIGVTALRM, 26),
+    (Signal::SIGPROF, 27),
+    (Signal::SIGWINCH, 28),
+    (Signal::SIGUSR1, 30),
+    (Signal::SIGUSR2, 31),
+    (Signal::SIGPWR, 32),
+];
+
+/// The number of a signal in the remote protocol.
+pub fn to_gdb_signal(signal: Signal) -> u8 {
+    SIGNALS.iter().find(|(known, _)| *known == signal).map_or(GDB_SIGNAL_UNKNOWN, |(_, number)| *number)
+}
+
+/// The signal of a number of the remote protocol, `None` for 0 (no signal) or a signal Linux does
+/// not have.
+pub fn from_gdb_signal(number: u8) -> Option<Signal> {
+    SIGNALS.iter().find(|(_, known)| *known == number).map(|(signal, _)| *signal)
+}
+
+/// The checksum of the data of a packet: the sum of its bytes, modulo 256.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Frame the data of a packet as `$<data>#<checksum>`, escaping the `$`, `#`, `}` and `*` it
+/// contains as `}` followed by the byte xor 0x20.
+pub fn frame(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &byte in data {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            escaped.extend([b'}', byte ^ 0x20]);
+        } else {
+            escaped.push(byte);
+        }
+    }
+    let mut packet = vec![b'$'];
+    packet.extend(&escaped);
+    packet.extend(format!("#{:02x}", checksum(&escaped)).bytes());
+    packet
+}
+
+/// A connection to gdb. Until `QStartNoAckMode`, each packet is acknowledged with `+`, or with `-`
+/// when its checksum is wrong to have it sent again.
+pub struct Connection<R, W> {
+    reader: R,
+    writer: W,
+    /// False once gdb and the server agreed to stop acknowledging the packets.
+    pub ack: bool,
+}
+
+impl<R: BufRead, W: Write> Connection<R, W> {
+    /// A connection reading the packets of gdb from `reader` and writing the replies to `writer`.
+    pub fn new(reader: R, writer: W) -> Connection<R, W> {
+        Connection { reader, writer, ack: true }
+    }
+
+    /// Receive the next packet, acknowledging it. The acknowledgments and the interrupts read
+    /// between the packets are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The data of the packet, or `None` once gdb has closed the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the connection.
+    pub fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] != b'$' {
+                continue;
+            }
+            let mut data = Vec::new();
+            self.reader.read_until(b'#', &mut data)?;
+            if data.pop() != Some(b'#') {
+                return Ok(None);
+            }
+            let mut sum = [0; 2];
+            match self.reader.read_exact(&mut sum) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err),
+            }
+            if !self.ack {
+                return Ok(Some(data));
+            }
+            let valid = std::str::from_utf8(&sum).ok().and_then(|sum| u8::from_str_radix(sum, 16).ok()) == Some(checksum(&data));
+            self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            self.writer.flush()?;
+            if valid {
+                return Ok(Some(data));
+            }
+        }
+    }
+
+    /// Send a packet, again each time gdb answers `-`, until it acknowledges it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the connection, or [`io::ErrorKind::UnexpectedEof`] if gdb closes it
+    /// before acknowledging the packet.
+    pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let packet = frame(data);
+        loop {
+            self.writer.write_all(&packet)?;
+            self.writer.flush()?;
+            if !self.ack {
+                return Ok(());
+            }
+            loop {
+                let mut byte = [0];
+                if self.reader.read(&mut byte)? == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                match byte[0] {
+                    b'+' => return Ok(()),
+                    b'-' => break,
+                    _ => continue,
+                }
+            }
+        }
+    }
+}
+
+/// What the server does for a packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Send this reply.
+    Reply(Vec<u8>),
+    /// Resume the program, one instruction of the current thread with `step`, delivering `signal`
+    /// when continuing, then send the stop reply.
+    Resume { step: bool, signal: Option<Signal> },
+    /// Send this reply, if any, and end the session.
+    Close(Option<Vec<u8>>),
+}
+
+/// The bytes as hexadecimal digits.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The bytes written as hexadecimal digits.
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok()).collect()
+}
+
+/// An address or a length of a packet, in hexadecimal.
+fn parse_hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text, 16).ok()
+}
+
+/// A thread ID of a packet, in hexadecimal: `None` for `-1` (all threads) and `0` (any thread).
+fn parse_thread(text: &str) -> Option<Pid> {
+    match text {
+        "-1" | "0" => None,
+        tid => i32::from_str_radix(tid, 16).ok().map(Pid::from_raw),
+    }
+}
+
+/// The general registers in the layout of a `g` packet, [`REGISTERS_SIZE`] bytes. gdb fetches
+/// the other registers (x87, SSE) with `p`, and sees them as unavailable.
+#[cfg(target_arch = "x86_64")]
+pub fn encode_registers(regs: &user_regs_struct) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(REGISTERS_SIZE);
+    let wide = [
+        regs.rax, regs.rbx, regs.rcx, regs.rdx, regs.rsi, regs.rdi, regs.rbp, regs.rsp, regs.r8, regs.r9, regs.r10, regs.r11,
+        regs.r12, regs.r13, regs.r14, regs.r15, regs.rip,
+    ];
+    for value in wide {
+        bytes.extend(value.to_le_bytes());
+    }
+    for value in [regs.eflags, regs.cs, regs.ss, regs.ds, regs.es, regs.fs, regs.gs] {
+        bytes.extend((value as u32).to_le_bytes());
+    }
+    bytes
+}
+
+/// The general registers in the layout of a `g` packet, [`REGISTERS_SIZE`] bytes. gdb fetches
+/// the other registers (the vector ones) with `p`, and sees them as unavailable.
+#[cfg(target_arch = "aarch64")]
+pub fn encode_registers(regs: &user_regs_struct) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(REGISTERS_SIZE);
+    for value in regs.regs.into_iter().chain([regs.sp, regs.pc]) {
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes.extend((regs.pstate as u32).to_le_bytes());
+    bytes
+}
+
+/// Write the general registers of a `G` packet over `regs`, the other registers being kept.
+///
+/// # Returns
+///
+/// False if `bytes` is shorter than the general registers.
+#[cfg(target_arch = "x86_64")]
+pub fn decode_registers(bytes: &[u8], regs: &mut user_regs_struct) -> bool {
+    if bytes.len() < REGISTERS_SIZE {
+        return false;
+    }
+    let (wide, narrow) = bytes[..REGISTERS_SIZE].split_at(17 * 8);
+    let wide_registers = [
+        &mut regs.rax, &mut regs.rbx, &mut regs.rcx, &mut regs.rdx, &mut regs.rsi, &mut regs.rdi, &mut regs.rbp,
+        &mut regs.rsp, &mut regs.r8, &mut regs.r9, &mut regs.r10, &mut regs.r11, &mut regs.r12, &mut regs.r13,
+        &mut regs.r14, &mut regs.r15, &mut regs.rip,
+    ];
+    for (register, value) in wide_registers.into_iter().zip(wide.chunks(8)) {
+        *register = u64::from_le_bytes(value.try_into().unwrap());
+    }
+    let narrow_registers = [&mut regs.eflags, &mut regs.cs, &mut regs.ss, &mut regs.ds, &mut regs.es, &mut regs.fs, &mut regs.gs];
+    for (register, value) in narrow_registers.into_iter().zip(narrow.chunks(4)) {
+        *register = u32::from_le_bytes(value.try_into().unwrap()) as u64;
+    }
+    true
+}
+
+/// Write the general registers of a `G` packet over `regs`, the other registers being kept.
+///
+/// # Returns
+///
+/// False if `bytes` is shorter than the general registers.
+#[cfg(target_arch = "aarch64")]
+pub fn decode_registers(bytes: &[u8], regs: &mut user_regs_struct) -> bool {
+    if bytes.len() < REGISTERS_SIZE {
+        return false;
+    }
+    let (wide, narrow) = bytes[..REGISTERS_SIZE].split_at(33 * 8);
+    let wide_registers = regs.regs.iter_mut().chain([&mut regs.sp, &mut regs.pc]);
+    for (register, value) in wide_registers.zip(wide.chunks(8)) {
+        *register = u64::from_le_bytes(value.try_into().unwrap());
+    }
+    regs.pstate = u32::from_le_bytes(narrow.try_into().unwrap()) as u64;
+    true
+}
+
+/// The stop reply telling gdb why the program stopped, as `T05thread:4d2;swbreak:;` for a
+/// breakpoint, `W00` once it has exited or `X0b` once killed by a signal.
+pub fn stop_reply(debugger: &Debugger, stop: &StepStop) -> String {
+    let signal = match *stop {
+        StepStop::Exited(code) => return format!("W{:02x}", code as u8),
+        StepStop::Killed(signal) => return format!("X{:02x}", to_gdb_signal(signal)),
+        StepStop::Signal(signal) => to_gdb_signal(signal),
+        StepStop::Done | StepStop::Breakpoint(_) | StepStop::Watchpoint(_) => to_gdb_signal(Signal::SIGTRAP),
+    };
+    let mut reply = format!("T{:02x}thread:{:x};", signal, debugger.thread.as_raw());
+    if let StepStop::Breakpoint(address) = *stop {
+        // The pc is only rewound to the breakpoint when it was one set by gdb, not an int3 of the
+        // program.
+        if debugger.tracee().getregs().is_ok_and(|regs| regs.pc() == address) {
+            reply.push_str("swbreak:;");
+        }
+    }
+    reply
+}
+
+/// The part of a file from `offset` of at most `length` bytes, for `qXfer:<object>:read`: `l`
+/// then the bytes when it is the end of the file, `m` then the bytes otherwise.
+fn transfer(contents: &[u8], offset: usize, length: usize) -> Vec<u8> {
+    let start = offset.min(contents.len());
+    let end = start.saturating_add(length).min(contents.len());
+    let mut reply = vec![if end == contents.len() { b'l' } else { b'm' }];
+    reply.extend(&contents[start..end]);
+    reply
+}
+
+/// Read an object for `qXfer:<object>:read:<annex>:<offset>,<length>`: the auxiliary vector of
+/// the process, which gdb needs to find where a position independent executable is loaded, or
+/// the path of its executable.
+fn read_object(debugger: &Debugger, request: &str) -> Vec<u8> {
+    let mut fields = request.splitn(3, ':');
+    let (Some(object), Some(_annex), Some(range)) = (fields.next(), fields.next(), fields.next()) else {
+        return b"E00".to_vec();
+    };
+    let Some((offset, length)) = range.split_once(',').and_then(|(offset, length)| Some((parse_hex(offset)?, parse_hex(length)?))) else {
+        return b"E00".to_vec();
+    };
+    let contents = match object {
+        "auxv" => std::fs::read(format!("/proc/{}/auxv", debugger.child)),
+        "exec-file" => std::fs::read_link(format!("/proc/{}/exe", debugger.child))
+            .map(|path| path.to_string_lossy().into_owned().into_bytes())
+            .or_else(|_| Ok(debugger.program_path.clone().into_bytes())),
+        _ => return Vec::new(),
+    };
+    match contents {
+        Ok(contents) => transfer(&contents, offset as usize, length as usize),
+        Err(_) => b"E01".to_vec(),
+    }
+}
+
+/// The action of a `vCont` packet, as `vCont;s:4d2;c`: its first step, or else its first action.
+/// The actions apply to the current thread, or to the thread they name, which becomes the current
+/// thread.
+fn parse_vcont(debugger: &mut Debugger, actions: &str) -> Option<Action> {
+    let actions: Vec<(&str, Option<Pid>)> = actions
+        .split(';')
+        .map(|action| match action.split_once(':') {
+            Some((action, thread)) => (action, parse_thread(thread)),
+            None => (action, None),
+        })
+        .collect();
+    let (action, thread) = actions.iter().find(|(action, _)| action.starts_with(['s', 'S'])).or(actions.first())?;
+    let signal = match action.get(1..) {
+        Some("") => None,
+        Some(number) => from_gdb_signal(u8::from_str_radix(number, 16).ok()?),
+        None => return None,
+    };
+    let step = match action.as_bytes().first() {
+        Some(b's' | b'S') => true,
+        Some(b'c' | b'C') => false,
+        _ => return None,
+    };
+    if let Some(thread) = thread {
+        if !debugger.threads.iter().any(|known| known.tid == *thread) {
+            return None;
+        }
+        debugger.thread = *thread;
+    }
+    Some(Action::Resume { step, signal })
+}
+
+/// Handle a packet of gdb on the stopped program.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `packet` - The data of the packet.
+/// * `last_stop` - Why the program last stopped, for `?`.
+///
+/// # Returns
+///
+/// What to do: the reply to send, `E01` when the operation failed, or nothing for a packet not
+/// supported, as the protocol wants.
+pub fn handle(debugger: &mut Debugger, packet: &[u8], last_stop: &StepStop) -> Action {
+    let packet = String::from_utf8_lossy(packet);
+    let reply = |text: &str| Action::Reply(text.as_bytes().to_vec());
+    let ok = |result: Result<(), DbgError>| reply(if result.is_ok() { "OK" } else { "E01" });
+    let (kind, rest) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+    match kind {
+        "?" => reply(&stop_reply(debugger, last_stop)),
+        "g" => match debugger.tracee().getregs() {
+            Ok(regs) => reply(&hex(&encode_registers(&regs))),
+            Err(_) => reply("E01"),
+        },
+        "G" => {
+            let mut tracee = debugger.thread;
+            let Some(regs) = parse_hex_bytes(rest).zip(tracee.getregs().ok()).and_then(|(bytes, mut regs)| {
+                decode_registers(&bytes, &mut regs).then_some(regs)
+            }) else {
+                return reply("E01");
+            };
+            ok(tracee.setregs(regs).map_err(DbgError::ptrace("write the registers")))
+        }
+        "m" => {
+            let Some((address, length)) = rest.split_once(',').and_then(|(address, length)| Some((parse_hex(address)?, parse_hex(length)?))) else {
+                return reply("E01");
+            };
+            let length = (length as usize).min(PACKET_SIZE / 2);
+            match read_code(debugger.tracee().as_ref(), &debugger.breakpoint_table, address, length) {
+                Ok(bytes) => reply(&hex(&bytes)),
+                Err(_) => reply("E01"),
+            }
+        }
+        "M" => {
+            let Some((address, mut bytes)) = rest.split_once(':').and_then(|(range, data)| {
+                let address = parse_hex(range.split_once(',')?.0)?;
+                Some((address, parse_hex_bytes(data)?))
+            }) else {
+                return reply("E01");
+            };
+            keep_breakpoints(&mut debugger.breakpoint_table, address, &mut bytes);
+            ok(memory::write_bytes(debugger.thread, address, &bytes))
+        }
+        "Z" | "z" => {
+            let mut fields = rest.split(',');
+            let (Some("0"), Some(address)) = (fields.next(), fields.next().and_then(parse_hex)) else {
+                // Only the software breakpoints are supported.
+                return reply("");
+            };
+            if kind == "Z" {
+                return ok(working::set_breakpoint(&mut debugger.breakpoint_table, debugger.thread, address));
+            }
+            match working::remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, address) {
+                // A breakpoint is removed once hit, before gdb removes it.
+                Ok(()) | Err(DbgError::NoSuchBreakpoint(_)) => reply("OK"),
+                Err(_) => reply("E01"),
+            }
+        }
+        "c" | "s" => Action::Resume { step: kind == "s", signal: None },
+        "C" | "S" => {
+            let number = rest.split(';').next().and_then(|number| u8::from_str_radix(number, 16).ok());
+            Action::Resume { step: kind == "S", signal: number.and_then(from_gdb_signal) }
+        }
+        "H" => {
+            let thread = rest.get(1..).and_then(parse_thread);
+            match thread {
+                Some(thread) if !debugger.threads.iter().any(|known| known.tid == thread) => reply("E01"),
+                Some(thread) => {
+                    debugger.thread = thread;
+                    reply("OK")
+                }
+                None => reply("OK"),
+            }
+        }
+        "T" => match parse_thread(rest) {
+            Some(thread) if debugger.threads.iter().any(|known| known.tid == thread) => reply("OK"),
+            _ => reply("E01"),
+        },
+        "D" => {
+            launch::detach(debugger);
+            Action::Close(Some(b"OK".to_vec()))
+        }
+        "k" => {
+            launch::kill(debugger);
+            Action::Close(None)
+        }
+        _ => match packet.as_ref() {
+            "qSupported" => reply("PacketSize=4000;QStartNoAckMode+;swbreak+;vContSupported+;qXfer:auxv:read+;qXfer:exec-file:read+"),
+            packet if packet.starts_with("qSupported:") => handle(debugger, b"qSupported", last_stop),
+            "qAttached" => reply(if debugger.attached { "1" } else { "0" }),
+            packet if packet.starts_with("qAttached:") => handle(debugger, b"qAttached", last_stop),
+            "qC" => reply(&format!("QC{:x}", debugger.thread.as_raw())),
+            "qfThreadInfo" => {
+                let threads: Vec<String> = debugger.threads.iter().map(|thread| format!("{:x}", thread.tid.as_raw())).collect();
+                reply(&format!("m{}", threads.join(",")))
+            }
+            "qsThreadInfo" => reply("l"),
+            "qSymbol::" => reply("OK"),
+            "vCont?" => reply("vCont;c;C;s;S"),
+            packet if packet.starts_with("vCont;") => parse_vcont(debugger, &packet["vCont;".len()..]).unwrap_or_else(|| reply("E01")),
+            packet if packet.starts_with("qXfer:") => {
+                let request = &packet["qXfer:".len()..];
+                match request.split_once(':') {
+                    Some((object, request)) if request.starts_with("read:") => {
+                        Action::Reply(read_object(debugger, &format!("{}:{}", object, &request["read:".len()..])))
+                    }
+                    _ => reply(""),
+                }
+            }
+            packet if packet == "vKill" || packet.starts_with("vKill;") => {
+                launch::kill(debugger);
+                Action::Close(Some(b"OK".to_vec()))
+            }
+            _ => reply(""),
+        },
+    }
+}
+
+/// Resume the program for gdb, sending it `SIGINT` when gdb interrupts it meanwhile, as Ctrl-C in
+/// gdb does.
+fn resume(debugger: &mut Debugger, stream: &TcpStream, step: bool, signal: Option<Signal>) -> Result<StepStop, DbgError> {
+    if step {
+        // The signal of `S` is not delivered by a single step.
+        return debugger.step();
+    }
+    let Ok(mut watched) = stream.try_clone() else {
+        return debugger.cont_with_signal(signal);
+    };
+    let (running, child) = (AtomicBool::new(true), debugger.child);
+    let stop = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            // Only an interrupt comes while the program runs, until the stop reply.
+            watched.set_read_timeout(Some(INTERRUPT_POLL)).ok();
+            let mut byte = [0];
+            while running.load(Ordering::Relaxed) {
+                match watched.read(&mut byte) {
+                    Ok(0) => break,
+                    Ok(_) if byte[0] == INTERRUPT => {
+                        signal::kill(child, Signal::SIGINT).ok();
+                    }
+                    _ => {}
+                }
+            }
+        });
+        let stop = debugger.cont_with_signal(signal);
+        running.store(false, Ordering::Relaxed);
+        stop
+    });
+    stream.set_read_timeout(None).ok();
+    stop
+}
+
+/// Let gdb debug the program with the remote protocol (`target remote <host>:<port>` in gdb),
+/// listening on `address` (`[host]:port`, `:1234` listening on the local host) for a single
+/// connection. The packets are translated into the operations of the debugger until gdb detaches,
+/// kills the program or disconnects, or the program terminates.
+///
+/// # Returns
+///
+/// The exit status of the debugger: the one of the program once it has terminated, 0 otherwise.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Remote`] if the address can't be listened on or the connection fails.
+pub fn serve(debugger: &mut Debugger, address: &str) -> Result<i32, DbgError> {
+    let error = |err: io::Error| DbgError::Remote { address: address.to_string(), reason: err.to_string() };
+    let listened = if address.starts_with(':') { format!("localhost{}", address) } else { address.to_string() };
+    let listener = TcpListener::bind(listened).map_err(error)?;
+    outln!(debugger, "Listening on port {}", listener.local_addr().map_err(error)?.port());
+    let (stream, peer) = listener.accept().map_err(error)?;
+    outln!(debugger, "Remote debugging from host {}", peer.ip());
+    stream.set_nodelay(true).ok();
+    let mut connection = Connection::new(BufReader::new(stream.try_clone().map_err(error)?), stream.try_clone().map_err(error)?);
+    let mut last_stop = StepStop::Done;
+    while let Some(packet) = connection.receive().map_err(error)? {
+        if packet == b"QStartNoAckMode" {
+            connection.send(b"OK").map_err(error)?;
+            connection.ack = false;
+            continue;
+        }
+        match handle(debugger, &packet, &last_stop) {
+            Action::Reply(reply) => connection.send(&reply).map_err(error)?,
+            Action::Resume { step, signal } => {
+                let stop = match resume(debugger, &stream, step, signal) {
+                    Ok(stop) => stop,
+                    Err(err) => {
+                        errln!(debugger, "{}", err);
+                        connection.send(b"E01").map_err(error)?;
+                        continue;
+                    }
+                };
+                connection.send(stop_reply(debugger, &stop).as_bytes()).map_err(error)?;
+                last_stop = stop;
+                if let Some(status) = debugger.exit_status() {
+                    outln!(debugger, "Child exited with status {}", status);
+                    return Ok(status);
+                }
+            }
+            Action::Close(reply) => {
+                if let Some(reply) = reply {
+                    connection.send(&reply).map_err(error)?;
+                }
+                return Ok(0);
+            }
+        }
+    }
+    // gdb went away without detaching nor killing the program.
+    launch::release(debugger);
+    Ok(0)
+}
@@ -0,0 +1,414 @@
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::{json, logging};
+use nix::errno::Errno;
+use nix::fcntl::{self, FcntlArg, FdFlag, OFlag};
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::pty::openpty;
+use nix::sys::stat::Mode;
+use nix::unistd;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the reading thread waits for the program to write before checking whether it should
+/// stop, in milliseconds.
+const POLL_INTERVAL: u16 = 200;
+/// How long a stop waits for the reading thread to read what the program wrote.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long the end of the program waits for its output to be closed, which a process it left
+/// running can keep open.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Where the standard output and error of the program go (`set inferior-tty`, `tty`).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum InferiorTty {
+    /// Read by the debugger, which prints their lines prefixed with `[out]` and `[err]`.
+    #[default]
+    Capture,
+    /// The terminal of the debugger, shared with the program.
+    Inherit,
+    /// Another terminal, as `/dev/pts/3`, its standard input too.
+    Path(String),
+}
+
+impl InferiorTty {
+    /// Parse `capture`, `inherit` or the path of a terminal.
+    pub fn parse(value: &str) -> InferiorTty {
+        match value {
+            "capture" => InferiorTty::Capture,
+            "inherit" => InferiorTty::Inherit,
+            path => InferiorTty::Path(path.to_string()),
+        }
+    }
+
+    /// The name of `set inferior-tty`: `capture`, `inherit` or the path.
+    pub fn name(&self) -> &str {
+        match self {
+            InferiorTty::Capture => "capture",
+            InferiorTty::Inherit => "inherit",
+            InferiorTty::Path(path) => path,
+        }
+    }
+}
+
+/// A stream written by the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    /// The prefix of its lines, `[out]` or `[err]`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Stream::Stdout => "[out]",
+            Stream::Stderr => "[err]",
+        }
+    }
+
+    /// Its name in the JSON records, `stdout` or `stderr`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        }
+    }
+}
+
+/// The descriptors given to the program as its standard streams, opened before it is started.
+#[derive(Debug, Default)]
+pub struct ChildStdio {
+    /// The standard input, output and error of the program, None where it inherits the one of the
+    /// debugger.
+    child: [Option<OwnedFd>; 3],
+    /// The ends the debugger reads the output and the error of the program from, when captured.
+    readers: Vec<(Stream, OwnedFd)>,
+    /// The end the debugger writes the standard input of the program to (`input`), when it is a
+    /// pipe.
+    input: Option<OwnedFd>,
+}
+
+impl ChildStdio {
+    /// Make the descriptors the standard streams of the process, in the child after fork, so only
+    /// with async-signal-safe calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errno of `dup2` if it failed.
+    pub fn install(&self) -> Result<(), Errno> {
+        for (target, fd) in (0..).zip(&self.child) {
+            if let Some(fd) = fd {
+                // The copy is not closed on exec, unlike the descriptors opened by the debugger.
+                unistd::dup2(fd.as_raw_fd(), target)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Open the descriptors the program is started with, for `tty`: a pseudo-terminal per stream
+/// when it is captured and the debugger writes to a terminal, so that the program still flushes
+/// its lines as it writes them, or else a pipe per stream. A captured program reads a pipe written
+/// by `input`.
+///
+/// # Arguments
+///
+/// * `tty` - Where the standard streams of the program go.
+/// * `stdin_file` - The file the program reads as its standard input instead (`run < file`).
+///
+/// # Errors
+///
+/// Returns [`DbgError::InferiorTty`] if a file, a terminal or a pipe can't be opened.
+pub fn prepare(tty: &InferiorTty, stdin_file: Option<&str>) -> Result<ChildStdio, DbgError> {
+    let mut stdio = match tty {
+        InferiorTty::Inherit => ChildStdio::default(),
+        InferiorTty::Path(path) => {
+            let fd = open(path, OFlag::O_RDWR | OFlag::O_NOCTTY)?;
+            let copy = || fd.try_clone().map_err(|_| DbgError::InferiorTty { tty: path.clone(), errno: Errno::last() });
+            ChildStdio { child: [Some(copy()?), Some(copy()?), Some(fd)], readers: Vec::new(), input: None }
+        }
+        InferiorTty::Capture => {
+            let terminal = unistd::isatty(libc::STDOUT_FILENO).unwrap_or(false);
+            let mut stdio = ChildStdio::default();
+            if stdin_file.is_none() {
+                let (reader, writer) = unistd::pipe2(OFlag::O_CLOEXEC).map_err(failed("a pipe"))?;
+                // `input` fails instead of waiting when the program does not read.
+                fcntl::fcntl(writer.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).map_err(failed("a pipe"))?;
+                stdio.child[0] = Some(reader);
+                stdio.input = Some(writer);
+            }
+            for (target, stream) in [(1, Stream::Stdout), (2, Stream::Stderr)] {
+                let (reader, writer) = if terminal {
+                    let pty = openpty(None, None).map_err(failed("a pseudo-terminal"))?;
+                    for fd in [&pty.master, &pty.slave] {
+                        fcntl::fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map_err(failed("a pseudo-terminal"))?;
+                    }
+                    (pty.master, pty.slave)
+                } else {
+                    unistd::pipe2(OFlag::O_CLOEXEC).map_err(failed("a pipe"))?
+                };
+                stdio.child[target] = Some(writer);
+                stdio.readers.push((stream, reader));
+            }
+            stdio
+        }
+    };
+    if let Some(path) = stdin_file {
+        stdio.child[0] = Some(open(path, OFlag::O_RDONLY)?);
+    }
+    Ok(stdio)
+}
+
+/// Open `path` for the program, closed on exec in the debugger.
+fn open(path: &str, flags: OFlag) -> Result<OwnedFd, DbgError> {
+    let fd = fcntl::open(path, flags | OFlag::O_CLOEXEC, Mode::empty()).map_err(failed(path))?;
+    // Owned from here, closed once the program has it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Split the words given to `run` and `set args` into the arguments of the program and the file
+/// after `<`, which it reads as its standard input.
+///
+/// # Errors
+///
+/// Returns a message if `<` is not followed by a file.
+pub fn parse_arguments(words: &[&str]) -> Result<(Vec<String>, Option<String>), String> {
+    let mut arguments = Vec::new();
+    let mut stdin_file = None;
+    let mut words = words.iter();
+    while let Some(word) = words.next() {
+        match word.strip_prefix('<') {
+            Some("") => match words.next() {
+                Some(path) => stdin_file = Some(path.to_string()),
+                None => return Err("< requires the file the program reads".to_string()),
+            },
+            Some(path) => stdin_file = Some(path.to_string()),
+            None => arguments.push(word.to_string()),
+        }
+    }
+    Ok((arguments, stdin_file))
+}
+
+/// Format the arguments of the program and its input file as `run` takes them, as `-v < in.txt`.
+pub fn format_arguments(arguments: &[String], stdin_file: Option<&str>) -> String {
+    let mut words = arguments.to_vec();
+    if let Some(path) = stdin_file {
+        words.push(format!("< {}", path));
+    }
+    words.join(" ")
+}
+
+/// Write `text` to the standard input of the program, when it is a pipe of the debugger.
+///
+/// # Errors
+///
+/// Returns a message if the standard input of the program is not a pipe, was closed, or is full
+/// because the program does not read it.
+pub fn write_input(debugger: &mut Debugger, text: &str) -> Result<(), String> {
+    let Some(input) = &mut debugger.program_input else {
+        return Err("The standard input of the program is not a pipe of the debugger, or input --eof closed it. It is one \
+                    when the output is captured (set inferior-tty capture) and no < file is given, from the next restart."
+            .to_string());
+    };
+    match input.write_all(text.as_bytes()) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+            Err("The standard input of the program is full: continue it so that it reads, then send the rest.".to_string())
+        }
+        Err(err) => Err(format!("Cannot write to the standard input of the program: {}", err)),
+    }
+}
+
+/// The error of opening `tty` for the program.
+fn failed(tty: &str) -> impl FnOnce(Errno) -> DbgError + '_ {
+    move |errno| DbgError::InferiorTty { tty: tty.to_string(), errno }
+}
+
+/// The standard output and error of the program, read by a thread of the debugger as the program
+/// writes them. The thread stops when the capture is dropped.
+pub struct Capture {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// The state shared with the reading thread.
+struct Shared {
+    /// The streams not closed yet.
+    readers: Vec<Reader>,
+    /// The lines read, not yet written to the transcript or as JSON records.
+    lines: Vec<(Stream, String)>,
+    /// True if the thread prints the lines as they come, on the terminal of the debugger.
+    print: bool,
+    /// Set when the capture is dropped.
+    stopped: bool,
+}
+
+/// A stream being read.
+struct Reader {
+    stream: Stream,
+    fd: OwnedFd,
+    /// The end of the last line, not written yet.
+    partial: Vec<u8>,
+}
+
+impl Shared {
+    /// Read what the program wrote to the reader at `index`, closing it at the end.
+    fn read(&mut self, index: usize, buffer: &mut [u8]) {
+        let reader = &mut self.readers[index];
+        match unistd::read(reader.fd.as_raw_fd(), buffer) {
+            Err(Errno::EINTR | Errno::EAGAIN) => {}
+            // A pseudo-terminal fails with EIO once the program closed it.
+            Ok(0) | Err(_) => {
+                let reader = self.readers.remove(index);
+                if !reader.partial.is_empty() {
+                    self.push(reader.stream, line_text(&reader.partial));
+                }
+            }
+            Ok(count) => {
+                reader.partial.extend_from_slice(&buffer[..count]);
+                let stream = reader.stream;
+                let mut lines = Vec::new();
+                while let Some(end) = reader.partial.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = reader.partial.drain(..=end).collect();
+                    lines.push(line_text(&line[..end]));
+                }
+                for line in lines {
+                    self.push(stream, line);
+                }
+            }
+        }
+    }
+
+    /// Keep a line read, printing it first on a terminal.
+    fn push(&mut self, stream: Stream, line: String) {
+        if self.print {
+            logging::print_line(&format!("{} {}", stream.label(), line), stream == Stream::Stderr);
+        }
+        self.lines.push((stream, line));
+    }
+}
+
+/// The text of a line, without the carriage return a pseudo-terminal adds.
+fn line_text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes.strip_suffix(b"\r").unwrap_or(bytes)).into_owned()
+}
+
+/// The number of bytes that can be read from `fd` without waiting.
+fn pending(fd: RawFd) -> usize {
+    let mut count: libc::c_int = 0;
+    if unsafe { libc::ioctl(fd, libc::FIONREAD, &mut count) } < 0 {
+        return 0;
+    }
+    count as usize
+}
+
+impl Capture {
+    /// Start reading the captured streams of `stdio`, printing their lines as they come if
+    /// `print`, and else only keeping them for [`flush`].
+    fn start(readers: Vec<(Stream, OwnedFd)>, print: bool) -> Option<Capture> {
+        if readers.is_empty() {
+            return None;
+        }
+        let readers = readers.into_iter().map(|(stream, fd)| Reader { stream, fd, partial: Vec::new() }).collect();
+        let shared = Arc::new(Mutex::new(Shared { readers, lines: Vec::new(), print, stopped: false }));
+        let thread_shared = shared.clone();
+        std::thread::Builder::new().name("inferior-output".to_string()).spawn(move || read_streams(thread_shared)).ok()?;
+        Some(Capture { shared })
+    }
+
+    /// Take the lines read, once the thread has read everything the program wrote so far, or
+    /// until the streams are closed if `until_closed`. The end of an unfinished line is taken
+    /// too, as a line of its own.
+    ///
+    /// # Returns
+    ///
+    /// Each line, with true if the thread printed it already.
+    fn take_lines(&self, until_closed: bool) -> Vec<(Stream, String, bool)> {
+        let deadline = Instant::now() + if until_closed { CLOSE_TIMEOUT } else { READ_TIMEOUT };
+        loop {
+            let Ok(mut shared) = self.shared.lock() else {
+                return Vec::new();
+            };
+            // The thread reads while holding the lock, so nothing pending is all read and kept.
+            let done = if until_closed { shared.readers.is_empty() } else { shared.readers.iter().all(|reader| pending(reader.fd.as_raw_fd()) == 0) };
+            if done || Instant::now() >= deadline {
+                let print = shared.print;
+                let mut lines: Vec<_> = shared.lines.drain(..).map(|(stream, line)| (stream, line, print)).collect();
+                for reader in &mut shared.readers {
+                    if !reader.partial.is_empty() {
+                        lines.push((reader.stream, line_text(&std::mem::take(&mut reader.partial)), false));
+                    }
+                }
+                return lines;
+            }
+            drop(shared);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.stopped = true;
+        }
+    }
+}
+
+/// The reading thread: waits for the program to write, and reads, until the streams are closed or
+/// the capture is dropped.
+fn read_streams(shared: Arc<Mutex<Shared>>) {
+    let mut buffer = [0; 4096];
+    loop {
+        let fds: Vec<RawFd> = match shared.lock() {
+            Ok(shared) if !shared.stopped && !shared.readers.is_empty() => shared.readers.iter().map(|reader| reader.fd.as_raw_fd()).collect(),
+            _ => return,
+        };
+        // Only this thread closes the descriptors, they stay open meanwhile.
+        let mut poll_fds: Vec<PollFd> = fds.iter().map(|fd| PollFd::new(unsafe { BorrowedFd::borrow_raw(*fd) }, PollFlags::POLLIN)).collect();
+        match poll(&mut poll_fds, PollTimeout::from(POLL_INTERVAL)) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(_) => return,
+        }
+        let ready: Vec<bool> = poll_fds.iter().map(|fd| fd.revents().is_some_and(|events| !events.is_empty())).collect();
+        let Ok(mut shared) = shared.lock() else {
+            return;
+        };
+        // From the last, as a closed stream is removed.
+        for (index, _) in ready.iter().enumerate().rev().filter(|(_, ready)| **ready) {
+            shared.read(index, &mut buffer);
+        }
+    }
+}
+
+/// Start reading the standard output and error of the program just started with `stdio`, when
+/// they are captured, closing the ends the program got, and keep the end of its standard input. The lines are printed as they come,
+/// except in JSON mode, where [`flush`] writes them as records.
+pub fn capture(debugger: &mut Debugger, stdio: ChildStdio) {
+    debugger.capture = Capture::start(stdio.readers, debugger.json.is_none());
+    debugger.program_input = stdio.input.map(File::from);
+}
+
+/// Write the lines the program wrote since the last flush to the transcript, or as
+/// `{"type": "output", ...}` records in JSON mode, and print the end of an unfinished line. Once
+/// the program has terminated, its output is read to the end first.
+pub fn flush(debugger: &mut Debugger) {
+    let Some(capture) = &debugger.capture else {
+        return;
+    };
+    for (stream, line, printed) in capture.take_lines(debugger.state.has_terminated()) {
+        let labeled = format!("{} {}", stream.label(), line);
+        if debugger.json.is_some() {
+            debugger.transcript.write_output(&labeled);
+            json::output_event(debugger, stream, &line);
+        } else if printed {
+            debugger.transcript.write_output(&labeled);
+        } else {
+            outln!(debugger, "{}", labeled);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::location;
+use crate::tracee::Tracee;
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use serde_json::json;
+
+/// `syscall`, the system call instruction of x86_64.
+#[cfg(target_arch = "x86_64")]
+const SYSCALL_INSTRUCTION: [u8; 2] = [0x0f, 0x05];
+/// `int 0x80`, the system call instruction of i386.
+#[cfg(target_arch = "x86_64")]
+const INT80_INSTRUCTION: [u8; 2] = [0xcd, 0x80];
+/// `svc #0`, the system call instruction of aarch64.
+#[cfg(target_arch = "aarch64")]
+const SVC_INSTRUCTION: [u8; 4] = [0x01, 0x00, 0x00, 0xd4];
+/// The system call numbers of `mmap` and `munmap` on x86_64.
+#[cfg(target_arch = "x86_64")]
+const MMAP_X86_64: u64 = 9;
+#[cfg(target_arch = "x86_64")]
+const MUNMAP_X86_64: u64 = 11;
+/// The system call numbers of `mmap2` (offset in pages) and `munmap` on i386.
+#[cfg(target_arch = "x86_64")]
+const MMAP2_I386: u64 = 192;
+#[cfg(target_arch = "x86_64")]
+const MUNMAP_I386: u64 = 91;
+/// The system call numbers of `mprotect` on x86_64 and i386.
+#[cfg(target_arch = "x86_64")]
+const MPROTECT_X86_64: u64 = 10;
+#[cfg(target_arch = "x86_64")]
+const MPROTECT_I386: u64 = 125;
+/// The system call numbers of `mmap`, `munmap` and `mprotect` on aarch64.
+#[cfg(target_arch = "aarch64")]
+const MMAP_AARCH64: u64 = 222;
+#[cfg(target_arch = "aarch64")]
+const MUNMAP_AARCH64: u64 = 215;
+#[cfg(target_arch = "aarch64")]
+const MPROTECT_AARCH64: u64 = 226;
+/// The size of the pages mapped, the granularity of `alloc` and `dealloc`.
+const PAGE_SIZE: u64 = 4096;
+
+/// Make the current thread of the program run a system call, then put it back as it was.
+///
+/// The system call instruction is written over the instruction at the pc, executed with a single
+/// step, and the original bytes and registers restored. The other threads stay stopped.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session, with a live process stopped out of a
+///   system call.
+/// * `number` - The number of the system call, for the architecture of the program.
+/// * `arguments` - Its arguments, at most 6: in `rdi`, `rsi`, `rdx`, `r10`, `r8` and `r9` on
+///   x86_64, `ebx`, `ecx`, `edx`, `esi`, `edi` and `ebp` on i386, `x0` to `x5` on aarch64.
+///
+/// # Returns
+///
+/// The value returned by the system call, in `rax` or `x0`, a negated errno on failure.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the registers can't be accessed or the step fails,
+/// [`DbgError::Memory`] if the code at the pc can't be patched, or [`DbgError::Syscall`] if the
+/// program is stopped in a system call or does not come back from the step.
+pub fn inject_syscall(debugger: &mut Debugger, number: u64, arguments: &[u64]) -> Result<u64, DbgError> {
+    if debugger.in_syscall {
+        let reason = "the program is stopped in a system call, step out of it first".to_string();
+        return Err(DbgError::Syscall { name: debugger.arch.syscall_name(number), reason });
+    }
+    inject_syscall_in(debugger.arch, debugger.thread, number, arguments)
+}
+
+/// Make `thread` run a system call as [`inject_syscall`] does for the current thread, the thread
+/// being stopped out of a system call, as at a fault.
+///
+/// # Errors
+///
+/// Same as [`inject_syscall`].
+pub fn inject_syscall_in(arch: Arch, mut thread: Pid, number: u64, arguments: &[u64]) -> Result<u64, DbgError> {
+    let saved = thread.getregs().map_err(DbgError::ptrace("read the registers"))?;
+    let mut regs = saved;
+    let instruction: &[u8] = match arch {
+        #[cfg(target_arch = "x86_64")]
+        Arch::X86_64 => &SYSCALL_INSTRUCTION,
+        #[cfg(target_arch = "x86_64")]
+        Arch::I386 => &INT80_INSTRUCTION,
+        #[cfg(target_arch = "aarch64")]
+        Arch::Aarch64 => &SVC_INSTRUCTION,
+    };
+    for (register, value) in arch.syscall_argument_registers(&mut regs).into_iter().zip(arguments) {
+        *register = *value;
+    }
+    arch.set_syscall_number(&mut regs, number);
+    // Not in a system call anymore, which the kernel would restart.
+    regs.cancel_syscall_restart();
+    let pc = saved.pc();
+    let original = thread.read_word(pc).map_err(DbgError::memory(pc))?;
+    let mut patched = original.to_le_bytes();
+    patched[..instruction.len()].copy_from_slice(instruction);
+    crate::cache::invalidate();
+    thread.write_word(pc, i64::from_le_bytes(patched)).map_err(DbgError::memory(pc))?;
+    log::debug!("Injecting the system call {} at {:#x}", number, pc);
+    let stepped = thread
+        .setregs(regs)
+        .and_then(|()| thread.step())
+        .map_err(DbgError::ptrace("single-step"))
+        .and_then(|()| thread.wait().map_err(DbgError::ptrace("wait for the program")));
+    let returned = thread.getregs().map(|regs| regs.return_value());
+    crate::cache::invalidate();
+    thread.write_word(pc, original).map_err(DbgError::memory(pc))?;
+    thread.setregs(saved).map_err(DbgError::ptrace("write the registers"))?;
+    match stepped? {
+        WaitStatus::Stopped(_, Signal::SIGTRAP) => returned.map_err(DbgError::ptrace("read the registers")),
+        status => {
+            log::debug!("The injected system call stopped with {:?}", status);
+            Err(DbgError::Syscall { name: arch.syscall_name(number), reason: format!("the program stopped with {:?}", status) })
+        }
+    }
+}
+
+/// The errno of the value returned by a system call, if it failed: -4095 to -1.
+pub(crate) fn syscall_error(arch: Arch, returned: u64) -> Option<Errno> {
+    let value = arch.return_value(returned) as i64;
+    let value = if arch.is_i386() { value as i32 as i64 } else { value };
+    (-4095..0).contains(&value).then(|| Errno::from_raw(-value as i32))
+}
+
+/// Map `size` bytes of anonymous, readable and writable memory in the program, with an injected
+/// `mmap` system call (see [`inject_syscall`]).
+///
+/// # Returns
+///
+/// The address of the memory, aligned on a page.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Syscall`] if `mmap` fails, or the error of [`inject_syscall`].
+pub fn allocate(debugger: &mut Debugger, size: u64) -> Result<u64, DbgError> {
+    let protection = (libc::PROT_READ | libc::PROT_WRITE) as u64;
+    let flags = (libc::MAP_PRIVATE | libc::MAP_ANONYMOUS) as u64;
+    let arch = debugger.arch;
+    let (number, fd) = match arch {
+        #[cfg(target_arch = "x86_64")]
+        Arch::X86_64 => (MMAP_X86_64, u64::MAX),
+        #[cfg(target_arch = "x86_64")]
+        Arch::I386 => (MMAP2_I386, u32::MAX as u64),
+        #[cfg(target_arch = "aarch64")]
+        Arch::Aarch64 => (MMAP_AARCH64, u64::MAX),
+    };
+    let returned = inject_syscall(debugger, number, &[0, size, protection, flags, fd, 0])?;
+    match syscall_error(arch, returned) {
+        Some(errno) => Err(DbgError::Syscall { name: arch.syscall_name(number), reason: errno.to_string() }),
+        None => Ok(arch.return_value(returned)),
+    }
+}
+
+/// Unmap `size` bytes of memory of the program at `address`, with an injected `munmap` system call
+/// (see [`inject_syscall`]).
+///
+/// # Errors
+///
+/// Returns [`DbgError::Syscall`] if `munmap` fails, as when `address` is not aligned on a page,
+/// or the error of [`inject_syscall`].
+pub fn deallocate(debugger: &mut Debugger, address: u64, size: u64) -> Result<(), DbgError> {
+    let arch = debugger.arch;
+    let number = match arch {
+        #[cfg(target_arch = "x86_64")]
+        Arch::X86_64 => MUNMAP_X86_64,
+        #[cfg(target_arch = "x86_64")]
+        Arch::I386 => MUNMAP_I386,
+        #[cfg(target_arch = "aarch64")]
+        Arch::Aarch64 => MUNMAP_AARCH64,
+    };
+    let returned = inject_syscall(debugger, number, &[address, size])?;
+    match syscall_error(arch, returned) {
+        Some(errno) => Err(DbgError::Syscall { name: arch.syscall_name(number), reason: errno.to_string() }),
+        None => Ok(()),
+    }
+}
+
+/// Change the protection of `len` bytes of memory of the program at `address`, with an `mprotect`
+/// system call injected in `thread` (see [`inject_syscall_in`]).
+///
+/// # Arguments
+///
+/// * `arch` - The architecture of the program.
+/// * `thread` - A thread of the program stopped out of a system call.
+/// * `address` - The first byte, aligned on a page.
+/// * `len` - The number of bytes, the pages containing them being changed.
+/// * `protection` - The `PROT_` bits.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Syscall`] if `mprotect` fails, or the error of [`inject_syscall_in`].
+pub fn protect(arch: Arch, thread: Pid, address: u64, len: u64, protection: i32) -> Result<(), DbgError> {
+    let number = match arch {
+        #[cfg(target_arch = "x86_64")]
+        Arch::X86_64 => MPROTECT_X86_64,
+        #[cfg(target_arch = "x86_64")]
+        Arch::I386 => MPROTECT_I386,
+        #[cfg(target_arch = "aarch64")]
+        Arch::Aarch64 => MPROTECT_AARCH64,
+    };
+    let returned = inject_syscall_in(arch, thread, number, &[address, len, protection as u64])?;
+    match syscall_error(arch, returned) {
+        Some(errno) => Err(DbgError::Syscall { name: arch.syscall_name(number), reason: errno.to_string() }),
+        None => Ok(()),
+    }
+}
+
+/// Map memory in the program (`alloc <size>`) and print its address.
+///
+/// # Returns
+///
+/// False if the size is invalid or the memory can't be mapped.
+pub fn alloc(debugger: &mut Debugger, size: &str) -> bool {
+    let size = match location::parse_value(debugger, size, "size") {
+        Ok(0) => {
+            errln!(debugger, "The size must be at least 1.");
+            return false;
+        }
+        Ok(size) => size,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    match debugger.allocate(size) {
+        Ok(address) => {
+            let mapped = size.div_ceil(PAGE_SIZE) * PAGE_SIZE;
+            outln!(debugger, "Allocated {} bytes at {:#x} ({} mapped, readable and writable).", size, address, mapped);
+            json::set_data(debugger, |_| json!({ "address": json::address(address), "size": size, "mapped": mapped }));
+            true
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
+
+/// Unmap memory of the program (`dealloc <address> <size>`).
+///
+/// # Returns
+///
+/// False if an argument is invalid or the memory can't be unmapped.
+pub fn dealloc(debugger: &mut Debugger, address: &str, size: &str) -> bool {
+    let range = location::parse_value(debugger, address, "address").and_then(|address| Ok((address, location::parse_value(debugger, size, "size")?)));
+    let (address, size) = match range {
+        Ok(range) => range,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    match debugger.deallocate(address, size) {
+        Ok(()) => {
+            outln!(debugger, "Freed {} bytes at {:#x}.", size, address);
+            true
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
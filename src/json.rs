@@ -0,0 +1,191 @@
+use crate::debugger::Debugger;
+use crate::inferior::Stream;
+use crate::output::{JsonText, Lines, Output};
+use crate::state::{InferiorState, StopReason};
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// The state of `--interpreter json`, where each command run writes one JSON record
+/// `{"command": ..., "ok": ..., "data": ...}` and each stop of the program an asynchronous
+/// `{"type": "stop", ...}` record, each line of the program a `{"type": "output", ...}` record when
+/// its output is captured, while the text for people goes to the standard error.
+pub struct JsonState {
+    /// The lines written by the command being run, or by its caller when it is run by a script.
+    lines: Rc<RefCell<Lines>>,
+    /// Where the records are written, the standard output.
+    records: Box<dyn Write>,
+    /// The payload of the command being run, set by the commands that have one.
+    data: Option<Value>,
+    /// The commands being run, the innermost last, as `source` runs commands of its own.
+    commands: Vec<String>,
+}
+
+/// Format an address for a record. Addresses are strings, as the numbers of some JSON readers
+/// cannot hold 64 bits.
+pub fn address(address: u64) -> Value {
+    Value::String(format!("{:#x}", address))
+}
+
+/// Switch the output of the debugger to JSON mode.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `text` - Where the text for people goes, usually the standard error.
+/// * `records` - Where the records go, usually the standard output.
+pub fn start(debugger: &mut Debugger, text: Box<dyn Output>, records: Box<dyn Write>) {
+    let lines = Rc::new(RefCell::new(Lines::default()));
+    debugger.output = Box::new(JsonText::new(text, lines.clone()));
+    debugger.json = Some(JsonState { lines, records, data: None, commands: Vec::new() });
+}
+
+/// Set the payload of the record of the command being run, in JSON mode. Commands without one
+/// get the lines they printed.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `data` - Builds the payload, only called in JSON mode.
+pub fn set_data(debugger: &mut Debugger, data: impl FnOnce(&mut Debugger) -> Value) {
+    if debugger.json.is_some() {
+        let data = data(debugger);
+        if let Some(state) = &mut debugger.json {
+            state.data = Some(data);
+        }
+    }
+}
+
+/// Write the asynchronous record of the last stop of the program, in JSON mode, from its state:
+/// why it stopped (`breakpoint`, `step`, `watchpoint`, `syscall`, `signal`, `entry`, `attached`, `core`,
+/// `exited` or `killed`) with its banner, and where, with the line of `where`.
+pub fn stop_event(debugger: &mut Debugger) {
+    if debugger.json.is_none() {
+        return;
+    }
+    // Not a stop.
+    let Some(reason) = crate::summary::stop_reason(&debugger.state) else {
+        return;
+    };
+    let mut event = Map::new();
+    event.insert("type".to_string(), json!("stop"));
+    event.insert("reason".to_string(), json!(reason));
+    if let Some(banner) = crate::summary::banner(debugger) {
+        event.insert("banner".to_string(), json!(banner));
+    }
+    match debugger.state {
+        InferiorState::Stopped { reason: StopReason::Breakpoint(address) } => {
+            event.insert("breakpoint".to_string(), json!(crate::summary::breakpoint_number(debugger, address)));
+        }
+        InferiorState::Stopped { reason: StopReason::Syscall { number, entry } } => {
+            event.insert("syscall".to_string(), json!(debugger.arch.syscall_name(number)));
+            event.insert("number".to_string(), json!(number));
+            event.insert("entry".to_string(), json!(entry));
+        }
+        InferiorState::Stopped { reason: StopReason::Watchpoint(address) } => {
+            event.insert("address".to_string(), self::address(address));
+            if let Some(watchpoint) = debugger.watchpoints.find(address) {
+                event.insert("watchpoint".to_string(), json!(watchpoint.name));
+            } else if let Some(region) = debugger.region_watches.find(address) {
+                event.insert("region".to_string(), json!(region.number));
+                if let Some(hit) = debugger.region_watches.last_hit.as_ref().filter(|hit| hit.number == region.number) {
+                    event.insert("written".to_string(), self::address(hit.address));
+                    event.insert("pc".to_string(), self::address(hit.pc));
+                    event.insert("old".to_string(), json!(crate::swatch::format_bytes(&hit.old)));
+                    event.insert("new".to_string(), json!(crate::swatch::format_bytes(&hit.new)));
+                }
+            }
+        }
+        InferiorState::Stopped { reason: StopReason::Signal(signal) } | InferiorState::Signaled { sig: signal } => {
+            event.insert("signal".to_string(), json!(signal.as_str()));
+            if let Some(info) = debugger.last_signal {
+                event.insert("siginfo".to_string(), crate::siginfo::to_json(&info));
+            }
+        }
+        InferiorState::Stopped { reason: StopReason::Core } => {
+            // The signal that ended the program, none for a core saved with gcore.
+            let signal = debugger.core.as_ref().map_or(0, |core| core.signal());
+            if signal != 0 {
+                event.insert("signal".to_string(), json!(crate::siginfo::signal_name(signal)));
+            }
+        }
+        InferiorState::Exited { code } => {
+            event.insert("code".to_string(), json!(code));
+        }
+        _ => {}
+    }
+    if !debugger.state.has_terminated() {
+        event.insert("thread".to_string(), json!(debugger.thread.as_raw()));
+        if let Some(summary) = crate::summary::summarize(debugger) {
+            summary.add_to(&mut event);
+        }
+    }
+    write_record(debugger, &Value::Object(event));
+}
+
+/// Write the asynchronous record of a line the program wrote to a captured stream, in JSON mode:
+/// `{"type": "output", "stream": "stdout", "line": ...}`, or `"stderr"`.
+pub fn output_event(debugger: &mut Debugger, stream: Stream, line: &str) {
+    write_record(debugger, &json!({ "type": "output", "stream": stream.name(), "line": line }));
+}
+
+/// Run a command and write its record, in JSON mode.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session, in JSON mode.
+/// * `command` - The command line.
+/// * `run` - Runs the command line, returning false if it failed.
+///
+/// # Returns
+///
+/// The result of `run`.
+pub fn run_command(debugger: &mut Debugger, command: &str, run: fn(&str, &mut Debugger) -> bool) -> bool {
+    let Some(state) = &mut debugger.json else {
+        return run(command, debugger);
+    };
+    // The lines and payload of a `source` running this command are set aside meanwhile.
+    let outer_lines = std::mem::take(&mut *state.lines.borrow_mut());
+    let outer_data = state.data.take();
+    state.commands.push(command.to_string());
+    let ok = run(command, debugger);
+    finish_command(debugger, ok);
+    if let Some(state) = &mut debugger.json {
+        *state.lines.borrow_mut() = outer_lines;
+        state.data = outer_data;
+    }
+    ok
+}
+
+/// Write the record of the innermost command being run, with the lines it printed.
+fn finish_command(debugger: &mut Debugger, ok: bool) {
+    let Some(state) = &mut debugger.json else {
+        return;
+    };
+    let Some(command) = state.commands.pop() else {
+        return;
+    };
+    let lines = std::mem::take(&mut *state.lines.borrow_mut());
+    let data = state.data.take().unwrap_or_else(|| json!({ "output": lines.output }));
+    let mut record = json!({ "command": command, "ok": ok, "data": data });
+    if !lines.errors.is_empty() {
+        record["error"] = json!(lines.errors.join("\n"));
+    }
+    write_record(debugger, &record);
+}
+
+/// Write the records of the commands being run when the debugger exits in the middle of them.
+pub fn exit(debugger: &mut Debugger) {
+    while debugger.json.as_ref().is_some_and(|state| !state.commands.is_empty()) {
+        finish_command(debugger, true);
+    }
+}
+
+/// Write a record on its own line.
+fn write_record(debugger: &mut Debugger, record: &Value) {
+    if let Some(state) = &mut debugger.json {
+        writeln!(state.records, "{}", record).ok();
+        state.records.flush().ok();
+    }
+}
@@ -0,0 +1,139 @@
+use crate::debugger::Debugger;
+use crate::json;
+use crate::location;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// A name given to an address with `label`, covering the `length` bytes from it when one is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label {
+    pub address: u64,
+    /// The bytes named, 0 when only the address itself is.
+    pub length: u64,
+}
+
+impl Label {
+    /// Returns true if `address` is the labeled address or one of the bytes the label covers.
+    pub fn contains(&self, address: u64) -> bool {
+        address == self.address || address.wrapping_sub(self.address) < self.length
+    }
+}
+
+/// Find the label of an address: among the labels covering it, the one starting the closest
+/// before it, the most precise.
+///
+/// # Returns
+///
+/// The name of the label and the offset of the address in it.
+pub fn find(labels: &BTreeMap<String, Label>, address: u64) -> Option<(&str, u64)> {
+    labels
+        .iter()
+        .filter(|(_, label)| label.contains(address))
+        .min_by_key(|(_, label)| address - label.address)
+        .map(|(name, label)| (name.as_str(), address - label.address))
+}
+
+/// Returns true if `name` can name a label, used as a value: letters, digits and `_`, not
+/// starting with a digit.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The commands defining the labels again, as written by `label --save` for `source`.
+pub fn script(labels: &BTreeMap<String, Label>) -> String {
+    let mut script = String::new();
+    for (name, label) in labels {
+        match label.length {
+            0 => writeln!(script, "label {:#x} {}", label.address, name),
+            length => writeln!(script, "label {:#x} {} {}", label.address, name, length),
+        }
+        .ok();
+    }
+    script
+}
+
+/// Name an address, `label <address> <name> [length]`, list the labels without arguments, or
+/// save them as commands with `label --save <file>`.
+///
+/// # Returns
+///
+/// False if the address, the name or the length is invalid, or the file can't be written.
+pub fn label_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match args[1..] {
+        [] => {
+            info_labels(debugger);
+            true
+        }
+        ["--save", path] => {
+            if let Err(err) = std::fs::write(path, script(&debugger.labels)) {
+                errln!(debugger, "Could not write {}: {}", path, err);
+                return false;
+            }
+            outln!(debugger, "Saved {} labels to {}, source it to define them again.", debugger.labels.len(), path);
+            true
+        }
+        [address, name] | [address, name, _] => {
+            if !is_valid_name(name) {
+                errln!(debugger, "Invalid label name \"{}\": letters, digits and _, not starting with a digit.", name);
+                return false;
+            }
+            let parsed = location::parse_value(debugger, address, "address")
+                .and_then(|address| Ok((address, args.get(3).map(|length| location::parse_number(length, "length")).transpose()?)));
+            let (address, length) = match parsed {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    return false;
+                }
+            };
+            if debugger.resolve_symbol(name).is_some() {
+                warnln!(debugger, "The label {} hides the symbol of the same name in values.", name);
+            }
+            let label = Label { address, length: length.unwrap_or(0) };
+            debugger.labels.insert(name.to_string(), label);
+            match label.length {
+                0 => outln!(debugger, "Label {} at {:#x}.", name, address),
+                length => outln!(debugger, "Label {} at {:#x}, {} bytes.", name, address, length),
+            }
+            true
+        }
+        _ => {
+            errln!(debugger, "Usage: label [<address> <name> [length] | --save <file>]");
+            false
+        }
+    }
+}
+
+/// Remove a label, `unlabel <name>`.
+pub fn unlabel_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let [_, name] = args else {
+        errln!(debugger, "Usage: unlabel <name>");
+        return false;
+    };
+    if debugger.labels.remove(*name).is_none() {
+        errln!(debugger, "No label named \"{}\".", name);
+        return false;
+    }
+    true
+}
+
+/// List the labels by name, with their address and length (`info labels`).
+pub fn info_labels(debugger: &mut Debugger) {
+    let labels: Vec<(String, Label)> = debugger.labels.iter().map(|(name, label)| (name.clone(), *label)).collect();
+    json::set_data(debugger, |_| {
+        let labels: Vec<_> = labels
+            .iter()
+            .map(|(name, label)| json!({ "name": name, "address": json::address(label.address), "length": label.length }))
+            .collect();
+        json!({ "labels": labels })
+    });
+    if labels.is_empty() {
+        outln!(debugger, "No labels, name an address with label <address> <name> [length].");
+        return;
+    }
+    outln!(debugger, "{:<20}  {:<18}  Length", "Name", "Address");
+    for (name, label) in labels {
+        outln!(debugger, "{:<20}  {:#018x}  {}", name, label.address, label.length);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::inferior::{ChildStdio, InferiorTty};
+use crate::sandbox::{self, Environment, ResourceLimit};
+use crate::state::{InferiorState, StopReason};
+use crate::threads;
+use crate::working::{is_breakpoint, remove_breakpoint, set_breakpoint};
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{fork, AccessFlags, ForkResult, Pid};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStringExt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// How the program is started: at startup, and again by `run`, `start` and `restart`, from the
+/// settings of the session or the arguments given to `run`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LaunchSpec {
+    pub program_path: String,
+    /// Its arguments, after its path which is its `argv[0]`.
+    pub args: Vec<String>,
+    /// The file it reads as its standard input (`run < file`), instead of the one of
+    /// `set inferior-tty`.
+    pub stdin_file: Option<String>,
+    /// The directory it starts in (`set cwd`), the one of the debugger when `None`.
+    pub cwd: Option<String>,
+    /// True to turn the address space randomization off for the program.
+    pub no_aslr: bool,
+    /// Its environment (`set env`), the one of the debugger by default.
+    pub env: Environment,
+    /// The resource limits set in the child before it executes the program (`set rlimit`).
+    pub rlimits: Vec<ResourceLimit>,
+}
+
+/// Start the program traced by the debugger, stopped at its first instruction, with its standard
+/// streams opened as `tty` and the spec say.
+///
+/// # Returns
+///
+/// The process and its standard streams, given to [`crate::inferior::capture`].
+///
+/// # Errors
+///
+/// Returns the errors of [`crate::inferior::prepare`] and [`launch`].
+pub fn start(spec: &LaunchSpec, tty: &InferiorTty) -> Result<(Pid, ChildStdio), DbgError> {
+    let stdio = crate::inferior::prepare(tty, spec.stdin_file.as_deref())?;
+    let child = launch(spec, &stdio)?;
+    Ok((child, stdio))
+}
+
+/// Start the program traced by the debugger, stopped at its first instruction. The child enters
+/// the directory of the spec and sets its resource limits before it executes the program, found
+/// from the directory of the debugger when its path is relative, with the environment of the
+/// spec.
+///
+/// # Arguments
+///
+/// * `spec` - The program, its arguments and how to start it. Its standard input file is opened
+///   by [`crate::inferior::prepare`] for `stdio`.
+/// * `stdio` - The standard streams of the program, from [`crate::inferior::prepare`].
+///
+/// # Errors
+///
+/// Returns [`DbgError::InvalidArgument`] if the path, an argument or the directory contains a
+/// NUL, [`DbgError::Cwd`] if the directory can't be entered, [`DbgError::Rlimit`] if a resource
+/// limit can't be set, [`DbgError::Exec`] if the program
+/// could not be executed, as when it does not exist or is not executable, or another error if it
+/// could not be started.
+pub fn launch(spec: &LaunchSpec, stdio: &ChildStdio) -> Result<Pid, DbgError> {
+    let program_path = spec.program_path.as_str();
+    let invalid = |what, value: &str| DbgError::InvalidArgument { what, value: value.to_string() };
+    let path = CString::new(program_path).map_err(|_| invalid("program path", program_path))?;
+    let mut argv = vec![path.clone()];
+    for arg in &spec.args {
+        argv.push(CString::new(arg.as_str()).map_err(|_| invalid("program argument", arg))?);
+    }
+    // Checked before forking: the child can only report an errno, which exec failures give too.
+    let cwd = match &spec.cwd {
+        Some(cwd) => {
+            let directory = CString::new(cwd.as_str()).map_err(|_| invalid("working directory", cwd))?;
+            let error = |errno| DbgError::Cwd { path: cwd.clone(), errno };
+            let metadata = std::fs::metadata(cwd).map_err(|err| error(Errno::from_raw(err.raw_os_error().unwrap_or(0))))?;
+            if !metadata.is_dir() {
+                return Err(error(Errno::ENOTDIR));
+            }
+            nix::unistd::access(directory.as_c_str(), AccessFlags::X_OK).map_err(error)?;
+            Some(directory)
+        }
+        None => None,
+    };
+    for limit in &spec.rlimits {
+        sandbox::check_limit(limit).map_err(DbgError::Rlimit)?;
+    }
+    let environment = spec.env.entries();
+    // A relative path is the one from the directory of the debugger, not from the new directory.
+    let executable = match (&cwd, Path::new(program_path).is_relative()) {
+        (Some(_), true) => {
+            let absolute = std::path::absolute(program_path).map_err(|_| invalid("program path", program_path))?;
+            CString::new(absolute.into_os_string().into_vec()).map_err(|_| invalid("program path", program_path))?
+        }
+        _ => path,
+    };
+    match unsafe { fork() }.map_err(DbgError::Fork)? {
+        ForkResult::Parent { child } => {
+            // Stopped at its first instruction after exec, or exited with the errno of a failure.
+            let status = waitpid(child, None).map_err(DbgError::ptrace("wait for the program"))?;
+            log::debug!("Started {} as {}, waitpid = {:?}", program_path, child, status);
+            match status {
+                WaitStatus::Stopped(_, _) => Ok(child),
+                WaitStatus::Exited(_, errno) => Err(DbgError::Exec { path: program_path.to_string(), errno: Errno::from_raw(errno) }),
+                status => Err(DbgError::Start { path: program_path.to_string(), status }),
+            }
+        }
+        ForkResult::Child => {
+            if spec.no_aslr {
+                // The personality is kept by exec. 0xffffffff reads the current one.
+                unsafe {
+                    let persona = nix::libc::personality(0xffffffff);
+                    nix::libc::personality((persona | nix::libc::ADDR_NO_RANDOMIZE) as nix::libc::c_ulong);
+                }
+            }
+            let entered = match &cwd {
+                Some(directory) => nix::unistd::chdir(directory.as_c_str()),
+                None => Ok(()),
+            };
+            // The limits are set after the streams are installed, which a low NOFILE could prevent.
+            let prepared = entered.and_then(|()| stdio.install()).and_then(|()| sandbox::apply_limits(&spec.rlimits));
+            let errno = match prepared.and_then(|()| ptrace::traceme()) {
+                Ok(()) => nix::unistd::execve::<CString, CString>(&executable, &argv, &environment).unwrap_err(),
+                Err(errno) => errno,
+            };
+            // Only async-signal-safe calls after fork: no unwinding nor destructors.
+            unsafe { nix::libc::_exit(errno as i32) }
+        }
+    }
+}
+
+/// Returns true if a child can ask to be traced, as [`launch`] does, false where ptrace is not
+/// allowed, as in a container without `CAP_SYS_PTRACE`. Checked once, by a forked child.
+pub fn ptrace_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match unsafe { fork() } {
+        Ok(ForkResult::Child) => {
+            let status = if ptrace::traceme().is_ok() { 0 } else { 1 };
+            // Only async-signal-safe calls after fork.
+            unsafe { nix::libc::_exit(status) }
+        }
+        Ok(ForkResult::Parent { child }) => matches!(waitpid(child, None), Ok(WaitStatus::Exited(_, 0))),
+        Err(_) => false,
+    })
+}
+
+/// Attach to a running process and each of its threads, which are stopped until they are resumed.
+///
+/// # Returns
+///
+/// The process and the path of its executable.
+///
+/// # Errors
+///
+/// Returns [`DbgError::NoProcess`] if there is no such process, [`DbgError::AttachDenied`] if
+/// this user cannot trace it, or [`DbgError::Ptrace`] if attaching failed otherwise.
+pub fn attach(pid: i32) -> Result<(Pid, String), DbgError> {
+    let child = Pid::from_raw(pid);
+    let path = std::fs::read_link(format!("/proc/{}/exe", pid)).map_err(|err| DbgError::NoProcess { pid, reason: err.to_string() })?;
+    log::debug!("PTRACE_ATTACH {}", child);
+    match ptrace::attach(child) {
+        Ok(()) => {}
+        Err(Errno::EPERM) => return Err(DbgError::AttachDenied(pid)),
+        Err(errno) => return Err(DbgError::Ptrace { op: "attach to the process", errno }),
+    }
+    waitpid(child, None).map_err(DbgError::ptrace("attach to the process"))?;
+    for tid in threads::tasks(child).into_iter().skip(1) {
+        // A thread that exited meanwhile can't be attached.
+        log::debug!("PTRACE_ATTACH {}", tid);
+        if ptrace::attach(tid).is_ok() {
+            waitpid(tid, Some(WaitPidFlag::__WALL)).map_err(DbgError::ptrace("attach to the process"))?;
+        }
+    }
+    Ok((child, path.to_string_lossy().into_owned()))
+}
+
+/// Leave the process behind when the debugger is done with it: a process attached with `--pid`
+/// is detached, without the breakpoints, and keeps running; a process started by the debugger is
+/// killed. A core file has no process to leave.
+pub fn release(debugger: &mut Debugger) {
+    // The process of a core file is not traced, and likely gone.
+    if debugger.core.is_some() {
+        return;
+    }
+    if debugger.attached {
+        detach(debugger);
+    } else {
+        kill(debugger);
+    }
+}
+
+/// Detach from every thread of the process, removing the breakpoints first, so that it keeps
+/// running untraced. The threads left running in non-stop mode are stopped first.
+pub fn detach(debugger: &mut Debugger) {
+    if crate::threads::any_running(debugger) {
+        crate::threads::stop_others(debugger).ok();
+    }
+    // The program would crash writing to the pages left read-only.
+    crate::pagewatch::unprotect_all(debugger);
+    let child = debugger.child;
+    for address in debugger.breakpoints.clone() {
+        // The breakpoints already hit are not armed anymore.
+        remove_breakpoint(&mut debugger.breakpoint_table, child, address).ok();
+    }
+    for thread in &debugger.threads {
+        log::debug!("PTRACE_DETACH {}", thread.tid);
+        ptrace::detach(thread.tid, None).ok();
+    }
+}
+
+/// Kill the process, waiting for its threads to be gone.
+pub fn kill(debugger: &mut Debugger) {
+    if nix::sys::signal::kill(debugger.child, Signal::SIGKILL).is_ok() {
+        // The main thread is reported last, once the other threads are waited for.
+        for thread in debugger.threads.iter().rev() {
+            let status = waitpid(thread.tid, Some(WaitPidFlag::__WALL));
+            log::debug!("waitpid {} = {:?}", thread.tid, status);
+        }
+    }
+}
+
+/// Run the program to the entry point of its executable (`--stop-at-entry`), past the dynamic
+/// loader.
+///
+/// # Errors
+///
+/// Returns [`DbgError::NoEntryPoint`] if the entry point is not known,
+/// [`DbgError::EntryNotReached`] if the program stopped elsewhere, or the error of the operation
+/// that failed.
+pub fn run_to_entry(debugger: &mut Debugger) -> Result<u64, DbgError> {
+    let Some(executable) = debugger.executable().cloned() else {
+        return Err(DbgError::NoEntryPoint);
+    };
+    let entry = executable.header.entry.wrapping_add(executable.bias.unwrap_or_default());
+    let child = debugger.child;
+    set_breakpoint(&mut debugger.breakpoint_table, child, entry)?;
+    debugger.resume();
+    log::debug!("PTRACE_CONT {} to the entry point {:#x}", child, entry);
+    crate::cache::invalidate();
+    ptrace::cont(child, None).map_err(DbgError::ptrace("continue execution"))?;
+    let status = waitpid(child, None).map_err(DbgError::ptrace("wait for the program"))?;
+    log::debug!("waitpid {} = {:?}", child, status);
+    // Not armed anymore if the program terminated.
+    remove_breakpoint(&mut debugger.breakpoint_table, child, entry).ok();
+    debugger.state = match status {
+        WaitStatus::Exited(_, code) => InferiorState::Exited { code },
+        WaitStatus::Signaled(_, sig, _) => InferiorState::Signaled { sig },
+        WaitStatus::Stopped(_, signal) if signal != Signal::SIGTRAP => InferiorState::Stopped { reason: StopReason::Signal(signal) },
+        _ => InferiorState::Stopped { reason: StopReason::Entry },
+    };
+    debugger.refresh_mappings();
+    if status != WaitStatus::Stopped(child, Signal::SIGTRAP) {
+        return Err(DbgError::EntryNotReached(status));
+    }
+    // Back to the instruction the breakpoint replaced.
+    let mut regs = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?;
+    regs.set_pc(entry);
+    crate::cache::invalidate();
+    crate::arch::set_registers(child, regs).map_err(DbgError::ptrace("write the registers"))?;
+    Ok(entry)
+}
+
+/// The function `start` and `--stop-at-main` run the program to, and how it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MainFunction {
+    /// The demangled name of the function, or `_start` for the entry point.
+    pub name: String,
+    /// The runtime address of its first instruction.
+    pub address: u64,
+    /// Why this function was chosen, as in `the main symbol`.
+    pub reason: String,
+}
+
+/// Find the main function of the program, in order: the function marked as such by the DWARF
+/// information (`DW_AT_main_subprogram`), the mangled `<crate>::main` of a Rust program, run by
+/// `std::rt::lang_start` behind the `main` of the C library, the `main` symbol, and the entry
+/// point of the executable for stripped programs.
+///
+/// # Errors
+///
+/// Returns [`DbgError::NoEntryPoint`] if the executable is not known.
+pub fn resolve_main(debugger: &mut Debugger) -> Result<MainFunction, DbgError> {
+    let Some(executable) = debugger.executable().cloned() else {
+        return Err(DbgError::NoEntryPoint);
+    };
+    let bias = executable.bias.unwrap_or_default();
+    let name_at = |debugger: &mut Debugger, address: u64| {
+        debugger.symbolize(address).map(|(name, _, _)| crate::symbols::demangle(&name))
+    };
+    if let Some(address) = debugger.debug_info.as_ref().and_then(|debug_info| debug_info.main_subprogram()) {
+        let address = address.wrapping_add(bias);
+        if let Some(name) = name_at(debugger, address) {
+            let reason = "marked as the main function by the debugging information".to_string();
+            return Ok(MainFunction { name, address, reason });
+        }
+    }
+    let program = std::path::Path::new(&debugger.program_path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    if let Some(symbol) = crate::symbols::rust_main(&executable.symbols, &program) {
+        return Ok(MainFunction {
+            name: symbol.demangled.clone(),
+            address: symbol.address.wrapping_add(bias),
+            reason: format!("the Rust main run by std::rt::lang_start, from its mangled symbol {}", symbol.name),
+        });
+    }
+    if let Some(address) = executable.lookup("main") {
+        return Ok(MainFunction { name: "main".to_string(), address, reason: "the main symbol".to_string() });
+    }
+    let address = executable.header.entry.wrapping_add(bias);
+    let name = name_at(debugger, address).unwrap_or_else(|| "_start".to_string());
+    let reason = format!("the entry point {:#x}: no main function is in the symbols, as when the executable is stripped", address);
+    Ok(MainFunction { name, address, reason })
+}
+
+/// Run the program to its main function (`start` and `--stop-at-main`), past the prologue of
+/// the function when it has line information, with a temporary breakpoint removed at the next
+/// stop, and report the stop with the source line.
+///
+/// # Returns
+///
+/// False if the main function could not be found or the program could not be resumed.
+pub fn run_to_main(debugger: &mut Debugger) -> bool {
+    let main = match resolve_main(debugger) {
+        Ok(main) => main,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let address = debugger.after_prologue(main.address);
+    outln!(debugger, "Stopping at {}, {}.", main.name, main.reason);
+    // A breakpoint of the user at the same place stays, and is the one reported.
+    let temporary = !is_breakpoint(&debugger.breakpoint_table, address);
+    if temporary {
+        if let Err(err) = set_breakpoint(&mut debugger.breakpoint_table, debugger.child, address) {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+        debugger.temporary_breakpoint = Some(address);
+    }
+    let stop = crate::runstatus::while_running(debugger, Debugger::cont);
+    if let Ok(stop) = &stop {
+        crate::step::report_stop(debugger, stop);
+    }
+    if temporary {
+        // Already removed if it was hit, not armed anymore if the program terminated.
+        remove_breakpoint(&mut debugger.breakpoint_table, debugger.child, address).ok();
+        debugger.temporary_breakpoint = None;
+    }
+    match stop {
+        Ok(_) => true,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            false
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! # rustdbg
+//!
+//! The debugger as a library: a [`Debugger`] session starts a program traced with
+//! [`Debugger::launch`], attaches to a process with [`Debugger::attach`] or reads a core file with
+//! [`Debugger::open_core`], then controls it with
+//! methods mirroring the commands ([`Debugger::set_breakpoint`], [`Debugger::cont`],
+//! [`Debugger::step`], [`Debugger::read_memory`], [`Debugger::registers`],
+//! [`Debugger::allocate`] and [`Debugger::wait_event`]), or runs the commands of the prompt with [`run_command`]. The
+//! `dbg_rust` binary is a prompt over this library. [`dispatch`] runs a command keeping its
+//! output in the returned [`CommandOutcome`], with whether the session goes on.
+//!
+//! The output of the commands and of the methods goes to [`Debugger::output`], the terminal by
+//! default. The methods return a [`DbgError`] when they fail.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use rustdbg::debugger::Debugger;
+//! use rustdbg::arch::Registers;
+//! use rustdbg::step::StepStop;
+//!
+//! let mut debugger = Debugger::launch("/path/to/program", &["--verbose".to_string()])?;
+//! let address = debugger.set_breakpoint("main")?;
+//! assert_eq!(debugger.cont()?, StepStop::Breakpoint(address));
+//! let sp = debugger.registers()?.sp();
+//! let stack = debugger.read_memory(sp, 16)?;
+//! println!("{:x?}", stack);
+//! rustdbg::run_command("bt", &mut debugger);
+//! # Ok::<(), rustdbg::error::DbgError>(())
+//! ```
+//!
+//! ## Modules
+//!
+//! - `syscall`: Provides utilities to work with system calls.
+//! - `threads`: Traces the threads of the program, stopped and resumed together, or one by one in non-stop mode.
+//! - `tracee`: The ptrace operations on the debugged process, behind a trait the tests can mock.
+//! - `transcript`: Copies the commands and their output to a log file (`set logging`).
+//! - `working`: Contains various functions for debugger operations.
+//! - `unwind`: Unwinds frames with the DWARF call frame information.
+//! - `alias`: Parses and expands the aliases defined with `alias`.
+//! - `antidebug`: Warns about the anti-debugging checks of the program, and defeats `ptrace(PTRACE_TRACEME)` (`set anti-anti-debug`).
+//! - `arch`: The architecture of the program, x86_64, i386 or aarch64: the view of its registers (`Registers`), pointers, system calls and call arguments.
+//! - `assertion`: Checks comparisons of values, the registers and memory of the program included (`assert`).
+//! - `backtrace`: Unwinds the call stack.
+//! - `call`: Calls functions of the program from the debugger (`call`).
+//! - `cache`: Keeps the registers and the pages of memory read during a stop, until the program is resumed or written to.
+//! - `commands`: The table of the commands, with their documentation and implementation.
+//! - `coredump`: Writes an ELF core file of the program with its threads and memory (`gcore`).
+//! - `coverage`: Records the code executed, by the basic blocks of the program or by single-stepping (`cover`).
+//! - `corefile`: Reads the threads and memory of a core file, for the post-mortem mode of `--core`.
+//! - `crash`: Reports the faulting instruction, address and access when the program crashes.
+//! - `debugger`: Holds the state of a debugging session.
+//! - `debuginfo`: Reads the DWARF line tables.
+//! - `define`: Collects and runs the user commands defined with `define`.
+//! - `disasm`: Decodes x86_64 instructions from the debugged process memory.
+//! - `display`: The expressions shown at every stop (`display`, `undisplay`, `info display`).
+//! - `error`: The errors of the operations on the debugged process.
+//! - `examine`: Shows memory as units, strings or instructions with the formats of gdb (`x/16xb`).
+//! - `exec`: Follows the program into the new programs it executes, setting its breakpoints again in them.
+//! - `gdbserver`: Serves gdb over TCP with the remote protocol (`--gdbserver`), on the operations of the debugger.
+//! - `inferior`: Captures the standard output and error of the program, or gives it another terminal (`tty`).
+//! - `inject`: Runs system calls in the program, to map memory in it (`alloc`, `dealloc`).
+//! - `source`: Reads and prints source files.
+//! - `state`: The state of the debugged program, and the states in which each command can run.
+//! - `step`: Implements instruction and source line stepping.
+//! - `variables`: Reads variables using the DWARF debugging information.
+//! - `watch`: Watches static variables by name, with the debug registers or by single-stepping (`watch`, `awatch`).
+//! - `json`: Writes the JSON records of `--interpreter json`.
+//! - `label`: Names addresses with `label`, shown where addresses are described and usable as values.
+//! - `launch`: Starts the program traced or attaches to a process, and leaves it when done.
+//! - `location`: Parses the numbers, values and locations given to the commands.
+//! - `logging`: Logs the ptrace calls and the wait statuses with `-v`, above the prompt while a command is read.
+//! - `ltrace`: Traces the library calls of the program through its PLT stubs.
+//! - `output`: Prints the output of the commands, colored on a terminal.
+//! - `maps`: Parses the memory mappings of the debugged process.
+//! - `memory`: Reads and writes bytes, and reads strings, in the debugged process memory.
+//! - `symbols`: Loads ELF symbol tables.
+//! - `nonstop`: Reports the stops of the threads left running in non-stop mode, at the prompt and before each command (`set non-stop`, `interrupt`).
+//! - `options`: Parses the command line options into the configuration of the session.
+//! - `pagewatch`: Watches the writes to whole regions of memory by write-protecting their pages with mprotect (`watch-region`).
+//! - `pager`: Stops long output at each screenful on a terminal (`set pagination`).
+//! - `printf`: Formats the values of `printf`.
+//! - `procfs`: Reads the status, CPU times, links and open file descriptors of the process from `/proc`, and tracks the descriptors from stop to stop (`set track-fds`).
+//! - `record`: Records the instructions stepped and the registers they change to a file (`record on`).
+//! - `prompt`: Builds the prompt from the state of the program (`set prompt`).
+//! - `settings`: The settings changed by `set` and shown by `show`, and the configuration file.
+//! - `script`: Runs command scripts (`-x` and `source`).
+//! - `session`: Records the commands entered with their results, saved and replayed by `session save` and `session replay`.
+//! - `scripting`: Runs Rhai scripts (`script eval`, `script file`), with bindings over the session and stop hooks.
+//! - `runstatus`: Shows what the program does while `c` waits for it (`set run-status interval`).
+//! - `repl`: Reads the commands at the prompt, with line editing, a persistent history and Tab completion.
+//! - `sandbox`: The environment and the resource limits the program is started with (`set env`, `set rlimit`).
+//! - `sharedlib`: Enumerates the shared libraries loaded by the debugged process.
+//! - `siginfo`: Decodes the details of the signals, their `si_code` for each signal, the address and the sender.
+//! - `snapshot`: Snapshots of the registers and the writable memory, to go back with `reverse-continue`.
+//! - `summary`: Sums up where the program is stopped and why in one line (`where`), at each stop and in the JSON stop records.
+//! - `swatch`: Software watchpoints, single-stepping the program until watched bytes change.
+//! - `syscallinfo`: Decodes the system call a thread is in as strace does (`info syscall`), and the one a signal interrupted.
+
+#[macro_use]
+pub mod output;
+mod alias;
+mod antidebug;
+pub mod arch;
+mod assertion;
+mod backtrace;
+mod cache;
+mod call;
+mod commands;
+mod coredump;
+mod coverage;
+pub mod corefile;
+mod crash;
+pub mod debugger;
+mod define;
+mod debuginfo;
+mod disasm;
+mod display;
+pub mod error;
+mod examine;
+mod exec;
+pub mod gdbserver;
+pub mod inferior;
+mod inject;
+pub mod json;
+mod label;
+pub mod launch;
+pub mod location;
+pub mod logging;
+mod ltrace;
+mod maps;
+mod memory;
+pub mod nonstop;
+pub mod options;
+mod printf;
+mod procfs;
+mod pager;
+mod pagewatch;
+mod record;
+pub mod prompt;
+pub mod repl;
+mod runstatus;
+pub mod sandbox;
+pub mod script;
+mod scripting;
+mod session;
+pub mod settings;
+pub mod sharedlib;
+pub mod siginfo;
+mod snapshot;
+mod source;
+mod summary;
+pub mod state;
+pub mod threads;
+pub mod step;
+mod swatch;
+mod symbols;
+mod syscall;
+mod syscallinfo;
+mod tracee;
+mod transcript;
+mod unwind;
+mod variables;
+mod watch;
+mod working;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::output::{Recorder, Recording};
+use std::{cell::RefCell, rc::Rc};
+
+/// What the session does after a command run by [`dispatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Go on with the next command.
+    Continue,
+    /// Exit the debugger (`quit`). The program is already killed, or detached.
+    Quit,
+}
+
+/// The result of a command run by [`dispatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutcome {
+    /// What the command printed, for the caller to write.
+    pub output: Recording,
+    /// Whether the session goes on.
+    pub control: Control,
+}
+
+/// Run a command line as [`run_command`] does, keeping its output instead of printing it.
+///
+/// The output is colored as [`Debugger::output`] is, and written to it by the caller with
+/// [`Recording::replay`]. The confirmation questions are still asked on the terminal. In JSON
+/// mode, use [`run_command`], which writes the records.
+///
+/// # Returns
+///
+/// The output of the command, and [`Control::Quit`] after `quit`.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Command`] with the output of the command, error messages included, if it
+/// failed.
+///
+/// # Example
+///
+/// ```no_run
+/// # let mut debugger = rustdbg::debugger::Debugger::launch("/bin/true", &[]).unwrap();
+/// let outcome = rustdbg::dispatch(&mut debugger, "r")?;
+/// assert!(outcome.output.text().contains("rip:"));
+/// # Ok::<(), rustdbg::error::DbgError>(())
+/// ```
+pub fn dispatch(debugger: &mut Debugger, command: &str) -> Result<CommandOutcome, DbgError> {
+    let recording = Rc::new(RefCell::new(Recording::new(debugger.output.color(), debugger.output.pagination())));
+    let output = std::mem::replace(&mut debugger.output, Box::new(Recorder::new(recording.clone())));
+    let succeeded = run_command(command, debugger);
+    debugger.output = output;
+    let recording = recording.take();
+    // `set color` and `set pagination` change the output of the session.
+    if recording.color() != debugger.output.color() {
+        debugger.output.set_color(recording.color());
+    }
+    if recording.pagination() != debugger.output.pagination() {
+        debugger.output.set_pagination(recording.pagination());
+    }
+    if !succeeded {
+        return Err(DbgError::Command { command: command.to_string(), output: recording });
+    }
+    let control = if debugger.quit_requested { Control::Quit } else { Control::Continue };
+    Ok(CommandOutcome { output: recording, control })
+}
+
+/// Executes the specified command in the debugger.
+///
+/// Several commands can be given separated by `;`, as in `b main; c; r`: they run in order, and
+/// the rest of the line is skipped once the program terminates or after `quit`.
+///
+/// # Arguments
+///
+/// * `command` - A string slice representing the command to execute.
+/// * `debugger` - The state of the debugging session.
+///
+/// # Returns
+///
+/// False if the command failed: unknown command, invalid arguments, or an operation on the
+/// process that failed.
+///
+/// # Example
+///
+/// ```no_run
+/// # let mut debugger = rustdbg::debugger::Debugger::launch("/bin/true", &[]).unwrap();
+/// rustdbg::run_command("c", &mut debugger);
+/// ```
+///
+pub fn run_command(command: &str, debugger: &mut Debugger) -> bool {
+    // The lines of a `define` are kept as they are, until `end`.
+    if debugger.definition.is_some() {
+        let recorded = session::is_recorded(debugger, command);
+        let succeeded = json::run_command(debugger, command, define::collect_line);
+        if recorded {
+            debugger.session.entries.push(session::Entry { command: command.trim().to_string(), succeeded, regions: Vec::new() });
+        }
+        return succeeded;
+    }
+    let mut succeeded = true;
+    // The commands run after the program has terminated are told so by the state checks.
+    let terminated = debugger.state.has_terminated();
+    for command in script::split_commands(command) {
+        if !terminated && debugger.state.has_terminated() {
+            errln!(debugger, "The program has terminated, skipping \"{}\".", command);
+            return false;
+        }
+        let regions = session::is_recorded(debugger, &command).then(|| session::regions(debugger, &command));
+        let ok = json::run_command(debugger, &command, run_single_command);
+        if let Some(regions) = regions {
+            debugger.session.entries.push(session::Entry { command, succeeded: ok, regions });
+        }
+        succeeded &= ok;
+        if debugger.quit_requested {
+            break;
+        }
+    }
+    inferior::flush(debugger);
+    succeeded
+}
+
+/// Execute one command, expanding it if it is an alias.
+fn run_single_command(command: &str, debugger: &mut Debugger) -> bool {
+    // The threads left running in non-stop mode may have stopped since the last command.
+    nonstop::poll(debugger);
+    let args: Vec<&str> = command.split_whitespace().collect();
+    let Some(name) = args.first() else {
+        return true;
+    };
+    if let Some(line) = commands::shell_command_line(command) {
+        return commands::run_shell(debugger, line);
+    }
+    if debugger.aliases.contains_key(*name) {
+        let commands = match alias::expand(&debugger.aliases, command) {
+            Ok(commands) => commands,
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return false;
+            }
+        };
+        // Stop at the first command that fails, like a script.
+        return commands.iter().all(|command| !debugger.state.has_terminated() && !debugger.quit_requested && run_single_command(command, debugger));
+    }
+    if debugger.user_commands.contains_key(*name) {
+        return define::run(debugger, command, run_command);
+    }
+    // A format after the name, as in `print/full`.
+    let (name, format) = match name.split_once('/') {
+        Some((name, format)) if !name.is_empty() => (name, Some(format)),
+        _ => (*name, None),
+    };
+    // A `!` after the name runs the command without asking for a confirmation.
+    let (name, forced) = match name.strip_suffix('!') {
+        Some(name) if !name.is_empty() => (name, true),
+        _ => (name, false),
+    };
+    match commands::lookup_command(name) {
+        commands::CommandMatch::Found(spec) => {
+            // Abbreviations are expanded, as some commands tell their names apart (`step` and `next`).
+            let name = if spec.names.contains(&name) { name } else { spec.name() };
+            let name = match format {
+                Some(format) if commands::takes_format(spec) => format!("{}/{}", name, format),
+                Some(format) => {
+                    errln!(debugger, "{} takes no /{} format.", spec.name(), format);
+                    return false;
+                }
+                None => name.to_string(),
+            };
+            let args: Vec<&str> = std::iter::once(name.as_str()).chain(args[1..].iter().copied()).collect();
+            if let Err(message) = spec.valid_in.check(&debugger.state) {
+                errln!(debugger, "{}", message);
+                return false;
+            }
+            debugger.forced = forced;
+            let succeeded = (spec.handler)(debugger, &args);
+            debugger.forced = false;
+            succeeded
+        }
+        commands::CommandMatch::Ambiguous => {
+            commands::print_ambiguous_command(debugger, name);
+            false
+        }
+        commands::CommandMatch::Unknown => {
+            errln!(debugger, "Unknown command: {}", command);
+            false
+        }
+    }
+}
+
+// The unit tests run x86_64 fixtures and read the x86_64 registers.
+#[cfg(all(test, target_arch = "x86_64"))]
+mod test;
@@ -0,0 +1,229 @@
+use crate::debugger::Debugger;
+use crate::unwind;
+use std::fmt;
+
+/// The forms accepted for a number.
+const NUMBER_FORMS: &str = "0x<hex>, <decimal> or 0b<binary>";
+/// The forms accepted for a value.
+const VALUE_FORMS: &str = "0x<hex>, <decimal>, 0b<binary>, $<register>, $<variable>, <symbol> or <symbol>+<offset>";
+/// The forms accepted for a location.
+const LOCATION_FORMS: &str = "0x<hex>, <decimal>, 0b<binary>, $<register>, $<variable>, <function>, <symbol>+<offset> or <file>:<line>";
+
+/// The registers that can be used in a value, in the DWARF order of `UnwindRegisters`.
+const REGISTERS: [&str; unwind::REGISTER_COUNT] = unwind::REGISTER_NAMES;
+/// The 32-bit registers of i386, the low halves of the first of [`REGISTERS`].
+#[cfg(target_arch = "x86_64")]
+const I386_REGISTERS: [(&str, &str); 9] = [
+    ("eax", "rax"),
+    ("edx", "rdx"),
+    ("ecx", "rcx"),
+    ("ebx", "rbx"),
+    ("esi", "rsi"),
+    ("edi", "rdi"),
+    ("ebp", "rbp"),
+    ("esp", "rsp"),
+    ("eip", "rip"),
+];
+/// No 32-bit programs run on aarch64.
+#[cfg(target_arch = "aarch64")]
+const I386_REGISTERS: [(&str, &str); 0] = [];
+
+/// Why an argument could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// What was being parsed, as in "address" or "count".
+    pub what: &'static str,
+    /// The argument.
+    pub input: String,
+    /// The index of the character where parsing failed.
+    pub position: usize,
+    /// What is wrong there.
+    pub reason: String,
+    /// The forms accepted.
+    pub accepted: &'static str,
+}
+
+impl ParseError {
+    fn new(what: &'static str, input: &str, position: usize, reason: impl Into<String>, accepted: &'static str) -> ParseError {
+        ParseError { what, input: input.to_string(), position, reason: reason.into(), accepted }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid {} \"{}\": {} at character {}. Expected {}.",
+            self.what,
+            self.input,
+            self.reason,
+            self.position + 1,
+            self.accepted
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A place in the program given to `b`, `list`, `disas` or `info line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// An address: a number, a register or `symbol+offset`.
+    Address(u64),
+    /// A function, by name, with the address of its first instruction.
+    Function(String, u64),
+    /// A line of a source file, with the path of the file as known to the line tables when found.
+    Line(String, u64),
+}
+
+/// Parse a number: `0x` hexadecimal, `0b` binary or decimal.
+///
+/// # Arguments
+///
+/// * `text` - The argument.
+/// * `what` - What the number is, for the error message.
+///
+/// # Errors
+///
+/// Returns an error pointing at the first invalid character.
+pub fn parse_number(text: &str, what: &'static str) -> Result<u64, ParseError> {
+    let (digits, radix, start) = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        (hex, 16, 2)
+    } else if let Some(binary) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        (binary, 2, 2)
+    } else {
+        (text, 10, 0)
+    };
+    if digits.is_empty() {
+        return Err(ParseError::new(what, text, start, "missing digits", NUMBER_FORMS));
+    }
+    if let Some((index, c)) = digits.char_indices().find(|(_, c)| !c.is_digit(radix)) {
+        let kind = match radix {
+            16 => "hexadecimal",
+            2 => "binary",
+            _ => "decimal",
+        };
+        return Err(ParseError::new(what, text, start + index, format!("'{}' is not a {} digit", c, kind), NUMBER_FORMS));
+    }
+    u64::from_str_radix(digits, radix).map_err(|_| ParseError::new(what, text, start, "too large for 64 bits", NUMBER_FORMS))
+}
+
+/// Parse a count, as [`parse_number`] does.
+///
+/// # Errors
+///
+/// Returns an error if `text` is not a number that fits in a `usize`.
+pub fn parse_count(text: &str, what: &'static str) -> Result<usize, ParseError> {
+    let count = parse_number(text, what)?;
+    usize::try_from(count).map_err(|_| ParseError::new(what, text, 0, "too large", NUMBER_FORMS))
+}
+
+/// Returns true if `name` is a register that can be used in a value, as `$<name>`.
+pub fn is_register(name: &str) -> bool {
+    matches!(name, "pc" | "sp" | "fp") || REGISTERS.contains(&name) || I386_REGISTERS.iter().any(|(low, _)| *low == name)
+}
+
+/// Returns true if `name` is a 32-bit register of i386 (`eax`), the low half of a 64-bit one.
+pub fn is_half_register(name: &str) -> bool {
+    I386_REGISTERS.iter().any(|(low, _)| *low == name)
+}
+
+/// Returns the value of a register of the selected frame, by name, with `pc`, `sp` and `fp` as
+/// other names of `rip`, `rsp` and `rbp` (of `pc`, `sp` and `x29` on aarch64) and the 32-bit
+/// registers of i386 (`eax`) as their low halves, or else of a convenience variable
+/// (`set $base = ...`).
+fn register_value(debugger: &mut Debugger, name: &str, text: &str, what: &'static str) -> Result<u64, ParseError> {
+    if let Some((_, full)) = I386_REGISTERS.iter().find(|(low, _)| *low == name) {
+        return register_value(debugger, full, text, what).map(|value| value & 0xffff_ffff);
+    }
+    let name = match name {
+        "pc" => REGISTERS[unwind::PC],
+        "sp" => REGISTERS[unwind::SP],
+        "fp" => REGISTERS[unwind::FP],
+        name => name,
+    };
+    let Some(index) = REGISTERS.iter().position(|register| *register == name) else {
+        if let Some(value) = debugger.variables.get(name) {
+            return Ok(*value);
+        }
+        return Err(ParseError::new(what, text, 1, format!("no register or convenience variable named \"{}\"", name), VALUE_FORMS));
+    };
+    let Some(frame) = debugger.selected_frame() else {
+        return Err(ParseError::new(what, text, 0, "the registers cannot be read", VALUE_FORMS));
+    };
+    frame.registers.0[index].ok_or_else(|| {
+        ParseError::new(what, text, 0, format!("${} is not known in frame {}", name, debugger.selected_frame), VALUE_FORMS)
+    })
+}
+
+/// Parse a value: a number (see [`parse_number`]), a register of the selected frame (`$rip`,
+/// `$sp`...) or a convenience variable (`$base`), a label (see `label`) or a symbol, or one of
+/// them plus an offset (`main+0x10`, `$base+0x1234`).
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session, to read registers and resolve symbols.
+/// * `text` - The argument.
+/// * `what` - What the value is, for the error message.
+///
+/// # Errors
+///
+/// Returns an error if the number is malformed, or the register or symbol does not exist.
+pub fn parse_value(debugger: &mut Debugger, text: &str, what: &'static str) -> Result<u64, ParseError> {
+    if text.is_empty() {
+        return Err(ParseError::new(what, text, 0, "empty", VALUE_FORMS));
+    }
+    if text.starts_with(|c: char| c.is_ascii_digit()) {
+        return parse_number(text, what).map_err(|err| ParseError { accepted: VALUE_FORMS, ..err });
+    }
+    let (name, offset) = match text.rsplit_once('+') {
+        Some((name, offset)) if !name.is_empty() => {
+            let offset = parse_number(offset, what).map_err(|err| ParseError {
+                input: text.to_string(),
+                position: err.position + name.len() + 1,
+                accepted: VALUE_FORMS,
+                ..err
+            })?;
+            (name, offset)
+        }
+        _ => (text, 0),
+    };
+    if let Some(register) = name.strip_prefix('$') {
+        return register_value(debugger, register, text, what).map(|value| value.wrapping_add(offset));
+    }
+    if let Some(label) = debugger.labels.get(name) {
+        return Ok(label.address.wrapping_add(offset));
+    }
+    match debugger.resolve_symbol(name) {
+        Some(address) => Ok(address.wrapping_add(offset)),
+        None => Err(ParseError::new(what, text, 0, format!("no symbol \"{}\" in loaded objects", name), VALUE_FORMS)),
+    }
+}
+
+/// Parse a location: `file:line`, a function name, or any other value (see [`parse_value`]).
+///
+/// # Errors
+///
+/// Returns an error if the line number is malformed, or the value cannot be parsed.
+pub fn parse_location(debugger: &mut Debugger, text: &str) -> Result<Location, ParseError> {
+    const WHAT: &str = "location";
+    // A Rust path (`main::run`) is not a line.
+    if let Some((file, line)) = text.rsplit_once(':').filter(|(file, _)| !file.is_empty() && !file.ends_with(':')) {
+        let line = parse_number(line, "line number").map_err(|err| ParseError {
+            what: WHAT,
+            input: text.to_string(),
+            position: err.position + file.len() + 1,
+            accepted: LOCATION_FORMS,
+            ..err
+        })?;
+        let file = debugger.source_file(file).unwrap_or_else(|| file.to_string());
+        return Ok(Location::Line(file, line));
+    }
+    let address = parse_value(debugger, text, WHAT).map_err(|err| ParseError { accepted: LOCATION_FORMS, ..err })?;
+    let is_function = !text.starts_with(|c: char| c.is_ascii_digit() || c == '$') && !text.contains('+') && !debugger.labels.contains_key(text);
+    if is_function {
+        Ok(Location::Function(text.to_string(), address))
+    } else {
+        Ok(Location::Address(address))
+    }
+}
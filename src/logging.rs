@@ -0,0 +1,99 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use rustyline::ExternalPrinter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// The names of `set log-level`, the quietest first.
+pub const LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// Writes the log records of the debugger to the standard error, as `[debug threads] waitpid...`,
+/// or above the prompt while a command is read, so that they don't break the line being edited.
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+/// Prints above the prompt of the line editor.
+static PRINTER: Mutex<Option<Box<dyn ExternalPrinter + Send>>> = Mutex::new(None);
+
+/// True while the line editor reads a command.
+static READING: AtomicBool = AtomicBool::new(false);
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Not the records of the libraries, as the line editor.
+        metadata.level() <= log::max_level() && metadata.target().starts_with("rustdbg")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let module = record.target().rsplit("::").next().unwrap_or_default();
+        print_line(&format!("[{} {}] {}", record.level().as_str().to_lowercase(), module, record.args()), true);
+    }
+
+    fn flush(&self) {}
+}
+
+/// The level of `-v` given `verbosity` times: the warnings only without it, the ptrace calls,
+/// wait statuses and breakpoints with `-v`, and the register and memory accesses too with `-vv`.
+pub fn level_for(verbosity: u8) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Install the logger of the debugger, at the level of `-v` given `verbosity` times.
+pub fn init(verbosity: u8) {
+    // Only the first logger installed is kept.
+    log::set_logger(&LOGGER).ok();
+    log::set_max_level(level_for(verbosity));
+}
+
+/// The level set by `-v` or `set log-level`, by its name in [`LEVELS`].
+pub fn level() -> &'static str {
+    LEVELS[log::max_level() as usize]
+}
+
+/// Change the level, by its name in [`LEVELS`].
+///
+/// # Errors
+///
+/// Returns a message if the level is unknown.
+pub fn set_level(name: &str) -> Result<(), String> {
+    let level = name.parse::<LevelFilter>().map_err(|_| format!("Unknown log level {}, expected {}", name, LEVELS.join("|")))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Print the log records above the prompt of the line editor with `printer`, while a command is
+/// read.
+pub fn set_printer(printer: impl ExternalPrinter + Send + 'static) {
+    if let Ok(mut current) = PRINTER.lock() {
+        *current = Some(Box::new(printer));
+    }
+}
+
+/// Print a line above the prompt of the line editor while a command is read, or else on the
+/// standard output, or the standard error if `stderr`. The line editor is told from any thread.
+pub fn print_line(line: &str, stderr: bool) {
+    if READING.load(Ordering::Relaxed) {
+        if let Some(printer) = PRINTER.lock().ok().as_mut().and_then(|printer| printer.as_mut()) {
+            if printer.print(format!("{}\n", line)).is_ok() {
+                return;
+            }
+        }
+    }
+    if stderr {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Tell the logger whether the line editor is reading a command.
+pub fn set_reading(reading: bool) {
+    READING.store(reading, Ordering::Relaxed);
+}
@@ -0,0 +1,171 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::threads::{self, ThreadState};
+use crate::working::{is_breakpoint, remove_breakpoint, set_breakpoint};
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use std::collections::HashMap;
+
+/// Number of argument registers shown for each traced call.
+const TRACED_ARGUMENTS: usize = 3;
+
+/// The library calls traced by `ltrace on`.
+#[derive(Debug, Clone, Default)]
+pub struct LibraryTracer {
+    /// The function called through each traced PLT stub, by runtime address of the stub.
+    pub tracepoints: HashMap<u64, String>,
+    /// Number of calls seen for each traced function.
+    pub calls: HashMap<String, usize>,
+}
+
+/// Set a tracepoint on the PLT stubs of the main executable.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `filter` - The functions to trace, all of them when `None`.
+///
+/// # Returns
+///
+/// The number of functions traced.
+///
+/// # Errors
+///
+/// Returns a message if the executable has no matching PLT stub or isn't mapped yet.
+pub fn enable(debugger: &mut Debugger, filter: Option<&[&str]>) -> Result<usize, String> {
+    let child = debugger.thread;
+    let executable = debugger.executable().ok_or("The symbols of the program are not loaded.")?;
+    let bias = executable.bias.ok_or("The program is not mapped yet.")?;
+    let entries: Vec<(u64, String)> = executable
+        .plt_entries
+        .iter()
+        .filter(|entry| filter.is_none_or(|names| names.contains(&entry.name.as_str())))
+        .map(|entry| (entry.address.wrapping_add(bias), entry.name.clone()))
+        .collect();
+    if entries.is_empty() {
+        return Err(match filter {
+            Some(names) => format!("No PLT stub of the program calls {}.", names.join(", ")),
+            None => "The program has no PLT stubs (it may be linked with -fno-plt).".to_string(),
+        });
+    }
+    for (address, name) in entries {
+        if !is_breakpoint(&debugger.breakpoint_table, address) {
+            set_breakpoint(&mut debugger.breakpoint_table, child, address).map_err(|err| format!("Could not trace {}: {}", name, err))?;
+        }
+        debugger.ltrace.tracepoints.insert(address, name);
+    }
+    Ok(debugger.ltrace.tracepoints.len())
+}
+
+/// Remove every tracepoint set by [`enable`].
+pub fn disable(debugger: &mut Debugger) {
+    for address in debugger.ltrace.tracepoints.keys() {
+        // A tracepoint stepped over by another command is not armed.
+        remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, *address).ok();
+    }
+    debugger.ltrace.tracepoints.clear();
+}
+
+/// Format a traced call with its first integer arguments, as in `malloc(0x40, 0x0, 0x7f...)`.
+pub fn format_call(name: &str, arguments: &[u64]) -> String {
+    let arguments: Vec<String> = arguments.iter().map(|value| format!("{:#x}", value)).collect();
+    format!("{}({})", name, arguments.join(", "))
+}
+
+/// Re-arm the tracepoints removed by other commands (stepping over a stub), before the program is
+/// continued.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Memory`] if a tracepoint can't be written.
+pub fn arm_tracepoints(debugger: &mut Debugger) -> Result<(), DbgError> {
+    for address in debugger.ltrace.tracepoints.keys() {
+        if !is_breakpoint(&debugger.breakpoint_table, *address) {
+            set_breakpoint(&mut debugger.breakpoint_table, debugger.thread, *address)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the library call of a thread stopped at a tracepoint, then execute the original
+/// instruction of the stub, re-arm the tracepoint and resume the thread.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `tid` - The thread that stopped.
+/// * `status` - How it stopped.
+///
+/// # Returns
+///
+/// `None` if the thread was at a tracepoint and runs again, the stop to report otherwise: `status`,
+/// or how the thread stopped while stepping over the stub.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] or [`DbgError::Memory`] if the thread can't be stepped over the
+/// tracepoint.
+pub fn trace_call(debugger: &mut Debugger, tid: Pid, status: WaitStatus) -> Result<Option<WaitStatus>, DbgError> {
+    if status != WaitStatus::Stopped(tid, Signal::SIGTRAP) || debugger.ltrace.tracepoints.is_empty() {
+        return Ok(Some(status));
+    }
+    let mut regs = crate::arch::get_registers(tid).map_err(DbgError::ptrace("read the registers"))?;
+    let address = crate::working::trap_address(regs.pc());
+    let Some(name) = debugger.ltrace.tracepoints.get(&address).cloned() else {
+        return Ok(Some(status));
+    };
+    // At the stub, the return address is on top of the stack, the arguments of i386 above it.
+    let arguments = debugger.arch.call_arguments(&tid, &regs, regs.sp() + 4, TRACED_ARGUMENTS).unwrap_or_default();
+    outln!(debugger, "[ltrace] {}", format_call(&name, &arguments));
+    *debugger.ltrace.calls.entry(name).or_default() += 1;
+    // Execute the original instruction of the stub, then re-arm the tracepoint.
+    remove_breakpoint(&mut debugger.breakpoint_table, tid, address)?;
+    regs.set_pc(address);
+    crate::cache::invalidate();
+    crate::arch::set_registers(tid, regs).map_err(DbgError::ptrace("write the registers"))?;
+    log::debug!("PTRACE_SINGLESTEP {} over the tracepoint at {:#x}", tid, address);
+    crate::cache::invalidate();
+    ptrace::step(tid, None).map_err(DbgError::ptrace("single-step"))?;
+    let status = waitpid(tid, Some(WaitPidFlag::__WALL)).map_err(DbgError::ptrace("wait for the program"))?;
+    log::debug!("waitpid {} = {:?}", tid, status);
+    match status {
+        WaitStatus::Stopped(_, Signal::SIGTRAP) => set_breakpoint(&mut debugger.breakpoint_table, tid, address)?,
+        status @ (WaitStatus::Stopped(..) | WaitStatus::Exited(..) | WaitStatus::Signaled(..)) => return Ok(Some(status)),
+        _ => {}
+    }
+    log::debug!("PTRACE_CONT {}", tid);
+    crate::cache::invalidate();
+    ptrace::cont(tid, None).map_err(DbgError::ptrace("continue execution"))?;
+    threads::set_state(debugger, tid, ThreadState::Running);
+    Ok(None)
+}
+
+/// Run the `ltrace on [function,...]`, `ltrace off` and `ltrace` commands.
+pub fn ltrace_command(debugger: &mut Debugger, args: &[&str]) {
+    match args {
+        ["on"] | ["on", _] => {
+            let names: Option<Vec<&str>> = args.get(1).map(|names| names.split(',').filter(|name| !name.is_empty()).collect());
+            match enable(debugger, names.as_deref()) {
+                Ok(count) => outln!(debugger, "Tracing calls to {} library functions.", count),
+                Err(err) => errln!(debugger, "{}", err),
+            }
+        }
+        ["off"] => {
+            disable(debugger);
+            outln!(debugger, "Library call tracing disabled.");
+        }
+        [] if debugger.ltrace.tracepoints.is_empty() => outln!(debugger, "Library call tracing is off."),
+        [] => {
+            let mut names: Vec<&String> = debugger.ltrace.tracepoints.values().collect();
+            names.sort();
+            outln!(debugger, "{:<8}  Function", "Calls");
+            for name in names {
+                outln!(debugger, "{:<8}  {}", debugger.ltrace.calls.get(name).copied().unwrap_or(0), name);
+            }
+        }
+        _ => errln!(debugger, "Usage: ltrace on [function,...] | ltrace off | ltrace"),
+    }
+}
@@ -11,21 +11,189 @@
 //! cargo run <program_path>
 //! ```
 //!
+//! The arguments after `--` are given to the program. `-p <pid>` attaches to a running process instead, detached by
+//! `quit`. `--no-aslr` turns the address space randomization off, `--stop-at-entry` runs the program to the entry point
+//! of the executable before the commands, `--stop-at-main` to its main function as `start` does, and `--help` lists the
+//! options. `--core <corefile> <program_path>` reads the registers and memory of a core file instead of starting the
+//! program: the commands reading it work, the ones resuming or changing it say `Not supported on core files.`
+//!
+//! The standard output and error of the program are captured and printed above the prompt, each line prefixed with
+//! `[out]` or `[err]`. `tty <path>` (or `--tty <path>`) gives the program another terminal instead, and `set
+//! inferior-tty inherit` the one of the debugger, from the next `restart`. A captured program reads a pipe written by
+//! `input`, and `run < file` gives it a file to read instead.
+//!
+//! A 32-bit (i386) program is debugged too: `r` shows `eax` to `eip`, `m` reads 4-byte words, the code is decoded as
+//! 32-bit, `s` names the i386 system calls, `bt` follows the frame pointers and `info args` reads the arguments on the
+//! stack. `$eax`... are the low halves of the registers in values.
+//!
+//! The debugger runs on x86_64 and aarch64 Linux. The breakpoints are written for the instruction of the host (`int3`, or the
+//! 4-byte `brk #0` of aarch64, where the pc is not rewound), and the registers are read through the view of `arch::Registers`:
+//! `pc`, `sp` and `x0`-`x30` on aarch64, whose system calls take their number in `x8` and their arguments in `x0`-`x5`.
+//! The commands decoding x86 code or using the x86 debug registers and flags (`disas`, `x/i`, `awatch`, the decoded
+//! `eflags`) are refused on aarch64, and `watch` falls back to software watchpoints there.
+//!
+//! `-v` logs the ptrace calls, the wait statuses and the breakpoints armed and removed to the standard error, and `-vv`
+//! the register and memory accesses too, to tell why a breakpoint does not hit. `set log-level` changes it at the prompt.
+//!
+//! `--gdbserver [host]:<port> <program_path>` lets gdb debug the program instead of the prompt: `target remote
+//! [host]:<port>` in gdb connects to it, then its commands are translated into the operations of this debugger with the
+//! GDB Remote Serial Protocol (registers, memory, breakpoints, continue, single step and Ctrl-C). With no host, only the
+//! local host can connect.
+//!
 //! Once the debugger is running, you'll be prompted with a debug console (rustdbg>). You can input various commands
-//! to control the debugger's behavior.
+//! to control the debugger's behavior. The prompt shows the state of the program, as in
+//! `rustdbg [pid 1234, stopped @ 0x555555555139 <main+0x4>]> `, `[running]` or `[no process]` before `run`.
+//!
+//! With `--interpreter json`, each command read from the standard input writes one JSON record
+//! `{"command": ..., "ok": ..., "data": ...}` on the standard output, each stop of the program a
+//! `{"type": "stop", "reason": ...}` record, each line of the program a `{"type": "output", "stream": ..., "line": ...}`
+//! record, and the text for people goes to the standard error.
+//!
+//! Errors and warnings go to the standard error. The exit status is 0 after `quit`, the one of the program in batch
+//! mode, 1 when the program cannot be started (exec failed), 2 when the command line is invalid and 3 when an `assert`
+//! fails in batch mode.
+//!
+//! A command can be abbreviated to any prefix matching no other command (`disa`, `backt`). The names of the commands
+//! win over the prefixes, so `c`, `b`, `r`, `m`, `n` and `s` keep their meaning, and `i` is `info`.
+//!
+//! The commands reading or resuming the program only run while it is stopped, and say why otherwise: `The program is not
+//! being run.` once it has terminated, or `The program is already running, use c to continue it.` for a second `run`.
+//!
+//! The threads of the program are traced as they are created. When one of them stops, the others are stopped too and it
+//! becomes the current thread (`[Switching to thread <number> (tid <tid>)]`), whose registers the commands read, until
+//! `thread <number>` selects another one. `c` resumes every thread, the stepping commands only the current one.
+//!
+//! Several commands can be given on one line separated by `;`, as in `b main; c; bt`, at the prompt, in scripts and
+//! with `-ex`. They run in order, and the rest of the line is skipped once the program has terminated. A `;` inside
+//! quotes is not a separator.
+//!
+//! Numbers and addresses given to the commands can be written in hexadecimal (`0x401126`), decimal (`42`) or binary
+//! (`0b101`), or as a register of the selected frame (`$pc`, `$sp`, `$rax`), a convenience variable (`$base`), a label (`input_buf`), a symbol (`main`) or one of them plus an offset
+//! (`main+0x10`, `$base+0x1234`). Locations (`b`, `list`, `disas`, `info line`) can also be a source line (`file.c:12`). An invalid
+//! argument is reported with the character where it went wrong and the forms accepted.
+//!
+//! The settings changed by `set` and listed by `show` can be given defaults in `~/.config/rustdbg/config.toml`, a small
+//! subset of TOML (`disassembly-flavor = "att"`, or `limit = 8` in a `[backtrace]` table).
+//!
+//! At startup, the commands of `~/.config/rustdbg/init` run before the `-x` and `-ex` commands, then the ones of the
+//! `.rustdbginit` of the current directory, after asking first unless `set auto-load-local on` was given.
 //!
 //! ## Commands
 //!
 //! The following commands are supported:
 //!
-//! - `c` or `continue`: Continue program execution.
-//! - `s` or `syscall`: Step into the next system call.
-//! - `n` or `next`: Execute the next line of code.
-//! - `r` or `registers`: Display register states.
+//! - `run [argument...] [< file]`: Start the program, which is stopped before its first instruction until it is resumed by `run`, `c` or a step. With arguments or `< file`, read as its standard input, it is started again with them first, keeping the breakpoints: they replace the ones of `set args` for this run only, `restart` goes back to those.
+//! - `start [argument...] [< file]`: Start the program as `run` does and stop at the first line of its main function, with a temporary breakpoint: the function marked `DW_AT_main_subprogram` (the `main` of a Rust crate), the Rust `main` run by `std::rt::lang_start` found from its mangled symbol, the `main` symbol, or the entry point of a stripped program. It tells which one it stopped at, and why.
+//! - `input [text]` / `input --eof`: Write a line to the standard input of the program, a pipe of the debugger while its output is captured, or close it so that it reads the end of the file.
+//! - `restart`: Kill the program and start it again, stopped before its first instruction, with its symbols loaded again and the breakpoints set again at the locations given to `b`.
+//! - `tty <path>`: Start the program with the terminal at that path as its standard input, output and error from the next `restart`, instead of capturing its output.
+//! - `c` or `continue`: Continue program execution. Each stop starts with a line telling exactly why the program stopped, as `Breakpoint 2, parse at input.c:88`, `Step completed.`, `Caught syscall openat (257) (entry)`, `Hardware watchpoint total: value changed 3 -> 4` or `Program received signal SIGPIPE.`. When the program crashes (SIGSEGV, SIGBUS, SIGILL, SIGFPE), a crash report shows the faulting address and instruction, the memory access at fault, the registers and a backtrace.
+//! - `s` or `syscall`: Run until the next system call entry or exit, showing its name (and its return value on exit). A `ptrace` call or the opening of `/proc/self/status` is reported as a likely anti-debugging check.
+//! - `n` or `next`: Execute the next line of code, stepping over function calls.
+//! - `step`: Execute the next line of code, entering called functions.
+//! - `si` or `stepi`: Execute a single instruction. Steps show the disassembled instruction at the new pc.
+//! - `swatch <address> <len> [function...]`: Software watchpoint: single-step the program until the `len` bytes at the address change, then show the instruction that changed them with the old and new bytes. Very slow: Ctrl-C interrupts it, and the listed functions run at full speed until they return when called.
+//! - `watch [variable]`: Stop the program when a static variable, named as for `print`, changes, showing its old and new values with its type. A variable of 1, 2, 4 or 8 bytes aligned on its size uses one of the 4 debug registers, at full speed; a larger one is checked by single-stepping the program at each `c`, very slowly, and is then the only watchpoint. Without a variable, list the watchpoints.
+//! - `awatch <variable>`: Stop the program when a static variable is read or written, with a debug register only.
+//! - `unwatch <variable>`: Delete the watchpoint on a variable. Watchpoints are set again by `restart`.
+//! - `watch-region [<address> <len>]`: Stop the program when it writes to the `len` bytes at the address, at full speed whatever their size: their pages are made read-only with `mprotect`, and a write to the pages outside of the region is single-stepped with them writable again. The stop shows the address written, the instruction and the old and new bytes. A system call writing to the pages fails with `EFAULT` instead. Without arguments, list the regions with their writes.
+//! - `unwatch-region <number>`: Delete a region watchpoint, giving its pages back their protection. Region watchpoints are set again at the same addresses by `restart`.
+//! - `snapshot`: Save the registers of every thread and a full copy of the private writable memory of the program (data, bss, heap, stacks). The last 16 are kept.
+//! - `rc` or `reverse-continue`: Go back to the previous breakpoint hit: restore the latest snapshot before it and execute the program again until it hits that breakpoint in the same state. WARNING: only the registers and the private writable memory go back, not the files, the output, the sockets, the other processes nor the state kept by the kernel, and the code executed again makes its system calls again.
+//! - `rsi` or `reverse-stepi`: Go back one instruction: restore the latest snapshot before it, count the instructions executed since, and execute the program again up to the previous one.
+//! - `r`, `registers` or `regs`: Display register states. `r diff <snapshot> [<snapshot> | live]` compares the registers of the current thread saved by two snapshots, or by a snapshot and the program: only the differing registers, with both values, their XOR and their bytes as ASCII, and the flags of `eflags` decoded on both sides (`[ PF ZF IF ]`) with the ones that flipped.
 //! - `m <address>` or `memory <address>`: View the memory contents at a specified address.
-//! - `b <address>` or `breakpoint <address>`: Set a breakpoint at a specified address.
-//! - `h` or `help`: Display help information.
-//! - `q` or `quit`: Exit the debugger.
+//! - `x[/<count><format><size>] <address>`: Examine memory as gdb's `x` does: `count` units (1) of a size, `b`, `h`, `w` (the default) or `g`, shown in a format, `x` (hexadecimal, the default), `d`, `u`, `t` (binary) or `c` (characters), or `count` NUL terminated strings (`s`) or instructions (`i`), as in `x/16xb $sp`, `x/4gx 0x7ffc3a2b1e40`, `x/s $rdi` and `x/5i $pc`.
+//! - `gcore [path]`: Save a core file of the program to `path` (`core.<pid>` by default), with the registers of every thread and its memory, for `gdb <program> <core>`.
+//! - `b <address|symbol|file:line>` or `breakpoint <address|symbol|file:line>`: Set a breakpoint at a specified address, symbol or source line. Breakpoints on a function stop after its prologue and show its arguments. When the program executes another program (`execve`), the breakpoints on a function or line are set again in it, or pending until the next exec or `restart` if it has none, and the breakpoints on an address are dropped.
+//! - `rbreak <regex>`: Set a breakpoint on every function of the program and of the loaded libraries matching a regular expression, asking first above 50 functions. The breakpoints form a group, shown by `info breakpoints`.
+//! - `delete <number>` / `delete group <id>`: Remove the breakpoint numbered as in `info breakpoints`, or the breakpoints of a group set by `rbreak`. `restart` does not set them again.
+//! - `sym <address>` or `symbol <address>`: Show the symbol containing an address.
+//! - `ltrace on [function,...]` / `ltrace off`: Trace the library calls made through the PLT of the program (all, or the listed functions), showing their first arguments while `c` continues. `ltrace` alone counts the calls seen.
+//! - `cover start [step]` / `cover stop` / `cover report` / `cover save [--drcov] <file>`: Record the code executed. `cover start` finds the basic blocks of the functions of the program from their disassembly and puts a breakpoint on each, removed the first time it is entered, so the program soon runs at full speed. `cover start step` single-steps the current thread when `c` continues it, recording every instruction, in the libraries too. `cover report` shows the addresses executed in each function with its number of blocks, `cover save` writes them one per line, or in the drcov format read by lighthouse and other coverage viewers.
+//! - `record on <file>` / `record off` / `record stats`: Append a JSON line to the file for each instruction stepped by `si`, `step` and `next`, with its pc and the registers it changed (every register on the first line, and after the program ran unrecorded), to diff two runs. `record stats` shows the instructions recorded and the size of the file.
+//! - `script eval '<code>'` / `script file <path>` / `script clear`: Run a [Rhai](https://rhai.rs) script, with bindings to read and write the registers (`reg`, `set_reg`) and the memory (`read_memory`, `read_u64`, `write_memory`, `write_u64`), resolve symbols, set breakpoints, resume the program (`cont`, `stepi`, returning the stop event), run commands (`command`) and print. `on_stop(|event| ...)` registers a function called at each stop until `script clear`. `examples/malloc_sizes.rhai` logs the size of each allocation.
+//! - `disas [--raw] [address|function] [count|end|+len] [> file]` or `disassemble`: Disassemble `count` instructions (10) around the pc of the selected frame (the call of a caller picked with `up`) or at an address, a whole function, or the raw range up to `end` or of `len` bytes, optionally writing the listing to a file. Undecodable bytes are shown as `.byte`. `=>` marks the current instruction, in the innermost frame. Calls and jumps show the symbol of their destination (and `[-> +n]` when it is n lines away in the listing), `rip`-relative operands show the address and a preview of the data they designate. A conditional jump at the pc shows whether it will be taken, from the flags it tests. `B<n>` marks the instructions where breakpoint n is armed, and a breakpoint in the middle of an instruction is pointed out. `--raw` decodes the bytes as they are in memory, with the breakpoint instructions.
+//! - `l [file:line|function]` or `list [file:line|function]`: Show the source lines around the current line or a location.
+//! - `set substitute-path <from> <to>`: Look for source files under `<to>` instead of `<from>`.
+//! - `set debug-file-directory <path>[:<path>...]`: Look for separate debug files (by build ID or debug link) there instead of `/usr/lib/debug`.
+//! - `where`: Show in one line where the program is stopped and why: the pc with its function and offset, the source line, the mapping containing it with its permissions, the reason (`breakpoint 1`, `step`, `signal SIGSEGV`...) and the thread, as in `0x0000555555555140 in count+0x7 at loop.c:6 [loop r-xp], breakpoint 1, thread 1 (tid 1234)`. The same line is shown at each stop, unless `set stop-summary off`.
+//! - `bt [count]` or `backtrace [count]`: Show the call stack, inlined functions are marked "(inlined)".
+//! - `f [number]` or `frame [number]`: Select a stack frame, or describe the selected one.
+//! - `up [count]` / `down [count]`: Select the caller / callee of the selected frame.
+//! - `thread [number]`: Select the thread with that number in `info threads`, whose registers and stack the commands read and which the stepping commands step, or describe the current one.
+//! - `interrupt`: Stop the threads left running in non-stop mode, showing where each one stopped.
+//! - `set non-stop on|off`: Stop only the thread that hits a breakpoint or gets a signal, the others keep running, and `c` continues the current thread only, `c -a` every thread (off).
+//! - `set backtrace limit <count>`: Change the default number of frames shown by `bt` (32).
+//! - `set disassembly-flavor intel|att`: Choose the syntax of disassembled instructions (intel).
+//! - `set script-on-error stop|continue`: Whether a failed command stops the script running it (stop).
+//! - `set show-instruction on|off`: Also show the instruction at the pc when a breakpoint is hit (off).
+//! - `set stop-summary on|off`: Show the line of `where` at each stop (on).
+//! - `set track-fds on|off`: Compare the open file descriptors of the program at each stop with the ones at the previous stop, and print the changes under the banner of the stop, as `opened fd 5 -> /tmp/cache.db`, `closed fd 3 (was /tmp/cache.db)` or `reopened fd 1 -> /dev/null (was /dev/pts/0)`, to catch descriptor leaks and unexpected file accesses without tracing the system calls. Nothing is printed when nothing changed (off).
+//! - `set snapshot-interval <count>`: Take a snapshot every that many stops, for `reverse-continue` and `reverse-stepi` (0, none).
+//! - `set run-status interval <seconds>|off`: While `c` waits for the program, print a line every that many seconds with its state from `/proc/<pid>/stat` (R running, S sleeping, D disk sleep), the CPU time it used meanwhile, and the system call and kernel function (`wchan`) it waits in, as in `[running for 10s] S (sleeping) in read, wchan pipe_read, 0.00s of CPU in the last 5s` (off).
+//! - `set color on|off`: Color addresses, registers, changed values, errors and the prompt on a terminal (on, off with `--no-color` or `NO_COLOR`).
+//! - `set inferior-tty capture|inherit|<path>`: Where the standard output and error of the program go from the next `restart`: printed prefixed with `[out]` and `[err]`, the terminal of the debugger, or another terminal as with `tty` (capture).
+//! - `set log-level off|error|warn|info|debug|trace`: Log the ptrace calls, wait statuses and breakpoints (debug), and the register and memory accesses too (trace), to the standard error (warn, debug with `-v`, trace with `-vv`).
+//! - `set logging on|off`: Append the commands entered and their output to the log file with timestamps, after a header naming the program and its pid (off).
+//! - `set logging file <path>`: Change the log file (rustdbg.log).
+//! - `set prompt <template>`: Change the prompt. `{pid}`, `{state}` (stopped, running or no process), `{pc}`, `{symbol}` (as in `main+0x4`) and `{status}` (all of them) are replaced, so `set prompt "(dbg {symbol}) "` keeps it short. Quote the template to keep its spaces, `set prompt` alone restores `rustdbg [{status}]> `.
+//! - `set backtrace inline-depth <count>`: Change how many inlined functions `bt` shows for each frame (8, 0 to hide them).
+//! - `set args [argument...] [< file]`: The arguments of the program and the file it reads as its standard input, from the next `run` or `restart` (none).
+//! - `set cwd [<directory>]`: The directory the program starts in from the next `run` or `restart`, for the programs opening relative paths: the child enters it before executing the program. Empty, the directory of the debugger (empty). `show args` and `show cwd` show them.
+//! - `set env <name>=<value>|--clear|--inherit`: Add a variable to the environment of the program from the next `run` or `restart`, or replace its value. The program inherits the environment of the debugger, or only the variables set with `--clear`, and again the one of the debugger with `--inherit`. `show env` shows them.
+//! - `set rlimit <resource> [<soft>[:<hard>]]`: Limit a resource of the program from the next `run` or `restart`, as when reproducing an out of memory or a shortage of file descriptors: the child sets it before executing the program. The resources are `AS`, `CORE`, `CPU`, `DATA`, `FSIZE`, `NOFILE`, `NPROC` and `STACK`, the limits numbers with `K`, `M` or `G` after them or `unlimited`. Without a hard limit the one of the debugger is kept, raising it needs root, and without any the resource is not limited again. `show rlimits` shows the limits of every resource the program gets, set or inherited.
+//! - `set anti-anti-debug on|off`: Make a failing `ptrace(PTRACE_TRACEME)`, the check of a program for a debugger, return 0, watching the system calls of the program during `c` too (off).
+//! - `set auto-load-local ask|on|off`: Whether the `.rustdbginit` of the current directory is run at startup, after asking first (ask).
+//! - `set pagination on|off`: Stop long output at each screenful with a `--More--` prompt on a terminal: `q` drops the rest, Enter shows one more line and Space the next page (on, off in batch mode).
+//! - `set $<variable> = <value>`: Keep a value in a convenience variable, usable as `$<variable>` wherever a value or an address is expected, as in `m $base+0x1234`. The names of the registers cannot be used.
+//! - `set mem <address> = <byte> [<byte>...]`: Write bytes to the memory of the program. Writes to a read-only or executable mapping ask first, naming the mapping and its permissions, since ptrace writes bypass the page protections: `set! mem` writes without asking, with a warning.
+//! - `set confirm on|off`: Ask before `quit` and `restart` kill the running program, before `define` replaces a user command, before `rbreak` sets more than 50 breakpoints and before `set mem` writes to a read-only or executable mapping (on); the other commands never ask, the reverse commands restoring a snapshot at once. There are no `kill`, `detach`, `jump` or `return` commands yet to ask before. Batch mode never asks, and when the commands are not read from a terminal the question is answered yes. A `!` after the name of a command (`quit!`) skips the question once.
+//! - `show [setting]`: Show every setting with its value, or describe one setting with its default and the values `set` accepts. An unknown setting suggests the close names.
+//! - `info convenience`: List the convenience variables with their values.
+//! - `label <address> <name> [length]`: Name an address, or the `length` bytes from it. Disassembly operands, `m`, `info line` and the other address annotations show the label (`<input_buf+8>`) before the symbols, and the label is usable as a value wherever an address is expected. `label` alone or `info labels` lists them, `label --save <file>` writes them as commands to `source` later.
+//! - `unlabel <name>`: Remove a label.
+//! - `info frame`: Describe the selected frame: its canonical frame address (CFA, the stack pointer before the call) and how it is computed, whether the call frame information or the frame pointer chain unwinds it, where its return address and the registers it saved are on the stack with their values, its size, and its function and line. `info f` is short for it.
+//! - `info locals`: Show the local variables of the selected frame.
+//! - `info args`: Show the arguments of the selected frame.
+//! - `p [/full] <variable>` or `print [/full] <variable>`: Show the value of a local variable, parameter or static (Rust statics match by their last path components, thread-local statics of the program are read for the traced thread). Structs show their fields, arrays their first 16 elements, Rust enums their variant (`Some(7)`, `Shape::Circle { radius: 9 }`) and pointers the value they point to; the structs nested in a value show as `{...}` unless `/full` is given.
+//! - `display <expression>`: Show an expression, numbered, at every stop of the program, as `1: counter = 7`: a variable as `p` shows it, or a value as `assert` reads it (`$rax`, `*0x601040`) in hexadecimal and decimal. An expression that can't be evaluated, as an address no longer mapped, shows its error in its place without stopping the others. `display` alone shows them all, `info display` lists them with the values shown at the last stop.
+//! - `undisplay <number>...`: Remove expressions added with `display`.
+//! - `call <function>(<argument>, ...)`: Call a function of the program in the current thread, as `call malloc_stats()` or `call dump_state($rdi, "after the loop")`, and show the value it returns in `rax`. Up to 6 integer or pointer arguments are passed in registers with the System V calling convention, string literals being written to the stack and passed by address. The function returns to a breakpoint instruction at the entry point of the program, the breakpoints are disarmed during the call and the other threads stay stopped. The registers are restored afterwards, and when the program receives a signal in the function, which is then abandoned. 64-bit programs only.
+//! - `alloc <size>`: Map `<size>` bytes of anonymous, readable and writable memory in the program, rounded up to whole pages, and show its address: scratch space for `set mem` or the arguments of `call`. The current thread makes the `mmap` system call (`mmap2` for a 32-bit program), its instruction written over the one at the pc and stepped, then the code and the registers put back. Not while the program is stopped in a system call.
+//! - `dealloc <address> <size>`: Unmap memory of the program, as mapped by `alloc`, with a `munmap` system call made the same way.
+//! - `info address <symbol>`: Show the section, file address, runtime address and object of a symbol.
+//! - `info symbol <regex>`: Search the symbols of every loaded object, matching mangled and demangled names.
+//! - `info variables [regex]`: List the global and static data symbols, optionally filtered by a regular expression.
+//! - `info line <address|function|file:line>`: Show the source line of an address and its address range, or the address of a line.
+//! - `info file`: Show the ELF header of the program: type, machine, entry point and interpreter.
+//! - `info sections`: List the sections of the program, marking the mapped ones with `*`.
+//! - `info breakpoints`: List the armed breakpoints with their number, symbol and the instruction they are set on, then the breakpoints pending since an exec.
+//! - `info sharedlibrary`: List the shared libraries loaded by the program, and whether their debug information was found. The vDSO the kernel maps in the program (`[vdso]`) comes last, its symbols read from the memory of the program, so the frames and addresses in it are named, as `__vdso_clock_gettime`.
+//! - `info dl`: Show the state of the dynamic loader: the interpreter, `AT_BASE` and `AT_ENTRY` from the auxiliary vector, whether `r_debug` is initialized and how many objects its link map lists, and whether the pc is in the loader. Until the loader has relocated the program, as at the first instruction after exec, the symbols of the libraries are not known: the prompt and the stop banners say so, with how to run to `main`.
+//! - `info threads`: List the threads of the program with their tid, state and the function they are in, `*` marking the current thread.
+//! - `info proc`: Show the process of the program from `/proc`: its name, state, parent, threads and memory use.
+//! - `info proc cwd` / `info proc exe`: Show the working directory or the executable of the process.
+//! - `info proc fd`: List the open file descriptors of the process with their flags, offset and target.
+//! - `info signal`: Show again the details of the signal the program is stopped by: its `si_code` named (as `SEGV_MAPERR`), the faulting address, or the process and user that sent it.
+//! - `info syscall`: Show the system call the current thread is in, from `/proc/<pid>/task/<tid>/syscall`, with its arguments decoded as strace does and what it returned, as `read(0, 0x7ffd3b2c1e40, 4096) = ? ERESTARTSYS (To be restarted if SA_RESTART is set)`, or that it is not in a syscall. When a signal, as the `SIGINT` of Ctrl-C, interrupts a blocked system call, the stop says so: `Interrupted in read(0, 0x7ffd3b2c1e40, 4096).`
+//! - `info maps`: List the memory mappings of the program with their start, end, size, file offset, permissions and file.
+//! - `info snapshots`: List the snapshots kept, with the stop they were taken at, the pc and the memory they hold.
+//! - `sharedlibrary <name>`: Load the symbols of the shared libraries matching a name.
+//! - `echo <text>`: Print a line of text. `\n` starts a new line, `\t` is a tab and `\\` a backslash.
+//! - `printf "<format>" [value, ...]`: Print values, read as addresses are, with a C format: `%x`, `%d`, `%u`, `%p`, `%c` and `%s` (the string the value points to in the program), with the `-` and `0` flags and a width. The output always ends with a line.
+//! - `assert <value> [==|!=|<|<=|>|>= <value>]`: Check a comparison of values, read as addresses are, `*<value>` reading the pointer-sized integer at an address and `-<value>` negating. Values compare as the signed 64-bit integers of C, `$eax` as a signed 32-bit one, unless one of them is a symbol, has an offset or is above `0x7fffffffffffffff`: the comparison is then unsigned. A failure shows the values; in batch mode it kills the program and exits with the status 3, so `-ex 'assert $rax == 0x5'` makes a test.
+//! - `shell <command line>` or `!<command line>`: Run a command line with `$SHELL` (or `/bin/sh`) on the terminal of the debugger, reporting its exit status when it fails. The rest of the line goes to the shell as typed, `;` included.
+//! - `set build-command <command line>`: The command line run by `rebuild`, as in `cargo build` (none).
+//! - `rebuild`: Run the build command with the shell. The symbols loaded are then stale until `restart` runs the new program, loads its symbols and sets the breakpoints again.
+//! - `h [command]` or `help [command]`: List the commands, or show every form of a command with examples. Close matches are suggested for an unknown command.
+//! - `alias <name> = "<command>[; <command>...]"`: Define an alias running one or more commands. `$1`, `$2`... are replaced with its arguments and `$*` with all of them, so `alias bm = "b $1; c"` makes `bm main` break on `main` and continue. `alias` alone or `info alias` lists the aliases.
+//! - `unalias <name>`: Remove an alias.
+//! - `define <name>`: Define a user command running the commands of the following lines, up to a line saying just `end` (the prompt is `>` meanwhile). `$arg0`, `$arg1`... are replaced with its arguments and `$argc` with their number. It stops at the first of its commands that fails, and user commands can run each other 16 deep at most. `help user-defined` lists them and `help <name>` shows the commands of one.
+//! - `undefine <name>`: Remove a user command.
+//! - `source <file>`: Run the commands of a script file in order, printing each command before its output. Blank lines and lines starting with `#` are skipped.
+//! - `session save <file>` and `session replay <file>`: Save the commands entered since the start with their results, the program, its arguments and the ASLR setting, to reproduce a session on the same program, as in a bug report against the debugger. `session replay` starts the program again as recorded and runs the commands like `source`, stopping before a command whose addresses are in a file or the stack loaded elsewhere than when it was recorded, unless it was recorded with `--no-aslr`.
+//! - `q` or `quit`: Kill the program and exit the debugger, asking first when it is running. The end of the input (Ctrl-D) quits too.
 //!
 //! ## Example
 //!
@@ -37,6 +205,14 @@
 //! rustdbg>
 //! ```
 //!
+//! ## Library
+//!
+//! The debugger is also the `rustdbg` library, which this binary is a prompt over. `Debugger::launch(path, args)` and
+//! `Debugger::attach(pid)` create a session, controlled with `set_breakpoint`, `cont`, `step`, `wait_event`,
+//! `read_memory`, `registers`, and `allocate` and `deallocate` for scratch memory in the program, or with the commands of the prompt through `run_command`. `dispatch` runs a command
+//! keeping its output, returned with whether the session goes on (`quit`) or as the error of a failed command.
+//! `cargo doc --open` shows its documentation and examples.
+//!
 //! ## Testing
 //!
 //! Unit tests are provided to ensure the correctness of debugger functionalities. Run the tests using:
@@ -45,10 +221,10 @@
 //! cargo test
 //! ```
 //!
-//! ## Modules
-//!
-//! - `syscall`: Provides utilities to work with system calls.
-//! - `working`: Contains various functions for debugger operations.
+//! The integration tests of `tests/` run the `dbg_rust` binary in batch mode on the C programs of `tests/fixtures`
+//! (a loop, a crash, a fork, threads, system calls, output to both streams and input), compiled by `build.rs` with `cc`, and check its JSON records.
+//! `tests/gdbserver.rs` speaks the remote protocol to `--gdbserver`, and runs gdb against it where gdb is installed.
+//! The tests needing ptrace are skipped where it is not allowed, as in a container without `CAP_SYS_PTRACE`.
 //!
 //! ## Note
 //!
@@ -66,157 +242,212 @@
 //! 
 //! 
 //! 
-use std::ffi::{CStr, CString};
-use std::io::{self, Write};
-use nix::sys::ptrace;
-use nix::unistd::{self, fork, ForkResult};
-use nix::sys::wait::waitpid;
-mod syscall;
-mod working;
-use crate::working::prettier;
-use crate::working::show_registers;
-use crate::working::help_commands;
-use crate::working::set_breakpoint;
+use rustdbg::debugger::Debugger;
+use rustdbg::error::DbgError;
+use rustdbg::inferior::ChildStdio;
+use rustdbg::output::Terminal;
+use rustdbg::state::InferiorState;
+use rustdbg::{corefile, dispatch, errln, gdbserver, inferior, json, launch, logging, nonstop, options, outln, prompt, repl, run_command, script, settings, sharedlib, Control};
 
+/// Exit the debugger after `quit`, or once the program has terminated. In batch mode, the exit
+/// status of the program is the one of the debugger.
+fn exit_if_terminated(debugger: &mut Debugger) {
+    if debugger.quit_requested {
+        debugger.exit(if debugger.assertion_failed { EXIT_ASSERTION_FAILED } else { 0 });
+    }
+    let Some(status) = debugger.exit_status() else {
+        return;
+    };
+    if debugger.batch {
+        debugger.exit(status);
+    }
+    outln!(debugger, "rustdbg> Child process has terminated.");
+    debugger.exit(0);
+}
 
-/// Executes the specified command in the debugger.
-///
-/// # Arguments
-///
-/// * `command` - A string slice representing the command to execute.
-/// * `child` - The process ID (Pid) of the child being debugged.
-///
-/// # Example
-///
-/// ```rust
-/// run_command("c", child_pid);
-/// ```
-///
+/// The exit status when the command line is invalid.
+const EXIT_USAGE: i32 = 2;
+/// The exit status when the program cannot be started.
+const EXIT_STARTUP_FAILED: i32 = 1;
+/// The exit status when an `assert` fails in batch mode.
+const EXIT_ASSERTION_FAILED: i32 = 3;
 
-fn run_command(command: &str, child: unistd::Pid) {
-    let args: Vec<&str> = command.split_whitespace().collect();
-    match args.get(0) {
-        Some(&"c" | &"continue") => {
-            println!("Continuing execution...");
-            if let Err(err) = ptrace::cont(child, None) {
-                println!("Failed to continue execution: {:?}", err);
-            } else {
-                prettier(child);
-            }
+/// Entry point of the debugger application.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut config = match options::parse(&args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", options::USAGE);
+            std::process::exit(EXIT_USAGE);
         }
-        Some(&"s" | &"syscall") => {
-            if let Err(err) = waitpid(child, None) {
-                println!("Failed to wait: {:?}", err);
-                return;
+    };
+    if config.help {
+        println!("{}", options::HELP);
+        return;
+    }
+    if config.version {
+        println!("{}", options::version());
+        return;
+    }
+    logging::init(config.verbosity);
+    let json = config.interpreter == options::Interpreter::Json;
+    // The standard streams of the program started, captured once the output of the debugger is set up.
+    let mut stdio = ChildStdio::default();
+    let mut debugger = match config.core {
+        Some(_) => match Debugger::open_core(&config) {
+            Ok(debugger) => debugger,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(EXIT_STARTUP_FAILED);
             }
-            let registers_syscall = match ptrace::getregs(child) {
-                Ok(registers) => registers,
+        },
+        None => {
+            let started = match config.pid {
+                Some(pid) => launch::attach(pid).map(|(child, path)| {
+                    config.program_path = path;
+                    child
+                }),
+                None => {
+                    let spec = launch::LaunchSpec {
+                        program_path: config.program_path.clone(),
+                        args: config.program_args.clone(),
+                        no_aslr: config.no_aslr,
+                        ..launch::LaunchSpec::default()
+                    };
+                    launch::start(&spec, &config.inferior_tty).map(|(child, opened)| {
+                        stdio = opened;
+                        child
+                    })
+                }
+            };
+            let child = match started {
+                Ok(child) => child,
                 Err(err) => {
-                    println!("Could not get child's registers: {:?}", err);
-                    return;
+                    eprintln!("{}", err);
+                    std::process::exit(EXIT_STARTUP_FAILED);
                 }
             };
-            let _syscall_name = syscall::syscall_name(registers_syscall.orig_rax);
-            println!("Entering {} ({}) syscall", _syscall_name, registers_syscall.orig_rax);
-            if let Err(err) = ptrace::syscall(child, None) {
-                println!("Failed to use PTRACE_SYSCALL: {:?}", err);
+            let announce = if config.pid.is_some() { format!("Attached to process {} ({})", child, config.program_path) } else { format!("Child pid: {}", child) };
+            if json {
+                eprintln!("{}", announce);
+            } else if !config.batch {
+                println!("{}", announce);
             }
+            Debugger::new(child, &config)
         }
-        Some(&"n" | &"next") => {
-            println!("Taking a single step...");
-            if let Err(err) = ptrace::step(child, None) {
-                println!("Failed to continue execution: {:?}", err);
-            }
-        }
-        Some(&"r" | &"registers") => {
-            println!("Showing register states...");
-            show_registers(child);
-        }
-        Some(&"m" | &"memory") => {
-            if args.len() != 2 {
-                println!("Usage: m <address>");
-                return;
-            }
-            let hex_address = args[1];
-            if !hex_address.starts_with("0x") {
-                println!("Your address should start with 0x !");
-                return;
+    };
+    if json {
+        json::start(&mut debugger, Box::new(Terminal::stderr(!config.no_color)), Box::new(std::io::stdout()));
+    }
+    inferior::capture(&mut debugger, stdio);
+    settings::load_config(&mut debugger);
+    if config.core.is_some() {
+        corefile::report_core(&mut debugger);
+    }
+    if config.stop_at_entry {
+        match launch::run_to_entry(&mut debugger) {
+            Ok(entry) => {
+                outln!(debugger, "Stopped at the entry point {:#x}", entry);
+                debugger.print_stop_location(entry);
             }
-            let hex_address = &hex_address[2..]; // Removing "0x" prefix
-            match u64::from_str_radix(hex_address, 16) {
-                Ok(address) => {
-                    match ptrace::read(child, address as nix::sys::ptrace::AddressType) {
-                        Ok(value) => println!("{:#018x}", value),
-                        Err(_) => println!("Not able to read the content of this address"),
-                    }
-                }
-                Err(_) => println!("Invalid address format"),
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                std::process::exit(EXIT_STARTUP_FAILED);
             }
         }
-        Some(&"b" | &"breakpoint") => {
-            if args.len() != 2 {
-                println!("Usage: b <address>");
-                return;
-            }
-            let hex_address = args[1];
-            if !hex_address.starts_with("0x") {
-                println!("Your address should start with 0x !");
-                return;
-            }
-            let hex_address = &hex_address[2..];
-            match u64::from_str_radix(hex_address, 16) {
-                Ok(address) => {
-                    if let Err(err) = set_breakpoint(child, address) {
-                        println!("Failed to set breakpoint: {:?}", err);
-                    }
-                }
-                Err(_) => println!("Invalid address format"),
+    }
+    if config.stop_at_main && !launch::run_to_main(&mut debugger) {
+        std::process::exit(EXIT_STARTUP_FAILED);
+    }
+    if let Some(address) = &config.gdbserver {
+        // gdb runs the commands, the ones of the init files are not for it.
+        match gdbserver::serve(&mut debugger, address) {
+            Ok(status) => debugger.exit(status),
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                launch::release(&mut debugger);
+                debugger.exit(EXIT_STARTUP_FAILED);
             }
         }
-        Some(&"h" | &"help") => {
-            help_commands();
-        }
-        Some(&"q" | &"quit") => {
-            println!("Exiting the debugger !");
-            std::process::exit(0);
+    }
+    if !config.batch {
+        // Symbols of the libraries that "don't work" yet, at the first instruction after exec.
+        if let Some(hint) = sharedlib::loader_hint(&mut debugger) {
+            outln!(debugger, "{}", hint);
         }
-        _ => println!("Unknown command: {}", command),
     }
-}
-
-/// Entry point of the debugger application.
-
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: cargo run <program_path>");
-        return;
+    if config.batch {
+        // Nobody is there to turn the pages.
+        debugger.output.set_pagination(false);
     }
-    let program_path = &args[1];
-    let path: &CStr = &CString::new(program_path.clone()).unwrap();
-
-    match unsafe { fork() }.expect("Failed to fork") {
-        ForkResult::Parent { child } => {
-            println!("Child pid: {}", child);
-            loop {
-                print!("rustdbg> ");
-                io::stdout().flush().expect("Failed to flush stdout");
-                let mut input = String::new();
-                io::stdin()
-                    .read_line(&mut input)
-                    .expect("Failed to read line");
-
-                let input = input.trim().trim_end_matches(&['\r', '\n'][..]);
-
-                run_command(input, child);
+    if config.no_color {
+        debugger.output.set_color(false);
+    }
+    let global_init = settings::config_directory().map(|directory| directory.join("init"));
+    let local_init = std::path::Path::new(script::LOCAL_INIT_FILE);
+    let mut succeeded =
+        script::source_init_files(&mut debugger, global_init.as_deref(), local_init, run_command, script::ask_load_local);
+    exit_if_terminated(&mut debugger);
+    for action in &config.startup {
+        succeeded &= match action {
+            options::StartupAction::Script(path) => script::source(&mut debugger, path, run_command),
+            options::StartupAction::Command(command) => {
+                script::echo_command(&mut debugger, command);
+                run_command(command, &mut debugger)
             }
+        };
+        exit_if_terminated(&mut debugger);
+    }
+    if config.run_immediately && debugger.state == InferiorState::NotStarted {
+        succeeded &= run_command("run", &mut debugger);
+        exit_if_terminated(&mut debugger);
+    }
+    if config.batch {
+        // The commands are done, don't leave the program behind.
+        launch::release(&mut debugger);
+        debugger.exit(if succeeded { 0 } else { 1 });
+    }
+    if json {
+        // Tools write one command per line, without line editing nor a prompt.
+        let mut lines = std::io::stdin().lines();
+        loop {
+            let input = lines.next().and_then(Result::ok).unwrap_or_else(|| "quit".to_string());
+            debugger.transcript.write_command(&input);
+            run_command(&input, &mut debugger);
+            exit_if_terminated(&mut debugger);
         }
-        ForkResult::Child => {
-            ptrace::traceme().expect("Failed to call traceme in child");
-            nix::unistd::execve::<&CStr, &CStr>(path, &[], &[]).unwrap();
+    }
+    let mut repl = repl::PromptThread::new(debugger.symbols.index.clone()).expect("Failed to set up the prompt");
+    loop {
+        // End of input (Ctrl-D) quits like `quit`.
+        let prompt = prompt::prompt(&mut debugger);
+        // An empty line in a definition is not the previous command.
+        let forget_last_command = debugger.definition.is_some();
+        // The end of the input ends a definition first.
+        let end = if debugger.definition.is_some() { "end" } else { "quit" };
+        // The threads left running in non-stop mode stop while the command is typed.
+        let line = loop {
+            if let Some(line) = repl.read_command(&prompt, debugger.output.color(), forget_last_command, nonstop::POLL) {
+                break line;
+            }
+            nonstop::poll_above_prompt(&mut debugger);
+        };
+        let input = line.unwrap_or_else(|| end.to_string());
+        debugger.transcript.write_command(&input);
+        debugger.output.new_page();
+        match dispatch(&mut debugger, &input) {
+            Ok(outcome) => {
+                outcome.output.replay(debugger.output.as_mut());
+                if outcome.control == Control::Quit {
+                    debugger.exit(0);
+                }
+            }
+            Err(DbgError::Command { output, .. }) => output.replay(debugger.output.as_mut()),
+            Err(err) => errln!(debugger, "{}", err),
         }
+        exit_if_terminated(&mut debugger);
     }
 }
 
-#[cfg(test)]
-mod test;
@@ -0,0 +1,180 @@
+use crate::debugger::Debugger;
+use crate::json;
+use nix::unistd;
+use serde_json::json;
+use std::fs;
+use std::io;
+
+/// A single line of `/proc/<pid>/maps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapEntry {
+    pub start: u64,
+    pub end: u64,
+    pub perms: String,
+    pub offset: u64,
+    pub inode: u64,
+    pub pathname: Option<String>,
+}
+
+impl MapEntry {
+    /// Returns true if `address` falls inside this mapping.
+    pub fn contains(&self, address: u64) -> bool {
+        address >= self.start && address < self.end
+    }
+
+    /// Returns true if the mapping is executable.
+    pub fn is_executable(&self) -> bool {
+        self.perms.as_bytes().get(2) == Some(&b'x')
+    }
+
+    /// Returns true if the mapping is writable.
+    pub fn is_writable(&self) -> bool {
+        self.perms.as_bytes().get(1) == Some(&b'w')
+    }
+
+    /// Describe the mapping for messages: its range, permissions and file.
+    pub fn describe(&self) -> String {
+        format!("{:#x}-{:#x} {} {}", self.start, self.end, self.perms, self.pathname.as_deref().unwrap_or("anonymous"))
+    }
+
+    /// Returns true if the mapping is backed by a file on disk (not `[heap]`, `[stack]`, anonymous...).
+    pub fn is_file_backed(&self) -> bool {
+        self.inode != 0 && self.pathname.as_deref().is_some_and(|p| p.starts_with('/'))
+    }
+}
+
+/// A file mapped into the process, made of one or more contiguous mappings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedFile {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    /// Start address of the mapping with file offset 0, when there is one.
+    pub base: Option<u64>,
+}
+
+/// Parse the content of a `/proc/<pid>/maps` file.
+///
+/// # Arguments
+///
+/// * `content` - The text of the maps file.
+///
+/// # Returns
+///
+/// One `MapEntry` per well-formed line, malformed lines are skipped.
+pub fn parse_maps(content: &str) -> Vec<MapEntry> {
+    content.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<MapEntry> {
+    let mut fields = line.split_whitespace();
+    let (start, end) = fields.next()?.split_once('-')?;
+    let perms = fields.next()?.to_string();
+    let offset = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let _dev = fields.next()?;
+    let inode = fields.next()?.parse().ok()?;
+    let pathname: Vec<&str> = fields.collect();
+    Some(MapEntry {
+        start: u64::from_str_radix(start, 16).ok()?,
+        end: u64::from_str_radix(end, 16).ok()?,
+        perms,
+        offset,
+        inode,
+        pathname: if pathname.is_empty() { None } else { Some(pathname.join(" ")) },
+    })
+}
+
+/// Read and parse the memory mappings of the debugged process.
+///
+/// # Arguments
+///
+/// * `child` - The process ID (Pid) of the child being debugged.
+///
+/// # Errors
+///
+/// Returns an error if `/proc/<pid>/maps` can't be read.
+pub fn read_maps(child: unistd::Pid) -> io::Result<Vec<MapEntry>> {
+    let content = fs::read_to_string(format!("/proc/{}/maps", child))?;
+    Ok(parse_maps(&content))
+}
+
+/// Find the mapping containing `address`.
+pub fn find_mapping(entries: &[MapEntry], address: u64) -> Option<&MapEntry> {
+    entries.iter().find(|entry| entry.contains(address))
+}
+
+/// Group contiguous mappings of the same file, in address order.
+///
+/// The same file mapped twice at distant addresses is reported twice.
+pub fn mapped_files(entries: &[MapEntry]) -> Vec<MappedFile> {
+    let mut files: Vec<MappedFile> = Vec::new();
+    for entry in entries.iter().filter(|e| e.is_file_backed()) {
+        let path = entry.pathname.clone().unwrap_or_default();
+        let base = if entry.offset == 0 { Some(entry.start) } else { None };
+        match files.last_mut() {
+            Some(last) if last.path == path && last.end == entry.start => {
+                last.end = entry.end;
+                last.base = last.base.or(base);
+            }
+            _ => files.push(MappedFile { path, start: entry.start, end: entry.end, base }),
+        }
+    }
+    files
+}
+
+/// Find the mappings modified by a write of `len` bytes at `address` that are read-only or
+/// executable. ptrace writes bypass page protections, so these writes succeed all the same.
+///
+/// # Errors
+///
+/// Returns the first address of the range that is not mapped.
+pub fn protected_mappings(entries: &[MapEntry], address: u64, len: u64) -> Result<Vec<&MapEntry>, u64> {
+    let end = address.saturating_add(len);
+    let mut protected = Vec::new();
+    let mut current = address;
+    while current < end {
+        let mapping = find_mapping(entries, current).ok_or(current)?;
+        if !mapping.is_writable() || mapping.is_executable() {
+            protected.push(mapping);
+        }
+        current = mapping.end;
+    }
+    Ok(protected)
+}
+
+/// List the memory mappings of the program (`info maps`): their range, size, offset, permissions
+/// and file, from `/proc/<pid>/maps` or the core file.
+///
+/// # Returns
+///
+/// False if the program is not running or its mappings can't be read.
+pub fn info_maps(debugger: &mut Debugger) -> bool {
+    if !debugger.state.is_alive() {
+        errln!(debugger, "The program is not being run.");
+        return false;
+    }
+    let entries = match debugger.read_mappings() {
+        Ok(entries) => entries,
+        Err(err) => {
+            errln!(debugger, "Could not read /proc/{}/maps: {}", debugger.child, err);
+            return false;
+        }
+    };
+    let mut data = Vec::new();
+    outln!(debugger, "{:>18}  {:>18}  {:>10}  {:>10}  Perms  File", "Start", "End", "Size", "Offset");
+    for entry in &entries {
+        let file = entry.pathname.as_deref().unwrap_or("");
+        let line = format!("{:#18x}  {:#18x}  {:#10x}  {:#10x}  {:<5}  {}", entry.start, entry.end, entry.end - entry.start, entry.offset, entry.perms, file);
+        outln!(debugger, "{}", line.trim_end());
+        data.push(json!({
+            "start": json::address(entry.start),
+            "end": json::address(entry.end),
+            "size": entry.end - entry.start,
+            "offset": entry.offset,
+            "perms": entry.perms,
+            "file": entry.pathname,
+        }));
+    }
+    json::set_data(debugger, |_| json!({ "mappings": data }));
+    true
+}
@@ -0,0 +1,106 @@
+use crate::error::DbgError;
+use crate::tracee::Tracee;
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::unistd;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+
+const WORD_SIZE: u64 = std::mem::size_of::<i64>() as u64;
+
+/// Read `len` bytes of the debugged process memory starting at `address`.
+///
+/// # Arguments
+///
+/// * `tracee` - The process being debugged, or the core file being read.
+/// * `address` - The first address to read.
+/// * `len` - The number of bytes to read.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Memory`] if any word of the range can't be read.
+pub fn read_bytes(tracee: &(impl Tracee + ?Sized), address: u64, len: usize) -> Result<Vec<u8>, DbgError> {
+    let mut bytes = Vec::with_capacity(len + WORD_SIZE as usize);
+    let aligned = address & !(WORD_SIZE - 1);
+    let skip = (address - aligned) as usize;
+    let mut current = aligned;
+    while bytes.len() < skip + len {
+        let word = tracee.read_word(current).map_err(DbgError::memory(current.max(address)))?;
+        bytes.extend_from_slice(&word.to_le_bytes());
+        current += WORD_SIZE;
+    }
+    Ok(bytes[skip..skip + len].to_vec())
+}
+
+/// Write `bytes` to the debugged process memory starting at `address`, keeping the other bytes of
+/// the words written.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Memory`] if any word of the range can't be read or written.
+pub fn write_bytes(child: unistd::Pid, address: u64, bytes: &[u8]) -> Result<(), DbgError> {
+    let aligned = address & !(WORD_SIZE - 1);
+    let skip = (address - aligned) as usize;
+    let len = (skip + bytes.len()).div_ceil(WORD_SIZE as usize) * WORD_SIZE as usize;
+    let mut words = read_bytes(&child, aligned, len)?;
+    words[skip..skip + bytes.len()].copy_from_slice(bytes);
+    log::trace!("PTRACE_POKEDATA {} {:#x}, {} bytes", child, address, bytes.len());
+    crate::cache::invalidate();
+    for (index, word) in words.chunks(WORD_SIZE as usize).enumerate() {
+        let word = i64::from_le_bytes(word.try_into().unwrap());
+        let current = aligned + index as u64 * WORD_SIZE;
+        unsafe { ptrace::write(child, current as ptrace::AddressType, word as ptrace::AddressType) }
+            .map_err(DbgError::memory(current.max(address)))?;
+    }
+    Ok(())
+}
+
+/// Read a 64-bit little endian value from the debugged process memory.
+pub fn read_u64(tracee: &(impl Tracee + ?Sized), address: u64) -> Result<u64, DbgError> {
+    let bytes = read_bytes(tracee, address, WORD_SIZE as usize)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Read a NUL terminated string from the debugged process memory.
+///
+/// # Arguments
+///
+/// * `tracee` - The process being debugged, or the core file being read.
+/// * `address` - The address of the first character.
+/// * `max_len` - The maximum number of bytes to read before giving up on finding the terminator.
+pub fn read_c_string(tracee: &(impl Tracee + ?Sized), address: u64, max_len: usize) -> Result<String, DbgError> {
+    read_c_bytes(tracee, address, max_len).map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Read the bytes of a NUL terminated string from the debugged process memory, without the NUL:
+/// `max_len` bytes when it is not found before.
+pub fn read_c_bytes(tracee: &(impl Tracee + ?Sized), address: u64, max_len: usize) -> Result<Vec<u8>, DbgError> {
+    let mut bytes = Vec::new();
+    let mut current = address;
+    while bytes.len() < max_len {
+        let word = read_bytes(tracee, current, WORD_SIZE as usize)?;
+        if let Some(nul) = word.iter().position(|&b| b == 0) {
+            bytes.extend_from_slice(&word[..nul]);
+            break;
+        }
+        bytes.extend_from_slice(&word);
+        current += WORD_SIZE;
+    }
+    bytes.truncate(max_len);
+    Ok(bytes)
+}
+
+/// Read `len` bytes of the debugged process memory starting at `address` in one go, through
+/// `/proc/<pid>/mem`, for large ranges that would take a ptrace call per word.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Memory`] if the range can't be read in full, as when part of it is not
+/// mapped.
+pub fn read_bulk(child: unistd::Pid, address: u64, len: usize) -> Result<Vec<u8>, DbgError> {
+    let to_errno = |err: std::io::Error| err.raw_os_error().map_or(Errno::EIO, Errno::from_raw);
+    let file = File::open(format!("/proc/{}/mem", child)).map_err(|err| DbgError::memory(address)(to_errno(err)))?;
+    let mut bytes = vec![0; len];
+    file.read_exact_at(&mut bytes, address).map_err(|err| DbgError::memory(address)(to_errno(err)))?;
+    Ok(bytes)
+}
@@ -0,0 +1,137 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::json;
+use crate::output::{Recorder, Recording, Style, Written};
+use crate::state::InferiorState;
+use crate::step::{self, StepStop};
+use crate::threads::{self, ThreadState};
+use crate::working;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use serde_json::json;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How often the prompt looks for the stops of the threads left running in non-stop mode while a
+/// command is typed.
+pub const POLL: Duration = Duration::from_millis(100);
+
+/// Report the stops of the threads left running in non-stop mode, without waiting for them:
+/// each thread that stopped stays stopped, with its stop kept for when it is selected, and the
+/// current thread stays the current one. Run before each command, and at the prompt.
+pub fn poll(debugger: &mut Debugger) {
+    if !threads::any_running(debugger) || !debugger.state.is_alive() || debugger.core.is_some() {
+        return;
+    }
+    // The running threads change the memory under the commands.
+    crate::cache::invalidate();
+    loop {
+        let (tid, status) = match threads::poll_any(debugger) {
+            Ok(Some(event)) => event,
+            Ok(None) => return,
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return;
+            }
+        };
+        match working::filter_event(debugger, tid, status) {
+            Ok(Some(status)) => report(debugger, tid, status),
+            Ok(None) => {}
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return;
+            }
+        }
+        if debugger.state.has_terminated() {
+            return;
+        }
+    }
+}
+
+/// Report the stop of a thread left running, `[Thread 2 (tid 1235) stopped] Breakpoint 1, ...`,
+/// or the termination of the program, which is reported as `c` reports it.
+fn report(debugger: &mut Debugger, tid: Pid, status: WaitStatus) {
+    let stop = match crate::watch::take_hit(debugger) {
+        Some(stop) => stop,
+        None => match working::classify_stop(&mut debugger.breakpoint_table, tid, status) {
+            Ok(Some((stop, _))) => stop,
+            Ok(None) => return,
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return;
+            }
+        },
+    };
+    if let StepStop::Exited(_) | StepStop::Killed(_) = stop {
+        debugger.thread = debugger.child;
+        step::report_stop(debugger, &stop);
+        return;
+    }
+    if let Some(thread) = debugger.threads.iter_mut().find(|thread| thread.tid == tid) {
+        thread.stop = Some(stop.clone());
+    }
+    // The banner and the stop record describe the thread that stopped, not the current one.
+    let (current, state) = (debugger.thread, debugger.state);
+    debugger.thread = tid;
+    debugger.state = InferiorState::from_stop(&stop);
+    crate::inferior::flush(debugger);
+    json::stop_event(debugger);
+    let banner = crate::summary::banner(debugger).unwrap_or_default();
+    debugger.thread = current;
+    debugger.state = state;
+    crate::cache::invalidate();
+    outln!(debugger, "[Thread {} (tid {}) stopped] {}", threads::number(debugger, tid), tid, banner);
+}
+
+/// Report the stops of the threads left running while the prompt reads a command: the lines are
+/// printed above the prompt.
+pub fn poll_above_prompt(debugger: &mut Debugger) {
+    if !threads::any_running(debugger) {
+        return;
+    }
+    let recording = Rc::new(RefCell::new(Recording::new(false, false)));
+    let output = std::mem::replace(&mut debugger.output, Box::new(Recorder::new(recording.clone())));
+    poll(debugger);
+    debugger.output = output;
+    for written in recording.take().written {
+        match written {
+            Written::Text(text) => crate::logging::print_line(text.trim_end_matches('\n'), false),
+            Written::Styled(line, Style::Error | Style::Warning) => crate::logging::print_line(&line, true),
+            Written::Line(line) | Written::Styled(line, _) => crate::logging::print_line(&line, false),
+        }
+    }
+}
+
+/// Stop the threads left running in non-stop mode (`interrupt`), where they are: they can be
+/// selected with `thread` then.
+///
+/// # Returns
+///
+/// False if a thread could not be stopped.
+pub fn interrupt_command(debugger: &mut Debugger, _args: &[&str]) -> bool {
+    let running: Vec<Pid> = debugger.threads.iter().filter(|thread| thread.state == ThreadState::Running).map(|thread| thread.tid).collect();
+    if running.is_empty() {
+        outln!(debugger, "No thread is running.");
+        json::set_data(debugger, |_| json!({ "stopped": [] }));
+        return true;
+    }
+    if let Err(err) = threads::stop_others(debugger) {
+        errln!(debugger, "{}", err);
+        return false;
+    }
+    crate::cache::invalidate();
+    json::set_data(debugger, |_| json!({ "stopped": running.iter().map(|tid| tid.as_raw()).collect::<Vec<_>>() }));
+    for tid in running {
+        // A thread that exited on the way is gone.
+        if !debugger.threads.iter().any(|thread| thread.tid == tid) {
+            continue;
+        }
+        let frame = match debugger.tracee_of(tid).getregs() {
+            Ok(regs) => debugger.describe_address(regs.pc()),
+            Err(_) => "?".to_string(),
+        };
+        outln!(debugger, "[Thread {} (tid {}) stopped] Interrupted in {}", threads::number(debugger, tid), tid, frame);
+    }
+    true
+}
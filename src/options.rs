@@ -0,0 +1,225 @@
+use crate::inferior::InferiorTty;
+
+/// The usage shown when the command line is invalid.
+pub const USAGE: &str = "Usage: dbg_rust [options] <program_path> [-- <program arguments>...]
+       dbg_rust [options] -p <pid>
+       dbg_rust [options] --core <corefile> <program_path>
+       dbg_rust [options] --gdbserver [host]:<port> <program_path>
+Try dbg_rust --help for the options.";
+
+/// The description of the options, for `--help`.
+pub const HELP: &str = "Usage: dbg_rust [options] <program_path> [-- <program arguments>...]
+       dbg_rust [options] -p <pid>
+       dbg_rust [options] --core <corefile> <program_path>
+       dbg_rust [options] --gdbserver [host]:<port> <program_path>
+
+Debug a program started by the debugger, stopped before its first instruction, a running
+process attached with -p, or the core file of a program that crashed with --core.
+
+Options:
+  -p, --pid <pid>                 Attach to a running process instead of starting a program.
+  --core <corefile>               Read the registers and memory saved in a core file of the
+                                  program instead of starting it. Only the commands reading the
+                                  program run.
+  --gdbserver [host]:<port>       Let gdb debug the program over TCP (target remote
+                                  [host]:<port>) instead of the prompt, listening on the local
+                                  host when no host is given.
+  -x, --command <file>            Run the commands of a script file before the prompt.
+  -ex, --eval-command <command>   Run a command before the prompt. -x and -ex run in order.
+  --batch                         Exit after the -x and -ex commands, without prompt nor echo.
+  --run-immediately               Run the program after the -x and -ex commands.
+  --stop-at-entry                 Run the program to its entry point before the commands.
+  --stop-at-main                  Run the program to its main function before the commands,
+                                  as start does: the main of the crate for a Rust program.
+  --no-aslr                       Start the program with address space randomization off.
+  --tty <path>                    Give the program another terminal for its standard input,
+                                  output and error, instead of capturing its output.
+  --no-color                      Never color the output.
+  --interpreter console|json      Write one JSON record per command, for tools.
+  -v, --verbose                   Log the ptrace calls, wait statuses and breakpoints to the
+                                  standard error. -v -v (or -vv) logs the register and memory
+                                  accesses too.
+  -h, --help                      Show this help.
+  --version                       Show the version of the debugger.
+
+The arguments after -- are given to the program.";
+
+/// Something to do before the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupAction {
+    /// Run a script file (`-x`/`--command`).
+    Script(String),
+    /// Run one command (`-ex`).
+    Command(String),
+}
+
+/// How the debugger talks to its user (`--interpreter`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpreter {
+    /// Text for people, with a prompt.
+    #[default]
+    Console,
+    /// One JSON record per command on the standard output, for editors and other tools.
+    Json,
+}
+
+/// The configuration of a debugging session, from the command line options.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Config {
+    /// Path of the program to debug. With `--pid`, empty until it is read from the process.
+    pub program_path: String,
+    /// The arguments given to the program, after `--`.
+    pub program_args: Vec<String>,
+    /// `-p`/`--pid`: the process to attach to, instead of starting the program.
+    pub pid: Option<i32>,
+    /// `--core`: the core file to read, instead of starting the program.
+    pub core: Option<String>,
+    /// `--gdbserver`: the `[host]:port` to serve gdb on, instead of the prompt.
+    pub gdbserver: Option<String>,
+    /// Scripts and commands to run before the prompt, in the order of the command line.
+    pub startup: Vec<StartupAction>,
+    /// `--batch`: exit after the startup actions instead of showing the prompt.
+    pub batch: bool,
+    /// `--run-immediately`: run the program after the startup actions, if they didn't.
+    pub run_immediately: bool,
+    /// `--stop-at-entry`: run the program to the entry point of the executable before the startup
+    /// actions.
+    pub stop_at_entry: bool,
+    /// `--stop-at-main`: run the program to its main function before the startup actions.
+    pub stop_at_main: bool,
+    /// `--no-aslr`: start the program with address space randomization off.
+    pub no_aslr: bool,
+    /// `--tty <path>`: the terminal the standard streams of the program go to, instead of being
+    /// captured.
+    pub inferior_tty: InferiorTty,
+    /// `--no-color`: never color the output, even on a terminal.
+    pub no_color: bool,
+    /// `--interpreter`: text for people or JSON records.
+    pub interpreter: Interpreter,
+    /// How many times `-v`/`--verbose` is given, `-vv` counting twice.
+    pub verbosity: u8,
+    /// `-h`/`--help`: show the options and exit.
+    pub help: bool,
+    /// `--version`: show the version and exit.
+    pub version: bool,
+}
+
+/// The version shown by `--version`.
+pub fn version() -> String {
+    format!("rustdbg {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Parse the command line arguments, without the name of the debugger.
+///
+/// # Errors
+///
+/// Returns a message if an option is unknown or incomplete, the program is missing, or options
+/// that don't go together are given, as a program and `--pid`.
+pub fn parse(args: &[String]) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut program_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-x" | "--command" => match args.next() {
+                Some(file) => config.startup.push(StartupAction::Script(file.clone())),
+                None => return Err(format!("{} requires a file", arg)),
+            },
+            "-ex" | "--eval-command" => match args.next() {
+                Some(command) => config.startup.push(StartupAction::Command(command.clone())),
+                None => return Err(format!("{} requires a command", arg)),
+            },
+            "-p" | "--pid" => match args.next() {
+                Some(pid) => match pid.parse::<i32>() {
+                    Ok(pid) if pid > 0 => config.pid = Some(pid),
+                    _ => return Err(format!("Invalid process ID \"{}\" for {}", pid, arg)),
+                },
+                None => return Err(format!("{} requires a process ID", arg)),
+            },
+            "--core" => match args.next() {
+                Some(core) => config.core = Some(core.clone()),
+                None => return Err(format!("{} requires a core file", arg)),
+            },
+            "--gdbserver" => match args.next() {
+                Some(address) if address.contains(':') => config.gdbserver = Some(address.clone()),
+                Some(address) => return Err(format!("Invalid address \"{}\" for {}, expected [host]:<port>", address, arg)),
+                None => return Err(format!("{} requires [host]:<port>", arg)),
+            },
+            "--tty" => match args.next() {
+                Some(path) => config.inferior_tty = InferiorTty::Path(path.clone()),
+                None => return Err(format!("{} requires the path of a terminal", arg)),
+            },
+            "--interpreter" => match args.next().map(String::as_str) {
+                Some("console") => config.interpreter = Interpreter::Console,
+                Some("json") => config.interpreter = Interpreter::Json,
+                Some(other) => return Err(format!("Unknown interpreter {}, expected console or json", other)),
+                None => return Err(format!("{} requires console or json", arg)),
+            },
+            "--batch" => config.batch = true,
+            "--run-immediately" => config.run_immediately = true,
+            "--stop-at-entry" => config.stop_at_entry = true,
+            "--stop-at-main" => config.stop_at_main = true,
+            "--no-aslr" => config.no_aslr = true,
+            "--no-color" => config.no_color = true,
+            "-v" | "--verbose" => config.verbosity += 1,
+            "-vv" => config.verbosity += 2,
+            "-h" | "--help" => config.help = true,
+            "--version" => config.version = true,
+            "--" => {
+                // The program can be given after `--` too, before its arguments.
+                if program_path.is_none() && config.pid.is_none() {
+                    program_path = args.next().cloned();
+                }
+                config.program_args = args.by_ref().cloned().collect();
+                if config.program_args.is_empty() && program_path.is_none() {
+                    return Err("No program after --".to_string());
+                }
+            }
+            option if option.starts_with('-') => return Err(format!("Unknown option {}", option)),
+            path if program_path.is_none() => program_path = Some(path.to_string()),
+            _ => return Err("Only one program can be debugged, give its arguments after --".to_string()),
+        }
+    }
+    if config.help || config.version {
+        return Ok(config);
+    }
+    if config.pid.is_some() && config.core.is_some() {
+        return Err("Give either --pid to attach to a process or --core to read a core file, not both".to_string());
+    }
+    if config.gdbserver.is_some() && config.core.is_some() {
+        return Err("--gdbserver serves a process, not a core file".to_string());
+    }
+    if config.gdbserver.is_some() && config.batch {
+        return Err("--gdbserver serves gdb instead of running the commands, not with --batch".to_string());
+    }
+    if config.pid.is_some() {
+        if program_path.is_some() {
+            return Err("Give either a program to start or --pid to attach to a process, not both".to_string());
+        }
+        if !config.program_args.is_empty() {
+            return Err("Program arguments cannot be given to a process attached with --pid".to_string());
+        }
+        let tty = config.inferior_tty != InferiorTty::default();
+        for (given, option) in [(config.no_aslr, "--no-aslr"), (config.stop_at_entry, "--stop-at-entry"), (config.stop_at_main, "--stop-at-main"), (config.run_immediately, "--run-immediately"), (tty, "--tty")] {
+            if given {
+                return Err(format!("{} only applies to a program started by the debugger, not with --pid", option));
+            }
+        }
+        return Ok(config);
+    }
+    if config.core.is_some() {
+        if !config.program_args.is_empty() {
+            return Err("Program arguments cannot be given with --core, the program is not started".to_string());
+        }
+        let tty = config.inferior_tty != InferiorTty::default();
+        for (given, option) in [(config.no_aslr, "--no-aslr"), (config.stop_at_entry, "--stop-at-entry"), (config.stop_at_main, "--stop-at-main"), (config.run_immediately, "--run-immediately"), (tty, "--tty")] {
+            if given {
+                return Err(format!("{} only applies to a program started by the debugger, not with --core", option));
+            }
+        }
+        config.program_path = program_path.ok_or("--core requires the program the core file comes from")?;
+        return Ok(config);
+    }
+    config.program_path = program_path.ok_or("No program to debug")?;
+    Ok(config)
+}
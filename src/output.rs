@@ -0,0 +1,489 @@
+use crate::pager::{read_key, terminal_size, Pager, MORE_PROMPT};
+use std::io::{IsTerminal, Write};
+use std::{cell::RefCell, rc::Rc};
+
+/// Print a line through the output of the debugger, with the arguments of `println!`. The line
+/// is copied to the transcript when logging is on.
+#[macro_export]
+macro_rules! outln {
+    ($debugger:expr) => {
+        $crate::outln!($debugger, "")
+    };
+    ($debugger:expr, $($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        $debugger.transcript.write_output(&line);
+        $debugger.output.write_line(&line)
+    }};
+}
+
+/// Print an error message through the output of the debugger, with the arguments of `println!`.
+#[macro_export]
+macro_rules! errln {
+    ($debugger:expr, $($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        $debugger.transcript.write_output(&line);
+        $debugger.output.write_styled_line(&line, $crate::output::Style::Error)
+    }};
+}
+
+/// Print a warning through the output of the debugger, with the arguments of `println!`.
+#[macro_export]
+macro_rules! warnln {
+    ($debugger:expr, $($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        $debugger.transcript.write_output(&line);
+        $debugger.output.write_styled_line(&line, $crate::output::Style::Warning)
+    }};
+}
+
+/// What a piece of the output is, to color it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// An address, as in `0x401126`.
+    Address,
+    /// The name of a register.
+    Register,
+    /// A value that changed since it was last shown.
+    Changed,
+    /// An error message.
+    Error,
+    /// A warning: the command went on, but something is not as expected.
+    Warning,
+    /// The prompt.
+    Prompt,
+}
+
+impl Style {
+    /// The SGR parameters of the ANSI escape sequence of the style.
+    fn sgr(self) -> &'static str {
+        match self {
+            Style::Address => "36",
+            Style::Register => "32",
+            Style::Changed => "1;33",
+            Style::Error => "31",
+            Style::Warning => "33",
+            Style::Prompt => "1;34",
+        }
+    }
+}
+
+/// Surround `text` with the ANSI escape sequences of `style`.
+pub fn paint(text: &str, style: Style) -> String {
+    format!("\x1b[{}m{}\x1b[0m", style.sgr(), text)
+}
+
+/// Remove the ANSI escape sequences of the styles from `text`.
+pub fn strip_styles(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip up to the final `m` of the SGR sequence.
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Color the hexadecimal numbers of `line` as addresses. Lines already colored are left as is.
+pub fn highlight_addresses(line: &str) -> String {
+    if line.contains('\x1b') {
+        return line.to_string();
+    }
+    let mut highlighted = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("0x") {
+        // Not the end of a word, as in `a0x1`.
+        let preceded = rest[..start].chars().next_back().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+        let digits = rest[start + 2..].find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(rest.len() - start - 2);
+        let end = start + 2 + digits;
+        highlighted.push_str(&rest[..start]);
+        if preceded || digits == 0 {
+            highlighted.push_str(&rest[start..end]);
+        } else {
+            highlighted.push_str(&paint(&rest[start..end], Style::Address));
+        }
+        rest = &rest[end..];
+    }
+    highlighted.push_str(rest);
+    highlighted
+}
+
+/// Where the commands print their output. Every command writes through the output of the
+/// debugger, so it can be colored, captured or redirected in one place.
+pub trait Output {
+    /// Write `text` as is.
+    fn write_str(&mut self, text: &str);
+
+    /// Returns true if the output is colored.
+    fn color(&self) -> bool;
+
+    /// Turn the colors on or off (`set color`).
+    fn set_color(&mut self, color: bool);
+
+    /// Write a line, coloring its addresses.
+    fn write_line(&mut self, line: &str) {
+        let line = if self.color() { highlight_addresses(line) } else { line.to_string() };
+        self.write_str(&line);
+        self.write_str("\n");
+    }
+
+    /// Write a line in a style.
+    fn write_styled_line(&mut self, line: &str, style: Style) {
+        let line = self.paint(line, style);
+        self.write_str(&line);
+        self.write_str("\n");
+    }
+
+    /// Returns true if long output stops at each screenful (`set pagination`).
+    fn pagination(&self) -> bool {
+        false
+    }
+
+    /// Turn the pager on or off (`set pagination`).
+    fn set_pagination(&mut self, _pagination: bool) {}
+
+    /// Start a new page, before the output of a command.
+    fn new_page(&mut self) {}
+
+    /// `text` in a style, if the output is colored.
+    fn paint(&self, text: &str, style: Style) -> String {
+        if self.color() {
+            paint(text, style)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Writes to the standard output, or the standard error. The errors and warnings always go to
+/// the standard error.
+pub struct Terminal {
+    color: bool,
+    /// True if the errors and warnings are colored, when the standard error is a terminal.
+    error_color: bool,
+    stderr: bool,
+    /// `set pagination`.
+    pagination: bool,
+    /// True if both the input and the output are a terminal, to page the output.
+    interactive: bool,
+    pager: Pager,
+}
+
+impl Terminal {
+    /// Write to the standard output, colored if it is a terminal, `color` is true and the
+    /// `NO_COLOR` environment variable is not set. Long output is paged on a terminal.
+    pub fn new(color: bool) -> Terminal {
+        let terminal = std::io::stdout().is_terminal();
+        Terminal {
+            color: color && !no_color() && terminal,
+            error_color: color && !no_color() && std::io::stderr().is_terminal(),
+            stderr: false,
+            pagination: true,
+            interactive: terminal && std::io::stdin().is_terminal(),
+            pager: Pager::default(),
+        }
+    }
+
+    /// Write to the standard error, colored as [`Terminal::new`] decides for the standard output.
+    pub fn stderr(color: bool) -> Terminal {
+        let color = color && !no_color() && std::io::stderr().is_terminal();
+        Terminal {
+            color,
+            error_color: color,
+            stderr: true,
+            pagination: false,
+            interactive: false,
+            pager: Pager::default(),
+        }
+    }
+
+    /// Write `text` a line at a time, showing the prompt of the pager when the page is full.
+    fn write_paged(&mut self, text: &str) {
+        for piece in text.split_inclusive('\n') {
+            if self.pager.quit {
+                return;
+            }
+            let line = piece.strip_suffix('\n');
+            print!("{}", piece);
+            self.pager.write(line.unwrap_or(piece));
+            if line.is_some() && self.pager.end_line() {
+                print!("{}", MORE_PROMPT);
+                std::io::stdout().flush().ok();
+                self.pager.answer(read_key());
+                // Erase the prompt.
+                print!("\r\x1b[K");
+            }
+        }
+        std::io::stdout().flush().ok();
+    }
+}
+
+/// Returns true if the `NO_COLOR` environment variable asks for no colors.
+fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+impl Output for Terminal {
+    fn write_str(&mut self, text: &str) {
+        if self.stderr {
+            eprint!("{}", text);
+            return;
+        }
+        if self.pagination && self.interactive && self.pager.size != (0, 0) {
+            self.write_paged(text);
+            return;
+        }
+        print!("{}", text);
+        if text.ends_with('\n') {
+            std::io::stdout().flush().ok();
+        }
+    }
+
+    fn write_styled_line(&mut self, line: &str, style: Style) {
+        if !matches!(style, Style::Error | Style::Warning) {
+            let line = self.paint(line, style);
+            self.write_str(&line);
+            self.write_str("\n");
+            return;
+        }
+        // After the output already printed, which the message is about.
+        std::io::stdout().flush().ok();
+        eprintln!("{}", if self.error_color { paint(line, style) } else { line.to_string() });
+    }
+
+    fn pagination(&self) -> bool {
+        self.pagination
+    }
+
+    fn set_pagination(&mut self, pagination: bool) {
+        self.pagination = pagination && !self.stderr;
+    }
+
+    fn new_page(&mut self) {
+        self.pager.reset(terminal_size().unwrap_or_default());
+    }
+
+    fn color(&self) -> bool {
+        self.color
+    }
+
+    fn set_color(&mut self, color: bool) {
+        self.color = color;
+        self.error_color = color;
+    }
+}
+
+/// The lines written by a command in JSON mode, for its record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lines {
+    /// The lines of its output, without colors.
+    pub output: Vec<String>,
+    /// Its error messages.
+    pub errors: Vec<String>,
+}
+
+/// The output of `--interpreter json`: the text for people goes to `text`, the standard error,
+/// and each line is also kept in `lines` for the JSON record of the command.
+pub struct JsonText {
+    text: Box<dyn Output>,
+    lines: Rc<RefCell<Lines>>,
+}
+
+impl JsonText {
+    /// Write the text to `text` and keep its lines in `lines`.
+    pub fn new(text: Box<dyn Output>, lines: Rc<RefCell<Lines>>) -> JsonText {
+        JsonText { text, lines }
+    }
+}
+
+impl Output for JsonText {
+    fn write_str(&mut self, text: &str) {
+        self.text.write_str(text);
+    }
+
+    fn color(&self) -> bool {
+        self.text.color()
+    }
+
+    fn set_color(&mut self, color: bool) {
+        self.text.set_color(color);
+    }
+
+    fn write_line(&mut self, line: &str) {
+        self.lines.borrow_mut().output.push(strip_styles(line));
+        self.text.write_line(line);
+    }
+
+    fn write_styled_line(&mut self, line: &str, style: Style) {
+        let mut lines = self.lines.borrow_mut();
+        let lines = if style == Style::Error { &mut lines.errors } else { &mut lines.output };
+        lines.push(strip_styles(line));
+        self.text.write_styled_line(line, style);
+    }
+}
+
+/// One write to the output, kept by a [`Recording`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Written {
+    /// Text written as is.
+    Text(String),
+    /// A line, whose addresses are colored.
+    Line(String),
+    /// A line in a style.
+    Styled(String, Style),
+}
+
+/// The output of a command run by [`dispatch`](crate::dispatch), kept to be written by the caller.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// The writes of the command, in order.
+    pub written: Vec<Written>,
+    color: bool,
+    pagination: bool,
+}
+
+impl Recording {
+    /// Record the output of a command, colored as `color` says. `set color` and `set pagination`
+    /// change the colors and the pagination of the recording, to be applied to the output of the
+    /// session after the command.
+    pub fn new(color: bool, pagination: bool) -> Recording {
+        Recording { written: Vec::new(), color, pagination }
+    }
+
+    /// Returns true if the output is colored.
+    pub fn color(&self) -> bool {
+        self.color
+    }
+
+    /// Returns true if long output stops at each screenful.
+    pub fn pagination(&self) -> bool {
+        self.pagination
+    }
+
+    /// The text written, without colors nor the error messages.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for written in &self.written {
+            match written {
+                Written::Text(piece) => text.push_str(piece),
+                Written::Line(line) | Written::Styled(line, Style::Address | Style::Register | Style::Changed | Style::Prompt) => {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+                Written::Styled(_, Style::Error | Style::Warning) => {}
+            }
+        }
+        strip_styles(&text)
+    }
+
+    /// The error messages written, without colors.
+    pub fn errors(&self) -> Vec<String> {
+        self.written
+            .iter()
+            .filter_map(|written| match written {
+                Written::Styled(line, Style::Error) => Some(strip_styles(line)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Write the recorded output to `output`, in order.
+    pub fn replay(&self, output: &mut dyn Output) {
+        for written in &self.written {
+            match written {
+                Written::Text(text) => output.write_str(text),
+                Written::Line(line) => output.write_line(line),
+                Written::Styled(line, style) => output.write_styled_line(line, *style),
+            }
+        }
+    }
+}
+
+/// Keeps what a command writes in a [`Recording`], for [`dispatch`](crate::dispatch).
+pub struct Recorder {
+    recording: Rc<RefCell<Recording>>,
+}
+
+impl Recorder {
+    /// Write to `recording`.
+    pub fn new(recording: Rc<RefCell<Recording>>) -> Recorder {
+        Recorder { recording }
+    }
+}
+
+impl Output for Recorder {
+    fn write_str(&mut self, text: &str) {
+        self.recording.borrow_mut().written.push(Written::Text(text.to_string()));
+    }
+
+    fn color(&self) -> bool {
+        self.recording.borrow().color
+    }
+
+    fn set_color(&mut self, color: bool) {
+        self.recording.borrow_mut().color = color;
+    }
+
+    fn write_line(&mut self, line: &str) {
+        self.recording.borrow_mut().written.push(Written::Line(line.to_string()));
+    }
+
+    fn write_styled_line(&mut self, line: &str, style: Style) {
+        self.recording.borrow_mut().written.push(Written::Styled(line.to_string(), style));
+    }
+
+    fn pagination(&self) -> bool {
+        self.recording.borrow().pagination
+    }
+
+    fn set_pagination(&mut self, pagination: bool) {
+        self.recording.borrow_mut().pagination = pagination;
+    }
+}
+
+/// Keeps the output in memory, for the tests.
+#[cfg(test)]
+pub struct Capture {
+    /// The text written so far.
+    pub text: Rc<RefCell<String>>,
+    color: bool,
+}
+
+#[cfg(test)]
+impl Capture {
+    /// Capture the output into `text`.
+    pub fn new(text: Rc<RefCell<String>>, color: bool) -> Capture {
+        Capture { text, color }
+    }
+}
+
+#[cfg(test)]
+impl Output for Capture {
+    fn write_str(&mut self, text: &str) {
+        self.text.borrow_mut().push_str(text);
+    }
+
+    fn color(&self) -> bool {
+        self.color
+    }
+
+    fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+}
+
+/// JSON records are written to a [`Capture`] in the tests.
+#[cfg(test)]
+impl Write for Capture {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.text.borrow_mut().push_str(&String::from_utf8_lossy(bytes));
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::output::strip_styles;
+use nix::sys::termios::{self, LocalFlags, SetArg, SpecialCharacterIndices};
+use std::io::Read;
+
+/// Shown at the bottom of a full page, until a key is pressed.
+pub const MORE_PROMPT: &str = "--More-- (q to quit, Enter for next line, Space for next page)";
+
+/// Counts the rows of the output of a command on the terminal, to stop at each screenful.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pager {
+    /// The rows and columns of the terminal.
+    pub size: (usize, usize),
+    /// The rows written since the top of the page.
+    pub shown: usize,
+    /// The visible characters written on the current row so far.
+    pub line_length: usize,
+    /// True once `q` was pressed: the rest of the output of the command is dropped.
+    pub quit: bool,
+}
+
+impl Pager {
+    /// Start a page for the output of the next command, on a terminal of `size` rows and columns.
+    pub fn reset(&mut self, size: (usize, usize)) {
+        *self = Pager { size, ..Pager::default() };
+    }
+
+    /// Count `text`, written on the current line.
+    pub fn write(&mut self, text: &str) {
+        self.line_length += strip_styles(text).chars().count();
+    }
+
+    /// Count the end of the current line, which takes several rows when it is wider than the
+    /// terminal.
+    ///
+    /// # Returns
+    ///
+    /// True if the page is full: the prompt has to be shown before the next line.
+    pub fn end_line(&mut self) -> bool {
+        let (rows, columns) = self.size;
+        self.shown += 1 + self.line_length.saturating_sub(1) / columns.max(1);
+        self.line_length = 0;
+        // The last row is for the prompt.
+        self.shown + 1 >= rows
+    }
+
+    /// Act on the key pressed at the prompt: `q` (or Ctrl-C) drops the rest of the output, Enter
+    /// shows one more line and any other key a new page.
+    pub fn answer(&mut self, key: Option<u8>) {
+        match key {
+            None | Some(b'q' | b'Q' | 0x03) => self.quit = true,
+            Some(b'\r' | b'\n') => self.shown = self.size.0.saturating_sub(2),
+            Some(_) => self.shown = 0,
+        }
+    }
+}
+
+/// The rows and columns of the terminal of the standard output.
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let mut size = nix::libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // TIOCGWINSZ only fills the winsize given.
+    let result = unsafe { nix::libc::ioctl(nix::libc::STDOUT_FILENO, nix::libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_row > 0 && size.ws_col > 0).then_some((size.ws_row as usize, size.ws_col as usize))
+}
+
+/// Read a single key from the terminal, without waiting for Enter nor echoing it.
+///
+/// # Returns
+///
+/// The key, or `None` if the standard input could not be read.
+pub fn read_key() -> Option<u8> {
+    let stdin = std::io::stdin();
+    let saved = termios::tcgetattr(&stdin).ok()?;
+    let mut raw = saved.clone();
+    // Without ISIG, Ctrl-C is read as a key instead of killing the debugger.
+    raw.local_flags.remove(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG);
+    raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+    raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+    termios::tcsetattr(&stdin, SetArg::TCSANOW, &raw).ok()?;
+    let mut key = [0];
+    let read = stdin.lock().read(&mut key);
+    termios::tcsetattr(&stdin, SetArg::TCSANOW, &saved).ok();
+    matches!(read, Ok(1)).then_some(key[0])
+}
@@ -0,0 +1,485 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::inject;
+use crate::json;
+use crate::location;
+use crate::maps;
+use crate::memory::read_bytes;
+use crate::step::StepStop;
+use crate::swatch::format_bytes;
+use crate::threads::{self, ThreadState};
+use nix::libc;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use serde_json::json;
+
+/// The size of the pages write-protected.
+const PAGE_SIZE: u64 = 4096;
+/// The `si_code` of a `SIGSEGV` for an access the protection of the page forbids.
+const SEGV_ACCERR: i32 = 2;
+/// The bytes compared from the address of a write, the largest store of an instruction
+/// (AVX-512): a write starting before a region can reach it.
+const ACCESS_LEN: u64 = 64;
+
+/// Pages of a watched region in one mapping, with the protection they had before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtectedPages {
+    pub start: u64,
+    pub end: u64,
+    /// The `PROT_` bits of the mapping, given back by `unwatch-region`.
+    pub protection: i32,
+}
+
+/// A region of memory watched by `watch-region`, its pages write-protected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionWatch {
+    pub number: usize,
+    pub address: u64,
+    pub len: u64,
+    /// The pages containing the region, in each mapping they are in.
+    pub pages: Vec<ProtectedPages>,
+    /// The writes to the region caught.
+    pub hits: usize,
+    /// The writes to its pages outside of it, done by single-stepping them.
+    pub emulated: usize,
+}
+
+impl RegionWatch {
+    /// Returns true if `address` is in the region.
+    pub fn contains(&self, address: u64) -> bool {
+        address >= self.address && address - self.address < self.len
+    }
+
+    /// Returns true if `address` is in the pages of the region.
+    fn in_pages(&self, address: u64) -> bool {
+        self.pages.iter().any(|pages| (pages.start..pages.end).contains(&address))
+    }
+}
+
+/// A write caught in a region, described at the stop it makes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionHit {
+    /// The number of the region.
+    pub number: usize,
+    /// The first byte of the region the write could change.
+    pub address: u64,
+    /// The instruction that wrote.
+    pub pc: u64,
+    /// The bytes from `address` before and after the write.
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// The regions watched by `watch-region`.
+#[derive(Debug, Default)]
+pub struct RegionWatches {
+    pub list: Vec<RegionWatch>,
+    /// The number of regions watched so far, the last number given.
+    count: usize,
+    /// The last write caught, describing its stop.
+    pub last_hit: Option<RegionHit>,
+    /// True while the last write caught is not taken as the stop of the program by [`take_hit`].
+    hit: bool,
+}
+
+impl RegionWatches {
+    /// The region starting at `address`, the address of its stops.
+    pub fn find(&self, address: u64) -> Option<&RegionWatch> {
+        self.list.iter().find(|region| region.address == address)
+    }
+}
+
+/// What became of the stop of a thread by a `SIGSEGV` (see [`handle_fault`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Not a write to the pages of a region: the stop, or the one the thread stopped with
+    /// instead of doing its write.
+    Other(WaitStatus),
+    /// A write to a region, done: [`take_hit`] gives its stop.
+    Hit,
+    /// A write to the pages of a region outside of it, done.
+    Elsewhere,
+}
+
+/// The `PROT_` bits of the permissions of a mapping, as `rw-p`.
+fn protection(perms: &str) -> i32 {
+    let bits = [(b'r', libc::PROT_READ), (b'w', libc::PROT_WRITE), (b'x', libc::PROT_EXEC)];
+    perms.bytes().zip(bits).filter(|(permission, (letter, _))| permission == letter).fold(0, |protection, (_, (_, bit))| protection | bit)
+}
+
+/// Write-protect the pages of a region, or give them back their protection, with `mprotect` calls
+/// injected in `thread`.
+fn set_protection(arch: Arch, thread: Pid, region: &RegionWatch, protected: bool) -> Result<(), DbgError> {
+    for pages in &region.pages {
+        let protection = if protected { pages.protection & !libc::PROT_WRITE } else { pages.protection };
+        log::debug!("mprotect({:#x}, {:#x}, {:#x}) for region watchpoint {}", pages.start, pages.end - pages.start, protection, region.number);
+        inject::protect(arch, thread, pages.start, pages.end - pages.start, protection)?;
+    }
+    Ok(())
+}
+
+/// Watch the writes to `len` bytes at `address` by write-protecting the pages containing them.
+///
+/// # Returns
+///
+/// The region watched, with its number and pages.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Region`] if the region is empty, not mapped or not writable, or shares pages
+/// with another region, and the error of `mprotect` if its pages can't be protected.
+pub fn watch_region(debugger: &mut Debugger, address: u64, len: u64) -> Result<RegionWatch, DbgError> {
+    let end = address
+        .checked_add(len)
+        .filter(|end| len > 0 && *end <= u64::MAX - PAGE_SIZE)
+        .ok_or_else(|| DbgError::Region("the length must be at least 1, within the address space.".to_string()))?;
+    if debugger.in_syscall {
+        return Err(DbgError::Region("the program is stopped in a system call, step out of it first.".to_string()));
+    }
+    let (first, last) = (address & !(PAGE_SIZE - 1), end.div_ceil(PAGE_SIZE) * PAGE_SIZE);
+    let overlapping = debugger.region_watches.list.iter().find(|region| region.pages.iter().any(|pages| pages.start < last && first < pages.end));
+    if let Some(region) = overlapping {
+        return Err(DbgError::Region(format!("its pages are those of region watchpoint {} too: unwatch-region it and watch both in one region.", region.number)));
+    }
+    let entries = maps::read_maps(debugger.child).map_err(|err| DbgError::Region(format!("could not read the mappings of the program: {}", err)))?;
+    let mut pages = Vec::new();
+    let mut current = first;
+    while current < last {
+        let Some(mapping) = maps::find_mapping(&entries, current) else {
+            return Err(DbgError::Region(format!("{:#x} is not mapped.", current.max(address))));
+        };
+        if !mapping.is_writable() {
+            return Err(DbgError::Region(format!("{:#x} is in {}, which is not writable: only the writes are watched.", current.max(address), mapping.describe())));
+        }
+        pages.push(ProtectedPages { start: current, end: mapping.end.min(last), protection: protection(&mapping.perms) });
+        current = mapping.end.min(last);
+    }
+    let region = RegionWatch { number: debugger.region_watches.count + 1, address, len, pages, hits: 0, emulated: 0 };
+    if let Err(err) = set_protection(debugger.arch, debugger.thread, &region, true) {
+        // The pages protected already are given back.
+        set_protection(debugger.arch, debugger.thread, &region, false).ok();
+        return Err(err);
+    }
+    crate::cache::invalidate();
+    debugger.region_watches.count += 1;
+    debugger.region_watches.list.push(region.clone());
+    Ok(region)
+}
+
+/// Watch a region of memory (`watch-region <address> <len>`), or list the regions watched.
+///
+/// # Returns
+///
+/// False if an argument is invalid or the region can't be watched.
+pub fn watch_region_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let (address, len) = match args {
+        [_] => {
+            list(debugger);
+            return true;
+        }
+        [_, address, len] => {
+            let region = location::parse_value(debugger, address, "address").and_then(|address| Ok((address, location::parse_value(debugger, len, "length")?)));
+            match region {
+                Ok(region) => region,
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    return false;
+                }
+            }
+        }
+        _ => {
+            errln!(debugger, "Usage: watch-region [<address> <len>]");
+            return false;
+        }
+    };
+    let region = match watch_region(debugger, address, len) {
+        Ok(region) => region,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let number = region.number;
+    let count = region.pages.iter().map(|pages| (pages.end - pages.start) / PAGE_SIZE).sum::<u64>();
+    let (start, end) = (region.pages[0].start, region.pages[region.pages.len() - 1].end);
+    json::set_data(debugger, |_| {
+        json!({ "number": number, "address": json::address(address), "len": len, "pages": count, "start": json::address(start), "end": json::address(end) })
+    });
+    outln!(
+        debugger,
+        "Region watchpoint {}: {} bytes at {:#x}, {} page{} write-protected ({:#x}-{:#x}).",
+        number,
+        len,
+        address,
+        count,
+        if count == 1 { "" } else { "s" },
+        start,
+        end
+    );
+    true
+}
+
+/// Stop watching a region (`unwatch-region <number>`), giving its pages back their protection.
+///
+/// # Returns
+///
+/// False if no region has that number, or its pages can't be given back their protection.
+pub fn unwatch_region_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    let number = match args {
+        [_, number] => match location::parse_count(number, "region number") {
+            Ok(number) => number,
+            Err(err) => {
+                errln!(debugger, "{}", err);
+                return false;
+            }
+        },
+        _ => {
+            errln!(debugger, "Usage: unwatch-region <number>");
+            return false;
+        }
+    };
+    let Some(index) = debugger.region_watches.list.iter().position(|region| region.number == number) else {
+        errln!(debugger, "No region watchpoint {}, watch-region lists them.", number);
+        return false;
+    };
+    if debugger.state.is_alive() && debugger.core.is_none() {
+        if debugger.in_syscall {
+            errln!(debugger, "The program is stopped in a system call, step out of it first.");
+            return false;
+        }
+        if let Err(err) = set_protection(debugger.arch, debugger.thread, &debugger.region_watches.list[index], false) {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+        crate::cache::invalidate();
+    }
+    let region = debugger.region_watches.list.remove(index);
+    outln!(debugger, "Deleted region watchpoint {} ({} bytes at {:#x}), its pages writable again.", number, region.len, region.address);
+    true
+}
+
+/// List the regions watched with the writes caught in them, and the writes to their pages
+/// outside of them (`watch-region` alone).
+pub fn list(debugger: &mut Debugger) {
+    let regions = debugger.region_watches.list.clone();
+    json::set_data(debugger, |_| {
+        let regions: Vec<_> = regions
+            .iter()
+            .map(|region| json!({ "number": region.number, "address": json::address(region.address), "len": region.len, "hits": region.hits, "emulated": region.emulated }))
+            .collect();
+        json!({ "regions": regions })
+    });
+    if regions.is_empty() {
+        outln!(debugger, "No region watchpoints.");
+        return;
+    }
+    for region in regions {
+        outln!(
+            debugger,
+            "{}: {} bytes at {:#x}, {} write{} caught, {} other write{} to its pages single-stepped",
+            region.number,
+            region.len,
+            region.address,
+            region.hits,
+            if region.hits == 1 { "" } else { "s" },
+            region.emulated,
+            if region.emulated == 1 { "" } else { "s" }
+        );
+    }
+}
+
+/// The address a thread stopped by a `SIGSEGV` wrote to, in a write-protected page.
+fn write_fault_address(tid: Pid) -> Option<u64> {
+    let info = crate::siginfo::read(tid).ok()?;
+    info.address.filter(|_| info.code == SEGV_ACCERR)
+}
+
+/// Single-step the instruction of `tid` that faulted in the pages of the region at `index`, its
+/// pages writable again for the step, then protect them again. An instruction writing to the
+/// pages of another region too gets them as well. The other threads running meanwhile are not
+/// caught writing to the pages during the step.
+fn step_unprotected(debugger: &mut Debugger, tid: Pid, index: usize) -> Result<WaitStatus, DbgError> {
+    let arch = debugger.arch;
+    let mut unprotected = vec![index];
+    set_protection(arch, tid, &debugger.region_watches.list[index], false)?;
+    let status = loop {
+        crate::cache::invalidate();
+        log::debug!("PTRACE_SINGLESTEP {} over the write", tid);
+        ptrace::step(tid, None).map_err(DbgError::ptrace("single-step"))?;
+        let status = waitpid(tid, Some(WaitPidFlag::__WALL)).map_err(DbgError::ptrace("wait for the program"))?;
+        log::debug!("waitpid {} = {:?}", tid, status);
+        let other = match status {
+            WaitStatus::Stopped(_, Signal::SIGSEGV) => write_fault_address(tid)
+                .and_then(|address| debugger.region_watches.list.iter().position(|region| region.in_pages(address)))
+                .filter(|other| !unprotected.contains(other)),
+            _ => None,
+        };
+        let Some(other) = other else {
+            break status;
+        };
+        set_protection(arch, tid, &debugger.region_watches.list[other], false)?;
+        unprotected.push(other);
+    };
+    if !matches!(status, WaitStatus::Exited(..) | WaitStatus::Signaled(..)) {
+        for index in unprotected {
+            set_protection(arch, tid, &debugger.region_watches.list[index], true)?;
+        }
+    }
+    crate::cache::invalidate();
+    Ok(status)
+}
+
+/// Tell whether a thread stopped by a `SIGSEGV` wrote to the pages of a watched region, and do
+/// the write by single-stepping it with the pages writable: the write is caught if it changes the
+/// region or starts in it, as writes leaving the bytes unchanged count too.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the thread can't be stepped or its memory read, or the error
+/// of changing the protection of the pages.
+pub fn handle_fault(debugger: &mut Debugger, tid: Pid, status: WaitStatus) -> Result<Fault, DbgError> {
+    if status != WaitStatus::Stopped(tid, Signal::SIGSEGV) || debugger.region_watches.list.is_empty() {
+        return Ok(Fault::Other(status));
+    }
+    let Some(fault) = write_fault_address(tid) else {
+        return Ok(Fault::Other(status));
+    };
+    let Some(index) = debugger.region_watches.list.iter().position(|region| region.in_pages(fault)) else {
+        return Ok(Fault::Other(status));
+    };
+    let pc = crate::arch::get_registers(tid).map_err(DbgError::ptrace("read the registers"))?.pc();
+    let region = &debugger.region_watches.list[index];
+    log::debug!("Write to {:#x} by {:#x} in the pages of region watchpoint {}", fault, pc, region.number);
+    // The bytes of the region the write can change.
+    let start = fault.max(region.address);
+    let end = fault.saturating_add(ACCESS_LEN).min(region.address + region.len);
+    let len = end.saturating_sub(start) as usize;
+    let old = if len > 0 { read_bytes(&tid, start, len)? } else { Vec::new() };
+    let status = step_unprotected(debugger, tid, index)?;
+    if !matches!(status, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+        return Ok(Fault::Other(status));
+    }
+    let new = if len > 0 { read_bytes(&tid, start, len)? } else { Vec::new() };
+    let region = &mut debugger.region_watches.list[index];
+    if !region.contains(fault) && old == new {
+        region.emulated += 1;
+        return Ok(Fault::Elsewhere);
+    }
+    region.hits += 1;
+    let number = region.number;
+    debugger.region_watches.last_hit = Some(RegionHit { number, address: start, pc, old, new });
+    debugger.region_watches.hit = true;
+    Ok(Fault::Hit)
+}
+
+/// Handle a write to the pages of a watched region while the program continues, with
+/// [`handle_fault`]: a write outside of the regions resumes the thread, and the wait for the
+/// program goes on.
+///
+/// # Returns
+///
+/// None if the thread was resumed, the stop of the thread after a write caught, kept for
+/// [`take_hit`], or else `status`.
+///
+/// # Errors
+///
+/// Returns the error of [`handle_fault`], or [`DbgError::Ptrace`] if the thread can't be resumed.
+pub fn write_fault(debugger: &mut Debugger, tid: Pid, status: WaitStatus) -> Result<Option<WaitStatus>, DbgError> {
+    match handle_fault(debugger, tid, status)? {
+        Fault::Other(status) => Ok(Some(status)),
+        Fault::Hit => Ok(Some(WaitStatus::Stopped(tid, Signal::SIGTRAP))),
+        Fault::Elsewhere => {
+            crate::antidebug::resume(debugger.anti_debug.enabled, tid, None)?;
+            threads::set_state(debugger, tid, ThreadState::Running);
+            Ok(None)
+        }
+    }
+}
+
+/// Take the write caught by [`handle_fault`] as the stop of the program, a stop at the watchpoint
+/// of the address of its region.
+///
+/// # Returns
+///
+/// The stop, or None if no write was caught since the last stop.
+pub fn take_hit(debugger: &mut Debugger) -> Option<StepStop> {
+    if !std::mem::take(&mut debugger.region_watches.hit) {
+        return None;
+    }
+    let number = debugger.region_watches.last_hit.as_ref()?.number;
+    debugger.region_watches.list.iter().find(|region| region.number == number).map(|region| StepStop::Watchpoint(region.address))
+}
+
+/// Describe the last write caught in the region at `address`, the banner of its stop, as in
+/// `Region watchpoint 1 (64 bytes at 0x4040a0): 0x4040ac written by 0x401136 <fill+22>, 00 00 -> 2a 00`.
+pub fn describe_hit(debugger: &mut Debugger, address: u64) -> Option<String> {
+    let region = debugger.region_watches.find(address)?.clone();
+    let hit = debugger.region_watches.last_hit.clone().filter(|hit| hit.number == region.number)?;
+    // The bytes up to the last one changed.
+    let shown = hit.old.iter().zip(&hit.new).rposition(|(old, new)| old != new).map_or(hit.old.len().min(8), |last| last + 1);
+    let change = if hit.old == hit.new {
+        format!("{} (unchanged)", format_bytes(&hit.new[..shown]))
+    } else {
+        format!("{} -> {}", format_bytes(&hit.old[..shown]), format_bytes(&hit.new[..shown]))
+    };
+    Some(format!(
+        "Region watchpoint {} ({} bytes at {:#x}): {:#x} written by {}, {}",
+        region.number,
+        region.len,
+        region.address,
+        hit.address,
+        debugger.describe_address(hit.pc),
+        change
+    ))
+}
+
+/// Give the pages of every region back their protection, before detaching from the program,
+/// which would crash writing to them.
+pub fn unprotect_all(debugger: &mut Debugger) {
+    if debugger.region_watches.list.is_empty() || !debugger.state.is_alive() || debugger.core.is_some() {
+        return;
+    }
+    for region in debugger.region_watches.list.clone() {
+        let unprotected = if debugger.in_syscall {
+            Err("the program is stopped in a system call".to_string())
+        } else {
+            set_protection(debugger.arch, debugger.thread, &region, false).map_err(|err| err.to_string())
+        };
+        if let Err(err) = unprotected {
+            warnln!(debugger, "The pages of region watchpoint {} stay write-protected: {}", region.number, err);
+        }
+    }
+    crate::cache::invalidate();
+}
+
+/// Drop the regions watched in the previous program, whose pages are gone with it, when the
+/// program executes a new one.
+pub fn forget(debugger: &mut Debugger) {
+    for region in std::mem::take(&mut debugger.region_watches.list) {
+        warnln!(debugger, "Region watchpoint {} ({} bytes at {:#x}) dropped: its pages were in the previous program.", region.number, region.len, region.address);
+    }
+    debugger.region_watches.hit = false;
+}
+
+/// Watch again the regions watched in the previous process, at the same addresses in the new one
+/// (`restart`). They are numbered again.
+///
+/// # Returns
+///
+/// False if one of them can't be watched anymore, as a region of the heap not mapped yet.
+pub fn rewatch(debugger: &mut Debugger) -> bool {
+    let regions = std::mem::take(&mut debugger.region_watches.list);
+    debugger.region_watches.count = 0;
+    debugger.region_watches.hit = false;
+    let mut succeeded = true;
+    for region in regions {
+        if let Err(err) = watch_region(debugger, region.address, region.len) {
+            errln!(debugger, "Region watchpoint {} ({} bytes at {:#x}) dropped: {}", region.number, region.len, region.address, err);
+            succeeded = false;
+        }
+    }
+    succeeded
+}
@@ -0,0 +1,133 @@
+use crate::debugger::Debugger;
+use crate::location;
+use crate::memory;
+
+/// The longest string read from the program for `%s`.
+const MAX_STRING_LENGTH: usize = 4096;
+
+/// Replace the escapes of `echo` and `printf` text: `\n`, `\t`, `\"`, `\\` and `\e` (escape).
+/// Other backslashes are kept.
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('e') => unescaped.push('\x1b'),
+            Some(c @ ('"' | '\\')) => unescaped.push(c),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Split the text given to `printf` into its quoted format and its arguments, separated by commas
+/// or spaces.
+///
+/// # Errors
+///
+/// Returns a message if the format is not quoted.
+pub fn split_arguments(text: &str) -> Result<(String, Vec<&str>), String> {
+    let text = text.trim();
+    let Some(rest) = text.strip_prefix('"') else {
+        return Err("The format of printf must be quoted, as in printf \"rax=%x\\n\" $rax".to_string());
+    };
+    let mut escaped = false;
+    let end = rest.char_indices().find(|&(_, c)| {
+        let end = c == '"' && !escaped;
+        escaped = c == '\\' && !escaped;
+        end
+    });
+    let Some((end, _)) = end else {
+        return Err(format!("Missing the \" closing the format of printf: {}", text));
+    };
+    let arguments = rest[end + 1..].split(|c: char| c == ',' || c.is_whitespace()).filter(|argument| !argument.is_empty()).collect();
+    Ok((unescape(&rest[..end]), arguments))
+}
+
+/// Format the values of `printf`, evaluated as addresses are (registers, symbols, convenience
+/// variables and offsets).
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session, to evaluate the arguments and read strings.
+/// * `format` - The format, with its escapes already replaced. `%x`, `%d`, `%u`, `%p`, `%s`, `%c`
+///   and `%%` are known, with the `-` and `0` flags and a width; `l` and `ll` are accepted and
+///   ignored, every value having 64 bits.
+/// * `arguments` - One argument for each conversion.
+///
+/// # Errors
+///
+/// Returns a message if a conversion is unknown, an argument is invalid or missing, there are
+/// arguments left, or a string cannot be read.
+pub fn format(debugger: &mut Debugger, format: &str, arguments: &[&str]) -> Result<String, String> {
+    let mut formatted = String::new();
+    let mut arguments = arguments.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            formatted.push('%');
+            continue;
+        }
+        let (mut left, mut zero) = (false, false);
+        while let Some(flag) = chars.next_if(|c| *c == '-' || *c == '0') {
+            left |= flag == '-';
+            zero |= flag == '0';
+        }
+        let mut width = 0;
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            width = width * 10 + digit.to_digit(10).unwrap_or_default() as usize;
+        }
+        while chars.next_if_eq(&'l').is_some() {}
+        let Some(conversion) = chars.next() else {
+            return Err("The format of printf ends with %".to_string());
+        };
+        if !"xdupsc".contains(conversion) {
+            return Err(format!("Unknown conversion %{} in the format of printf, expected %x, %d, %u, %p, %s, %c or %%", conversion));
+        }
+        let Some(argument) = arguments.next() else {
+            return Err(format!("Missing an argument for %{}", conversion));
+        };
+        let value = location::parse_value(debugger, argument, "argument").map_err(|err| err.to_string())?;
+        let text = match conversion {
+            'x' => format!("{:x}", value),
+            'd' => (value as i64).to_string(),
+            'u' => value.to_string(),
+            'p' => format!("{:#x}", value),
+            'c' => char::from_u32(value as u32).unwrap_or(char::REPLACEMENT_CHARACTER).to_string(),
+            _ => memory::read_c_string(debugger.tracee().as_ref(), value, MAX_STRING_LENGTH)
+                .map_err(|err| format!("Cannot read the string at {:#x} for %s: {}", value, err))?,
+        };
+        let padding = width.saturating_sub(text.chars().count());
+        if left {
+            formatted.push_str(&text);
+            formatted.push_str(&" ".repeat(padding));
+        } else if zero && "xdup".contains(conversion) {
+            // The zeros go after the sign and the 0x.
+            let split = if text.starts_with('-') { 1 } else if text.starts_with("0x") { 2 } else { 0 };
+            formatted.push_str(&text[..split]);
+            formatted.push_str(&"0".repeat(padding));
+            formatted.push_str(&text[split..]);
+        } else {
+            formatted.push_str(&" ".repeat(padding));
+            formatted.push_str(&text);
+        }
+    }
+    if arguments.len() > 0 {
+        return Err(format!("Too many arguments for the format of printf: {} left", arguments.len()));
+    }
+    Ok(formatted)
+}
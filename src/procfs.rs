@@ -0,0 +1,459 @@
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use nix::libc;
+use nix::unistd::Pid;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+/// The fields of `/proc/<pid>/status` shown by `info proc`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcStatus {
+    /// The name of the program, truncated by the kernel to 15 bytes.
+    pub name: String,
+    /// The state letter with its description, as in `t (tracing stop)`.
+    pub state: String,
+    /// The parent process.
+    pub ppid: Option<i32>,
+    /// The number of threads.
+    pub threads: Option<usize>,
+    /// The size of the virtual memory, in kB.
+    pub vm_size: Option<u64>,
+    /// The resident memory, in kB.
+    pub vm_rss: Option<u64>,
+}
+
+/// The fields of `/proc/<pid>/stat` shown while the program runs (`set run-status interval`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcStat {
+    /// The state letter, as `R` (running), `S` (sleeping) or `D` (disk sleep).
+    pub state: char,
+    /// The CPU time used by the threads of the process, in user and system mode, in clock ticks.
+    pub cpu_ticks: u64,
+}
+
+/// The position and the open flags of a file descriptor, from `/proc/<pid>/fdinfo/<fd>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdInfo {
+    /// The current offset in the file.
+    pub pos: u64,
+    /// The flags given to `open`, and changed by `fcntl`.
+    pub flags: u32,
+}
+
+/// An open file descriptor of the process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenFile {
+    pub fd: i32,
+    /// What it refers to: a path, or `socket:[inode]`, `pipe:[inode]`, `anon_inode:[eventfd]`...
+    pub target: String,
+    /// Its offset and flags, `None` if the descriptor was closed meanwhile.
+    pub info: Option<FdInfo>,
+}
+
+/// A change of the open file descriptors of the process between two stops (`set track-fds on`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FdChange {
+    Opened { fd: i32, target: String },
+    Closed { fd: i32, target: String },
+    /// Closed and opened again with another target, as by `dup2`.
+    Replaced { fd: i32, old: String, new: String },
+}
+
+impl FdChange {
+    /// The line reporting the change, as in `opened fd 5 -> /tmp/cache.db` or `closed fd 3`.
+    pub fn describe(&self) -> String {
+        match self {
+            FdChange::Opened { fd, target } => format!("opened fd {} -> {}", fd, target),
+            FdChange::Closed { fd, target } => format!("closed fd {} (was {})", fd, target),
+            FdChange::Replaced { fd, old, new } => format!("reopened fd {} -> {} (was {})", fd, new, old),
+        }
+    }
+}
+
+/// The descriptors tracked from stop to stop with `set track-fds on`.
+#[derive(Debug, Default)]
+pub struct FdTracking {
+    pub enabled: bool,
+    /// The descriptors open at the last stop, and the process they belong to, so that a new
+    /// process started by `restart` is not compared with the previous one.
+    last: Option<(Pid, Vec<OpenFile>)>,
+}
+
+/// The names of the open flags shown, besides the access mode. `O_SYNC` includes `O_DSYNC`, so it
+/// comes first and `O_DSYNC` only shows without it.
+const OPEN_FLAGS: &[(i32, &str)] = &[
+    (libc::O_CREAT, "O_CREAT"),
+    (libc::O_EXCL, "O_EXCL"),
+    (libc::O_NOCTTY, "O_NOCTTY"),
+    (libc::O_TRUNC, "O_TRUNC"),
+    (libc::O_APPEND, "O_APPEND"),
+    (libc::O_NONBLOCK, "O_NONBLOCK"),
+    (libc::O_SYNC, "O_SYNC"),
+    (libc::O_DSYNC, "O_DSYNC"),
+    (libc::O_ASYNC, "O_ASYNC"),
+    (libc::O_DIRECT, "O_DIRECT"),
+    // The kernel sets it on every file of a 64-bit process, where libc defines it as 0.
+    (0o100000, "O_LARGEFILE"),
+    (libc::O_DIRECTORY, "O_DIRECTORY"),
+    (libc::O_NOFOLLOW, "O_NOFOLLOW"),
+    (libc::O_NOATIME, "O_NOATIME"),
+    (libc::O_CLOEXEC, "O_CLOEXEC"),
+    (libc::O_PATH, "O_PATH"),
+];
+
+/// Parse the content of a `/proc/<pid>/status` file. The fields missing or malformed are left
+/// empty.
+pub fn parse_status(content: &str) -> ProcStatus {
+    let mut status = ProcStatus::default();
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        // The sizes are given as "1234 kB".
+        let kilobytes = || value.strip_suffix("kB").and_then(|value| value.trim().parse().ok());
+        match key {
+            "Name" => status.name = value.to_string(),
+            "State" => status.state = value.to_string(),
+            "PPid" => status.ppid = value.parse().ok(),
+            "Threads" => status.threads = value.parse().ok(),
+            "VmSize" => status.vm_size = kilobytes(),
+            "VmRSS" => status.vm_rss = kilobytes(),
+            _ => {}
+        }
+    }
+    status
+}
+
+/// Parse the content of a `/proc/<pid>/stat` file. The name of the program, in parentheses, may
+/// contain spaces and parentheses, so the fields are counted after the last one.
+///
+/// # Returns
+///
+/// `None` if the state or the CPU times are missing.
+pub fn parse_stat(content: &str) -> Option<ProcStat> {
+    let fields: Vec<&str> = content[content.rfind(')')? + 1..].split_whitespace().collect();
+    // The state is the field 3, utime and stime the fields 14 and 15.
+    let ticks = |index: usize| fields.get(index - 3)?.parse::<u64>().ok();
+    Some(ProcStat { state: fields.first()?.chars().next()?, cpu_ticks: ticks(14)? + ticks(15)? })
+}
+
+/// Parse the content of a `/proc/<pid>/syscall` file: the number of the system call the thread is
+/// blocked in, followed by its arguments, `-1` when it is blocked outside of one, or `running`.
+pub fn parse_syscall(content: &str) -> Option<u64> {
+    match parse_syscall_line(content)? {
+        ThreadSyscall::In { number, .. } => Some(number),
+        ThreadSyscall::Running | ThreadSyscall::NotIn { .. } => None,
+    }
+}
+
+/// What a thread is doing, from `/proc/<pid>/task/<tid>/syscall`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadSyscall {
+    /// The thread runs, the kernel doesn't tell.
+    Running,
+    /// The thread entered the kernel by something else than a system call, as a breakpoint or an
+    /// interrupt, with its stack pointer and pc.
+    NotIn { sp: u64, pc: u64 },
+    /// The thread entered the kernel by the system call `number` with its 6 arguments: it is
+    /// blocked in it, stopped at its entry or exit, or by a signal delivered at its exit.
+    In { number: u64, args: [u64; 6], sp: u64, pc: u64 },
+}
+
+/// Parse the content of a `/proc/<pid>/syscall` file: `running`, `-1 <sp> <pc>`, or the number of
+/// the system call followed by its 6 arguments, the stack pointer and the pc, in hexadecimal.
+pub fn parse_syscall_line(content: &str) -> Option<ThreadSyscall> {
+    let fields: Vec<&str> = content.split_whitespace().collect();
+    if fields.first() == Some(&"running") {
+        return Some(ThreadSyscall::Running);
+    }
+    let hex = |field: &&str| u64::from_str_radix(field.trim_start_matches("0x"), 16).ok();
+    let values: Vec<u64> = fields.iter().skip(1).map(hex).collect::<Option<_>>()?;
+    match (fields.first()?.parse::<i64>().ok()?, values.as_slice()) {
+        (-1, [sp, pc]) => Some(ThreadSyscall::NotIn { sp: *sp, pc: *pc }),
+        (number, [args @ .., sp, pc]) if number >= 0 => {
+            Some(ThreadSyscall::In { number: number as u64, args: args.try_into().ok()?, sp: *sp, pc: *pc })
+        }
+        _ => None,
+    }
+}
+
+/// The auxiliary vector entry giving the address the interpreter (dynamic loader) is loaded at,
+/// 0 for a program without one.
+pub const AT_BASE: u64 = 7;
+/// The auxiliary vector entry giving the entry point of the program.
+pub const AT_ENTRY: u64 = 9;
+/// The auxiliary vector entry giving the address of the ELF header of the vDSO.
+pub const AT_SYSINFO_EHDR: u64 = 33;
+
+/// Parse an auxiliary vector, as in `/proc/<pid>/auxv`: pairs of words of `word_size` bytes, a
+/// type and a value, up to the `AT_NULL` entry.
+///
+/// # Returns
+///
+/// The type and value of each entry.
+pub fn parse_auxv(bytes: &[u8], word_size: usize) -> Vec<(u64, u64)> {
+    let word = |chunk: &[u8]| chunk.iter().rev().fold(0u64, |value, byte| value << 8 | u64::from(*byte));
+    bytes
+        .chunks_exact(2 * word_size)
+        .map(|pair| (word(&pair[..word_size]), word(&pair[word_size..])))
+        .take_while(|(kind, _)| *kind != 0)
+        .collect()
+}
+
+/// Parse the content of a `/proc/<pid>/fdinfo/<fd>` file, whose flags are in octal.
+///
+/// # Returns
+///
+/// `None` if the position or the flags are missing.
+pub fn parse_fdinfo(content: &str) -> Option<FdInfo> {
+    let field = |name: &str| content.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(':').map(str::trim));
+    Some(FdInfo {
+        pos: field("pos")?.parse().ok()?,
+        flags: u32::from_str_radix(field("flags")?, 8).ok()?,
+    })
+}
+
+/// Compare the descriptors open at two stops, by number and target: the offsets and flags are
+/// not compared.
+///
+/// # Returns
+///
+/// The changes, ordered by descriptor, none if nothing changed.
+pub fn diff_fds(old: &[OpenFile], new: &[OpenFile]) -> Vec<FdChange> {
+    let targets = |files: &[OpenFile]| files.iter().map(|file| (file.fd, file.target.clone())).collect::<BTreeMap<_, _>>();
+    let (mut old, new) = (targets(old), targets(new));
+    let mut changes = Vec::new();
+    for (fd, target) in new {
+        match old.remove(&fd) {
+            None => changes.push(FdChange::Opened { fd, target }),
+            Some(previous) if previous != target => changes.push(FdChange::Replaced { fd, old: previous, new: target }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(old.into_iter().map(|(fd, target)| FdChange::Closed { fd, target }));
+    changes.sort_by_key(|change| match change {
+        FdChange::Opened { fd, .. } | FdChange::Closed { fd, .. } | FdChange::Replaced { fd, .. } => *fd,
+    });
+    changes
+}
+
+/// Name the open flags, as in `O_WRONLY|O_APPEND|O_CLOEXEC`: the access mode, the flags known, then
+/// the other bits in octal.
+pub fn describe_flags(flags: u32) -> String {
+    let flags = flags as i32;
+    let mut names = vec![match flags & libc::O_ACCMODE {
+        libc::O_RDONLY => "O_RDONLY".to_string(),
+        libc::O_WRONLY => "O_WRONLY".to_string(),
+        libc::O_RDWR => "O_RDWR".to_string(),
+        mode => format!("{:#o}", mode),
+    }];
+    let mut rest = flags & !libc::O_ACCMODE;
+    for (flag, name) in OPEN_FLAGS {
+        if rest & flag == *flag {
+            names.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        names.push(format!("{:#o}", rest));
+    }
+    names.join("|")
+}
+
+/// Read and parse `/proc/<pid>/status`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, as once the process is gone.
+pub fn read_status(pid: Pid) -> io::Result<ProcStatus> {
+    Ok(parse_status(&fs::read_to_string(format!("/proc/{}/status", pid))?))
+}
+
+/// Read and parse `/proc/<pid>/stat`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, as once the process is gone, or is malformed.
+pub fn read_stat(pid: Pid) -> io::Result<ProcStat> {
+    parse_stat(&fs::read_to_string(format!("/proc/{}/stat", pid))?).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed stat"))
+}
+
+/// Read and parse `/proc/<pid>/auxv`, the auxiliary vector given by the kernel to the program,
+/// whose words are 4 bytes for a 32-bit program.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, as once the process is gone.
+pub fn read_auxv(pid: Pid, word_size: usize) -> io::Result<Vec<(u64, u64)>> {
+    Ok(parse_auxv(&fs::read(format!("/proc/{}/auxv", pid))?, word_size))
+}
+
+/// The system call the main thread of a process is blocked in, from `/proc/<pid>/syscall`.
+pub fn read_syscall(pid: Pid) -> Option<u64> {
+    parse_syscall(&fs::read_to_string(format!("/proc/{}/syscall", pid)).ok()?)
+}
+
+/// What a thread of a process is doing, from `/proc/<pid>/task/<tid>/syscall`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, as once the thread is gone, or
+/// [`io::ErrorKind::InvalidData`] if it can't be parsed.
+pub fn read_thread_syscall(pid: Pid, tid: Pid) -> io::Result<ThreadSyscall> {
+    parse_syscall_line(&fs::read_to_string(format!("/proc/{}/task/{}/syscall", pid, tid))?)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed syscall"))
+}
+
+/// The kernel function the main thread of a process waits in, from `/proc/<pid>/wchan`, which is
+/// `0` when it does not wait or the kernel hides it.
+pub fn read_wchan(pid: Pid) -> Option<String> {
+    let wchan = fs::read_to_string(format!("/proc/{}/wchan", pid)).ok()?;
+    let wchan = wchan.trim();
+    (!wchan.is_empty() && wchan != "0").then(|| wchan.to_string())
+}
+
+/// Read a link of `/proc/<pid>`, as `cwd` or `exe`.
+///
+/// # Errors
+///
+/// Returns an error if the link can't be read, as when the process belongs to another user.
+pub fn read_link(pid: Pid, name: &str) -> io::Result<String> {
+    Ok(fs::read_link(format!("/proc/{}/{}", pid, name))?.to_string_lossy().into_owned())
+}
+
+/// List the open file descriptors of a process, in order, with their target, offset and flags.
+///
+/// # Errors
+///
+/// Returns an error if `/proc/<pid>/fd` can't be read.
+pub fn read_fds(pid: Pid) -> io::Result<Vec<OpenFile>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{}/fd", pid))? {
+        let entry = entry?;
+        let Some(fd) = entry.file_name().to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+        // Closed since the directory was read.
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        let info = fs::read_to_string(format!("/proc/{}/fdinfo/{}", pid, fd)).ok().and_then(|content| parse_fdinfo(&content));
+        files.push(OpenFile { fd, target: target.to_string_lossy().into_owned(), info });
+    }
+    files.sort_by_key(|file| file.fd);
+    Ok(files)
+}
+
+/// Start or stop tracking the descriptors of the program from stop to stop (`set track-fds`),
+/// the ones open now being the first ones compared.
+pub fn track_fds(debugger: &mut Debugger, enabled: bool) {
+    let pid = debugger.child;
+    debugger.fd_tracking.enabled = enabled;
+    debugger.fd_tracking.last = if enabled && debugger.state.is_alive() { read_fds(pid).ok().map(|files| (pid, files)) } else { None };
+}
+
+/// Print the descriptors opened and closed by the program since its last stop, with
+/// `set track-fds on`. Nothing is printed when nothing changed.
+pub fn report_fd_changes(debugger: &mut Debugger) {
+    if !debugger.fd_tracking.enabled || !debugger.state.is_alive() {
+        return;
+    }
+    let pid = debugger.child;
+    // Not readable, as for a core file.
+    let Ok(files) = read_fds(pid) else {
+        return;
+    };
+    if let Some((last_pid, last)) = &debugger.fd_tracking.last {
+        if *last_pid == pid {
+            for change in diff_fds(last, &files) {
+                outln!(debugger, "{}", change.describe());
+            }
+        }
+    }
+    debugger.fd_tracking.last = Some((pid, files));
+}
+
+/// Show what `/proc/<pid>` tells about the program (`info proc`): its status without a topic,
+/// `cwd`, `exe` or its open file descriptors with `fd`.
+///
+/// # Returns
+///
+/// False if the program is not running, the topic is unknown or the information can't be read.
+pub fn info_proc(debugger: &mut Debugger, topic: Option<&str>) -> bool {
+    if !debugger.state.is_alive() {
+        errln!(debugger, "The program is not being run.");
+        return false;
+    }
+    if debugger.core.is_some() {
+        errln!(debugger, "{}", DbgError::NotSupportedOnCore);
+        return false;
+    }
+    let pid = debugger.child;
+    let failed = |debugger: &mut Debugger, what: &str, err: io::Error| {
+        errln!(debugger, "Could not read /proc/{}/{}: {}", pid, what, err);
+        false
+    };
+    match topic {
+        None => {
+            let status = match read_status(pid) {
+                Ok(status) => status,
+                Err(err) => return failed(debugger, "status", err),
+            };
+            let unknown = |value: Option<String>| value.unwrap_or_else(|| "?".to_string());
+            json::set_data(debugger, |_| {
+                json!({
+                    "pid": pid.as_raw(),
+                    "name": status.name,
+                    "state": status.state,
+                    "ppid": status.ppid,
+                    "threads": status.threads,
+                    "vm_size": status.vm_size,
+                    "vm_rss": status.vm_rss,
+                })
+            });
+            outln!(debugger, "process {} ({})", pid, status.name);
+            outln!(debugger, "State: {}", status.state);
+            outln!(debugger, "Parent: {}", unknown(status.ppid.map(|ppid| ppid.to_string())));
+            outln!(debugger, "Threads: {}", unknown(status.threads.map(|threads| threads.to_string())));
+            outln!(debugger, "VM size: {}", unknown(status.vm_size.map(|size| format!("{} kB", size))));
+            outln!(debugger, "VM RSS: {}", unknown(status.vm_rss.map(|size| format!("{} kB", size))));
+        }
+        Some(name @ ("cwd" | "exe")) => {
+            let target = match read_link(pid, name) {
+                Ok(target) => target,
+                Err(err) => return failed(debugger, name, err),
+            };
+            json::set_data(debugger, |_| json!({ name: target }));
+            outln!(debugger, "{} = '{}'", name, target);
+        }
+        Some("fd") => {
+            let files = match read_fds(pid) {
+                Ok(files) => files,
+                Err(err) => return failed(debugger, "fd", err),
+            };
+            let mut data = Vec::new();
+            outln!(debugger, "{:<4}  {:<32}  {:>10}  Target", "FD", "Flags", "Offset");
+            for file in files {
+                let flags = file.info.map(|info| describe_flags(info.flags)).unwrap_or_else(|| "?".to_string());
+                let offset = file.info.map(|info| info.pos.to_string()).unwrap_or_else(|| "?".to_string());
+                outln!(debugger, "{:<4}  {:<32}  {:>10}  {}", file.fd, flags, offset, file.target);
+                data.push(json!({
+                    "fd": file.fd,
+                    "target": file.target,
+                    "flags": file.info.map(|info| describe_flags(info.flags)),
+                    "offset": file.info.map(|info| info.pos),
+                }));
+            }
+            json::set_data(debugger, |_| json!({ "fds": data }));
+        }
+        Some(topic) => {
+            errln!(debugger, "Unknown info proc topic \"{}\", use info proc [cwd | exe | fd].", topic);
+            return false;
+        }
+    }
+    true
+}
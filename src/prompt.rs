@@ -0,0 +1,93 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::state::InferiorState;
+use crate::symbols::demangle;
+use nix::unistd;
+
+/// The prompt template used until `set prompt` changes it.
+pub const DEFAULT_PROMPT_TEMPLATE: &str = "rustdbg [{status}]> ";
+
+/// The prompt of the lines of a `define`, until `end`.
+pub const DEFINITION_PROMPT: &str = ">";
+
+/// The state of the program shown by the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessState {
+    /// Not started with `run` yet, or terminated.
+    NoProcess,
+    /// Executing, not stopped by the debugger.
+    Running,
+    /// Stopped at `pc`, inside `symbol` (its name and the offset of `pc` in it) if it is known.
+    Stopped { pc: u64, symbol: Option<(String, u64)> },
+}
+
+impl ProcessState {
+    /// The name of the state, as shown by `{state}`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcessState::NoProcess => "no process",
+            ProcessState::Running => "running",
+            ProcessState::Stopped { .. } => "stopped",
+        }
+    }
+}
+
+/// Find out the state of the program shown by the prompt, from [`Debugger::state`], and where it
+/// is stopped.
+pub fn process_state(debugger: &mut Debugger) -> ProcessState {
+    match debugger.state {
+        InferiorState::NotStarted | InferiorState::Exited { .. } | InferiorState::Signaled { .. } => return ProcessState::NoProcess,
+        InferiorState::Running => return ProcessState::Running,
+        InferiorState::Stopped { .. } => {}
+    }
+    let Ok(regs) = debugger.tracee().getregs() else {
+        return ProcessState::NoProcess;
+    };
+    let symbol = debugger.symbolize(regs.pc()).map(|(name, offset, _)| (demangle(&name), offset));
+    ProcessState::Stopped { pc: regs.pc(), symbol }
+}
+
+/// Expand the tokens of a prompt template.
+///
+/// # Arguments
+///
+/// * `template` - The prompt, with `{pid}`, `{state}`, `{pc}`, `{symbol}` and `{status}` tokens.
+/// * `pid` - The process ID of the program.
+/// * `state` - The state of the program.
+///
+/// # Returns
+///
+/// The prompt. `{pc}` and `{symbol}` are empty unless the program is stopped in a known function.
+pub fn render(template: &str, pid: unistd::Pid, state: &ProcessState) -> String {
+    let (pc, symbol) = match state {
+        ProcessState::Stopped { pc, symbol } => (
+            format!("{:#x}", pc),
+            match symbol {
+                Some((name, 0)) => name.clone(),
+                Some((name, offset)) => format!("{}+{:#x}", name, offset),
+                None => String::new(),
+            },
+        ),
+        _ => (String::new(), String::new()),
+    };
+    let status = match state {
+        ProcessState::Stopped { .. } if symbol.is_empty() => format!("pid {}, stopped @ {}", pid, pc),
+        ProcessState::Stopped { .. } => format!("pid {}, stopped @ {} <{}>", pid, pc, symbol),
+        state => state.name().to_string(),
+    };
+    template
+        .replace("{pid}", &pid.to_string())
+        .replace("{state}", state.name())
+        .replace("{pc}", &pc)
+        .replace("{symbol}", &symbol)
+        .replace("{status}", &status)
+}
+
+/// The prompt to show before the next command, or the next line of a definition.
+pub fn prompt(debugger: &mut Debugger) -> String {
+    if debugger.definition.is_some() {
+        return DEFINITION_PROMPT.to_string();
+    }
+    let state = process_state(debugger);
+    render(&debugger.prompt, debugger.child, &state)
+}
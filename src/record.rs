@@ -0,0 +1,195 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::json;
+use crate::tracee::Tracee;
+use nix::libc::user_regs_struct;
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+
+/// The number of registers recorded, after the pc.
+#[cfg(target_arch = "x86_64")]
+const RECORDED: usize = 17;
+/// The registers recorded, after the pc.
+#[cfg(target_arch = "x86_64")]
+const REGISTERS: [&str; RECORDED] =
+    ["rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "eflags"];
+
+/// The values of [`REGISTERS`].
+#[cfg(target_arch = "x86_64")]
+fn register_values(regs: &user_regs_struct) -> [u64; RECORDED] {
+    [
+        regs.rax, regs.rbx, regs.rcx, regs.rdx, regs.rsi, regs.rdi, regs.rbp, regs.rsp, regs.r8, regs.r9, regs.r10, regs.r11,
+        regs.r12, regs.r13, regs.r14, regs.r15, regs.eflags,
+    ]
+}
+
+/// The number of registers recorded on aarch64, after the pc.
+#[cfg(target_arch = "aarch64")]
+const RECORDED: usize = 33;
+/// The registers recorded on aarch64, after the pc.
+#[cfg(target_arch = "aarch64")]
+const REGISTERS: [&str; RECORDED] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15", "x16", "x17", "x18",
+    "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30", "sp", "pstate",
+];
+
+/// The values of [`REGISTERS`].
+#[cfg(target_arch = "aarch64")]
+fn register_values(regs: &user_regs_struct) -> [u64; RECORDED] {
+    let mut values = [0; RECORDED];
+    values[..31].copy_from_slice(&regs.regs);
+    values[31] = regs.sp;
+    values[32] = regs.pstate;
+    values
+}
+
+/// The instructions stepped, recorded to a file by `record on <file>`: one JSON line per
+/// instruction, as `{"pc":"0x401126","rax":"0x1","eflags":"0x246"}`, with the pc of the instruction
+/// and the registers it changed. The first line after `record on`, and the first one after the
+/// program ran without being recorded, have every register, so that the file can be replayed.
+#[derive(Default)]
+pub struct InstructionRecord {
+    /// The file given to the last `record on`.
+    path: Option<String>,
+    writer: Option<BufWriter<File>>,
+    /// The registers after the last instruction recorded.
+    last: Option<[u64; RECORDED]>,
+    /// The number of instructions recorded since `record on`.
+    pub instructions: u64,
+}
+
+impl InstructionRecord {
+    /// Returns true while recording.
+    pub fn is_on(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Start recording to `path`, appended to, after finishing the file recorded to before.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn start(&mut self, path: &str) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.stop().ok();
+        *self = InstructionRecord { path: Some(path.to_string()), writer: Some(BufWriter::new(file)), last: None, instructions: 0 };
+        Ok(())
+    }
+
+    /// Stop recording, writing what is buffered to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the end of the record cannot be written.
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.last = None;
+        match self.writer.take() {
+            Some(mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// The size of the file recorded to, with what is still buffered.
+    fn size(&self) -> io::Result<u64> {
+        match &self.writer {
+            Some(writer) => Ok(writer.get_ref().metadata()?.len() + writer.buffer().len() as u64),
+            None => Ok(0),
+        }
+    }
+
+    /// Record the instruction at the pc of `before`, which made the registers `after`. Nothing
+    /// is allocated, the line is formatted into the buffer of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn record(&mut self, before: &user_regs_struct, after: &user_regs_struct) -> io::Result<()> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        let (before_values, after_values) = (register_values(before), register_values(after));
+        // Registers changed by an instruction not recorded would be blamed on this one.
+        let previous = self.last.filter(|last| *last == before_values);
+        write!(writer, "{{\"pc\":\"{:#x}\"", before.pc())?;
+        for (index, value) in after_values.iter().enumerate() {
+            if previous.is_none_or(|previous| previous[index] != *value) {
+                write!(writer, ",\"{}\":\"{:#x}\"", REGISTERS[index], value)?;
+            }
+        }
+        writer.write_all(b"}\n")?;
+        self.last = Some(after_values);
+        self.instructions += 1;
+        Ok(())
+    }
+}
+
+/// Record a single step of the current thread from the registers `before` it, when recording.
+/// The recording stops with a warning if the file cannot be written.
+pub fn record_step(debugger: &mut Debugger, before: &user_regs_struct) {
+    if !debugger.record.is_on() {
+        return;
+    }
+    let result = match debugger.thread.getregs() {
+        Ok(after) => debugger.record.record(before, &after),
+        Err(errno) => Err(errno.into()),
+    };
+    if let Err(err) = result {
+        let path = debugger.record.path.clone().unwrap_or_default();
+        debugger.record.stop().ok();
+        warnln!(debugger, "Recording stopped, cannot write {}: {}", path, err);
+    }
+}
+
+/// Run the `record on <file>`, `record off` and `record stats` commands.
+///
+/// # Returns
+///
+/// False if the arguments are invalid or the file cannot be opened or written.
+pub fn record_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match args {
+        ["on", path] => match debugger.record.start(path) {
+            Ok(()) => {
+                outln!(debugger, "Recording the instructions stepped to {}.", path);
+                true
+            }
+            Err(err) => {
+                errln!(debugger, "Cannot record to {}: {}", path, err);
+                false
+            }
+        },
+        ["off"] => {
+            let (path, instructions) = (debugger.record.path.clone(), debugger.record.instructions);
+            let Some(path) = path.filter(|_| debugger.record.is_on()) else {
+                outln!(debugger, "Not recording.");
+                return true;
+            };
+            if let Err(err) = debugger.record.stop() {
+                errln!(debugger, "Cannot write {}: {}", path, err);
+                return false;
+            }
+            outln!(debugger, "Recorded {} instructions to {}.", instructions, path);
+            true
+        }
+        ["stats"] => {
+            let size = match debugger.record.size() {
+                Ok(size) => size,
+                Err(err) => {
+                    errln!(debugger, "{}", err);
+                    return false;
+                }
+            };
+            let (on, instructions, path) = (debugger.record.is_on(), debugger.record.instructions, debugger.record.path.clone());
+            json::set_data(debugger, |_| json!({ "recording": on, "file": path, "instructions": instructions, "bytes": size }));
+            match path.filter(|_| on) {
+                Some(path) => outln!(debugger, "Recording to {}: {} instructions, {} bytes.", path, instructions, size),
+                None => outln!(debugger, "Not recording."),
+            }
+            true
+        }
+        _ => {
+            errln!(debugger, "Usage: record on <file> | record off | record stats");
+            false
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use crate::commands::{CommandMatch, COMMANDS};
+use crate::debugger::Debugger;
+use crate::logging;
+use crate::output::{paint, Style};
+use crate::sandbox::RESOURCES;
+use crate::settings::{find_setting, SettingKind, SETTINGS};
+use crate::symbols::SymbolIndex;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::borrow::Cow;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The prompt shown before the commands of scripts and `-ex`.
+pub const PROMPT: &str = "rustdbg> ";
+/// Commands run again when an empty line is submitted.
+const REPEATABLE_COMMANDS: &[&str] = &["c", "continue", "s", "syscall", "n", "next", "step", "si", "stepi", "l", "list"];
+const INFO_KEYWORDS: &[&str] =
+    &["address", "alias", "args", "breakpoints", "convenience", "display", "dl", "file", "frame", "labels", "line", "locals", "sections", "sharedlibrary", "symbol", "syscall", "variables"];
+
+/// What the word under the cursor is completed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Argument {
+    Commands,
+    Keywords(&'static [&'static str]),
+    /// The names of the settings after `set` or `show`, then the values of the setting after `set`.
+    Setting,
+    File,
+    Function,
+    Data,
+    Nothing,
+}
+
+/// The kind of argument expected after the words `words` already typed.
+fn expected_argument(words: &[&str]) -> Argument {
+    match words {
+        [] => Argument::Commands,
+        // The output of `disas` redirected to a file.
+        [.., ">"] => Argument::File,
+        ["i" | "info"] => Argument::Keywords(INFO_KEYWORDS),
+        ["set" | "show", ..] => Argument::Setting,
+        ["ltrace"] => Argument::Keywords(&["off", "on"]),
+        ["source"] => Argument::File,
+        ["b" | "breakpoint" | "l" | "list" | "disas" | "disassemble"] | ["disas" | "disassemble", "--raw"] => {
+            Argument::Function
+        }
+        ["i" | "info", "line" | "address"] => Argument::Function,
+        ["p" | "print" | "watch-region"] => Argument::Data,
+        _ => Argument::Nothing,
+    }
+}
+
+/// Complete the paths starting with `prefix`, directories ending with `/`.
+fn complete_path(prefix: &str) -> Vec<String> {
+    let (directory, name) = match prefix.rfind('/') {
+        Some(slash) => (&prefix[..=slash], &prefix[slash + 1..]),
+        None => ("", prefix),
+    };
+    let Ok(entries) = std::fs::read_dir(if directory.is_empty() { Path::new(".") } else { Path::new(directory) }) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            // Hidden files only when asked for.
+            if !file_name.starts_with(name) || (file_name.starts_with('.') && !name.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", directory, file_name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Complete the name of a setting or, after `set <setting>`, its value.
+///
+/// # Arguments
+///
+/// * `words` - The words typed after `set` or `show`.
+/// * `value` - True after `set`, where the value follows the name.
+/// * `prefix` - The word being completed.
+fn complete_setting(words: &[&str], value: bool, prefix: &str) -> Vec<String> {
+    // The next word of the names starting with the words typed.
+    let mut candidates: Vec<String> = SETTINGS
+        .iter()
+        .filter_map(|spec| {
+            let mut name = spec.name.split(' ');
+            name.by_ref().take(words.len()).eq(words.iter().copied()).then(|| name.next())?
+        })
+        .map(str::to_string)
+        .collect();
+    if let Some((spec, count)) = find_setting(words).filter(|_| value) {
+        match spec.kind {
+            SettingKind::Bool if count == words.len() => candidates.extend(["off".to_string(), "on".to_string()]),
+            SettingKind::Enum(names) if count == words.len() => candidates.extend(names.iter().map(|name| name.to_string())),
+            SettingKind::IntOrOff { .. } if count == words.len() => candidates.push("off".to_string()),
+            SettingKind::Path => candidates.extend(complete_path(prefix)),
+            _ if spec.name == "rlimit" && count == words.len() => candidates.extend(RESOURCES.iter().map(|(name, _)| name.to_string())),
+            _ => {}
+        }
+    }
+    if !value && words.is_empty() {
+        candidates.push("rlimits".to_string());
+    }
+    candidates.retain(|candidate| candidate.starts_with(prefix));
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// Complete the word ending at `pos` in `line`: command names at the start of the line, the
+/// keywords of `info` and `set`, file paths, and the loaded functions for `b`, `disas` and `list`
+/// or data symbols for `print`.
+///
+/// # Arguments
+///
+/// * `line` - The line being edited.
+/// * `pos` - The position of the cursor in `line`.
+/// * `index` - The names of the loaded symbols.
+///
+/// # Returns
+///
+/// The position where the completed word starts, and the candidates replacing it, sorted.
+pub fn complete(line: &str, pos: usize, index: &SymbolIndex) -> (usize, Vec<String>) {
+    let line = &line[..pos];
+    let start = line.rfind(char::is_whitespace).map_or(0, |space| space + 1);
+    let word = &line[start..];
+    let words: Vec<&str> = line[..start].split_whitespace().collect();
+    let candidates = match expected_argument(&words) {
+        Argument::Commands => {
+            let mut names: Vec<String> =
+                COMMANDS.iter().map(|spec| spec.name()).filter(|name| name.starts_with(word)).map(str::to_string).collect();
+            names.sort();
+            names
+        }
+        Argument::Keywords(keywords) => {
+            keywords.iter().filter(|keyword| keyword.starts_with(word)).map(|keyword| keyword.to_string()).collect()
+        }
+        Argument::Setting => complete_setting(&words[1..], words[0] == "set", word),
+        Argument::File => complete_path(word),
+        Argument::Function => SymbolIndex::starting_with(&index.functions, word).cloned().collect(),
+        Argument::Data => SymbolIndex::starting_with(&index.data, word).cloned().collect(),
+        Argument::Nothing => Vec::new(),
+    };
+    (start, candidates)
+}
+
+/// Completes the commands and their arguments with Tab, from the symbols of the debugger.
+struct CommandHelper {
+    /// The symbol index of the debugger, updated as libraries are loaded.
+    symbols: Arc<Mutex<SymbolIndex>>,
+    /// True if the prompt is colored.
+    color: bool,
+}
+
+impl Completer for CommandHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        match self.symbols.lock() {
+            Ok(index) => Ok(complete(line, pos, &index)),
+            Err(_) => Ok((pos, Vec::new())),
+        }
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _default: bool) -> Cow<'b, str> {
+        if self.color {
+            Cow::Owned(paint(prompt, Style::Prompt))
+        } else {
+            Cow::Borrowed(prompt)
+        }
+    }
+}
+
+impl Validator for CommandHelper {}
+
+impl Helper for CommandHelper {}
+
+/// The interactive prompt: Emacs-style line editing, history (up/down, Ctrl-R search) saved
+/// across sessions, Tab completion.
+pub struct Repl {
+    editor: Editor<CommandHelper, FileHistory>,
+    /// File the history is loaded from and saved to, if there is a home directory.
+    history_path: Option<PathBuf>,
+    /// The last command, if an empty line should run it again.
+    last_command: Option<String>,
+}
+
+/// Returns true if an empty line should run `command` again (stepping, continuing, listing).
+pub fn is_repeatable(command: &str) -> bool {
+    let Some(name) = command.split_whitespace().next() else {
+        return false;
+    };
+    match crate::commands::lookup_command(name) {
+        CommandMatch::Found(spec) => spec.names.iter().any(|name| REPEATABLE_COMMANDS.contains(name)),
+        _ => false,
+    }
+}
+
+/// Returns true if `confirm` has to ask: `set confirm` is on, the command was not forced with a
+/// `!` suffix and the debugger is neither in batch mode nor in JSON mode, where a program reads the
+/// output.
+pub fn needs_confirmation(debugger: &Debugger) -> bool {
+    debugger.confirm && !debugger.forced && !debugger.batch && debugger.json.is_none()
+}
+
+/// Ask whether to go on with a command that would lose the state of the session, such as killing
+/// the program.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `message` - The question, as in "Kill the program and quit?".
+///
+/// # Returns
+///
+/// True if the command can go on: the answer was yes, or nobody has to be asked (see
+/// [`needs_confirmation`]). When the commands are not read from a terminal, the answer is yes and
+/// the question is still printed, saying so.
+pub fn confirm(debugger: &mut Debugger, message: &str) -> bool {
+    if !needs_confirmation(debugger) {
+        return true;
+    }
+    if !std::io::stdin().is_terminal() {
+        outln!(debugger, "{} (y or n) [answered Y; input not from terminal]", message);
+        return true;
+    }
+    loop {
+        print!("{} (y or n) ", message);
+        std::io::stdout().flush().ok();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            // The end of the input (Ctrl-D) answers yes, as it quits.
+            Ok(0) | Err(_) => {
+                println!("EOF [answered Y]");
+                return true;
+            }
+            Ok(_) => {}
+        }
+        match answer.trim() {
+            "y" | "Y" | "yes" => return true,
+            "n" | "N" | "no" => {
+                outln!(debugger, "Not confirmed.");
+                return false;
+            }
+            _ => println!("Please answer y or n."),
+        }
+    }
+}
+
+/// The history file, `~/.local/share/rustdbg/history` (under `$XDG_DATA_HOME` when set).
+pub fn history_path() -> Option<PathBuf> {
+    let data_home = match std::env::var_os("XDG_DATA_HOME").filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data_home.join("rustdbg").join("history"))
+}
+
+impl Repl {
+    /// Create the prompt and load the history of the previous sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols` - The symbol index of the debugger, to complete function and variable names.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the terminal can't be set up.
+    pub fn new(symbols: Arc<Mutex<SymbolIndex>>) -> Result<Repl, ReadlineError> {
+        let mut editor = Editor::new()?;
+        if let Ok(printer) = editor.create_external_printer() {
+            logging::set_printer(printer);
+        }
+        editor.set_helper(Some(CommandHelper { symbols, color: false }));
+        let history_path = history_path();
+        if let Some(path) = &history_path {
+            // There is no history yet on the first run.
+            editor.load_history(path).ok();
+        }
+        Ok(Repl { editor, history_path, last_command: None })
+    }
+
+    /// Read the next command, adding it to the history.
+    ///
+    /// An empty line repeats the last command if it is repeatable, echoing it dimmed. Ctrl-C
+    /// discards the line being edited and prompts again.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The prompt shown before the line.
+    /// * `color` - True to color the prompt.
+    ///
+    /// # Returns
+    ///
+    /// The line read, or `None` at the end of the input (Ctrl-D).
+    pub fn read_command(&mut self, prompt: &str, color: bool) -> Option<String> {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.color = color;
+        }
+        loop {
+            logging::set_reading(true);
+            let line = self.editor.readline(prompt);
+            logging::set_reading(false);
+            match line {
+                Ok(line) => {
+                    let line = line.trim().to_string();
+                    if line.is_empty() {
+                        let repeated = self.last_command.clone().unwrap_or_default();
+                        if !repeated.is_empty() {
+                            echo(&repeated);
+                        }
+                        return Some(repeated);
+                    }
+                    self.add_history(&line);
+                    self.last_command = is_repeatable(&line).then(|| line.clone());
+                    return Some(line);
+                }
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return None,
+                Err(err) => {
+                    println!("Failed to read the command: {}", err);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Don't run the last command again on an empty line.
+    pub fn forget_last_command(&mut self) {
+        self.last_command = None;
+    }
+
+    fn add_history(&mut self, line: &str) {
+        if !self.editor.add_history_entry(line).unwrap_or(false) {
+            return;
+        }
+        let Some(path) = &self.history_path else {
+            return;
+        };
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).ok();
+        }
+        if let Err(err) = self.editor.append_history(path) {
+            println!("Could not save the history to {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// A line asked to the thread of [`PromptThread`].
+struct LineRequest {
+    prompt: String,
+    color: bool,
+    /// Don't run the last command again on an empty line, as in a definition.
+    forget_last_command: bool,
+}
+
+/// The prompt, reading the commands in a thread of its own so that the debugger goes on while a
+/// command is typed: the stops of the threads left running in non-stop mode are shown above the
+/// prompt.
+pub struct PromptThread {
+    requests: Sender<LineRequest>,
+    lines: Receiver<Option<String>>,
+    /// True while a line is asked and not received yet: the prompt is shown.
+    waiting: bool,
+}
+
+impl PromptThread {
+    /// Start the thread, creating the prompt in it as [`Repl::new`] does.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols` - The symbol index of the debugger, to complete function and variable names.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`Repl::new`].
+    pub fn new(symbols: Arc<Mutex<SymbolIndex>>) -> Result<PromptThread, ReadlineError> {
+        let (requests, requested) = mpsc::channel::<LineRequest>();
+        let (sender, lines) = mpsc::channel();
+        let (ready_sender, ready) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut repl = match Repl::new(symbols) {
+                Ok(repl) => repl,
+                Err(err) => {
+                    ready_sender.send(Err(err)).ok();
+                    return;
+                }
+            };
+            ready_sender.send(Ok(())).ok();
+            for request in requested {
+                if request.forget_last_command {
+                    repl.forget_last_command();
+                }
+                if sender.send(repl.read_command(&request.prompt, request.color)).is_err() {
+                    return;
+                }
+            }
+        });
+        ready.recv().unwrap_or(Err(ReadlineError::Eof))?;
+        Ok(PromptThread { requests, lines, waiting: false })
+    }
+
+    /// Show the prompt, unless it is already shown, and wait for the next command at most
+    /// `timeout`, as [`Repl::read_command`] reads it.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The prompt shown before the line.
+    /// * `color` - True to color the prompt.
+    /// * `forget_last_command` - True if an empty line should not run the last command again.
+    /// * `timeout` - How long to wait for the line.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the line is not entered yet: the prompt stays, and the next call waits for the
+    /// same line. Otherwise the line read, `None` at the end of the input.
+    pub fn read_command(&mut self, prompt: &str, color: bool, forget_last_command: bool, timeout: Duration) -> Option<Option<String>> {
+        if !self.waiting {
+            let request = LineRequest { prompt: prompt.to_string(), color, forget_last_command };
+            // The thread is gone: the end of the input.
+            if self.requests.send(request).is_err() {
+                return Some(None);
+            }
+            self.waiting = true;
+        }
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => {
+                self.waiting = false;
+                Some(line)
+            }
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                self.waiting = false;
+                Some(None)
+            }
+        }
+    }
+}
+
+/// Show a repeated command, dimmed on a terminal, so transcripts show what ran.
+fn echo(command: &str) {
+    if std::io::stdout().is_terminal() {
+        println!("\x1b[2m{}\x1b[0m", command);
+    } else {
+        println!("{}", command);
+    }
+}
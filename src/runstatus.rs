@@ -0,0 +1,92 @@
+use crate::arch::Arch;
+use crate::debugger::Debugger;
+use crate::logging;
+use crate::procfs::{self, ProcStat};
+use nix::libc;
+use nix::unistd::Pid;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often the thread showing the status checks whether the program has stopped.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Describe a state letter of `/proc/<pid>/stat`.
+pub fn describe_state(state: char) -> &'static str {
+    match state {
+        'R' => "running",
+        'S' => "sleeping",
+        'D' => "disk sleep",
+        'T' => "stopped",
+        't' => "tracing stop",
+        'Z' => "zombie",
+        'X' => "dead",
+        'I' => "idle",
+        _ => "unknown",
+    }
+}
+
+/// The line showing what the program does while it runs, as in
+/// `[running for 10s] S (sleeping) in read, wchan pipe_read, 0.00s of CPU in the last 5s`.
+///
+/// # Arguments
+///
+/// * `elapsed` - How long the program has been running.
+/// * `interval` - The time since the previous line.
+/// * `stat` - The state of the program.
+/// * `cpu` - The CPU time it used since the previous line.
+/// * `syscall` - The name of the system call it is blocked in, if any.
+/// * `wchan` - The kernel function it waits in, if known.
+pub fn status_line(elapsed: Duration, interval: Duration, stat: ProcStat, cpu: Duration, syscall: Option<&str>, wchan: Option<&str>) -> String {
+    let mut line = format!("[running for {}s] {} ({})", elapsed.as_secs(), stat.state, describe_state(stat.state));
+    if let Some(syscall) = syscall {
+        line.push_str(&format!(" in {}", syscall));
+    }
+    if let Some(wchan) = wchan {
+        line.push_str(&format!(", wchan {}", wchan));
+    }
+    line.push_str(&format!(", {:.2}s of CPU in the last {}s", cpu.as_secs_f64(), interval.as_secs()));
+    line
+}
+
+/// Print a status line every `interval` until `running` is cleared.
+fn show_status(running: &AtomicBool, pid: Pid, arch: Arch, interval: Duration) {
+    let start = Instant::now();
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+    let mut last = procfs::read_stat(pid).map_or(0, |stat| stat.cpu_ticks);
+    let mut next = start + interval;
+    while running.load(Ordering::Relaxed) {
+        if Instant::now() < next {
+            std::thread::sleep(POLL);
+            continue;
+        }
+        next += interval;
+        // Gone, or not readable.
+        let Ok(stat) = procfs::read_stat(pid) else {
+            continue;
+        };
+        let cpu = Duration::from_millis(stat.cpu_ticks.saturating_sub(last) * 1000 / ticks_per_second);
+        last = stat.cpu_ticks;
+        let syscall = procfs::read_syscall(pid).map(|number| arch.syscall_name(number));
+        let wchan = procfs::read_wchan(pid);
+        if running.load(Ordering::Relaxed) {
+            logging::print_line(&status_line(start.elapsed(), interval, stat, cpu, syscall, wchan.as_deref()), true);
+        }
+    }
+}
+
+/// Run `run`, which waits for the program to stop, showing every `set run-status interval` seconds
+/// what the program does meanwhile, from a thread reading `/proc/<pid>`. The lines go to the
+/// standard error, as the lines of the program are printed, whole, from their own thread.
+pub fn while_running<T>(debugger: &mut Debugger, run: impl FnOnce(&mut Debugger) -> T) -> T {
+    if debugger.run_status_interval == 0 {
+        return run(debugger);
+    }
+    let (pid, arch, interval) = (debugger.child, debugger.arch, Duration::from_secs(debugger.run_status_interval));
+    let running = AtomicBool::new(true);
+    std::thread::scope(|scope| {
+        scope.spawn(|| show_status(&running, pid, arch, interval));
+        let result = run(debugger);
+        running.store(false, Ordering::Relaxed);
+        result
+    })
+}
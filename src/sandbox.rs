@@ -0,0 +1,272 @@
+use crate::debugger::Debugger;
+use crate::json;
+use nix::sys::resource::{getrlimit, setrlimit, Resource, RLIM_INFINITY};
+use serde_json::json;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+
+/// The resources of `set rlimit`, named after their `RLIMIT_` constant.
+pub const RESOURCES: &[(&str, Resource)] = &[
+    ("AS", Resource::RLIMIT_AS),
+    ("CORE", Resource::RLIMIT_CORE),
+    ("CPU", Resource::RLIMIT_CPU),
+    ("DATA", Resource::RLIMIT_DATA),
+    ("FSIZE", Resource::RLIMIT_FSIZE),
+    ("NOFILE", Resource::RLIMIT_NOFILE),
+    ("NPROC", Resource::RLIMIT_NPROC),
+    ("STACK", Resource::RLIMIT_STACK),
+];
+
+/// A limit of `set rlimit`, set in the program before it executes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimit {
+    /// The name of the resource in [`RESOURCES`].
+    pub name: &'static str,
+    pub resource: Resource,
+    /// The soft limit, `RLIM_INFINITY` for unlimited.
+    pub soft: u64,
+    /// The hard limit, the one of the debugger when `None`.
+    pub hard: Option<u64>,
+}
+
+/// The environment of the program, changed by `set env`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Environment {
+    /// `set env --clear`: the program does not inherit the environment of the debugger.
+    pub clear: bool,
+    /// The variables of `set env`, added to the inherited ones or replacing them.
+    pub variables: Vec<(String, String)>,
+}
+
+impl Environment {
+    /// Add a variable, or replace the value of one already set.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.variables.retain(|(variable, _)| variable != name);
+        self.variables.push((name.to_string(), value.to_string()));
+    }
+
+    /// The `NAME=value` strings the program is executed with: the variables of the debugger
+    /// unless cleared, then the ones set.
+    pub fn entries(&self) -> Vec<CString> {
+        let mut entries = Vec::new();
+        if !self.clear {
+            for (name, value) in std::env::vars_os() {
+                if self.variables.iter().any(|(variable, _)| variable.as_bytes() == name.as_bytes()) {
+                    continue;
+                }
+                let entry = [name.as_bytes(), b"=", value.as_bytes()].concat();
+                entries.extend(CString::new(entry).ok());
+            }
+        }
+        // The names and values of set env are checked for NULs.
+        entries.extend(self.variables.iter().filter_map(|(name, value)| CString::new(format!("{}={}", name, value)).ok()));
+        entries
+    }
+
+    /// The environment as `show env` shows it, as in `--clear TERM=dumb`.
+    pub fn describe(&self) -> String {
+        let mut words: Vec<String> = self.variables.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        if self.clear {
+            words.insert(0, "--clear".to_string());
+        }
+        words.join(" ")
+    }
+}
+
+/// Change the environment of the program from the text of `set env`: `--clear` to start from an
+/// empty environment, `--inherit` to start from the one of the debugger again, or a variable
+/// `<name>=<value>`.
+///
+/// # Errors
+///
+/// Returns the usage for anything else, and an error for a name that is empty or has a space,
+/// or a NUL in the name or value.
+pub fn set_env(environment: &mut Environment, text: &str) -> Result<(), String> {
+    match text {
+        "--clear" => environment.clear = true,
+        "--inherit" => environment.clear = false,
+        _ => {
+            let Some((name, value)) = text.split_once('=') else {
+                return Err("Usage: set env <name>=<value>|--clear|--inherit".to_string());
+            };
+            if name.is_empty() || name.contains(char::is_whitespace) || name.contains('\0') {
+                return Err(format!("Invalid name of environment variable \"{}\".", name));
+            }
+            let value = crate::settings::unquote(value);
+            if value.contains('\0') {
+                return Err(format!("The value of {} contains a NUL.", name));
+            }
+            environment.set(name, value);
+        }
+    }
+    Ok(())
+}
+
+/// Find a resource by its name, as in `NOFILE`, `nofile` or `RLIMIT_NOFILE`.
+///
+/// # Errors
+///
+/// Returns an error listing the resources for an unknown one.
+pub fn find_resource(name: &str) -> Result<(&'static str, Resource), String> {
+    let upper = name.to_ascii_uppercase();
+    let upper = upper.strip_prefix("RLIMIT_").unwrap_or(&upper);
+    RESOURCES.iter().copied().find(|(resource, _)| *resource == upper).ok_or_else(|| {
+        let names: Vec<&str> = RESOURCES.iter().map(|(name, _)| *name).collect();
+        format!("Unknown resource \"{}\", set rlimit takes {}.", name, names.join(", "))
+    })
+}
+
+/// Parse an amount of a limit: `unlimited`, or a number with an optional `K`, `M` or `G` suffix
+/// multiplying it by 1024 for each.
+fn parse_amount(text: &str) -> Result<u64, String> {
+    if text == "unlimited" {
+        return Ok(RLIM_INFINITY);
+    }
+    let (digits, shift) = match text.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&text[..index], 10),
+        Some((index, 'm' | 'M')) => (&text[..index], 20),
+        Some((index, 'g' | 'G')) => (&text[..index], 30),
+        _ => (text, 0),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(1 << shift))
+        .filter(|number| *number != RLIM_INFINITY)
+        .ok_or_else(|| format!("Invalid limit \"{}\", give a number, with K, M or G after it, or unlimited.", text))
+}
+
+/// Format an amount of a limit, `unlimited` for `RLIM_INFINITY`.
+pub fn format_amount(amount: u64) -> String {
+    if amount == RLIM_INFINITY {
+        "unlimited".to_string()
+    } else {
+        amount.to_string()
+    }
+}
+
+/// Parse the limit of `set rlimit <resource> <soft>[:<hard>]`.
+///
+/// # Errors
+///
+/// Returns the error of [`find_resource`] for an unknown resource, and an error for an invalid
+/// amount or a soft limit above the hard one.
+pub fn parse_limit(name: &str, limit: &str) -> Result<ResourceLimit, String> {
+    let (name, resource) = find_resource(name)?;
+    let (soft, hard) = match limit.split_once(':') {
+        Some((soft, hard)) => (parse_amount(soft)?, Some(parse_amount(hard)?)),
+        None => (parse_amount(limit)?, None),
+    };
+    if let Some(hard) = hard.filter(|hard| soft > *hard) {
+        return Err(format!("The soft limit of {} ({}) is above its hard limit ({}).", name, format_amount(soft), format_amount(hard)));
+    }
+    Ok(ResourceLimit { name, resource, soft, hard })
+}
+
+/// Check that the program can be given a limit: its soft limit can't be above the hard one it
+/// inherits, only root raises a hard limit above the one of the debugger, and the files it opens
+/// are limited by the system.
+///
+/// # Errors
+///
+/// Returns why the limit can't be set.
+pub fn check_limit(limit: &ResourceLimit) -> Result<(), String> {
+    let (_, current) = getrlimit(limit.resource).map_err(|errno| format!("Could not read the limit of {}: {}", limit.name, errno))?;
+    let hard = limit.hard.unwrap_or(current);
+    if limit.soft > hard {
+        return Err(format!("The soft limit of {} ({}) is above the hard limit ({}).", limit.name, format_amount(limit.soft), format_amount(hard)));
+    }
+    if hard > current && unsafe { nix::libc::geteuid() } != 0 {
+        return Err(format!("Only root can raise the hard limit of {} above {}.", limit.name, format_amount(current)));
+    }
+    // Not even root opens more files than fs.nr_open.
+    let nr_open = std::fs::read_to_string("/proc/sys/fs/nr_open").ok().and_then(|text| text.trim().parse::<u64>().ok());
+    if let Some(nr_open) = nr_open.filter(|nr_open| limit.resource == Resource::RLIMIT_NOFILE && hard > *nr_open) {
+        return Err(format!("The limit of NOFILE can't be above {}, the fs.nr_open of the system.", nr_open));
+    }
+    Ok(())
+}
+
+/// Set the limits in the child, between fork and exec: only async-signal-safe calls.
+///
+/// # Errors
+///
+/// Returns the errno of a limit that could not be set.
+pub fn apply_limits(limits: &[ResourceLimit]) -> nix::Result<()> {
+    for limit in limits {
+        let hard = match limit.hard {
+            Some(hard) => hard,
+            None => getrlimit(limit.resource)?.1,
+        };
+        setrlimit(limit.resource, limit.soft, hard)?;
+    }
+    Ok(())
+}
+
+/// Set a limit of the program from the next run, `set rlimit <resource> <soft>[:<hard>]`, or
+/// give it back the one of the debugger with `set rlimit <resource>`.
+///
+/// # Errors
+///
+/// Returns the usage, or the errors of [`find_resource`], [`parse_limit`] and [`check_limit`].
+pub fn set_rlimit(debugger: &mut Debugger, text: &str) -> Result<(), String> {
+    match text.split_whitespace().collect::<Vec<_>>()[..] {
+        [name] => {
+            let (name, _) = find_resource(name)?;
+            debugger.rlimits.retain(|limit| limit.name != name);
+        }
+        [name, limit] => {
+            let limit = parse_limit(name, limit)?;
+            check_limit(&limit)?;
+            debugger.rlimits.retain(|set| set.name != limit.name);
+            debugger.rlimits.push(limit);
+            debugger.rlimits.sort_by_key(|limit| RESOURCES.iter().position(|(name, _)| *name == limit.name));
+        }
+        _ => return Err("Usage: set rlimit <resource> [<soft>[:<hard>]]".to_string()),
+    }
+    Ok(())
+}
+
+/// The limits of `set rlimit` as `show rlimit` shows them, as in `CORE 0, NOFILE 64:128`.
+pub fn describe_limits(limits: &[ResourceLimit]) -> String {
+    if limits.is_empty() {
+        return "none".to_string();
+    }
+    let limits: Vec<String> = limits
+        .iter()
+        .map(|limit| match limit.hard {
+            Some(hard) => format!("{} {}:{}", limit.name, format_amount(limit.soft), format_amount(hard)),
+            None => format!("{} {}", limit.name, format_amount(limit.soft)),
+        })
+        .collect();
+    limits.join(", ")
+}
+
+/// Show the limits the program gets from the next run (`show rlimits`): the ones of `set rlimit`,
+/// and the ones of the debugger it inherits for the other resources.
+pub fn show_rlimits(debugger: &mut Debugger) {
+    let mut rows = Vec::new();
+    for (name, resource) in RESOURCES {
+        let set = debugger.rlimits.iter().find(|limit| limit.name == *name);
+        let Ok((soft, hard)) = getrlimit(*resource) else {
+            continue;
+        };
+        let (soft, hard) = match set {
+            Some(limit) => (limit.soft, limit.hard.unwrap_or(hard)),
+            None => (soft, hard),
+        };
+        rows.push((*name, soft, hard, set.is_some()));
+    }
+    json::set_data(debugger, |_| {
+        let amount = |amount: u64| if amount == RLIM_INFINITY { json!(null) } else { json!(amount) };
+        let limits: Vec<_> =
+            rows.iter().map(|(name, soft, hard, set)| json!({ "resource": name, "soft": amount(*soft), "hard": amount(*hard), "set": set })).collect();
+        json!({ "rlimits": limits })
+    });
+    outln!(debugger, "Resource limits of the program from the next run:");
+    outln!(debugger, "{:<8} {:>20} {:>20}", "Resource", "Soft", "Hard");
+    for (name, soft, hard, set) in rows {
+        let origin = if set { "  (set rlimit)" } else { "" };
+        outln!(debugger, "{:<8} {:>20} {:>20}{}", name, format_amount(soft), format_amount(hard), origin);
+    }
+}
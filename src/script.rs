@@ -0,0 +1,226 @@
+use crate::commands::shell_command_line;
+use crate::debugger::Debugger;
+use crate::output::Style;
+use crate::repl::PROMPT;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+
+/// Scripts sourced from scripts are nested at most this deep, to stop a script sourcing itself.
+pub const MAX_SCRIPT_DEPTH: usize = 16;
+
+/// What to do when a command of a script fails (`set script-on-error`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScriptOnError {
+    /// Skip the rest of the script.
+    #[default]
+    Stop,
+    /// Run the next commands anyway.
+    Continue,
+}
+
+impl ScriptOnError {
+    /// Parse the name used by `set script-on-error`.
+    pub fn parse(name: &str) -> Option<ScriptOnError> {
+        match name {
+            "stop" => Some(ScriptOnError::Stop),
+            "continue" => Some(ScriptOnError::Continue),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the `.rustdbginit` of the current directory is run at startup (`set auto-load-local`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoLoadLocal {
+    /// Ask first, and don't run it when there is nobody to answer.
+    #[default]
+    Ask,
+    /// Run it.
+    On,
+    /// Never run it.
+    Off,
+}
+
+impl AutoLoadLocal {
+    /// Parse the name used by `set auto-load-local`.
+    pub fn parse(name: &str) -> Option<AutoLoadLocal> {
+        match name {
+            "ask" => Some(AutoLoadLocal::Ask),
+            "on" => Some(AutoLoadLocal::On),
+            "off" => Some(AutoLoadLocal::Off),
+            _ => None,
+        }
+    }
+
+    /// The name used by `set auto-load-local`.
+    pub fn name(self) -> &'static str {
+        match self {
+            AutoLoadLocal::Ask => "ask",
+            AutoLoadLocal::On => "on",
+            AutoLoadLocal::Off => "off",
+        }
+    }
+}
+
+/// The init file of the current directory, run after the one of the configuration directory.
+pub const LOCAL_INIT_FILE: &str = ".rustdbginit";
+
+/// The commands of a script with their line numbers, skipping blank lines and `#` comments.
+pub fn script_commands(text: &str) -> Vec<(usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+/// Split a command line into the commands separated by `;`, ignoring the `;` inside quotes
+/// (`"..."`, with `\"`, or `'...'`) and in the command line of `shell` and `!`. Empty commands are
+/// dropped.
+pub fn split_commands(text: &str) -> Vec<String> {
+    // The shell gets the rest of the line as typed, `;` included.
+    if shell_command_line(text).is_some() {
+        return vec![text.trim().to_string()];
+    }
+    let mut commands = Vec::new();
+    let mut command = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                command.push(c);
+                continue;
+            }
+            (Some(open), c) if c == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ';') => {
+                commands.push(std::mem::take(&mut command));
+                continue;
+            }
+            _ => {}
+        }
+        escaped = false;
+        command.push(c);
+    }
+    commands.push(command);
+    commands.into_iter().map(|command| command.trim().to_string()).filter(|command| !command.is_empty()).collect()
+}
+
+/// Print a command run from a script or the command line before its output, except in batch mode.
+pub fn echo_command(debugger: &mut Debugger, command: &str) {
+    if debugger.batch {
+        // Not shown, but the transcript still tells which command produced the output.
+        debugger.transcript.write_command(command);
+    } else {
+        let prompt = debugger.output.paint(PROMPT, Style::Prompt);
+        outln!(debugger, "{}{}", prompt, command);
+    }
+}
+
+/// Run the commands of a script file in order, printing each one before its output.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `path` - The script file.
+/// * `run_command` - Runs one command, returning false if it failed.
+///
+/// # Returns
+///
+/// False if the script could not be read or a command failed.
+pub fn source(debugger: &mut Debugger, path: &str, run_command: fn(&str, &mut Debugger) -> bool) -> bool {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            errln!(debugger, "Could not read {}: {}", path, err);
+            return false;
+        }
+    };
+    if debugger.script_depth >= MAX_SCRIPT_DEPTH {
+        errln!(debugger, "Scripts are nested too deeply, not running {}.", path);
+        return false;
+    }
+    debugger.script_depth += 1;
+    let mut succeeded = true;
+    for (line, command) in script_commands(&text) {
+        echo_command(debugger, command);
+        let ok = run_command(command, debugger);
+        if debugger.quit_requested {
+            break;
+        }
+        if ok {
+            continue;
+        }
+        succeeded = false;
+        if debugger.script_on_error == ScriptOnError::Stop {
+            errln!(debugger, "{}:{}: \"{}\" failed, stopping the script.", path, line, command);
+            break;
+        }
+    }
+    if let Some(definition) = debugger.definition.take() {
+        errln!(debugger, "{}: \"define {}\" has no \"end\", the command is not defined.", path, definition.name);
+        succeeded = false;
+    }
+    debugger.script_depth -= 1;
+    succeeded
+}
+
+/// Ask whether to run the `.rustdbginit` of the current directory, on the terminal.
+///
+/// # Returns
+///
+/// True if the answer is yes. False when the input is not a terminal, as nobody can answer.
+pub fn ask_load_local(path: &Path) -> bool {
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    print!("Run the commands of {}? (y or n) ", path.display());
+    std::io::stdout().flush().ok();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "yes")
+}
+
+/// Run the init files at startup, before the `-x` scripts: `init` in the configuration
+/// directory, then the `.rustdbginit` of the current directory, which may be a project
+/// downloaded from anywhere and so is only run as `set auto-load-local` allows.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `global` - The init file of the configuration directory, run when it exists.
+/// * `local` - The init file of the current directory.
+/// * `run_command` - Runs one command, returning false if it failed.
+/// * `ask` - Asks whether to run `local`, for `set auto-load-local ask`.
+///
+/// # Returns
+///
+/// False if an init file could not be read or one of its commands failed.
+pub fn source_init_files(
+    debugger: &mut Debugger,
+    global: Option<&Path>,
+    local: &Path,
+    run_command: fn(&str, &mut Debugger) -> bool,
+    ask: fn(&Path) -> bool,
+) -> bool {
+    let mut succeeded = true;
+    if let Some(global) = global.filter(|path| path.is_file()) {
+        succeeded &= source(debugger, &global.to_string_lossy(), run_command);
+    }
+    if debugger.state.has_terminated() || debugger.quit_requested || !local.is_file() {
+        return succeeded;
+    }
+    let load = match debugger.auto_load_local {
+        AutoLoadLocal::On => true,
+        AutoLoadLocal::Off => false,
+        AutoLoadLocal::Ask if debugger.batch => false,
+        AutoLoadLocal::Ask => ask(local),
+    };
+    if load {
+        succeeded &= source(debugger, &local.to_string_lossy(), run_command);
+    } else if debugger.auto_load_local == AutoLoadLocal::Ask {
+        warnln!(debugger, "Not running {}: \"set auto-load-local on\" in ~/.config/rustdbg/init allows it.", local.display());
+    }
+    succeeded
+}
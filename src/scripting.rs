@@ -0,0 +1,293 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::memory;
+use crate::step::{self, StepStop};
+use crate::tracee::Tracee;
+use crate::working::keep_breakpoints;
+use nix::libc::user_regs_struct;
+use rhai::{Blob, Dynamic, Engine, EvalAltResult, FnPtr, Map, AST, INT};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// A function of a script called at each stop of the program, registered with `on_stop`.
+#[derive(Clone)]
+struct StopHook {
+    function: FnPtr,
+    /// The script registering it, which defines the function.
+    ast: Rc<AST>,
+}
+
+/// The scripts of the session: the engine running them, and the stop hooks they registered.
+#[derive(Default)]
+pub struct Scripting {
+    /// Created with its bindings by the first script.
+    engine: Option<Rc<Engine>>,
+    /// The script running, for `on_stop` to keep.
+    ast: Option<Rc<AST>>,
+    hooks: Vec<StopHook>,
+    /// True while the stop hooks run, so that the stops they make do not run them again.
+    in_hooks: bool,
+}
+
+impl Scripting {
+    /// The engine running the scripts, created on first use.
+    fn engine(&mut self) -> Rc<Engine> {
+        self.engine.get_or_insert_with(|| Rc::new(new_engine())).clone()
+    }
+}
+
+thread_local! {
+    /// The session the bindings act on, while a script runs.
+    static DEBUGGER: Cell<*mut Debugger> = const { Cell::new(std::ptr::null_mut()) };
+}
+
+/// Run `f` with `debugger` as the session of the bindings, for the scripts it runs.
+fn with_session<T>(debugger: &mut Debugger, f: impl FnOnce() -> T) -> T {
+    /// Puts the session of the outer script back, even if `f` panics.
+    struct Restore(*mut Debugger);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DEBUGGER.set(self.0);
+        }
+    }
+    let _restore = Restore(DEBUGGER.replace(debugger));
+    f()
+}
+
+/// Call `f` with the session of the running script.
+fn session<T>(f: impl FnOnce(&mut Debugger) -> Result<T, DbgError>) -> Result<T, Box<EvalAltResult>> {
+    let debugger = DEBUGGER.get();
+    if debugger.is_null() {
+        return Err("no debugging session".into());
+    }
+    // SAFETY: set by `with_session` from a `&mut Debugger` that is not used while the script runs,
+    // and only on this thread.
+    f(unsafe { &mut *debugger }).map_err(|err| err.to_string().into())
+}
+
+/// The register called `name`, as `r` names them, with `pc`, `sp` and `fp` as other names of the
+/// program counter, the stack and the frame pointers (`rip`, `rsp` and `rbp` on x86_64).
+fn register<'a>(arch: Arch, regs: &'a mut user_regs_struct, name: &str) -> Result<&'a mut u64, DbgError> {
+    arch.register_mut(regs, name).ok_or_else(|| DbgError::InvalidArgument { what: "register", value: name.to_string() })
+}
+
+/// Returns [`DbgError::NotSupportedOnCore`] on a core file, which can't be changed.
+fn check_writable(debugger: &Debugger) -> Result<(), DbgError> {
+    match debugger.core {
+        Some(_) => Err(DbgError::NotSupportedOnCore),
+        None => Ok(()),
+    }
+}
+
+/// The event given to the stop hooks, and returned by `cont` and `stepi`: the `reason` of the stop
+/// (`breakpoint`, `watchpoint`, `signal`, `step`, `exited` or `killed`), the `pc` and `thread` while
+/// the program is alive, the `address` of the breakpoint or of the watched variable, the name of the
+/// `signal`, or the exit `code`.
+fn stop_event(debugger: &Debugger, stop: &StepStop) -> Map {
+    let mut event = Map::new();
+    let reason = match stop {
+        StepStop::Breakpoint(address) => {
+            event.insert("address".into(), (*address as INT).into());
+            "breakpoint"
+        }
+        StepStop::Watchpoint(address) => {
+            event.insert("address".into(), (*address as INT).into());
+            "watchpoint"
+        }
+        StepStop::Signal(signal) => {
+            event.insert("signal".into(), signal.as_str().into());
+            "signal"
+        }
+        StepStop::Exited(code) => {
+            event.insert("code".into(), (*code as INT).into());
+            "exited"
+        }
+        StepStop::Killed(signal) => {
+            event.insert("signal".into(), signal.as_str().into());
+            "killed"
+        }
+        StepStop::Done => "step",
+    };
+    event.insert("reason".into(), reason.into());
+    if let Ok(regs) = debugger.registers() {
+        event.insert("pc".into(), (regs.pc() as INT).into());
+        event.insert("thread".into(), (debugger.thread.as_raw() as INT).into());
+    }
+    event
+}
+
+/// Report a stop of the program resumed by a script as the commands do, and return its event.
+fn report(debugger: &mut Debugger, stop: Result<StepStop, DbgError>) -> Result<Map, DbgError> {
+    let stop = stop?;
+    step::report_stop(debugger, &stop);
+    Ok(stop_event(debugger, &stop))
+}
+
+/// Create the engine running the scripts, with the bindings over the session.
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| {
+        session(|debugger| {
+            outln!(debugger, "{}", text);
+            Ok(())
+        })
+        .ok();
+    });
+    engine.on_debug(|text, _, position| {
+        session(|debugger| {
+            outln!(debugger, "[{}] {}", position, text);
+            Ok(())
+        })
+        .ok();
+    });
+    engine.register_fn("reg", |name: &str| {
+        session(|debugger| {
+            let mut regs = debugger.registers()?;
+            Ok(*register(debugger.arch, &mut regs, name)? as INT)
+        })
+    });
+    engine.register_fn("set_reg", |name: &str, value: INT| {
+        session(|debugger| {
+            check_writable(debugger)?;
+            let mut regs = debugger.registers()?;
+            *register(debugger.arch, &mut regs, name)? = value as u64;
+            let mut thread = debugger.thread;
+            thread.setregs(regs).map_err(DbgError::ptrace("write the registers"))?;
+            debugger.shown_registers = None;
+            Ok(())
+        })
+    });
+    engine.register_fn("read_memory", |address: INT, len: INT| {
+        session(|debugger| debugger.read_memory(address as u64, len.max(0) as usize))
+    });
+    engine.register_fn("read_u64", |address: INT| {
+        session(|debugger| {
+            let bytes = debugger.read_memory(address as u64, 8)?;
+            Ok(u64::from_le_bytes(bytes.try_into().unwrap_or_default()) as INT)
+        })
+    });
+    engine.register_fn("write_memory", |address: INT, bytes: Blob| {
+        session(|debugger| {
+            check_writable(debugger)?;
+            let mut bytes = bytes;
+            keep_breakpoints(&mut debugger.breakpoint_table, address as u64, &mut bytes);
+            memory::write_bytes(debugger.thread, address as u64, &bytes)
+        })
+    });
+    engine.register_fn("write_u64", |address: INT, value: INT| {
+        session(|debugger| {
+            check_writable(debugger)?;
+            let mut bytes = value.to_le_bytes().to_vec();
+            keep_breakpoints(&mut debugger.breakpoint_table, address as u64, &mut bytes);
+            memory::write_bytes(debugger.thread, address as u64, &bytes)
+        })
+    });
+    engine.register_fn("resolve", |name: &str| {
+        session(|debugger| {
+            let address = debugger.resolve_symbol(name);
+            address.map(|address| address as INT).ok_or(DbgError::InvalidArgument { what: "symbol", value: name.to_string() })
+        })
+    });
+    engine.register_fn("breakpoint", |location: &str| session(|debugger| Ok(debugger.set_breakpoint(location)? as INT)));
+    engine.register_fn("cont", || {
+        session(|debugger| {
+            let stop = debugger.cont();
+            report(debugger, stop)
+        })
+    });
+    engine.register_fn("stepi", || {
+        session(|debugger| {
+            let stop = debugger.step();
+            report(debugger, stop)
+        })
+    });
+    engine.register_fn("command", |line: &str| {
+        session(|debugger| crate::dispatch(debugger, line).map(|outcome| outcome.output.text()))
+    });
+    engine.register_fn("on_stop", |function: FnPtr| {
+        session(|debugger| {
+            let Some(ast) = debugger.scripting.ast.clone() else {
+                return Err(DbgError::InvalidArgument { what: "stop hook", value: function.fn_name().to_string() });
+            };
+            debugger.scripting.hooks.push(StopHook { function, ast });
+            Ok(())
+        })
+    });
+    engine
+}
+
+/// Run a script.
+///
+/// # Returns
+///
+/// False if it does not compile or fails, the error being printed.
+fn run(debugger: &mut Debugger, source: &str) -> bool {
+    let engine = debugger.scripting.engine();
+    let ast = match engine.compile(source) {
+        Ok(ast) => Rc::new(ast),
+        Err(err) => {
+            errln!(debugger, "Script error: {}", err);
+            return false;
+        }
+    };
+    let outer = debugger.scripting.ast.replace(ast.clone());
+    let result = with_session(debugger, || engine.run_ast(&ast));
+    debugger.scripting.ast = outer;
+    if let Err(err) = result {
+        errln!(debugger, "Script error: {}", err);
+        return false;
+    }
+    true
+}
+
+/// Call the stop hooks registered by the scripts with the event of `stop`. A hook failing is
+/// reported, and the others still run. The stops made by the hooks do not run them again.
+pub fn run_stop_hooks(debugger: &mut Debugger, stop: &StepStop) {
+    if debugger.scripting.hooks.is_empty() || debugger.scripting.in_hooks {
+        return;
+    }
+    let engine = debugger.scripting.engine();
+    let hooks = debugger.scripting.hooks.clone();
+    let event = stop_event(debugger, stop);
+    debugger.scripting.in_hooks = true;
+    for hook in hooks {
+        let result = with_session(debugger, || hook.function.call::<Dynamic>(&engine, &hook.ast, (event.clone(),)));
+        if let Err(err) = result {
+            errln!(debugger, "Stop hook {} failed: {}", hook.function.fn_name(), err);
+        }
+    }
+    debugger.scripting.in_hooks = false;
+}
+
+/// Run the `script eval <code>`, `script file <path>` and `script clear` commands. The code given
+/// to `eval` is the rest of the line, without the quotes around it.
+///
+/// # Returns
+///
+/// False if the arguments are invalid, the file cannot be read, or the script fails.
+pub fn script_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match args {
+        ["eval", code @ ..] if !code.is_empty() => {
+            let code = code.join(" ");
+            let unquoted = ['\'', '"'].iter().find_map(|quote| code.strip_prefix(*quote)?.strip_suffix(*quote));
+            run(debugger, unquoted.unwrap_or(&code))
+        }
+        ["file", path] => match std::fs::read_to_string(path) {
+            Ok(source) => run(debugger, &source),
+            Err(err) => {
+                errln!(debugger, "Cannot read {}: {}", path, err);
+                false
+            }
+        },
+        ["clear"] => {
+            let count = std::mem::take(&mut debugger.scripting.hooks).len();
+            outln!(debugger, "Removed {} stop hooks.", count);
+            true
+        }
+        _ => {
+            errln!(debugger, "Usage: script eval '<code>' | script file <path> | script clear");
+            false
+        }
+    }
+}
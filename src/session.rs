@@ -0,0 +1,251 @@
+use crate::commands::{lookup_command, CommandMatch};
+use crate::debugger::Debugger;
+use crate::location;
+use crate::maps;
+use crate::script::{echo_command, ScriptOnError, MAX_SCRIPT_DEPTH};
+use crate::state::InferiorState;
+use crate::symbols::same_file;
+
+/// The commands that change nothing and are not recorded: the help, and the `session` command
+/// itself.
+const NOT_RECORDED: &[&str] = &["help", "apropos", "session"];
+
+/// A region of the memory an address given to a command was in when it was recorded, with the
+/// address it was loaded at: its lowest mapping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// The file mapped, or `[stack]`, `[heap]`...
+    pub name: String,
+    pub base: u64,
+}
+
+/// A command run during the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub command: String,
+    pub succeeded: bool,
+    /// The regions of the addresses in the command, checked before it is replayed.
+    pub regions: Vec<Region>,
+}
+
+/// The commands run since the debugger started, with what the program was started with, written
+/// by `session save` and run again by `session replay`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Session {
+    pub program: String,
+    pub args: Vec<String>,
+    /// `--no-aslr`: the addresses are the same from run to run.
+    pub no_aslr: bool,
+    pub entries: Vec<Entry>,
+}
+
+/// Returns true if a command entered is recorded: the commands typed or given with `-ex`, not the
+/// ones of scripts and user commands, which run again with the command running them.
+pub fn is_recorded(debugger: &Debugger, command: &str) -> bool {
+    if debugger.script_depth > 0 || debugger.user_command_depth > 0 {
+        return false;
+    }
+    let name = command.split_whitespace().next().unwrap_or_default().trim_end_matches('!');
+    match lookup_command(name) {
+        CommandMatch::Found(spec) => !NOT_RECORDED.contains(&spec.name()),
+        _ => true,
+    }
+}
+
+/// Find the regions of the numbers written in a command (`m 0x7ffd1234`, `b *0x401126+4`) that
+/// are addresses mapped by the program.
+pub fn regions(debugger: &Debugger, command: &str) -> Vec<Region> {
+    if !debugger.has_live_process() {
+        return Vec::new();
+    }
+    let Ok(entries) = maps::read_maps(debugger.child) else {
+        return Vec::new();
+    };
+    let mut regions: Vec<Region> = Vec::new();
+    let numbers = command
+        .split(|c: char| c.is_whitespace() || "+-*/,=()[]<>".contains(c))
+        .filter(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .filter_map(|word| location::parse_number(word, "address").ok());
+    for address in numbers {
+        let Some(name) = maps::find_mapping(&entries, address).and_then(|mapping| mapping.pathname.clone()) else {
+            continue;
+        };
+        if regions.iter().any(|region| region.name == name) {
+            continue;
+        }
+        if let Some(base) = region_base(&entries, &name) {
+            regions.push(Region { name, base });
+        }
+    }
+    regions
+}
+
+/// The lowest address of the mappings of a region.
+fn region_base(entries: &[maps::MapEntry], name: &str) -> Option<u64> {
+    entries.iter().filter(|entry| entry.pathname.as_deref() == Some(name)).map(|entry| entry.start).min()
+}
+
+/// Write a session as a command script, with its header and the regions and failures in `#`
+/// comments, so `source` runs it too.
+pub fn format(session: &Session) -> String {
+    let mut text = String::from("# rustdbg session, run it again with: session replay <file>\n");
+    text.push_str(&format!("# program {}\n", session.program));
+    text.push_str(&format!("# args {}\n", serde_json::to_string(&session.args).unwrap_or_default()));
+    text.push_str(&format!("# aslr {}\n", if session.no_aslr { "off" } else { "on" }));
+    for entry in &session.entries {
+        for region in &entry.regions {
+            text.push_str(&format!("# region {:#x} {}\n", region.base, region.name));
+        }
+        text.push_str(&entry.command);
+        text.push('\n');
+        if !entry.succeeded {
+            text.push_str("# failed\n");
+        }
+    }
+    text
+}
+
+/// Read a session written by `format`.
+///
+/// # Errors
+///
+/// Returns an error if the text has no `# program` line, or its arguments, ASLR setting or
+/// regions can't be read.
+pub fn parse(text: &str) -> Result<Session, String> {
+    let mut program = None;
+    let mut session = Session::default();
+    let mut regions = Vec::new();
+    for (number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+        let invalid = |what: &str| format!("Line {}: invalid {} \"{}\".", number, what, line);
+        if let Some(path) = line.strip_prefix("# program ") {
+            program = Some(path.to_string());
+        } else if let Some(args) = line.strip_prefix("# args ") {
+            session.args = serde_json::from_str(args).map_err(|_| invalid("arguments"))?;
+        } else if let Some(aslr) = line.strip_prefix("# aslr ") {
+            session.no_aslr = match aslr {
+                "on" => false,
+                "off" => true,
+                _ => return Err(invalid("ASLR setting")),
+            };
+        } else if let Some(region) = line.strip_prefix("# region ") {
+            let (base, name) = region.split_once(' ').ok_or_else(|| invalid("region"))?;
+            let base = location::parse_number(base, "address").map_err(|_| invalid("region"))?;
+            regions.push(Region { name: name.to_string(), base });
+        } else if line == "# failed" {
+            if let Some(entry) = session.entries.last_mut() {
+                entry.succeeded = false;
+            }
+        } else if !line.is_empty() && !line.starts_with('#') {
+            session.entries.push(Entry { command: line.to_string(), succeeded: true, regions: std::mem::take(&mut regions) });
+        }
+    }
+    session.program = program.ok_or("Not a session file, it has no \"# program\" line.")?;
+    Ok(session)
+}
+
+/// Save or replay the session, `session save <file>` or `session replay <file>`.
+pub fn session_command(debugger: &mut Debugger, args: &[&str]) -> bool {
+    match args {
+        ["save", path] => save(debugger, path),
+        ["replay", path] => replay(debugger, path),
+        _ => {
+            errln!(debugger, "Usage: session save|replay <file>");
+            false
+        }
+    }
+}
+
+fn save(debugger: &mut Debugger, path: &str) -> bool {
+    if let Err(err) = std::fs::write(path, format(&debugger.session)) {
+        errln!(debugger, "Could not write {}: {}", path, err);
+        return false;
+    }
+    outln!(debugger, "Saved {} commands to {}, run them again with session replay {}.", debugger.session.entries.len(), path, path);
+    true
+}
+
+/// Start the program again as it was started in the recorded session and run its commands,
+/// through the script engine like `source`.
+///
+/// Before a command with addresses, the regions they were in must be loaded at the same
+/// addresses, or the replay stops: with the address space randomization on, they move from run
+/// to run.
+///
+/// # Returns
+///
+/// False if the file can't be read, the replay stops, or a command that succeeded when recorded
+/// fails.
+fn replay(debugger: &mut Debugger, path: &str) -> bool {
+    let session = match std::fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err)).and_then(|text| parse(&text)) {
+        Ok(session) => session,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    if debugger.script_depth >= MAX_SCRIPT_DEPTH {
+        errln!(debugger, "Scripts are nested too deeply, not replaying {}.", path);
+        return false;
+    }
+    if !same_file(&session.program, &debugger.program_path) {
+        warnln!(debugger, "The session was recorded on {}, not {}: its addresses may not match.", session.program, debugger.program_path);
+    }
+    if session.args != debugger.program_args || session.no_aslr != debugger.no_aslr || debugger.state != InferiorState::NotStarted {
+        debugger.program_args = session.args.clone();
+        debugger.no_aslr = session.no_aslr;
+        echo_command(debugger, "restart!");
+        if !crate::run_command("restart!", debugger) {
+            return false;
+        }
+    }
+    debugger.script_depth += 1;
+    let mut succeeded = true;
+    let mut replayed = 0;
+    for entry in &session.entries {
+        if let Some(moved) = moved_region(debugger, &session, entry) {
+            warnln!(debugger, "Stopping the replay before \"{}\": {}", entry.command, moved);
+            succeeded = false;
+            break;
+        }
+        echo_command(debugger, &entry.command);
+        let ok = crate::run_command(&entry.command, debugger);
+        replayed += 1;
+        if debugger.quit_requested {
+            break;
+        }
+        if ok != entry.succeeded {
+            warnln!(debugger, "\"{}\" {} when the session was recorded.", entry.command, if entry.succeeded { "succeeded" } else { "failed" });
+        }
+        if !ok && entry.succeeded {
+            succeeded = false;
+            if debugger.script_on_error == ScriptOnError::Stop {
+                errln!(debugger, "Stopping the replay of {}.", path);
+                break;
+            }
+        }
+    }
+    debugger.script_depth -= 1;
+    outln!(debugger, "Replayed {} of the {} commands of {}.", replayed, session.entries.len(), path);
+    succeeded
+}
+
+/// Find a region of the addresses of a command that is not loaded where it was when recorded.
+///
+/// # Returns
+///
+/// Why the addresses don't match, `None` if they do or the session was recorded with the address
+/// space randomization off.
+fn moved_region(debugger: &Debugger, session: &Session, entry: &Entry) -> Option<String> {
+    if session.no_aslr || entry.regions.is_empty() {
+        return None;
+    }
+    let entries = if debugger.has_live_process() { maps::read_maps(debugger.child).unwrap_or_default() } else { Vec::new() };
+    entry.regions.iter().find_map(|region| match region_base(&entries, &region.name) {
+        Some(base) if base == region.base => None,
+        Some(base) => Some(format!(
+            "{} was loaded at {:#x} when it was recorded and is at {:#x} now. Record the session with --no-aslr to replay its addresses.",
+            region.name, region.base, base
+        )),
+        None => Some(format!("{} was loaded at {:#x} when it was recorded and is not loaded now.", region.name, region.base)),
+    })
+}
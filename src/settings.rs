@@ -0,0 +1,729 @@
+use crate::debugger::Debugger;
+use crate::disasm::DisassemblyFlavor;
+use crate::inferior::{self, InferiorTty};
+use crate::location;
+use crate::prompt::DEFAULT_PROMPT_TEMPLATE;
+use crate::script::{AutoLoadLocal, ScriptOnError};
+use crate::symbols::DEFAULT_DEBUG_FILE_DIRECTORY;
+use crate::transcript::DEFAULT_LOG_FILE;
+use std::path::PathBuf;
+
+/// The type of the value of a setting, which decides how `set` parses it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    /// `on` or `off`.
+    Bool,
+    /// One of the names listed.
+    Enum(&'static [&'static str]),
+    /// A number, at least `min`.
+    Int { min: u64 },
+    /// A number, at least `min`, or `off`, given to the setter as 0.
+    IntOrOff { min: u64 },
+    /// Any text, quoted to keep its leading or trailing spaces.
+    String,
+    /// One or more paths.
+    Path,
+}
+
+/// A value given to `set`, parsed for the kind of the setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(u64),
+    /// The name of an `Enum`, a `String` or a `Path`.
+    Text(String),
+}
+
+/// Reads the value of a setting, as shown by `show`.
+pub type SettingGetter = fn(&Debugger) -> String;
+/// Changes a setting to a value already validated for its kind.
+pub type SettingSetter = fn(&mut Debugger, SettingValue) -> Result<(), String>;
+
+/// A setting of the debugger, changed by `set` and shown by `show`.
+pub struct SettingSpec {
+    /// The name of the setting, several words for the settings of a group (`backtrace limit`).
+    pub name: &'static str,
+    /// The type of its value.
+    pub kind: SettingKind,
+    /// The value given to `set`, as in `on|off` or `<count>`.
+    pub arguments: &'static str,
+    /// The value before any `set`.
+    pub default: &'static str,
+    /// What the setting changes.
+    pub doc: &'static str,
+    pub get: SettingGetter,
+    pub set: SettingSetter,
+}
+
+impl SettingSpec {
+    /// `set <name> <arguments>`.
+    pub fn synopsis(&self) -> String {
+        format!("set {} {}", self.name, self.arguments)
+    }
+}
+
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+/// The settings of the debugger, sorted by name.
+pub const SETTINGS: &[SettingSpec] = &[
+    SettingSpec {
+        name: "anti-anti-debug",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "off",
+        doc: "Make ptrace(PTRACE_TRACEME), with which a program checks it is not debugged, return 0. c watches the system \
+              calls of the program for it, slower; syscall catches it either way.",
+        get: |debugger| on_off(debugger.anti_debug.enabled),
+        set: |debugger, value| {
+            if let SettingValue::Bool(enabled) = value {
+                debugger.anti_debug.enabled = enabled;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "args",
+        kind: SettingKind::String,
+        arguments: "[argument...] [< file]",
+        default: "",
+        doc: "The arguments of the program, and the file it reads as its standard input after <, from the next run or \
+              restart.",
+        get: |debugger| format!("\"{}\"", inferior::format_arguments(&debugger.program_args, debugger.stdin_file.as_deref())),
+        set: |debugger, value| {
+            let line = text(&value).unwrap_or_default();
+            let (arguments, stdin_file) = inferior::parse_arguments(&line.split_whitespace().collect::<Vec<_>>())?;
+            debugger.program_args = arguments;
+            debugger.stdin_file = stdin_file;
+            debugger.launch_changed = true;
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "auto-load-local",
+        kind: SettingKind::Enum(&["ask", "on", "off"]),
+        arguments: "ask|on|off",
+        default: "ask",
+        doc: "Whether the .rustdbginit of the current directory is run at startup, after asking first with ask. Only useful in the init file of the configuration directory or config.toml, which are read before it.",
+        get: |debugger| debugger.auto_load_local.name().to_string(),
+        set: |debugger, value| {
+            if let Some(mode) = text(&value).and_then(AutoLoadLocal::parse) {
+                debugger.auto_load_local = mode;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "backtrace inline-depth",
+        kind: SettingKind::Int { min: 0 },
+        arguments: "<count>",
+        default: "8",
+        doc: "How many inlined functions bt shows for each frame, 0 hides them.",
+        get: |debugger| debugger.inline_depth.to_string(),
+        set: |debugger, value| {
+            if let SettingValue::Int(count) = value {
+                debugger.inline_depth = count as usize;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "backtrace limit",
+        kind: SettingKind::Int { min: 1 },
+        arguments: "<count>",
+        default: "32",
+        doc: "The default number of frames shown by bt.",
+        get: |debugger| debugger.backtrace_limit.to_string(),
+        set: |debugger, value| {
+            if let SettingValue::Int(count) = value {
+                debugger.backtrace_limit = count as usize;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "build-command",
+        kind: SettingKind::String,
+        arguments: "<command line>",
+        default: "",
+        doc: "The command line run with the shell by rebuild, as in cargo build. Quote it to keep a ;.",
+        get: |debugger| format!("\"{}\"", debugger.build_command),
+        set: |debugger, value| {
+            if let SettingValue::Text(command) = value {
+                debugger.build_command = command;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "color",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "on",
+        doc: "Color addresses, registers, changed values, errors and the prompt on a terminal (off with --no-color or NO_COLOR).",
+        get: |debugger| on_off(debugger.output.color()),
+        set: |debugger, value| {
+            if let SettingValue::Bool(color) = value {
+                debugger.output.set_color(color);
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "confirm",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "on",
+        doc: "Ask before quit and restart kill the running program, define replaces a user command, rbreak sets more than 50 breakpoints and set mem writes to a read-only or executable mapping; no other command asks, and there are no kill, detach, jump or return commands yet. Batch mode never asks, and a ! after the name of a command (quit!) skips the question once.",
+        get: |debugger| on_off(debugger.confirm),
+        set: |debugger, value| {
+            if let SettingValue::Bool(confirm) = value {
+                debugger.confirm = confirm;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "cwd",
+        kind: SettingKind::String,
+        arguments: "[<directory>]",
+        default: "",
+        doc: "The directory the program starts in from the next run or restart, for the programs opening relative \
+              paths. Empty, the directory of the debugger.",
+        get: |debugger| format!("\"{}\"", debugger.cwd.as_deref().unwrap_or_default()),
+        set: |debugger, value| {
+            let directory = text(&value).unwrap_or_default();
+            if !directory.is_empty() && !std::path::Path::new(directory).is_dir() {
+                return Err(format!("{} is not a directory.", directory));
+            }
+            debugger.cwd = (!directory.is_empty()).then(|| directory.to_string());
+            debugger.launch_changed = true;
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "debug-file-directory",
+        kind: SettingKind::Path,
+        arguments: "<path>[:<path>...]",
+        default: DEFAULT_DEBUG_FILE_DIRECTORY,
+        doc: "The directories searched for separate debug files.",
+        get: |debugger| debugger.symbols.debug_file_directories.join(":"),
+        set: |debugger, value| {
+            if let SettingValue::Text(paths) = value {
+                let directories = paths.split(':').filter(|dir| !dir.is_empty()).map(str::to_string).collect();
+                debugger.set_debug_file_directories(directories);
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "disassembly-flavor",
+        kind: SettingKind::Enum(&["intel", "att"]),
+        arguments: "intel|att",
+        default: "intel",
+        doc: "The syntax of disassembled instructions.",
+        get: |debugger| match debugger.disassembly_flavor {
+            DisassemblyFlavor::Intel => "intel".to_string(),
+            DisassemblyFlavor::Att => "att".to_string(),
+        },
+        set: |debugger, value| {
+            if let Some(flavor) = text(&value).and_then(DisassemblyFlavor::parse) {
+                debugger.disassembly_flavor = flavor;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "env",
+        kind: SettingKind::String,
+        arguments: "<name>=<value>|--clear|--inherit",
+        default: "",
+        doc: "The environment of the program from the next run or restart: the one of the debugger with the \
+              variables set added, or only the variables set after --clear, as when the environment changes the \
+              behavior under test. --inherit starts from the one of the debugger again.",
+        get: |debugger| format!("\"{}\"", debugger.environment.describe()),
+        set: |debugger, value| {
+            crate::sandbox::set_env(&mut debugger.environment, text(&value).unwrap_or_default())?;
+            debugger.launch_changed = true;
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "inferior-tty",
+        kind: SettingKind::Path,
+        arguments: "capture|inherit|<path>",
+        default: "capture",
+        doc: "Where the standard output and error of the program go when it is started again by restart: capture prints \
+              their lines prefixed with [out] and [err], inherit shares the terminal of the debugger, and a path is \
+              another terminal, as with tty.",
+        get: |debugger| debugger.inferior_tty.name().to_string(),
+        set: |debugger, value| {
+            if let Some(tty) = text(&value) {
+                debugger.inferior_tty = InferiorTty::parse(tty);
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "log-level",
+        kind: SettingKind::Enum(crate::logging::LEVELS),
+        arguments: "off|error|warn|info|debug|trace",
+        default: "warn",
+        doc: "What the debugger logs to the standard error: debug logs the ptrace calls, wait statuses and breakpoints, trace the register and memory accesses too. -v sets debug, -vv trace.",
+        get: |_| crate::logging::level().to_string(),
+        set: |_, value| crate::logging::set_level(text(&value).unwrap_or_default()),
+    },
+    SettingSpec {
+        name: "logging",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "off",
+        doc: "Copy the commands and their output to the log file, with timestamps.",
+        get: |debugger| on_off(debugger.transcript.is_on()),
+        set: |debugger, value| match value {
+            SettingValue::Bool(true) => start_logging(debugger),
+            _ => {
+                if debugger.transcript.is_on() {
+                    debugger.transcript.stop();
+                    outln!(debugger, "Done logging to {}.", debugger.transcript.path);
+                }
+                Ok(())
+            }
+        },
+    },
+    SettingSpec {
+        name: "logging file",
+        kind: SettingKind::Path,
+        arguments: "<path>",
+        default: DEFAULT_LOG_FILE,
+        doc: "The file the log is appended to.",
+        get: |debugger| debugger.transcript.path.clone(),
+        set: |debugger, value| {
+            let logging = debugger.transcript.is_on();
+            debugger.transcript.stop();
+            if let SettingValue::Text(path) = value {
+                debugger.transcript.path = path;
+            }
+            if logging {
+                return start_logging(debugger);
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "non-stop",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "off",
+        doc: "Stop only the thread that hits a breakpoint or gets a signal, the others keep running; c continues the current thread only.",
+        get: |debugger| on_off(debugger.non_stop),
+        set: |debugger, value| {
+            if let SettingValue::Bool(non_stop) = value {
+                // The threads left running stop with the program again.
+                if !non_stop && crate::threads::any_running(debugger) {
+                    crate::threads::stop_others(debugger).map_err(|err| err.to_string())?;
+                }
+                debugger.non_stop = non_stop;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "pagination",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "on",
+        doc: "Stop long output at each screenful with a --More-- prompt, on a terminal.",
+        get: |debugger| on_off(debugger.output.pagination()),
+        set: |debugger, value| {
+            if let SettingValue::Bool(pagination) = value {
+                debugger.output.set_pagination(pagination);
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "prompt",
+        kind: SettingKind::String,
+        arguments: "<template>",
+        default: DEFAULT_PROMPT_TEMPLATE,
+        doc: "The prompt, with {pid}, {state}, {pc}, {symbol} and {status} replaced. Quote it to keep its spaces, \
+              an empty template restores the default.",
+        get: |debugger| format!("\"{}\"", debugger.prompt),
+        set: |debugger, value| {
+            debugger.prompt = match value {
+                SettingValue::Text(template) if !template.is_empty() => template,
+                _ => DEFAULT_PROMPT_TEMPLATE.to_string(),
+            };
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "rlimit",
+        kind: SettingKind::String,
+        arguments: "<resource> [<soft>[:<hard>]]",
+        default: "",
+        doc: "Limit a resource of the program from the next run or restart, set before it executes: AS, CORE, CPU, \
+              DATA, FSIZE, NOFILE, NPROC or STACK, with K, M or G after the number or unlimited. Without a hard \
+              limit, the one of the debugger is kept, and without a limit the resource is not limited again. show \
+              rlimits shows the limits of every resource.",
+        get: |debugger| crate::sandbox::describe_limits(&debugger.rlimits),
+        set: |debugger, value| {
+            crate::sandbox::set_rlimit(debugger, text(&value).unwrap_or_default())?;
+            debugger.launch_changed = true;
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "run-status interval",
+        kind: SettingKind::IntOrOff { min: 1 },
+        arguments: "<seconds>|off",
+        default: "off",
+        doc: "While c waits for the program, print a line every that many seconds with the state of the program, the CPU \
+              time it used meanwhile and where it waits: the system call it is in and the kernel function (wchan).",
+        get: |debugger| match debugger.run_status_interval {
+            0 => "off".to_string(),
+            seconds => seconds.to_string(),
+        },
+        set: |debugger, value| {
+            if let SettingValue::Int(seconds) = value {
+                debugger.run_status_interval = seconds;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "script-on-error",
+        kind: SettingKind::Enum(&["stop", "continue"]),
+        arguments: "stop|continue",
+        default: "stop",
+        doc: "Whether a failed command stops the script running it.",
+        get: |debugger| match debugger.script_on_error {
+            ScriptOnError::Stop => "stop".to_string(),
+            ScriptOnError::Continue => "continue".to_string(),
+        },
+        set: |debugger, value| {
+            if let Some(mode) = text(&value).and_then(ScriptOnError::parse) {
+                debugger.script_on_error = mode;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "show-instruction",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "off",
+        doc: "Also show the instruction at the pc when a breakpoint is hit.",
+        get: |debugger| on_off(debugger.show_instruction),
+        set: |debugger, value| {
+            if let SettingValue::Bool(show) = value {
+                debugger.show_instruction = show;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "snapshot-interval",
+        kind: SettingKind::Int { min: 0 },
+        arguments: "<count>",
+        default: "0",
+        doc: "Take a snapshot of the program every that many stops, for reverse-continue and reverse-stepi. 0 takes none, each snapshot copies the writable memory of the program.",
+        get: |debugger| debugger.snapshots.interval.to_string(),
+        set: |debugger, value| {
+            if let SettingValue::Int(count) = value {
+                debugger.snapshots.interval = count;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "stop-summary",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "on",
+        doc: "Print the line of where at each stop: the pc, its function, source line and mapping, why the program stopped and the thread.",
+        get: |debugger| on_off(debugger.stop_summary),
+        set: |debugger, value| {
+            if let SettingValue::Bool(show) = value {
+                debugger.stop_summary = show;
+            }
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "substitute-path",
+        kind: SettingKind::Path,
+        arguments: "<from> <to>",
+        default: "",
+        doc: "Look for source files under <to> instead of <from>. Each rule is added to the others.",
+        get: |debugger| {
+            let rules: Vec<String> =
+                debugger.substitute_paths.iter().map(|(from, to)| format!("{} -> {}", from, to)).collect();
+            if rules.is_empty() {
+                "none".to_string()
+            } else {
+                rules.join(", ")
+            }
+        },
+        set: |debugger, value| {
+            let text = text(&value).unwrap_or_default();
+            let [from, to] = text.split_whitespace().collect::<Vec<_>>()[..] else {
+                return Err("Usage: set substitute-path <from> <to>".to_string());
+            };
+            debugger.substitute_paths.retain(|(rule, _)| rule != from);
+            debugger.substitute_paths.push((from.to_string(), to.to_string()));
+            Ok(())
+        },
+    },
+    SettingSpec {
+        name: "track-fds",
+        kind: SettingKind::Bool,
+        arguments: "on|off",
+        default: "off",
+        doc: "Compare the open file descriptors of the program at each stop with the ones at the previous stop, and \
+              print the ones opened and closed meanwhile under the banner of the stop.",
+        get: |debugger| on_off(debugger.fd_tracking.enabled),
+        set: |debugger, value| {
+            if let SettingValue::Bool(enabled) = value {
+                crate::procfs::track_fds(debugger, enabled);
+            }
+            Ok(())
+        },
+    },
+];
+
+fn text(value: &SettingValue) -> Option<&str> {
+    match value {
+        SettingValue::Text(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Start copying the commands and their output to the log file.
+fn start_logging(debugger: &mut Debugger) -> Result<(), String> {
+    let pid = debugger.child.as_raw();
+    if let Err(err) = debugger.transcript.start(&debugger.program_path, pid) {
+        return Err(format!("Could not log to {}: {}", debugger.transcript.path, err));
+    }
+    outln!(debugger, "Copying output to {}.", debugger.transcript.path);
+    Ok(())
+}
+
+/// Find the setting named by the first words of `words`, the longest name first so
+/// `logging file` is not taken for `logging`.
+///
+/// # Returns
+///
+/// The setting and the number of words of its name.
+pub fn find_setting(words: &[&str]) -> Option<(&'static SettingSpec, usize)> {
+    SETTINGS
+        .iter()
+        .map(|spec| (spec, spec.name.split(' ').count()))
+        .filter(|(spec, count)| words.len() >= *count && spec.name.split(' ').eq(words[..*count].iter().copied()))
+        .max_by_key(|(_, count)| *count)
+}
+
+/// Remove the double quotes around `text`, if any.
+pub fn unquote(text: &str) -> &str {
+    text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')).unwrap_or(text)
+}
+
+/// Parse the words given to `set` after the name of a setting.
+///
+/// # Errors
+///
+/// Returns a message with the accepted values if the words don't fit the kind of the setting.
+pub fn parse_value(spec: &SettingSpec, words: &[&str]) -> Result<SettingValue, String> {
+    let usage = || format!("Usage: {}", spec.synopsis());
+    match spec.kind {
+        SettingKind::Bool => match words {
+            ["on" | "true"] => Ok(SettingValue::Bool(true)),
+            ["off" | "false"] => Ok(SettingValue::Bool(false)),
+            _ => Err(usage()),
+        },
+        SettingKind::Enum(names) => match words {
+            [name] if names.contains(name) => Ok(SettingValue::Text(name.to_string())),
+            _ => Err(usage()),
+        },
+        SettingKind::IntOrOff { .. } if words == ["off"] => Ok(SettingValue::Int(0)),
+        SettingKind::Int { min } | SettingKind::IntOrOff { min } => match words {
+            [number] => match location::parse_number(number, "number") {
+                Ok(number) if number >= min => Ok(SettingValue::Int(number)),
+                Ok(_) => Err(format!("The {} must be at least {}.", spec.name, min)),
+                Err(err) => Err(err.to_string()),
+            },
+            _ => Err(usage()),
+        },
+        SettingKind::String => Ok(SettingValue::Text(unquote(&words.join(" ")).to_string())),
+        SettingKind::Path if words.is_empty() => Err(usage()),
+        SettingKind::Path => Ok(SettingValue::Text(unquote(&words.join(" ")).to_string())),
+    }
+}
+
+/// Change a setting from the words following `set`.
+///
+/// # Errors
+///
+/// Returns a message if there is no such setting, or the value is invalid.
+pub fn apply(debugger: &mut Debugger, words: &[&str]) -> Result<(), String> {
+    let Some((spec, count)) = find_setting(words) else {
+        // The name has two words when the first one starts a longer name (`backtrace limit`).
+        let is_group = |word: &str| SETTINGS.iter().any(|spec| spec.name.starts_with(&format!("{} ", word)));
+        let length = if words.len() > 1 && is_group(words[0]) { 2 } else { 1 };
+        return Err(unknown_setting(&words[..length.min(words.len())].join(" ")));
+    };
+    let value = parse_value(spec, &words[count..])?;
+    (spec.set)(debugger, value)
+}
+
+/// The message for an unknown setting, with the close names.
+pub fn unknown_setting(name: &str) -> String {
+    let close = crate::commands::suggestions(name, SETTINGS.iter().map(|spec| spec.name));
+    if close.is_empty() {
+        format!("No setting named \"{}\", see show.", name)
+    } else {
+        format!("No setting named \"{}\". Did you mean: {}?", name, close.join(", "))
+    }
+}
+
+/// The directory of the configuration files, `~/.config/rustdbg` (under `$XDG_CONFIG_HOME` when set).
+pub fn config_directory() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME").filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("rustdbg"))
+}
+
+/// Split `text` at the `separator`s outside of quotes.
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), c) if c == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if c == separator => {
+                parts.push(&text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Parse one TOML value: a string, a boolean, a number or an array of them.
+fn parse_toml_value(text: &str) -> Result<Vec<String>, String> {
+    let text = text.trim();
+    if let Some(items) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        let mut words = Vec::new();
+        for item in split_outside_quotes(items, ',').into_iter().filter(|item| !item.trim().is_empty()) {
+            words.extend(parse_toml_value(item)?);
+        }
+        return Ok(words);
+    }
+    if let Some(string) = text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        let mut value = String::new();
+        let mut chars = string.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(escaped) => value.push(escaped),
+                None => return Err("Unterminated escape".to_string()),
+            }
+        }
+        return Ok(vec![value]);
+    }
+    if let Some(string) = text.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')) {
+        return Ok(vec![string.to_string()]);
+    }
+    match text {
+        "true" => Ok(vec!["on".to_string()]),
+        "false" => Ok(vec!["off".to_string()]),
+        number if !number.is_empty() && number.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            Ok(vec![number.replace('_', "")])
+        }
+        _ => Err(format!("Invalid value {}", text)),
+    }
+}
+
+/// A setting of a configuration file: its line number, its name and the words of its value.
+pub type ConfigSetting = (usize, String, Vec<String>);
+
+/// Parse the settings of a configuration file, a small subset of TOML: `name = value` lines,
+/// where the value is a string, a boolean, a number or an array, and `[group]` tables for the
+/// settings of a group. `backtrace.limit = 8`, `"backtrace limit" = 8` and `limit = 8` in a
+/// `[backtrace]` table all change `backtrace limit`.
+///
+/// # Errors
+///
+/// Returns the line number and a message for a malformed line.
+pub fn parse_config(text: &str) -> Result<Vec<ConfigSetting>, (usize, String)> {
+    let mut settings = Vec::new();
+    let mut table = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = split_outside_quotes(line, '#')[0].trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            table = name.trim().replace('.', " ");
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err((number, format!("Expected name = value, found \"{}\"", line)));
+        };
+        let key = key.trim();
+        let key = match key.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+            Some(quoted) => quoted.to_string(),
+            None => key.replace('.', " "),
+        };
+        let name = if table.is_empty() { key } else { format!("{} {}", table, key) };
+        let words = parse_toml_value(value).map_err(|err| (number, err))?;
+        settings.push((number, name, words));
+    }
+    Ok(settings)
+}
+
+/// Apply the settings of `config.toml` in the configuration directory, if it exists. The
+/// invalid settings are reported and skipped.
+pub fn load_config(debugger: &mut Debugger) {
+    let Some(path) = config_directory().map(|directory| directory.join("config.toml")) else {
+        return;
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return;
+    };
+    let settings = match parse_config(&text) {
+        Ok(settings) => settings,
+        Err((line, err)) => {
+            errln!(debugger, "{}:{}: {}", path.display(), line, err);
+            return;
+        }
+    };
+    for (line, name, words) in settings {
+        let mut args: Vec<&str> = name.split(' ').collect();
+        // A string is one word, even with spaces.
+        args.extend(words.iter().map(String::as_str));
+        if let Err(err) = apply(debugger, &args) {
+            errln!(debugger, "{}:{}: {}", path.display(), line, err);
+        }
+    }
+}
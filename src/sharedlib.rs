@@ -0,0 +1,315 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::maps::{self, MapEntry, MappedFile};
+use crate::memory::{read_bytes, read_c_string};
+use crate::procfs::{self, AT_BASE, AT_ENTRY, AT_SYSINFO_EHDR};
+use crate::state::InferiorState;
+use crate::symbols::{load_bias, same_file, ObjectSymbols, SymbolError};
+use crate::tracee::Tracee;
+use serde_json::json;
+
+const DT_NULL: u64 = 0;
+const DT_DEBUG: u64 = 21;
+
+/// The name of the vDSO, in the mappings and the shared libraries.
+pub const VDSO: &str = "[vdso]";
+
+/// A shared object loaded in the debugged process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedLibrary {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    /// Load bias, from the link_map when available, otherwise guessed from the mappings.
+    pub bias: Option<u64>,
+}
+
+/// An entry of the dynamic linker's `link_map` chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkMapEntry {
+    pub name: String,
+    pub bias: u64,
+}
+
+/// Find the `r_debug` structure of the dynamic linker through the `DT_DEBUG` entry of the executable.
+///
+/// # Arguments
+///
+/// * `tracee` - The process being debugged, or the core file being read.
+/// * `arch` - The architecture of the program, whose pointers size the entries of `_DYNAMIC`.
+/// * `executable` - The symbols of the main executable, with a known load bias.
+///
+/// # Returns
+///
+/// The address of `r_debug`, or `None` if the dynamic linker hasn't filled it in yet.
+pub fn r_debug_address(tracee: &dyn Tracee, arch: Arch, executable: &ObjectSymbols) -> Option<u64> {
+    dynamic_debug(tracee, arch, executable.dynamic_address?.wrapping_add(executable.bias?))
+}
+
+/// The value of the `DT_DEBUG` entry of the `_DYNAMIC` array at `dynamic`, whose entries are a tag
+/// and a value of the size of a pointer each.
+pub(crate) fn dynamic_debug(tracee: &dyn Tracee, arch: Arch, dynamic: u64) -> Option<u64> {
+    let mut entry = dynamic;
+    loop {
+        match arch.read_pointer(tracee, entry).ok()? {
+            DT_NULL => return None,
+            DT_DEBUG => {
+                let value = arch.read_pointer(tracee, entry + arch.pointer_size()).ok()?;
+                return if value == 0 { None } else { Some(value) };
+            }
+            _ => entry += 2 * arch.pointer_size(),
+        }
+    }
+}
+
+/// Walk the `link_map` chain starting at `r_debug.r_map`.
+///
+/// # Arguments
+///
+/// * `tracee` - The process being debugged, or the core file being read.
+/// * `arch` - The architecture of the program, whose pointers size the fields of the structures.
+/// * `r_debug` - The address of the dynamic linker's `r_debug` structure.
+pub fn read_link_map(tracee: &dyn Tracee, arch: Arch, r_debug: u64) -> Vec<LinkMapEntry> {
+    let pointer = arch.pointer_size();
+    let mut entries = Vec::new();
+    // r_map follows r_version, an int padded to the size of a pointer.
+    let mut current = arch.read_pointer(tracee, r_debug + pointer).unwrap_or(0);
+    // Guard against a corrupted chain looping forever.
+    while current != 0 && entries.len() < 4096 {
+        // l_addr, l_name, l_ld, l_next: pointers.
+        let (Ok(l_addr), Ok(l_name), Ok(l_next)) = (
+            arch.read_pointer(tracee, current),
+            arch.read_pointer(tracee, current + pointer),
+            arch.read_pointer(tracee, current + 3 * pointer),
+        ) else {
+            break;
+        };
+        let name = if l_name == 0 { String::new() } else { read_c_string(tracee, l_name, 4096).unwrap_or_default() };
+        entries.push(LinkMapEntry { name, bias: l_addr });
+        current = l_next;
+    }
+    entries
+}
+
+/// List the shared objects mapped into the debugged process.
+///
+/// Libraries are found by walking the mappings of the process. When the dynamic linker's
+/// `link_map` chain is readable, its load addresses take precedence over the ones guessed from the
+/// mappings, and libraries are returned in the dynamic linker's load order (the order used to
+/// resolve symbols).
+///
+/// # Arguments
+///
+/// * `tracee` - The process being debugged, or the core file being read.
+/// * `arch` - The architecture of the program.
+/// * `mappings` - The memory mappings of the process.
+/// * `executable` - The symbols of the main executable, excluded from the listing.
+pub fn list_shared_libraries(tracee: &dyn Tracee, arch: Arch, mappings: &[MapEntry], executable: Option<&ObjectSymbols>) -> Vec<SharedLibrary> {
+    let files = maps::mapped_files(mappings);
+    let link_map = executable
+        .and_then(|exe| r_debug_address(tracee, arch, exe))
+        .map(|r_debug| read_link_map(tracee, arch, r_debug))
+        .unwrap_or_default();
+
+    let mut libraries: Vec<(usize, SharedLibrary)> = files
+        .into_iter()
+        .filter(|file| !executable.is_some_and(|exe| same_file(&exe.path, &file.path)))
+        .map(|file: MappedFile| {
+            let position = link_map
+                .iter()
+                .position(|entry| !entry.name.is_empty() && same_file(&entry.name, &file.path));
+            let bias = position
+                .map(|index| link_map[index].bias)
+                .or_else(|| load_bias(&file.path, file.base?));
+            let library = SharedLibrary { path: file.path, start: file.start, end: file.end, bias };
+            (position.unwrap_or(usize::MAX), library)
+        })
+        .collect();
+    libraries.sort_by_key(|(position, _)| *position);
+    libraries.into_iter().map(|(_, library)| library).collect()
+}
+
+/// Find the vDSO, the shared object the kernel maps in every process for the system calls made
+/// without entering it (`clock_gettime`, `gettimeofday`...): the mapping at `AT_SYSINFO_EHDR`,
+/// or the `[vdso]` mapping when the auxiliary vector can't be read.
+///
+/// # Arguments
+///
+/// * `auxv` - The auxiliary vector of the process.
+/// * `mappings` - The memory mappings of the process.
+///
+/// # Returns
+///
+/// The start and end addresses of the vDSO.
+pub fn find_vdso(auxv: &[(u64, u64)], mappings: &[MapEntry]) -> Option<(u64, u64)> {
+    let mapping = match auxv.iter().find(|(kind, _)| *kind == AT_SYSINFO_EHDR) {
+        Some((_, header)) => maps::find_mapping(mappings, *header)?,
+        None => mappings.iter().find(|mapping| mapping.pathname.as_deref() == Some(VDSO))?,
+    };
+    Some((mapping.start, mapping.end))
+}
+
+/// Returns true if the object at `path` is read from the memory of the program rather than from
+/// a file, as the vDSO.
+pub fn in_memory(path: &str) -> bool {
+    path.starts_with('[')
+}
+
+/// Read the symbols of an ELF object mapped in the memory of the program, from `start` to `end`,
+/// as the vDSO, which is in no file, or another anonymous executable mapping holding a whole ELF
+/// image. Its load bias is found from `start`, where its ELF header is.
+///
+/// # Errors
+///
+/// Returns an error if the range can't be read, or doesn't hold an ELF object.
+pub fn read_memory_object(tracee: &dyn Tracee, name: &str, start: u64, end: u64) -> Result<ObjectSymbols, SymbolError> {
+    let data = read_bytes(tracee, start, end.saturating_sub(start) as usize).map_err(|source| SymbolError::Memory { path: name.to_string(), source })?;
+    let mut object = ObjectSymbols::parse(name, &data)?;
+    object.bias = Some(object.bias_from_base(start));
+    Ok(object)
+}
+
+/// How far the dynamic loader is in starting the program, shown by `info dl`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoaderState {
+    /// The interpreter requested by the executable, `None` for a statically linked program.
+    pub interpreter: Option<String>,
+    /// Where the interpreter is loaded (`AT_BASE`), `None` without one.
+    pub base: Option<u64>,
+    /// The entry point of the program (`AT_ENTRY`).
+    pub entry: Option<u64>,
+    /// The `r_debug` structure, once the dynamic loader has filled in `DT_DEBUG`.
+    pub r_debug: Option<u64>,
+    /// The pc of the current thread.
+    pub pc: u64,
+    /// True if the pc is in the interpreter, the mapping containing `AT_BASE`.
+    pub in_loader: bool,
+}
+
+impl LoaderState {
+    /// Returns true if the program is stopped in the dynamic loader before it relocated the
+    /// program, as at the first instruction after exec: the libraries are not loaded yet.
+    pub fn before_relocation(&self) -> bool {
+        self.in_loader && self.r_debug.is_none()
+    }
+}
+
+/// Read how far the dynamic loader is, from the auxiliary vector, the mappings and the
+/// `DT_DEBUG` entry of the executable.
+///
+/// # Returns
+///
+/// `None` for a core file, or if the auxiliary vector or the registers can't be read.
+pub fn loader_state(debugger: &mut Debugger) -> Option<LoaderState> {
+    if debugger.core.is_some() || !debugger.state.is_stopped() {
+        return None;
+    }
+    let auxv = procfs::read_auxv(debugger.child, debugger.arch.pointer_size() as usize).ok()?;
+    let value = |kind: u64| auxv.iter().find(|(found, _)| *found == kind).map(|(_, value)| *value);
+    let base = value(AT_BASE).filter(|base| *base != 0);
+    let pc = debugger.tracee().getregs().ok()?.pc();
+    let mapping = |address: u64| maps::find_mapping(&debugger.mappings, address).and_then(|entry| entry.pathname.clone());
+    let in_loader = base.and_then(mapping).is_some_and(|interpreter| mapping(pc) == Some(interpreter));
+    let executable = debugger.executable().cloned();
+    let arch = debugger.arch;
+    let tracee = debugger.tracee();
+    Some(LoaderState {
+        interpreter: executable.as_ref().and_then(|executable| executable.header.interpreter.clone()),
+        base,
+        entry: value(AT_ENTRY),
+        r_debug: executable.as_ref().and_then(|executable| r_debug_address(&*tracee, arch, executable)),
+        pc,
+        in_loader,
+    })
+}
+
+/// The hint printed at the stops before the dynamic loader relocated the program, where the
+/// symbols of the libraries are not known yet.
+///
+/// # Returns
+///
+/// `None` if the program is not stopped in the dynamic loader, or is past the relocation.
+pub fn loader_hint(debugger: &mut Debugger) -> Option<String> {
+    if !loader_state(debugger)?.before_relocation() {
+        return None;
+    }
+    let run_to_main = if debugger.state == InferiorState::NotStarted { "'start'" } else { "'b main' then 'c'" };
+    Some(format!("Stopped in the dynamic loader before relocation; use {} to run to main.", run_to_main))
+}
+
+/// Show the state of the dynamic loader (`info dl`): the interpreter, `AT_BASE` and `AT_ENTRY`,
+/// whether `r_debug` is initialized and the libraries it lists.
+///
+/// # Returns
+///
+/// False if the program is not running, or is read from a core file.
+pub fn info_dl(debugger: &mut Debugger) -> bool {
+    if debugger.core.is_some() {
+        errln!(debugger, "{}", DbgError::NotSupportedOnCore);
+        return false;
+    }
+    if !debugger.state.is_alive() {
+        errln!(debugger, "The program is not being run.");
+        return false;
+    }
+    let Some(state) = loader_state(debugger) else {
+        errln!(debugger, "Could not read the auxiliary vector and the registers of the program.");
+        return false;
+    };
+    let arch = debugger.arch;
+    let tracee = debugger.tracee();
+    // The fields of r_debug after r_version: r_map, r_brk, r_state.
+    let r_state = state
+        .r_debug
+        .and_then(|r_debug| arch.read_pointer(&*tracee, r_debug + 3 * arch.pointer_size()).ok())
+        .map(|r_state| r_state as u32);
+    let objects = state.r_debug.map(|r_debug| read_link_map(&*tracee, arch, r_debug).len());
+    match &state.interpreter {
+        Some(interpreter) => outln!(debugger, "Interpreter: {}", interpreter),
+        None => outln!(debugger, "Interpreter: none, the program is statically linked"),
+    }
+    match state.base {
+        Some(base) => outln!(debugger, "AT_BASE: {:#x}, where the interpreter is loaded", base),
+        None => outln!(debugger, "AT_BASE: 0, no interpreter"),
+    }
+    if let Some(entry) = state.entry {
+        outln!(debugger, "AT_ENTRY: {:#x}, the entry point of the program", entry);
+    }
+    match (state.r_debug, r_state, objects) {
+        (Some(r_debug), Some(r_state), Some(objects)) => {
+            let r_state = match r_state {
+                0 => "consistent",
+                1 => "adding an object",
+                2 => "deleting an object",
+                _ => "unknown state",
+            };
+            outln!(debugger, "r_debug: {:#x}, {}, {} objects in the link map", r_debug, r_state, objects);
+        }
+        (Some(r_debug), _, _) => outln!(debugger, "r_debug: {:#x}, unreadable", r_debug),
+        (None, _, _) if state.interpreter.is_some() => {
+            outln!(debugger, "r_debug: not initialized yet, the libraries are not loaded nor their symbols known")
+        }
+        (None, _, _) => outln!(debugger, "r_debug: none"),
+    }
+    let where_pc = if state.in_loader { "in the dynamic loader" } else { "outside of the dynamic loader" };
+    outln!(debugger, "The pc {:#x} is {}.", state.pc, where_pc);
+    if let Some(hint) = loader_hint(debugger) {
+        outln!(debugger, "{}", hint);
+    }
+    json::set_data(debugger, |_| {
+        json!({
+            "interpreter": state.interpreter,
+            "base": state.base.map(json::address),
+            "entry": state.entry.map(json::address),
+            "r_debug": state.r_debug.map(json::address),
+            "r_state": r_state,
+            "objects": objects,
+            "pc": json::address(state.pc),
+            "in_loader": state.in_loader,
+            "before_relocation": state.before_relocation(),
+        })
+    });
+    true
+}
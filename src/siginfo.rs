@@ -0,0 +1,202 @@
+use crate::crash::is_fault;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::state::{InferiorState, StopReason};
+use nix::libc;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use serde_json::{json, Value};
+
+/// `si_code` of a signal sent with `kill`.
+pub const SI_USER: i32 = 0;
+/// `si_code` of a signal sent by the kernel, as for a general protection fault.
+pub const SI_KERNEL: i32 = 0x80;
+/// `si_code` of a signal sent with `sigqueue`.
+pub const SI_QUEUE: i32 = -1;
+/// `si_code` of a signal sent with `tkill` or `tgkill`.
+pub const SI_TKILL: i32 = -6;
+
+/// The codes any signal can have, set when it was not raised by the event the signal is about.
+const GENERIC_CODES: &[(i32, &str, &str)] = &[
+    (SI_USER, "SI_USER", "sent by kill"),
+    (SI_KERNEL, "SI_KERNEL", "sent by the kernel"),
+    (SI_QUEUE, "SI_QUEUE", "sent by sigqueue"),
+    (-2, "SI_TIMER", "POSIX timer expired"),
+    (-3, "SI_MESGQ", "POSIX message queue state changed"),
+    (-4, "SI_ASYNCIO", "AIO completed"),
+    (-5, "SI_SIGIO", "queued SIGIO"),
+    (SI_TKILL, "SI_TKILL", "sent by tkill or tgkill"),
+];
+
+// The codes of each signal raised by the kernel, from 1, as documented in sigaction(2).
+const ILL_CODES: &[(&str, &str)] = &[
+    ("ILL_ILLOPC", "illegal opcode"),
+    ("ILL_ILLOPN", "illegal operand"),
+    ("ILL_ILLADR", "illegal addressing mode"),
+    ("ILL_ILLTRP", "illegal trap"),
+    ("ILL_PRVOPC", "privileged opcode"),
+    ("ILL_PRVREG", "privileged register"),
+    ("ILL_COPROC", "coprocessor error"),
+    ("ILL_BADSTK", "internal stack error"),
+];
+const FPE_CODES: &[(&str, &str)] = &[
+    ("FPE_INTDIV", "integer divide by zero"),
+    ("FPE_INTOVF", "integer overflow"),
+    ("FPE_FLTDIV", "floating-point divide by zero"),
+    ("FPE_FLTOVF", "floating-point overflow"),
+    ("FPE_FLTUND", "floating-point underflow"),
+    ("FPE_FLTRES", "floating-point inexact result"),
+    ("FPE_FLTINV", "floating-point invalid operation"),
+    ("FPE_FLTSUB", "subscript out of range"),
+];
+const SEGV_CODES: &[(&str, &str)] = &[
+    ("SEGV_MAPERR", "address not mapped to object"),
+    ("SEGV_ACCERR", "invalid permissions for mapped object"),
+    ("SEGV_BNDERR", "failed address bound checks"),
+    ("SEGV_PKUERR", "access denied by memory protection keys"),
+];
+const BUS_CODES: &[(&str, &str)] = &[
+    ("BUS_ADRALN", "invalid address alignment"),
+    ("BUS_ADRERR", "nonexistent physical address"),
+    ("BUS_OBJERR", "object-specific hardware error"),
+    ("BUS_MCEERR_AR", "hardware memory error consumed on a machine check"),
+    ("BUS_MCEERR_AO", "hardware memory error detected but not consumed"),
+];
+const TRAP_CODES: &[(&str, &str)] = &[
+    ("TRAP_BRKPT", "process breakpoint"),
+    ("TRAP_TRACE", "process trace trap"),
+    ("TRAP_BRANCH", "process taken branch trap"),
+    ("TRAP_HWBKPT", "hardware breakpoint or watchpoint"),
+];
+const CLD_CODES: &[(&str, &str)] = &[
+    ("CLD_EXITED", "child has exited"),
+    ("CLD_KILLED", "child was killed"),
+    ("CLD_DUMPED", "child terminated abnormally"),
+    ("CLD_TRAPPED", "traced child has trapped"),
+    ("CLD_STOPPED", "child has stopped"),
+    ("CLD_CONTINUED", "stopped child has continued"),
+];
+const POLL_CODES: &[(&str, &str)] = &[
+    ("POLL_IN", "data input available"),
+    ("POLL_OUT", "output buffers available"),
+    ("POLL_MSG", "input message available"),
+    ("POLL_ERR", "I/O error"),
+    ("POLL_PRI", "high priority input available"),
+    ("POLL_HUP", "device disconnected"),
+];
+const SYS_CODES: &[(&str, &str)] = &[("SYS_SECCOMP", "system call denied by a seccomp filter")];
+
+/// The details the kernel gives about a signal, from `PTRACE_GETSIGINFO`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigInfo {
+    /// `si_signo`, the number of the signal.
+    pub signo: i32,
+    /// `si_code`, why it was sent: by a process (0 or less), or by the kernel for a reason
+    /// specific to the signal.
+    pub code: i32,
+    /// `si_addr`, the address accessed or executed, for a fault raised by an instruction.
+    pub address: Option<u64>,
+    /// The process and the user that sent the signal, for a signal sent by `kill` and the like.
+    pub sender: Option<(i32, u32)>,
+}
+
+/// Name and describe the `si_code` of a signal, as `SEGV_MAPERR` and "address not mapped to
+/// object".
+///
+/// # Returns
+///
+/// `None` for a code unknown for this signal.
+pub fn describe_code(signo: i32, code: i32) -> Option<(&'static str, &'static str)> {
+    if let Some((_, name, description)) = GENERIC_CODES.iter().find(|(value, _, _)| *value == code) {
+        return Some((name, description));
+    }
+    let codes = match Signal::try_from(signo).ok()? {
+        Signal::SIGILL => ILL_CODES,
+        Signal::SIGFPE => FPE_CODES,
+        Signal::SIGSEGV => SEGV_CODES,
+        Signal::SIGBUS => BUS_CODES,
+        Signal::SIGTRAP => TRAP_CODES,
+        Signal::SIGCHLD => CLD_CODES,
+        Signal::SIGIO => POLL_CODES,
+        Signal::SIGSYS => SYS_CODES,
+        _ => return None,
+    };
+    let (name, description) = codes.get(usize::try_from(code).ok()?.checked_sub(1)?)?;
+    Some((name, description))
+}
+
+/// The name of a signal number, as `SIGSEGV`, or `SIG34` for one nix does not know.
+pub fn signal_name(signo: i32) -> String {
+    match Signal::try_from(signo) {
+        Ok(signal) => signal.as_str().to_string(),
+        Err(_) => format!("SIG{}", signo),
+    }
+}
+
+/// Decode a `siginfo_t`: the fields of its union that are valid depend on the signal and its code.
+pub fn decode(siginfo: &libc::siginfo_t) -> SigInfo {
+    let (signo, code) = (siginfo.si_signo, siginfo.si_code);
+    // The kernel gives the address of the faults it raises, but not of a general protection fault.
+    let raised = code > 0 && code != SI_KERNEL;
+    let address = Signal::try_from(signo)
+        .is_ok_and(|signal| raised && is_fault(signal))
+        .then(|| unsafe { siginfo.si_addr() } as u64);
+    let sender = matches!(code, SI_USER | SI_QUEUE | SI_TKILL).then(|| unsafe { (siginfo.si_pid(), siginfo.si_uid()) });
+    SigInfo { signo, code, address, sender }
+}
+
+/// Read and decode the details of the signal a thread is stopped with.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the thread is not stopped by a signal.
+pub fn read(tid: Pid) -> Result<SigInfo, DbgError> {
+    log::trace!("PTRACE_GETSIGINFO {}", tid);
+    ptrace::getsiginfo(tid).map(|siginfo| decode(&siginfo)).map_err(DbgError::ptrace("read the signal information"))
+}
+
+/// Describe the code, the address and the sender of a signal in a line, as in
+/// `si_code SEGV_MAPERR (1): address not mapped to object, at address 0x10`.
+pub fn summary(info: &SigInfo) -> String {
+    let mut line = match describe_code(info.signo, info.code) {
+        Some((name, description)) => format!("si_code {} ({}): {}", name, info.code, description),
+        None => format!("si_code {}", info.code),
+    };
+    if let Some(address) = info.address {
+        line.push_str(&format!(", at address {:#x}", address));
+    }
+    if let Some((pid, uid)) = info.sender {
+        line.push_str(&format!(", from pid {} (uid {})", pid, uid));
+    }
+    line
+}
+
+/// The details of a signal in the JSON records.
+pub fn to_json(info: &SigInfo) -> Value {
+    json!({
+        "signo": info.signo,
+        "code": info.code,
+        "code_name": describe_code(info.signo, info.code).map(|(name, _)| name),
+        "address": info.address.map(json::address),
+        "sender_pid": info.sender.map(|(pid, _)| pid),
+        "sender_uid": info.sender.map(|(_, uid)| uid),
+    })
+}
+
+/// Show again the details of the signal the program is stopped by (`info signal`).
+///
+/// # Returns
+///
+/// False if the program is not stopped by a signal.
+pub fn info_signal(debugger: &mut Debugger) -> bool {
+    let (InferiorState::Stopped { reason: StopReason::Signal(_) }, Some(info)) = (debugger.state, debugger.last_signal) else {
+        errln!(debugger, "The program is not stopped by a signal.");
+        return false;
+    };
+    json::set_data(debugger, |_| to_json(&info));
+    outln!(debugger, "Program stopped by signal {} ({}).", signal_name(info.signo), info.signo);
+    outln!(debugger, "{}", summary(&info));
+    true
+}
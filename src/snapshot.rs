@@ -0,0 +1,639 @@
+use crate::arch::{describe_eflags, eflags_names, get_fp_registers, set_fp_registers, FpRegisters, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::maps::MapEntry;
+use crate::memory::read_bulk;
+use crate::output::{Recorder, Recording, Style};
+use crate::step::{self, StepStop};
+use crate::tracee::Tracee;
+use crate::working;
+use nix::errno::Errno;
+use nix::libc::user_regs_struct;
+use nix::sys::ptrace;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use serde_json::json;
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::os::unix::fs::FileExt;
+use std::rc::Rc;
+
+/// The most snapshots kept, the oldest one being dropped for a new one.
+pub const MAX_SNAPSHOTS: usize = 16;
+
+/// The most instructions `reverse-stepi` executes from a snapshot to find the current state again.
+const MAX_REPLAY_STEPS: u64 = 10_000_000;
+
+/// The size of the pages compared and written back by a restore.
+const PAGE_SIZE: usize = 4096;
+
+/// The registers of a thread, general purpose and floating point.
+#[derive(Clone, Copy)]
+struct ThreadRegisters {
+    tid: Pid,
+    regs: user_regs_struct,
+    fpregs: FpRegisters,
+}
+
+/// The contents of a private writable mapping.
+struct SavedMapping {
+    entry: MapEntry,
+    bytes: Vec<u8>,
+}
+
+/// The state of the program at one point: the registers of its threads and its private writable
+/// memory, full copies. Nothing else is saved, see [`SnapshotStore`].
+pub struct Snapshot {
+    /// Its number, shown by `info snapshots`, from 1.
+    pub number: usize,
+    /// The number of the stop it was taken at, or after when it was not taken at a stop, 0 being
+    /// the start of the program.
+    pub stop: u64,
+    /// True if it was taken at the stop, false if the program had executed instructions since.
+    pub at_stop: bool,
+    /// The current thread.
+    thread: Pid,
+    threads: Vec<ThreadRegisters>,
+    mappings: Vec<SavedMapping>,
+}
+
+impl Snapshot {
+    /// The registers of `tid` when the snapshot was taken.
+    fn registers(&self, tid: Pid) -> Option<&user_regs_struct> {
+        self.threads.iter().find(|thread| thread.tid == tid).map(|thread| &thread.regs)
+    }
+
+    /// The pc of the current thread when the snapshot was taken.
+    pub fn pc(&self) -> u64 {
+        self.registers(self.thread).map_or(0, |regs| regs.pc())
+    }
+
+    /// The bytes of memory saved.
+    pub fn size(&self) -> usize {
+        self.mappings.iter().map(|mapping| mapping.bytes.len()).sum()
+    }
+}
+
+/// A stop reported to the user, which `reverse-continue` can go back to.
+#[derive(Clone, Copy)]
+struct StopRecord {
+    number: u64,
+    thread: Pid,
+    regs: user_regs_struct,
+    /// The address of the breakpoint hit.
+    breakpoint: Option<u64>,
+}
+
+/// The snapshots of the program and the stops since the oldest one, to go back with
+/// `reverse-continue` and `reverse-stepi`.
+///
+/// Going back restores the registers and the private writable memory of a snapshot, then executes
+/// the program forward again. Only that is undone: the files written or removed, the output
+/// printed, the data sent or received on sockets and pipes, the shared memory, the other processes,
+/// the time, and the state kept by the kernel (open files and their offsets, the program break,
+/// the mappings made since, pending signals, timers) are not. The code executed again makes its
+/// system calls again, which write their output twice and may read different input, in which case
+/// the program takes another path than the first time.
+#[derive(Default)]
+pub struct SnapshotStore {
+    /// `set snapshot-interval`: a snapshot is taken every that many stops, never with 0.
+    pub interval: u64,
+    snapshots: Vec<Snapshot>,
+    stops: Vec<StopRecord>,
+    /// The number of the last stop at or before the current state of the program.
+    position: u64,
+    next_number: usize,
+}
+
+impl SnapshotStore {
+    /// The snapshots kept, the oldest first.
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    /// Forget the snapshots and the stops of the previous process, keeping the interval.
+    pub fn clear(&mut self) {
+        *self = SnapshotStore { interval: self.interval, ..SnapshotStore::default() };
+    }
+
+    /// Keep a snapshot, dropping the oldest one beyond [`MAX_SNAPSHOTS`], and the stops before it.
+    fn push(&mut self, snapshot: Snapshot) {
+        self.snapshots.push(snapshot);
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.remove(0);
+        }
+        self.drop_old_stops();
+    }
+
+    /// Forget the stops before the oldest snapshot, which cannot be gone back to, but the last one.
+    fn drop_old_stops(&mut self) {
+        let oldest = self.snapshots.first().map_or(self.position, |snapshot| snapshot.stop);
+        self.stops.retain(|stop| stop.number >= oldest || stop.number == self.position);
+    }
+
+    /// Forget the stops and the snapshots after `position`, the program having gone back to it. The
+    /// snapshots taken after that stop, which may be ahead, are forgotten too, but `keep`.
+    fn truncate(&mut self, position: u64, keep: Option<usize>) {
+        self.position = position;
+        self.stops.retain(|stop| stop.number <= position);
+        self.snapshots.retain(|snapshot| {
+            snapshot.stop < position || (snapshot.stop == position && snapshot.at_stop) || Some(snapshot.number) == keep
+        });
+    }
+}
+
+/// The general purpose registers and flags, compared to tell whether the program is in a state.
+#[cfg(target_arch = "x86_64")]
+fn state_values(regs: &user_regs_struct) -> [u64; 18] {
+    [
+        regs.rax, regs.rbx, regs.rcx, regs.rdx, regs.rsi, regs.rdi, regs.rbp, regs.sp(), regs.r8, regs.r9, regs.r10, regs.r11,
+        regs.r12, regs.r13, regs.r14, regs.r15, regs.pc(), regs.eflags,
+    ]
+}
+
+/// The general purpose registers and flags, compared to tell whether the program is in a state.
+#[cfg(target_arch = "aarch64")]
+fn state_values(regs: &user_regs_struct) -> [u64; 34] {
+    let mut values = [0; 34];
+    values[..31].copy_from_slice(&regs.regs);
+    values[31..].copy_from_slice(&[regs.sp, regs.pc, regs.pstate]);
+    values
+}
+
+fn same_state(a: &user_regs_struct, b: &user_regs_struct) -> bool {
+    state_values(a) == state_values(b)
+}
+
+/// Returns true for the mappings a snapshot saves: the private writable ones.
+fn is_saved(entry: &MapEntry) -> bool {
+    let perms = entry.perms.as_bytes();
+    perms.first() == Some(&b'r') && entry.is_writable() && perms.get(3) == Some(&b'p')
+}
+
+/// Take a snapshot of the stopped program.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the registers of a thread can't be read, or
+/// [`DbgError::Memory`] if a mapping can't be read.
+fn take(debugger: &mut Debugger, stop: u64, at_stop: bool) -> Result<Snapshot, DbgError> {
+    let mut threads = Vec::new();
+    for thread in &debugger.threads {
+        let regs = thread.tid.getregs().map_err(DbgError::ptrace("read the registers"))?;
+        let fpregs = get_fp_registers(thread.tid).map_err(DbgError::ptrace("read the floating point registers"))?;
+        threads.push(ThreadRegisters { tid: thread.tid, regs, fpregs });
+    }
+    let entries = debugger.read_mappings().map_err(|_| DbgError::ProcessExited)?;
+    let mut mappings = Vec::new();
+    for entry in entries.into_iter().filter(is_saved) {
+        let bytes = read_bulk(debugger.child, entry.start, (entry.end - entry.start) as usize)?;
+        mappings.push(SavedMapping { entry, bytes });
+    }
+    debugger.snapshots.next_number += 1;
+    Ok(Snapshot { number: debugger.snapshots.next_number, stop, at_stop, thread: debugger.thread, threads, mappings })
+}
+
+/// Put the program back in the state of `snapshot`: the registers of its threads and the pages of
+/// its memory that changed since. Nothing is changed if the threads or the mappings saved are not
+/// there anymore.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Reverse`] if the threads or the mappings changed, or the error of the
+/// write that failed.
+fn restore(debugger: &mut Debugger, snapshot: &Snapshot) -> Result<(), DbgError> {
+    let mut tids: Vec<Pid> = debugger.threads.iter().map(|thread| thread.tid).collect();
+    let mut saved_tids: Vec<Pid> = snapshot.threads.iter().map(|thread| thread.tid).collect();
+    tids.sort_unstable();
+    saved_tids.sort_unstable();
+    if tids != saved_tids {
+        return Err(DbgError::Reverse("threads were created or exited since the snapshot".to_string()));
+    }
+    let entries = debugger.read_mappings().map_err(|_| DbgError::ProcessExited)?;
+    for saved in &snapshot.mappings {
+        let present = entries.iter().any(|entry| {
+            entry.start == saved.entry.start && entry.end >= saved.entry.end && entry.pathname == saved.entry.pathname
+        });
+        if !present {
+            return Err(DbgError::Reverse(format!("{} was unmapped since the snapshot", saved.entry.describe())));
+        }
+    }
+    let to_errno = |err: std::io::Error| err.raw_os_error().map_or(Errno::EIO, Errno::from_raw);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(format!("/proc/{}/mem", debugger.child))
+        .map_err(|err| DbgError::ptrace("open the memory of the program")(to_errno(err)))?;
+    let mut current = vec![0; PAGE_SIZE];
+    crate::cache::invalidate();
+    for saved in &snapshot.mappings {
+        for (index, page) in saved.bytes.chunks(PAGE_SIZE).enumerate() {
+            let address = saved.entry.start + (index * PAGE_SIZE) as u64;
+            let current = &mut current[..page.len()];
+            file.read_exact_at(current, address).map_err(|err| DbgError::memory(address)(to_errno(err)))?;
+            if current != page {
+                file.write_all_at(page, address).map_err(|err| DbgError::memory(address)(to_errno(err)))?;
+            }
+        }
+    }
+    for thread in &snapshot.threads {
+        let mut tid = thread.tid;
+        tid.setregs(thread.regs).map_err(DbgError::ptrace("write the registers"))?;
+        set_fp_registers(thread.tid, &thread.fpregs).map_err(DbgError::ptrace("write the floating point registers"))?;
+    }
+    debugger.thread = snapshot.thread;
+    debugger.selected_frame = 0;
+    debugger.shown_registers = None;
+    debugger.last_crash = None;
+    Ok(())
+}
+
+/// Execute a single instruction of `tid` alone, without reporting it.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Reverse`] if the program terminated, or [`DbgError::Ptrace`] if the thread
+/// can't be stepped.
+fn replay_step(tid: Pid) -> Result<(), DbgError> {
+    log::debug!("PTRACE_SINGLESTEP {} replaying", tid);
+    crate::cache::invalidate();
+    ptrace::step(tid, None).map_err(DbgError::ptrace("single-step"))?;
+    let status = waitpid(tid, Some(WaitPidFlag::__WALL)).map_err(DbgError::ptrace("wait for the program"))?;
+    log::debug!("waitpid {} = {:?}", tid, status);
+    match status {
+        WaitStatus::Exited(..) | WaitStatus::Signaled(..) => Err(diverged()),
+        _ => Ok(()),
+    }
+}
+
+/// The error of a replay that did not find the state it was going back to.
+fn diverged() -> DbgError {
+    DbgError::Reverse(
+        "the program took another path when executed again from the snapshot, it depends on something a snapshot \
+         does not restore. It is left where it stopped"
+            .to_string(),
+    )
+}
+
+/// Disarm every armed breakpoint, so that replaying does not stop at them.
+///
+/// # Returns
+///
+/// Their addresses, to arm them again with [`rearm`].
+fn disarm(debugger: &mut Debugger) -> Result<Vec<u64>, DbgError> {
+    let armed = working::armed_breakpoints(&debugger.breakpoint_table);
+    for address in &armed {
+        working::remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, *address)?;
+    }
+    Ok(armed)
+}
+
+/// Arm the breakpoints disarmed by [`disarm`] again, but the one at the pc, which would be hit
+/// right away.
+fn rearm(debugger: &mut Debugger, armed: &[u64]) -> Result<(), DbgError> {
+    let pc = debugger.registers()?.pc();
+    for address in armed.iter().filter(|address| **address != pc) {
+        working::set_breakpoint(&mut debugger.breakpoint_table, debugger.thread, *address)?;
+    }
+    Ok(())
+}
+
+/// Record a stop reported to the user, and take a snapshot every `snapshot-interval` stops. A
+/// snapshot that can't be taken is warned about.
+pub fn stopped(debugger: &mut Debugger) {
+    if !debugger.state.is_stopped() || debugger.core.is_some() {
+        return;
+    }
+    let Ok(regs) = debugger.tracee().getregs() else {
+        return;
+    };
+    let number = debugger.snapshots.position + 1;
+    let breakpoint = match debugger.state {
+        crate::state::InferiorState::Stopped { reason: crate::state::StopReason::Breakpoint(address) } => Some(address),
+        _ => None,
+    };
+    let store = &mut debugger.snapshots;
+    store.stops.push(StopRecord { number, thread: debugger.thread, regs, breakpoint });
+    store.position = number;
+    store.drop_old_stops();
+    let interval = debugger.snapshots.interval;
+    if interval > 0 && number.is_multiple_of(interval) {
+        match take(debugger, number, true) {
+            Ok(snapshot) => debugger.snapshots.push(snapshot),
+            Err(err) => warnln!(debugger, "Could not take a snapshot: {}", err),
+        }
+    }
+}
+
+/// Whether the current state of the program is the one of the last stop recorded.
+fn at_last_stop(debugger: &Debugger, regs: &user_regs_struct) -> bool {
+    let store = &debugger.snapshots;
+    store.stops.last().is_some_and(|stop| {
+        stop.number == store.position && stop.thread == debugger.thread && same_state(&stop.regs, regs)
+    })
+}
+
+/// Take a snapshot now, with `snapshot`.
+///
+/// # Returns
+///
+/// False if the snapshot can't be taken.
+pub fn snapshot_command(debugger: &mut Debugger, _: &[&str]) -> bool {
+    let result = debugger.registers().and_then(|regs| {
+        let at_stop = debugger.snapshots.stops.is_empty() || at_last_stop(debugger, &regs);
+        take(debugger, debugger.snapshots.position, at_stop)
+    });
+    match result {
+        Ok(snapshot) => {
+            let (number, pc, size) = (snapshot.number, snapshot.pc(), snapshot.size());
+            debugger.snapshots.push(snapshot);
+            json::set_data(debugger, |_| json!({ "number": number, "pc": json::address(pc), "bytes": size }));
+            outln!(debugger, "Snapshot {} at {:#x}, {} bytes of memory.", number, pc, size);
+            true
+        }
+        Err(err) => {
+            errln!(debugger, "Could not take a snapshot: {}", err);
+            false
+        }
+    }
+}
+
+/// List the snapshots kept, with `info snapshots`.
+pub fn info_snapshots(debugger: &mut Debugger) {
+    let rows: Vec<(usize, u64, bool, u64, usize)> = debugger
+        .snapshots
+        .snapshots()
+        .iter()
+        .map(|snapshot| (snapshot.number, snapshot.stop, snapshot.at_stop, snapshot.pc(), snapshot.size()))
+        .collect();
+    json::set_data(debugger, |_| {
+        let snapshots: Vec<_> = rows
+            .iter()
+            .map(|(number, stop, at_stop, pc, size)| {
+                json!({ "number": number, "stop": stop, "at_stop": at_stop, "pc": json::address(*pc), "bytes": size })
+            })
+            .collect();
+        json!({ "snapshots": snapshots })
+    });
+    if rows.is_empty() {
+        outln!(debugger, "No snapshots, take one with snapshot or set snapshot-interval.");
+        return;
+    }
+    outln!(debugger, "Num   Taken               Pc                  Memory");
+    for (number, stop, at_stop, pc, size) in rows {
+        let taken = match (stop, at_stop) {
+            (0, true) => "at the start".to_string(),
+            (stop, true) => format!("at stop {}", stop),
+            (stop, false) => format!("after stop {}", stop),
+        };
+        outln!(debugger, "{:<4}  {:<18}  {:#018x}  {} bytes", number, taken, pc, size);
+    }
+}
+
+/// The registers compared by `r diff`: the ones `r` shows, then `eflags` on x86_64.
+fn compared_registers(debugger: &Debugger, regs: &user_regs_struct) -> Vec<(&'static str, u64)> {
+    let mut registers = debugger.arch.registers(regs);
+    registers.extend(eflags(regs).map(|eflags| ("eflags", eflags)));
+    registers
+}
+
+/// The flags register decoded by `r diff`, `eflags`.
+#[cfg(target_arch = "x86_64")]
+fn eflags(regs: &user_regs_struct) -> Option<u64> {
+    Some(regs.eflags)
+}
+
+/// No `eflags` on aarch64, whose `pstate` is compared as the other registers.
+#[cfg(target_arch = "aarch64")]
+fn eflags(_regs: &user_regs_struct) -> Option<u64> {
+    None
+}
+
+/// The bytes of a register in memory order, the printable ASCII characters shown and the others
+/// as `.`, as in `/bin/sh.` for a register holding a string.
+fn ascii(value: u64, size: usize) -> String {
+    value.to_le_bytes()[..size].iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect()
+}
+
+/// Find the registers of the current thread in a snapshot, by its number, or of the program with
+/// `live`.
+///
+/// # Returns
+///
+/// The name of the side, as in `snapshot 2`, with the registers, or the error message.
+fn registers_of(debugger: &mut Debugger, side: &str) -> Result<(String, user_regs_struct), String> {
+    if side == "live" {
+        return debugger.registers().map(|regs| ("live".to_string(), regs)).map_err(|err| err.to_string());
+    }
+    let number: usize = side.parse().map_err(|_| format!("Invalid snapshot \"{}\": give its number, see info snapshots, or live.", side))?;
+    debugger
+        .snapshots
+        .snapshots()
+        .iter()
+        .find(|snapshot| snapshot.number == number)
+        .and_then(|snapshot| snapshot.registers(snapshot.thread).copied())
+        .map(|regs| (format!("snapshot {}", number), regs))
+        .ok_or_else(|| format!("No snapshot {}, see info snapshots.", number))
+}
+
+/// Compare the registers of two snapshots, or of a snapshot and the program
+/// (`r diff <snapshot> [<snapshot> | live]`), printing the ones that differ with both values,
+/// their XOR showing the flipped bits and their bytes as ASCII, and the flags set on both sides
+/// when `eflags` differs.
+///
+/// # Arguments
+///
+/// * `sides` - The snapshot numbers or `live`, the second one being `live` when omitted.
+///
+/// # Returns
+///
+/// False if a snapshot does not exist or the registers can't be read.
+pub fn registers_diff(debugger: &mut Debugger, sides: &[&str]) -> bool {
+    let sides = match sides {
+        [from] => [*from, "live"],
+        [from, to] => [*from, *to],
+        _ => {
+            errln!(debugger, "Usage: r diff <snapshot> [<snapshot> | live]");
+            return false;
+        }
+    };
+    let (from, to) = match (registers_of(debugger, sides[0]), registers_of(debugger, sides[1])) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(err), _) | (_, Err(err)) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let size = debugger.arch.pointer_size() as usize;
+    let width = size * 2 + 2;
+    let differing: Vec<(&str, u64, u64)> = compared_registers(debugger, &from.1)
+        .into_iter()
+        .zip(compared_registers(debugger, &to.1))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((name, old), (_, new))| (name.trim(), old, new))
+        .collect();
+    let flags = match (eflags(&from.1), eflags(&to.1)) {
+        (Some(old), Some(new)) if old != new => Some((eflags_names(old), eflags_names(new), eflags_names(old ^ new))),
+        _ => None,
+    };
+    json::set_data(debugger, |_| {
+        let registers: Vec<_> = differing
+            .iter()
+            .map(|(name, old, new)| json!({ "name": name, "from": json::address(*old), "to": json::address(*new), "xor": json::address(old ^ new) }))
+            .collect();
+        let flags = flags.as_ref().map(|(old, new, flipped)| json!({ "from": old, "to": new, "flipped": flipped }));
+        json!({ "from": from.0, "to": to.0, "registers": registers, "flags": flags })
+    });
+    if differing.is_empty() {
+        outln!(debugger, "The registers of {} and {} are the same.", from.0, to.0);
+        return true;
+    }
+    outln!(debugger, "{:<8}  {:<width$}  {:<width$}  {:<width$}  ASCII / flags", "Register", from.0, to.0, "XOR", width = width);
+    for (name, old, new) in differing {
+        let detail = match name {
+            "eflags" => {
+                let flipped = eflags_names(old ^ new).join(" ");
+                format!("{} -> {}, flipped {}", describe_eflags(old), describe_eflags(new), flipped)
+            }
+            _ => format!("{} -> {}", ascii(old, size), ascii(new, size)),
+        };
+        let painted = debugger.output.paint(&format!("{:<8}", name), Style::Register);
+        outln!(debugger, "{}  {:#0width$x}  {:#0width$x}  {:#0width$x}  {}", painted, old, new, old ^ new, detail, width = width);
+    }
+    true
+}
+
+/// Report the stop reached by going back, as a stop of the program, without recording it again.
+fn report_reverse_stop(debugger: &mut Debugger, stop: &StepStop) {
+    debugger.record_stop(stop);
+    json::stop_event(debugger);
+    step::print_stop(debugger, stop);
+}
+
+/// Continue the program quietly until its next stop, the breakpoint at `target` being armed.
+fn replay_until(debugger: &mut Debugger, target: u64) -> Result<StepStop, DbgError> {
+    if debugger.registers()?.pc() == target {
+        // The instruction of the breakpoint runs first.
+        replay_step(debugger.thread)?;
+    }
+    working::set_breakpoint(&mut debugger.breakpoint_table, debugger.thread, target)?;
+    let recording = Rc::new(RefCell::new(Recording::new(false, false)));
+    let output = std::mem::replace(&mut debugger.output, Box::new(Recorder::new(recording)));
+    let stop = debugger.cont();
+    debugger.output = output;
+    stop
+}
+
+/// Go back to the last breakpoint hit before the current state of the program: restore the latest
+/// snapshot before it, and execute the program forward until that breakpoint is hit in the same
+/// state again.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Reverse`] if no breakpoint was hit since the oldest snapshot, if the
+/// snapshot can't be restored, or if the program does not reach the breakpoint again.
+pub fn reverse_continue(debugger: &mut Debugger) -> Result<StepStop, DbgError> {
+    let current = debugger.registers()?;
+    let at_stop = at_last_stop(debugger, &current);
+    let store = &debugger.snapshots;
+    let target = store
+        .stops
+        .iter()
+        .rev()
+        .filter(|stop| stop.number <= store.position && stop.breakpoint.is_some())
+        .find(|stop| !(at_stop && stop.number == store.position))
+        .copied()
+        .ok_or_else(|| DbgError::Reverse("no breakpoint was hit since the oldest snapshot".to_string()))?;
+    let address = target.breakpoint.unwrap_or_default();
+    let index = store
+        .snapshots
+        .iter()
+        .rposition(|snapshot| snapshot.stop < target.number || (snapshot.stop == target.number && snapshot.at_stop))
+        .ok_or_else(|| DbgError::Reverse(format!("no snapshot was taken before the breakpoint hit at {:#x}", address)))?;
+    let snapshot = debugger.snapshots.snapshots.remove(index);
+    let armed = disarm(debugger)?;
+    let result = restore(debugger, &snapshot).and_then(|()| {
+        if snapshot.stop == target.number {
+            return Ok(());
+        }
+        loop {
+            match replay_until(debugger, address)? {
+                StepStop::Breakpoint(hit)
+                    if hit == address
+                        && debugger.thread == target.thread
+                        && same_state(&debugger.registers()?, &target.regs) =>
+                {
+                    return Ok(())
+                }
+                StepStop::Exited(_) | StepStop::Killed(_) => return Err(diverged()),
+                _ => {}
+            }
+        }
+    });
+    debugger.snapshots.snapshots.insert(index, snapshot);
+    working::remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, address).ok();
+    let rearmed = rearm(debugger, &armed);
+    result?;
+    rearmed?;
+    debugger.snapshots.truncate(target.number, None);
+    let stop = StepStop::Breakpoint(address);
+    report_reverse_stop(debugger, &stop);
+    Ok(stop)
+}
+
+/// Go back one instruction of the current thread: restore the latest snapshot before the current
+/// state, count the instructions from it to the current state, then restore it again and execute
+/// one instruction less.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Reverse`] if no snapshot was taken before the current state, if it can't be
+/// restored, or if the current state is not found again within [`MAX_REPLAY_STEPS`] instructions.
+pub fn reverse_stepi(debugger: &mut Debugger) -> Result<StepStop, DbgError> {
+    let current = debugger.registers()?;
+    let thread = debugger.thread;
+    let at_stop = at_last_stop(debugger, &current);
+    let store = &debugger.snapshots;
+    let index = store
+        .snapshots
+        .iter()
+        .rposition(|snapshot| {
+            let before = snapshot.stop < store.position || snapshot.at_stop || !at_stop;
+            before && snapshot.registers(thread).is_some_and(|regs| !same_state(regs, &current))
+        })
+        .ok_or_else(|| DbgError::Reverse("no snapshot was taken before the current instruction".to_string()))?;
+    let snapshot = debugger.snapshots.snapshots.remove(index);
+    let armed = disarm(debugger)?;
+    let result = restore(debugger, &snapshot).and_then(|()| {
+        let mut steps = 0;
+        while !same_state(&thread.getregs().map_err(DbgError::ptrace("read the registers"))?, &current) {
+            if steps == MAX_REPLAY_STEPS {
+                return Err(diverged());
+            }
+            replay_step(thread)?;
+            steps += 1;
+        }
+        restore(debugger, &snapshot)?;
+        debugger.thread = thread;
+        for _ in 1..steps {
+            replay_step(thread)?;
+        }
+        Ok(())
+    });
+    debugger.snapshots.snapshots.insert(index, snapshot);
+    let rearmed = rearm(debugger, &armed);
+    result?;
+    rearmed?;
+    let store = &debugger.snapshots;
+    let (number, from) = (store.snapshots[index].number, store.snapshots[index].stop);
+    let position = match at_stop {
+        // The stop of the current state is now ahead.
+        true => store.stops.iter().rev().map(|stop| stop.number).find(|number| *number < store.position).unwrap_or(0).max(from),
+        false => store.position,
+    };
+    debugger.snapshots.truncate(position, Some(number));
+    let stop = StepStop::Done;
+    report_reverse_stop(debugger, &stop);
+    Ok(stop)
+}
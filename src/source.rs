@@ -0,0 +1,48 @@
+use crate::debugger::Debugger;
+use std::fs;
+use std::io;
+
+/// Number of lines printed by `list`.
+pub const LIST_SIZE: u64 = 10;
+
+/// Rewrite a source path recorded at compile time with the `set substitute-path` rules.
+///
+/// # Arguments
+///
+/// * `path` - The path found in the debugging information.
+/// * `rules` - `(from, to)` prefix replacements, the first matching one is applied.
+pub fn substitute_path(path: &str, rules: &[(String, String)]) -> String {
+    for (from, to) in rules {
+        if let Some(rest) = path.strip_prefix(from.as_str()) {
+            if rest.is_empty() || rest.starts_with('/') || from.ends_with('/') {
+                return format!("{}{}", to, rest);
+            }
+        }
+    }
+    path.to_string()
+}
+
+/// Read the lines of a source file.
+pub fn read_source_lines(path: &str) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?.lines().map(str::to_string).collect())
+}
+
+/// Compute the range of lines to show to center a listing on `line`.
+///
+/// # Returns
+///
+/// The first and last line numbers (1-based, inclusive).
+pub fn centered_range(line: u64, line_count: u64) -> (u64, u64) {
+    let first = line.saturating_sub(LIST_SIZE / 2).max(1);
+    let last = (first + LIST_SIZE - 1).min(line_count);
+    (first, last)
+}
+
+/// Print the lines `first..=last` of a source file, prefixed with their line numbers.
+pub fn print_lines(debugger: &mut Debugger, lines: &[String], first: u64, last: u64) {
+    for number in first..=last {
+        if let Some(text) = lines.get(number as usize - 1) {
+            outln!(debugger, "{}\t{}", number, text);
+        }
+    }
+}
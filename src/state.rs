@@ -0,0 +1,109 @@
+use crate::step::StepStop;
+use nix::sys::signal::Signal;
+
+/// Why the program is stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Attached with `--pid`, stopped where it was running.
+    Attached,
+    /// At the entry point of the executable (`--stop-at-entry`).
+    Entry,
+    /// At the breakpoint at this address.
+    Breakpoint(u64),
+    /// After a change or an access of the variable watched at this address.
+    Watchpoint(u64),
+    /// After a step, or at the end of a stepping command.
+    Step,
+    /// Entering or leaving the system call with this number (`syscall`).
+    Syscall { number: u64, entry: bool },
+    /// By a signal, before it is delivered.
+    Signal(Signal),
+    /// Where the core file read with `--core` was dumped, for good.
+    Core,
+}
+
+/// The state of the debugged program, kept up to date as it is resumed and stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InferiorState {
+    /// Stopped before its first instruction, not resumed yet by `run` or another command.
+    NotStarted,
+    /// Stopped by the debugger.
+    Stopped { reason: StopReason },
+    /// Resumed, and not waited for yet.
+    Running,
+    /// Terminated with an exit code.
+    Exited { code: i32 },
+    /// Killed by a signal.
+    Signaled { sig: Signal },
+}
+
+impl InferiorState {
+    /// The state after a stop reported by the stepping commands.
+    pub fn from_stop(stop: &StepStop) -> InferiorState {
+        match *stop {
+            StepStop::Done => InferiorState::Stopped { reason: StopReason::Step },
+            StepStop::Breakpoint(address) => InferiorState::Stopped { reason: StopReason::Breakpoint(address) },
+            StepStop::Watchpoint(address) => InferiorState::Stopped { reason: StopReason::Watchpoint(address) },
+            StepStop::Signal(signal) => InferiorState::Stopped { reason: StopReason::Signal(signal) },
+            StepStop::Exited(code) => InferiorState::Exited { code },
+            StepStop::Killed(sig) => InferiorState::Signaled { sig },
+        }
+    }
+
+    /// Returns true if the process exists, stopped or running.
+    pub fn is_alive(&self) -> bool {
+        !self.has_terminated()
+    }
+
+    /// Returns true once the program has exited or was killed.
+    pub fn has_terminated(&self) -> bool {
+        matches!(self, InferiorState::Exited { .. } | InferiorState::Signaled { .. })
+    }
+
+    /// Returns true if the program can be read and resumed: stopped, started or not.
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, InferiorState::NotStarted | InferiorState::Stopped { .. })
+    }
+
+    /// The exit status of the program once it has terminated, 128 + the signal if it was killed.
+    pub fn exit_status(&self) -> Option<i32> {
+        match *self {
+            InferiorState::Exited { code } => Some(code),
+            InferiorState::Signaled { sig } => Some(128 + sig as i32),
+            _ => None,
+        }
+    }
+}
+
+/// The states of the program in which a command can run, declared by each command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidIn {
+    /// Any state: the command does not use the program, as `help` or `set`.
+    Any,
+    /// While the program is stopped, before `run` included, or read from a core file: the command
+    /// reads it.
+    Stopped,
+    /// Before the program is resumed for the first time (`run`).
+    NotStarted,
+    /// While a process is stopped: the command resumes it, so it can't run on a core file.
+    Live,
+}
+
+impl ValidIn {
+    /// Check that a command can run in `state`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the message telling why the command cannot run, the same for every command.
+    pub fn check(self, state: &InferiorState) -> Result<(), &'static str> {
+        match (self, state) {
+            (ValidIn::Live | ValidIn::NotStarted, InferiorState::Stopped { reason: StopReason::Core }) => Err("Not supported on core files."),
+            (ValidIn::Any, _) | (ValidIn::NotStarted, InferiorState::NotStarted) => Ok(()),
+            (ValidIn::Stopped | ValidIn::Live, state) if state.is_stopped() => Ok(()),
+            (ValidIn::NotStarted, state) if state.has_terminated() => Err("The program has terminated, use restart to run it again."),
+            (ValidIn::NotStarted, _) => Err("The program is already running, use c to continue it."),
+            (ValidIn::Stopped | ValidIn::Live, InferiorState::Running) => Err("The program is running, it must stop before this command."),
+            (ValidIn::Stopped | ValidIn::Live, _) => Err("The program is not being run."),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::debuginfo::SourceLocation;
+use crate::error::DbgError;
+#[cfg(target_arch = "x86_64")]
+use crate::memory::read_u64;
+use crate::record;
+use crate::pagewatch::Fault;
+use crate::threads;
+use crate::tracee::Tracee;
+use crate::working::{handle_breakpoint, is_breakpoint};
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::libc::user_regs_struct;
+use nix::unistd;
+
+/// Longest possible x86_64 instruction, used to recognize the return address pushed by a call.
+#[cfg(target_arch = "x86_64")]
+const MAX_INSTRUCTION_LEN: u64 = 15;
+
+/// Why a stepping command stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepStop {
+    /// The step completed normally.
+    Done,
+    /// A breakpoint was reached at this address.
+    Breakpoint(u64),
+    /// The variable watched at this address changed, or was accessed (`watch`, `awatch`).
+    Watchpoint(u64),
+    /// The child received a signal.
+    Signal(Signal),
+    /// The child exited with this status.
+    Exited(i32),
+    /// The child was killed by a signal.
+    Killed(Signal),
+}
+
+/// Execute a single instruction.
+///
+/// If a breakpoint is armed at the current address, its original instruction is restored first
+/// so the step executes it rather than the `int3`. The instruction is recorded while `record` is
+/// on.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+pub fn single_step(debugger: &mut Debugger) -> Result<StepStop, DbgError> {
+    let mut child = debugger.thread;
+    let before = child.getregs().map_err(DbgError::ptrace("read the registers"))?;
+    if !crate::coverage::reached(debugger, before.pc()) && is_breakpoint(&debugger.breakpoint_table, before.pc()) {
+        handle_breakpoint(debugger, before.pc());
+    }
+    child.step().map_err(DbgError::ptrace("single-step"))?;
+    let stop = wait_stop(debugger, true)?;
+    if stop == StepStop::Done {
+        record::record_step(debugger, &before);
+    }
+    Ok(stop)
+}
+
+/// Wait for the current thread, resumed alone by `step` (a single step) or by `cont`. A thread it
+/// creates on the way is added to the threads, stopped, and the current thread resumed again, as
+/// it is after following the new program it executes.
+fn wait_stop(debugger: &mut Debugger, stepping: bool) -> Result<StepStop, DbgError> {
+    let child = debugger.thread;
+    loop {
+        let status = waitpid(child, Some(WaitPidFlag::__WALL)).map_err(DbgError::ptrace("wait for the program"))?;
+        log::debug!("waitpid {} = {:?}", child, status);
+        if crate::exec::is_exec(&status) {
+            crate::exec::follow_exec(debugger, debugger.child);
+        }
+        if crate::exec::is_exec(&status) || threads::adopt_new_thread(debugger, &status) {
+            log::debug!("{} {} again", if stepping { "PTRACE_SINGLESTEP" } else { "PTRACE_CONT" }, child);
+            crate::cache::invalidate();
+            let resumed = if stepping { ptrace::step(child, None) } else { ptrace::cont(child, None) };
+            resumed.map_err(DbgError::ptrace(if stepping { "single-step" } else { "continue execution" }))?;
+            continue;
+        }
+        // A write to the pages of a watched region is done on the way, stopping if it is in the region.
+        let status = match crate::pagewatch::handle_fault(debugger, child, status)? {
+            Fault::Other(status) => status,
+            Fault::Hit => return Ok(crate::watch::take_hit(debugger).unwrap_or(StepStop::Done)),
+            Fault::Elsewhere if stepping => return Ok(StepStop::Done),
+            Fault::Elsewhere => {
+                log::debug!("PTRACE_CONT {} after the write", child);
+                crate::cache::invalidate();
+                ptrace::cont(child, None).map_err(DbgError::ptrace("continue execution"))?;
+                continue;
+            }
+        };
+        return match status {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => Ok(StepStop::Done),
+            WaitStatus::Stopped(_, signal) => Ok(StepStop::Signal(signal)),
+            WaitStatus::Exited(_, code) => Ok(StepStop::Exited(code)),
+            WaitStatus::Signaled(_, signal, _) => Ok(StepStop::Killed(signal)),
+            _ => Ok(StepStop::Done),
+        };
+    }
+}
+
+/// Run until the function called from the current frame returns to `return_address`.
+///
+/// A temporary breakpoint is inserted at the return address. Hits of that breakpoint by deeper
+/// recursive calls (with a lower stack pointer) are stepped over.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `return_address` - The address the call will return to.
+/// * `frame_sp` - The stack pointer of the caller once the call has returned.
+pub fn run_until_return(debugger: &mut Debugger, return_address: u64, frame_sp: u64) -> Result<StepStop, DbgError> {
+    let child = debugger.thread;
+    let address = return_address as ptrace::AddressType;
+    let original = ptrace::read(child, address).map_err(DbgError::memory(return_address))?;
+    loop {
+        crate::cache::invalidate();
+        unsafe { ptrace::write(child, address, crate::working::patch_word(original, &crate::arch::BREAKPOINT_INSTRUCTION) as ptrace::AddressType) }.map_err(DbgError::memory(return_address))?;
+        log::debug!("Breakpoint armed at the return address {:#x}, PTRACE_CONT {}", return_address, child);
+        crate::cache::invalidate();
+        ptrace::cont(child, None).map_err(DbgError::ptrace("continue execution"))?;
+        let stop = wait_stop(debugger, false)?;
+        if matches!(stop, StepStop::Exited(_) | StepStop::Killed(_)) {
+            return Ok(stop);
+        }
+        crate::cache::invalidate();
+        unsafe { ptrace::write(child, address, original as ptrace::AddressType) }.map_err(DbgError::memory(return_address))?;
+        log::debug!("Breakpoint disarmed at the return address {:#x}", return_address);
+        if stop != StepStop::Done {
+            return Ok(stop);
+        }
+        let mut regs = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?;
+        let trap = crate::working::trap_address(regs.pc());
+        if trap != return_address && crate::coverage::reached(debugger, trap) {
+            // A block recorded by cover, run on.
+            regs.set_pc(trap);
+            crate::cache::invalidate();
+            crate::arch::set_registers(child, regs).map_err(DbgError::ptrace("write the registers"))?;
+            continue;
+        }
+        if trap != return_address {
+            // Another breakpoint was hit inside the called function.
+            if handle_breakpoint(debugger, trap) {
+                regs.set_pc(trap);
+                crate::cache::invalidate();
+                crate::arch::set_registers(child, regs).map_err(DbgError::ptrace("write the registers"))?;
+            }
+            return Ok(StepStop::Breakpoint(trap));
+        }
+        regs.set_pc(return_address);
+        crate::cache::invalidate();
+        crate::arch::set_registers(child, regs).map_err(DbgError::ptrace("write the registers"))?;
+        if regs.sp() >= frame_sp {
+            return Ok(StepStop::Done);
+        }
+        // A recursive call returned, get past the return address before re-arming.
+        let stop = single_step(debugger)?;
+        if stop != StepStop::Done {
+            return Ok(stop);
+        }
+    }
+}
+
+/// Step by source line.
+///
+/// With `over_calls` (`next`), functions called from the current line are run until they return.
+/// Without it (`step`), the called functions are entered if they have line information, and run
+/// until they return otherwise. When the current address has no line information, a single
+/// instruction is stepped instead.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `over_calls` - True to step over function calls.
+pub fn step_line(debugger: &mut Debugger, over_calls: bool) -> Result<StepStop, DbgError> {
+    let child = debugger.thread;
+    let start = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?.pc();
+    let Some(start_location) = debugger.source_location(start) else {
+        outln!(debugger, "No line number information for {:#x}, stepping one instruction.", start);
+        return single_step(debugger);
+    };
+    loop {
+        let before = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?;
+        let stop = single_step(debugger)?;
+        if stop != StepStop::Done {
+            return Ok(stop);
+        }
+        let after = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?;
+        if let Some(return_address) = call_return(child, &before, &after) {
+            let entered = debugger.source_location(after.pc()).is_some();
+            if over_calls || !entered {
+                let stop = run_until_return(debugger, return_address, before.sp())?;
+                if stop != StepStop::Done {
+                    return Ok(stop);
+                }
+            } else {
+                return Ok(StepStop::Done);
+            }
+        }
+        let rip = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?.pc();
+        if !crate::coverage::reached(debugger, rip) && is_breakpoint(&debugger.breakpoint_table, rip) {
+            handle_breakpoint(debugger, rip);
+            return Ok(StepStop::Breakpoint(rip));
+        }
+        match debugger.source_location(rip) {
+            Some(location) if !same_line(&location, &start_location) => return Ok(StepStop::Done),
+            Some(_) => {}
+            // Returned into a caller without line information.
+            None if after.sp() > before.sp() => return Ok(StepStop::Done),
+            None => {}
+        }
+    }
+}
+
+/// The return address of the call the instruction just stepped made, if it was one: it pushed a
+/// return address pointing right after itself and jumped elsewhere.
+#[cfg(target_arch = "x86_64")]
+pub fn call_return(child: unistd::Pid, before: &user_regs_struct, after: &user_regs_struct) -> Option<u64> {
+    if after.sp() != before.sp().wrapping_sub(8) {
+        return None;
+    }
+    let pushed = read_u64(&child, after.sp()).ok()?;
+    (pushed > before.pc() && pushed <= before.pc() + MAX_INSTRUCTION_LEN && after.pc() != pushed).then_some(pushed)
+}
+
+/// The return address of the call the instruction just stepped made, if it was one: a `bl` or
+/// `blr`, which put the address of the next instruction in the link register `x30` and jumped
+/// elsewhere.
+#[cfg(target_arch = "aarch64")]
+pub fn call_return(_child: unistd::Pid, before: &user_regs_struct, after: &user_regs_struct) -> Option<u64> {
+    let next = before.pc() + 4;
+    (after.regs[30] == next && before.regs[30] != next && after.pc() != next).then_some(next)
+}
+
+fn same_line(a: &SourceLocation, b: &SourceLocation) -> bool {
+    a.line == b.line && a.file == b.file
+}
+
+/// Print why a stepping command stopped, and where.
+///
+/// The instruction at the new pc is shown after a step, and after a breakpoint hit when
+/// `set show-instruction` is on. A fault gets a crash report. The new state of the child is
+/// recorded in [`Debugger::state`], and the mappings of the child are read again. The stop is
+/// kept for `reverse-continue`, with a snapshot every `set snapshot-interval` stops, and the stop
+/// hooks of the scripts run.
+pub fn report_stop(debugger: &mut Debugger, stop: &StepStop) {
+    debugger.record_stop(stop);
+    // What the program wrote comes before its stop.
+    crate::inferior::flush(debugger);
+    crate::snapshot::stopped(debugger);
+    crate::json::stop_event(debugger);
+    print_stop(debugger, stop);
+    crate::scripting::run_stop_hooks(debugger, stop);
+}
+
+/// Print why the program stopped, its banner, with the descriptors opened and closed since the
+/// last stop with `set track-fds on`, then the line of `where` with `set stop-summary on`, where
+/// it stopped, and the expressions of `display`.
+pub fn print_stop(debugger: &mut Debugger, stop: &StepStop) {
+    if let Some(banner) = crate::summary::banner(debugger) {
+        outln!(debugger, "{}", banner);
+    }
+    crate::procfs::report_fd_changes(debugger);
+    if debugger.stop_summary {
+        if let Some(summary) = crate::summary::summarize(debugger) {
+            outln!(debugger, "{}", summary.text());
+        }
+    }
+    match stop {
+        StepStop::Exited(_) | StepStop::Killed(_) => {}
+        StepStop::Signal(signal) => {
+            if let Some(info) = debugger.last_signal {
+                outln!(debugger, "{}", crate::siginfo::summary(&info));
+            }
+            // Ctrl-C in a program blocked in a system call: say which.
+            if let Some(hint) = crate::syscallinfo::interrupted_hint(debugger) {
+                outln!(debugger, "{}", hint);
+            }
+            if !crate::crash::report_crash(debugger, *signal) {
+                print_current_location(debugger, true);
+            }
+        }
+        StepStop::Breakpoint(address) => {
+            crate::variables::report_function_breakpoint(debugger, *address);
+            let show_instruction = debugger.show_instruction;
+            print_current_location(debugger, show_instruction);
+        }
+        StepStop::Watchpoint(_) | StepStop::Done => print_current_location(debugger, true),
+    }
+    crate::display::show_displays(debugger);
+}
+
+/// Print the source line of the pc when known, then the instruction at the pc if requested.
+fn print_current_location(debugger: &mut Debugger, show_instruction: bool) {
+    let Ok(regs) = debugger.tracee().getregs() else {
+        return;
+    };
+    let has_source = debugger.source_location(regs.pc()).is_some();
+    if has_source {
+        debugger.print_stop_location(regs.pc());
+    }
+    match show_instruction.then(|| crate::disasm::current_instruction(debugger, regs.pc())).flatten() {
+        Some(line) => outln!(debugger, "{}", line),
+        None if !has_source => outln!(debugger, "pc = {:#x}", regs.pc()),
+        None => {}
+    }
+}
@@ -0,0 +1,211 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::debuginfo::SourceLocation;
+use crate::json;
+use crate::maps;
+use crate::state::{InferiorState, StopReason};
+use crate::threads;
+use serde_json::{json, Map, Value};
+
+/// Where the program is stopped and why, the line of `where`: shown at each stop with
+/// `set stop-summary on`, and the fields of the JSON stop records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopSummary {
+    /// The pc of the current thread.
+    pub pc: u64,
+    /// The symbol containing the pc, demangled, with the offset of the pc in it.
+    pub function: Option<(String, u64)>,
+    /// The source line of the pc.
+    pub location: Option<SourceLocation>,
+    /// The mapping containing the pc: its file, or `[vdso]`, `[stack]`..., and its permissions.
+    pub mapping: Option<(Option<String>, String)>,
+    /// Why the program stopped, as [`stop_reason`] names it.
+    pub reason: Option<&'static str>,
+    /// The number of the breakpoint hit, the watched variable, the system call, or the signal
+    /// received, after the reason.
+    pub detail: Option<String>,
+    /// The number of the current thread, and its thread id.
+    pub thread: (usize, i32),
+}
+
+impl StopSummary {
+    /// The summary in one line, as in
+    /// `0x0000555555555140 in count+0x7 at loop.c:6 [loop r-xp], breakpoint 1, thread 1 (tid 1234)`.
+    pub fn text(&self) -> String {
+        let mut line = format!("{:#018x}", self.pc);
+        match &self.function {
+            Some((name, 0)) => line.push_str(&format!(" in {}", name)),
+            Some((name, offset)) => line.push_str(&format!(" in {}+{:#x}", name, offset)),
+            None => line.push_str(" in ??"),
+        }
+        if let Some(location) = &self.location {
+            line.push_str(&format!(" at {}:{}", location.file, location.line));
+        }
+        if let Some((path, perms)) = &self.mapping {
+            let name = path.as_deref().map_or("anonymous", |path| path.rsplit('/').next().unwrap_or(path));
+            line.push_str(&format!(" [{} {}]", name, perms));
+        }
+        match (self.reason, &self.detail) {
+            (Some(reason), Some(detail)) => line.push_str(&format!(", {} {}", reason, detail)),
+            (Some(reason), None) => line.push_str(&format!(", {}", reason)),
+            _ => {}
+        }
+        line.push_str(&format!(", thread {} (tid {})", self.thread.0, self.thread.1));
+        line
+    }
+
+    /// Add the fields of the summary to a JSON record: `pc`, `function`, `offset`, `file`, `line`,
+    /// `mapping` and `perms` when known, and the `summary` line.
+    pub fn add_to(&self, record: &mut Map<String, Value>) {
+        record.insert("pc".to_string(), json::address(self.pc));
+        if let Some((name, offset)) = &self.function {
+            record.insert("function".to_string(), json!(name));
+            record.insert("offset".to_string(), json!(offset));
+        }
+        if let Some(location) = &self.location {
+            record.insert("file".to_string(), json!(location.file));
+            record.insert("line".to_string(), json!(location.line));
+        }
+        if let Some((path, perms)) = &self.mapping {
+            record.insert("mapping".to_string(), json!(path));
+            record.insert("perms".to_string(), json!(perms));
+        }
+        record.insert("summary".to_string(), json!(self.text()));
+    }
+}
+
+/// Name why the program stopped, from its state: `breakpoint`, `watchpoint`, `step`, `syscall`, `signal`,
+/// `entry`, `attached`, `core`, `exited` or `killed`. None while it runs or before it starts.
+pub fn stop_reason(state: &InferiorState) -> Option<&'static str> {
+    Some(match state {
+        InferiorState::Stopped { reason: StopReason::Step } => "step",
+        InferiorState::Stopped { reason: StopReason::Breakpoint(_) } => "breakpoint",
+        InferiorState::Stopped { reason: StopReason::Watchpoint(_) } => "watchpoint",
+        InferiorState::Stopped { reason: StopReason::Syscall { .. } } => "syscall",
+        InferiorState::Stopped { reason: StopReason::Signal(_) } => "signal",
+        InferiorState::Stopped { reason: StopReason::Entry } => "entry",
+        InferiorState::Stopped { reason: StopReason::Attached } => "attached",
+        InferiorState::Stopped { reason: StopReason::Core } => "core",
+        InferiorState::Exited { .. } => "exited",
+        InferiorState::Signaled { .. } => "killed",
+        InferiorState::NotStarted | InferiorState::Running => return None,
+    })
+}
+
+/// The number of the breakpoint at `address`, from 1. Hit breakpoints are disarmed until the
+/// program is resumed, so it is looked for by address.
+pub fn breakpoint_number(debugger: &Debugger, address: u64) -> Option<usize> {
+    debugger.breakpoints.iter().rposition(|breakpoint| *breakpoint == address).map(|index| index + 1)
+}
+
+/// Name a system call the program is stopped at, as in `openat (257) (entry)`.
+fn syscall_detail(debugger: &Debugger, number: u64, entry: bool) -> String {
+    format!("{} ({}) ({})", debugger.arch.syscall_name(number), number, if entry { "entry" } else { "exit" })
+}
+
+/// The first line printed at a stop, telling exactly why the program stopped, as in
+/// `Breakpoint 2, parse at input.c:88`, `Step completed.`, `Caught syscall openat (257) (entry)`,
+/// `Hardware watchpoint total: value changed 3 -> 4` or `Program received signal SIGPIPE.`. The
+/// JSON stop records give it as their `banner`.
+///
+/// # Returns
+///
+/// None while the program runs or before it starts.
+pub fn banner(debugger: &mut Debugger) -> Option<String> {
+    let reason = match debugger.state {
+        InferiorState::Stopped { reason } => reason,
+        InferiorState::Exited { code } => return Some(format!("Child process exited with status {}.", code)),
+        InferiorState::Signaled { sig } => return Some(format!("Child process was killed by {:?}.", sig)),
+        InferiorState::NotStarted | InferiorState::Running => return None,
+    };
+    let banner = match reason {
+        StopReason::Breakpoint(address) => match breakpoint_number(debugger, address) {
+            Some(number) => format!("Breakpoint {}, {}", number, breakpoint_location(debugger, address)),
+            None if debugger.temporary_breakpoint == Some(address) => {
+                format!("Temporary breakpoint (start), {}", breakpoint_location(debugger, address))
+            }
+            None => format!("Hit unknown breakpoint at address {:#x}, a breakpoint instruction of the program.", address),
+        },
+        StopReason::Watchpoint(address) => match debugger.watchpoints.find(address).cloned() {
+            Some(watchpoint) => crate::watch::describe_hit(debugger, &watchpoint),
+            None => crate::pagewatch::describe_hit(debugger, address).unwrap_or_else(|| format!("Watchpoint at {:#x}.", address)),
+        },
+        StopReason::Step => "Step completed.".to_string(),
+        StopReason::Syscall { number, entry } => format!("Caught syscall {}", syscall_detail(debugger, number, entry)),
+        StopReason::Signal(signal) => format!("Program received signal {:?}.", signal),
+        StopReason::Entry => "Stopped at the entry point of the program.".to_string(),
+        StopReason::Attached => "Attached, stopped where the program was running.".to_string(),
+        StopReason::Core => "Stopped for good where the core file was dumped.".to_string(),
+    };
+    // Early stops in the dynamic loader confuse: the symbols of the libraries are not known yet.
+    Some(match crate::sharedlib::loader_hint(debugger) {
+        Some(hint) => format!("{} {}", banner, hint),
+        None => banner,
+    })
+}
+
+/// Describe where a breakpoint is, as in `parse at input.c:88`.
+fn breakpoint_location(debugger: &mut Debugger, address: u64) -> String {
+    let function = debugger.symbolize(address).map_or_else(|| "??".to_string(), |(name, _, _)| crate::symbols::demangle(&name));
+    match debugger.source_location(address) {
+        Some(location) => format!("{} at {}:{}", function, location.file, location.line),
+        None => format!("{} ({:#x})", function, address),
+    }
+}
+
+/// Summarize where the current thread of the program is stopped.
+///
+/// # Returns
+///
+/// None if the program is not stopped, or its registers can't be read.
+pub fn summarize(debugger: &mut Debugger) -> Option<StopSummary> {
+    if !debugger.state.is_stopped() {
+        return None;
+    }
+    let pc = debugger.tracee().getregs().ok()?.pc();
+    let function = debugger.symbolize(pc).map(|(name, offset, _)| (crate::symbols::demangle(&name), offset));
+    let location = debugger.source_location(pc);
+    let mapping = maps::find_mapping(&debugger.mappings, pc).map(|entry| (entry.pathname.clone(), entry.perms.clone()));
+    let detail = match debugger.state {
+        InferiorState::Stopped { reason: StopReason::Breakpoint(address) } => breakpoint_number(debugger, address).map(|number| number.to_string()),
+        InferiorState::Stopped { reason: StopReason::Watchpoint(address) } => match debugger.watchpoints.find(address) {
+            Some(watchpoint) => Some(watchpoint.name.clone()),
+            None => debugger.region_watches.find(address).map(|region| format!("region {}", region.number)),
+        },
+        InferiorState::Stopped { reason: StopReason::Syscall { number, entry } } => Some(syscall_detail(debugger, number, entry)),
+        InferiorState::Stopped { reason: StopReason::Signal(signal) } => Some(signal.as_str().to_string()),
+        _ => None,
+    };
+    Some(StopSummary {
+        pc,
+        function,
+        location,
+        mapping,
+        reason: stop_reason(&debugger.state),
+        detail,
+        thread: (threads::number(debugger, debugger.thread), debugger.thread.as_raw()),
+    })
+}
+
+/// Print where the program is stopped in one line (`where`), as [`StopSummary::text`] does.
+///
+/// # Returns
+///
+/// False if the program is not stopped.
+pub fn print_summary(debugger: &mut Debugger) -> bool {
+    let Some(summary) = summarize(debugger) else {
+        errln!(debugger, "The program is not being run.");
+        return false;
+    };
+    outln!(debugger, "{}", summary.text());
+    json::set_data(debugger, |_| {
+        let mut data = Map::new();
+        if let Some(reason) = summary.reason {
+            data.insert("reason".to_string(), json!(reason));
+        }
+        data.insert("thread".to_string(), json!(summary.thread.1));
+        summary.add_to(&mut data);
+        Value::Object(data)
+    });
+    true
+}
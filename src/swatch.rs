@@ -0,0 +1,112 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::memory::read_bulk;
+use crate::step::{self, StepStop};
+use crate::symbols::path_matches;
+use crate::working::{handle_breakpoint, is_breakpoint};
+use nix::libc;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by Ctrl-C while a software watchpoint single-steps the program.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Why a software watchpoint stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchStop {
+    /// The watched bytes changed from `old` to `new` by the instruction at `pc`, or by the call at
+    /// `pc` to an ignored function.
+    Changed { pc: u64, old: Vec<u8>, new: Vec<u8> },
+    /// Ctrl-C was pressed.
+    Interrupted,
+    /// The program stopped for another reason: a breakpoint, a signal, or it terminated.
+    Stopped(StepStop),
+}
+
+/// Single-step the current thread until the `len` bytes at `address` change, comparing them after
+/// each instruction. The functions named in `ignore` are run until they return instead, when the
+/// program calls them. Ctrl-C interrupts the scan.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+/// * `address` - The address of the watched bytes.
+/// * `len` - The number of bytes watched.
+/// * `ignore` - The functions not scanned, by name or by the last components of their path.
+///
+/// # Returns
+///
+/// Why it stopped, and the number of instructions stepped, the ignored calls counting for one.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Memory`] if the watched bytes can't be read, or [`DbgError::Ptrace`] if the
+/// program can't be stepped.
+pub fn watch(debugger: &mut Debugger, address: u64, len: usize, ignore: &[&str]) -> Result<(WatchStop, u64), DbgError> {
+    let old = read_bulk(debugger.thread, address, len)?;
+    INTERRUPTED.store(false, Ordering::Relaxed);
+    let action = SigAction::new(SigHandler::Handler(interrupt), SaFlags::empty(), SigSet::empty());
+    // Without the handler, Ctrl-C stops the program only, which ends the scan as a signal.
+    let previous = unsafe { signal::sigaction(Signal::SIGINT, &action) }.ok();
+    let result = scan(debugger, address, old, ignore);
+    if let Some(previous) = previous {
+        unsafe { signal::sigaction(Signal::SIGINT, &previous) }.ok();
+    }
+    result
+}
+
+fn scan(debugger: &mut Debugger, address: u64, old: Vec<u8>, ignore: &[&str]) -> Result<(WatchStop, u64), DbgError> {
+    let child = debugger.thread;
+    let mut steps = 0;
+    // The program in the same process group gets the SIGINT of Ctrl-C too, and stops with it.
+    let stopped = |stop: StepStop| match stop {
+        StepStop::Signal(Signal::SIGINT) if INTERRUPTED.load(Ordering::Relaxed) => WatchStop::Interrupted,
+        stop => WatchStop::Stopped(stop),
+    };
+    loop {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            return Ok((WatchStop::Interrupted, steps));
+        }
+        let before = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?;
+        let stop = step::single_step(debugger)?;
+        steps += 1;
+        if stop != StepStop::Done {
+            return Ok((stopped(stop), steps));
+        }
+        let after = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?;
+        let call = if ignore.is_empty() { None } else { step::call_return(child, &before, &after) };
+        if let Some(return_address) = call.filter(|_| is_ignored(debugger, after.pc(), ignore)) {
+            let stop = step::run_until_return(debugger, return_address, before.sp())?;
+            if stop != StepStop::Done {
+                return Ok((stopped(stop), steps));
+            }
+        }
+        let new = read_bulk(child, address, old.len())?;
+        if new != old {
+            return Ok((WatchStop::Changed { pc: before.pc(), old, new }, steps));
+        }
+        let rip = crate::arch::get_registers(child).map_err(DbgError::ptrace("read the registers"))?.pc();
+        if !crate::coverage::reached(debugger, rip) && is_breakpoint(&debugger.breakpoint_table, rip) {
+            handle_breakpoint(debugger, rip);
+            return Ok((WatchStop::Stopped(StepStop::Breakpoint(rip)), steps));
+        }
+    }
+}
+
+/// Returns true if the function starting at `address` is one of `ignore`.
+fn is_ignored(debugger: &mut Debugger, address: u64, ignore: &[&str]) -> bool {
+    match debugger.symbolize(address) {
+        Some((name, 0, _)) => ignore.iter().any(|ignored| path_matches(&name, ignored)),
+        _ => false,
+    }
+}
+
+/// Format watched bytes in hexadecimal, as `00 2a 00 00`.
+pub fn format_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,629 @@
+use object::read::elf::{ElfFile64, ProgramHeader};
+use object::{Endianness, Object, ObjectSymbolTable, ObjectKind, ObjectSection, ObjectSegment, ObjectSymbol, SectionFlags, SymbolKind};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// Directory searched by default for separate debug information files.
+pub const DEFAULT_DEBUG_FILE_DIRECTORY: &str = "/usr/lib/debug";
+
+/// Errors that can happen while loading the symbols of an ELF file.
+#[derive(Debug, Error)]
+pub enum SymbolError {
+    #[error("could not read {path}: {source}")]
+    Io { path: String, source: io::Error },
+    #[error("could not parse {path}: {source}")]
+    Parse { path: String, source: object::Error },
+    #[error("could not read {path} in the memory of the program: {source}")]
+    Memory { path: String, source: crate::error::DbgError },
+}
+
+/// A function or data symbol, with its address relative to the ELF file (not rebased).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// The demangled name for Rust symbols (without the hash), the name itself otherwise.
+    pub demangled: String,
+    pub address: u64,
+    pub size: u64,
+    pub is_function: bool,
+    /// Index of the section defining the symbol.
+    pub section: Option<usize>,
+}
+
+/// A section of an ELF file, with its address relative to the file (not rebased).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub index: usize,
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    /// The `sh_flags` of the section.
+    pub flags: u64,
+}
+
+impl Section {
+    /// Returns true if the section occupies memory when the file is loaded (`SHF_ALLOC`).
+    pub fn is_alloc(&self) -> bool {
+        self.flags & object::elf::SHF_ALLOC as u64 != 0
+    }
+
+    /// The flags of the section as letters, in the style of `readelf -S`.
+    pub fn flag_letters(&self) -> String {
+        let letters = [
+            (object::elf::SHF_WRITE, 'W'),
+            (object::elf::SHF_ALLOC, 'A'),
+            (object::elf::SHF_EXECINSTR, 'X'),
+            (object::elf::SHF_MERGE, 'M'),
+            (object::elf::SHF_STRINGS, 'S'),
+            (object::elf::SHF_INFO_LINK, 'I'),
+            (object::elf::SHF_TLS, 'T'),
+        ];
+        letters
+            .iter()
+            .filter(|(flag, _)| self.flags & *flag as u64 != 0)
+            .map(|(_, letter)| letter)
+            .collect()
+    }
+}
+
+/// Summary of the ELF header of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    /// `EXEC`, `DYN`, ...
+    pub kind: String,
+    pub machine: String,
+    /// File address of the entry point.
+    pub entry: u64,
+    /// The program interpreter (dynamic linker) requested by the file.
+    pub interpreter: Option<String>,
+}
+
+/// A PLT stub of a file: calls to `name`, a function of a shared library, go through `address`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PltEntry {
+    pub name: String,
+    /// File address of the stub (not rebased).
+    pub address: u64,
+}
+
+/// The thread-local storage template of a file (its `PT_TLS` segment).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsSegment {
+    /// File address of the initialization image.
+    pub address: u64,
+    /// Size of the TLS block of each thread (`p_memsz`).
+    pub size: u64,
+    pub align: u64,
+}
+
+/// The symbols of one ELF object (the main executable or a shared library).
+#[derive(Debug, Clone)]
+pub struct ObjectSymbols {
+    pub path: String,
+    pub symbols: Vec<Symbol>,
+    /// Load bias (runtime address - file address), unknown until the object is seen mapped.
+    pub bias: Option<u64>,
+    /// File address of the `.dynamic` section, if any.
+    pub dynamic_address: Option<u64>,
+    pub header: FileHeader,
+    pub sections: Vec<Section>,
+    /// The thread-local storage template, if the file defines thread-local variables.
+    pub tls_segment: Option<TlsSegment>,
+    /// The PLT stubs of the file, from its `.rela.plt` relocations.
+    pub plt_entries: Vec<PltEntry>,
+    /// The GNU build ID of the file (`.note.gnu.build-id`).
+    pub build_id: Option<Vec<u8>>,
+    /// The file name and CRC32 of the separate debug file named by `.gnu_debuglink`.
+    pub debuglink: Option<(String, u32)>,
+    /// True if the file itself carries DWARF debugging information.
+    pub has_dwarf: bool,
+    /// The separate debug file whose symbols were merged into this object, if any.
+    pub debug_file: Option<String>,
+    /// Positions in `symbols` by name: the raw name, the demangled path and each of its suffixes.
+    index: HashMap<String, Vec<usize>>,
+    is_pie: bool,
+    min_vaddr: u64,
+    max_vaddr: u64,
+}
+
+impl ObjectSymbols {
+    /// Parse the `.symtab` and `.dynsym` tables of the ELF file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or isn't a valid object file.
+    pub fn load(path: &str) -> Result<ObjectSymbols, SymbolError> {
+        let data = fs::read(path).map_err(|source| SymbolError::Io { path: path.to_string(), source })?;
+        ObjectSymbols::parse(path, &data)
+    }
+
+    /// Parse the symbols of an ELF object read from a file or from memory, named `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `data` isn't a valid object file.
+    pub fn parse(path: &str, data: &[u8]) -> Result<ObjectSymbols, SymbolError> {
+        let file = object::File::parse(data).map_err(|source| SymbolError::Parse { path: path.to_string(), source })?;
+
+        let mut symbols: Vec<Symbol> = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter(|sym| sym.is_definition() && sym.address() != 0)
+            .filter(|sym| matches!(sym.kind(), SymbolKind::Text | SymbolKind::Data))
+            .filter_map(|sym| {
+                let name = sym.name().ok()?;
+                Some(Symbol {
+                    name: name.to_string(),
+                    demangled: demangle(name),
+                    address: sym.address(),
+                    size: sym.size(),
+                    is_function: sym.kind() == SymbolKind::Text,
+                    section: sym.section_index().map(|index| index.0),
+                })
+            })
+            .filter(|sym| !sym.name.is_empty())
+            .collect();
+        symbols.sort_by(|a, b| a.address.cmp(&b.address).then_with(|| a.name.cmp(&b.name)));
+        symbols.dedup();
+
+        let sections = file
+            .sections()
+            .map(|section| Section {
+                index: section.index().0,
+                name: section.name().unwrap_or("").to_string(),
+                address: section.address(),
+                size: section.size(),
+                flags: match section.flags() {
+                    SectionFlags::Elf { sh_flags } => sh_flags,
+                    _ => 0,
+                },
+            })
+            .collect();
+        let header = FileHeader {
+            kind: match file.kind() {
+                ObjectKind::Executable => "EXEC".to_string(),
+                ObjectKind::Dynamic => "DYN".to_string(),
+                ObjectKind::Relocatable => "REL".to_string(),
+                ObjectKind::Core => "CORE".to_string(),
+                _ => "unknown".to_string(),
+            },
+            machine: format!("{:?}", file.architecture()),
+            entry: file.entry(),
+            interpreter: file
+                .section_by_name(".interp")
+                .and_then(|section| section.data().ok())
+                .map(|data| String::from_utf8_lossy(data).trim_end_matches('\0').to_string()),
+        };
+
+        let min_vaddr = file.segments().map(|seg| seg.address()).min().unwrap_or(0);
+        let max_vaddr = file.segments().map(|seg| seg.address() + seg.size()).max().unwrap_or(0);
+        let index = name_index(&symbols);
+        Ok(ObjectSymbols {
+            path: path.to_string(),
+            symbols,
+            bias: if file.kind() == object::ObjectKind::Executable { Some(0) } else { None },
+            dynamic_address: file.section_by_name(".dynamic").map(|section| section.address()),
+            header,
+            sections,
+            tls_segment: tls_segment(data),
+            plt_entries: plt_entries(&file),
+            build_id: file.build_id().ok().flatten().map(|id| id.to_vec()),
+            debuglink: file
+                .gnu_debuglink()
+                .ok()
+                .flatten()
+                .map(|(name, crc)| (String::from_utf8_lossy(name).into_owned(), crc)),
+            has_dwarf: file.section_by_name(".debug_info").is_some_and(|section| section.size() > 0),
+            debug_file: None,
+            index,
+            is_pie: file.kind() != object::ObjectKind::Executable,
+            min_vaddr,
+            max_vaddr,
+        })
+    }
+
+    /// Returns true if DWARF debugging information was found, in the file or in a separate debug file.
+    pub fn has_debug_info(&self) -> bool {
+        self.has_dwarf || self.debug_file.is_some()
+    }
+
+    /// Merge the symbols of the separate debug file at `path` into this object.
+    pub fn merge_debug_file(&mut self, path: &str) -> Result<(), SymbolError> {
+        let debug = ObjectSymbols::load(path)?;
+        self.symbols.extend(debug.symbols);
+        self.symbols.sort_by(|a, b| a.address.cmp(&b.address).then_with(|| a.name.cmp(&b.name)));
+        self.symbols.dedup();
+        self.index = name_index(&self.symbols);
+        self.debug_file = Some(path.to_string());
+        Ok(())
+    }
+
+    /// Compute the load bias of this object from the address of its first mapping (file offset 0).
+    pub fn bias_from_base(&self, base: u64) -> u64 {
+        if self.is_pie {
+            base.wrapping_sub(self.min_vaddr & !0xfff)
+        } else {
+            0
+        }
+    }
+
+    /// Find the symbol containing the runtime address `address`.
+    ///
+    /// # Returns
+    ///
+    /// The symbol and the offset of `address` inside it.
+    pub fn symbolize(&self, address: u64) -> Option<(&Symbol, u64)> {
+        let file_address = address.wrapping_sub(self.bias?);
+        if file_address < self.min_vaddr || file_address >= self.max_vaddr {
+            return None;
+        }
+        let index = self.symbols.partition_point(|sym| sym.address <= file_address);
+        let symbol = self.symbols[..index]
+            .iter()
+            .rev()
+            .find(|sym| sym.size == 0 || file_address < sym.address + sym.size)?;
+        Some((symbol, file_address - symbol.address))
+    }
+
+    /// Find the runtime address of a symbol by exact name.
+    pub fn lookup(&self, name: &str) -> Option<u64> {
+        let bias = self.bias?;
+        self.find(name)
+            .into_iter()
+            .find(|sym| sym.name == name)
+            .map(|sym| sym.address.wrapping_add(bias))
+    }
+
+    /// Find the symbols designated by `name`: by exact name, or by the last components of a
+    /// demangled Rust path (`COUNTER` for `my_crate::state::COUNTER`).
+    pub fn find(&self, name: &str) -> Vec<&Symbol> {
+        self.index
+            .get(name)
+            .map(|positions| positions.iter().map(|&position| &self.symbols[position]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the name of the section defining a symbol.
+    pub fn section_name(&self, symbol: &Symbol) -> Option<&str> {
+        let index = symbol.section?;
+        self.sections.iter().find(|section| section.index == index).map(|section| section.name.as_str())
+    }
+}
+
+/// The names of the symbols of every loaded object, sorted, to complete them at the prompt.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    /// Demangled names of the functions.
+    pub functions: BTreeSet<String>,
+    /// Demangled names of the data symbols (globals, statics and thread-locals).
+    pub data: BTreeSet<String>,
+}
+
+impl SymbolIndex {
+    /// Add the symbols of an object. Names with spaces (`<impl Trait for Type>::f`) can't be typed
+    /// as one argument and are left out.
+    pub fn add(&mut self, object: &ObjectSymbols) {
+        for symbol in &object.symbols {
+            if symbol.demangled.is_empty() || symbol.demangled.contains(char::is_whitespace) {
+                continue;
+            }
+            let names = if symbol.is_function { &mut self.functions } else { &mut self.data };
+            names.insert(symbol.demangled.clone());
+        }
+    }
+
+    /// The names of `names` starting with `prefix`, in order.
+    pub fn starting_with<'a>(names: &'a BTreeSet<String>, prefix: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        names.range(prefix.to_string()..).take_while(move |name| name.starts_with(prefix))
+    }
+}
+
+/// Every object whose symbols have been loaded. The main executable, when loaded, comes first.
+#[derive(Debug)]
+pub struct SymbolTable {
+    pub objects: Vec<ObjectSymbols>,
+    /// Directories searched for separate debug files (`set debug-file-directory`).
+    pub debug_file_directories: Vec<String>,
+    /// Names of the loaded symbols, shared with the completion of the prompt, which reads the
+    /// commands in a thread of its own.
+    pub index: Arc<Mutex<SymbolIndex>>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        SymbolTable {
+            objects: Vec::new(),
+            debug_file_directories: vec![DEFAULT_DEBUG_FILE_DIRECTORY.to_string()],
+            index: Arc::default(),
+        }
+    }
+}
+
+impl SymbolTable {
+    /// Forget the symbols of every object, keeping the settings.
+    pub fn clear(&mut self) {
+        self.objects.clear();
+        if let Ok(mut index) = self.index.lock() {
+            *index = SymbolIndex::default();
+        }
+    }
+
+    /// Returns true if the symbols of the object at `path` are already loaded.
+    pub fn is_loaded(&self, path: &str) -> bool {
+        self.objects.iter().any(|object| same_file(&object.path, path))
+    }
+
+    /// Find the symbols of the object at `path`.
+    pub fn object_mut(&mut self, path: &str) -> Option<&mut ObjectSymbols> {
+        self.objects.iter_mut().find(|object| same_file(&object.path, path))
+    }
+
+    /// Load the symbols of the object at `path` with a known load bias.
+    ///
+    /// # Returns
+    ///
+    /// The number of symbols loaded.
+    pub fn load(&mut self, path: &str, bias: Option<u64>) -> Result<usize, SymbolError> {
+        let mut object = ObjectSymbols::load(path)?;
+        if !object.has_dwarf {
+            if let Some(debug_file) = find_debug_file(&object, &self.debug_file_directories) {
+                object.merge_debug_file(&debug_file)?;
+            }
+        }
+        if bias.is_some() {
+            object.bias = bias;
+        }
+        Ok(self.add(object))
+    }
+
+    /// Add the symbols of an object, replacing the ones of the same path.
+    ///
+    /// # Returns
+    ///
+    /// The number of symbols added.
+    pub fn add(&mut self, object: ObjectSymbols) -> usize {
+        let count = object.symbols.len();
+        if let Ok(mut index) = self.index.lock() {
+            index.add(&object);
+        }
+        self.objects.retain(|loaded| !same_file(&loaded.path, &object.path));
+        self.objects.push(object);
+        count
+    }
+
+    /// Find the runtime address of a symbol, searching objects in load order.
+    pub fn lookup(&self, name: &str) -> Option<u64> {
+        self.objects.iter().find_map(|object| object.lookup(name))
+    }
+
+    /// Find the symbols designated by `name` in every loaded object, as [`ObjectSymbols::find`].
+    pub fn find(&self, name: &str) -> Vec<(&ObjectSymbols, &Symbol)> {
+        self.objects
+            .iter()
+            .flat_map(|object| object.find(name).into_iter().map(move |symbol| (object, symbol)))
+            .collect()
+    }
+
+    /// Find the data symbols designated by `name` in every loaded object.
+    pub fn find_data(&self, name: &str) -> Vec<(&ObjectSymbols, &Symbol)> {
+        let mut matches: Vec<(&ObjectSymbols, &Symbol)> = Vec::new();
+        for (object, symbol) in self.find(name).into_iter().filter(|(_, symbol)| !symbol.is_function) {
+            // The same variable may be in both the static and the dynamic symbol tables.
+            let duplicate = matches.iter().any(|(other, found)| {
+                std::ptr::eq(*other, object) && found.address == symbol.address && found.demangled == symbol.demangled
+            });
+            if !duplicate {
+                matches.push((object, symbol));
+            }
+        }
+        matches
+    }
+
+    /// Find the symbol containing a runtime address in any loaded object.
+    ///
+    /// # Returns
+    ///
+    /// The object, the symbol and the offset of `address` inside the symbol.
+    pub fn symbolize(&self, address: u64) -> Option<(&ObjectSymbols, &Symbol, u64)> {
+        self.objects
+            .iter()
+            .find_map(|object| object.symbolize(address).map(|(symbol, offset)| (object, symbol, offset)))
+    }
+}
+
+/// Index symbols by their raw name, their demangled path and each suffix of that path.
+fn name_index(symbols: &[Symbol]) -> HashMap<String, Vec<usize>> {
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (position, symbol) in symbols.iter().enumerate() {
+        let mut keys = vec![symbol.name.as_str()];
+        let mut path = symbol.demangled.as_str();
+        loop {
+            keys.push(path);
+            match path.split_once("::") {
+                Some((_, rest)) => path = rest,
+                None => break,
+            }
+        }
+        keys.sort_unstable();
+        keys.dedup();
+        for key in keys {
+            index.entry(key.to_string()).or_default().push(position);
+        }
+    }
+    index
+}
+
+/// Size of a PLT stub on x86_64 and i386.
+const PLT_ENTRY_SIZE: u64 = 16;
+/// Size of the header stub of the `.plt` of aarch64, before the 16-byte stubs.
+const PLT_HEADER_SIZE_AARCH64: u64 = 32;
+/// Size of an `Elf64_Rela` relocation.
+const RELA_SIZE: usize = 24;
+/// Size of an `Elf32_Rel` relocation, of the PLT of i386.
+const REL_SIZE: usize = 8;
+
+/// Find the PLT stub of each `R_X86_64_JUMP_SLOT` (`R_AARCH64_JUMP_SLOT`) relocation of
+/// `.rela.plt`, or each `R_386_JMP_SLOT` relocation of `.rel.plt` for a 32-bit file.
+///
+/// The n-th relocation is called through the n-th stub of `.plt.sec` when the file has one
+/// (`-fcf-protection`), through the n-th stub of `.plt` after its header stub otherwise.
+fn plt_entries(file: &object::File) -> Vec<PltEntry> {
+    let (section, size) = if file.is_64() { (".rela.plt", RELA_SIZE) } else { (".rel.plt", REL_SIZE) };
+    let aarch64 = file.architecture() == object::Architecture::Aarch64;
+    let jump_slot_type = if aarch64 { object::elf::R_AARCH64_JUMP_SLOT } else { object::elf::R_X86_64_JUMP_SLOT };
+    let (Some(relocations), Some(dynamic_symbols)) = (file.section_by_name(section), file.dynamic_symbol_table()) else {
+        return Vec::new();
+    };
+    let first_stub = match (file.section_by_name(".plt.sec"), file.section_by_name(".plt")) {
+        (Some(plt_sec), _) => plt_sec.address(),
+        (None, Some(plt)) if aarch64 => plt.address() + PLT_HEADER_SIZE_AARCH64,
+        (None, Some(plt)) => plt.address() + PLT_ENTRY_SIZE,
+        (None, None) => return Vec::new(),
+    };
+    let Ok(data) = relocations.data() else {
+        return Vec::new();
+    };
+    data.chunks_exact(size)
+        .enumerate()
+        .filter_map(|(index, relocation)| {
+            let (jump_slot, symbol) = if size == RELA_SIZE {
+                let info = u64::from_le_bytes(relocation[8..16].try_into().unwrap());
+                (info & 0xffff_ffff == jump_slot_type as u64, info >> 32)
+            } else {
+                let info = u32::from_le_bytes(relocation[4..8].try_into().unwrap());
+                (info & 0xff == object::elf::R_386_JMP_SLOT, (info >> 8) as u64)
+            };
+            if !jump_slot {
+                return None;
+            }
+            let symbol = dynamic_symbols.symbol_by_index(object::SymbolIndex(symbol as usize)).ok()?;
+            Some(PltEntry { name: symbol.name().ok()?.to_string(), address: first_stub + index as u64 * PLT_ENTRY_SIZE })
+        })
+        .collect()
+}
+
+/// Find the `PT_TLS` program header of a 64-bit ELF file.
+fn tls_segment(data: &[u8]) -> Option<TlsSegment> {
+    let file = ElfFile64::<Endianness>::parse(data).ok()?;
+    let endian = file.endian();
+    let header = file.elf_program_headers().iter().find(|header| header.p_type(endian) == object::elf::PT_TLS)?;
+    Some(TlsSegment {
+        address: header.p_vaddr(endian),
+        size: header.p_memsz(endian),
+        align: header.p_align(endian).max(1),
+    })
+}
+
+/// Demangle a Rust symbol name, dropping its hash. Other names are returned unchanged.
+pub fn demangle(name: &str) -> String {
+    match rustc_demangle::try_demangle(name) {
+        Ok(demangled) => format!("{:#}", demangled),
+        Err(_) => name.to_string(),
+    }
+}
+
+/// Find the `main` of a Rust program among the symbols of its executable: the mangled function
+/// `<crate>::main` (`_ZN5hello4main17h…E`), which `std::rt::lang_start` runs behind the `main` of
+/// the C library.
+///
+/// # Arguments
+///
+/// * `symbols` - The symbols of the executable.
+/// * `program` - The file name of the executable, preferring the crate of the same name when
+///   several crates of the program have a `main`.
+///
+/// # Returns
+///
+/// `None` if the executable is not a Rust program or has no such symbol.
+pub fn rust_main<'a>(symbols: &'a [Symbol], program: &str) -> Option<&'a Symbol> {
+    if !symbols.iter().any(|symbol| symbol.demangled.starts_with("std::rt::lang_start")) {
+        return None;
+    }
+    let crate_name = program.replace('-', "_");
+    let mut candidates = symbols.iter().filter(|symbol| {
+        let mangled = symbol.name.starts_with("_ZN") || symbol.name.starts_with("_R");
+        let path: Vec<&str> = symbol.demangled.split("::").collect();
+        symbol.is_function && mangled && path.len() == 2 && path[1] == "main"
+    });
+    let first = candidates.clone().next()?;
+    Some(candidates.find(|symbol| symbol.demangled.split("::").next() == Some(crate_name.as_str())).unwrap_or(first))
+}
+
+/// Returns true if the path `qualified` (`my_crate::state::COUNTER`) is designated by `name`:
+/// the full path or its last components (`COUNTER`, `state::COUNTER`).
+pub fn path_matches(qualified: &str, name: &str) -> bool {
+    qualified == name || qualified.ends_with(&format!("::{}", name))
+}
+
+/// Find the separate debug file of an object.
+///
+/// The build ID is looked up first as `<directory>/.build-id/xx/rest.debug`, then the
+/// `.gnu_debuglink` file name next to the object, in its `.debug` subdirectory and under each
+/// directory followed by the object's own directory. Debug link candidates must match the CRC.
+///
+/// # Arguments
+///
+/// * `object` - The object whose debug file is searched.
+/// * `directories` - The debug file directories (`/usr/lib/debug` by default).
+pub fn find_debug_file(object: &ObjectSymbols, directories: &[String]) -> Option<String> {
+    if let Some(build_id) = object.build_id.as_ref().filter(|id| id.len() > 1) {
+        let hex: String = build_id.iter().map(|byte| format!("{:02x}", byte)).collect();
+        for directory in directories {
+            let candidate = format!("{}/.build-id/{}/{}.debug", directory, &hex[..2], &hex[2..]);
+            if Path::new(&candidate).is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    let (name, crc) = object.debuglink.as_ref()?;
+    let object_path = fs::canonicalize(&object.path).ok()?;
+    let object_dir = object_path.parent()?;
+    let mut candidates: Vec<PathBuf> = vec![object_dir.join(name), object_dir.join(".debug").join(name)];
+    for directory in directories {
+        let relative = object_dir.strip_prefix("/").unwrap_or(object_dir);
+        candidates.push(Path::new(directory).join(relative).join(name));
+    }
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.is_file() && *candidate != object_path)
+        .find(|candidate| fs::read(candidate).is_ok_and(|data| crc32(&data) == *crc))
+        .map(|candidate| candidate.to_string_lossy().into_owned())
+}
+
+/// CRC-32 (IEEE 802.3) of `data`, as stored in `.gnu_debuglink`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Compute the load bias of the ELF file at `path` mapped at `base`, without loading its symbols.
+pub fn load_bias(path: &str, base: u64) -> Option<u64> {
+    let data = fs::read(path).ok()?;
+    let file = object::File::parse(&*data).ok()?;
+    if file.kind() == object::ObjectKind::Executable {
+        return Some(0);
+    }
+    let min_vaddr = file.segments().map(|seg| seg.address()).min().unwrap_or(0);
+    Some(base.wrapping_sub(min_vaddr & !0xfff))
+}
+
+/// Returns true if both paths name the same file once symlinks are resolved.
+pub fn same_file(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
@@ -1,3 +1,11 @@
+use crate::arch::Registers;
+use crate::error::DbgError;
+use crate::step::StepStop;
+use crate::tracee::Tracee;
+use crate::working::{classify_stop, is_breakpoint, BreakpointTable};
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+
 /// Get the name of the syscall based on its number.
 ///
 /// # Arguments
@@ -7,6 +15,7 @@
 /// # Returns
 ///
 /// The name of the syscall as a static string.
+#[cfg(target_arch = "x86_64")]
 pub fn syscall_name(syscall_num: u64) -> &'static str {
     match syscall_num {
         0 => "read",
@@ -384,4 +393,654 @@ pub fn syscall_name(syscall_num: u64) -> &'static str {
         461 => "lsm_list_modules",
         _ => "unknown",
     }
-}
\ No newline at end of file
+}
+
+/// Get the name of the syscall of a 32-bit (i386) program based on its number, which differ from
+/// the numbers of x86_64.
+///
+/// # Arguments
+///
+/// * `syscall_num` - The syscall number, from `orig_eax`.
+///
+/// # Returns
+///
+/// The name of the syscall as a static string.
+#[cfg(target_arch = "x86_64")]
+pub fn syscall_name_i386(syscall_num: u64) -> &'static str {
+    match syscall_num {
+        0 => "restart_syscall",
+        1 => "exit",
+        2 => "fork",
+        3 => "read",
+        4 => "write",
+        5 => "open",
+        6 => "close",
+        7 => "waitpid",
+        8 => "creat",
+        9 => "link",
+        10 => "unlink",
+        11 => "execve",
+        12 => "chdir",
+        13 => "time",
+        14 => "mknod",
+        15 => "chmod",
+        16 => "lchown",
+        17 => "break",
+        18 => "oldstat",
+        19 => "lseek",
+        20 => "getpid",
+        21 => "mount",
+        22 => "umount",
+        23 => "setuid",
+        24 => "getuid",
+        25 => "stime",
+        26 => "ptrace",
+        27 => "alarm",
+        28 => "oldfstat",
+        29 => "pause",
+        30 => "utime",
+        31 => "stty",
+        32 => "gtty",
+        33 => "access",
+        34 => "nice",
+        35 => "ftime",
+        36 => "sync",
+        37 => "kill",
+        38 => "rename",
+        39 => "mkdir",
+        40 => "rmdir",
+        41 => "dup",
+        42 => "pipe",
+        43 => "times",
+        44 => "prof",
+        45 => "brk",
+        46 => "setgid",
+        47 => "getgid",
+        48 => "signal",
+        49 => "geteuid",
+        50 => "getegid",
+        51 => "acct",
+        52 => "umount2",
+        53 => "lock",
+        54 => "ioctl",
+        55 => "fcntl",
+        56 => "mpx",
+        57 => "setpgid",
+        58 => "ulimit",
+        59 => "oldolduname",
+        60 => "umask",
+        61 => "chroot",
+        62 => "ustat",
+        63 => "dup2",
+        64 => "getppid",
+        65 => "getpgrp",
+        66 => "setsid",
+        67 => "sigaction",
+        68 => "sgetmask",
+        69 => "ssetmask",
+        70 => "setreuid",
+        71 => "setregid",
+        72 => "sigsuspend",
+        73 => "sigpending",
+        74 => "sethostname",
+        75 => "setrlimit",
+        76 => "getrlimit",
+        77 => "getrusage",
+        78 => "gettimeofday",
+        79 => "settimeofday",
+        80 => "getgroups",
+        81 => "setgroups",
+        82 => "select",
+        83 => "symlink",
+        84 => "oldlstat",
+        85 => "readlink",
+        86 => "uselib",
+        87 => "swapon",
+        88 => "reboot",
+        89 => "readdir",
+        90 => "mmap",
+        91 => "munmap",
+        92 => "truncate",
+        93 => "ftruncate",
+        94 => "fchmod",
+        95 => "fchown",
+        96 => "getpriority",
+        97 => "setpriority",
+        98 => "profil",
+        99 => "statfs",
+        100 => "fstatfs",
+        101 => "ioperm",
+        102 => "socketcall",
+        103 => "syslog",
+        104 => "setitimer",
+        105 => "getitimer",
+        106 => "stat",
+        107 => "lstat",
+        108 => "fstat",
+        109 => "olduname",
+        110 => "iopl",
+        111 => "vhangup",
+        112 => "idle",
+        113 => "vm86old",
+        114 => "wait4",
+        115 => "swapoff",
+        116 => "sysinfo",
+        117 => "ipc",
+        118 => "fsync",
+        119 => "sigreturn",
+        120 => "clone",
+        121 => "setdomainname",
+        122 => "uname",
+        123 => "modify_ldt",
+        124 => "adjtimex",
+        125 => "mprotect",
+        126 => "sigprocmask",
+        127 => "create_module",
+        128 => "init_module",
+        129 => "delete_module",
+        130 => "get_kernel_syms",
+        131 => "quotactl",
+        132 => "getpgid",
+        133 => "fchdir",
+        134 => "bdflush",
+        135 => "sysfs",
+        136 => "personality",
+        137 => "afs_syscall",
+        138 => "setfsuid",
+        139 => "setfsgid",
+        140 => "_llseek",
+        141 => "getdents",
+        142 => "_newselect",
+        143 => "flock",
+        144 => "msync",
+        145 => "readv",
+        146 => "writev",
+        147 => "getsid",
+        148 => "fdatasync",
+        149 => "_sysctl",
+        150 => "mlock",
+        151 => "munlock",
+        152 => "mlockall",
+        153 => "munlockall",
+        154 => "sched_setparam",
+        155 => "sched_getparam",
+        156 => "sched_setscheduler",
+        157 => "sched_getscheduler",
+        158 => "sched_yield",
+        159 => "sched_get_priority_max",
+        160 => "sched_get_priority_min",
+        161 => "sched_rr_get_interval",
+        162 => "nanosleep",
+        163 => "mremap",
+        164 => "setresuid",
+        165 => "getresuid",
+        166 => "vm86",
+        167 => "query_module",
+        168 => "poll",
+        169 => "nfsservctl",
+        170 => "setresgid",
+        171 => "getresgid",
+        172 => "prctl",
+        173 => "rt_sigreturn",
+        174 => "rt_sigaction",
+        175 => "rt_sigprocmask",
+        176 => "rt_sigpending",
+        177 => "rt_sigtimedwait",
+        178 => "rt_sigqueueinfo",
+        179 => "rt_sigsuspend",
+        180 => "pread64",
+        181 => "pwrite64",
+        182 => "chown",
+        183 => "getcwd",
+        184 => "capget",
+        185 => "capset",
+        186 => "sigaltstack",
+        187 => "sendfile",
+        188 => "getpmsg",
+        189 => "putpmsg",
+        190 => "vfork",
+        191 => "ugetrlimit",
+        192 => "mmap2",
+        193 => "truncate64",
+        194 => "ftruncate64",
+        195 => "stat64",
+        196 => "lstat64",
+        197 => "fstat64",
+        198 => "lchown32",
+        199 => "getuid32",
+        200 => "getgid32",
+        201 => "geteuid32",
+        202 => "getegid32",
+        203 => "setreuid32",
+        204 => "setregid32",
+        205 => "getgroups32",
+        206 => "setgroups32",
+        207 => "fchown32",
+        208 => "setresuid32",
+        209 => "getresuid32",
+        210 => "setresgid32",
+        211 => "getresgid32",
+        212 => "chown32",
+        213 => "setuid32",
+        214 => "setgid32",
+        215 => "setfsuid32",
+        216 => "setfsgid32",
+        217 => "pivot_root",
+        218 => "mincore",
+        219 => "madvise",
+        220 => "getdents64",
+        221 => "fcntl64",
+        224 => "gettid",
+        225 => "readahead",
+        238 => "tkill",
+        239 => "sendfile64",
+        240 => "futex",
+        241 => "sched_setaffinity",
+        242 => "sched_getaffinity",
+        243 => "set_thread_area",
+        244 => "get_thread_area",
+        245 => "io_setup",
+        246 => "io_destroy",
+        247 => "io_getevents",
+        248 => "io_submit",
+        249 => "io_cancel",
+        250 => "fadvise64",
+        252 => "exit_group",
+        254 => "epoll_create",
+        255 => "epoll_ctl",
+        256 => "epoll_wait",
+        257 => "remap_file_pages",
+        258 => "set_tid_address",
+        264 => "clock_settime",
+        265 => "clock_gettime",
+        266 => "clock_getres",
+        267 => "clock_nanosleep",
+        268 => "statfs64",
+        269 => "fstatfs64",
+        270 => "tgkill",
+        271 => "utimes",
+        272 => "fadvise64_64",
+        295 => "openat",
+        296 => "mkdirat",
+        311 => "set_robust_list",
+        312 => "get_robust_list",
+        355 => "getrandom",
+        383 => "statx",
+        384 => "arch_prctl",
+        _ => "unknown",
+    }
+}
+
+/// Get the name of the syscall of an aarch64 program based on its number, from the generic table
+/// of the kernel (`asm-generic/unistd.h`), which differs from the numbers of x86_64.
+///
+/// # Arguments
+///
+/// * `syscall_num` - The syscall number, from `x8`.
+///
+/// # Returns
+///
+/// The name of the syscall as a static string.
+#[cfg(target_arch = "aarch64")]
+pub fn syscall_name_aarch64(syscall_num: u64) -> &'static str {
+    match syscall_num {
+        0 => "io_setup",
+        1 => "io_destroy",
+        2 => "io_submit",
+        3 => "io_cancel",
+        4 => "io_getevents",
+        5 => "setxattr",
+        6 => "lsetxattr",
+        7 => "fsetxattr",
+        8 => "getxattr",
+        9 => "lgetxattr",
+        10 => "fgetxattr",
+        11 => "listxattr",
+        12 => "llistxattr",
+        13 => "flistxattr",
+        14 => "removexattr",
+        15 => "lremovexattr",
+        16 => "fremovexattr",
+        17 => "getcwd",
+        18 => "lookup_dcookie",
+        19 => "eventfd2",
+        20 => "epoll_create1",
+        21 => "epoll_ctl",
+        22 => "epoll_pwait",
+        23 => "dup",
+        24 => "dup3",
+        25 => "fcntl",
+        26 => "inotify_init1",
+        27 => "inotify_add_watch",
+        28 => "inotify_rm_watch",
+        29 => "ioctl",
+        30 => "ioprio_set",
+        31 => "ioprio_get",
+        32 => "flock",
+        33 => "mknodat",
+        34 => "mkdirat",
+        35 => "unlinkat",
+        36 => "symlinkat",
+        37 => "linkat",
+        38 => "renameat",
+        39 => "umount2",
+        40 => "mount",
+        41 => "pivot_root",
+        42 => "nfsservctl",
+        43 => "statfs",
+        44 => "fstatfs",
+        45 => "truncate",
+        46 => "ftruncate",
+        47 => "fallocate",
+        48 => "faccessat",
+        49 => "chdir",
+        50 => "fchdir",
+        51 => "chroot",
+        52 => "fchmod",
+        53 => "fchmodat",
+        54 => "fchownat",
+        55 => "fchown",
+        56 => "openat",
+        57 => "close",
+        58 => "vhangup",
+        59 => "pipe2",
+        60 => "quotactl",
+        61 => "getdents64",
+        62 => "lseek",
+        63 => "read",
+        64 => "write",
+        65 => "readv",
+        66 => "writev",
+        67 => "pread64",
+        68 => "pwrite64",
+        69 => "preadv",
+        70 => "pwritev",
+        71 => "sendfile",
+        72 => "pselect6",
+        73 => "ppoll",
+        74 => "signalfd4",
+        75 => "vmsplice",
+        76 => "splice",
+        77 => "tee",
+        78 => "readlinkat",
+        79 => "newfstatat",
+        80 => "fstat",
+        81 => "sync",
+        82 => "fsync",
+        83 => "fdatasync",
+        84 => "sync_file_range",
+        85 => "timerfd_create",
+        86 => "timerfd_settime",
+        87 => "timerfd_gettime",
+        88 => "utimensat",
+        89 => "acct",
+        90 => "capget",
+        91 => "capset",
+        92 => "personality",
+        93 => "exit",
+        94 => "exit_group",
+        95 => "waitid",
+        96 => "set_tid_address",
+        97 => "unshare",
+        98 => "futex",
+        99 => "set_robust_list",
+        100 => "get_robust_list",
+        101 => "nanosleep",
+        102 => "getitimer",
+        103 => "setitimer",
+        104 => "kexec_load",
+        105 => "init_module",
+        106 => "delete_module",
+        107 => "timer_create",
+        108 => "timer_gettime",
+        109 => "timer_getoverrun",
+        110 => "timer_settime",
+        111 => "timer_delete",
+        112 => "clock_settime",
+        113 => "clock_gettime",
+        114 => "clock_getres",
+        115 => "clock_nanosleep",
+        116 => "syslog",
+        117 => "ptrace",
+        118 => "sched_setparam",
+        119 => "sched_setscheduler",
+        120 => "sched_getscheduler",
+        121 => "sched_getparam",
+        122 => "sched_setaffinity",
+        123 => "sched_getaffinity",
+        124 => "sched_yield",
+        125 => "sched_get_priority_max",
+        126 => "sched_get_priority_min",
+        127 => "sched_rr_get_interval",
+        128 => "restart_syscall",
+        129 => "kill",
+        130 => "tkill",
+        131 => "tgkill",
+        132 => "sigaltstack",
+        133 => "rt_sigsuspend",
+        134 => "rt_sigaction",
+        135 => "rt_sigprocmask",
+        136 => "rt_sigpending",
+        137 => "rt_sigtimedwait",
+        138 => "rt_sigqueueinfo",
+        139 => "rt_sigreturn",
+        140 => "setpriority",
+        141 => "getpriority",
+        142 => "reboot",
+        143 => "setregid",
+        144 => "setgid",
+        145 => "setreuid",
+        146 => "setuid",
+        147 => "setresuid",
+        148 => "getresuid",
+        149 => "setresgid",
+        150 => "getresgid",
+        151 => "setfsuid",
+        152 => "setfsgid",
+        153 => "times",
+        154 => "setpgid",
+        155 => "getpgid",
+        156 => "getsid",
+        157 => "setsid",
+        158 => "getgroups",
+        159 => "setgroups",
+        160 => "uname",
+        161 => "sethostname",
+        162 => "setdomainname",
+        163 => "getrlimit",
+        164 => "setrlimit",
+        165 => "getrusage",
+        166 => "umask",
+        167 => "prctl",
+        168 => "getcpu",
+        169 => "gettimeofday",
+        170 => "settimeofday",
+        171 => "adjtimex",
+        172 => "getpid",
+        173 => "getppid",
+        174 => "getuid",
+        175 => "geteuid",
+        176 => "getgid",
+        177 => "getegid",
+        178 => "gettid",
+        179 => "sysinfo",
+        180 => "mq_open",
+        181 => "mq_unlink",
+        182 => "mq_timedsend",
+        183 => "mq_timedreceive",
+        184 => "mq_notify",
+        185 => "mq_getsetattr",
+        186 => "msgget",
+        187 => "msgctl",
+        188 => "msgrcv",
+        189 => "msgsnd",
+        190 => "semget",
+        191 => "semctl",
+        192 => "semtimedop",
+        193 => "semop",
+        194 => "shmget",
+        195 => "shmctl",
+        196 => "shmat",
+        197 => "shmdt",
+        198 => "socket",
+        199 => "socketpair",
+        200 => "bind",
+        201 => "listen",
+        202 => "accept",
+        203 => "connect",
+        204 => "getsockname",
+        205 => "getpeername",
+        206 => "sendto",
+        207 => "recvfrom",
+        208 => "setsockopt",
+        209 => "getsockopt",
+        210 => "shutdown",
+        211 => "sendmsg",
+        212 => "recvmsg",
+        213 => "readahead",
+        214 => "brk",
+        215 => "munmap",
+        216 => "mremap",
+        217 => "add_key",
+        218 => "request_key",
+        219 => "keyctl",
+        220 => "clone",
+        221 => "execve",
+        222 => "mmap",
+        223 => "fadvise64",
+        224 => "swapon",
+        225 => "swapoff",
+        226 => "mprotect",
+        227 => "msync",
+        228 => "mlock",
+        229 => "munlock",
+        230 => "mlockall",
+        231 => "munlockall",
+        232 => "mincore",
+        233 => "madvise",
+        234 => "remap_file_pages",
+        235 => "mbind",
+        236 => "get_mempolicy",
+        237 => "set_mempolicy",
+        238 => "migrate_pages",
+        239 => "move_pages",
+        240 => "rt_tgsigqueueinfo",
+        241 => "perf_event_open",
+        242 => "accept4",
+        243 => "recvmmsg",
+        244 => "arch_specific_syscall",
+        260 => "wait4",
+        261 => "prlimit64",
+        262 => "fanotify_init",
+        263 => "fanotify_mark",
+        264 => "name_to_handle_at",
+        265 => "open_by_handle_at",
+        266 => "clock_adjtime",
+        267 => "syncfs",
+        268 => "setns",
+        269 => "sendmmsg",
+        270 => "process_vm_readv",
+        271 => "process_vm_writev",
+        272 => "kcmp",
+        273 => "finit_module",
+        274 => "sched_setattr",
+        275 => "sched_getattr",
+        276 => "renameat2",
+        277 => "seccomp",
+        278 => "getrandom",
+        279 => "memfd_create",
+        280 => "bpf",
+        281 => "execveat",
+        282 => "userfaultfd",
+        283 => "membarrier",
+        284 => "mlock2",
+        285 => "copy_file_range",
+        286 => "preadv2",
+        287 => "pwritev2",
+        288 => "pkey_mprotect",
+        289 => "pkey_alloc",
+        290 => "pkey_free",
+        291 => "statx",
+        292 => "io_pgetevents",
+        293 => "rseq",
+        294 => "kexec_file_load",
+        424 => "pidfd_send_signal",
+        425 => "io_uring_setup",
+        426 => "io_uring_enter",
+        427 => "io_uring_register",
+        428 => "open_tree",
+        429 => "move_mount",
+        430 => "fsopen",
+        431 => "fsconfig",
+        432 => "fsmount",
+        433 => "fspick",
+        434 => "pidfd_open",
+        435 => "clone3",
+        436 => "close_range",
+        437 => "openat2",
+        438 => "pidfd_getfd",
+        439 => "faccessat2",
+        440 => "process_madvise",
+        441 => "epoll_pwait2",
+        442 => "mount_setattr",
+        443 => "quotactl_fd",
+        444 => "landlock_create_ruleset",
+        445 => "landlock_add_rule",
+        446 => "landlock_restrict_self",
+        447 => "memfd_secret",
+        448 => "process_mrelease",
+        449 => "futex_waitv",
+        450 => "set_mempolicy_home_node",
+        451 => "syscalls",
+        _ => "unknown",
+    }
+}
+
+/// Where `syscall` stopped the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyscallStop {
+    /// Entering the system call `number`.
+    Entry { number: u64 },
+    /// Leaving the system call `number`, which returned `value`.
+    Exit { number: u64, value: u64 },
+    /// Stopped for another reason, as a breakpoint or a signal, or terminated.
+    Other(StepStop),
+}
+
+/// Run the program to the next system call entry or exit, and tell which it is.
+///
+/// The stops at the entry and at the exit of a system call look the same, so they are told apart
+/// by alternating: the stop after an entry is its exit. A value returned by the system call,
+/// `-ENOSYS` included, can't be mistaken for an entry. With `PTRACE_O_TRACESYSGOOD`, they are
+/// reported apart from the `SIGTRAP`s.
+///
+/// # Arguments
+///
+/// * `breakpoints` - The breakpoints armed in the child.
+/// * `tracee` - The child being debugged.
+/// * `in_syscall` - True if the program is stopped at the entry of a system call. Updated at each
+///   system call stop.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the program can't be resumed, waited for or its registers
+/// read, or the error of removing a breakpoint hit on the way.
+pub fn run_to_syscall(breakpoints: &mut BreakpointTable, mut tracee: impl Tracee, in_syscall: &mut bool) -> Result<SyscallStop, DbgError> {
+    tracee.syscall().map_err(DbgError::ptrace("run to the next system call"))?;
+    let status = tracee.wait().map_err(DbgError::ptrace("wait for the program"))?;
+    if let WaitStatus::Stopped(_, Signal::SIGTRAP) | WaitStatus::PtraceSyscall(_) = status {
+        let regs = tracee.getregs().map_err(DbgError::ptrace("read the registers"))?;
+        if matches!(status, WaitStatus::PtraceSyscall(_)) || !is_breakpoint(breakpoints, crate::working::trap_address(regs.pc())) {
+            *in_syscall = !*in_syscall;
+            return Ok(if *in_syscall {
+                SyscallStop::Entry { number: regs.syscall_number() }
+            } else {
+                SyscallStop::Exit { number: regs.syscall_number(), value: regs.return_value() }
+            });
+        }
+    }
+    match classify_stop(breakpoints, tracee, status)? {
+        Some((stop, _)) => Ok(SyscallStop::Other(stop)),
+        None => Ok(SyscallStop::Other(StepStop::Done)),
+    }
+}
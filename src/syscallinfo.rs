@@ -0,0 +1,293 @@
+use crate::arch::{Arch, Registers};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::memory;
+use crate::procfs::{self, describe_flags, ThreadSyscall};
+use crate::siginfo::signal_name;
+use crate::state::{InferiorState, StopReason};
+use crate::tracee::Tracee;
+use nix::errno::Errno;
+use nix::libc;
+use serde_json::json;
+
+/// The path arguments are shown up to this many bytes, then `...`.
+const MAX_PATH_LEN: usize = 256;
+
+/// The directory descriptor of the `*at` system calls that stands for the current directory.
+const AT_FDCWD: i64 = -100;
+
+/// How an argument of a system call is shown, as strace shows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg {
+    /// A signed integer: a descriptor, a count, a size.
+    Int,
+    /// A pointer or a value of bits, `NULL` for 0.
+    Hex,
+    /// A directory descriptor, `AT_FDCWD` for the current directory.
+    DirFd,
+    /// A NUL terminated path, read in the memory of the program.
+    Path,
+    /// The flags of `open`, as `O_RDONLY|O_CLOEXEC`.
+    OpenFlags,
+    /// Permissions, in octal.
+    Mode,
+    /// A signal number, as `SIGTERM`.
+    Signal,
+    /// A pointer to a `struct timespec`, as `{tv_sec=1, tv_nsec=0}`.
+    Timespec,
+}
+
+/// The arguments of the system calls decoded, by name. The others show their 6 arguments in
+/// hexadecimal.
+const SIGNATURES: &[(&str, &[Arg])] = &[
+    ("read", &[Arg::Int, Arg::Hex, Arg::Int]),
+    ("write", &[Arg::Int, Arg::Hex, Arg::Int]),
+    ("pread64", &[Arg::Int, Arg::Hex, Arg::Int, Arg::Int]),
+    ("pwrite64", &[Arg::Int, Arg::Hex, Arg::Int, Arg::Int]),
+    ("readv", &[Arg::Int, Arg::Hex, Arg::Int]),
+    ("writev", &[Arg::Int, Arg::Hex, Arg::Int]),
+    ("open", &[Arg::Path, Arg::OpenFlags, Arg::Mode]),
+    ("openat", &[Arg::DirFd, Arg::Path, Arg::OpenFlags, Arg::Mode]),
+    ("creat", &[Arg::Path, Arg::Mode]),
+    ("close", &[Arg::Int]),
+    ("lseek", &[Arg::Int, Arg::Int, Arg::Int]),
+    ("ioctl", &[Arg::Int, Arg::Hex, Arg::Hex]),
+    ("fcntl", &[Arg::Int, Arg::Int, Arg::Hex]),
+    ("dup2", &[Arg::Int, Arg::Int]),
+    ("dup3", &[Arg::Int, Arg::Int, Arg::OpenFlags]),
+    ("pipe", &[Arg::Hex]),
+    ("pipe2", &[Arg::Hex, Arg::OpenFlags]),
+    ("mmap", &[Arg::Hex, Arg::Int, Arg::Hex, Arg::Hex, Arg::Int, Arg::Hex]),
+    ("munmap", &[Arg::Hex, Arg::Int]),
+    ("stat", &[Arg::Path, Arg::Hex]),
+    ("lstat", &[Arg::Path, Arg::Hex]),
+    ("fstat", &[Arg::Int, Arg::Hex]),
+    ("newfstatat", &[Arg::DirFd, Arg::Path, Arg::Hex, Arg::Hex]),
+    ("access", &[Arg::Path, Arg::Int]),
+    ("mkdir", &[Arg::Path, Arg::Mode]),
+    ("unlink", &[Arg::Path]),
+    ("chdir", &[Arg::Path]),
+    ("execve", &[Arg::Path, Arg::Hex, Arg::Hex]),
+    ("poll", &[Arg::Hex, Arg::Int, Arg::Int]),
+    ("ppoll", &[Arg::Hex, Arg::Int, Arg::Timespec, Arg::Hex, Arg::Int]),
+    ("select", &[Arg::Int, Arg::Hex, Arg::Hex, Arg::Hex, Arg::Hex]),
+    ("pselect6", &[Arg::Int, Arg::Hex, Arg::Hex, Arg::Hex, Arg::Timespec, Arg::Hex]),
+    ("epoll_wait", &[Arg::Int, Arg::Hex, Arg::Int, Arg::Int]),
+    ("epoll_pwait", &[Arg::Int, Arg::Hex, Arg::Int, Arg::Int, Arg::Hex, Arg::Int]),
+    ("nanosleep", &[Arg::Timespec, Arg::Hex]),
+    ("clock_nanosleep", &[Arg::Int, Arg::Int, Arg::Timespec, Arg::Hex]),
+    ("pause", &[]),
+    ("wait4", &[Arg::Int, Arg::Hex, Arg::Hex, Arg::Hex]),
+    ("waitid", &[Arg::Int, Arg::Int, Arg::Hex, Arg::Hex, Arg::Hex]),
+    ("accept", &[Arg::Int, Arg::Hex, Arg::Hex]),
+    ("accept4", &[Arg::Int, Arg::Hex, Arg::Hex, Arg::Hex]),
+    ("connect", &[Arg::Int, Arg::Hex, Arg::Int]),
+    ("recvfrom", &[Arg::Int, Arg::Hex, Arg::Int, Arg::Hex, Arg::Hex, Arg::Hex]),
+    ("sendto", &[Arg::Int, Arg::Hex, Arg::Int, Arg::Hex, Arg::Hex, Arg::Int]),
+    ("recvmsg", &[Arg::Int, Arg::Hex, Arg::Hex]),
+    ("sendmsg", &[Arg::Int, Arg::Hex, Arg::Hex]),
+    ("futex", &[Arg::Hex, Arg::Int, Arg::Int, Arg::Timespec, Arg::Hex, Arg::Int]),
+    ("flock", &[Arg::Int, Arg::Int]),
+    ("kill", &[Arg::Int, Arg::Signal]),
+    ("tgkill", &[Arg::Int, Arg::Int, Arg::Signal]),
+    ("rt_sigsuspend", &[Arg::Hex, Arg::Int]),
+    ("rt_sigtimedwait", &[Arg::Hex, Arg::Hex, Arg::Timespec, Arg::Int]),
+    ("getrandom", &[Arg::Hex, Arg::Int, Arg::Hex]),
+    ("exit", &[Arg::Int]),
+    ("exit_group", &[Arg::Int]),
+];
+
+/// The system calls returning an address, shown in hexadecimal.
+const ADDRESS_RESULTS: &[&str] = &["mmap", "mmap2", "mremap", "brk", "shmat"];
+
+/// The codes the kernel returns from a system call interrupted by a signal, restarted after the
+/// signal unless its handler says otherwise, with the description strace gives them.
+const RESTART_CODES: &[(i64, &str, &str)] = &[
+    (512, "ERESTARTSYS", "To be restarted if SA_RESTART is set"),
+    (513, "ERESTARTNOINTR", "To be restarted"),
+    (514, "ERESTARTNOHAND", "To be restarted if no handler"),
+    (516, "ERESTART_RESTARTBLOCK", "Interrupted by signal"),
+];
+
+/// The arguments of a system call decoded, by name.
+pub fn signature(name: &str) -> Option<&'static [Arg]> {
+    SIGNATURES.iter().find(|(known, _)| *known == name).map(|(_, args)| *args)
+}
+
+/// A register or an argument as the signed value of the program, 32 bits for a 32-bit program.
+fn signed(arch: Arch, value: u64) -> i64 {
+    if arch.is_i386() {
+        value as u32 as i32 as i64
+    } else {
+        value as i64
+    }
+}
+
+/// Format an argument of a system call as strace does, reading the paths and structures it points
+/// to in the memory of the program. A pointer that can't be read is shown in hexadecimal.
+pub fn format_argument(tracee: &(impl Tracee + ?Sized), arch: Arch, value: u64, arg: Arg) -> String {
+    let hex = || if value == 0 { "NULL".to_string() } else { format!("{:#x}", value) };
+    match arg {
+        Arg::Int => signed(arch, value).to_string(),
+        Arg::Hex => hex(),
+        Arg::DirFd if signed(arch, value) == AT_FDCWD => "AT_FDCWD".to_string(),
+        Arg::DirFd => signed(arch, value).to_string(),
+        Arg::Path if value == 0 => hex(),
+        Arg::Path => match memory::read_c_bytes(tracee, value, MAX_PATH_LEN) {
+            Ok(bytes) => {
+                let truncated = if bytes.len() == MAX_PATH_LEN { "..." } else { "" };
+                format!("{:?}{}", String::from_utf8_lossy(&bytes), truncated)
+            }
+            Err(_) => hex(),
+        },
+        Arg::OpenFlags => describe_flags(value as u32),
+        Arg::Mode => format!("0{:o}", value & 0o7777),
+        Arg::Signal => signal_name(value as i32),
+        Arg::Timespec if value == 0 => hex(),
+        Arg::Timespec => {
+            let size = arch.pointer_size();
+            let fields = arch.read_pointer(tracee, value).and_then(|seconds| Ok((seconds, arch.read_pointer(tracee, value + size)?)));
+            match fields {
+                Ok((seconds, nanoseconds)) => format!("{{tv_sec={}, tv_nsec={}}}", signed(arch, seconds), signed(arch, nanoseconds)),
+                Err(_) => hex(),
+            }
+        }
+    }
+}
+
+/// Format a system call with its arguments as strace does, as in
+/// `openat(AT_FDCWD, "/etc/hosts", O_RDONLY|O_CLOEXEC)`: the arguments of the system calls
+/// known are decoded, the 6 arguments of the others shown in hexadecimal.
+pub fn format_call(tracee: &(impl Tracee + ?Sized), arch: Arch, number: u64, args: &[u64; 6]) -> String {
+    let name = match arch.syscall_name(number) {
+        "unknown" => format!("syscall_{}", number),
+        name => name.to_string(),
+    };
+    let arguments: Vec<String> = match signature(&name) {
+        Some(signature) => {
+            // The mode of open is only used to create a file.
+            let creates = |flags: u64| flags as i32 & (libc::O_CREAT | libc::O_TMPFILE) != 0;
+            let count = match name.as_str() {
+                "open" if !creates(args[1]) => 2,
+                "openat" if !creates(args[2]) => 3,
+                _ => signature.len(),
+            };
+            signature[..count].iter().zip(args).map(|(arg, value)| format_argument(tracee, arch, *value, *arg)).collect()
+        }
+        None => args.iter().map(|value| format!("{:#x}", value)).collect(),
+    };
+    format!("{}({})", name, arguments.join(", "))
+}
+
+/// Returns true if `rax` is what a system call interrupted by a signal returns: a code to restart
+/// it, or `-EINTR`.
+pub fn is_interrupted(arch: Arch, rax: u64) -> bool {
+    let value = signed(arch, rax);
+    value == -(Errno::EINTR as i64) || RESTART_CODES.iter().any(|(code, _, _)| value == -code)
+}
+
+/// Format what a system call returned as strace does, as `= 3`, `= -1 ENOENT (No such file or
+/// directory)`, or `= ? ERESTARTSYS (To be restarted if SA_RESTART is set)` when a signal
+/// interrupted it.
+///
+/// # Arguments
+///
+/// * `arch` - The architecture of the program.
+/// * `name` - The name of the system call.
+/// * `rax` - The register with the value returned.
+pub fn format_result(arch: Arch, name: &str, rax: u64) -> String {
+    let value = signed(arch, rax);
+    if let Some((_, code, description)) = RESTART_CODES.iter().find(|(code, _, _)| value == -code) {
+        return format!("= ? {} ({})", code, description);
+    }
+    if (-4095..0).contains(&value) {
+        let errno = Errno::from_raw(-value as i32);
+        return format!("= -1 {:?} ({})", errno, errno.desc());
+    }
+    if ADDRESS_RESULTS.contains(&name) {
+        return format!("= {:#x}", arch.return_value(rax));
+    }
+    format!("= {}", value)
+}
+
+/// What the current thread is doing, read in `/proc`.
+///
+/// # Errors
+///
+/// Returns an error for a core file, or if the file can't be read.
+fn current(debugger: &Debugger) -> Result<ThreadSyscall, String> {
+    if debugger.core.is_some() {
+        return Err(DbgError::NotSupportedOnCore.to_string());
+    }
+    procfs::read_thread_syscall(debugger.child, debugger.thread)
+        .map_err(|err| format!("Could not read /proc/{}/task/{}/syscall: {}", debugger.child, debugger.thread, err))
+}
+
+/// Show the system call the current thread is in, with its arguments decoded as strace does and
+/// what it returned: where a syscall catchpoint stopped it, or, when the program is interrupted
+/// (Ctrl-C), the system call it was blocked in (`info syscall`).
+///
+/// # Returns
+///
+/// False if the program is not being run, or `/proc` can't be read.
+pub fn info_syscall(debugger: &mut Debugger) -> bool {
+    let InferiorState::Stopped { reason } = debugger.state else {
+        errln!(debugger, "The program is not being run.");
+        return false;
+    };
+    let (number, args, sp, pc) = match current(debugger) {
+        Ok(ThreadSyscall::In { number, args, sp, pc }) => (number, args, sp, pc),
+        Ok(ThreadSyscall::NotIn { sp, pc }) => {
+            json::set_data(debugger, |debugger| json!({ "thread": debugger.thread.as_raw(), "syscall": null, "sp": json::address(sp), "pc": json::address(pc) }));
+            outln!(debugger, "Thread {} is not in a syscall.", debugger.thread);
+            return true;
+        }
+        Ok(ThreadSyscall::Running) => {
+            errln!(debugger, "Thread {} is running.", debugger.thread);
+            return false;
+        }
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let arch = debugger.arch;
+    let call = format_call(debugger.tracee().as_ref(), arch, number, &args);
+    // At the entry of a system call, it has returned nothing yet.
+    let result = match reason {
+        StopReason::Syscall { entry: true, .. } => None,
+        _ => debugger.tracee().getregs().ok().map(|regs| format_result(arch, arch.syscall_name(number), regs.return_value())),
+    };
+    json::set_data(debugger, |debugger| {
+        json!({
+            "thread": debugger.thread.as_raw(),
+            "syscall": arch.syscall_name(number),
+            "number": number,
+            "arguments": args.iter().map(|arg| json::address(*arg)).collect::<Vec<_>>(),
+            "call": call,
+            "result": result,
+            "sp": json::address(sp),
+            "pc": json::address(pc),
+        })
+    });
+    match result {
+        Some(result) => outln!(debugger, "Thread {} is in syscall {} ({}): {} {}", debugger.thread, arch.syscall_name(number), number, call, result),
+        None => outln!(debugger, "Thread {} is in syscall {} ({}): {}", debugger.thread, arch.syscall_name(number), number, call),
+    }
+    true
+}
+
+/// The line printed at a signal stop when the signal interrupted a blocked system call, as when
+/// Ctrl-C interrupts a program waiting in `read`: `Interrupted in read(0, 0x7ffd3b2c1e40, 4096).`
+///
+/// # Returns
+///
+/// None if the thread was not in a system call, or the system call was not interrupted.
+pub fn interrupted_hint(debugger: &Debugger) -> Option<String> {
+    let ThreadSyscall::In { number, args, .. } = current(debugger).ok()? else {
+        return None;
+    };
+    let returned = debugger.tracee().getregs().ok()?.return_value();
+    is_interrupted(debugger.arch, returned).then(|| format!("Interrupted in {}.", format_call(debugger.tracee().as_ref(), debugger.arch, number, &args)))
+}
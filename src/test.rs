@@ -1,51 +1,2589 @@
 #[cfg(test)]
 mod tests {
-    use super::*;
     use std::{io::{Read, Write}, process::{Command, Stdio}};
+    use nix::sys::ptrace;
+    use nix::sys::signal::Signal;
+    use nix::sys::wait::{waitpid, WaitStatus};
+    use nix::unistd::Pid;
+    use crate::arch::Arch;
+    use crate::tracee::MockTracee;
+    use crate::debugger::Debugger;
+    use crate::working::set_breakpoint;
+
+    /// Skip the rest of the test where ptrace is not available, as `require_ptrace!` does for
+    /// the integration tests.
+    macro_rules! require_ptrace {
+        () => {
+            if !crate::launch::ptrace_available() {
+                eprintln!("Skipping: ptrace is not available here.");
+                return;
+            }
+        };
+    }
+
+    #[test]
+    fn test_dispatch_registers() {
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("locals"), &[]).unwrap();
+        let outcome = crate::dispatch(&mut debugger, "r").unwrap();
+        let text = outcome.output.text();
+        assert!(text.contains("Registers:"), "Debugger failed to display register states: {}", text);
+        assert!(text.contains("rax:") && text.contains("rsp:") && text.contains("rip:"), "Debugger failed to display all registers: {}", text);
+        assert_eq!(outcome.control, crate::Control::Continue);
+        assert!(outcome.output.errors().is_empty());
+    }
 
     #[test]
-    fn test_command_execution() {
-        let output = execute_debugger_command("r").expect("Failed to execute debugger command");
-        assert!(output.contains("Registers:"), "Debugger failed to display register states");
+    fn test_dispatch_memory() {
+        use crate::error::DbgError;
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("locals"), &[]).unwrap();
+        let rsp = debugger.registers().unwrap().rsp;
+        let value = u64::from_le_bytes(debugger.read_memory(rsp, 8).unwrap().try_into().unwrap());
+        let outcome = crate::dispatch(&mut debugger, "m $rsp").unwrap();
+        assert_eq!(outcome.output.text(), format!("{:#018x}\n", value));
+        let err = crate::dispatch(&mut debugger, "m 0x12345678").unwrap_err();
+        let DbgError::Command { command, output } = &err else {
+            panic!("Unexpected error {:?}", err);
+        };
+        assert_eq!(command, "m 0x12345678");
+        assert_eq!(output.text(), "");
+        assert!(err.to_string().starts_with("Cannot access memory at address 0x12345678"), "Unexpected error {}", err);
     }
 
     #[test]
-    fn test_register_display() {
-        let output = execute_debugger_command("r").expect("Failed to execute debugger command");
-        assert!(output.contains("rax:") && output.contains("rsp:"), "Debugger failed to display all registers");
+    fn test_dispatch_breakpoints() {
+        use crate::error::DbgError;
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("locals"), &[]).unwrap();
+        let text = crate::dispatch(&mut debugger, "b main").unwrap().output.text();
+        let address = debugger.breakpoints[0];
+        assert_eq!(text, format!("Breakpoint 1 set at {:#x}\n", address));
+        let text = crate::dispatch(&mut debugger, "c").unwrap().output.text();
+        assert!(text.contains("Breakpoint 1, main at "), "Unexpected output {}", text);
+        assert!(text.contains("locals.c:24"), "Unexpected output {}", text);
+        assert_eq!(debugger.registers().unwrap().rip, address);
+        let err = crate::dispatch(&mut debugger, "b nowhere_at_all").unwrap_err();
+        assert!(matches!(err, DbgError::Command { .. }), "Unexpected error {:?}", err);
+        assert_eq!(debugger.breakpoints.len(), 1, "The failed breakpoint is not recorded");
+        crate::dispatch(&mut debugger, "c").unwrap();
+        assert_eq!(debugger.exit_status(), Some(0));
     }
 
     #[test]
-    fn test_memory_display() {
-        let output = execute_debugger_command("m 0x12345678").expect("Failed to execute debugger command");
-        assert!(output.contains("Memory content at address 0x12345678:"), "Debugger failed to display memory content");
+    fn test_dispatch_help_unknown_and_quit() {
+        use crate::error::DbgError;
+        use crate::Control;
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("locals"), &[]).unwrap();
+        let text = crate::dispatch(&mut debugger, "h").unwrap().output.text();
+        assert!(text.contains("Available commands:"), "Debugger failed to display help information: {}", text);
+        let err = crate::dispatch(&mut debugger, "invalid").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown command: invalid", "Debugger failed to handle unknown commands");
+        assert!(matches!(err, DbgError::Command { command, .. } if command == "invalid"));
+        // The output of a failed command is kept with its error.
+        let err = crate::dispatch(&mut debugger, "echo before; invalid").unwrap_err();
+        let DbgError::Command { output, .. } = err else {
+            panic!("Unexpected error {:?}", err);
+        };
+        assert_eq!(output.text(), "before\n");
+        assert_eq!(output.errors(), ["Unknown command: invalid"]);
+        let outcome = crate::dispatch(&mut debugger, "set pagination off; quit; echo skipped").unwrap();
+        assert_eq!(outcome.control, Control::Quit);
+        assert!(!outcome.output.text().contains("skipped"), "Nothing runs after quit: {}", outcome.output.text());
+        assert!(!debugger.output.pagination(), "The settings changed by the command apply to the session");
+        assert!(nix::sys::signal::kill(debugger.child, None).is_err(), "quit killed the program");
     }
 
     #[test]
-    fn test_help_command() {
-        let output = execute_debugger_command("h").expect("Failed to execute debugger command");
-        assert!(output.contains("Available commands:"), "Debugger failed to display help information");
+    fn test_mapped_files_grouping() {
+        let maps = crate::maps::parse_maps(
+            "55d0c8a00000-55d0c8a01000 r--p 00000000 fd:01 1234 /usr/bin/prog\n\
+             55d0c8a01000-55d0c8a02000 r-xp 00001000 fd:01 1234 /usr/bin/prog\n\
+             55d0c9000000-55d0c9021000 rw-p 00000000 00:00 0 [heap]\n\
+             7f0000000000-7f0000028000 r--p 00000000 fd:01 42 /usr/lib/libc.so.6\n\
+             7f0000028000-7f00001bd000 r-xp 00028000 fd:01 42 /usr/lib/libc.so.6\n\
+             7f00001bd000-7f00001be000 rw-p 00000000 00:00 0\n",
+        );
+        assert_eq!(maps.len(), 6, "Every maps line should be parsed");
+        let files = crate::maps::mapped_files(&maps);
+        assert_eq!(files.len(), 2, "Contiguous mappings of the same file should be grouped");
+        assert_eq!(files[1].path, "/usr/lib/libc.so.6");
+        assert_eq!((files[1].start, files[1].end), (0x7f0000000000, 0x7f00001bd000));
+        assert_eq!(files[1].base, Some(0x7f0000000000));
+    }
+
+    #[test]
+    fn test_breakpoint_in_shared_library() {
+        let path = fixture("malloc");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+
+        let main = debugger.resolve_symbol("main").expect("main should be found in the executable");
+        run_to_breakpoint(child, main);
+
+        let malloc = debugger.resolve_symbol("malloc").expect("malloc should be found in libc");
+        let (_, offset, object) = debugger.symbolize(malloc).expect("malloc should be symbolized");
+        assert_eq!(offset, 0);
+        assert!(object.contains("libc"), "malloc should be defined by libc, not {}", object);
+        run_to_breakpoint(child, malloc);
+
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
     }
 
     #[test]
-    fn test_invalid_commands() {
-        let output = execute_debugger_command("invalid").expect("Failed to execute debugger command");
-        assert!(output.contains("Unknown command"), "Debugger failed to handle unknown commands");
+    fn test_link_map_of_i386_program() {
+        use crate::sharedlib::{dynamic_debug, read_link_map, LinkMapEntry};
+        // The fixtures are 64-bit or static: a 32-bit _DYNAMIC, r_debug and link_map chain in memory.
+        let mut memory = vec![0u8; 0x3040];
+        let mut put = |address: u64, words: &[u32]| {
+            for (index, word) in words.iter().enumerate() {
+                let at = (address - 0x1000) as usize + 4 * index;
+                memory[at..at + 4].copy_from_slice(&word.to_le_bytes());
+            }
+        };
+        // DT_NEEDED, DT_DEBUG, DT_NULL.
+        put(0x1000, &[1, 0x10, 21, 0x2000, 0, 0]);
+        // r_version, r_map, r_brk, r_state.
+        put(0x2000, &[1, 0x3000, 0x1234, 0]);
+        // l_addr, l_name, l_ld, l_next, l_prev.
+        put(0x3000, &[0, 0x4000, 0, 0x3100, 0]);
+        put(0x3100, &[0xf7d0_0000, 0x4010, 0, 0, 0x3000]);
+        memory[0x3010..0x301e].copy_from_slice(b"/lib/libc.so.6");
+        let tracee = MockTracee::new(0x1000, &memory);
+        assert_eq!(dynamic_debug(&tracee, Arch::I386, 0x1000), Some(0x2000));
+        assert_eq!(dynamic_debug(&tracee, Arch::I386, 0x1008), Some(0x2000));
+        let entries = read_link_map(&tracee, Arch::I386, 0x2000);
+        assert_eq!(
+            entries,
+            [
+                LinkMapEntry { name: String::new(), bias: 0 },
+                LinkMapEntry { name: "/lib/libc.so.6".to_string(), bias: 0xf7d0_0000 }
+            ]
+        );
     }
 
-    fn execute_debugger_command(command: &str) -> Result<String, std::io::Error> {
+    #[test]
+    fn test_ltrace_plt_stubs() {
+        let path = fixture("malloc");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+
+        let names: Vec<String> = debugger.executable().unwrap().plt_entries.iter().map(|entry| entry.name.clone()).collect();
+        assert!(names.iter().any(|name| name == "malloc"), "No PLT stub for malloc in {:?}", names);
+        assert!(crate::ltrace::enable(&mut debugger, Some(&["memcpy"])).is_err());
+
+        assert_eq!(crate::ltrace::enable(&mut debugger, Some(&["malloc", "free"])), Ok(2));
+        assert_eq!(debugger.cont(), Ok(crate::step::StepStop::Exited(0)));
+        assert_eq!(debugger.ltrace.calls.get("malloc"), Some(&1));
+        assert_eq!(debugger.ltrace.calls.get("free"), Some(&1));
+        assert_eq!(debugger.ltrace.calls.get("puts"), None);
+    }
+
+    #[test]
+    fn test_line_table_lookup() {
+        let path = fixture("malloc");
+        let symbols = crate::symbols::ObjectSymbols::load(&path).unwrap();
+        let main = symbols.symbols.iter().find(|sym| sym.name == "main").expect("main should be defined");
+        let debug_info = crate::debuginfo::DebugInfo::load(&path).expect("The fixture is built with -g");
+        let location = debug_info.find_location(main.address).expect("main should have line information");
+        assert!(location.file.ends_with("tests/fixtures/malloc.c"), "Unexpected file {}", location.file);
+        assert_eq!(location.line, 4);
+        assert_eq!(debug_info.find_file("malloc.c"), Some(location.file));
+    }
+
+    #[test]
+    fn test_line_address_translation() {
+        let path = fixture("locals");
+        let debug_info = crate::debuginfo::DebugInfo::load(&path).unwrap();
+        let (line, start) = debug_info.find_line("locals.c", 20).unwrap();
+        assert_eq!(line, 20);
+        let (location, range_start, end) = debug_info.line_range(start).unwrap();
+        assert_eq!((location.line, range_start), (20, start));
+        assert!(end > start);
+        // Addresses between two line table rows belong to the previous row.
+        assert_eq!(debug_info.line_range(end - 1).unwrap().1, start);
+        // A blank line has no code, the next line with code is reported instead.
+        assert_eq!(debug_info.find_line("locals.c", 22).map(|(line, _)| line), Some(23));
+        assert_eq!(debug_info.find_line("locals.c", 99), None);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        use crate::disasm::{disassemble, read_code, DisassemblyFlavor};
+        let bytes = [0x55, 0x48, 0x89, 0xe5, 0xb8, 0x01, 0x00, 0x00, 0x00, 0xc3, 0x48];
+        let instructions = disassemble(&bytes, 0x1000, 10, DisassemblyFlavor::Intel, Arch::X86_64);
+        let text: Vec<&str> = instructions.iter().map(|instruction| instruction.text.as_str()).collect();
+        assert_eq!(text, ["push rbp", "mov rbp, rsp", "mov eax, 1", "ret"], "The truncated last instruction is dropped");
+        assert_eq!(instructions[2].address, 0x1004);
+        assert_eq!(instructions[2].bytes, [0xb8, 0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(disassemble(&bytes, 0x1000, 2, DisassemblyFlavor::Intel, Arch::X86_64).len(), 2);
+        // call 0x1005; lea rax, [rip+0x10]; jmp 0x1000
+        let bytes = [0xe8, 0x00, 0x00, 0x00, 0x00, 0x48, 0x8d, 0x05, 0x10, 0x00, 0x00, 0x00, 0xeb, 0xf2];
+        let instructions = disassemble(&bytes, 0x1000, 10, DisassemblyFlavor::Intel, Arch::X86_64);
+        assert_eq!(instructions[0].branch_target, Some(0x1005));
+        assert_eq!(instructions[1].memory_address, Some(0x100c + 0x10));
+        assert_eq!(instructions[1].text, "lea rax, [rip+0x10]");
+        assert_eq!(instructions[2].branch_target, Some(0x1000));
+        assert_eq!(instructions[2].memory_address, None);
+
+        let mov = [0x48, 0xc7, 0xc0, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(disassemble(&mov, 0x1000, 1, DisassemblyFlavor::Intel, Arch::X86_64)[0].text, "mov rax, 1");
+        assert_eq!(disassemble(&mov, 0x1000, 1, DisassemblyFlavor::Att, Arch::X86_64)[0].text, "movq $1, %rax");
+        assert_eq!(DisassemblyFlavor::parse("att"), Some(DisassemblyFlavor::Att));
+        assert_eq!(DisassemblyFlavor::parse("masm"), None);
+
+        use crate::disasm::branch_taken;
+        use iced_x86::ConditionCode;
+        let zero_flag = 1 << 6;
+        let (sign_flag, overflow_flag) = (1 << 7, 1 << 11);
+        assert_eq!(branch_taken(ConditionCode::ne, 0), Some((true, "ZF=0".to_string())));
+        assert_eq!(branch_taken(ConditionCode::ne, zero_flag), Some((false, "ZF=1".to_string())));
+        assert_eq!(branch_taken(ConditionCode::l, sign_flag), Some((true, "SF=1, OF=0".to_string())));
+        assert_eq!(branch_taken(ConditionCode::l, sign_flag | overflow_flag).map(|(taken, _)| taken), Some(false));
+        assert_eq!(branch_taken(ConditionCode::le, zero_flag).map(|(taken, _)| taken), Some(true));
+        assert_eq!(branch_taken(ConditionCode::None, 0), None);
+        // jne 0x1010
+        let jne = disassemble(&[0x75, 0x0e], 0x1000, 1, DisassemblyFlavor::Intel, Arch::X86_64);
+        assert_eq!(jne[0].condition, Some(ConditionCode::ne));
+        assert_eq!(jne[0].branch_target, Some(0x1010));
+        // push es is invalid in 64-bit mode, decoding resumes with the nop after it.
+        let text: Vec<String> = disassemble(&[0x06, 0x90], 0x1000, 10, DisassemblyFlavor::Intel, Arch::X86_64).into_iter().map(|i| i.text).collect();
+        assert_eq!(text, [".byte 0x06", "nop"]);
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let inspect = debugger.resolve_symbol("inspect").unwrap();
+        let original = read_code(&child, &debugger.breakpoint_table, inspect, 16).unwrap();
+        set_breakpoint(&mut debugger.breakpoint_table, child, inspect).unwrap();
+        assert_eq!(crate::memory::read_bytes(&child, inspect, 1).unwrap(), [0xcc]);
+        assert_eq!(read_code(&child, &debugger.breakpoint_table, inspect, 16).unwrap(), original, "Breakpoints are shown with the original bytes");
+        crate::working::remove_breakpoint(&mut debugger.breakpoint_table, child, inspect).unwrap();
+        run_to_breakpoint(child, inspect);
+        let line = crate::disasm::current_instruction(&mut debugger, inspect).unwrap();
+        assert!(line.starts_with("=> "), "The current instruction is marked: {}", line);
+        assert!(line.contains("<inspect>") && line.ends_with("push rbp"), "Unexpected line {}", line);
+        let listing = scratch("inspect.asm");
+        crate::disasm::disassemble_command(&mut debugger, &["inspect", "+4", ">", &listing]);
+        let text = std::fs::read_to_string(&listing).expect("The listing should be written");
+        assert_eq!(text.lines().count(), 2, "push rbp and mov rbp, rsp: {}", text);
+        assert!(text.lines().last().unwrap().ends_with("mov rbp, rsp"));
+        // A breakpoint in the middle of mov rbp, rsp (48 89 e5).
+        set_breakpoint(&mut debugger.breakpoint_table, child, inspect + 2).unwrap();
+        debugger.breakpoints.push(inspect + 2);
+        assert_eq!(debugger.breakpoint_number(inspect + 2), Some(1));
+        assert_eq!(crate::disasm::read_raw_code(&child, inspect + 2, 1).unwrap(), [0xcc]);
+        let containing = crate::disasm::containing_instruction(&mut debugger, inspect + 2).unwrap();
+        assert_eq!((containing.address, containing.text.as_str()), (inspect + 1, "mov rbp, rsp"));
+        let instructions = disassemble(&read_code(&child, &debugger.breakpoint_table, inspect, 4).unwrap(), inspect, 2, DisassemblyFlavor::Intel, Arch::X86_64);
+        let line = crate::disasm::format_instruction(&mut debugger, &instructions, 1, None);
+        assert!(line.ends_with(&format!("breakpoint B1 at {:#x} is inside this instruction", inspect + 2)), "{}", line);
+        debugger.breakpoints.push(inspect + 1);
+        set_breakpoint(&mut debugger.breakpoint_table, child, inspect + 1).unwrap();
+        let line = crate::disasm::format_instruction(&mut debugger, &instructions, 1, None);
+        assert!(line.starts_with("B2 "), "Breakpoint 2 is on the instruction: {}", line);
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_end_of_input_quits() {
+        let path = fixture("locals");
+        require_ptrace!();
         let mut debugger_process = Command::new("target/debug/dbg_rust")
+            .arg(&path)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .spawn()?;
-        
-        debugger_process.stdin.as_mut().unwrap().write_all(command.as_bytes())?;
-        
+            .spawn()
+            .expect("Failed to launch the debugger");
+        // A blank line, then the end of the input.
+        debugger_process.stdin.take().unwrap().write_all(b"\n").unwrap();
         let mut output = String::new();
-        debugger_process.stdout.as_mut().unwrap().read_to_string(&mut output)?;
-        
-        debugger_process.wait()?;
-        
-        Ok(output)
+        debugger_process.stdout.as_mut().unwrap().read_to_string(&mut output).unwrap();
+        assert!(debugger_process.wait().unwrap().success());
+        assert!(!output.contains("Unknown command"), "A blank line does nothing: {}", output);
+        assert!(output.contains("Exiting the debugger"), "The end of the input quits: {}", output);
+    }
+
+    #[test]
+    fn test_command_scripts() {
+        use crate::options::StartupAction;
+        use crate::script::{script_commands, split_commands, ScriptOnError};
+        let args: Vec<String> = ["-x", "a.gdb", "-ex", "b main", "--command", "b.gdb", "./app"].iter().map(|arg| arg.to_string()).collect();
+        let options = crate::options::parse(&args).unwrap();
+        assert_eq!(options.program_path, "./app");
+        assert_eq!(
+            options.startup,
+            [
+                StartupAction::Script("a.gdb".to_string()),
+                StartupAction::Command("b main".to_string()),
+                StartupAction::Script("b.gdb".to_string())
+            ]
+        );
+        assert!(!options.batch && !options.run_immediately);
+        assert!(crate::options::parse(&["-x".to_string()]).is_err());
+        assert!(crate::options::parse(&[]).is_err());
+        assert_eq!(script_commands("# setup\n\n  b main \nc\n"), [(3, "b main"), (4, "c")]);
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let script = scratch("locals.gdb");
+        std::fs::write(&script, "# stops at the unknown command\nb inspect\nbogus\nb main\n").unwrap();
+        assert!(!crate::script::source(&mut debugger, &script, crate::run_command));
+        assert_eq!(debugger.breakpoints.len(), 1, "The script stopped at the failed command");
+        debugger.script_on_error = ScriptOnError::Continue;
+        assert!(!crate::script::source(&mut debugger, &script, crate::run_command));
+        assert_eq!(debugger.breakpoints.len(), 3, "The commands after the failed one ran");
+        assert!(!crate::run_command(&format!("source {}", script), &mut debugger), "A failed script fails source");
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        assert_eq!(split_commands(r#"b main;c ; print "a;b"; "#), ["b main", "c", r#"print "a;b""#]);
+        assert!(crate::run_command("set backtrace limit 5; b inspect;; c", &mut debugger));
+        assert_eq!((debugger.backtrace_limit, debugger.breakpoints.len()), (5, 1));
+        // The program exits during c, so the rest of the line is skipped.
+        assert!(!crate::run_command("c; set backtrace limit 7", &mut debugger));
+        assert_eq!((debugger.exit_status(), debugger.backtrace_limit), (Some(0), 5));
+    }
+
+    #[test]
+    fn test_batch_mode() {
+        let path = fixture("locals");
+        require_ptrace!();
+        let output = Command::new("target/debug/dbg_rust")
+            .args(["--batch", "-ex", "b inspect", "-ex", "run", "-ex", "bt", &path])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to launch the debugger");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(!stdout.contains("rustdbg>") && !stdout.contains("Child pid"), "Only the output of the commands: {}", stdout);
+        assert!(stdout.lines().any(|line| line.starts_with("#1") && line.contains("in main")), "{}", stdout);
+
+        // The exit status of the program is the one of the debugger.
+        let output = Command::new("target/debug/dbg_rust").args(["--batch", "--run-immediately", &path]).output().unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        let output = Command::new("target/debug/dbg_rust").args(["--batch", "-ex", "bogus", &path]).output().unwrap();
+        assert_eq!(output.status.code(), Some(1), "A failed command fails the batch");
+    }
+
+    #[test]
+    fn test_errors_and_exit_codes() {
+        use crate::error::DbgError;
+        use nix::errno::Errno;
+        use crate::launch::{launch, LaunchSpec};
+        let spec = |path: &str| LaunchSpec { program_path: path.to_string(), ..LaunchSpec::default() };
+        let err = launch(&spec("/nonexistent/program"), &Default::default()).unwrap_err();
+        assert_eq!(err, DbgError::Exec { path: "/nonexistent/program".to_string(), errno: Errno::ENOENT });
+        assert!(err.to_string().contains("ENOENT"), "The failed exec is reported: {}", err);
+        let manifest = format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+        let err = launch(&spec(&manifest), &Default::default()).unwrap_err();
+        assert_eq!(err, DbgError::Exec { path: manifest.clone(), errno: Errno::EACCES }, "Not executable");
+        let elsewhere = LaunchSpec { cwd: Some("/nonexistent".to_string()), ..spec("/bin/true") };
+        assert_eq!(launch(&elsewhere, &Default::default()).unwrap_err(), DbgError::Cwd { path: "/nonexistent".to_string(), errno: Errno::ENOENT });
+        let in_a_file = LaunchSpec { cwd: Some(manifest.clone()), ..spec("/bin/true") };
+        assert_eq!(launch(&in_a_file, &Default::default()).unwrap_err(), DbgError::Cwd { path: manifest, errno: Errno::ENOTDIR });
+        require_ptrace!();
+        let output = Command::new("target/debug/dbg_rust").arg("/nonexistent/program").output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to execute /nonexistent/program"));
+        let output = Command::new("target/debug/dbg_rust").arg("--bogus").output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "Usage error");
+
+        let path = fixture("locals");
+        let output = Command::new("target/debug/dbg_rust").args(["--batch", "-ex", "bogus", "-ex", "b main", &path]).output().unwrap();
+        let (stdout, stderr) = (String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        assert!(stderr.contains("Unknown command: bogus") && !stdout.contains("Unknown command"), "Errors go to stderr: {}", stderr);
+        assert!(stdout.contains("Breakpoint 1 set"), "{}", stdout);
+    }
+
+    #[test]
+    fn test_command_line() {
+        use crate::options::parse;
+        let parse_args = |args: &[&str]| parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>());
+        let config = parse_args(&["--no-aslr", "--stop-at-entry", "./app", "--", "-v", "--", "x"]).unwrap();
+        assert_eq!((config.program_path.as_str(), config.program_args.as_slice()), ("./app", ["-v", "--", "x"].map(String::from).as_slice()));
+        assert!(config.no_aslr && config.stop_at_entry);
+        assert!(parse_args(&["--stop-at-main", "./app"]).unwrap().stop_at_main);
+        assert_eq!(parse_args(&["--", "./app", "1"]).unwrap().program_args, ["1"]);
+        assert_eq!(parse_args(&["-p", "42"]).unwrap().pid, Some(42));
+        assert!(parse_args(&["--version"]).unwrap().version && parse_args(&["-h"]).unwrap().help);
+        for (args, error) in [
+            (&["-p", "42", "./app"][..], "not both"),
+            (&["-p", "0"], "Invalid process ID"),
+            (&["-p", "42", "--no-aslr"], "--no-aslr only applies"),
+            (&["-p", "42", "--stop-at-main"], "--stop-at-main only applies"),
+            (&["-p", "42", "--", "1"], "cannot be given"),
+            (&["./app", "1"], "give its arguments after --"),
+            (&["--bogus"], "Unknown option"),
+        ] {
+            let err = parse_args(args).unwrap_err();
+            assert!(err.contains(error), "{:?}: {}", args, err);
+        }
+
+        // The arguments reach the program, and an attached process is detached, not killed.
+        require_ptrace!();
+        let output = Command::new("target/debug/dbg_rust").args(["--batch", "-ex", "run", "/bin/echo", "--", "a", "b"]).output().unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("a b"));
+        let mut sleeper = Command::new("sleep").arg("10").spawn().unwrap();
+        let mut debugger = Debugger::attach(sleeper.id() as i32).unwrap();
+        assert!(debugger.program_path.ends_with("sleep"), "{}", debugger.program_path);
+        assert!(debugger.attached && debugger.has_live_process());
+        crate::launch::release(&mut debugger);
+        assert!(sleeper.try_wait().unwrap().is_none(), "Still running once detached");
+        sleeper.kill().ok();
+        sleeper.wait().ok();
+    }
+
+    #[test]
+    fn test_history_path() {
+        let path = crate::repl::history_path().expect("HOME is set when running the tests");
+        assert!(path.ends_with("rustdbg/history"), "Unexpected history file {}", path.display());
+        assert!(crate::repl::is_repeatable("si") && crate::repl::is_repeatable("list main"));
+        assert!(!crate::repl::is_repeatable("r") && !crate::repl::is_repeatable("b main"));
+    }
+
+    #[test]
+    fn test_completion() {
+        use crate::repl::complete;
+        let mut index = crate::symbols::SymbolIndex::default();
+        index.functions.extend(["main", "make_node", "free_list", "app::state::update"].map(String::from));
+        index.data.extend(["COUNTER", "config"].map(String::from));
+        let complete_line = |line: &str| complete(line, line.len(), &index);
+
+        assert_eq!(complete_line("disas"), (0, vec!["disassemble".to_string()]));
+        assert!(complete_line("").1.contains(&"breakpoint".to_string()));
+        assert_eq!(complete_line("info break"), (5, vec!["breakpoints".to_string()]));
+        assert_eq!(complete_line("set show-instruction o"), (21, vec!["off".to_string(), "on".to_string()]));
+        assert_eq!(complete_line("set backtrace l").1, ["limit"]);
+        assert_eq!(complete_line("set logging ").1, ["file", "off", "on"]);
+        assert_eq!(complete_line("show disassembly-flavor ").1, Vec::<String>::new());
+        assert_eq!(complete_line("set disassembly-flavor ").1, ["att", "intel"]);
+        assert_eq!(complete_line("set rlimit NO").1, ["NOFILE"]);
+        assert_eq!(complete_line("show rl").1, ["rlimit", "rlimits"]);
+        assert_eq!(complete_line("b ma").1, ["main", "make_node"]);
+        assert_eq!(complete_line("disas --raw app::").1, ["app::state::update"]);
+        assert_eq!(complete_line("print CO").1, ["COUNTER"]);
+        assert!(complete_line("b main ").1.is_empty(), "b takes one location");
+        assert!(complete_line("r m").1.is_empty(), "r takes no argument");
+        // The word under the cursor is completed, not the end of the line.
+        assert_eq!(complete("b ma xyz", 4, &index).1, ["main", "make_node"]);
+
+        let fixtures = format!("{}/tests/fixt", env!("CARGO_MANIFEST_DIR"));
+        let (start, paths) = complete_line(&format!("source {}", fixtures));
+        assert_eq!((start, paths), (7, vec![format!("{}ures/", fixtures)]));
+        let (_, paths) = complete_line(&format!("disas main > {}ures/cra", fixtures));
+        assert_eq!(paths, [format!("{}ures/crash.c", fixtures)]);
+    }
+
+    #[test]
+    fn test_command_table() {
+        use crate::commands::{find_command, suggestions, COMMANDS};
+        let mut names: Vec<&str> = COMMANDS.iter().flat_map(|spec| spec.names.iter().copied()).collect();
+        let count = names.len();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), count, "Every name designates one command");
+        assert!(COMMANDS.iter().all(|spec| !spec.summary.is_empty() && !spec.long_help.is_empty()));
+
+        let breakpoint = find_command("b").expect("b is a command");
+        assert_eq!(breakpoint.name(), "breakpoint");
+        assert_eq!(breakpoint.synopsis(), "b or breakpoint <address|function|file:line>");
+        assert!(breakpoint.long_help.contains("file:line") && breakpoint.long_help.contains("Examples:"));
+        assert!(find_command("frobnicate").is_none());
+
+        assert_eq!(suggestions("brek", names.iter().copied()), ["breakpoint"]);
+        assert_eq!(suggestions("disa", names.iter().copied()), ["disas", "disassemble", "display"]);
+        assert_eq!(suggestions("hlep", names.iter().copied()), ["help"]);
+        assert!(suggestions("zz", names.iter().copied()).is_empty());
+    }
+
+    #[test]
+    fn test_command_abbreviations() {
+        use crate::commands::{commands_starting_with, lookup_command, CommandMatch};
+        use crate::output::Capture;
+        use crate::repl::is_repeatable;
+        use std::{cell::RefCell, rc::Rc};
+        let found = |name: &str| match lookup_command(name) {
+            CommandMatch::Found(spec) => Some(spec.name()),
+            _ => None,
+        };
+        assert_eq!(found("disa"), Some("disassemble"), "disas and disassemble are one command");
+        assert!(matches!(lookup_command("dis"), CommandMatch::Ambiguous), "disassemble and display");
+        assert_eq!(found("backt"), Some("backtrace"));
+        // The short names keep their meaning even though longer names start with them.
+        assert_eq!((found("c"), found("s"), found("n"), found("r"), found("b"), found("m")),
+            (Some("continue"), Some("syscall"), Some("next"), Some("registers"), Some("breakpoint"), Some("memory")));
+        assert!(matches!(lookup_command("d"), CommandMatch::Ambiguous));
+        assert!(matches!(lookup_command("ste"), CommandMatch::Ambiguous));
+        assert!(matches!(lookup_command("frobnicate"), CommandMatch::Unknown));
+        assert!(matches!(lookup_command(""), CommandMatch::Unknown));
+        assert_eq!(commands_starting_with("u"), ["unalias", "undefine", "undisplay", "unlabel", "unwatch", "unwatch-region", "up"]);
+        assert!(is_repeatable("cont") && is_repeatable("ne") && !is_repeatable("bac"));
+
+        let path = fixture("step");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let text = Rc::new(RefCell::new(String::new()));
+        debugger.output = Box::new(Capture::new(text.clone(), false));
+        assert!(crate::run_command("break main; cont", &mut debugger));
+        assert_eq!(debugger.breakpoints.len(), 1);
+        let line = |debugger: &mut Debugger| {
+            let rip = ptrace::getregs(child).unwrap().rip;
+            debugger.source_location(rip).map(|location| location.line)
+        };
+        assert_eq!(line(&mut debugger), Some(9));
+        // `nex` is `next`, which steps over the call on line 10 that `step` would enter.
+        assert!(crate::run_command("nex", &mut debugger));
+        assert_eq!(line(&mut debugger), Some(10));
+        assert!(crate::run_command("nex", &mut debugger));
+        assert_eq!(line(&mut debugger), Some(11));
+        text.borrow_mut().clear();
+        assert!(!crate::run_command("u", &mut debugger));
+        assert!(text.borrow().contains("Ambiguous command \"u\": unalias, undefine, undisplay, unlabel, unwatch, unwatch-region, up."), "{}", text.borrow());
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_aliases() {
+        use crate::alias::{expand, parse_definition, tokenize};
+        use crate::script::split_commands;
+        assert_eq!(tokenize(r#"b "a b" 'c \d' e\"f"#).unwrap(), ["b", "a b", r"c \d", r#"e"f"#]);
+        assert_eq!(tokenize(r#"say "x \"y\"""#).unwrap(), ["say", r#"x "y""#]);
+        assert_eq!(tokenize(r#""" x"#).unwrap(), ["", "x"]);
+        assert!(tokenize(r#""open"#).is_err() && tokenize("'open").is_err());
+        assert_eq!(split_commands(r#"b $1; c;; print "a;b""#), ["b $1", "c", r#"print "a;b""#]);
+
+        assert_eq!(parse_definition(r#"bm = "b $1; c""#).unwrap(), ("bm".to_string(), "b $1; c".to_string()));
+        assert_eq!(parse_definition("w = bt 5").unwrap(), ("w".to_string(), "bt 5".to_string()));
+        assert!(parse_definition("bm b $1").is_err() && parse_definition("b/m = c").is_err());
+
+        let mut aliases = std::collections::BTreeMap::new();
+        aliases.insert("bm".to_string(), "b $1; c".to_string());
+        aliases.insert("w".to_string(), "bt".to_string());
+        aliases.insert("trace".to_string(), "bm $2; w $1".to_string());
+        aliases.insert("mem".to_string(), "m $$rsp $*".to_string());
+        aliases.insert("ping".to_string(), "pong".to_string());
+        aliases.insert("pong".to_string(), "ping".to_string());
+        assert_eq!(expand(&aliases, "bm main").unwrap(), ["b main", "c"]);
+        assert_eq!(expand(&aliases, r#"bm "my func""#).unwrap(), ["b my func", "c"]);
+        assert!(expand(&aliases, "bm").unwrap_err().contains("$1"));
+        // Arguments are appended to an alias without parameters, aliases expand other aliases.
+        assert_eq!(expand(&aliases, "w 3").unwrap(), ["bt 3"]);
+        assert_eq!(expand(&aliases, "trace 4 inspect").unwrap(), ["b inspect", "c", "bt 4"]);
+        assert_eq!(expand(&aliases, "mem a b").unwrap(), ["m $rsp a b"]);
+        assert_eq!(expand(&aliases, "c").unwrap(), ["c"]);
+        assert!(expand(&aliases, "ping").unwrap_err().contains("recursive"));
+    }
+
+    #[test]
+    fn test_colored_output() {
+        use crate::output::{highlight_addresses, Capture};
+        use std::{cell::RefCell, rc::Rc};
+        assert_eq!(highlight_addresses("at 0x1f, a0x2 and 0x"), "at \x1b[36m0x1f\x1b[0m, a0x2 and 0x");
+        assert_eq!(highlight_addresses("\x1b[31m0x1\x1b[0m"), "\x1b[31m0x1\x1b[0m");
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let text = Rc::new(RefCell::new(String::new()));
+        debugger.output = Box::new(Capture::new(text.clone(), true));
+        crate::working::show_registers(&mut debugger).unwrap();
+        assert!(text.borrow().contains("\x1b[32mrip\x1b[0m: \x1b[36m0x"), "Unexpected output {}", text.borrow());
+        assert!(!text.borrow().contains("\x1b[1;33m"), "Nothing changed yet");
+        crate::step::single_step(&mut debugger).unwrap();
+        text.borrow_mut().clear();
+        crate::working::show_registers(&mut debugger).unwrap();
+        assert!(text.borrow().contains("\x1b[32mrip\x1b[0m: \x1b[1;33m0x"), "The pc changed: {}", text.borrow());
+
+        text.borrow_mut().clear();
+        assert!(!crate::run_command("set color off; info frobnicate", &mut debugger));
+        assert!(!text.borrow().contains('\x1b'), "Colors are off: {}", text.borrow());
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_pager() {
+        use crate::pager::Pager;
+        let mut pager = Pager::default();
+        pager.reset((5, 20));
+        let mut full = Vec::new();
+        for line in ["one", "\x1b[36m0x1234\x1b[0m is not wider than it looks", "three"] {
+            pager.write(line);
+            full.push(pager.end_line());
+        }
+        // The second line is 33 characters wide once uncolored, 2 rows, and the last row is for the prompt.
+        assert_eq!(full, [false, false, true]);
+        pager.answer(Some(b'\r'));
+        pager.write("four");
+        assert!(pager.end_line(), "Enter shows a single line");
+        pager.answer(Some(b' '));
+        assert_eq!(pager.shown, 0);
+        pager.write("fi");
+        pager.write("ve");
+        assert!(!pager.end_line());
+        assert!(!pager.quit);
+        pager.answer(Some(b'q'));
+        assert!(pager.quit);
+        pager.reset((5, 20));
+        assert!(!pager.quit && pager.shown == 0);
+        pager.answer(None);
+        assert!(pager.quit, "The rest is dropped when the terminal cannot be read");
+    }
+
+    #[test]
+    fn test_transcript() {
+        use crate::output::{strip_styles, Capture};
+        use crate::transcript::timestamp;
+        use std::time::{Duration, UNIX_EPOCH};
+        use std::{cell::RefCell, rc::Rc};
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01 00:00:00.000");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_millis(1_709_251_199_250)), "2024-02-29 23:59:59.250");
+        assert_eq!(strip_styles("\x1b[1;34mrustdbg> \x1b[0mb \x1b[36m0x1\x1b[0m"), "rustdbg> b 0x1");
+
+        let log = scratch("transcript.log");
+        std::fs::remove_file(&log).ok();
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let text = Rc::new(RefCell::new(String::new()));
+        debugger.output = Box::new(Capture::new(text.clone(), true));
+        assert!(crate::run_command(&format!("set logging file {}; set logging on", log), &mut debugger));
+        debugger.transcript.write_command("help q");
+        assert!(crate::run_command("help q", &mut debugger));
+        assert!(!crate::run_command("bogus", &mut debugger));
+        assert!(crate::run_command("set logging off", &mut debugger));
+        crate::run_command("help q", &mut debugger);
+
+        let logged = std::fs::read_to_string(&log).unwrap();
+        let lines: Vec<&str> = logged.lines().collect();
+        assert!(lines[0].starts_with("=== Session started ") && lines[0].ends_with(&format!("{} (pid {}) ===", path, child)), "{}", lines[0]);
+        assert!(lines[1].ends_with(&format!("] Copying output to {}.", log)), "{}", lines[1]);
+        assert!(lines[2].ends_with("] rustdbg> help q"), "{}", lines[2]);
+        assert!(lines.iter().any(|line| line.ends_with("] Usage: q or quit")), "{}", logged);
+        assert!(lines.iter().any(|line| line.ends_with("] Unknown command: bogus")), "{}", logged);
+        assert!(lines.last().unwrap().starts_with("=== Session stopped "), "{}", logged);
+        assert!(!logged.contains('\x1b'), "The colors are not logged");
+        assert_eq!(logged.matches("Usage: q").count(), 1, "Nothing is logged once logging is off");
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_prompt() {
+        use crate::prompt::{process_state, prompt, render, ProcessState, DEFAULT_PROMPT_TEMPLATE};
+        let pid = Pid::from_raw(1234);
+        let in_main = ProcessState::Stopped { pc: 0x401126, symbol: Some(("main".to_string(), 0x12)) };
+        let unknown = ProcessState::Stopped { pc: 0x401126, symbol: None };
+        let cases = [
+            (DEFAULT_PROMPT_TEMPLATE, &in_main, "rustdbg [pid 1234, stopped @ 0x401126 <main+0x12>]> "),
+            (DEFAULT_PROMPT_TEMPLATE, &unknown, "rustdbg [pid 1234, stopped @ 0x401126]> "),
+            (DEFAULT_PROMPT_TEMPLATE, &ProcessState::Running, "rustdbg [running]> "),
+            (DEFAULT_PROMPT_TEMPLATE, &ProcessState::NoProcess, "rustdbg [no process]> "),
+            ("({pid} {state} {pc} {symbol}) ", &in_main, "(1234 stopped 0x401126 main+0x12) "),
+            ("({symbol}{pc}) ", &ProcessState::NoProcess, "() "),
+        ];
+        for (template, state, expected) in cases {
+            assert_eq!(render(template, pid, state), expected, "{} in {:?}", template, state);
+        }
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        assert_eq!(process_state(&mut debugger), ProcessState::NoProcess);
+        let inspect = debugger.resolve_symbol("inspect").unwrap();
+        run_to_breakpoint(child, inspect);
+        debugger.state = crate::state::InferiorState::Stopped { reason: crate::state::StopReason::Breakpoint(inspect) };
+        assert_eq!(process_state(&mut debugger), ProcessState::Stopped { pc: inspect, symbol: Some(("inspect".to_string(), 0)) });
+        assert!(crate::run_command("set prompt \"<{symbol}> \"", &mut debugger));
+        assert_eq!(prompt(&mut debugger), "<inspect> ");
+        assert!(crate::run_command("set prompt", &mut debugger));
+        assert_eq!(debugger.prompt, DEFAULT_PROMPT_TEMPLATE);
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_parse_values_and_locations() {
+        use crate::location::{parse_count, parse_location, parse_number, parse_value, Location};
+        let accepted: [(&str, u64); 6] =
+            [("0x401126", 0x401126), ("0XfF", 0xff), ("42", 42), ("0b101", 5), ("0", 0), ("18446744073709551615", u64::MAX)];
+        for (text, expected) in accepted {
+            assert_eq!(parse_number(text, "address"), Ok(expected), "{}", text);
+        }
+        // The argument, the character where parsing fails and a part of the reason.
+        let rejected = [
+            ("0x", 2, "missing digits"),
+            ("0x12g4", 4, "'g' is not a hexadecimal digit"),
+            ("0b102", 4, "'2' is not a binary digit"),
+            ("12a", 2, "'a' is not a decimal digit"),
+            ("-1", 0, "'-' is not a decimal digit"),
+            ("", 0, "missing digits"),
+            ("0x10000000000000000", 2, "too large"),
+        ];
+        for (text, position, reason) in rejected {
+            let err = parse_number(text, "address").unwrap_err();
+            assert_eq!((err.position, err.reason.contains(reason)), (position, true), "{}: {}", text, err);
+        }
+        let message = parse_number("0x12g4", "address").unwrap_err().to_string();
+        assert_eq!(message, "Invalid address \"0x12g4\": 'g' is not a hexadecimal digit at character 5. Expected 0x<hex>, <decimal> or 0b<binary>.");
+        assert_eq!(parse_count("0x10", "count"), Ok(16));
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let inspect = debugger.resolve_symbol("inspect").unwrap();
+        let rip = ptrace::getregs(child).unwrap().rip;
+        assert_eq!(parse_value(&mut debugger, "inspect", "address"), Ok(inspect));
+        assert_eq!(parse_value(&mut debugger, "inspect+0x10", "address"), Ok(inspect + 0x10));
+        assert_eq!(parse_value(&mut debugger, "inspect+8", "address"), Ok(inspect + 8));
+        assert_eq!(parse_value(&mut debugger, "$rip", "address"), Ok(rip));
+        assert_eq!(parse_value(&mut debugger, "$pc", "address"), Ok(rip));
+        let rejected = [("inspect+0xz", 10, "'z' is not"), ("$xyz", 1, "no register"), ("nowhere", 0, "no symbol \"nowhere\"")];
+        for (text, position, reason) in rejected {
+            let err = parse_value(&mut debugger, text, "address").unwrap_err();
+            assert_eq!((err.position, err.reason.contains(reason)), (position, true), "{}: {}", text, err);
+            assert!(err.to_string().contains("$<register>"), "The accepted forms are listed: {}", err);
+        }
+        assert_eq!(parse_location(&mut debugger, "inspect"), Ok(Location::Function("inspect".to_string(), inspect)));
+        assert_eq!(parse_location(&mut debugger, "inspect+1"), Ok(Location::Address(inspect + 1)));
+        assert!(matches!(parse_location(&mut debugger, "locals.c:13"), Ok(Location::Line(file, 13)) if file.ends_with("/locals.c")));
+        assert_eq!(parse_location(&mut debugger, "locals.c:1x").unwrap_err().position, 10);
+        assert!(parse_location(&mut debugger, "std::process::exit").unwrap_err().reason.contains("no symbol"));
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_settings() {
+        use crate::output::Capture;
+        use crate::settings::{find_setting, parse_config, parse_value, SettingValue, SETTINGS};
+        use std::{cell::RefCell, rc::Rc};
+        assert_eq!(find_setting(&["logging", "file", "x"]).map(|(spec, count)| (spec.name, count)), Some(("logging file", 2)));
+        assert_eq!(find_setting(&["logging", "on"]).map(|(spec, count)| (spec.name, count)), Some(("logging", 1)));
+        assert!(find_setting(&["backtrace"]).is_none());
+        let spec = |name: &str| SETTINGS.iter().find(|spec| spec.name == name).unwrap();
+        assert_eq!(parse_value(spec("show-instruction"), &["on"]), Ok(SettingValue::Bool(true)));
+        assert!(parse_value(spec("show-instruction"), &["yes"]).unwrap_err().contains("on|off"));
+        assert!(parse_value(spec("disassembly-flavor"), &["arm"]).unwrap_err().contains("intel|att"));
+        assert_eq!(parse_value(spec("backtrace limit"), &["0x10"]), Ok(SettingValue::Int(16)));
+        assert!(parse_value(spec("backtrace limit"), &["0"]).unwrap_err().contains("at least 1"));
+        assert_eq!(parse_value(spec("prompt"), &["\"(dbg)", "\""]), Ok(SettingValue::Text("(dbg) ".to_string())));
+        assert!(parse_value(spec("debug-file-directory"), &[]).is_err());
+
+        let config = "# Defaults\ndisassembly-flavor = \"att\" # comment\nprompt = \"# \"\nbacktrace.limit = 1_0\n[backtrace]\n\"inline-depth\" = 0\n[substitute]\npath = ['/a', \"/b\"]\n";
+        let names = parse_config(config).unwrap();
+        let expected = [
+            (2, "disassembly-flavor", vec!["att"]),
+            (3, "prompt", vec!["# "]),
+            (4, "backtrace limit", vec!["10"]),
+            (6, "backtrace inline-depth", vec!["0"]),
+            (8, "substitute path", vec!["/a", "/b"]),
+        ];
+        for ((line, name, words), (expected_line, expected_name, expected_words)) in names.iter().zip(expected) {
+            assert_eq!((*line, name.as_str(), words.clone()), (expected_line, expected_name, expected_words.iter().map(|word| word.to_string()).collect()));
+        }
+        assert_eq!(parse_config("limit 8").unwrap_err().0, 1);
+        assert_eq!(parse_config("x = [1, @]").unwrap_err().0, 1);
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let text = Rc::new(RefCell::new(String::new()));
+        debugger.output = Box::new(Capture::new(text.clone(), false));
+        assert!(crate::run_command("set disassembly-flavor att; set backtrace inline-depth 2; set substitute-path /a /b", &mut debugger));
+        assert_eq!(debugger.disassembly_flavor, crate::disasm::DisassemblyFlavor::Att);
+        assert_eq!(debugger.inline_depth, 2);
+        assert!(crate::run_command("show", &mut debugger));
+        assert!(text.borrow().contains("disassembly-flavor = att\n") && text.borrow().contains("substitute-path = /a -> /b\n"), "{}", text.borrow());
+        text.borrow_mut().clear();
+        assert!(crate::run_command("show backtrace limit", &mut debugger));
+        assert!(text.borrow().starts_with("backtrace limit = 32\n") && text.borrow().contains("(default 32)"), "{}", text.borrow());
+        text.borrow_mut().clear();
+        assert!(!crate::run_command("set disasembly-flavor att", &mut debugger));
+        assert!(text.borrow().contains("Did you mean: disassembly-flavor?"), "{}", text.borrow());
+        assert!(!crate::run_command("set substitute-path /a", &mut debugger));
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_init_files() {
+        use crate::script::{source_init_files, AutoLoadLocal};
+        use std::path::Path;
+        let directory = scratch("init");
+        std::fs::create_dir_all(&directory).unwrap();
+        let global = format!("{}/init", directory);
+        let local = format!("{}/.rustdbginit", directory);
+        std::fs::write(&global, "alias limit = \"set backtrace limit $1\"\nlimit 5\nset prompt \"(global) \"\n").unwrap();
+        // Run after the global init file, so its alias is defined and its prompt replaced.
+        std::fs::write(&local, "limit 6\nset prompt \"(local) \"\n").unwrap();
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+
+        assert!(source_init_files(&mut debugger, Some(Path::new(&global)), Path::new(&local), crate::run_command, |_| false));
+        assert_eq!((debugger.backtrace_limit, debugger.prompt.as_str()), (5, "(global) "), "Not run without a yes");
+        assert!(source_init_files(&mut debugger, None, Path::new(&local), crate::run_command, |_| true));
+        assert_eq!((debugger.backtrace_limit, debugger.prompt.as_str()), (6, "(local) "));
+        debugger.batch = true;
+        assert!(source_init_files(&mut debugger, Some(Path::new(&global)), Path::new(&local), crate::run_command, |_| true));
+        assert_eq!(debugger.backtrace_limit, 5, "Nobody to ask in batch mode");
+        debugger.batch = false;
+        assert!(crate::run_command("set auto-load-local off", &mut debugger));
+        assert_eq!(debugger.auto_load_local, AutoLoadLocal::Off);
+        assert!(source_init_files(&mut debugger, Some(Path::new(&global)), Path::new(&local), crate::run_command, |_| true));
+        assert_eq!(debugger.backtrace_limit, 5);
+        assert!(crate::run_command("set auto-load-local on", &mut debugger));
+        assert!(source_init_files(&mut debugger, Some(Path::new(&global)), Path::new(&local), crate::run_command, |_| false));
+        assert_eq!((debugger.backtrace_limit, debugger.prompt.as_str()), (6, "(local) "));
+        let missing = format!("{}/missing", directory);
+        assert!(source_init_files(&mut debugger, Some(Path::new(&missing)), Path::new(&missing), crate::run_command, |_| true));
+        std::fs::write(&local, "bogus\n").unwrap();
+        assert!(!source_init_files(&mut debugger, None, Path::new(&local), crate::run_command, |_| true));
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_shell_and_restart() {
+        use crate::commands::shell_command_line;
+        use crate::script::split_commands;
+        assert_eq!(shell_command_line("!echo a; echo b"), Some("echo a; echo b"));
+        assert_eq!(shell_command_line("shell ls"), Some("ls"));
+        assert_eq!(shell_command_line("she ls"), Some("ls"), "An abbreviation of shell");
+        assert_eq!(shell_command_line("show"), None);
+        assert_eq!(split_commands("!echo a; echo b"), ["!echo a; echo b"]);
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        assert!(crate::run_command("!true", &mut debugger));
+        assert!(!crate::run_command("shell exit 3", &mut debugger));
+        assert!(!crate::run_command("rebuild", &mut debugger), "No build command");
+        assert!(crate::run_command("set build-command \"true\"", &mut debugger));
+        assert!(crate::run_command("rebuild", &mut debugger));
+        assert!(debugger.symbols_stale);
+
+        assert!(crate::run_command("b inspect", &mut debugger));
+        assert!(crate::run_command("restart", &mut debugger));
+        assert_ne!(debugger.child, child);
+        assert!(!debugger.symbols_stale);
+        assert_eq!(debugger.breakpoint_locations, ["inspect"]);
+        assert!(crate::run_command("c", &mut debugger));
+        let regs = ptrace::getregs(debugger.child).unwrap();
+        assert_eq!(debugger.symbolize(regs.rip).map(|(name, _, _)| name), Some("inspect".to_string()));
+        ptrace::kill(debugger.child).ok();
+        waitpid(debugger.child, None).ok();
+    }
+
+    #[test]
+    fn test_printf_and_variables() {
+        use crate::output::Capture;
+        use crate::printf::{split_arguments, unescape};
+        use std::{cell::RefCell, rc::Rc};
+        assert_eq!(unescape(r#"a\tb\n\\\"\q"#), "a\tb\n\\\"\\q");
+        assert_eq!(split_arguments(r#""%x, %d\n" $rax, 3"#).unwrap(), ("%x, %d\n".to_string(), vec!["$rax", "3"]));
+        assert_eq!(split_arguments(r#""say \"hi\"" 1"#).unwrap(), ("say \"hi\"".to_string(), vec!["1"]));
+        assert!(split_arguments("%x 1").is_err());
+        assert!(split_arguments("\"%x 1").is_err());
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        assert!(crate::run_command("b inspect; c", &mut debugger));
+        let text = Rc::new(RefCell::new(String::new()));
+        debugger.output = Box::new(Capture::new(text.clone(), false));
+        assert!(crate::run_command("set $base = 0x1000; set $next = $base+0x10", &mut debugger));
+        assert_eq!(debugger.variables.get("next"), Some(&0x1010));
+        assert!(crate::run_command("info convenience", &mut debugger));
+        assert!(crate::run_command("echo a\\tb\\nc", &mut debugger));
+        assert!(crate::run_command(r#"printf "%x %5d|%-3u|%04x %p %c\n", $next+1, 42, 7, 0xab, $base, 65"#, &mut debugger));
+        // The second argument of inspect is the string "hello".
+        assert!(crate::run_command(r#"printf "%s %d" $rsi $rdi"#, &mut debugger));
+        assert!(!crate::run_command("set $rip = 1", &mut debugger), "Registers are not variables");
+        assert!(!crate::run_command("set $2x = 1", &mut debugger));
+        assert!(!crate::run_command(r#"printf "%x %x" 1"#, &mut debugger));
+        assert!(!crate::run_command(r#"printf "%x" 1 2"#, &mut debugger));
+        assert!(!crate::run_command(r#"printf "%y" 1"#, &mut debugger));
+        assert!(!crate::run_command(r#"printf "%x" $missing"#, &mut debugger));
+        let lines: Vec<String> = text.borrow().lines().take(6).map(str::to_string).collect();
+        assert_eq!(lines, ["$base = 0x1000 (4096)", "$next = 0x1010 (4112)", "a\tb", "c", "1011    42|7  |00ab 0x1000 A", "hello 1"]);
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_confirm() {
+        use crate::repl::needs_confirmation;
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        assert!(needs_confirmation(&debugger));
+        assert!(crate::run_command("b main; c", &mut debugger));
+        assert!(debugger.has_live_process());
+        // Forced, so it does not ask even though the program is running.
+        assert!(crate::run_command("restart!", &mut debugger));
+        assert!(!debugger.forced, "Only for the command given with !");
+        assert_ne!(debugger.child, child);
+        assert!(crate::run_command("set confirm off", &mut debugger));
+        assert!(!needs_confirmation(&debugger));
+        assert!(crate::run_command("c; res", &mut debugger));
+        assert!(crate::run_command("set confirm on", &mut debugger));
+        debugger.batch = true;
+        assert!(!needs_confirmation(&debugger), "Nobody to ask in batch mode");
+        assert!(!crate::run_command("bogus!", &mut debugger));
+        ptrace::kill(debugger.child).ok();
+        waitpid(debugger.child, None).ok();
+    }
+
+    #[test]
+    fn test_user_commands() {
+        use crate::define::substitute_arguments;
+        let args = ["main".to_string(), "3".to_string()];
+        assert_eq!(substitute_arguments("b $arg0; bt $arg1 # $argc $rip $args", &args).unwrap(), "b main; bt 3 # 2 $rip $args");
+        assert!(substitute_arguments("p $arg2", &args).unwrap_err().contains("$arg2"));
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        for line in ["define tb", "b $arg0", "define inner", "bt 1", "end", "c", "end"] {
+            assert!(crate::run_command(line, &mut debugger), "{}", line);
+        }
+        assert!(debugger.definition.is_none());
+        assert_eq!(debugger.user_commands["tb"], ["b $arg0", "define inner", "bt 1", "end", "c"], "The nested define is part of the body");
+        assert!(crate::run_command("tb inspect", &mut debugger));
+        assert_eq!(debugger.breakpoints.len(), 1);
+        assert!(debugger.user_commands.contains_key("inner"), "Defined by running tb");
+        assert!(!crate::run_command("define b", &mut debugger), "A command can't be replaced");
+        assert!(crate::run_command("help user-defined; help tb", &mut debugger));
+
+        // A recursive command stops at the depth limit instead of overflowing the stack.
+        for line in ["define forever", "forever", "end"] {
+            assert!(crate::run_command(line, &mut debugger));
+        }
+        assert!(!crate::run_command("forever", &mut debugger));
+        assert_eq!(debugger.user_command_depth, 0);
+        assert!(crate::run_command("undefine forever", &mut debugger));
+        assert!(!crate::run_command("forever", &mut debugger), "Removed");
+
+        let script = scratch("define.gdb");
+        std::fs::write(&script, "define unfinished\nbt\n").unwrap();
+        assert!(!crate::script::source(&mut debugger, &script, crate::run_command), "define without end");
+        assert!(debugger.definition.is_none() && !debugger.user_commands.contains_key("unfinished"));
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_json_mode() {
+        use crate::options::Interpreter;
+        use crate::output::Capture;
+        use std::{cell::RefCell, rc::Rc};
+        let args: Vec<String> = ["--interpreter", "json", "./app"].iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(crate::options::parse(&args).unwrap().interpreter, Interpreter::Json);
+        assert!(crate::options::parse(&["--interpreter".to_string(), "xml".to_string(), "./app".to_string()]).is_err());
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let text = Rc::new(RefCell::new(String::new()));
+        let records = Rc::new(RefCell::new(String::new()));
+        crate::json::start(&mut debugger, Box::new(Capture::new(text.clone(), false)), Box::new(Capture::new(records.clone(), false)));
+        assert!(crate::run_command("b inspect; c", &mut debugger));
+        assert!(!crate::run_command("bogus", &mut debugger));
+        assert!(crate::run_command("r; m $sp; info breakpoints; help q", &mut debugger));
+        let records: Vec<serde_json::Value> = records.borrow().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let commands: Vec<&str> = records.iter().map(|record| record["command"].as_str().unwrap_or("<event>")).collect();
+        assert_eq!(commands, ["b inspect", "<event>", "c", "bogus", "r", "m $sp", "info breakpoints", "help q"]);
+
+        assert_eq!(records[0]["data"]["number"], 1);
+        assert_eq!(records[0]["data"]["function"], "inspect");
+        let address = records[0]["data"]["address"].as_str().unwrap().to_string();
+        assert_eq!((&records[1]["type"], &records[1]["reason"], &records[1]["breakpoint"]), (&"stop".into(), &"breakpoint".into(), &1.into()));
+        assert_eq!((records[1]["pc"].as_str(), &records[1]["function"], &records[1]["line"]), (Some(address.as_str()), &"inspect".into(), &13.into()));
+        assert_eq!(records[2]["ok"], true);
+        assert_eq!((&records[3]["ok"], &records[3]["error"]), (&false.into(), &"Unknown command: bogus".into()));
+        let rsp = ptrace::getregs(child).unwrap().rsp;
+        assert_eq!(records[4]["data"]["registers"]["rsp"], format!("{:#x}", rsp));
+        assert_eq!(records[5]["data"]["address"], format!("{:#x}", rsp));
+        assert_eq!(records[5]["data"]["bytes"].as_array().map(Vec::len), Some(8));
+        // The breakpoint hit is disarmed until the program is resumed.
+        assert_eq!(records[6]["data"]["breakpoints"], serde_json::json!([]));
+        assert_eq!(records[7]["data"]["output"][0], "Usage: q or quit");
+        assert!(text.borrow().contains("Breakpoint 1 set at") && !text.borrow().contains("\"command\""), "The text is kept apart");
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_crash_report() {
+        use crate::crash::memory_accesses;
+        let mut regs: nix::libc::user_regs_struct = unsafe { std::mem::zeroed() };
+        (regs.rip, regs.rax, regs.rsp) = (0x1000, 0x10, 0x7ff0);
+        // mov [rax+0x8], edx
+        let accesses = memory_accesses(&[0x89, 0x50, 0x08], &regs, Arch::X86_64);
+        assert_eq!(accesses.len(), 1);
+        assert_eq!((accesses[0].operand.as_str(), accesses[0].kind), ("[rax+0x8]", "write"));
+        assert_eq!((accesses[0].address, accesses[0].size), (0x18, 4));
+        assert_eq!(accesses[0].registers, [("rax".to_string(), 0x10)]);
+        // push rbp writes below the stack pointer.
+        let accesses = memory_accesses(&[0x55], &regs, Arch::X86_64);
+        assert_eq!((accesses[0].operand.as_str(), accesses[0].address), ("[rsp-0x8]", 0x7fe8));
+
+        let path = fixture("crash");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        ptrace::cont(child, None).unwrap();
+        assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Stopped(child, Signal::SIGSEGV));
+        assert!(crate::crash::report_crash(&mut debugger, Signal::SIGSEGV));
+        let (_, fault, _) = debugger.last_crash.unwrap();
+        assert_eq!(fault, 0x10, "node->next is 0x10");
+        // Retrying the instruction faults again, the crash is reported once.
+        ptrace::cont(child, None).unwrap();
+        assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Stopped(child, Signal::SIGSEGV));
+        assert!(!crate::crash::report_crash(&mut debugger, Signal::SIGSEGV));
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_inline_frames() {
+        let path = fixture("inline");
+        let debug_info = crate::debuginfo::DebugInfo::load(&path).expect("The fixture is built with -g");
+        let (_, address) = debug_info.find_line("inline.c", 2).expect("square is inlined with always_inline");
+        let frames = debug_info.find_frames(address);
+        let names: Vec<&str> = frames.iter().map(|frame| frame.function.as_deref().unwrap()).collect();
+        assert_eq!(names, ["square", "sum_of_squares", "main"]);
+        let inlined: Vec<bool> = frames.iter().map(|frame| frame.inlined).collect();
+        assert_eq!(inlined, [true, true, false]);
+        let lines: Vec<u64> = frames.iter().map(|frame| frame.location.as_ref().unwrap().line).collect();
+        assert_eq!(lines, [2, 7, 12], "Callers are at the line of the inlined call");
+
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let bias = debugger.executable_bias().unwrap();
+        assert_eq!(debugger.inlined_function(address + bias).as_deref(), Some("square (inlined into main)"));
+        debugger.inline_depth = 1;
+        let names: Vec<String> = debugger.function_frames(address + bias).into_iter().filter_map(|frame| frame.function).collect();
+        assert_eq!(names, ["sum_of_squares", "main"], "The innermost inlined functions are hidden first");
+        debugger.inline_depth = 0;
+        assert_eq!(debugger.inlined_function(address + bias), None);
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_substitute_path() {
+        let rules = vec![("/build/src".to_string(), "/home/me/src".to_string())];
+        assert_eq!(crate::source::substitute_path("/build/src/main.rs", &rules), "/home/me/src/main.rs");
+        assert_eq!(crate::source::substitute_path("/build/srcs/main.rs", &rules), "/build/srcs/main.rs");
+        assert_eq!(crate::source::centered_range(3, 100), (1, 10));
+        assert_eq!(crate::source::centered_range(50, 52), (45, 52));
+    }
+
+    #[test]
+    fn test_source_line_stepping() {
+        let path = fixture("step");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let main = debugger.resolve_symbol("main").unwrap();
+        run_to_breakpoint(child, main);
+
+        let current_line = |debugger: &mut Debugger| {
+            let rip = ptrace::getregs(child).unwrap().rip;
+            debugger.source_location(rip).map(|location| location.line)
+        };
+        assert_eq!(current_line(&mut debugger), Some(8));
+        assert_eq!(crate::step::step_line(&mut debugger, true), Ok(crate::step::StepStop::Done));
+        assert_eq!(current_line(&mut debugger), Some(9));
+        crate::step::step_line(&mut debugger, true).unwrap();
+        assert_eq!(current_line(&mut debugger), Some(10));
+        crate::step::step_line(&mut debugger, false).unwrap();
+        assert_eq!(current_line(&mut debugger), Some(3), "step should enter square");
+        for line in [4, 5, 6, 10] {
+            crate::step::step_line(&mut debugger, false).unwrap();
+            assert_eq!(current_line(&mut debugger), Some(line));
+        }
+        crate::step::step_line(&mut debugger, true).unwrap();
+        assert_eq!(current_line(&mut debugger), Some(11), "next should step over printf");
+
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_frame_pointer_backtrace() {
+        let path = fixture("step");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let main = debugger.resolve_symbol("main").unwrap();
+        run_to_breakpoint(child, main);
+        let square = debugger.resolve_symbol("square").unwrap();
+        run_to_breakpoint(child, square);
+
+        // Stopped on the first instruction of square, before its prologue.
+        let frames = crate::backtrace::backtrace(&mut debugger, 32).unwrap();
+        let names: Vec<String> = frames.iter().filter_map(|frame| debugger.symbolize(frame.pc)).map(|(name, _, _)| name).collect();
+        assert_eq!(names[..2], ["square", "main"]);
+        let caller = debugger.source_location(frames[1].pc - 1).unwrap();
+        assert_eq!(caller.line, 10);
+        debugger.selected_frame = 1;
+        let pc = debugger.selected_frame_lookup_pc().unwrap();
+        assert_eq!(debugger.source_location(pc), Some(caller), "frame 1 should list the caller's line");
+        debugger.selected_frame = 0;
+
+        // Inside the body, once rbp is set up.
+        crate::step::step_line(&mut debugger, false).unwrap();
+        let frames = crate::backtrace::backtrace(&mut debugger, 2).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(debugger.symbolize(frames[1].pc).unwrap().0, "main");
+
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_cfi_backtrace_without_frame_pointers() {
+        let path = fixture("unwind-release");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let main = debugger.resolve_symbol("main").unwrap();
+        run_to_breakpoint(child, main);
+        let level3 = debugger.resolve_symbol("level3").unwrap();
+        run_to_breakpoint(child, level3);
+        // Move past the first instruction so the frame is not at a function entry.
+        crate::step::single_step(&mut debugger).unwrap();
+
+        let frames = crate::backtrace::backtrace(&mut debugger, 32).unwrap();
+        let names: Vec<String> = frames.iter().filter_map(|frame| debugger.symbolize(frame.pc)).map(|(name, _, _)| name).collect();
+        assert_eq!(names[..4], ["level3", "level2", "level1", "main"]);
+
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_locals_and_statics() {
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let inspect = debugger.resolve_symbol("inspect").unwrap();
+        run_to_breakpoint(child, inspect);
+        while debugger.source_location(ptrace::getregs(child).unwrap().rip).unwrap().line != 20 {
+            crate::step::step_line(&mut debugger, true).unwrap();
+        }
+
+        let (scope, context) = crate::variables::selected_scope(&mut debugger).unwrap();
+        let frame_base = scope.frame_base(&context);
+        let value = |name: &str| crate::variables::format_variable(scope.find(name).unwrap(), frame_base, &context, crate::variables::FormatLimits::DEFAULT);
+        assert_eq!(value("answer"), "42");
+        assert_eq!(value("flag"), "true");
+        assert_eq!(value("letter"), "65 'A'");
+        assert_eq!(value("ratio"), "2.5");
+        assert_eq!(value("origin"), "point { x: 3, y: -4 }");
+        assert_eq!(value("color"), "BLUE");
+        assert_eq!(value("depth"), "1");
+        assert_eq!(value("message"), value("label"));
+        assert!(scope.find("depth").unwrap().is_parameter);
+
+        let globals = crate::variables::find_globals(debugger.debug_info.as_ref().unwrap(), "counter").unwrap();
+        assert_eq!(globals.len(), 1);
+        assert_eq!(crate::variables::format_variable(&globals[0].1, None, &context, crate::variables::FormatLimits::DEFAULT), "7");
+
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_thread_local_statics() {
+        use crate::symbols::TlsSegment;
+        use crate::variables::static_tls_offset;
+        assert_eq!(static_tls_offset(&TlsSegment { address: 0x3df0, size: 4, align: 4 }), 4);
+        assert_eq!(static_tls_offset(&TlsSegment { address: 0x3df0, size: 20, align: 16 }), 32);
+
+        let path = fixture("tls");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let report = debugger.resolve_symbol("report").unwrap();
+        // The worker thread is traced, its creation reported on the way.
+        debugger.set_breakpoint(&format!("{:#x}", report)).unwrap();
+        assert_eq!(debugger.cont(), Ok(crate::step::StepStop::Breakpoint(report)));
+        assert_eq!(debugger.threads.len(), 1, "The worker has exited");
+
+        let (_, context) = crate::variables::selected_scope(&mut debugger).unwrap();
+        assert!(context.tls_block.is_some(), "The executable has a PT_TLS segment");
+        let debug_info = debugger.debug_info.as_ref().unwrap();
+        let value = |name: &str| {
+            let globals = crate::variables::find_globals(debug_info, name).unwrap();
+            crate::variables::format_variable(&globals[0].1, None, &context, crate::variables::FormatLimits::DEFAULT)
+        };
+        // The main thread's copy, while the worker saw its own copy go from 1 to 42.
+        assert_eq!(value("counter"), "5");
+        assert_eq!(value("worker_counter"), "42");
+
+        let without_tls = crate::variables::FrameContext { tls_block: None, ..context.clone() };
+        let globals = crate::variables::find_globals(debug_info, "counter").unwrap();
+        let message = crate::variables::format_variable(&globals[0].1, None, &without_tls, crate::variables::FormatLimits::DEFAULT);
+        assert!(message.starts_with("<TLS resolution not available"), "Unexpected message {}", message);
+
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_function_arguments() {
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let entry = debugger.resolve_symbol("inspect").unwrap();
+        let address = debugger.function_breakpoint_address("inspect").unwrap();
+        assert!(address > entry, "the breakpoint should be placed after the prologue");
+        run_to_breakpoint(child, address);
+        let arguments = crate::variables::frame_arguments(&mut debugger).unwrap();
+        let names: Vec<&str> = arguments.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["depth", "label"]);
+        assert_eq!(arguments[0].1, "1");
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+
+        // Without DWARF, the calling convention registers are shown on function entry.
+        let path = fixture("locals-nodebug");
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        let address = debugger.function_breakpoint_address("inspect").unwrap();
+        assert_eq!(Some(address), debugger.resolve_symbol("inspect"));
+        run_to_breakpoint(child, address);
+        let arguments = crate::variables::frame_arguments(&mut debugger).unwrap();
+        assert_eq!(arguments.len(), 6);
+        assert_eq!(arguments[0], ("arg1".to_string(), "0x1".to_string()));
+        assert!(arguments[1].1.ends_with(" \"hello\""), "{}", arguments[1].1);
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_sections_and_header() {
+        let path = fixture("locals");
+        let object = crate::symbols::ObjectSymbols::load(&path).unwrap();
+        assert_eq!(object.header.machine, "X86_64");
+        assert!(object.header.interpreter.as_deref().is_some_and(|interpreter| interpreter.contains("ld-linux")));
+        let text = object.sections.iter().find(|section| section.name == ".text").unwrap();
+        assert!(text.is_alloc());
+        assert_eq!(text.flag_letters(), "AX");
+        assert!(text.address <= object.header.entry && object.header.entry < text.address + text.size);
+        let debug_info = object.sections.iter().find(|section| section.name == ".debug_info").unwrap();
+        assert!(!debug_info.is_alloc());
+    }
+
+    #[test]
+    fn test_separate_debug_file() {
+        assert_eq!(crate::symbols::crc32(b"123456789"), 0xcbf4_3926);
+        // Stripped in place, a copy of the fixture.
+        let path = scratch(&format!("locals-stripped.{}", std::process::id()));
+        std::fs::copy(fixture("locals-buildid"), &path).unwrap();
+        let debug_path = format!("{}.debug", path);
+        let run = |program: &str, args: &[&str]| {
+            let status = Command::new(program).args(args).status().expect("binutils should be installed");
+            assert!(status.success(), "{} {:?} failed", program, args);
+        };
+        run("objcopy", &["--only-keep-debug", &path, &debug_path]);
+        run("strip", &["--strip-all", &path]);
+        run("objcopy", &[&format!("--add-gnu-debuglink={}", debug_path), &path]);
+
+        let mut symbols = crate::symbols::SymbolTable::default();
+        symbols.debug_file_directories.clear();
+        symbols.load(&path, None).unwrap();
+        let object = &symbols.objects[0];
+        assert!(!object.has_dwarf);
+        assert_eq!(object.debug_file.as_deref(), Some(debug_path.as_str()), "found through the debug link");
+        let inspect = object.symbols.iter().find(|symbol| symbol.name == "inspect").expect("merged from the debug file");
+
+        // The build ID layout of a debug file directory is searched first.
+        let build_id: String = object.build_id.as_ref().unwrap().iter().map(|byte| format!("{:02x}", byte)).collect();
+        let directory = format!("{}.dir", path);
+        let build_id_dir = format!("{}/.build-id/{}", directory, &build_id[..2]);
+        std::fs::create_dir_all(&build_id_dir).unwrap();
+        let build_id_file = format!("{}/{}.debug", build_id_dir, &build_id[2..]);
+        std::fs::copy(&debug_path, &build_id_file).unwrap();
+        let found = crate::symbols::find_debug_file(object, &[directory]);
+        assert_eq!(found.as_deref(), Some(build_id_file.as_str()));
+
+        let debug_info = crate::debuginfo::DebugInfo::load(object.debug_file.as_ref().unwrap()).unwrap();
+        assert_eq!(debug_info.find_location(inspect.address).map(|location| location.line), Some(12));
+    }
+
+    #[test]
+    fn test_static_symbol_lookup() {
+        use crate::symbols::{demangle, path_matches};
+        assert_eq!(demangle("_ZN8my_crate5state7COUNTER17h0123456789abcdefE"), "my_crate::state::COUNTER");
+        assert_eq!(demangle("counter"), "counter");
+        assert!(path_matches("my_crate::state::COUNTER", "COUNTER"));
+        assert!(path_matches("my_crate::state::COUNTER", "state::COUNTER"));
+        assert!(!path_matches("my_crate::state::COUNTER", "TER"));
+
+        let path = fixture("locals");
+        let mut symbols = crate::symbols::SymbolTable::default();
+        symbols.load(&path, None).unwrap();
+        let found = symbols.find_data("counter");
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].1.name.as_str(), found[0].1.size), ("counter", 4));
+        assert!(symbols.find_data("inspect").is_empty(), "functions are not data symbols");
+    }
+
+    #[test]
+    fn test_symbol_index_and_sections() {
+        let path = fixture("locals");
+        let object = crate::symbols::ObjectSymbols::load(&path).unwrap();
+        let main = object.find("main");
+        assert_eq!(main.len(), 1);
+        assert_eq!(object.section_name(main[0]), Some(".text"));
+        let counter = object.find("counter");
+        assert_eq!(object.section_name(counter[0]), Some(".data"));
+        assert!(object.find("mai").is_empty(), "only whole names and path components match");
+    }
+
+    #[test]
+    fn test_format_str_slice() {
+        use crate::variables::{format_value, DwarfType, FormatLimits, Member, TypeKind};
+        let usize_type = DwarfType { name: "usize".to_string(), size: 8, kind: TypeKind::Base(gimli::DW_ATE_unsigned) };
+        let pointer_type = DwarfType { name: "*const u8".to_string(), size: 8, kind: TypeKind::Pointer(None) };
+        let str_type = DwarfType {
+            name: "&str".to_string(),
+            size: 16,
+            kind: TypeKind::Struct(vec![
+                Member { name: "data_ptr".to_string(), offset: 0, ty: pointer_type },
+                Member { name: "length".to_string(), offset: 8, ty: usize_type },
+            ]),
+        };
+        let mut bytes = 0x1000u64.to_le_bytes().to_vec();
+        bytes.extend(5u64.to_le_bytes());
+        let mut memory = |address: u64, len: usize| (address == 0x1000).then(|| b"hello world"[..len].to_vec());
+        assert_eq!(format_value(&str_type, &bytes, FormatLimits::DEFAULT, &mut memory), "\"hello\"");
+    }
+
+    #[test]
+    fn test_format_aggregates() {
+        use crate::variables::{format_value, DwarfType, FormatLimits, Member, TypeKind};
+        let base = |name: &str, size: u64, encoding| DwarfType { name: name.to_string(), size, kind: TypeKind::Base(encoding) };
+        let member = |name: &str, offset: u64, ty: &DwarfType| Member { name: name.to_string(), offset, ty: ty.clone() };
+        let u8_type = base("u8", 1, gimli::DW_ATE_unsigned);
+        let u16_type = base("u16", 2, gimli::DW_ATE_unsigned);
+        let array = |element: &DwarfType, count: u64| DwarfType {
+            name: format!("{}[{}]", element.name, count),
+            size: element.size * count,
+            kind: TypeKind::Array(Box::new(element.clone()), count),
+        };
+        let leaf = DwarfType { name: "Leaf".to_string(), size: 1, kind: TypeKind::Struct(vec![member("v", 0, &u8_type)]) };
+        let inner = DwarfType {
+            name: "Inner".to_string(),
+            size: 8,
+            kind: TypeKind::Struct(vec![member("a", 0, &u8_type), member("leaf", 1, &leaf), member("b", 2, &array(&u16_type, 3))]),
+        };
+        let outer = DwarfType { name: "Outer".to_string(), size: 9, kind: TypeKind::Struct(vec![member("inner", 0, &inner), member("c", 8, &u8_type)]) };
+        let bytes = [1, 3, 4, 0, 5, 0, 6, 0, 9];
+        let mut no_memory = |_: u64, _: usize| None;
+        assert_eq!(
+            format_value(&outer, &bytes, FormatLimits::DEFAULT, &mut no_memory),
+            "Outer { inner: Inner { a: 1, leaf: {...}, b: {...} }, c: 9 }",
+            "One level of nesting by default"
+        );
+        assert_eq!(
+            format_value(&outer, &bytes, FormatLimits::FULL, &mut no_memory),
+            "Outer { inner: Inner { a: 1, leaf: Leaf { v: 3 }, b: [4, 5, 6] }, c: 9 }"
+        );
+        let many: Vec<u8> = (0..20).collect();
+        assert_eq!(
+            format_value(&array(&u8_type, 20), &many, FormatLimits::DEFAULT, &mut no_memory),
+            "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, ... 4 more]"
+        );
+        let char_type = base("char", 1, gimli::DW_ATE_signed_char);
+        assert_eq!(format_value(&array(&char_type, 8), b"abc\0junk", FormatLimits::DEFAULT, &mut no_memory), "\"abc\"");
+    }
+
+    #[test]
+    fn test_format_rust_enums() {
+        use crate::variables::{format_value, DwarfType, FormatLimits, Member, TypeKind, Variant};
+        let i32_type = DwarfType { name: "i32".to_string(), size: 4, kind: TypeKind::Base(gimli::DW_ATE_signed) };
+        let u64_type = DwarfType { name: "u64".to_string(), size: 8, kind: TypeKind::Base(gimli::DW_ATE_unsigned) };
+        let payload = |name: &str, members: Vec<Member>| Member {
+            name: name.to_string(),
+            offset: 0,
+            ty: DwarfType { name: name.to_string(), size: 8, kind: TypeKind::Struct(members) },
+        };
+        let field = |name: &str, offset: u64, ty: &DwarfType| Member { name: name.to_string(), offset, ty: ty.clone() };
+        let mut no_memory = |_: u64, _: usize| None;
+
+        // A discriminant of 4 bytes, then the payload.
+        let option = DwarfType {
+            name: "Option<i32>".to_string(),
+            size: 8,
+            kind: TypeKind::Variants(
+                Some(Box::new(field("", 0, &i32_type))),
+                vec![
+                    Variant { discriminant: Some(0), member: payload("None", Vec::new()) },
+                    Variant { discriminant: Some(1), member: payload("Some", vec![field("__0", 4, &i32_type)]) },
+                ],
+            ),
+        };
+        let some = [1, 0, 0, 0, 0xf9, 0xff, 0xff, 0xff];
+        assert_eq!(format_value(&option, &some, FormatLimits::DEFAULT, &mut no_memory), "Some(-7)");
+        assert_eq!(format_value(&option, &[0; 8], FormatLimits::DEFAULT, &mut no_memory), "None");
+
+        // The niche of Option<&i32>: 0 is None, any other pointer the default variant, Some.
+        let pointer = DwarfType { name: "i32 *".to_string(), size: 8, kind: TypeKind::Pointer(Some(Box::new(i32_type.clone()))) };
+        let niche = DwarfType {
+            name: "Option<&i32>".to_string(),
+            size: 8,
+            kind: TypeKind::Variants(
+                Some(Box::new(field("", 0, &u64_type))),
+                vec![
+                    Variant { discriminant: Some(0), member: payload("None", Vec::new()) },
+                    Variant { discriminant: None, member: payload("Some", vec![field("__0", 0, &pointer)]) },
+                ],
+            ),
+        };
+        let mut memory = |address: u64, len: usize| (address == 0x1000 && len == 4).then(|| 42i32.to_le_bytes().to_vec());
+        assert_eq!(format_value(&niche, &0x1000u64.to_le_bytes(), FormatLimits::DEFAULT, &mut memory), "Some(0x1000 -> 42)");
+        assert_eq!(
+            format_value(&niche, &0x1000u64.to_le_bytes(), FormatLimits { depth: 1, hops: 0 }, &mut memory),
+            "Some(0x1000)",
+            "Pointers are not followed without hops"
+        );
+        assert_eq!(format_value(&niche, &0x2000u64.to_le_bytes(), FormatLimits::DEFAULT, &mut memory), "Some(0x2000 <cannot access memory>)");
+
+        let shape = DwarfType {
+            name: "Shape".to_string(),
+            size: 8,
+            kind: TypeKind::Variants(
+                Some(Box::new(field("", 0, &i32_type))),
+                vec![Variant { discriminant: Some(3), member: payload("Circle", vec![field("r", 4, &i32_type)]) }],
+            ),
+        };
+        assert_eq!(format_value(&shape, &[3, 0, 0, 0, 2, 0, 0, 0], FormatLimits::DEFAULT, &mut no_memory), "Shape::Circle { r: 2 }");
+        assert_eq!(format_value(&shape, &[4, 0, 0, 0, 2, 0, 0, 0], FormatLimits::DEFAULT, &mut no_memory), "<unknown variant of Shape>");
+    }
+
+    #[test]
+    fn test_format_c_string_pointer() {
+        use crate::variables::{format_value, DwarfType, FormatLimits, TypeKind};
+        let char_type = DwarfType { name: "char".to_string(), size: 1, kind: TypeKind::Base(gimli::DW_ATE_signed_char) };
+        let pointer = DwarfType { name: "char *".to_string(), size: 8, kind: TypeKind::Pointer(Some(Box::new(char_type))) };
+        let mut memory = |address: u64, len: usize| (address == 0x1000).then(|| b"hi\0there".iter().copied().chain(std::iter::repeat(0)).take(len).collect());
+        assert_eq!(format_value(&pointer, &0x1000u64.to_le_bytes(), FormatLimits::DEFAULT, &mut memory), "0x1000 \"hi\"");
+        assert_eq!(format_value(&pointer, &0u64.to_le_bytes(), FormatLimits::DEFAULT, &mut memory), "0x0");
+    }
+
+    /// Set a breakpoint at `address`, continue and check the child stops there.
+    fn run_to_breakpoint(child: Pid, address: u64) {
+        let mut breakpoints = crate::working::BreakpointTable::default();
+        set_breakpoint(&mut breakpoints, child, address).expect("Failed to set breakpoint");
+        ptrace::cont(child, None).expect("Failed to continue");
+        assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Stopped(child, Signal::SIGTRAP));
+        crate::working::remove_breakpoint(&mut breakpoints, child, address).unwrap();
+        let mut regs = ptrace::getregs(child).unwrap();
+        assert_eq!(regs.rip - 1, address, "The child should stop right after the breakpoint");
+        regs.rip = address;
+        ptrace::setregs(child, regs).unwrap();
+    }
+
+    /// The path of a fixture program compiled by `build.rs`, by the name of its source file
+    /// without `.c`, or of one of its variants.
+    fn fixture(name: &str) -> String {
+        let path = format!("{}/{}", env!("RUSTDBG_FIXTURES"), name);
+        assert!(std::path::Path::new(&path).is_file(), "The fixture {} was not built, is a C compiler (cc) installed?", name);
+        path
+    }
+
+    /// The path of a file written by a test, in `target/scratch`.
+    fn scratch(name: &str) -> String {
+        let directory = format!("{}/target/scratch", env!("CARGO_MANIFEST_DIR"));
+        std::fs::create_dir_all(&directory).expect("Failed to create the scratch directory");
+        format!("{}/{}", directory, name)
+    }
+
+    #[test]
+    fn test_memory_writes() {
+        use crate::maps::{parse_maps, protected_mappings};
+        use crate::memory::{read_bytes, read_u64};
+        let mappings = parse_maps(
+            "1000-2000 r-xp 00000000 08:01 12 /bin/prog\n2000-3000 rw-p 00001000 08:01 12 /bin/prog\n4000-5000 r--p 00000000 00:00 0\n",
+        );
+        assert_eq!(protected_mappings(&mappings, 0x2000, 16).unwrap(), Vec::<&crate::maps::MapEntry>::new());
+        let protected = protected_mappings(&mappings, 0x1ff8, 16).unwrap();
+        assert_eq!(protected.len(), 1);
+        assert_eq!(protected[0].describe(), "0x1000-0x2000 r-xp /bin/prog");
+        assert_eq!(protected_mappings(&mappings, 0x2ff8, 16), Err(0x3000));
+        assert_eq!(protected_mappings(&mappings, 0x4000, 1).unwrap()[0].describe(), "0x4000-0x5000 r--p anonymous");
+
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let child = debugger.child;
+        assert!(crate::run_command("b inspect; c", &mut debugger));
+        let rsp = ptrace::getregs(child).unwrap().rsp;
+        let below = read_u64(&child, rsp - 8).unwrap();
+        // Across two words, keeping the bytes around.
+        assert!(crate::run_command(&format!("set mem {:#x} = 1, 2, 3, 4, 5", rsp - 3), &mut debugger));
+        assert_eq!(read_bytes(&child, rsp - 3, 5).unwrap(), [1, 2, 3, 4, 5]);
+        assert_eq!(read_u64(&child, rsp - 8).unwrap() & 0xff_ffff_ffff, below & 0xff_ffff_ffff);
+        let rip = ptrace::getregs(child).unwrap().rip;
+        let code = read_bytes(&child, rip, 1).unwrap();
+        // Writing to the code asks first, and with confirm off it writes at once.
+        assert!(crate::repl::needs_confirmation(&debugger));
+        assert!(crate::run_command("set confirm off", &mut debugger));
+        assert!(crate::run_command("set mem $rip = 0x90", &mut debugger));
+        assert_eq!(read_bytes(&child, rip, 1).unwrap(), [0x90]);
+        assert!(crate::run_command(&format!("set mem $rip = {:#x}", code[0]), &mut debugger));
+        assert_eq!(read_bytes(&child, rip, 1).unwrap(), code);
+        assert!(crate::run_command("set confirm on", &mut debugger));
+        assert!(!crate::run_command("set mem $rsp = 0x100", &mut debugger));
+        assert!(!crate::run_command("set mem 0 = 1", &mut debugger));
+        // The int3 of a breakpoint stays, with the byte written as its original byte.
+        assert!(crate::run_command("b main", &mut debugger));
+        let main = debugger.breakpoints[debugger.breakpoints.len() - 1];
+        assert!(crate::run_command(&format!("set! mem {:#x} = 0x90", main), &mut debugger));
+        let mut bytes = read_bytes(&child, main, 1).unwrap();
+        assert_eq!(bytes, [0xcc]);
+        crate::working::restore_original_bytes(&debugger.breakpoint_table, main, &mut bytes);
+        assert_eq!(bytes, [0x90]);
+        ptrace::kill(child).ok();
+        waitpid(child, None).ok();
+    }
+
+    #[test]
+    fn test_library_api() {
+        use crate::step::StepStop;
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        let address = debugger.set_breakpoint("inspect").unwrap();
+        assert_eq!(debugger.breakpoint_locations, ["inspect"]);
+        assert!(debugger.set_breakpoint("nowhere").is_err());
+        assert_eq!(debugger.cont(), Ok(StepStop::Breakpoint(address)));
+        let registers = debugger.registers().unwrap();
+        assert_eq!(registers.rip, address);
+        // The second argument of inspect is the string "hello".
+        assert_eq!(debugger.read_memory(registers.rsi, 5).unwrap(), b"hello");
+        // The int3 of a breakpoint is not among the bytes read.
+        let main = debugger.set_breakpoint("main").unwrap();
+        assert_ne!(debugger.read_memory(main, 1).unwrap(), [0xcc]);
+        assert_eq!(debugger.step(), Ok(StepStop::Done));
+        assert_ne!(debugger.registers().unwrap().rip, address);
+        assert_eq!(debugger.cont(), Ok(StepStop::Exited(0)));
+        assert_eq!(debugger.exit_status(), Some(0));
+        assert!(debugger.mappings.is_empty(), "No mappings once the program has exited");
+        assert!(Debugger::launch("/nonexistent/program", &[]).is_err());
+    }
+
+    #[test]
+    fn test_error_variants() {
+        use crate::error::DbgError;
+        use crate::step::StepStop;
+        let path = fixture("locals");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&path, &[]).unwrap();
+        assert!(matches!(debugger.set_breakpoint("main+"), Err(DbgError::Parse(err)) if err.input == "main+"));
+        assert!(matches!(debugger.set_breakpoint("locals.c:999"), Err(DbgError::LineOutOfRange { line: 999, .. })));
+        assert_eq!(crate::working::remove_breakpoint(&mut debugger.breakpoint_table, debugger.child, 0x1234), Err(DbgError::NoSuchBreakpoint(0x1234)));
+        assert!(matches!(debugger.read_memory(0, 8), Err(DbgError::Memory { address: 0, .. })));
+        assert!(matches!(crate::memory::read_bytes(&debugger.child, 0x7, 4), Err(DbgError::Memory { address: 0x7, .. })));
+        assert_eq!(debugger.cont(), Ok(StepStop::Exited(0)));
+        assert_eq!(debugger.cont(), Err(DbgError::ProcessExited));
+        assert_eq!(debugger.registers().map(|registers| registers.rip), Err(DbgError::ProcessExited));
+        assert_eq!(debugger.wait_event(), Err(DbgError::ProcessExited), "The program was already waited for");
+        assert!(matches!(Debugger::attach(i32::MAX), Err(DbgError::NoProcess { pid: i32::MAX, .. })));
+    }
+
+    #[test]
+    fn test_inferior_state() {
+        use crate::state::{InferiorState, StopReason};
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("locals"), &[]).unwrap();
+        assert_eq!(debugger.state, InferiorState::NotStarted);
+        crate::dispatch(&mut debugger, "r").unwrap();
+        let address = debugger.set_breakpoint("main").unwrap();
+        crate::dispatch(&mut debugger, "run").unwrap();
+        assert_eq!(debugger.state, InferiorState::Stopped { reason: StopReason::Breakpoint(address) });
+        let err = crate::dispatch(&mut debugger, "run").unwrap_err();
+        assert_eq!(err.to_string(), "The program is already running, use c to continue it.");
+        crate::dispatch(&mut debugger, "si").unwrap();
+        assert_eq!(debugger.state, InferiorState::Stopped { reason: StopReason::Step });
+        crate::dispatch(&mut debugger, "syscall").unwrap();
+        assert!(matches!(debugger.state, InferiorState::Stopped { reason: StopReason::Syscall { entry: true, .. } }), "{:?}", debugger.state);
+
+        // A command that doesn't apply is refused before it runs.
+        let stopped = debugger.state;
+        debugger.state = InferiorState::Running;
+        assert_eq!(crate::dispatch(&mut debugger, "r").unwrap_err().to_string(), "The program is running, it must stop before this command.");
+        assert_eq!(crate::prompt::prompt(&mut debugger), "rustdbg [running]> ");
+        debugger.state = stopped;
+
+        // The rest of the line is skipped once the program terminates.
+        let err = crate::dispatch(&mut debugger, "c; r").unwrap_err();
+        assert_eq!(err.to_string(), "The program has terminated, skipping \"r\".");
+        assert_eq!(debugger.state, InferiorState::Exited { code: 0 });
+        assert_eq!(debugger.exit_status(), Some(0));
+        for command in ["c", "r", "m $rsp", "bt", "si"] {
+            let err = crate::dispatch(&mut debugger, command).unwrap_err();
+            assert_eq!(err.to_string(), "The program is not being run.", "{}", command);
+        }
+        assert_eq!(crate::dispatch(&mut debugger, "run").unwrap_err().to_string(), "The program has terminated, use restart to run it again.");
+        assert!(crate::dispatch(&mut debugger, "help").is_ok(), "Commands not using the program still run");
+        assert_eq!(crate::prompt::prompt(&mut debugger), "rustdbg [no process]> ");
+    }
+
+    #[test]
+    fn test_mock_breakpoints_in_same_word() {
+        use crate::error::DbgError;
+        use crate::tracee::MockTracee;
+        use crate::working::{remove_breakpoint, BreakpointTable};
+        let mut breakpoints = BreakpointTable::default();
+        let code = [0x55, 0x48, 0x89, 0xe5, 0x90, 0x90, 0x90, 0xc3, 0x0f, 0x05, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3];
+        let mut tracee = MockTracee::new(0x1000, &code);
+        set_breakpoint(&mut breakpoints, &mut tracee, 0x1000).unwrap();
+        set_breakpoint(&mut breakpoints, &mut tracee, 0x1003).unwrap();
+        assert_eq!(tracee.bytes(0x1000, 8), [0xcc, 0x48, 0x89, 0xcc, 0x90, 0x90, 0x90, 0xc3]);
+        // Setting it again keeps the original byte, not the int3.
+        set_breakpoint(&mut breakpoints, &mut tracee, 0x1003).unwrap();
+        let mut read = tracee.bytes(0x1000, 8);
+        crate::working::restore_original_bytes(&breakpoints, 0x1000, &mut read);
+        assert_eq!(read, code[..8]);
+        // Removing one leaves the int3 of the other in the same word.
+        remove_breakpoint(&mut breakpoints, &mut tracee, 0x1000).unwrap();
+        assert_eq!(tracee.bytes(0x1000, 8), [0x55, 0x48, 0x89, 0xcc, 0x90, 0x90, 0x90, 0xc3]);
+        remove_breakpoint(&mut breakpoints, &mut tracee, 0x1003).unwrap();
+        assert_eq!(tracee.bytes(0x1000, 16), code);
+        assert_eq!(remove_breakpoint(&mut breakpoints, &mut tracee, 0x1003), Err(DbgError::NoSuchBreakpoint(0x1003)));
+        // The word of a breakpoint 8 bytes before the end of the memory is the last one readable.
+        assert!(matches!(set_breakpoint(&mut breakpoints, &mut tracee, 0x100c), Err(DbgError::Memory { address: 0x100c, .. })));
+        assert!(!crate::working::is_breakpoint(&breakpoints, 0x100c), "A failed breakpoint is not armed");
+        assert!(tracee.requests.is_empty(), "Nothing was resumed");
+
+        // Each process has its own breakpoints, at the same addresses too.
+        let (mut first, mut second) = (MockTracee::new(0x1000, &code), MockTracee::new(0x1000, &code));
+        let (mut first_breakpoints, mut second_breakpoints) = (BreakpointTable::default(), BreakpointTable::default());
+        set_breakpoint(&mut first_breakpoints, &mut first, 0x1000).unwrap();
+        assert!(!crate::working::is_breakpoint(&second_breakpoints, 0x1000));
+        set_breakpoint(&mut second_breakpoints, &mut second, 0x1000).unwrap();
+        remove_breakpoint(&mut first_breakpoints, &mut first, 0x1000).unwrap();
+        assert_eq!(first.bytes(0x1000, 1), [0x55]);
+        assert_eq!(second.bytes(0x1000, 1), [0xcc], "Still armed in the other process");
+        assert!(crate::working::is_breakpoint(&second_breakpoints, 0x1000));
+    }
+
+    #[test]
+    fn test_mock_stop_classification() {
+        use crate::step::StepStop;
+        use crate::tracee::{MockTracee, Tracee};
+        use crate::working::{classify_stop, BreakpointTable};
+        let mut breakpoints = BreakpointTable::default();
+        let code = [0x90; 16];
+        let mut tracee = MockTracee::new(0x2000, &code);
+        set_breakpoint(&mut breakpoints, &mut tracee, 0x2004).unwrap();
+        let trap = WaitStatus::Stopped(MockTracee::PID, Signal::SIGTRAP);
+        // The int3 executed, the pc is after it.
+        tracee.regs = MockTracee::registers(|regs| regs.rip = 0x2005);
+        assert_eq!(classify_stop(&mut breakpoints, &mut tracee, trap).unwrap(), Some((StepStop::Breakpoint(0x2004), true)));
+        assert_eq!(tracee.getregs().unwrap().rip, 0x2004, "The pc is rewound to the restored instruction");
+        assert_eq!(tracee.bytes(0x2000, 16), code);
+        // A trap not at a breakpoint of the user keeps its pc.
+        tracee.regs = MockTracee::registers(|regs| regs.rip = 0x2009);
+        assert_eq!(classify_stop(&mut breakpoints, &mut tracee, trap).unwrap(), Some((StepStop::Breakpoint(0x2008), false)));
+        assert_eq!(tracee.getregs().unwrap().rip, 0x2009);
+        let segv = WaitStatus::Stopped(MockTracee::PID, Signal::SIGSEGV);
+        assert_eq!(classify_stop(&mut breakpoints, &mut tracee, segv).unwrap(), Some((StepStop::Signal(Signal::SIGSEGV), false)));
+        assert_eq!(classify_stop(&mut breakpoints, &mut tracee, WaitStatus::Exited(MockTracee::PID, 3)).unwrap(), Some((StepStop::Exited(3), false)));
+        let killed = WaitStatus::Signaled(MockTracee::PID, Signal::SIGKILL, false);
+        assert_eq!(classify_stop(&mut breakpoints, &mut tracee, killed).unwrap(), Some((StepStop::Killed(Signal::SIGKILL), false)));
+        assert_eq!(classify_stop(&mut breakpoints, &mut tracee, WaitStatus::Continued(MockTracee::PID)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_mock_syscall_entry_and_exit() {
+        use crate::step::StepStop;
+        use crate::syscall::{run_to_syscall, SyscallStop};
+        use crate::tracee::MockTracee;
+        let trap = WaitStatus::Stopped(MockTracee::PID, Signal::SIGTRAP);
+        let enosys = -(nix::libc::ENOSYS as i64) as u64;
+        let mut tracee = MockTracee::new(0x3000, &[0x0f, 0x05, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90]);
+        let at_syscall = |orig_rax, rax| MockTracee::registers(|regs| {
+            regs.rip = 0x3002;
+            regs.orig_rax = orig_rax;
+            regs.rax = rax;
+        });
+        tracee.push_stop(trap, at_syscall(1, enosys));
+        tracee.push_stop(trap, at_syscall(1, 13));
+        // A system call failing with ENOSYS is still an exit.
+        tracee.push_stop(trap, at_syscall(999, enosys));
+        tracee.push_stop(trap, at_syscall(999, enosys));
+        tracee.push_stop(WaitStatus::Stopped(MockTracee::PID, Signal::SIGINT), at_syscall(0, 0));
+        tracee.push_stop(trap, at_syscall(60, enosys));
+        tracee.push_stop(WaitStatus::Exited(MockTracee::PID, 0), at_syscall(60, enosys));
+        let mut in_syscall = false;
+        let mut breakpoints = crate::working::BreakpointTable::default();
+        let mut next = |in_syscall: &mut bool| run_to_syscall(&mut breakpoints, &mut tracee, in_syscall).unwrap();
+        assert_eq!(next(&mut in_syscall), SyscallStop::Entry { number: 1 });
+        assert!(in_syscall);
+        assert_eq!(next(&mut in_syscall), SyscallStop::Exit { number: 1, value: 13 });
+        assert!(!in_syscall);
+        assert_eq!(next(&mut in_syscall), SyscallStop::Entry { number: 999 });
+        assert_eq!(next(&mut in_syscall), SyscallStop::Exit { number: 999, value: enosys });
+        assert_eq!(next(&mut in_syscall), SyscallStop::Other(StepStop::Signal(Signal::SIGINT)));
+        assert!(!in_syscall, "A signal doesn't count as a system call stop");
+        assert_eq!(next(&mut in_syscall), SyscallStop::Entry { number: 60 });
+        assert_eq!(next(&mut in_syscall), SyscallStop::Other(StepStop::Exited(0)));
+        assert_eq!(tracee.requests, ["syscall"; 7]);
+
+        // A breakpoint hit on the way is not a system call stop.
+        let mut tracee = MockTracee::new(0x3100, &[0x90; 16]);
+        set_breakpoint(&mut breakpoints, &mut tracee, 0x3100).unwrap();
+        tracee.push_stop(trap, MockTracee::registers(|regs| regs.rip = 0x3101));
+        let mut in_syscall = false;
+        assert_eq!(run_to_syscall(&mut breakpoints, &mut tracee, &mut in_syscall).unwrap(), SyscallStop::Other(StepStop::Breakpoint(0x3100)));
+        assert!(!in_syscall);
+        assert_eq!(tracee.regs.rip, 0x3100);
+    }
+
+    #[test]
+    fn test_procfs_parsing() {
+        use crate::procfs::{describe_flags, parse_fdinfo, parse_status, read_fds, FdInfo};
+        let status = parse_status("Name:\tloop\nUmask:\t0022\nState:\tt (tracing stop)\nPPid:\t42\nThreads:\t3\nVmSize:\t    2496 kB\nVmRSS:\tlots\nmalformed line\n");
+        assert_eq!((status.name.as_str(), status.state.as_str()), ("loop", "t (tracing stop)"));
+        assert_eq!((status.ppid, status.threads, status.vm_size, status.vm_rss), (Some(42), Some(3), Some(2496), None));
+        assert_eq!(parse_fdinfo("pos:\t12\nflags:\t02102002\nmnt_id:\t1\n"), Some(FdInfo { pos: 12, flags: 0o2102002 }));
+        assert_eq!(parse_fdinfo("pos:\t12\nmnt_id:\t1\n"), None);
+        assert_eq!(describe_flags(0), "O_RDONLY");
+        assert_eq!(describe_flags(0o1), "O_WRONLY");
+        assert_eq!(describe_flags(0o2102002), "O_RDWR|O_APPEND|O_LARGEFILE|O_CLOEXEC");
+        assert_eq!(describe_flags(0o4000000000), "O_RDONLY|0o4000000000", "The unknown bits in octal");
+        let files = read_fds(Pid::this()).unwrap();
+        assert!(files.windows(2).all(|pair| pair[0].fd < pair[1].fd), "{:?}", files);
+        assert!(files.iter().all(|file| file.info.is_some()), "{:?}", files);
+    }
+
+    #[test]
+    fn test_siginfo_decoding() {
+        use crate::siginfo::{describe_code, summary, SigInfo};
+        use crate::step::StepStop;
+        assert_eq!(describe_code(11, 1), Some(("SEGV_MAPERR", "address not mapped to object")));
+        assert_eq!(describe_code(11, 2).map(|(name, _)| name), Some("SEGV_ACCERR"));
+        assert_eq!(describe_code(7, 1).map(|(name, _)| name), Some("BUS_ADRALN"));
+        assert_eq!(describe_code(8, 1).map(|(name, _)| name), Some("FPE_INTDIV"));
+        assert_eq!(describe_code(17, 1).map(|(name, _)| name), Some("CLD_EXITED"));
+        assert_eq!(describe_code(10, 0).map(|(name, _)| name), Some("SI_USER"), "Any signal can be sent by kill");
+        assert_eq!(describe_code(11, -6).map(|(name, _)| name), Some("SI_TKILL"));
+        assert_eq!((describe_code(11, 5), describe_code(10, 1), describe_code(11, 0x81)), (None, None, None));
+        let fault = SigInfo { signo: 11, code: 1, address: Some(0x10), sender: None };
+        assert_eq!(summary(&fault), "si_code SEGV_MAPERR (1): address not mapped to object, at address 0x10");
+        let killed = SigInfo { signo: 10, code: 0, address: None, sender: Some((42, 1000)) };
+        assert_eq!(summary(&killed), "si_code SI_USER (0): sent by kill, from pid 42 (uid 1000)");
+        assert_eq!(summary(&SigInfo { signo: 10, code: 7, address: None, sender: None }), "si_code 7");
+
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("crash"), &[]).unwrap();
+        assert_eq!(debugger.cont(), Ok(StepStop::Signal(Signal::SIGSEGV)));
+        assert_eq!(debugger.last_signal, Some(SigInfo { signo: 11, code: 1, address: Some(0x10), sender: None }), "node->next is 0x10");
+        assert!(crate::dispatch(&mut debugger, "info signal").unwrap().output.text().contains("SEGV_MAPERR"));
+        let mut debugger = Debugger::launch(&fixture("loop"), &[]).unwrap();
+        nix::sys::signal::kill(debugger.child, Signal::SIGCHLD).unwrap();
+        assert_eq!(debugger.cont(), Ok(StepStop::Signal(Signal::SIGCHLD)));
+        let sender = (nix::unistd::getpid().as_raw(), unsafe { nix::libc::getuid() });
+        assert_eq!(debugger.last_signal, Some(SigInfo { signo: 17, code: 0, address: None, sender: Some(sender) }));
+        assert_eq!(debugger.step(), Ok(StepStop::Done));
+        assert!(crate::dispatch(&mut debugger, "info signal").is_err(), "Not stopped by a signal anymore");
+    }
+
+    #[test]
+    fn test_coredump() {
+        use crate::coredump::{dump_rule, parse_smaps, write_core, Dump, SmapsEntry};
+        use crate::maps::parse_maps;
+        let smaps = parse_smaps("1000-2000 r--p 00000000 08:01 12 /bin/prog\nAnonymous:         4 kB\nVmFlags: rd mr mw me dw\n3000-4000 rw-s 00000000 00:05 7 /dev/mem\nVmFlags: rd wr sh io pf\n");
+        assert_eq!(smaps, [
+            SmapsEntry { start: 0x1000, vm_flags: vec!["rd".into(), "mr".into(), "mw".into(), "me".into(), "dw".into()], anonymous: 4 },
+            SmapsEntry { start: 0x3000, vm_flags: vec!["rd".into(), "wr".into(), "sh".into(), "io".into(), "pf".into()], anonymous: 0 },
+        ]);
+        let mappings = parse_maps(
+            "1000-2000 r--p 00000000 08:01 12 /bin/prog\n2000-3000 r-xp 00001000 08:01 12 /bin/prog\n3000-4000 rw-s 00000000 00:05 7 /dev/mem\n\
+             4000-5000 rw-p 00000000 00:00 0 [heap]\n5000-6000 rw-s 00000000 00:01 9 /dev/zero (deleted)\n6000-7000 ---p 00000000 00:00 0\n\
+             7000-8000 r-xp 00000000 00:00 0 [vdso]\n8000-9000 rw-p 00000000 00:00 0\n",
+        );
+        let flags = |names: &[&str], anonymous: u64| SmapsEntry { start: 0, vm_flags: names.iter().map(|name| name.to_string()).collect(), anonymous };
+        let rule = |index: usize, smaps: &SmapsEntry, filter: u32| dump_rule(&mappings[index], smaps, filter);
+        let none = flags(&[], 0);
+        assert_eq!(rule(0, &none, 0x33), Dump::Header, "The ELF header of a file");
+        assert_eq!(rule(0, &flags(&[], 4), 0x33), Dump::Whole, "Relocated data");
+        assert_eq!(rule(1, &none, 0x33), Dump::Skip, "Read from the file");
+        assert_eq!(rule(1, &none, 0x37), Dump::Whole, "Private file mappings filtered in");
+        assert_eq!(rule(2, &flags(&["io", "pf"], 0), 0xff), Dump::Skip, "Device memory");
+        assert_eq!((rule(3, &none, 0x33), rule(3, &none, 0x32)), (Dump::Whole, Dump::Skip));
+        assert_eq!((rule(4, &none, 0x33), rule(4, &none, 0x31)), (Dump::Whole, Dump::Skip), "Shared anonymous memory");
+        assert_eq!(rule(5, &none, 0xff), Dump::Skip, "Not readable");
+        assert_eq!(rule(6, &flags(&["dd"], 0), 0), Dump::Whole, "The vDSO");
+        assert_eq!(rule(7, &flags(&["dd"], 4), 0x33), Dump::Skip, "madvise(MADV_DONTDUMP)");
+        assert_eq!((rule(7, &flags(&["ht"], 0), 0x33), rule(7, &flags(&["ht"], 0), 0x13)), (Dump::Whole, Dump::Skip), "Huge pages");
+
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("loop"), &[]).unwrap();
+        let breakpoint = debugger.set_breakpoint("count").unwrap();
+        let path = scratch(&format!("loop.core.{}", std::process::id()));
+        let stats = write_core(&debugger, &path).unwrap();
+        let core = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!((&core[..4], core[16], core[18]), (&b"\x7fELF"[..], 4, 62), "An x86_64 core file");
+        let program_headers = u16::from_le_bytes([core[56], core[57]]) as usize;
+        assert_eq!((stats.threads, stats.segments + 1), (1, program_headers));
+        let field = |offset: usize| u64::from_le_bytes(core[offset..offset + 8].try_into().unwrap());
+        let segment = (1..program_headers).map(|index| 64 + index * 56).find(|header| {
+            (field(header + 16)..field(header + 16) + field(header + 32)).contains(&breakpoint)
+        });
+        let header = segment.expect("The code of the breakpoint is dumped, an int3 written there");
+        let byte = core[(field(header + 8) + breakpoint - field(header + 16)) as usize];
+        assert_ne!(byte, 0xcc, "The original byte, without the breakpoint");
+        assert_eq!(byte, debugger.read_memory(breakpoint, 1).unwrap()[0]);
+    }
+
+    #[test]
+    fn test_corefile() {
+        use crate::corefile::CoreFile;
+        use crate::error::DbgError;
+        use crate::options::Config;
+        use crate::state::{InferiorState, StopReason, ValidIn};
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        let options = crate::options::parse(&args(&["--core", "core.1", "./app"])).unwrap();
+        assert_eq!((options.core.as_deref(), options.program_path.as_str()), (Some("core.1"), "./app"));
+        assert!(crate::options::parse(&args(&["--core", "core.1"])).is_err(), "The program is needed for its symbols");
+        assert!(crate::options::parse(&args(&["--core", "core.1", "-p", "1"])).is_err());
+        assert!(crate::options::parse(&args(&["--core", "core.1", "./app", "--", "arg"])).is_err());
+        let program = fixture("loop");
+        assert!(matches!(CoreFile::open(&program), Err(DbgError::Core { .. })), "An executable is not a core file");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&program, &[]).unwrap();
+        let breakpoint = debugger.set_breakpoint("count").unwrap();
+        assert_eq!(debugger.cont(), Ok(crate::step::StepStop::Breakpoint(breakpoint)));
+        let path = scratch(&format!("loop.core.{}", std::process::id()));
+        crate::coredump::write_core(&debugger, &path).unwrap();
+        let config = Config { program_path: program.clone(), core: Some(path.clone()), ..Config::default() };
+        let mut core = Debugger::open_core(&config).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(core.state, InferiorState::Stopped { reason: StopReason::Core });
+        assert_eq!((core.child, core.thread, core.threads.len()), (debugger.child, debugger.thread, 1));
+        let regs = debugger.registers().unwrap();
+        assert_eq!(core.registers().unwrap().rip, regs.rip);
+        assert_eq!(core.registers().unwrap().rsp, regs.rsp);
+        assert_eq!(core.read_memory(regs.rsp, 64), debugger.read_memory(regs.rsp, 64), "The stack, from the dump");
+        let main = core.resolve_symbol("main").unwrap();
+        assert_eq!(core.read_memory(main, 16), debugger.read_memory(main, 16), "The code, from the executable");
+        let functions: Vec<String> = crate::backtrace::backtrace(&mut core, 2)
+            .unwrap()
+            .iter()
+            .filter_map(|frame| core.symbolize(frame.pc).map(|(name, _, _)| name))
+            .collect();
+        assert_eq!(functions, ["count", "main"]);
+        assert_eq!(core.cont(), Err(DbgError::NotSupportedOnCore));
+        assert_eq!(core.step(), Err(DbgError::NotSupportedOnCore));
+        assert_eq!(core.set_breakpoint("main"), Err(DbgError::NotSupportedOnCore));
+        assert_eq!(ValidIn::Live.check(&core.state), Err("Not supported on core files."));
+        assert_eq!(ValidIn::Stopped.check(&core.state), Ok(()));
+        assert!(!core.has_live_process());
+        crate::launch::release(&mut core);
+        assert_eq!(debugger.cont(), Ok(crate::step::StepStop::Exited(0)), "The process of the core is left alone");
+    }
+
+    #[test]
+    fn test_gdbserver() {
+        use crate::gdbserver::{self, Action, Connection};
+        use crate::step::StepStop;
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        assert_eq!(crate::options::parse(&args(&["--gdbserver", ":1234", "./app"])).unwrap().gdbserver.as_deref(), Some(":1234"));
+        assert!(crate::options::parse(&args(&["--gdbserver", "1234", "./app"])).is_err(), "A port alone is not an address");
+        assert!(crate::options::parse(&args(&["--gdbserver", ":1234", "--core", "core.1", "./app"])).is_err());
+        assert_eq!(gdbserver::checksum(b"qSupported"), 0x37);
+        assert_eq!(gdbserver::frame(b"OK"), b"$OK#9a");
+        assert_eq!(gdbserver::frame(b"a#b}"), b"$a}\x03b}]#1d", "Escaped, the checksum of the escaped data");
+        assert_eq!((gdbserver::to_gdb_signal(Signal::SIGUSR1), gdbserver::to_gdb_signal(Signal::SIGCHLD)), (30, 20));
+        assert_eq!((gdbserver::to_gdb_signal(Signal::SIGSEGV), gdbserver::from_gdb_signal(10)), (11, Some(Signal::SIGBUS)));
+        assert_eq!(gdbserver::from_gdb_signal(0), None);
+        // A corrupted packet is asked again, a reply is sent again until acknowledged.
+        let input: &[u8] = b"+$?#00$?#3f-+";
+        let mut written = Vec::new();
+        let mut connection = Connection::new(input, &mut written);
+        assert_eq!(connection.receive().unwrap(), Some(b"?".to_vec()));
+        connection.send(b"OK").unwrap();
+        assert_eq!(connection.receive().unwrap(), None);
+        assert_eq!(written, b"-+$OK#9a$OK#9a");
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("loop"), &[]).unwrap();
+        let handle = |debugger: &mut Debugger, packet: &str| gdbserver::handle(debugger, packet.as_bytes(), &StepStop::Done);
+        let reply = |action: Action| match action {
+            Action::Reply(reply) => String::from_utf8(reply).unwrap(),
+            other => panic!("Not a reply: {:?}", other),
+        };
+        assert!(reply(handle(&mut debugger, "qSupported:multiprocess+;swbreak+")).contains("QStartNoAckMode+"));
+        assert_eq!(reply(handle(&mut debugger, "qAttached")), "0", "Started by the debugger");
+        assert_eq!(reply(handle(&mut debugger, "?")), format!("T05thread:{:x};", debugger.child.as_raw()));
+        let regs = debugger.registers().unwrap();
+        let registers = reply(handle(&mut debugger, "g"));
+        assert_eq!(registers.len(), gdbserver::REGISTERS_SIZE * 2);
+        let mut decoded = regs;
+        decoded.rip = 0;
+        let bytes: Vec<u8> = (0..registers.len()).step_by(2).map(|index| u8::from_str_radix(&registers[index..index + 2], 16).unwrap()).collect();
+        assert!(gdbserver::decode_registers(&bytes, &mut decoded));
+        assert_eq!(decoded.rip, regs.rip);
+        let count = debugger.resolve_symbol("count").unwrap();
+        let code = reply(handle(&mut debugger, &format!("m{:x},4", count)));
+        assert_eq!(reply(handle(&mut debugger, &format!("Z0,{:x},1", count))), "OK");
+        assert_eq!(reply(handle(&mut debugger, &format!("m{:x},4", count))), code, "The breakpoint is not shown");
+        assert_eq!(handle(&mut debugger, "vCont;c"), Action::Resume { step: false, signal: None });
+        assert_eq!(handle(&mut debugger, "C0b"), Action::Resume { step: false, signal: Some(Signal::SIGSEGV) });
+        let stop = debugger.cont().unwrap();
+        assert_eq!(stop, StepStop::Breakpoint(count));
+        assert_eq!(gdbserver::stop_reply(&debugger, &stop), format!("T05thread:{:x};swbreak:;", debugger.child.as_raw()));
+        assert_eq!(reply(handle(&mut debugger, &format!("z0,{:x},1", count))), "OK", "Removed when hit");
+        assert_eq!(reply(handle(&mut debugger, "Z1,0,1")), "", "No hardware breakpoints");
+        assert_eq!(reply(handle(&mut debugger, "vMustReplyEmpty")), "");
+        assert_eq!(handle(&mut debugger, "vKill;1"), Action::Close(Some(b"OK".to_vec())));
+    }
+
+    #[test]
+    fn test_record() {
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("loop"), &[]).unwrap();
+        let count = debugger.set_breakpoint("count").unwrap();
+        debugger.cont().unwrap();
+        let path = scratch(&format!("loop.record.{}", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        assert!(crate::dispatch(&mut debugger, "record on").is_err(), "The file is needed");
+        crate::dispatch(&mut debugger, &format!("record on {}", path)).unwrap();
+        let mut pcs = Vec::new();
+        for _ in 0..3 {
+            pcs.push(debugger.registers().unwrap().rip);
+            debugger.step().unwrap();
+        }
+        assert_eq!(pcs[0], count);
+        let stats = crate::dispatch(&mut debugger, "record stats").unwrap().output.text();
+        assert!(stats.contains(": 3 instructions,"), "{}", stats);
+        // Not single-stepped, not recorded.
+        debugger.set_breakpoint("count").unwrap();
+        debugger.cont().unwrap();
+        debugger.step().unwrap();
+        crate::dispatch(&mut debugger, "record off").unwrap();
+        debugger.step().unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let lines: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 4, "{}", contents);
+        for (line, pc) in lines.iter().zip(&pcs) {
+            assert_eq!(line["pc"], crate::json::address(*pc));
+        }
+        assert_eq!(lines[0].as_object().unwrap().len(), 18, "Every register first: {}", lines[0]);
+        assert!(lines[1].as_object().unwrap().len() < 18, "Then the registers changed: {}", lines[1]);
+        assert_eq!(lines[3].as_object().unwrap().len(), 18, "Every register after running unrecorded: {}", lines[3]);
+        assert_eq!(lines[3]["pc"], crate::json::address(count));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("loop"), &[]).unwrap();
+        assert!(crate::dispatch(&mut debugger, "rc").is_err(), "Nothing to go back to");
+        debugger.set_breakpoint("main").unwrap();
+        crate::dispatch(&mut debugger, "c").unwrap();
+        crate::dispatch(&mut debugger, "snapshot").unwrap();
+        crate::dispatch(&mut debugger, "set snapshot-interval 3").unwrap();
+        let mut hits = Vec::new();
+        for _ in 0..3 {
+            let count = debugger.set_breakpoint("count").unwrap();
+            crate::dispatch(&mut debugger, "c").unwrap();
+            let regs = debugger.registers().unwrap();
+            assert_eq!(regs.rip, count);
+            hits.push(regs);
+            // Off the one-shot breakpoint, to arm it again.
+            debugger.step().unwrap();
+        }
+        let info = crate::dispatch(&mut debugger, "info snapshots").unwrap().output.text();
+        assert!(info.contains("at stop 1") && info.contains("at stop 3"), "{}", info);
+        // Executing again from the snapshot taken at the second hit, to the third one.
+        crate::dispatch(&mut debugger, "reverse-continue").unwrap();
+        assert_eq!(debugger.registers().unwrap().rdi, 2);
+        // Restoring that snapshot.
+        crate::dispatch(&mut debugger, "reverse-continue").unwrap();
+        assert_eq!(debugger.registers().unwrap().rdi, 1);
+        // Executing again from the snapshot taken at main.
+        crate::dispatch(&mut debugger, "reverse-continue").unwrap();
+        let regs = debugger.registers().unwrap();
+        assert_eq!((regs.rip, regs.rdi, regs.rsp), (hits[0].rip, 0, hits[0].rsp));
+        // Back to the end of the prologue.
+        crate::dispatch(&mut debugger, "reverse-stepi").unwrap();
+        let rip = debugger.registers().unwrap().rip;
+        assert!(rip < hits[0].rip && rip + 8 > hits[0].rip, "{:#x} before {:#x}", rip, hits[0].rip);
+        debugger.step().unwrap();
+        assert_eq!(debugger.registers().unwrap().rip, hits[0].rip);
+        // The total of the program went back too.
+        assert_eq!(debugger.cont(), Ok(crate::step::StepStop::Exited(0)));
+    }
+
+    #[test]
+    fn test_scripting() {
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("loop"), &[]).unwrap();
+        let rip = debugger.registers().unwrap().rip;
+        let output = crate::dispatch(&mut debugger, "script eval 'print(reg(\"pc\") == reg(\"rip\")); print(reg(\"rip\"))'").unwrap().output.text();
+        assert_eq!(output, format!("true\n{}\n", rip));
+        let script = "let sp = reg(\"rsp\"); let old = read_u64(sp); write_u64(sp, 0x1234); print(read_u64(sp)); write_u64(sp, old); set_reg(\"rax\", 42)";
+        let output = crate::dispatch(&mut debugger, &format!("script eval '{}'", script)).unwrap().output.text();
+        assert_eq!(output, "4660\n");
+        assert_eq!(debugger.registers().unwrap().rax, 42);
+        let err = crate::dispatch(&mut debugger, "script eval 'reg(\"xyz\")'").unwrap_err().to_string();
+        assert!(err.contains("Invalid register \"xyz\""), "{}", err);
+        // The hooks run at the stops of the commands too, with the event of the stop.
+        crate::dispatch(&mut debugger, "script eval 'breakpoint(\"count\"); on_stop(|event| print(`${event.reason} ${event.address == reg(\"pc\")}`))'").unwrap();
+        let output = crate::dispatch(&mut debugger, "c").unwrap().output.text();
+        assert!(output.ends_with("breakpoint true\n"), "{}", output);
+        assert_eq!(crate::dispatch(&mut debugger, "script clear").unwrap().output.text(), "Removed 1 stop hooks.\n");
+        let output = crate::dispatch(&mut debugger, "c").unwrap().output.text();
+        assert!(output.contains("exited") && !output.contains("true"), "{}", output);
+    }
+
+    #[test]
+    fn test_log_level() {
+        use crate::options::parse;
+        use log::LevelFilter;
+        let verbosity = |args: &[&str]| parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>()).unwrap().verbosity;
+        assert_eq!((verbosity(&["./app"]), verbosity(&["-v", "./app"]), verbosity(&["-vv", "./app"]), verbosity(&["-v", "--verbose", "./app"])), (0, 1, 2, 2));
+        // After --, -v is an argument of the program.
+        assert_eq!(verbosity(&["./app", "--", "-v"]), 0);
+        let levels: Vec<LevelFilter> = (0..4).map(crate::logging::level_for).collect();
+        assert_eq!(levels, [LevelFilter::Warn, LevelFilter::Debug, LevelFilter::Trace, LevelFilter::Trace]);
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("loop"), &[]).unwrap();
+        crate::dispatch(&mut debugger, "set log-level trace").unwrap();
+        assert!(crate::dispatch(&mut debugger, "show log-level").unwrap().output.text().starts_with("log-level = trace\n"));
+        assert!(crate::dispatch(&mut debugger, "set log-level loud").is_err());
+        assert_eq!(crate::logging::level(), "trace");
+        crate::dispatch(&mut debugger, "set log-level warn").unwrap();
+        assert_eq!(crate::logging::level(), "warn");
+    }
+
+    #[test]
+    fn test_mock_stop_cache() {
+        use crate::cache::{CachedTracee, StopCache};
+        use crate::tracee::{MockTracee, Tracee};
+        let mut tracee = CachedTracee { tid: MockTracee::PID, tracee: MockTracee::new(0x4000, &[0; 4096]), cache: std::rc::Rc::new(StopCache::default()) };
+        tracee.tracee.regs = MockTracee::registers(|regs| regs.rip = 0x4010);
+        // The prompt, the displays and the commands read the registers once per stop.
+        for _ in 0..12 {
+            assert_eq!(tracee.getregs().unwrap().rip, 0x4010);
+        }
+        assert_eq!(tracee.tracee.getregs_calls.get(), 1);
+        tracee.setregs(MockTracee::registers(|regs| regs.rip = 0x4020)).unwrap();
+        assert_eq!(tracee.getregs().unwrap().rip, 0x4020);
+        assert_eq!(tracee.tracee.getregs_calls.get(), 2);
+        // The page is read once, a change of the process is seen after it is resumed.
+        assert_eq!(tracee.read_word(0x4008).unwrap(), 0);
+        tracee.tracee.memory.insert(0x4008, 7);
+        assert_eq!(tracee.read_word(0x4008).unwrap(), 0);
+        tracee.tracee.push_stop(WaitStatus::Stopped(MockTracee::PID, Signal::SIGTRAP), MockTracee::registers(|regs| regs.rip = 0x4030));
+        tracee.cont().unwrap();
+        tracee.wait().unwrap();
+        assert_eq!(tracee.read_word(0x4008).unwrap(), 7);
+        assert_eq!(tracee.getregs().unwrap().rip, 0x4030);
+        assert_eq!(tracee.tracee.getregs_calls.get(), 3);
+        // Written through the cache, and out of the memory.
+        tracee.write_word(0x4008, 9).unwrap();
+        assert_eq!(tracee.read_word(0x4008).unwrap(), 9);
+        assert_eq!(tracee.read_word(0x4ffc), Err(nix::errno::Errno::EIO));
+    }
+
+    #[test]
+    fn test_arch() {
+        use crate::disasm::{disassemble, DisassemblyFlavor};
+        assert_eq!(Arch::of_executable(&fixture("loop")), Arch::X86_64);
+        assert_eq!(Arch::of_executable("/nonexistent"), Arch::X86_64);
+        // push ebp; mov ebp, esp; inc eax: 0x40 is a REX prefix in 64-bit code.
+        let code = [0x55, 0x89, 0xe5, 0x40];
+        let text: Vec<String> = disassemble(&code, 0x1000, 10, DisassemblyFlavor::Intel, Arch::I386).into_iter().map(|i| i.text).collect();
+        assert_eq!(text, ["push ebp", "mov ebp, esp", "inc eax"]);
+        assert_eq!(Arch::I386.syscall_name(4), "write");
+        assert_eq!(Arch::X86_64.syscall_name(4), "stat");
+        assert_eq!(Arch::I386.return_value(u64::MAX), 0xffff_ffff);
+        let regs = MockTracee::registers(|regs| {
+            regs.rsp = 0x2000;
+            regs.rdi = 7;
+        });
+        let mut stack = MockTracee::new(0x2000, &[0; 16]);
+        stack.memory.insert(0x2004, 9);
+        assert_eq!(Arch::I386.call_arguments(&stack, &regs, 0x2004, 2).unwrap(), [9, 0]);
+        assert_eq!(Arch::X86_64.call_arguments(&stack, &regs, 0x2004, 1).unwrap(), [7]);
+        let registers: Vec<&str> = Arch::I386.registers(&regs).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(registers, ["eax", "ebx", "ecx", "edx", "esi", "edi", "esp", "eip", "ebp"]);
+    }
+
+    #[test]
+    fn test_breakpoint_instruction() {
+        use crate::arch::{BREAKPOINT_INSTRUCTION, BREAKPOINT_LENGTH};
+        use crate::working::{patch_word, trap_address};
+        let word = i64::from_le_bytes([0x55, 0x48, 0x89, 0xe5, 0x90, 0x90, 0x90, 0xc3]);
+        let armed = patch_word(word, &BREAKPOINT_INSTRUCTION);
+        assert_eq!(armed.to_le_bytes()[..BREAKPOINT_LENGTH], BREAKPOINT_INSTRUCTION);
+        assert_eq!(armed.to_le_bytes()[BREAKPOINT_LENGTH..], word.to_le_bytes()[BREAKPOINT_LENGTH..], "Only the instruction is patched");
+        let original: [u8; BREAKPOINT_LENGTH] = word.to_le_bytes()[..BREAKPOINT_LENGTH].try_into().unwrap();
+        assert_eq!(patch_word(armed, &original), word);
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(trap_address(0x1001), 0x1000, "The pc is past the int3");
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(trap_address(0x1000), 0x1000, "The pc is at the brk");
+    }
+
+    #[test]
+    fn test_basic_blocks() {
+        use crate::coverage::basic_blocks;
+        // push rbp; test edi, edi; je 0x1008; xor eax, eax; ret; mov eax, 1; ret
+        let code = [0x55, 0x85, 0xff, 0x74, 0x03, 0x31, 0xc0, 0xc3, 0xb8, 0x01, 0x00, 0x00, 0x00, 0xc3];
+        assert_eq!(basic_blocks(&code, 0x1000, Arch::X86_64), [(0x1000, 5), (0x1005, 3), (0x1008, 6)]);
+        // call 0x2000; jmp 0x1000 (back to the start); a jump into the middle of the call.
+        let code = [0xe8, 0xfb, 0x0f, 0x00, 0x00, 0xeb, 0xf9, 0xeb, 0xf8];
+        assert_eq!(basic_blocks(&code, 0x1000, Arch::X86_64), [(0x1000, 5), (0x1005, 2), (0x1007, 2)]);
+        // Undecodable bytes end the code.
+        assert_eq!(basic_blocks(&[0x90, 0x0f, 0xff], 0x1000, Arch::X86_64), [(0x1000, 1)]);
+    }
+
+    #[test]
+    fn test_parse_call() {
+        use crate::call::{parse_call, CallArgument};
+        assert_eq!(parse_call("malloc_stats()").unwrap(), ("malloc_stats".to_string(), vec![]));
+        assert_eq!(parse_call("malloc_stats").unwrap(), ("malloc_stats".to_string(), vec![]));
+        let (function, arguments) = parse_call(" dump ( $rdi, \"a, \\\"b\\\"\\n\" ,-1 )").unwrap();
+        assert_eq!(function, "dump");
+        assert_eq!(
+            arguments,
+            [CallArgument::Value("$rdi".to_string()), CallArgument::Text("a, \"b\"\n".to_string()), CallArgument::Value("-1".to_string())]
+        );
+        assert!(parse_call("f(1,)").is_err(), "A missing argument");
+        assert!(parse_call("f(1").is_err(), "A missing parenthesis");
+        assert!(parse_call("f(\"abc)").is_err(), "A missing quote");
+        assert!(parse_call("f(\"a\" 1)").is_err(), "No comma after a string");
+    }
+
+    #[test]
+    fn test_allocate_memory() {
+        use crate::error::DbgError;
+        require_ptrace!();
+        let mut debugger = Debugger::launch(&fixture("locals"), &[]).unwrap();
+        let before = debugger.registers().unwrap();
+        let address = debugger.allocate(100).unwrap();
+        assert_eq!(address % 4096, 0, "Mapped on a page: {:#x}", address);
+        assert_eq!(debugger.registers().unwrap(), before, "The registers are put back");
+        crate::dispatch(&mut debugger, &format!("set mem {:#x} = 1 2 3", address)).unwrap();
+        assert_eq!(debugger.read_memory(address, 4).unwrap(), [1, 2, 3, 0]);
+        let err = debugger.deallocate(address + 1, 100).unwrap_err();
+        assert!(matches!(err, DbgError::Syscall { name: "munmap", .. }), "Not aligned on a page: {:?}", err);
+        debugger.deallocate(address, 100).unwrap();
+        assert!(debugger.read_memory(address, 4).is_err(), "Unmapped");
+        assert_eq!(debugger.cont().unwrap(), crate::step::StepStop::Exited(0), "The program goes on");
+    }
+
+    #[test]
+    fn test_stop_summary_text() {
+        use crate::debuginfo::SourceLocation;
+        use crate::summary::StopSummary;
+        let mut summary = StopSummary {
+            pc: 0x555555555140,
+            function: Some(("count".to_string(), 7)),
+            location: Some(SourceLocation { file: "loop.c".to_string(), line: 6 }),
+            mapping: Some((Some("/tmp/loop".to_string()), "r-xp".to_string())),
+            reason: Some("breakpoint"),
+            detail: Some("1".to_string()),
+            thread: (1, 1234),
+        };
+        assert_eq!(summary.text(), "0x0000555555555140 in count+0x7 at loop.c:6 [loop r-xp], breakpoint 1, thread 1 (tid 1234)");
+        summary.function = None;
+        summary.location = None;
+        summary.mapping = Some((None, "rw-p".to_string()));
+        summary.reason = Some("step");
+        summary.detail = None;
+        assert_eq!(summary.text(), "0x0000555555555140 in ?? [anonymous rw-p], step, thread 1 (tid 1234)");
+    }
+
+    #[test]
+    fn test_recognize_anti_debugging() {
+        use crate::antidebug::{is_status_file, recognize, AntiDebugCheck};
+        assert!(is_status_file("/proc/self/status"));
+        assert!(is_status_file("/proc/1234/status"));
+        assert!(is_status_file("/proc/thread-self/status"));
+        assert!(is_status_file("/proc/self/task/1235/status"));
+        assert!(!is_status_file("/proc/self/stat"));
+        assert!(!is_status_file("/proc/self/fd/status"));
+        assert!(!is_status_file("/home/status"));
+        let path = |path: &'static str| move |_| Some(path.to_string());
+        assert_eq!(recognize(Arch::X86_64, 101, [0, 0], path("")), Some(AntiDebugCheck::TraceMe));
+        assert_eq!(recognize(Arch::X86_64, 101, [16, 1], path("")), Some(AntiDebugCheck::Ptrace(16)));
+        assert_eq!(recognize(Arch::I386, 26, [0, 0], path("")), Some(AntiDebugCheck::TraceMe));
+        assert_eq!(recognize(Arch::X86_64, 257, [0, 0x1000], path("/proc/self/status")), Some(AntiDebugCheck::TracerPid("/proc/self/status".to_string())));
+        assert_eq!(recognize(Arch::I386, 5, [0x1000, 0], path("/proc/42/status")), Some(AntiDebugCheck::TracerPid("/proc/42/status".to_string())));
+        assert_eq!(recognize(Arch::X86_64, 257, [0, 0x1000], path("/etc/passwd")), None);
+        assert_eq!(recognize(Arch::X86_64, 1, [1, 0x1000], path("/proc/self/status")), None, "Not an open");
+    }
+
+    #[test]
+    fn test_assertion_comparisons() {
+        use crate::assertion::{compare, parse_assertion, Operand, Operator};
+        let minus_one = Operand::signed(-1);
+        let all_ones = Operand::unsigned(u64::MAX);
+        let zero = Operand::signed(0);
+        // Signed against signed: -1 is below 0.
+        assert!(compare(minus_one, Operator::Less, zero));
+        assert!(compare(Operand::signed(i64::MIN), Operator::Less, Operand::signed(i64::MAX)));
+        // An unsigned operand makes the comparison unsigned: -1 is the largest value.
+        assert!(compare(minus_one, Operator::Equal, all_ones));
+        assert!(compare(minus_one, Operator::Greater, Operand::unsigned(0)));
+        assert!(compare(all_ones, Operator::GreaterOrEqual, zero));
+        assert!(!compare(Operand::unsigned(1 << 63), Operator::Less, Operand::signed(0)));
+        // A 32-bit register is sign-extended.
+        let eax = Operand { value: 0xffff_ffff, signed: true, bits: 32 };
+        assert!(compare(eax, Operator::Equal, minus_one));
+        assert!(compare(eax, Operator::Less, zero));
+        assert!(compare(eax, Operator::NotEqual, Operand::signed(0xffff_ffff)));
+        assert!(compare(Operand::signed(5), Operator::LessOrEqual, Operand::signed(5)));
+        assert!(!compare(Operand::signed(5), Operator::NotEqual, Operand::signed(5)));
+        assert_eq!(parse_assertion("$rax"), Ok(("$rax".to_string(), None)));
+        assert_eq!(parse_assertion(" $rax==0x5 "), Ok(("$rax".to_string(), Some((Operator::Equal, "0x5".to_string())))));
+        assert_eq!(parse_assertion("*$rsp >= -1"), Ok(("*$rsp".to_string(), Some((Operator::GreaterOrEqual, "-1".to_string())))));
+        assert_eq!(parse_assertion("$rax < 3"), Ok(("$rax".to_string(), Some((Operator::Less, "3".to_string())))));
+        assert!(parse_assertion("$rax = 5").unwrap_err().contains("Unknown comparison"));
+        assert!(parse_assertion("== 5").unwrap_err().contains("Missing a value"));
+    }
+
+    #[test]
+    fn test_debug_control_bits() {
+        use crate::watch::{control_bits, WatchKind};
+        // DR0 enabled, written, 4 bytes.
+        assert_eq!(control_bits(0, 4, WatchKind::Write), 0b1 | (0b01 << 16) | (0b11 << 18));
+        // DR1 enabled, read or written, 2 bytes.
+        assert_eq!(control_bits(1, 2, WatchKind::Access), 0b100 | (0b11 << 20) | (0b01 << 22));
+        // DR3, 8 bytes, 1 byte.
+        assert_eq!(control_bits(3, 8, WatchKind::Write), 0b100_0000 | (0b01 << 28) | (0b10 << 30));
+        assert_eq!(control_bits(2, 1, WatchKind::Write), 0b1_0000 | (0b01 << 24));
+    }
+
+    #[test]
+    fn test_run_status_lines() {
+        use crate::procfs::{parse_stat, parse_syscall, ProcStat};
+        use crate::runstatus::status_line;
+        use crate::settings::{find_setting, parse_value, SettingValue};
+        use std::time::Duration;
+        let stat = parse_stat("1234 (a (weird) name) S 1 1234 1234 0 -1 4194560 95 0 0 0 250 17 0 0 20 0 1 0 42 2555904 221 ").unwrap();
+        assert_eq!(stat, ProcStat { state: 'S', cpu_ticks: 267 });
+        assert_eq!(parse_stat("1234 (truncated) R 1"), None);
+        assert_eq!(parse_syscall("35 0x7ffc 0x0 0x0 0x0 0x0 0x0 0x7ffd 0x7f12\n"), Some(35));
+        assert_eq!(parse_syscall("-1 0x7ffd 0x7f12\n"), None);
+        assert_eq!(parse_syscall("running\n"), None);
+        let (elapsed, interval) = (Duration::from_secs(10), Duration::from_secs(5));
+        assert_eq!(status_line(elapsed, interval, stat, Duration::ZERO, Some("read"), Some("pipe_read")), "[running for 10s] S (sleeping) in read, wchan pipe_read, 0.00s of CPU in the last 5s");
+        let running = ProcStat { state: 'R', cpu_ticks: 0 };
+        assert_eq!(status_line(elapsed, interval, running, Duration::from_millis(4990), None, None), "[running for 10s] R (running), 4.99s of CPU in the last 5s");
+        let (spec, _) = find_setting(&["run-status", "interval"]).unwrap();
+        assert_eq!(parse_value(spec, &["off"]), Ok(SettingValue::Int(0)));
+        assert_eq!(parse_value(spec, &["5"]), Ok(SettingValue::Int(5)));
+        assert!(parse_value(spec, &["0"]).is_err());
+        assert!(parse_value(spec, &["on"]).is_err());
+    }
+
+    #[test]
+    fn test_fd_changes_between_stops() {
+        use crate::procfs::{diff_fds, FdChange, OpenFile};
+        let files = |listing: &[(i32, &str)]| -> Vec<OpenFile> {
+            listing.iter().map(|(fd, target)| OpenFile { fd: *fd, target: target.to_string(), info: None }).collect()
+        };
+        let before = files(&[(0, "/dev/pts/0"), (1, "/dev/pts/0"), (3, "/tmp/cache.db"), (4, "socket:[4242]"), (7, "/tmp/log (deleted)")]);
+        assert_eq!(diff_fds(&before, &before), [], "Silent when nothing changed");
+        let after = files(&[(7, "/tmp/log (deleted)"), (0, "/dev/pts/0"), (1, "/dev/null"), (4, "socket:[4242]"), (5, "/tmp/cache.db"), (9, "pipe:[77]")]);
+        let changes = diff_fds(&before, &after);
+        assert_eq!(changes, [
+            FdChange::Replaced { fd: 1, old: "/dev/pts/0".to_string(), new: "/dev/null".to_string() },
+            FdChange::Closed { fd: 3, target: "/tmp/cache.db".to_string() },
+            FdChange::Opened { fd: 5, target: "/tmp/cache.db".to_string() },
+            FdChange::Opened { fd: 9, target: "pipe:[77]".to_string() },
+        ]);
+        let lines: Vec<String> = changes.iter().map(FdChange::describe).collect();
+        assert_eq!(lines, ["reopened fd 1 -> /dev/null (was /dev/pts/0)", "closed fd 3 (was /tmp/cache.db)", "opened fd 5 -> /tmp/cache.db", "opened fd 9 -> pipe:[77]"]);
+        assert_eq!(diff_fds(&[], &files(&[(3, "/tmp/a")])), [FdChange::Opened { fd: 3, target: "/tmp/a".to_string() }]);
+        assert_eq!(diff_fds(&files(&[(3, "/tmp/a")]), &[]), [FdChange::Closed { fd: 3, target: "/tmp/a".to_string() }]);
+    }
+
+    #[test]
+    fn test_rust_main_from_mangled_symbols() {
+        use crate::symbols::{demangle, rust_main, Symbol};
+        let symbol = |name: &str, is_function: bool| Symbol {
+            name: name.to_string(),
+            demangled: demangle(name),
+            address: 0x1000,
+            size: 0x10,
+            is_function,
+            section: None,
+        };
+        let mut symbols = vec![
+            symbol("main", true),
+            symbol("_ZN3std2rt10lang_start17hdc627a4a6a1d28d3E", true),
+            symbol("_ZN6helper4main17h0123456789abcdefE", true),
+            symbol("_ZN7my_tool4main17hf7cd16a9b150221eE", true),
+            symbol("_ZN7my_tool3cli4main17h1111111111111111E", true),
+        ];
+        assert_eq!(rust_main(&symbols, "my-tool").map(|symbol| symbol.demangled.as_str()), Some("my_tool::main"));
+        assert_eq!(rust_main(&symbols, "other").map(|symbol| symbol.demangled.as_str()), Some("helper::main"));
+        symbols.retain(|symbol| !symbol.demangled.starts_with("std::rt"));
+        assert_eq!(rust_main(&symbols, "my-tool"), None, "Not a Rust program without std::rt::lang_start");
+    }
+    #[test]
+    fn test_auxiliary_vector() {
+        use crate::procfs::{parse_auxv, AT_BASE, AT_ENTRY};
+        let words = |words: &[u64], size: usize| words.iter().flat_map(|word| word.to_le_bytes()[..size].to_vec()).collect::<Vec<u8>>();
+        let auxv = [AT_BASE, 0x7f00_0000_0000, AT_ENTRY, 0x5555_0000_1040, 0, 0, 33, 1];
+        assert_eq!(parse_auxv(&words(&auxv, 8), 8), [(AT_BASE, 0x7f00_0000_0000), (AT_ENTRY, 0x5555_0000_1040)], "Up to AT_NULL");
+        let auxv = [AT_ENTRY, 0x0804_9000, AT_BASE, 0];
+        assert_eq!(parse_auxv(&words(&auxv, 4), 4), [(AT_ENTRY, 0x0804_9000), (AT_BASE, 0)]);
+        assert_eq!(parse_auxv(&[1, 2, 3], 8), []);
+    }
+    #[test]
+    fn test_eflags_decoded() {
+        use crate::arch::{describe_eflags, eflags_names};
+        assert_eq!(describe_eflags(0x246), "[ PF ZF IF ]");
+        assert_eq!(describe_eflags(0x297), "[ CF PF AF SF IF ]");
+        assert_eq!(describe_eflags(0x2), "[ ]", "The reserved bit 1 is not a flag");
+        assert_eq!(eflags_names(0x297 ^ 0x246), ["CF", "AF", "ZF", "SF"]);
+        assert_eq!(eflags_names(1 << 11 | 1 << 10 | 1 << 8), ["TF", "DF", "OF"]);
+    }
+    #[test]
+    fn test_labels_cover_addresses() {
+        use crate::label::{find, is_valid_name, script, Label};
+        let mut labels = std::collections::BTreeMap::new();
+        labels.insert("buffer".to_string(), Label { address: 0x1000, length: 0x40 });
+        labels.insert("header".to_string(), Label { address: 0x1000, length: 8 });
+        labels.insert("payload".to_string(), Label { address: 0x1010, length: 0 });
+        assert_eq!(find(&labels, 0x1004), Some(("buffer", 4)), "The first of the labels starting at the same address");
+        assert_eq!(find(&labels, 0x1010), Some(("payload", 0)), "The closest start wins");
+        assert_eq!(find(&labels, 0x1011), Some(("buffer", 0x11)), "A label without length covers its address only");
+        assert_eq!(find(&labels, 0x1040), None);
+        assert_eq!(find(&labels, 0xfff), None);
+        assert_eq!(script(&labels), "label 0x1000 buffer 64\nlabel 0x1000 header 8\nlabel 0x1010 payload\n");
+        assert!(is_valid_name("input_buf") && is_valid_name("_x1"));
+        assert!(!is_valid_name("1st") && !is_valid_name("a-b") && !is_valid_name("$x") && !is_valid_name(""));
+    }
+    #[test]
+    fn test_session_files() {
+        use crate::session::{format, parse, Entry, Region, Session};
+        let session = Session {
+            program: "/tmp/my prog".to_string(),
+            args: vec!["a b".to_string(), "\"c\"".to_string()],
+            no_aslr: false,
+            entries: vec![
+                Entry { command: "b main".to_string(), succeeded: true, regions: Vec::new() },
+                Entry {
+                    command: "m 0x7ffd0010".to_string(),
+                    succeeded: false,
+                    regions: vec![Region { name: "[stack]".to_string(), base: 0x7ffd0000 }],
+                },
+            ],
+        };
+        let text = format(&session);
+        assert!(text.contains("# args [\"a b\",\"\\\"c\\\"\"]\n# aslr on\nb main\n# region 0x7ffd0000 [stack]\nm 0x7ffd0010\n# failed\n"), "{}", text);
+        assert_eq!(parse(&text), Ok(session), "A saved session reads back the same");
+        assert_eq!(parse("b main\n").unwrap_err(), "Not a session file, it has no \"# program\" line.");
+        assert_eq!(parse("# program p\n# aslr maybe\n").unwrap_err(), "Line 2: invalid ASLR setting \"# aslr maybe\".");
+    }
+
+    #[test]
+    fn test_examine_formats_parse() {
+        use crate::examine::{parse_format, Examine, Format};
+        let examine = |count, format, size| Ok(Examine { count, format, size });
+        assert_eq!(parse_format(""), examine(1, Format::Hex, 4), "x alone is one hexadecimal word");
+        assert_eq!(parse_format("16xb"), examine(16, Format::Hex, 1));
+        assert_eq!(parse_format("4gx"), examine(4, Format::Hex, 8), "The size may come before the format");
+        assert_eq!(parse_format("5i"), examine(5, Format::Instruction, 4));
+        assert_eq!(parse_format("s"), examine(1, Format::String, 4));
+        assert_eq!(parse_format("h"), examine(1, Format::Hex, 2), "A size alone keeps the hexadecimal format");
+        assert_eq!(parse_format("8c"), examine(8, Format::Char, 1), "Characters are bytes by default");
+        assert_eq!(parse_format("2cw"), examine(2, Format::Char, 4));
+        for (letter, format) in [('x', Format::Hex), ('d', Format::Decimal), ('u', Format::Unsigned), ('t', Format::Binary)] {
+            for (size_letter, size) in [('b', 1), ('h', 2), ('w', 4), ('g', 8)] {
+                assert_eq!(parse_format(&format!("3{}{}", letter, size_letter)), examine(3, format, size));
+                assert_eq!(parse_format(&format!("{}{}", size_letter, letter)), examine(1, format, size));
+            }
+        }
+        assert_eq!(parse_format("0x").unwrap_err(), "The count of /0x must be at least 1.");
+        assert_eq!(parse_format("99999999999999999999x").unwrap_err(), "The count of /99999999999999999999x is too large.");
+        assert_eq!(parse_format("x4").unwrap_err(), "The count of /x4 must come first.");
+        assert_eq!(parse_format("xd").unwrap_err(), "/xd has two formats, give one of x, d, u, t, c, s or i.");
+        assert_eq!(parse_format("bg").unwrap_err(), "/bg has two sizes, give one of b, h, w or g.");
+        assert_eq!(parse_format("sb").unwrap_err(), "/sb gives a size to strings, which have none.");
+        assert_eq!(parse_format("2ig").unwrap_err(), "/2ig gives a size to instructions, which have none.");
+        for unknown in ["4q", "f", "a", "2xz"] {
+            let err = parse_format(unknown).unwrap_err();
+            assert!(err.starts_with(&format!("Unknown format letter '{}' in /{}, x takes the formats x (hex)", unknown.chars().last().unwrap(), unknown)), "{}", err);
+            assert!(err.ends_with("and the sizes b (1 byte), h (2), w (4) and g (8)."), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_examine_formats_units() {
+        use crate::examine::{format_unit, format_units, Format};
+        assert_eq!(format_unit(&[0xff], Format::Hex), "0xff");
+        assert_eq!(format_unit(&[0x34, 0x12], Format::Hex), "0x1234");
+        assert_eq!(format_unit(&[1, 0, 0, 0], Format::Hex), "0x00000001", "Padded to the size");
+        assert_eq!(format_unit(&[0xff], Format::Decimal), "-1", "Signed from the size");
+        assert_eq!(format_unit(&[0xff, 0xff, 0xff, 0xff], Format::Unsigned), "4294967295");
+        assert_eq!(format_unit(&[0xfe; 8], Format::Decimal), "-72340172838076674");
+        assert_eq!(format_unit(&[5], Format::Binary), "00000101");
+        assert_eq!(format_unit(b"A", Format::Char), "65 'A'");
+        assert_eq!(format_unit(b"\n", Format::Char), "10 '\\n'");
+        assert_eq!(format_unit(&[0xff], Format::Char), "-1 '\\377'");
+        let bytes: Vec<u8> = (0..20).collect();
+        assert_eq!(format_units(&bytes[..10], 0x1000, Format::Hex, 1), ["0x1000:\t0x00\t0x01\t0x02\t0x03\t0x04\t0x05\t0x06\t0x07", "0x1008:\t0x08\t0x09"], "8 bytes a line");
+        assert_eq!(format_units(&bytes[..20], 0x2000, Format::Unsigned, 4).len(), 2, "4 words a line");
+        assert_eq!(format_units(&bytes[..16], 0x3000, Format::Hex, 8), ["0x3000:\t0x0706050403020100\t0x0f0e0d0c0b0a0908"], "2 giant words a line");
+    }
+
+    #[test]
+    fn test_vdso_read_from_memory() {
+        use crate::maps::parse_maps;
+        use crate::procfs::{read_auxv, AT_SYSINFO_EHDR};
+        use crate::sharedlib::{find_vdso, read_memory_object, VDSO};
+        use std::os::unix::fs::FileExt;
+        let mappings = parse_maps("1000-2000 r-xp 00000000 08:01 12 /bin/prog\n7000-9000 r-xp 00000000 00:00 0 [vdso]\n");
+        assert_eq!(find_vdso(&[(AT_SYSINFO_EHDR, 0x1000)], &mappings), Some((0x1000, 0x2000)), "The auxiliary vector first");
+        assert_eq!(find_vdso(&[], &mappings), Some((0x7000, 0x9000)), "Else the mapping named [vdso]");
+        assert_eq!(find_vdso(&[(AT_SYSINFO_EHDR, 0x5000)], &mappings), None);
+
+        // The vDSO of the tests, moved to another address.
+        let pid = nix::unistd::getpid();
+        let mappings = crate::maps::read_maps(pid).unwrap();
+        let Some((start, end)) = find_vdso(&read_auxv(pid, 8).unwrap(), &mappings) else {
+            return;
+        };
+        let mut image = vec![0; (end - start) as usize];
+        std::fs::File::open("/proc/self/mem").unwrap().read_exact_at(&mut image, start).unwrap();
+        let tracee = MockTracee::new(0x7f00_0000_0000, &image);
+        let object = read_memory_object(&tracee, VDSO, 0x7f00_0000_0000, 0x7f00_0000_0000 + image.len() as u64).unwrap();
+        assert_eq!(object.path, "[vdso]");
+        let address = object.lookup("__vdso_clock_gettime").expect("The vDSO exports __vdso_clock_gettime");
+        assert!((0x7f00_0000_0000..0x7f00_0000_0000 + image.len() as u64).contains(&address), "{:#x}", address);
+        assert!(object.symbolize(address).is_some_and(|(symbol, offset)| symbol.name.ends_with("clock_gettime") && offset == 0));
+        let err = read_memory_object(&tracee, VDSO, 0x1000, 0x2000).unwrap_err().to_string();
+        assert!(err.starts_with("could not read [vdso] in the memory of the program: "), "{}", err);
+    }
+
+    #[test]
+    fn test_syscall_info_decoded() {
+        use crate::arch::Arch;
+        use crate::procfs::{parse_syscall_line, ThreadSyscall};
+        use crate::syscallinfo::{format_call, format_result, is_interrupted};
+        use crate::tracee::MockTracee;
+        assert_eq!(parse_syscall_line("running\n"), Some(ThreadSyscall::Running));
+        assert_eq!(parse_syscall_line("-1 0x7ffd 0x7f12\n"), Some(ThreadSyscall::NotIn { sp: 0x7ffd, pc: 0x7f12 }));
+        assert_eq!(
+            parse_syscall_line("0 0x3 0x7ffc 0x10 0x0 0x0 0x0 0x7ffd 0x7f12\n"),
+            Some(ThreadSyscall::In { number: 0, args: [3, 0x7ffc, 0x10, 0, 0, 0], sp: 0x7ffd, pc: 0x7f12 })
+        );
+        assert_eq!(parse_syscall_line("0 0x3 0x7ffd 0x7f12\n"), None, "Arguments missing");
+        // A path at 0x1000 and a struct timespec at 0x2000.
+        let mut tracee = MockTracee::new(0x1000, b"/etc/hosts\0\0\0\0\0\0");
+        tracee.memory.extend((0x2000..).zip([1u64, 500].iter().flat_map(|field| field.to_le_bytes())));
+        let at_fdcwd = -100i64 as u64;
+        assert_eq!(format_call(&tracee, Arch::X86_64, 257, &[at_fdcwd, 0x1000, 0o2000000, 0, 0, 0]), "openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY|O_CLOEXEC)");
+        assert_eq!(format_call(&tracee, Arch::X86_64, 2, &[0x1000, 0o101, 0o644, 0, 0, 0]), "open(\"/etc/hosts\", O_WRONLY|O_CREAT, 0644)", "The mode to create");
+        assert_eq!(format_call(&tracee, Arch::X86_64, 35, &[0x2000, 0, 0, 0, 0, 0]), "nanosleep({tv_sec=1, tv_nsec=500}, NULL)");
+        assert_eq!(format_call(&tracee, Arch::X86_64, 0, &[3, 0x3000, 16, 0, 0, 0]), "read(3, 0x3000, 16)");
+        assert_eq!(format_call(&tracee, Arch::X86_64, 2, &[0x5000, 0, 0, 0, 0, 0]), "open(0x5000, O_RDONLY)", "A path that can't be read");
+        assert_eq!(format_call(&tracee, Arch::X86_64, 62, &[42, 15, 0, 0, 0, 0]), "kill(42, SIGTERM)");
+        assert_eq!(format_call(&tracee, Arch::X86_64, 9999, &[1, 2, 3, 4, 5, 6]), "syscall_9999(0x1, 0x2, 0x3, 0x4, 0x5, 0x6)");
+        assert_eq!(format_call(&tracee, Arch::I386, 3, &[0xffff_ffff, 0x3000, 16, 0, 0, 0]), "read(-1, 0x3000, 16)", "32-bit arguments");
+        assert_eq!(format_result(Arch::X86_64, "read", 5), "= 5");
+        assert_eq!(format_result(Arch::X86_64, "openat", -2i64 as u64), "= -1 ENOENT (No such file or directory)");
+        assert_eq!(format_result(Arch::X86_64, "read", -512i64 as u64), "= ? ERESTARTSYS (To be restarted if SA_RESTART is set)");
+        assert_eq!(format_result(Arch::X86_64, "mmap", 0x7f12_3456_7000), "= 0x7f1234567000");
+        assert_eq!(format_result(Arch::I386, "read", 0xffff_fff2), "= -1 EFAULT (Bad address)");
+        assert!(is_interrupted(Arch::X86_64, -516i64 as u64));
+        assert!(is_interrupted(Arch::X86_64, -4i64 as u64), "EINTR");
+        assert!(!is_interrupted(Arch::X86_64, -2i64 as u64));
+        assert!(!is_interrupted(Arch::X86_64, 16));
+    }
+
+    #[test]
+    fn test_display_expressions_parsed() {
+        use crate::assertion::Operand;
+        use crate::display::{format_operand, is_variable};
+        assert!(is_variable("counter"));
+        assert!(is_variable("app::state::COUNTER"));
+        assert!(is_variable("_start"));
+        assert!(!is_variable("$rax"));
+        assert!(!is_variable("*0x601040"));
+        assert!(!is_variable("counter+4"));
+        assert!(!is_variable("0x10"));
+        assert_eq!(format_operand(Operand::signed(-1)), "0xffffffffffffffff (-1)");
+        assert_eq!(format_operand(Operand::unsigned(u64::MAX)), "0xffffffffffffffff (18446744073709551615)");
+        assert_eq!(format_operand(Operand { value: 0xffff_fffe, signed: true, bits: 32 }), "0xfffffffe (-2)");
+    }
+
+    #[test]
+    fn test_resource_limits_and_environment_parsed() {
+        use crate::sandbox::{describe_limits, parse_limit, set_env, Environment};
+        use nix::sys::resource::{Resource, RLIM_INFINITY};
+        let limit = parse_limit("nofile", "64:128").unwrap();
+        assert_eq!((limit.name, limit.resource, limit.soft, limit.hard), ("NOFILE", Resource::RLIMIT_NOFILE, 64, Some(128)));
+        assert_eq!(parse_limit("RLIMIT_AS", "1G").unwrap().soft, 1 << 30);
+        assert_eq!(parse_limit("CPU", "unlimited").unwrap().hard, None);
+        assert_eq!(parse_limit("core", "0:unlimited").unwrap().hard, Some(RLIM_INFINITY));
+        assert!(parse_limit("NOFILE", "128:64").unwrap_err().contains("above its hard limit"));
+        assert!(parse_limit("FILES", "64").unwrap_err().contains("AS, CORE, CPU"), "The resources are listed");
+        assert!(parse_limit("AS", "lots").is_err());
+        assert!(parse_limit("AS", "99999999999G").is_err(), "Overflows");
+        let limits = [parse_limit("CORE", "0").unwrap(), parse_limit("NOFILE", "64:128").unwrap()];
+        assert_eq!(describe_limits(&limits), "CORE 0, NOFILE 64:128");
+        assert_eq!(describe_limits(&[]), "none");
+
+        let mut environment = Environment::default();
+        set_env(&mut environment, "GREETING=hello world").unwrap();
+        set_env(&mut environment, "EMPTY=").unwrap();
+        set_env(&mut environment, "GREETING=\"hi there\"").unwrap();
+        assert_eq!(environment.variables, [("EMPTY".to_string(), String::new()), ("GREETING".to_string(), "hi there".to_string())]);
+        assert!(set_env(&mut environment, "=value").is_err());
+        assert!(set_env(&mut environment, "NOVALUE").unwrap_err().starts_with("Usage"));
+        assert!(environment.entries().len() > 2, "The environment of the debugger is inherited");
+        assert!(environment.entries().iter().any(|entry| entry.to_bytes() == b"GREETING=hi there"));
+        set_env(&mut environment, "--clear").unwrap();
+        let entries: Vec<_> = environment.entries().iter().map(|entry| entry.to_string_lossy().into_owned()).collect();
+        assert_eq!(entries, ["EMPTY=", "GREETING=hi there"]);
+        assert_eq!(environment.describe(), "--clear EMPTY= GREETING=hi there");
+        set_env(&mut environment, "--inherit").unwrap();
+        assert!(!environment.clear);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,416 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::step::StepStop;
+use crate::working::{is_breakpoint, BreakpointTable};
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use serde_json::json;
+
+/// Whether a thread of the program runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    /// Stopped by the debugger, as every thread is once the program stops.
+    Stopped,
+    /// Resumed, and not waited for yet.
+    Running,
+}
+
+/// A thread of the debugged program, traced since it was created or attached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    /// Its number, given in the order the threads are created and never reused, 1 being the main
+    /// thread. `thread <number>` selects it.
+    pub number: usize,
+    /// Its thread ID, the pid of the program for its main thread.
+    pub tid: Pid,
+    /// Whether it runs.
+    pub state: ThreadState,
+    /// A stop of the thread seen while the other threads were stopped, reported by the next wait
+    /// instead of resuming the thread.
+    pub pending: Option<WaitStatus>,
+    /// True while a `SIGSTOP` the thread will stop with is not waited for yet: the one the debugger
+    /// sent it to stop it, or the one a new thread starts with. That stop is not reported.
+    pub stop_expected: bool,
+    /// Why the thread stopped by itself, shown again when it is selected: in non-stop mode, the
+    /// threads stop one by one.
+    pub stop: Option<StepStop>,
+}
+
+impl Thread {
+    /// A thread stopped by the debugger.
+    pub fn stopped(number: usize, tid: Pid) -> Thread {
+        Thread { number, tid, state: ThreadState::Stopped, pending: None, stop_expected: false, stop: None }
+    }
+}
+
+/// List the threads of the process `pid`, main thread first, from `/proc/<pid>/task`. A process
+/// whose threads can't be listed has its main thread only.
+pub fn tasks(pid: Pid) -> Vec<Pid> {
+    let mut tids: Vec<Pid> = std::fs::read_dir(format!("/proc/{}/task", pid))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                .map(Pid::from_raw)
+                .filter(|tid| *tid != pid)
+                .collect()
+        })
+        .unwrap_or_default();
+    tids.sort();
+    tids.insert(0, pid);
+    tids
+}
+
+/// Trace the threads the thread `tid` creates: each stops first, before running, and the creating
+/// thread reports it with a `PTRACE_EVENT_CLONE` stop. The threads created inherit the options,
+/// the system call stops are told from the `SIGTRAP`s with `PTRACE_O_TRACESYSGOOD`, and executing
+/// a new program is reported by a `PTRACE_EVENT_EXEC` stop.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the thread is not traced by the debugger, or not stopped.
+pub fn trace_events(tid: Pid) -> Result<(), DbgError> {
+    log::debug!("PTRACE_SETOPTIONS {} PTRACE_O_TRACECLONE | PTRACE_O_TRACESYSGOOD | PTRACE_O_TRACEEXEC", tid);
+    let options = ptrace::Options::PTRACE_O_TRACECLONE | ptrace::Options::PTRACE_O_TRACESYSGOOD | ptrace::Options::PTRACE_O_TRACEEXEC;
+    ptrace::setoptions(tid, options).map_err(DbgError::ptrace("trace the threads"))
+}
+
+/// Add a running thread created by the program, unless it is already known (its first stop came
+/// first). `stop_expected` is true until its first stop is seen.
+fn add_thread(debugger: &mut Debugger, tid: Pid, stop_expected: bool) {
+    if !debugger.threads.iter().any(|thread| thread.tid == tid) {
+        let number = next_number(debugger);
+        debugger.threads.push(Thread { number, tid, state: ThreadState::Running, pending: None, stop_expected, stop: None });
+    }
+}
+
+/// Take the number of a thread created.
+fn next_number(debugger: &mut Debugger) -> usize {
+    debugger.next_thread_number += 1;
+    debugger.next_thread_number - 1
+}
+
+/// Forget a thread that has terminated.
+fn remove_thread(debugger: &mut Debugger, tid: Pid) {
+    debugger.threads.retain(|thread| thread.tid != tid);
+}
+
+/// The thread ID of a new thread, given by the `PTRACE_EVENT_CLONE` stop of the thread creating it.
+fn new_thread(status: &WaitStatus) -> Option<Pid> {
+    match *status {
+        WaitStatus::PtraceEvent(tid, _, event) if event == ptrace::Event::PTRACE_EVENT_CLONE as i32 => {
+            let new = ptrace::getevent(tid).ok().map(|new| Pid::from_raw(new as i32));
+            log::debug!("PTRACE_GETEVENTMSG {} = new thread {:?}", tid, new);
+            new
+        }
+        _ => None,
+    }
+}
+
+/// Add the thread created by a thread stopped for it with `status`, keeping the new thread stopped
+/// at its start.
+///
+/// # Returns
+///
+/// False if `status` is not the creation of a thread.
+pub fn adopt_new_thread(debugger: &mut Debugger, status: &WaitStatus) -> bool {
+    let Some(new) = new_thread(status) else {
+        return false;
+    };
+    if !debugger.threads.iter().any(|thread| thread.tid == new) {
+        let status = waitpid(new, Some(WaitPidFlag::__WALL));
+        log::debug!("waitpid {} = {:?}", new, status);
+        let number = next_number(debugger);
+        debugger.threads.push(Thread::stopped(number, new));
+    }
+    true
+}
+
+/// Rewind a thread stopped by the breakpoint instruction of an armed breakpoint to the breakpoint, so that it hits
+/// it again once resumed instead of reporting it along with the stop of another thread.
+///
+/// # Returns
+///
+/// False if the thread is not at an armed breakpoint.
+fn rewind_breakpoint(breakpoints: &BreakpointTable, tid: Pid) -> bool {
+    let Ok(mut regs) = crate::arch::get_registers(tid) else {
+        return false;
+    };
+    let address = crate::working::trap_address(regs.pc());
+    if !is_breakpoint(breakpoints, address) {
+        return false;
+    }
+    regs.set_pc(address);
+    log::debug!("Thread {} rewound to the breakpoint at {:#x}", tid, address);
+    crate::cache::invalidate();
+    crate::arch::set_registers(tid, regs).is_ok()
+}
+
+/// Wait for the next stop of a thread of the resumed program, or its termination.
+///
+/// The stops of the threads for the debugger itself are handled on the way: a thread created is
+/// added to [`Debugger::threads`] and resumed as well as its creator, a thread that exits is
+/// removed, and the `SIGSTOP` of [`stop_others`] reaching a resumed thread late is ignored. A stop
+/// seen while stopping the other threads is returned first.
+///
+/// While the program has one thread, only that thread is waited for, so the other children of the
+/// debugger (the programs of the other sessions of the tests) are left alone.
+///
+/// # Returns
+///
+/// The thread that stopped, or the main thread once the program has terminated, with its status.
+///
+/// # Errors
+///
+/// Returns [`DbgError::ProcessExited`] if the program has already terminated, or
+/// [`DbgError::Ptrace`] if it can't be waited for or resumed.
+pub fn wait_any(debugger: &mut Debugger) -> Result<(Pid, WaitStatus), DbgError> {
+    loop {
+        if let Some(event) = next_event(debugger, true)? {
+            return Ok(event);
+        }
+    }
+}
+
+/// Take the next stop of a running thread of the program as [`wait_any`] does, without waiting
+/// for one: in non-stop mode, the threads left running stop while the commands run.
+///
+/// # Returns
+///
+/// The thread that stopped with its status, or `None` if no thread has stopped.
+///
+/// # Errors
+///
+/// Same as [`wait_any`].
+pub fn poll_any(debugger: &mut Debugger) -> Result<Option<(Pid, WaitStatus)>, DbgError> {
+    next_event(debugger, false)
+}
+
+/// Wait for the next stop of a thread, or take it if one is already there without `block`,
+/// handling the stops of the threads for the debugger itself on the way.
+fn next_event(debugger: &mut Debugger, block: bool) -> Result<Option<(Pid, WaitStatus)>, DbgError> {
+    loop {
+        if let Some(thread) = debugger.threads.iter_mut().find(|thread| thread.pending.is_some()) {
+            let status = thread.pending.take().unwrap_or(WaitStatus::StillAlive);
+            log::debug!("Pending stop of thread {}: {:?}", thread.tid, status);
+            return Ok(Some((thread.tid, status)));
+        }
+        let target = match debugger.threads.as_slice() {
+            [thread] => thread.tid,
+            _ => Pid::from_raw(-1),
+        };
+        let flags = if block { WaitPidFlag::__WALL } else { WaitPidFlag::__WALL | WaitPidFlag::WNOHANG };
+        let status = waitpid(target, Some(flags));
+        if block || !matches!(status, Ok(WaitStatus::StillAlive)) {
+            log::debug!("waitpid {} = {:?}", target, status);
+        }
+        let status = match status {
+            Ok(WaitStatus::StillAlive) => return Ok(None),
+            Ok(status) => status,
+            Err(Errno::ECHILD) => return Err(DbgError::ProcessExited),
+            Err(errno) => return Err(DbgError::Ptrace { op: "wait for the program", errno }),
+        };
+        let Some(tid) = status.pid() else {
+            continue;
+        };
+        let known = debugger.threads.iter().position(|thread| thread.tid == tid);
+        if let Some(new) = new_thread(&status) {
+            add_thread(debugger, new, true);
+            log::debug!("PTRACE_CONT {}", tid);
+            crate::cache::invalidate();
+            ptrace::cont(tid, None).map_err(DbgError::ptrace("continue execution"))?;
+            continue;
+        }
+        match (status, known) {
+            // The first stop of a new thread, reported before or after its creation.
+            (WaitStatus::Stopped(_, Signal::SIGSTOP), None) => {
+                add_thread(debugger, tid, false);
+                log::debug!("PTRACE_CONT {}", tid);
+                crate::cache::invalidate();
+                ptrace::cont(tid, None).map_err(DbgError::ptrace("continue execution"))?;
+            }
+            (WaitStatus::Stopped(_, Signal::SIGSTOP), Some(index)) if debugger.threads[index].stop_expected => {
+                debugger.threads[index].stop_expected = false;
+                log::debug!("PTRACE_CONT {}", tid);
+                crate::cache::invalidate();
+                ptrace::cont(tid, None).map_err(DbgError::ptrace("continue execution"))?;
+            }
+            (WaitStatus::Exited(..) | WaitStatus::Signaled(..), _) if tid != debugger.child => remove_thread(debugger, tid),
+            (_, Some(index)) => {
+                debugger.threads[index].state = ThreadState::Stopped;
+                return Ok(Some((tid, status)));
+            }
+            // Not a thread of the program.
+            (_, None) => {}
+        }
+    }
+}
+
+/// Stop every thread still running but the current one, which has just stopped, so that the
+/// program is stopped as a whole. A thread that stops for another reason than the debugger keeps
+/// that stop pending, reported when the program is resumed.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if a thread can't be waited for.
+pub fn stop_others(debugger: &mut Debugger) -> Result<(), DbgError> {
+    let (pid, current) = (debugger.child, debugger.thread);
+    let running: Vec<Pid> = debugger
+        .threads
+        .iter()
+        .filter(|thread| thread.tid != current && thread.state == ThreadState::Running)
+        .map(|thread| thread.tid)
+        .collect();
+    for thread in debugger.threads.iter_mut().filter(|thread| running.contains(&thread.tid)) {
+        if !thread.stop_expected {
+            // A signal to this thread, not to the process which could deliver it to any thread.
+            log::debug!("tgkill {} SIGSTOP", thread.tid);
+            unsafe { nix::libc::syscall(nix::libc::SYS_tgkill, pid.as_raw(), thread.tid.as_raw(), Signal::SIGSTOP as i32) };
+            thread.stop_expected = true;
+        }
+    }
+    for tid in running {
+        let status = waitpid(tid, Some(WaitPidFlag::__WALL));
+        log::debug!("waitpid {} = {:?}", tid, status);
+        let status = match status {
+            Ok(status) => status,
+            Err(Errno::ECHILD) => {
+                remove_thread(debugger, tid);
+                continue;
+            }
+            Err(errno) => return Err(DbgError::Ptrace { op: "stop the threads", errno }),
+        };
+        let Some(index) = debugger.threads.iter().position(|thread| thread.tid == tid) else {
+            continue;
+        };
+        match status {
+            WaitStatus::Exited(..) | WaitStatus::Signaled(..) => {
+                remove_thread(debugger, tid);
+                continue;
+            }
+            WaitStatus::Stopped(_, Signal::SIGSTOP) if debugger.threads[index].stop_expected => {
+                debugger.threads[index].stop_expected = false;
+            }
+            // At a breakpoint too: it is hit again once resumed. The SIGSTOP sent is still to come,
+            // as when it created a thread.
+            WaitStatus::Stopped(_, Signal::SIGTRAP) if rewind_breakpoint(&debugger.breakpoint_table, tid) => {}
+            _ => {
+                if !adopt_new_thread(debugger, &status) {
+                    debugger.threads[index].pending = Some(status);
+                }
+            }
+        }
+        debugger.threads[index].state = ThreadState::Stopped;
+    }
+    Ok(())
+}
+
+/// Resume every stopped thread but the current one, which the caller resumes its own way. The
+/// threads with a pending stop stay stopped, it is reported by [`wait_any`].
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if a thread can't be resumed.
+pub fn resume_others(debugger: &mut Debugger) -> Result<(), DbgError> {
+    let current = debugger.thread;
+    let watch = debugger.anti_debug.enabled;
+    for thread in debugger.threads.iter_mut() {
+        if thread.tid == current || thread.state != ThreadState::Stopped || thread.pending.is_some() {
+            continue;
+        }
+        crate::antidebug::resume(watch, thread.tid, None)?;
+        thread.state = ThreadState::Running;
+        thread.stop = None;
+    }
+    Ok(())
+}
+
+/// Mark the thread `tid` as running or stopped, when it is resumed or waited for by itself. A
+/// thread resumed forgets its stop.
+pub fn set_state(debugger: &mut Debugger, tid: Pid, state: ThreadState) {
+    if let Some(thread) = debugger.threads.iter_mut().find(|thread| thread.tid == tid) {
+        thread.state = state;
+        if state == ThreadState::Running {
+            thread.stop = None;
+        }
+    }
+}
+
+/// Returns true if a thread of the program runs, as the threads left running in non-stop mode.
+pub fn any_running(debugger: &Debugger) -> bool {
+    debugger.threads.iter().any(|thread| thread.state == ThreadState::Running)
+}
+
+/// List the threads of the program with their state and the function they are in, `*` marking the
+/// current thread (`info threads`).
+pub fn info_threads(debugger: &mut Debugger) {
+    if !debugger.state.is_alive() {
+        json::set_data(debugger, |_| json!({ "threads": [] }));
+        outln!(debugger, "No threads.");
+        return;
+    }
+    let threads = debugger.threads.clone();
+    let mut data = Vec::new();
+    outln!(debugger, "  {:<4}  {:<8}  {:<8}  Frame", "Id", "Tid", "State");
+    for thread in threads {
+        let current = thread.tid == debugger.thread;
+        let state = match thread.state {
+            ThreadState::Stopped => "stopped",
+            ThreadState::Running => "running",
+        };
+        // The registers of a running thread can't be read.
+        let pc = (thread.state == ThreadState::Stopped).then(|| debugger.tracee_of(thread.tid).getregs().ok().map(|regs| regs.pc())).flatten();
+        let frame = match pc {
+            Some(pc) => debugger.describe_address(pc),
+            None if thread.state == ThreadState::Running => "(running)".to_string(),
+            None => "?".to_string(),
+        };
+        let function = pc.and_then(|pc| debugger.symbolize(pc)).map(|(name, _, _)| crate::symbols::demangle(&name));
+        outln!(debugger, "{} {:<4}  {:<8}  {:<8}  {}", if current { "*" } else { " " }, thread.number, thread.tid, state, frame);
+        data.push(json!({
+            "number": thread.number,
+            "tid": thread.tid.as_raw(),
+            "current": current,
+            "state": state,
+            "pc": pc.map(json::address),
+            "function": function,
+        }));
+    }
+    json::set_data(debugger, |_| json!({ "threads": data }));
+}
+
+/// The number of the thread `tid`, 0 if it is not known.
+pub fn number(debugger: &Debugger, tid: Pid) -> usize {
+    debugger.threads.iter().find(|thread| thread.tid == tid).map_or(0, |thread| thread.number)
+}
+
+/// Make the thread with `number` the current thread (`thread <number>`): the commands read its
+/// registers and stack, and the stepping commands step it. Its innermost frame is selected, and in
+/// non-stop mode the stop it stopped by itself with, if any, becomes the stop of the program.
+///
+/// # Errors
+///
+/// Returns a message if the program has no thread with that number, or if it runs, as the
+/// threads left running in non-stop mode.
+pub fn select_thread(debugger: &mut Debugger, number: usize) -> Result<Pid, String> {
+    let Some(thread) = debugger.threads.iter().find(|thread| thread.number == number).cloned() else {
+        return Err(format!("No thread {}, info threads lists them.", number));
+    };
+    if thread.state == ThreadState::Running {
+        return Err(format!("Thread {} is running, only a stopped thread can be selected: interrupt stops it.", number));
+    }
+    debugger.thread = thread.tid;
+    // In non-stop mode, each thread stopped for its own reason.
+    if let Some(stop) = thread.stop.as_ref().filter(|_| debugger.non_stop) {
+        debugger.record_stop(stop);
+    }
+    debugger.selected_frame = 0;
+    // The registers shown last are the ones of another thread.
+    debugger.shown_registers = None;
+    Ok(debugger.thread)
+}
@@ -0,0 +1,260 @@
+use crate::arch::Registers;
+use nix::errno::Errno;
+use nix::libc::user_regs_struct;
+use nix::sys::ptrace;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+#[cfg(all(test, target_arch = "x86_64"))]
+use std::{cell::Cell, collections::{BTreeMap, VecDeque}};
+
+/// The operations of the debugger on the traced process, so the logic over them can run on a
+/// [`MockTracee`] in the tests. The process itself, a [`Pid`], is traced with ptrace.
+pub trait Tracee {
+    /// Read the 8 bytes at `address`, as a little endian word.
+    fn read_word(&self, address: u64) -> Result<i64, Errno>;
+
+    /// Read the bytes of `page` at `address`, word by word unless the tracee reads them at once.
+    fn read_page(&self, address: u64, page: &mut [u8]) -> Result<(), Errno> {
+        for (index, word) in page.chunks_mut(8).enumerate() {
+            let bytes = self.read_word(address + index as u64 * 8)?.to_le_bytes();
+            word.copy_from_slice(&bytes[..word.len()]);
+        }
+        Ok(())
+    }
+
+    /// Write the 8 bytes at `address`.
+    fn write_word(&mut self, address: u64, word: i64) -> Result<(), Errno>;
+
+    /// Read the general purpose registers.
+    fn getregs(&self) -> Result<user_regs_struct, Errno>;
+
+    /// Write the general purpose registers.
+    fn setregs(&mut self, regs: user_regs_struct) -> Result<(), Errno>;
+
+    /// Resume the process until it stops.
+    fn cont(&mut self) -> Result<(), Errno>;
+
+    /// Execute a single instruction.
+    fn step(&mut self) -> Result<(), Errno>;
+
+    /// Resume the process until the next system call entry or exit.
+    fn syscall(&mut self) -> Result<(), Errno>;
+
+    /// Wait until the resumed process stops or terminates.
+    fn wait(&mut self) -> Result<WaitStatus, Errno>;
+}
+
+impl Tracee for Pid {
+    fn read_word(&self, address: u64) -> Result<i64, Errno> {
+        let result = ptrace::read(*self, address as ptrace::AddressType);
+        log::trace!("PTRACE_PEEKDATA {} {:#x} = {:x?}", self, address, result);
+        result
+    }
+
+    fn read_page(&self, address: u64, page: &mut [u8]) -> Result<(), Errno> {
+        // One read of the memory file instead of a PEEKDATA per word.
+        let to_errno = |err: std::io::Error| err.raw_os_error().map_or(Errno::EIO, Errno::from_raw);
+        let file = File::open(format!("/proc/{}/mem", self)).map_err(to_errno)?;
+        let result = file.read_exact_at(page, address).map_err(to_errno);
+        log::trace!("read /proc/{}/mem {:#x}, {} bytes = {:?}", self, address, page.len(), result);
+        result
+    }
+
+    fn write_word(&mut self, address: u64, word: i64) -> Result<(), Errno> {
+        crate::cache::invalidate();
+        let result = unsafe { ptrace::write(*self, address as ptrace::AddressType, word as ptrace::AddressType) };
+        log::trace!("PTRACE_POKEDATA {} {:#x} {:#x} = {:?}", self, address, word, result);
+        result
+    }
+
+    fn getregs(&self) -> Result<user_regs_struct, Errno> {
+        let result = crate::arch::get_registers(*self);
+        match &result {
+            Ok(regs) => log::trace!("PTRACE_GETREGS {} = pc={:#x} sp={:#x}", self, regs.pc(), regs.sp()),
+            Err(errno) => log::trace!("PTRACE_GETREGS {} = {}", self, errno),
+        }
+        result
+    }
+
+    fn setregs(&mut self, regs: user_regs_struct) -> Result<(), Errno> {
+        crate::cache::invalidate();
+        let result = crate::arch::set_registers(*self, regs);
+        log::trace!("PTRACE_SETREGS {} pc={:#x} = {:?}", self, regs.pc(), result);
+        result
+    }
+
+    fn cont(&mut self) -> Result<(), Errno> {
+        crate::cache::invalidate();
+        let result = ptrace::cont(*self, None);
+        log::debug!("PTRACE_CONT {} = {:?}", self, result);
+        result
+    }
+
+    fn step(&mut self) -> Result<(), Errno> {
+        crate::cache::invalidate();
+        let result = ptrace::step(*self, None);
+        log::debug!("PTRACE_SINGLESTEP {} = {:?}", self, result);
+        result
+    }
+
+    fn syscall(&mut self) -> Result<(), Errno> {
+        crate::cache::invalidate();
+        let result = ptrace::syscall(*self, None);
+        log::debug!("PTRACE_SYSCALL {} = {:?}", self, result);
+        result
+    }
+
+    fn wait(&mut self) -> Result<WaitStatus, Errno> {
+        let result = waitpid(*self, Some(WaitPidFlag::__WALL));
+        crate::cache::invalidate();
+        log::debug!("waitpid {} = {:?}", self, result);
+        result
+    }
+}
+
+/// A tracee borrowed mutably is a tracee, so the functions taking one by value take a [`Pid`] as
+/// well as a `&mut MockTracee`.
+impl<T: Tracee> Tracee for &mut T {
+    fn read_word(&self, address: u64) -> Result<i64, Errno> {
+        (**self).read_word(address)
+    }
+
+    fn read_page(&self, address: u64, page: &mut [u8]) -> Result<(), Errno> {
+        (**self).read_page(address, page)
+    }
+
+    fn write_word(&mut self, address: u64, word: i64) -> Result<(), Errno> {
+        (**self).write_word(address, word)
+    }
+
+    fn getregs(&self) -> Result<user_regs_struct, Errno> {
+        (**self).getregs()
+    }
+
+    fn setregs(&mut self, regs: user_regs_struct) -> Result<(), Errno> {
+        (**self).setregs(regs)
+    }
+
+    fn cont(&mut self) -> Result<(), Errno> {
+        (**self).cont()
+    }
+
+    fn step(&mut self) -> Result<(), Errno> {
+        (**self).step()
+    }
+
+    fn syscall(&mut self) -> Result<(), Errno> {
+        (**self).syscall()
+    }
+
+    fn wait(&mut self) -> Result<WaitStatus, Errno> {
+        (**self).wait()
+    }
+}
+
+/// A process replaying canned states, for the tests: each [`Tracee::wait`] returns the next stop
+/// of [`MockTracee::stops`] and takes its registers.
+#[cfg(all(test, target_arch = "x86_64"))]
+pub struct MockTracee {
+    /// The memory of the process, byte by byte. Reading or writing a byte not in it fails with
+    /// `EIO`, as for an unmapped address.
+    pub memory: BTreeMap<u64, u8>,
+    /// The registers.
+    pub regs: user_regs_struct,
+    /// The stops returned by `wait` in order, with the registers at each one.
+    pub stops: VecDeque<(WaitStatus, user_regs_struct)>,
+    /// The requests resuming the process, `cont`, `step` or `syscall`, in order.
+    pub requests: Vec<&'static str>,
+    /// The number of times the registers were read.
+    pub getregs_calls: Cell<usize>,
+}
+
+#[cfg(all(test, target_arch = "x86_64"))]
+impl MockTracee {
+    /// The process whose pid the wait statuses give.
+    pub const PID: Pid = Pid::from_raw(4242);
+
+    /// A process with `bytes` mapped at `address`, and the registers all zero.
+    pub fn new(address: u64, bytes: &[u8]) -> MockTracee {
+        MockTracee {
+            memory: (address..).zip(bytes.iter().copied()).collect(),
+            regs: MockTracee::registers(|_| {}),
+            stops: VecDeque::new(),
+            requests: Vec::new(),
+            getregs_calls: Cell::new(0),
+        }
+    }
+
+    /// Registers all zero but what `set` changes.
+    pub fn registers(set: impl FnOnce(&mut user_regs_struct)) -> user_regs_struct {
+        // Plain integers, for which zero is valid.
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        set(&mut regs);
+        regs
+    }
+
+    /// Add a stop returned by `wait`, where the process has the registers `regs`.
+    pub fn push_stop(&mut self, status: WaitStatus, regs: user_regs_struct) {
+        self.stops.push_back((status, regs));
+    }
+
+    /// The `len` bytes at `address`.
+    pub fn bytes(&self, address: u64, len: usize) -> Vec<u8> {
+        (address..address + len as u64).map(|address| self.memory[&address]).collect()
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64"))]
+impl Tracee for MockTracee {
+    fn read_word(&self, address: u64) -> Result<i64, Errno> {
+        let mut bytes = [0; 8];
+        for (offset, byte) in bytes.iter_mut().enumerate() {
+            *byte = *self.memory.get(&(address + offset as u64)).ok_or(Errno::EIO)?;
+        }
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    fn write_word(&mut self, address: u64, word: i64) -> Result<(), Errno> {
+        if (address..address + 8).any(|address| !self.memory.contains_key(&address)) {
+            return Err(Errno::EIO);
+        }
+        for (offset, byte) in word.to_le_bytes().into_iter().enumerate() {
+            self.memory.insert(address + offset as u64, byte);
+        }
+        Ok(())
+    }
+
+    fn getregs(&self) -> Result<user_regs_struct, Errno> {
+        self.getregs_calls.set(self.getregs_calls.get() + 1);
+        Ok(self.regs)
+    }
+
+    fn setregs(&mut self, regs: user_regs_struct) -> Result<(), Errno> {
+        self.regs = regs;
+        Ok(())
+    }
+
+    fn cont(&mut self) -> Result<(), Errno> {
+        self.requests.push("cont");
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<(), Errno> {
+        self.requests.push("step");
+        Ok(())
+    }
+
+    fn syscall(&mut self) -> Result<(), Errno> {
+        self.requests.push("syscall");
+        Ok(())
+    }
+
+    fn wait(&mut self) -> Result<WaitStatus, Errno> {
+        // No stop left: the process is gone.
+        let (status, regs) = self.stops.pop_front().ok_or(Errno::ECHILD)?;
+        self.regs = regs;
+        Ok(status)
+    }
+}
@@ -0,0 +1,109 @@
+use crate::output::strip_styles;
+use crate::repl::PROMPT;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The file written by `set logging on` when no other file was chosen.
+pub const DEFAULT_LOG_FILE: &str = "rustdbg.log";
+
+/// Copies the commands entered and the output of the debugger to a file (`set logging`), each
+/// line prefixed with the time it was written.
+pub struct Transcript {
+    /// The file to log to (`set logging file`).
+    pub path: String,
+    /// The open file while logging is on.
+    file: Option<File>,
+}
+
+impl Default for Transcript {
+    fn default() -> Transcript {
+        Transcript { path: DEFAULT_LOG_FILE.to_string(), file: None }
+    }
+}
+
+impl Transcript {
+    /// Returns true if logging is on.
+    pub fn is_on(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Start logging, appending to the log file after a header naming the program debugged.
+    ///
+    /// # Arguments
+    ///
+    /// * `program_path` - The program being debugged.
+    /// * `pid` - Its process ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or written.
+    pub fn start(&mut self, program_path: &str, pid: i32) -> io::Result<()> {
+        self.stop();
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let header = format!("=== Session started {}: {} (pid {}) ===\n", timestamp(SystemTime::now()), program_path, pid);
+        file.write_all(header.as_bytes())?;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Stop logging, noting the end of the session in the file.
+    pub fn stop(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            let footer = format!("=== Session stopped {} ===\n", timestamp(SystemTime::now()));
+            file.write_all(footer.as_bytes()).ok();
+        }
+    }
+
+    /// Log a command entered, after the prompt.
+    pub fn write_command(&mut self, command: &str) {
+        self.write_output(&format!("{}{}", PROMPT, command));
+    }
+
+    /// Log some output, one timestamped line per line of `text`, without its colors.
+    pub fn write_output(&mut self, text: &str) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let now = timestamp(SystemTime::now());
+        let mut lines = String::new();
+        for line in strip_styles(text).split('\n') {
+            lines.push_str(&format!("[{}] {}\n", now, line));
+        }
+        // Written at once and unbuffered, so the file is complete even if the debugger dies.
+        file.write_all(lines.as_bytes()).ok();
+    }
+}
+
+/// Format a time as `YYYY-MM-DD HH:MM:SS.mmm` in UTC.
+pub fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time_of_day = seconds % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// The date of a number of days since 1970-01-01, in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // From Howard Hinnant's `civil_from_days`: count in 400-year eras starting on March 1st.
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = (if month_index < 10 { month_index + 3 } else { month_index - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
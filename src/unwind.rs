@@ -0,0 +1,343 @@
+use crate::memory::read_u64;
+use crate::tracee::Tracee;
+use gimli::{
+    BaseAddresses, CfaRule, CieOrFde, DebugFrame, EhFrame, EndianArcSlice, FrameDescriptionEntry, Register,
+    RegisterRule, RunTimeEndian, UnwindContext, UnwindSection, UnwindTableRow,
+};
+use nix::libc::user_regs_struct;
+use object::{Object, ObjectSection};
+use std::fs;
+use std::sync::Arc;
+
+type Reader = EndianArcSlice<RunTimeEndian>;
+
+/// The number of registers of a frame: the DWARF numbered ones, and the program counter.
+#[cfg(target_arch = "x86_64")]
+pub const REGISTER_COUNT: usize = 17;
+/// DWARF number of the return address column on x86_64, the program counter of the caller.
+#[cfg(target_arch = "x86_64")]
+pub const RETURN_ADDRESS: usize = 16;
+/// The program counter, `rip`, the return address column on x86_64.
+#[cfg(target_arch = "x86_64")]
+pub const PC: usize = RETURN_ADDRESS;
+/// DWARF number of `rsp` on x86_64.
+#[cfg(target_arch = "x86_64")]
+pub const SP: usize = 7;
+/// DWARF number of `rbp` on x86_64.
+#[cfg(target_arch = "x86_64")]
+pub const FP: usize = 6;
+/// DWARF numbers of the registers a callee may clobber (rax, rdx, rcx, rsi, rdi, r8-r11).
+#[cfg(target_arch = "x86_64")]
+const CALLER_SAVED: [usize; 9] = [0, 1, 2, 4, 5, 8, 9, 10, 11];
+/// Names of the registers by DWARF number on x86_64.
+#[cfg(target_arch = "x86_64")]
+pub const REGISTER_NAMES: [&str; REGISTER_COUNT] =
+    ["rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "rip"];
+
+/// The number of registers of a frame on aarch64: `x0` to `x30`, `sp` and the program counter.
+#[cfg(target_arch = "aarch64")]
+pub const REGISTER_COUNT: usize = 33;
+/// DWARF number of the return address column on aarch64, the link register `x30`.
+#[cfg(target_arch = "aarch64")]
+pub const RETURN_ADDRESS: usize = 30;
+/// The program counter, kept after the DWARF numbered registers on aarch64, which have no column
+/// for it.
+#[cfg(target_arch = "aarch64")]
+pub const PC: usize = 32;
+/// DWARF number of `sp` on aarch64.
+#[cfg(target_arch = "aarch64")]
+pub const SP: usize = 31;
+/// DWARF number of the frame pointer `x29` on aarch64.
+#[cfg(target_arch = "aarch64")]
+pub const FP: usize = 29;
+/// DWARF numbers of the registers a callee may clobber (x0-x17).
+#[cfg(target_arch = "aarch64")]
+const CALLER_SAVED: [usize; 18] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17];
+/// Names of the registers by DWARF number on aarch64, then the program counter.
+#[cfg(target_arch = "aarch64")]
+pub const REGISTER_NAMES: [&str; REGISTER_COUNT] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15", "x16", "x17", "x18",
+    "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30", "sp", "pc",
+];
+
+/// The name of a register by DWARF number, `rip` for the return address column on x86_64.
+pub fn register_name(number: usize) -> &'static str {
+    REGISTER_NAMES.get(number).copied().unwrap_or("??")
+}
+
+/// How the caller of a frame is recovered, as shown by `info frame`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameRules {
+    /// True if the rules come from the call frame information, false for the frame pointer chain.
+    pub cfi: bool,
+    /// The register the canonical frame address is computed from, by DWARF number, and the offset added to it.
+    pub cfa_rule: (usize, i64),
+    /// The canonical frame address: the stack pointer before the call.
+    pub cfa: u64,
+    /// The registers of the caller saved by the frame, by DWARF number, with the address of their
+    /// slot. The return address is the column [`RETURN_ADDRESS`].
+    pub saved: Vec<(usize, u64)>,
+}
+
+/// Registers of a frame indexed by DWARF register number (0-15 general purpose, 16 `rip` on
+/// x86_64, `x0` to `x30` and `sp`, then the pc, on aarch64).
+///
+/// `None` means the value could not be recovered for this frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnwindRegisters(pub [Option<u64>; REGISTER_COUNT]);
+
+impl UnwindRegisters {
+    /// The registers of the innermost frame.
+    #[cfg(target_arch = "x86_64")]
+    pub fn from_regs(regs: &user_regs_struct) -> UnwindRegisters {
+        UnwindRegisters([
+            Some(regs.rax),
+            Some(regs.rdx),
+            Some(regs.rcx),
+            Some(regs.rbx),
+            Some(regs.rsi),
+            Some(regs.rdi),
+            Some(regs.rbp),
+            Some(regs.rsp),
+            Some(regs.r8),
+            Some(regs.r9),
+            Some(regs.r10),
+            Some(regs.r11),
+            Some(regs.r12),
+            Some(regs.r13),
+            Some(regs.r14),
+            Some(regs.r15),
+            Some(regs.rip),
+        ])
+    }
+
+    /// The registers of the innermost frame.
+    #[cfg(target_arch = "aarch64")]
+    pub fn from_regs(regs: &user_regs_struct) -> UnwindRegisters {
+        let mut registers = [None; REGISTER_COUNT];
+        for (register, value) in registers.iter_mut().zip(regs.regs) {
+            *register = Some(value);
+        }
+        registers[SP] = Some(regs.sp);
+        registers[PC] = Some(regs.pc);
+        UnwindRegisters(registers)
+    }
+
+    /// Program counter of the frame.
+    pub fn pc(&self) -> Option<u64> {
+        self.0[PC]
+    }
+
+    /// Stack pointer of the frame.
+    pub fn sp(&self) -> Option<u64> {
+        self.0[SP]
+    }
+
+    /// Frame pointer of the frame.
+    pub fn bp(&self) -> Option<u64> {
+        self.0[FP]
+    }
+
+    /// Build the registers of the caller of a frame using plain values.
+    pub fn caller(&self, pc: u64, sp: u64, bp: Option<u64>) -> UnwindRegisters {
+        let mut caller = *self;
+        for register in CALLER_SAVED {
+            caller.0[register] = None;
+        }
+        caller.0[PC] = Some(pc);
+        caller.0[SP] = Some(sp);
+        caller.0[FP] = bp;
+        caller
+    }
+}
+
+/// Result of unwinding one frame with the call frame information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfiStep {
+    /// The registers of the caller.
+    Caller(Box<UnwindRegisters>),
+    /// The CFI marks this frame as the outermost one (e.g. `_start`).
+    Outermost,
+    /// No usable CFI covers this address.
+    NoInfo,
+}
+
+enum FrameSection {
+    Eh(EhFrame<Reader>),
+    Debug(DebugFrame<Reader>),
+}
+
+/// The call frame information (`.eh_frame` or `.debug_frame`) of one ELF file.
+pub struct CfiModule {
+    section: FrameSection,
+    bases: BaseAddresses,
+    /// Every FDE of the module, parsed once and sorted by start address.
+    fdes: Vec<FrameDescriptionEntry<Reader>>,
+}
+
+impl CfiModule {
+    /// Load and index the call frame information of the ELF file at `path`.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the file can't be parsed or has neither `.eh_frame` nor `.debug_frame`.
+    pub fn load(path: &str) -> Option<CfiModule> {
+        let data = fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        let endian = if file.is_little_endian() { RunTimeEndian::Little } else { RunTimeEndian::Big };
+        let section_data = |name: &str| {
+            let section = file.section_by_name(name)?;
+            let bytes = section.uncompressed_data().ok()?;
+            Some((section.address(), EndianArcSlice::new(Arc::from(&*bytes), endian)))
+        };
+        let mut bases = BaseAddresses::default();
+        if let Some(text) = file.section_by_name(".text") {
+            bases = bases.set_text(text.address());
+        }
+        if let Some(got) = file.section_by_name(".got") {
+            bases = bases.set_got(got.address());
+        }
+        let section = match section_data(".eh_frame") {
+            Some((address, reader)) => {
+                bases = bases.set_eh_frame(address);
+                let mut eh_frame = EhFrame::from(reader);
+                eh_frame.set_address_size(8);
+                FrameSection::Eh(eh_frame)
+            }
+            None => {
+                let (_, reader) = section_data(".debug_frame")?;
+                let mut debug_frame = DebugFrame::from(reader);
+                debug_frame.set_address_size(8);
+                FrameSection::Debug(debug_frame)
+            }
+        };
+        let mut fdes = match &section {
+            FrameSection::Eh(eh_frame) => parse_fdes(eh_frame, &bases),
+            FrameSection::Debug(debug_frame) => parse_fdes(debug_frame, &bases),
+        };
+        fdes.sort_by_key(|fde| fde.initial_address());
+        Some(CfiModule { section, bases, fdes })
+    }
+
+    fn find_fde(&self, address: u64) -> Option<&FrameDescriptionEntry<Reader>> {
+        let index = self.fdes.partition_point(|fde| fde.initial_address() <= address);
+        let fde = self.fdes.get(index.checked_sub(1)?)?;
+        (address < fde.initial_address() + fde.len()).then_some(fde)
+    }
+
+    fn row(&self, context: &mut UnwindContext<usize>, address: u64) -> Option<UnwindTableRow<usize>> {
+        let fde = self.find_fde(address)?;
+        let row = match &self.section {
+            FrameSection::Eh(eh_frame) => fde.unwind_info_for_address(eh_frame, &self.bases, context, address),
+            FrameSection::Debug(debug_frame) => fde.unwind_info_for_address(debug_frame, &self.bases, context, address),
+        };
+        row.ok().cloned()
+    }
+
+    /// Compute the canonical frame address (the stack pointer before the call) of a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `registers` - The registers of the frame.
+    /// * `lookup` - The file address used to find the CFI row, as for [`CfiModule::unwind`].
+    /// * `bias` - The load bias of the module.
+    pub fn cfa(&self, registers: &UnwindRegisters, lookup: u64, bias: u64) -> Option<u64> {
+        let mut context = UnwindContext::new();
+        let row = self.row(&mut context, lookup.wrapping_sub(bias))?;
+        row_cfa(&row, registers)
+    }
+
+    /// Find the rules recovering the caller of a frame: how its canonical frame address is computed
+    /// and where the registers it saved are, as for [`CfiModule::unwind`].
+    ///
+    /// # Returns
+    ///
+    /// None if there is no call frame information for the frame, or its CFA is a DWARF expression.
+    pub fn rules(&self, registers: &UnwindRegisters, lookup: u64, bias: u64) -> Option<FrameRules> {
+        let mut context = UnwindContext::new();
+        let row = self.row(&mut context, lookup.wrapping_sub(bias))?;
+        let CfaRule::RegisterAndOffset { register, offset } = row.cfa() else {
+            return None;
+        };
+        let cfa = row_cfa(&row, registers)?;
+        let saved = row
+            .registers()
+            .filter_map(|(Register(number), rule)| match rule {
+                RegisterRule::Offset(offset) if (*number as usize) < REGISTER_NAMES.len() => {
+                    Some((*number as usize, cfa.wrapping_add_signed(*offset)))
+                }
+                _ => None,
+            })
+            .collect();
+        Some(FrameRules { cfi: true, cfa_rule: (register.0 as usize, *offset), cfa, saved })
+    }
+
+    /// Recover the registers of the caller of a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracee` - The thread being debugged, or the thread of the core file.
+    /// * `registers` - The registers of the frame.
+    /// * `lookup` - The file address used to find the CFI row: the pc for the innermost frame,
+    ///   the pc minus one for the callers (their pc is a return address).
+    /// * `bias` - The load bias of the module.
+    pub fn unwind(&self, tracee: &dyn Tracee, registers: &UnwindRegisters, lookup: u64, bias: u64) -> CfiStep {
+        let mut context = UnwindContext::new();
+        let Some(row) = self.row(&mut context, lookup.wrapping_sub(bias)) else {
+            return CfiStep::NoInfo;
+        };
+        let Some(cfa) = row_cfa(&row, registers) else {
+            return CfiStep::NoInfo;
+        };
+        let mut caller = *registers;
+        for register in CALLER_SAVED {
+            caller.0[register] = None;
+        }
+        caller.0[PC] = None;
+        caller.0[SP] = Some(cfa);
+        for (Register(number), rule) in row.registers() {
+            let number = *number as usize;
+            if number >= caller.0.len() {
+                continue;
+            }
+            caller.0[number] = match rule {
+                RegisterRule::Undefined => None,
+                RegisterRule::SameValue => registers.0[number],
+                RegisterRule::Offset(offset) => read_u64(tracee, cfa.wrapping_add_signed(*offset)).ok(),
+                RegisterRule::ValOffset(offset) => Some(cfa.wrapping_add_signed(*offset)),
+                RegisterRule::Register(Register(other)) => registers.0.get(*other as usize).copied().flatten(),
+                RegisterRule::Constant(value) => Some(*value),
+                _ => None,
+            };
+        }
+        // The return address of the frame is the pc of its caller (the same column on x86_64).
+        caller.0[PC] = caller.0[RETURN_ADDRESS];
+        match caller.pc() {
+            Some(0) | None => CfiStep::Outermost,
+            Some(_) => CfiStep::Caller(Box::new(caller)),
+        }
+    }
+}
+
+fn row_cfa(row: &UnwindTableRow<usize>, registers: &UnwindRegisters) -> Option<u64> {
+    match row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } => {
+            let value = registers.0.get(register.0 as usize).copied().flatten()?;
+            Some(value.wrapping_add_signed(*offset))
+        }
+        CfaRule::Expression(_) => None,
+    }
+}
+
+fn parse_fdes<S: UnwindSection<Reader>>(section: &S, bases: &BaseAddresses) -> Vec<FrameDescriptionEntry<Reader>> {
+    let mut fdes = Vec::new();
+    let mut entries = section.entries(bases);
+    while let Ok(Some(entry)) = entries.next() {
+        if let CieOrFde::Fde(partial) = entry {
+            if let Ok(fde) = partial.parse(S::cie_from_offset) {
+                fdes.push(fde);
+            }
+        }
+    }
+    fdes
+}
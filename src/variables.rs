@@ -0,0 +1,1189 @@
+use crate::backtrace;
+use crate::debugger::Debugger;
+use crate::debuginfo::{DebugInfo, Reader};
+use crate::maps::{self, MapEntry};
+use crate::memory::{read_bytes, read_c_string};
+use crate::symbols::path_matches;
+#[cfg(target_arch = "x86_64")]
+use crate::symbols::TlsSegment;
+use crate::tracee::Tracee;
+use crate::unwind::{self, UnwindRegisters};
+use gimli::{
+    AttributeValue, DebuggingInformationEntry, EntriesTreeNode, EvaluationResult, Expression, Location, Piece,
+    Reader as _, UnitOffset, UnitRef, Value,
+};
+use std::rc::Rc;
+use thiserror::Error;
+
+/// How many levels of nested types (struct fields, pointer targets) are decoded for a variable.
+const TYPE_DEPTH: usize = 4;
+/// Maximum number of elements of an array shown.
+const MAX_ARRAY_ELEMENTS: usize = 16;
+/// Maximum number of bytes read from the child to show a string.
+const MAX_STRING_LEN: usize = 200;
+/// Maximum number of bytes of a data symbol without DWARF type shown by `print`.
+const MAX_SYMBOL_BYTES: usize = 64;
+/// DWARF numbers and names of the registers holding the first six integer arguments in the
+/// System V x86_64 calling convention.
+const ARGUMENT_REGISTERS: [(usize, &str); 6] = [(5, "rdi"), (4, "rsi"), (1, "rdx"), (2, "rcx"), (8, "r8"), (9, "r9")];
+
+/// Errors raised while looking up or reading a variable.
+#[derive(Debug, Error)]
+pub enum VariableError {
+    #[error("No symbol table is loaded.")]
+    NoDebugInfo,
+    #[error("No stack.")]
+    NoFrame,
+    #[error("No function contains the selected frame's pc.")]
+    NoFunction,
+    #[error("<optimized out>")]
+    OptimizedOut,
+    #[error("<unsupported location expression: {0}>")]
+    Unsupported(String),
+    #[error("<register {0} is not available in this frame>")]
+    Register(u16),
+    #[error("<cannot access memory at address {0:#x}>")]
+    Memory(u64),
+    #[error("<TLS resolution not available: {0}>")]
+    Tls(&'static str),
+    #[error("DWARF error: {0}")]
+    Dwarf(#[from] gimli::Error),
+    #[error("No symbol \"{0}\" in current context.")]
+    NoSymbol(String),
+    #[error("\"{name}\" is ambiguous, use one of: {}", .candidates.join(", "))]
+    Ambiguous { name: String, candidates: Vec<String> },
+    #[error("The load address of {0} is not known yet.")]
+    NotLoaded(String),
+}
+
+/// A type described by the DWARF information, decoded enough to format values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DwarfType {
+    pub name: String,
+    /// Size of a value in bytes.
+    pub size: u64,
+    pub kind: TypeKind,
+}
+
+/// The layout of a [`DwarfType`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeKind {
+    /// A base type with its `DW_ATE_*` encoding.
+    Base(gimli::DwAte),
+    /// A pointer or reference, with its target type when it was decoded.
+    Pointer(Option<Box<DwarfType>>),
+    /// A struct, class or union.
+    Struct(Vec<Member>),
+    /// A C-like enumeration with the value of each enumerator.
+    Enum(Vec<(String, i64)>),
+    /// An array with its element type and number of elements.
+    Array(Box<DwarfType>, u64),
+    /// A Rust enum: the member holding the discriminant, if any, and the variants it selects.
+    Variants(Option<Box<Member>>, Vec<Variant>),
+    /// A type whose layout is not decoded (unsupported kind or nested too deeply).
+    Opaque,
+}
+
+/// A field of a struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub name: String,
+    /// Offset of the field from the start of the struct, in bytes.
+    pub offset: u64,
+    pub ty: DwarfType,
+}
+
+/// A variant of a Rust enum (`DW_TAG_variant`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// The discriminant selecting the variant, `None` for the variant selected by the values of
+    /// no other variant (the one holding the niche).
+    pub discriminant: Option<u64>,
+    /// The payload, a struct named after the variant.
+    pub member: Member,
+}
+
+#[derive(Debug, Clone)]
+enum VariableLocation {
+    Expression(Expression<Reader>),
+    /// The value itself is known (`DW_AT_const_value`).
+    Constant(Vec<u8>),
+    /// No location covers the pc.
+    OptimizedOut,
+}
+
+/// A local variable, parameter or static described by the DWARF information.
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    /// True for the parameters of a function.
+    pub is_parameter: bool,
+    pub ty: Option<DwarfType>,
+    location: VariableLocation,
+    encoding: gimli::Encoding,
+}
+
+/// The variables visible at a pc inside a function.
+#[derive(Debug, Clone)]
+pub struct FunctionScope {
+    /// The variables of the innermost lexical block come first, so they shadow the outer ones.
+    pub variables: Vec<Variable>,
+    frame_base: Option<Expression<Reader>>,
+    encoding: gimli::Encoding,
+}
+
+impl FunctionScope {
+    /// Evaluate the frame base (`DW_AT_frame_base`) used by the `DW_OP_fbreg` locations.
+    pub fn frame_base(&self, context: &FrameContext) -> Option<u64> {
+        let pieces = evaluate(self.frame_base.as_ref()?, self.encoding, None, context).ok()?;
+        match pieces.first()?.location {
+            Location::Register { register } => context.register(register.0).ok(),
+            Location::Address { address } => Some(address),
+            Location::Value { value } => value.to_u64(!0).ok(),
+            _ => None,
+        }
+    }
+
+    /// Find a variable by name, the innermost one when several have the same name.
+    pub fn find(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+}
+
+/// What location expressions need to know about the frame they are evaluated in.
+#[derive(Clone)]
+pub struct FrameContext {
+    /// The thread being debugged, or the thread of the core file.
+    pub tracee: Rc<dyn Tracee>,
+    /// The registers recovered for the frame.
+    pub registers: UnwindRegisters,
+    /// The canonical frame address of the frame, for `DW_OP_call_frame_cfa`.
+    pub cfa: Option<u64>,
+    /// The load bias of the module, for `DW_OP_addr`.
+    pub bias: u64,
+    /// Start of the module's thread-local storage block for the current thread, for
+    /// `DW_OP_form_tls_address`.
+    pub tls_block: Option<u64>,
+}
+
+impl FrameContext {
+    fn register(&self, register: u16) -> Result<u64, VariableError> {
+        self.registers.0.get(register as usize).copied().flatten().ok_or(VariableError::Register(register))
+    }
+
+    fn read_memory(&self, address: u64, len: usize) -> Result<Vec<u8>, VariableError> {
+        read_bytes(self.tracee.as_ref(), address, len).map_err(|_| VariableError::Memory(address))
+    }
+}
+
+/// Find the function containing a file address and the variables in scope at that address.
+pub fn function_scope(debug_info: &DebugInfo, address: u64) -> Result<Option<FunctionScope>, gimli::Error> {
+    let Some(unit) = debug_info.unit_at(address) else {
+        return Ok(None);
+    };
+    let mut tree = unit.entries_tree(None)?;
+    find_function(unit, tree.root()?, address)
+}
+
+/// Find the global and static variables designated by `name` in every compilation unit.
+///
+/// Variables nested in namespaces (Rust modules) match by their full path or by its last
+/// components, so `COUNTER` finds `my_crate::state::COUNTER`.
+///
+/// # Returns
+///
+/// The full path and the description of each matching variable.
+pub fn find_globals(debug_info: &DebugInfo, name: &str) -> Result<Vec<(String, Variable)>, gimli::Error> {
+    let dwarf = debug_info.dwarf();
+    let mut globals = Vec::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let unit = unit.unit_ref(dwarf);
+        let mut tree = unit.entries_tree(None)?;
+        find_globals_in(unit, tree.root()?, "", name, &mut globals)?;
+    }
+    Ok(globals)
+}
+
+fn find_globals_in(
+    unit: UnitRef<Reader>,
+    node: EntriesTreeNode<Reader>,
+    prefix: &str,
+    name: &str,
+    globals: &mut Vec<(String, Variable)>,
+) -> Result<(), gimli::Error> {
+    let mut children = node.children();
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        match entry.tag() {
+            gimli::DW_TAG_variable if entry.attr_value(gimli::DW_AT_location)?.is_some() => {
+                let Some(variable) = parse_variable(unit, entry, None)? else {
+                    continue;
+                };
+                let qualified = format!("{}{}", prefix, variable.name);
+                if path_matches(&qualified, name) {
+                    globals.push((qualified, variable));
+                }
+            }
+            gimli::DW_TAG_namespace | gimli::DW_TAG_module => {
+                let namespace = entry_name(unit, entry)?.unwrap_or_default();
+                find_globals_in(unit, child, &format!("{}{}::", prefix, namespace), name, globals)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn find_function(
+    unit: UnitRef<Reader>,
+    node: EntriesTreeNode<Reader>,
+    address: u64,
+) -> Result<Option<FunctionScope>, gimli::Error> {
+    let mut children = node.children();
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        match entry.tag() {
+            gimli::DW_TAG_subprogram if contains(unit, entry, address)? => {
+                let frame_base = entry.attr_value(gimli::DW_AT_frame_base)?.and_then(|value| value.exprloc_value());
+                let variables = scope_variables(unit, child, address)?;
+                return Ok(Some(FunctionScope { variables, frame_base, encoding: unit.encoding() }));
+            }
+            gimli::DW_TAG_namespace
+            | gimli::DW_TAG_module
+            | gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_class_type
+            | gimli::DW_TAG_union_type => {
+                if let Some(scope) = find_function(unit, child, address)? {
+                    return Ok(Some(scope));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+fn scope_variables(
+    unit: UnitRef<Reader>,
+    node: EntriesTreeNode<Reader>,
+    address: u64,
+) -> Result<Vec<Variable>, gimli::Error> {
+    let mut variables = Vec::new();
+    let mut inner = Vec::new();
+    let mut children = node.children();
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        match entry.tag() {
+            gimli::DW_TAG_variable | gimli::DW_TAG_formal_parameter => {
+                variables.extend(parse_variable(unit, entry, Some(address))?);
+            }
+            gimli::DW_TAG_lexical_block if contains(unit, entry, address)? => {
+                inner = scope_variables(unit, child, address)?;
+            }
+            _ => {}
+        }
+    }
+    inner.extend(variables);
+    Ok(inner)
+}
+
+fn contains(unit: UnitRef<Reader>, entry: &DebuggingInformationEntry<Reader>, address: u64) -> Result<bool, gimli::Error> {
+    let mut ranges = unit.die_ranges(entry)?;
+    while let Some(range) = ranges.next()? {
+        if address >= range.begin && address < range.end {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The entry an entry refers to for its name and type (inlined or out-of-line definitions).
+fn origin<'a>(
+    unit: UnitRef<'a, Reader>,
+    entry: &DebuggingInformationEntry<Reader>,
+) -> Result<Option<DebuggingInformationEntry<'a, 'a, Reader>>, gimli::Error> {
+    for attr in [gimli::DW_AT_abstract_origin, gimli::DW_AT_specification] {
+        if let Some(AttributeValue::UnitRef(offset)) = entry.attr_value(attr)? {
+            return Ok(Some(unit.unit.entry(offset)?));
+        }
+    }
+    Ok(None)
+}
+
+fn entry_name(unit: UnitRef<Reader>, entry: &DebuggingInformationEntry<Reader>) -> Result<Option<String>, gimli::Error> {
+    if let Some(name) = entry.attr_value(gimli::DW_AT_name)? {
+        return Ok(Some(unit.attr_string(name)?.to_string_lossy()?.into_owned()));
+    }
+    match origin(unit, entry)? {
+        Some(origin) => entry_name(unit, &origin),
+        None => Ok(None),
+    }
+}
+
+fn entry_type(unit: UnitRef<Reader>, entry: &DebuggingInformationEntry<Reader>) -> Result<Option<UnitOffset>, gimli::Error> {
+    if let Some(AttributeValue::UnitRef(offset)) = entry.attr_value(gimli::DW_AT_type)? {
+        return Ok(Some(offset));
+    }
+    match origin(unit, entry)? {
+        Some(origin) => entry_type(unit, &origin),
+        None => Ok(None),
+    }
+}
+
+/// Parse a variable or parameter entry.
+///
+/// # Arguments
+///
+/// * `address` - The file address the variable is looked up at, to pick its location list entry.
+fn parse_variable(
+    unit: UnitRef<Reader>,
+    entry: &DebuggingInformationEntry<Reader>,
+    address: Option<u64>,
+) -> Result<Option<Variable>, gimli::Error> {
+    if entry.attr_value(gimli::DW_AT_declaration)?.is_some() {
+        return Ok(None);
+    }
+    let Some(name) = entry_name(unit, entry)? else {
+        return Ok(None);
+    };
+    let ty = match entry_type(unit, entry)? {
+        Some(offset) => Some(parse_type(unit, offset, TYPE_DEPTH)?),
+        None => None,
+    };
+    let location = match entry.attr_value(gimli::DW_AT_location)? {
+        Some(AttributeValue::Exprloc(expression)) => VariableLocation::Expression(expression),
+        Some(value) => {
+            let mut location = VariableLocation::OptimizedOut;
+            if let (Some(mut locations), Some(address)) = (unit.attr_locations(value)?, address) {
+                while let Some(entry) = locations.next()? {
+                    if address >= entry.range.begin && address < entry.range.end {
+                        location = VariableLocation::Expression(entry.data);
+                        break;
+                    }
+                }
+            }
+            location
+        }
+        None => match entry.attr_value(gimli::DW_AT_const_value)? {
+            Some(AttributeValue::Block(bytes)) => VariableLocation::Constant(bytes.to_slice()?.into_owned()),
+            Some(value) => match value.sdata_value().map(|value| value as u64).or(value.udata_value()) {
+                Some(value) => VariableLocation::Constant(value.to_le_bytes().to_vec()),
+                None => VariableLocation::OptimizedOut,
+            },
+            None => VariableLocation::OptimizedOut,
+        },
+    };
+    Ok(Some(Variable {
+        name,
+        is_parameter: entry.tag() == gimli::DW_TAG_formal_parameter,
+        ty,
+        location,
+        encoding: unit.encoding(),
+    }))
+}
+
+fn parse_type(unit: UnitRef<Reader>, offset: UnitOffset, depth: usize) -> Result<DwarfType, gimli::Error> {
+    let entry = unit.entry(offset)?;
+    let name = entry_name(unit, &entry)?;
+    let size = entry.attr_value(gimli::DW_AT_byte_size)?.and_then(|value| value.udata_value());
+    let target = |depth| -> Result<Option<DwarfType>, gimli::Error> {
+        match entry_type(unit, &entry)? {
+            Some(offset) => Ok(Some(parse_type(unit, offset, depth)?)),
+            None => Ok(None),
+        }
+    };
+    let opaque = |name: Option<String>| DwarfType {
+        name: name.unwrap_or_else(|| "?".to_string()),
+        size: size.unwrap_or(0),
+        kind: TypeKind::Opaque,
+    };
+    let ty = match entry.tag() {
+        gimli::DW_TAG_base_type => {
+            let encoding = match entry.attr_value(gimli::DW_AT_encoding)? {
+                Some(AttributeValue::Encoding(encoding)) => encoding,
+                _ => gimli::DW_ATE_unsigned,
+            };
+            DwarfType { name: name.unwrap_or_default(), size: size.unwrap_or(0), kind: TypeKind::Base(encoding) }
+        }
+        gimli::DW_TAG_pointer_type | gimli::DW_TAG_reference_type | gimli::DW_TAG_rvalue_reference_type => {
+            let target = if depth > 0 { target(depth - 1)? } else { None };
+            let name = name.unwrap_or_else(|| {
+                let suffix = if entry.tag() == gimli::DW_TAG_pointer_type { "*" } else { "&" };
+                format!("{} {}", target.as_ref().map_or("void", |target| &target.name), suffix)
+            });
+            DwarfType { name, size: size.unwrap_or(8), kind: TypeKind::Pointer(target.map(Box::new)) }
+        }
+        gimli::DW_TAG_structure_type | gimli::DW_TAG_class_type | gimli::DW_TAG_union_type if depth > 0 => {
+            let mut members = Vec::new();
+            let mut tree = unit.entries_tree(Some(offset))?;
+            let mut children = tree.root()?.children();
+            while let Some(child) = children.next()? {
+                match child.entry().tag() {
+                    gimli::DW_TAG_member => members.extend(parse_member(unit, child.entry(), depth - 1)?),
+                    gimli::DW_TAG_variant_part => {
+                        let (discriminant, variants) = parse_variant_part(unit, child, depth - 1)?;
+                        let kind = TypeKind::Variants(discriminant.map(Box::new), variants);
+                        return Ok(DwarfType { name: name.unwrap_or_default(), size: size.unwrap_or(0), kind });
+                    }
+                    _ => {}
+                }
+            }
+            DwarfType { name: name.unwrap_or_default(), size: size.unwrap_or(0), kind: TypeKind::Struct(members) }
+        }
+        gimli::DW_TAG_array_type => {
+            // The element type is decoded even at the last level, to know the size of the array.
+            let Some(element) = target(depth.saturating_sub(1))? else {
+                return Ok(opaque(name));
+            };
+            let mut counts = Vec::new();
+            let mut tree = unit.entries_tree(Some(offset))?;
+            let mut children = tree.root()?.children();
+            while let Some(child) = children.next()? {
+                let subrange = child.entry();
+                if subrange.tag() != gimli::DW_TAG_subrange_type {
+                    continue;
+                }
+                let count = match subrange.attr_value(gimli::DW_AT_count)?.and_then(|value| value.udata_value()) {
+                    Some(count) => count,
+                    // A flexible array member has no bound.
+                    None => subrange.attr_value(gimli::DW_AT_upper_bound)?.and_then(|value| value.udata_value()).map_or(0, |bound| bound + 1),
+                };
+                counts.push(count);
+            }
+            // `int[2][3]` is an array of 2 arrays of 3 ints.
+            let mut ty = element;
+            let base = ty.name.clone();
+            for index in (0..counts.len()).rev() {
+                let dimensions: String = counts[index..].iter().map(|count| format!("[{}]", count)).collect();
+                let size = ty.size * counts[index];
+                ty = DwarfType { name: format!("{}{}", base, dimensions), size, kind: TypeKind::Array(Box::new(ty), counts[index]) };
+            }
+            ty
+        }
+        gimli::DW_TAG_enumeration_type => {
+            let mut enumerators = Vec::new();
+            let mut tree = unit.entries_tree(Some(offset))?;
+            let mut children = tree.root()?.children();
+            while let Some(child) = children.next()? {
+                let enumerator = child.entry();
+                if enumerator.tag() != gimli::DW_TAG_enumerator {
+                    continue;
+                }
+                let value = enumerator.attr_value(gimli::DW_AT_const_value)?;
+                if let (Some(name), Some(value)) = (
+                    entry_name(unit, enumerator)?,
+                    value.and_then(|value| value.sdata_value().or(value.udata_value().map(|value| value as i64))),
+                ) {
+                    enumerators.push((name, value));
+                }
+            }
+            DwarfType { name: name.unwrap_or_default(), size: size.unwrap_or(4), kind: TypeKind::Enum(enumerators) }
+        }
+        gimli::DW_TAG_typedef => match target(depth)? {
+            Some(target) => DwarfType { name: name.unwrap_or(target.name), ..target },
+            None => opaque(name),
+        },
+        gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type => {
+            let qualifier = if entry.tag() == gimli::DW_TAG_const_type { "const" } else { "volatile" };
+            match target(depth)? {
+                Some(target) => DwarfType { name: format!("{} {}", qualifier, target.name), ..target },
+                None => opaque(Some(format!("{} void", qualifier))),
+            }
+        }
+        gimli::DW_TAG_restrict_type | gimli::DW_TAG_atomic_type => target(depth)?.unwrap_or_else(|| opaque(name)),
+        _ => opaque(name),
+    };
+    Ok(ty)
+}
+
+/// Parse a field of a struct, skipping the ones without a type.
+fn parse_member(unit: UnitRef<Reader>, entry: &DebuggingInformationEntry<Reader>, depth: usize) -> Result<Option<Member>, gimli::Error> {
+    let Some(member_type) = entry_type(unit, entry)? else {
+        return Ok(None);
+    };
+    Ok(Some(Member {
+        name: entry_name(unit, entry)?.unwrap_or_default(),
+        offset: entry.attr_value(gimli::DW_AT_data_member_location)?.and_then(|value| value.udata_value()).unwrap_or(0),
+        ty: parse_type(unit, member_type, depth)?,
+    }))
+}
+
+/// Parse the variants of a Rust enum (`DW_TAG_variant_part`) and the member holding their
+/// discriminant (`DW_AT_discr`).
+fn parse_variant_part(
+    unit: UnitRef<Reader>,
+    node: EntriesTreeNode<Reader>,
+    depth: usize,
+) -> Result<(Option<Member>, Vec<Variant>), gimli::Error> {
+    let discriminant_offset = match node.entry().attr_value(gimli::DW_AT_discr)? {
+        Some(AttributeValue::UnitRef(offset)) => Some(offset),
+        _ => None,
+    };
+    let mut discriminant = None;
+    let mut variants = Vec::new();
+    let mut children = node.children();
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        match entry.tag() {
+            gimli::DW_TAG_member if Some(entry.offset()) == discriminant_offset => discriminant = parse_member(unit, entry, depth)?,
+            gimli::DW_TAG_variant => {
+                let value = entry.attr_value(gimli::DW_AT_discr_value)?;
+                let value = value.and_then(|value| value.udata_value().or(value.sdata_value().map(|value| value as u64)));
+                let mut members = child.children();
+                while let Some(member) = members.next()? {
+                    if member.entry().tag() == gimli::DW_TAG_member {
+                        if let Some(member) = parse_member(unit, member.entry(), depth)? {
+                            variants.push(Variant { discriminant: value, member });
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((discriminant, variants))
+}
+
+/// Evaluate a location expression.
+///
+/// # Arguments
+///
+/// * `expression` - The location expression.
+/// * `encoding` - The encoding of the unit defining the expression.
+/// * `frame_base` - The frame base of the function, for `DW_OP_fbreg`.
+/// * `context` - The frame the expression is evaluated in.
+fn evaluate(
+    expression: &Expression<Reader>,
+    encoding: gimli::Encoding,
+    frame_base: Option<u64>,
+    context: &FrameContext,
+) -> Result<Vec<Piece<Reader>>, VariableError> {
+    let unsupported = |operation: &str| VariableError::Unsupported(operation.to_string());
+    let mut evaluation = expression.clone().evaluation(encoding);
+    let mut result = evaluation.evaluate()?;
+    loop {
+        result = match result {
+            EvaluationResult::Complete => break,
+            EvaluationResult::RequiresMemory { address, size, .. } => {
+                let bytes = context.read_memory(address, size as usize)?;
+                let mut word = [0u8; 8];
+                word[..bytes.len()].copy_from_slice(&bytes);
+                evaluation.resume_with_memory(Value::Generic(u64::from_le_bytes(word)))?
+            }
+            EvaluationResult::RequiresRegister { register, .. } => {
+                evaluation.resume_with_register(Value::Generic(context.register(register.0)?))?
+            }
+            EvaluationResult::RequiresFrameBase => {
+                evaluation.resume_with_frame_base(frame_base.ok_or_else(|| unsupported("DW_OP_fbreg"))?)?
+            }
+            EvaluationResult::RequiresCallFrameCfa => {
+                evaluation.resume_with_call_frame_cfa(context.cfa.ok_or_else(|| unsupported("DW_OP_call_frame_cfa"))?)?
+            }
+            EvaluationResult::RequiresRelocatedAddress(address) => {
+                evaluation.resume_with_relocated_address(address.wrapping_add(context.bias))?
+            }
+            EvaluationResult::RequiresTls(offset) => {
+                let block = context.tls_block.ok_or(VariableError::Tls("no static TLS block for this module"))?;
+                evaluation.resume_with_tls(block.wrapping_add(offset))?
+            }
+            EvaluationResult::RequiresEntryValue(_) => return Err(unsupported("DW_OP_entry_value")),
+            EvaluationResult::RequiresParameterRef(_) => return Err(unsupported("DW_OP_GNU_parameter_ref")),
+            EvaluationResult::RequiresAtLocation(_) => return Err(unsupported("DW_OP_call")),
+            EvaluationResult::RequiresIndexedAddress { .. } => return Err(unsupported("DW_OP_addrx")),
+            EvaluationResult::RequiresBaseType(_) => return Err(unsupported("typed stack operations")),
+        };
+    }
+    Ok(evaluation.result())
+}
+
+/// Read the bytes of a variable's value.
+///
+/// # Arguments
+///
+/// * `variable` - The variable to read.
+/// * `frame_base` - The frame base of the function defining the variable, if any.
+/// * `context` - The frame the variable lives in.
+pub fn read_variable(variable: &Variable, frame_base: Option<u64>, context: &FrameContext) -> Result<Vec<u8>, VariableError> {
+    let expression = match &variable.location {
+        VariableLocation::Expression(expression) => expression,
+        VariableLocation::Constant(bytes) => return Ok(bytes.clone()),
+        VariableLocation::OptimizedOut => return Err(VariableError::OptimizedOut),
+    };
+    let size = variable.ty.as_ref().map_or(8, |ty| ty.size) as usize;
+    let mut bytes = Vec::new();
+    for piece in evaluate(expression, variable.encoding, frame_base, context)? {
+        let len = piece.size_in_bits.map_or(size, |bits| bits.div_ceil(8) as usize);
+        match piece.location {
+            Location::Address { address } => bytes.extend(context.read_memory(address, len)?),
+            Location::Register { register } => {
+                let value = context.register(register.0)?;
+                bytes.extend(&value.to_le_bytes()[..len.min(8)]);
+            }
+            Location::Value { value } => bytes.extend(&value.to_u64(!0)?.to_le_bytes()[..len.min(8)]),
+            Location::Bytes { value } => bytes.extend(value.to_slice()?.iter()),
+            Location::Empty => return Err(VariableError::OptimizedOut),
+            Location::ImplicitPointer { .. } => return Err(VariableError::Unsupported("DW_OP_implicit_pointer".to_string())),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Find the address of a variable living in memory, as a static does.
+///
+/// # Errors
+///
+/// Returns [`VariableError::Unsupported`] if the variable is in registers or only its value is
+/// known, or the error of evaluating its location.
+pub fn variable_address(variable: &Variable, context: &FrameContext) -> Result<u64, VariableError> {
+    let VariableLocation::Expression(expression) = &variable.location else {
+        return Err(VariableError::Unsupported("not in memory".to_string()));
+    };
+    match evaluate(expression, variable.encoding, None, context)?.first().map(|piece| piece.location.clone()) {
+        Some(Location::Address { address }) => Ok(address),
+        _ => Err(VariableError::Unsupported("not in memory".to_string())),
+    }
+}
+
+/// How much of a value [`format_value`] shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatLimits {
+    /// Levels of structs, arrays and enums nested in the value that are shown, the deeper ones
+    /// being `{...}`.
+    pub depth: usize,
+    /// Pointers followed to preview the value they point to.
+    pub hops: usize,
+}
+
+impl FormatLimits {
+    /// `print`: the fields of the fields, and what the pointers point to.
+    pub const DEFAULT: FormatLimits = FormatLimits { depth: 1, hops: 1 };
+    /// `print/full`: every level of the type decoded.
+    pub const FULL: FormatLimits = FormatLimits { depth: TYPE_DEPTH, hops: 1 };
+}
+
+/// Read a variable and format its value, or the reason it can't be read.
+pub fn format_variable(variable: &Variable, frame_base: Option<u64>, context: &FrameContext, limits: FormatLimits) -> String {
+    match read_variable(variable, frame_base, context) {
+        Ok(bytes) => match &variable.ty {
+            Some(ty) => format_value(ty, &bytes, limits, &mut |address, len| read_bytes(context.tracee.as_ref(), address, len).ok()),
+            None => format_raw(&bytes),
+        },
+        Err(err) => err.to_string(),
+    }
+}
+
+/// Format a value according to its type.
+///
+/// # Arguments
+///
+/// * `ty` - The type of the value.
+/// * `bytes` - The bytes of the value.
+/// * `limits` - The levels of nested values shown and the pointers followed.
+/// * `memory` - Reads memory of the child, used to show the text of string slices and what the
+///   pointers point to.
+pub fn format_value(ty: &DwarfType, bytes: &[u8], limits: FormatLimits, memory: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>) -> String {
+    match &ty.kind {
+        TypeKind::Base(encoding) => format_base(*encoding, bytes),
+        TypeKind::Pointer(target) => format_pointer(target.as_deref(), read_unsigned(bytes) as u64, limits, memory),
+        TypeKind::Struct(members) if ty.name == "&str" => format_str(members, bytes, memory),
+        TypeKind::Struct(members) => {
+            let fields = format_fields(members, bytes, limits, memory);
+            if ty.name.is_empty() {
+                format!("{{ {} }}", fields)
+            } else {
+                format!("{} {{ {} }}", ty.name, fields)
+            }
+        }
+        TypeKind::Array(element, count) if is_char(element) => {
+            let text: Vec<u8> = bytes.iter().take(*count as usize).take_while(|byte| **byte != 0).copied().collect();
+            format!("{:?}", String::from_utf8_lossy(&text))
+        }
+        TypeKind::Array(element, count) => {
+            let size = element.size as usize;
+            let shown = (*count as usize).min(MAX_ARRAY_ELEMENTS);
+            let mut elements: Vec<String> = (0..shown)
+                .map(|index| match bytes.get(index * size..(index + 1) * size) {
+                    Some(bytes) => format_nested(element, bytes, limits, memory),
+                    None => "<unavailable>".to_string(),
+                })
+                .collect();
+            if shown < *count as usize {
+                elements.push(format!("... {} more", *count as usize - shown));
+            }
+            format!("[{}]", elements.join(", "))
+        }
+        TypeKind::Variants(discriminant, variants) => format_variant(ty, discriminant.as_deref(), variants, bytes, limits, memory),
+        TypeKind::Enum(enumerators) => {
+            let value = read_signed(bytes);
+            match enumerators.iter().find(|(_, enumerator)| *enumerator as i128 == value) {
+                Some((name, _)) => name.clone(),
+                None => value.to_string(),
+            }
+        }
+        TypeKind::Opaque => format_raw(bytes),
+    }
+}
+
+/// Returns true for the structs, arrays and enums, whose values are made of other values.
+fn is_aggregate(ty: &DwarfType) -> bool {
+    matches!(ty.kind, TypeKind::Struct(_) | TypeKind::Array(..) | TypeKind::Variants(..)) && ty.name != "&str"
+}
+
+/// Returns true for the C character types, whose arrays and pointers are shown as strings.
+fn is_char(ty: &DwarfType) -> bool {
+    ty.size == 1 && matches!(ty.kind, TypeKind::Base(gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char))
+}
+
+/// Format a value nested in another one: a field, an element or a payload, shown as `{...}` when
+/// it is made of other values and no more levels are shown.
+fn format_nested(ty: &DwarfType, bytes: &[u8], limits: FormatLimits, memory: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>) -> String {
+    if !is_aggregate(ty) {
+        return format_value(ty, bytes, limits, memory);
+    }
+    match limits.depth.checked_sub(1) {
+        Some(depth) => format_value(ty, bytes, FormatLimits { depth, ..limits }, memory),
+        None => "{...}".to_string(),
+    }
+}
+
+/// Format the fields of a struct as `name: value, ...`.
+fn format_fields(members: &[Member], bytes: &[u8], limits: FormatLimits, memory: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>) -> String {
+    let fields: Vec<String> = members
+        .iter()
+        .map(|member| {
+            let start = member.offset as usize;
+            let value = match bytes.get(start..start + member.ty.size as usize) {
+                Some(field) => format_nested(&member.ty, field, limits, memory),
+                None => "<unavailable>".to_string(),
+            };
+            format!("{}: {}", member.name, value)
+        })
+        .collect();
+    fields.join(", ")
+}
+
+/// Format a pointer as its address, followed by a preview of the value it points to while
+/// `limits` allows following pointers: `0x7ffd1234 -> 5`, or the text of a C string.
+fn format_pointer(target: Option<&DwarfType>, address: u64, limits: FormatLimits, memory: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>) -> String {
+    let Some(target) = target.filter(|target| limits.hops > 0 && address != 0 && target.size > 0) else {
+        return format!("{:#x}", address);
+    };
+    if is_char(target) {
+        let Some(text) = memory(address, MAX_STRING_LEN) else {
+            return format!("{:#x} <cannot access memory>", address);
+        };
+        let end = text.iter().position(|byte| *byte == 0);
+        let text = format!("{:?}", String::from_utf8_lossy(&text[..end.unwrap_or(text.len())]));
+        return if end.is_some() { format!("{:#x} {}", address, text) } else { format!("{:#x} {}...", address, text) };
+    }
+    match memory(address, target.size as usize) {
+        Some(bytes) => {
+            // The value pointed to shows its fields, but not the values nested in them.
+            let value = format_value(target, &bytes, FormatLimits { depth: 0, hops: limits.hops - 1 }, memory);
+            format!("{:#x} -> {}", address, value)
+        }
+        None => format!("{:#x} <cannot access memory>", address),
+    }
+}
+
+/// Format the variant of a Rust enum selected by its discriminant, as `Some(5)`, `None` or
+/// `Shape::Circle { radius: 2 }`. `Option` and `Result` are so common their name is left out.
+fn format_variant(
+    ty: &DwarfType,
+    discriminant: Option<&Member>,
+    variants: &[Variant],
+    bytes: &[u8],
+    limits: FormatLimits,
+    memory: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>,
+) -> String {
+    let variant = match discriminant {
+        Some(member) => {
+            let start = member.offset as usize;
+            let Some(field) = bytes.get(start..start + member.ty.size as usize) else {
+                return format_raw(bytes);
+            };
+            let value = read_unsigned(field) as u64;
+            let mask = if field.len() >= 8 { u64::MAX } else { (1 << (field.len() * 8)) - 1 };
+            variants
+                .iter()
+                .find(|variant| variant.discriminant.is_some_and(|discriminant| discriminant & mask == value))
+                .or_else(|| variants.iter().find(|variant| variant.discriminant.is_none()))
+        }
+        None => variants.first(),
+    };
+    let Some(variant) = variant else {
+        return format!("<unknown variant of {}>", ty.name);
+    };
+    let generic = ty.name.split('<').next().unwrap_or_default();
+    let name = if matches!(generic.rsplit("::").next(), Some("Option" | "Result")) {
+        variant.member.name.clone()
+    } else {
+        format!("{}::{}", ty.name, variant.member.name)
+    };
+    let TypeKind::Struct(members) = &variant.member.ty.kind else {
+        return name;
+    };
+    let start = variant.member.offset as usize;
+    let payload = bytes.get(start..).unwrap_or_default();
+    if members.is_empty() {
+        return name;
+    }
+    // Tuple variants name their fields __0, __1...
+    if members.iter().all(|member| member.name.starts_with("__")) {
+        let fields: Vec<String> = members
+            .iter()
+            .map(|member| {
+                let start = member.offset as usize;
+                match payload.get(start..start + member.ty.size as usize) {
+                    Some(field) => format_nested(&member.ty, field, limits, memory),
+                    None => "<unavailable>".to_string(),
+                }
+            })
+            .collect();
+        return format!("{}({})", name, fields.join(", "));
+    }
+    format!("{} {{ {} }}", name, format_fields(members, payload, limits, memory))
+}
+
+fn format_base(encoding: gimli::DwAte, bytes: &[u8]) -> String {
+    match encoding {
+        gimli::DW_ATE_boolean => (read_unsigned(bytes) != 0).to_string(),
+        gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char if bytes.len() == 1 => {
+            let value = if encoding == gimli::DW_ATE_signed_char { bytes[0] as i8 as i32 } else { bytes[0] as i32 };
+            format!("{} {:?}", value, bytes[0] as char)
+        }
+        gimli::DW_ATE_signed | gimli::DW_ATE_signed_char => read_signed(bytes).to_string(),
+        gimli::DW_ATE_unsigned | gimli::DW_ATE_unsigned_char => read_unsigned(bytes).to_string(),
+        gimli::DW_ATE_float if bytes.len() == 4 => f32::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        gimli::DW_ATE_float if bytes.len() == 8 => f64::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        gimli::DW_ATE_UTF => match char::from_u32(read_unsigned(bytes) as u32) {
+            Some(character) => format!("{:?}", character),
+            None => format_raw(bytes),
+        },
+        _ => format_raw(bytes),
+    }
+}
+
+/// Format a Rust `&str` (a `data_ptr` and a `length`) as a quoted string.
+fn format_str(members: &[Member], bytes: &[u8], memory: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>) -> String {
+    let field = |name: &str| {
+        let member = members.iter().find(|member| member.name == name)?;
+        let start = member.offset as usize;
+        Some(read_unsigned(bytes.get(start..start + member.ty.size as usize)?) as u64)
+    };
+    let (Some(pointer), Some(length)) = (field("data_ptr"), field("length")) else {
+        return format_raw(bytes);
+    };
+    let len = (length as usize).min(MAX_STRING_LEN);
+    match memory(pointer, len) {
+        Some(text) => {
+            let text = format!("{:?}", String::from_utf8_lossy(&text));
+            if len < length as usize { format!("{}...", text) } else { text }
+        }
+        None => format!("<cannot access memory at address {:#x}>", pointer),
+    }
+}
+
+/// Format a value without a type: an unsigned integer in decimal and hexadecimal if it has the
+/// size of one, else its bytes.
+pub fn format_untyped(bytes: &[u8]) -> String {
+    match bytes.len() {
+        1 | 2 | 4 | 8 => {
+            let value = read_unsigned(bytes);
+            format!("{} ({:#x})", value, value)
+        }
+        _ => format_raw(bytes),
+    }
+}
+
+fn format_raw(bytes: &[u8]) -> String {
+    if bytes.len() <= 8 {
+        return format!("{:#x}", read_unsigned(bytes));
+    }
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("<{}>", hex.join(" "))
+}
+
+fn read_unsigned(bytes: &[u8]) -> u128 {
+    let mut word = [0u8; 16];
+    let len = bytes.len().min(16);
+    word[..len].copy_from_slice(&bytes[..len]);
+    u128::from_le_bytes(word)
+}
+
+fn read_signed(bytes: &[u8]) -> i128 {
+    let bits = (bytes.len().min(16) * 8) as u32;
+    if bits == 0 {
+        return 0;
+    }
+    let shift = 128 - bits;
+    ((read_unsigned(bytes) << shift) as i128) >> shift
+}
+
+/// Offset below the thread pointer of the executable's TLS block.
+///
+/// On x86_64 (TLS variant II) the block of the executable is the first one allocated below the
+/// thread control block, rounded so that its initialization image keeps its alignment.
+///
+/// # Arguments
+///
+/// * `segment` - The `PT_TLS` segment of the executable.
+#[cfg(target_arch = "x86_64")]
+pub fn static_tls_offset(segment: &TlsSegment) -> u64 {
+    let first_byte = segment.address.wrapping_neg() & (segment.align - 1);
+    (segment.size - first_byte).next_multiple_of(segment.align)
+}
+
+/// Start of the executable's thread-local storage block for the traced thread (`fs_base` minus
+/// the static TLS offset).
+///
+/// Blocks of shared libraries are reached through the dynamic thread vector instead and are not
+/// handled here, so their variables report that TLS resolution is not available.
+#[cfg(target_arch = "x86_64")]
+pub fn executable_tls_block(debugger: &mut Debugger) -> Option<u64> {
+    let segment = debugger.executable()?.tls_segment?;
+    let fs_base = debugger.tracee().getregs().ok()?.fs_base;
+    Some(fs_base.wrapping_sub(static_tls_offset(&segment)))
+}
+
+/// Start of the executable's thread-local storage block for the traced thread on aarch64: after
+/// the 16 bytes of the thread control block the thread pointer points to, aligned as the
+/// `PT_TLS` segment.
+///
+/// The thread pointer is read from the live thread, so core files report that TLS resolution is
+/// not available.
+#[cfg(target_arch = "aarch64")]
+pub fn executable_tls_block(debugger: &mut Debugger) -> Option<u64> {
+    let segment = debugger.executable()?.tls_segment?;
+    let thread_pointer = crate::arch::thread_pointer(debugger.thread).ok()?;
+    let first_byte = segment.address.wrapping_neg() & (segment.align - 1);
+    Some(thread_pointer + 16u64.saturating_sub(first_byte).next_multiple_of(segment.align) + first_byte)
+}
+
+/// Find the function scope of the selected frame and the context to evaluate its variables.
+pub fn selected_scope(debugger: &mut Debugger) -> Result<(FunctionScope, FrameContext), VariableError> {
+    let frame = debugger.selected_frame().ok_or(VariableError::NoFrame)?;
+    let lookup = if debugger.selected_frame == 0 { frame.pc } else { frame.pc - 1 };
+    let bias = debugger.executable_bias().ok_or(VariableError::NoDebugInfo)?;
+    let debug_info = debugger.debug_info.as_ref().ok_or(VariableError::NoDebugInfo)?;
+    let scope = function_scope(debug_info, lookup.wrapping_sub(bias))?.ok_or(VariableError::NoFunction)?;
+    let cfa = debugger.frame_cfa(&frame.registers, lookup);
+    let tls_block = executable_tls_block(debugger);
+    Ok((scope, FrameContext { tracee: debugger.tracee(), registers: frame.registers, cfa, bias, tls_block }))
+}
+
+/// Print the local variables of the selected frame (`info locals`).
+pub fn print_locals(debugger: &mut Debugger) {
+    let (scope, context) = match selected_scope(debugger) {
+        Ok(scope) => scope,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return;
+        }
+    };
+    let frame_base = scope.frame_base(&context);
+    let locals: Vec<&Variable> = scope.variables.iter().filter(|variable| !variable.is_parameter).collect();
+    if locals.is_empty() {
+        outln!(debugger, "No locals.");
+    }
+    for variable in locals {
+        outln!(debugger, "{} = {}", variable.name, format_variable(variable, frame_base, &context, FormatLimits::DEFAULT));
+    }
+}
+
+/// The context to evaluate the locations of the statics of the executable in: the registers of
+/// the current thread, and its thread-local storage block.
+///
+/// # Returns
+///
+/// None if the load address of the executable is not known.
+pub fn globals_context(debugger: &mut Debugger) -> Option<FrameContext> {
+    let bias = debugger.executable_bias()?;
+    let tracee = debugger.tracee();
+    let registers = match tracee.getregs() {
+        Ok(regs) => UnwindRegisters::from_regs(&regs),
+        Err(_) => UnwindRegisters([None; unwind::REGISTER_COUNT]),
+    };
+    let tls_block = executable_tls_block(debugger);
+    Some(FrameContext { tracee, registers, cfa: None, bias, tls_block })
+}
+
+/// Print a variable (`print <name>`): a local or parameter of the selected frame, else a static
+/// described by the DWARF information, else a data symbol read using its size.
+///
+/// # Arguments
+///
+/// * `limits` - The levels of the value shown, more with `print/full`.
+pub fn print_variable(debugger: &mut Debugger, name: &str, limits: FormatLimits) {
+    match variable_value(debugger, name, limits) {
+        Ok((name, value)) => outln!(debugger, "{} = {}", name, value),
+        Err(VariableError::Ambiguous { name, candidates }) => print_ambiguous(debugger, &name, &candidates),
+        Err(err) => errln!(debugger, "{}", err),
+    }
+}
+
+/// Find and format a variable as `print` shows it.
+///
+/// # Returns
+///
+/// The name of the variable, qualified for a static, and its value, or the error reading it in
+/// its place.
+///
+/// # Errors
+///
+/// Returns an error if no variable has the name, several statics or symbols have it, or the
+/// object of its symbol is not loaded yet.
+pub fn variable_value(debugger: &mut Debugger, name: &str, limits: FormatLimits) -> Result<(String, String), VariableError> {
+    if let Ok((scope, context)) = selected_scope(debugger) {
+        if let Some(variable) = scope.find(name) {
+            return Ok((name.to_string(), format_variable(variable, scope.frame_base(&context), &context, limits)));
+        }
+    }
+    let tracee = debugger.tracee();
+    let context = globals_context(debugger);
+    if let (Some(debug_info), Some(context)) = (debugger.debug_info.as_ref(), context) {
+        match find_globals(debug_info, name) {
+            Ok(globals) if globals.len() > 1 => {
+                let candidates = globals.iter().map(|(qualified, _)| qualified.clone()).collect();
+                return Err(VariableError::Ambiguous { name: name.to_string(), candidates });
+            }
+            Ok(globals) if globals.len() == 1 => {
+                return Ok((globals[0].0.clone(), format_variable(&globals[0].1, None, &context, limits)));
+            }
+            Ok(_) => {}
+            Err(err) => errln!(debugger, "DWARF error: {}", err),
+        }
+    }
+    let symbols = debugger.symbols.find_data(name);
+    match symbols[..] {
+        [] => Err(VariableError::NoSymbol(name.to_string())),
+        [(object, symbol)] => {
+            let Some(bias) = object.bias else {
+                return Err(VariableError::NotLoaded(object.path.clone()));
+            };
+            let address = symbol.address.wrapping_add(bias);
+            let len = (symbol.size as usize).clamp(1, MAX_SYMBOL_BYTES);
+            match read_bytes(tracee.as_ref(), address, len) {
+                Ok(bytes) => Ok((symbol.demangled.clone(), format_untyped(&bytes))),
+                Err(_) => Ok((symbol.demangled.clone(), VariableError::Memory(address).to_string())),
+            }
+        }
+        _ => {
+            let candidates = symbols.iter().map(|(_, symbol)| symbol.demangled.clone()).collect();
+            Err(VariableError::Ambiguous { name: name.to_string(), candidates })
+        }
+    }
+}
+
+fn print_ambiguous(debugger: &mut Debugger, name: &str, candidates: &[String]) {
+    errln!(debugger, "\"{}\" is ambiguous, use one of:", name);
+    for candidate in candidates {
+        outln!(debugger, "  {}", candidate);
+    }
+}
+
+/// Returns the arguments of the selected frame's function as `(name, value)` pairs.
+///
+/// The DWARF formal parameters are used when available. Otherwise the registers of the System V
+/// calling convention are shown as `arg1`..`arg6`, which is only meaningful on the first
+/// instruction of a function.
+pub fn frame_arguments(debugger: &mut Debugger) -> Result<Vec<(String, String)>, VariableError> {
+    if debugger.arch.is_i386() {
+        return cdecl_arguments(debugger);
+    }
+    match selected_scope(debugger) {
+        Ok((scope, context)) => {
+            let frame_base = scope.frame_base(&context);
+            Ok(scope
+                .variables
+                .iter()
+                .filter(|variable| variable.is_parameter)
+                .map(|variable| (variable.name.clone(), format_variable(variable, frame_base, &context, FormatLimits::DEFAULT)))
+                .collect())
+        }
+        Err(VariableError::NoDebugInfo | VariableError::NoFunction) => {
+            let frame = debugger.selected_frame().ok_or(VariableError::NoFrame)?;
+            let mappings = debugger.mappings.clone();
+            let tracee = debugger.tracee();
+            Ok(ARGUMENT_REGISTERS
+                .iter()
+                .enumerate()
+                .map(|(index, (register, _))| {
+                    let value = match frame.registers.0[*register] {
+                        Some(value) => format!("{:#x}{}", value, annotate_pointer(tracee.as_ref(), &mappings, value)),
+                        None => "<not available>".to_string(),
+                    };
+                    (format!("arg{}", index + 1), value)
+                })
+                .collect())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The arguments of the selected frame of a 32-bit program, the words on the stack above its
+/// return address (cdecl), as many as the registers of the arguments on x86_64. They are named by
+/// their position: the locations of the debug information number the registers of i386.
+fn cdecl_arguments(debugger: &mut Debugger) -> Result<Vec<(String, String)>, VariableError> {
+    let target = debugger.selected_frame;
+    let frames = backtrace::backtrace(debugger, target + 2).map_err(|_| VariableError::NoFrame)?;
+    // The stack pointer of the caller, once returned, is just above the return address.
+    let arguments = frames.get(target + 1).ok_or(VariableError::NoFrame)?.sp;
+    let mappings = debugger.mappings.clone();
+    let tracee = debugger.tracee();
+    Ok((0..ARGUMENT_REGISTERS.len() as u64)
+        .map(|index| {
+            let value = match debugger.arch.read_pointer(tracee.as_ref(), arguments + index * 4) {
+                Ok(value) => format!("{:#x}{}", value, annotate_pointer(tracee.as_ref(), &mappings, value)),
+                Err(_) => "<not available>".to_string(),
+            };
+            (format!("arg{}", index + 1), value)
+        })
+        .collect())
+}
+
+/// Describe what a register value points to: the string it points to, or the mapping containing it.
+fn annotate_pointer(tracee: &dyn Tracee, mappings: &[MapEntry], value: u64) -> String {
+    let Some(mapping) = maps::find_mapping(mappings, value) else {
+        return String::new();
+    };
+    match read_c_string(tracee, value, 64) {
+        Ok(text) if !text.is_empty() && text.chars().all(is_printable) => format!(" {:?}", text),
+        _ => format!(" <{}>", mapping.pathname.as_deref().unwrap_or("anonymous")),
+    }
+}
+
+/// Returns true if a character of a string read from the child can be shown as is.
+pub fn is_printable(character: char) -> bool {
+    !character.is_control() && character != char::REPLACEMENT_CHARACTER
+}
+
+/// Print the arguments of the selected frame (`info args`).
+pub fn print_arguments(debugger: &mut Debugger) {
+    match frame_arguments(debugger) {
+        Ok(arguments) if arguments.is_empty() => outln!(debugger, "No arguments."),
+        Ok(arguments) => {
+            for (name, value) in arguments {
+                outln!(debugger, "{} = {}", name, value);
+            }
+        }
+        Err(err) => errln!(debugger, "{}", err),
+    }
+}
+
+/// When the child stopped on a breakpoint set on a function name, print the function and its
+/// arguments.
+pub fn report_function_breakpoint(debugger: &mut Debugger, address: u64) {
+    let Some(function) = debugger.function_breakpoints.remove(&address) else {
+        return;
+    };
+    match frame_arguments(debugger) {
+        Ok(arguments) => {
+            let arguments: Vec<String> = arguments.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            outln!(debugger, "{} ({})", function, arguments.join(", "));
+        }
+        Err(err) => outln!(debugger, "{}: {}", function, err),
+    }
+}
@@ -0,0 +1,440 @@
+use crate::arch::Registers;
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::json;
+use crate::memory::read_bytes;
+use crate::step::StepStop;
+use crate::swatch::{self, WatchStop};
+use crate::symbols::path_matches;
+use crate::threads::{self, ThreadState};
+use crate::variables::{self, DwarfType};
+use crate::working::{is_breakpoint, trap_address};
+#[cfg(target_arch = "x86_64")]
+use nix::libc;
+#[cfg(target_arch = "x86_64")]
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use serde_json::json;
+
+/// The offset of the debug registers in the `user` area of a thread, read and written with
+/// `PTRACE_PEEKUSER` and `PTRACE_POKEUSER`.
+#[cfg(target_arch = "x86_64")]
+const DEBUG_REGISTERS: usize = std::mem::offset_of!(libc::user, u_debugreg);
+/// The number of hardware watchpoints, the address registers DR0 to DR3.
+#[cfg(target_arch = "x86_64")]
+pub const HARDWARE_SLOTS: usize = 4;
+/// No hardware watchpoints on aarch64, whose debug registers are not used: `watch` sets software
+/// watchpoints.
+#[cfg(target_arch = "aarch64")]
+pub const HARDWARE_SLOTS: usize = 0;
+/// The debug status register, whose low bits tell which address registers triggered.
+const DR6: usize = 6;
+/// The debug control register, enabling each address register for a length and an access.
+const DR7: usize = 7;
+
+/// What a watchpoint stops the program on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// A write changing the value (`watch`).
+    Write,
+    /// A read or a write (`awatch`), hardware watchpoints only.
+    Access,
+}
+
+/// A variable watched by `watch` or `awatch`.
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    /// The name of the variable, its full path for a static of the DWARF information.
+    pub name: String,
+    pub address: u64,
+    /// The size of the variable, the bytes watched.
+    pub len: usize,
+    /// The type of the variable, to show its values, if the DWARF information describes it.
+    pub ty: Option<DwarfType>,
+    pub kind: WatchKind,
+    /// The debug register watching it, DR0 to DR3, or None for a software watchpoint, which
+    /// single-steps the program.
+    pub slot: Option<usize>,
+    /// The value at the last stop.
+    pub value: Vec<u8>,
+    /// The value before the change reported at the last stop.
+    pub old: Option<Vec<u8>>,
+}
+
+/// The watchpoints of the session.
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    pub list: Vec<Watchpoint>,
+    /// The address of the watchpoint that stopped the program, until the stop is reported.
+    hit: Option<u64>,
+}
+
+impl Watchpoints {
+    /// The watchpoint on the variable at `address`.
+    pub fn find(&self, address: u64) -> Option<&Watchpoint> {
+        self.list.iter().find(|watchpoint| watchpoint.address == address)
+    }
+}
+
+/// The bits of DR7 giving the length of a hardware watchpoint, for the lengths the debug registers
+/// can watch: 1, 2, 4 or 8 bytes.
+fn length_bits(len: usize) -> Option<u64> {
+    match len {
+        1 => Some(0b00),
+        2 => Some(0b01),
+        8 => Some(0b10),
+        4 => Some(0b11),
+        _ => None,
+    }
+}
+
+/// The bits of DR7 enabling the address register `slot` for `len` bytes and an access of `kind`.
+pub fn control_bits(slot: usize, len: usize, kind: WatchKind) -> u64 {
+    let access = match kind {
+        WatchKind::Write => 0b01,
+        WatchKind::Access => 0b11,
+    };
+    let length = length_bits(len).unwrap_or_default();
+    (1 << (2 * slot)) | (access << (16 + 4 * slot)) | (length << (18 + 4 * slot))
+}
+
+#[cfg(target_arch = "x86_64")]
+fn write_debug_register(tid: Pid, index: usize, value: u64) -> Result<(), DbgError> {
+    let offset = (DEBUG_REGISTERS + index * 8) as ptrace::AddressType;
+    unsafe { ptrace::write_user(tid, offset, value as *mut libc::c_void) }.map_err(DbgError::ptrace("write the debug registers"))
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_debug_register(tid: Pid, index: usize) -> Result<u64, DbgError> {
+    let offset = (DEBUG_REGISTERS + index * 8) as ptrace::AddressType;
+    ptrace::read_user(tid, offset).map(|value| value as u64).map_err(DbgError::ptrace("read the debug registers"))
+}
+
+#[cfg(target_arch = "aarch64")]
+fn write_debug_register(_tid: Pid, _index: usize, _value: u64) -> Result<(), DbgError> {
+    Err(DbgError::X86Only("The debug registers"))
+}
+
+#[cfg(target_arch = "aarch64")]
+fn read_debug_register(_tid: Pid, _index: usize) -> Result<u64, DbgError> {
+    Err(DbgError::X86Only("The debug registers"))
+}
+
+/// Write the hardware watchpoints to the debug registers of every stopped thread, as the threads
+/// created don't inherit them. DR7 is written first, so that no address register is enabled with
+/// a stale address.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the debug registers of a thread can't be written.
+pub fn arm(debugger: &Debugger) -> Result<(), DbgError> {
+    let hardware: Vec<&Watchpoint> = debugger.watchpoints.list.iter().filter(|watchpoint| watchpoint.slot.is_some()).collect();
+    let control = hardware.iter().fold(0, |control, watchpoint| control | control_bits(watchpoint.slot.unwrap_or_default(), watchpoint.len, watchpoint.kind));
+    for thread in debugger.threads.iter().filter(|thread| thread.state == ThreadState::Stopped) {
+        write_debug_register(thread.tid, DR7, 0)?;
+        for watchpoint in &hardware {
+            write_debug_register(thread.tid, watchpoint.slot.unwrap_or_default(), watchpoint.address)?;
+        }
+        write_debug_register(thread.tid, DR7, control)?;
+    }
+    Ok(())
+}
+
+/// Find the address, size and type of a static variable: from the DWARF information, else from
+/// the data symbols.
+///
+/// # Returns
+///
+/// Its full name, address, size and type.
+///
+/// # Errors
+///
+/// Returns a message if no variable or several have the name, or it is not in memory.
+pub fn resolve(debugger: &mut Debugger, name: &str) -> Result<(String, u64, usize, Option<DwarfType>), String> {
+    let context = variables::globals_context(debugger);
+    if let (Some(debug_info), Some(context)) = (debugger.debug_info.as_ref(), context) {
+        let globals = variables::find_globals(debug_info, name).map_err(|err| format!("DWARF error: {}", err))?;
+        match &globals[..] {
+            [] => {}
+            [(qualified, variable)] => {
+                let address = variables::variable_address(variable, &context).map_err(|err| format!("{} can't be watched: {}", qualified, err))?;
+                let len = variable.ty.as_ref().map_or(0, |ty| ty.size as usize);
+                return Ok((qualified.clone(), address, len, variable.ty.clone()));
+            }
+            _ => {
+                let candidates: Vec<&str> = globals.iter().map(|(qualified, _)| qualified.as_str()).collect();
+                return Err(format!("\"{}\" is ambiguous, use one of: {}", name, candidates.join(", ")));
+            }
+        }
+    }
+    match debugger.symbols.find_data(name)[..] {
+        [] => Err(format!("No global variable \"{}\".", name)),
+        [(object, symbol)] => match object.bias {
+            Some(bias) => Ok((symbol.demangled.clone(), symbol.address.wrapping_add(bias), symbol.size as usize, None)),
+            None => Err(format!("The load address of {} is not known yet.", object.path)),
+        },
+        ref symbols => {
+            let candidates: Vec<&str> = symbols.iter().map(|(_, symbol)| symbol.demangled.as_str()).collect();
+            Err(format!("\"{}\" is ambiguous, use one of: {}", name, candidates.join(", ")))
+        }
+    }
+}
+
+/// Format a value of a watched variable, with its type if known.
+pub fn format_value(debugger: &Debugger, watchpoint: &Watchpoint, bytes: &[u8]) -> String {
+    let tracee = debugger.tracee();
+    match &watchpoint.ty {
+        Some(ty) => variables::format_value(ty, bytes, variables::FormatLimits::DEFAULT, &mut |address, len| read_bytes(tracee.as_ref(), address, len).ok()),
+        None => variables::format_untyped(bytes),
+    }
+}
+
+/// Watch a static variable by name (`watch`, `awatch`): with a debug register if it has 1, 2, 4 or
+/// 8 bytes aligned on its size and one is free, else, for writes, by single-stepping the program
+/// when it continues, such a software watchpoint being the only watchpoint.
+///
+/// # Returns
+///
+/// False if the variable is not found, already watched, or can't be watched.
+pub fn watch(debugger: &mut Debugger, name: &str, kind: WatchKind) -> bool {
+    let (name, address, len, ty) = match resolve(debugger, name) {
+        Ok(variable) => variable,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    if len == 0 {
+        errln!(debugger, "The size of {} is not known.", name);
+        return false;
+    }
+    if kind == WatchKind::Access {
+        if let Err(err) = crate::arch::require_x86("awatch") {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    if debugger.watchpoints.find(address).is_some() {
+        errln!(debugger, "{} is already watched.", name);
+        return false;
+    }
+    // The single-stepping of a software watchpoint does not check the debug registers, so it
+    // watches alone.
+    if debugger.watchpoints.list.iter().any(|watchpoint| watchpoint.slot.is_none()) {
+        errln!(debugger, "A software watchpoint is set, which watches alone: unwatch it first.");
+        return false;
+    }
+    let used: Vec<usize> = debugger.watchpoints.list.iter().filter_map(|watchpoint| watchpoint.slot).collect();
+    let free = (0..HARDWARE_SLOTS).find(|slot| !used.contains(slot));
+    let slot = match free {
+        Some(slot) if length_bits(len).is_some() && address % len as u64 == 0 => Some(slot),
+        _ if kind == WatchKind::Access => {
+            errln!(debugger, "{} can't be watched for reads: it needs a free debug register, and 1, 2, 4 or 8 bytes aligned on their size.", name);
+            return false;
+        }
+        _ if !used.is_empty() => {
+            errln!(debugger, "{} needs a software watchpoint, which watches alone: unwatch the other variables first.", name);
+            return false;
+        }
+        _ => None,
+    };
+    let value = match debugger.read_memory(address, len) {
+        Ok(value) => value,
+        Err(err) => {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    };
+    let watchpoint = Watchpoint { name, address, len, ty, kind, slot, value, old: None };
+    let shown = format_value(debugger, &watchpoint, &watchpoint.value);
+    let type_name = watchpoint.ty.as_ref().map(|ty| ty.name.clone()).filter(|name| !name.is_empty());
+    json::set_data(debugger, |_| {
+        json!({
+            "name": watchpoint.name,
+            "address": json::address(address),
+            "len": len,
+            "type": type_name,
+            "kind": if kind == WatchKind::Write { "write" } else { "access" },
+            "hardware": slot.is_some(),
+            "value": shown,
+        })
+    });
+    let description = format!("{} ({} bytes at {:#x}), value = {}", watchpoint.name, len, address, shown);
+    debugger.watchpoints.list.push(watchpoint);
+    match slot {
+        Some(slot) => {
+            if let Err(err) = arm(debugger) {
+                errln!(debugger, "{}", err);
+                debugger.watchpoints.list.pop();
+                return false;
+            }
+            let what = if kind == WatchKind::Write { "Hardware watchpoint" } else { "Hardware access watchpoint" };
+            outln!(debugger, "{} (DR{}): {}", what, slot, description);
+        }
+        None => outln!(debugger, "Software watchpoint: {}. c single-steps the program to catch its changes, very slowly.", description),
+    }
+    true
+}
+
+/// Stop watching a variable (`unwatch`), by its name as given to `watch` or its full name.
+///
+/// # Returns
+///
+/// False if the variable is not watched.
+pub fn unwatch(debugger: &mut Debugger, name: &str) -> bool {
+    let Some(index) = debugger.watchpoints.list.iter().position(|watchpoint| path_matches(&watchpoint.name, name)) else {
+        errln!(debugger, "No watchpoint on \"{}\".", name);
+        return false;
+    };
+    let watchpoint = debugger.watchpoints.list.remove(index);
+    if watchpoint.slot.is_some() && debugger.state.is_alive() {
+        if let Err(err) = arm(debugger) {
+            errln!(debugger, "{}", err);
+            return false;
+        }
+    }
+    outln!(debugger, "Deleted the watchpoint on {}.", watchpoint.name);
+    true
+}
+
+/// List the watchpoints with their values (`watch` alone).
+pub fn list(debugger: &mut Debugger) {
+    let watchpoints = debugger.watchpoints.list.clone();
+    if watchpoints.is_empty() {
+        outln!(debugger, "No watchpoints.");
+    }
+    let mut records = Vec::new();
+    for watchpoint in &watchpoints {
+        let how = match (watchpoint.slot, watchpoint.kind) {
+            (Some(slot), WatchKind::Write) => format!("hardware, DR{}", slot),
+            (Some(slot), WatchKind::Access) => format!("hardware access, DR{}", slot),
+            (None, _) => "software".to_string(),
+        };
+        let value = format_value(debugger, watchpoint, &watchpoint.value);
+        outln!(debugger, "{}: {} bytes at {:#x} ({}), value = {}", watchpoint.name, watchpoint.len, watchpoint.address, how, value);
+        records.push(json!({ "name": watchpoint.name, "address": json::address(watchpoint.address), "len": watchpoint.len, "hardware": watchpoint.slot.is_some(), "value": value }));
+    }
+    json::set_data(debugger, |_| json!({ "watchpoints": records }));
+}
+
+/// Tell whether a thread stopped by a `SIGTRAP` while the program continues was stopped by a
+/// hardware watchpoint, from DR6. A write leaving the value of the variable as it was is not a
+/// change: the thread is resumed, and the wait for the program goes on.
+///
+/// # Returns
+///
+/// None if the thread was resumed, else `status`, the watchpoint hit being kept for
+/// [`take_hit`].
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the debug registers of the thread can't be accessed, or it
+/// can't be resumed.
+pub fn hardware_hit(debugger: &mut Debugger, tid: Pid, status: WaitStatus) -> Result<Option<WaitStatus>, DbgError> {
+    if status != WaitStatus::Stopped(tid, Signal::SIGTRAP) || debugger.watchpoints.list.iter().all(|watchpoint| watchpoint.slot.is_none()) {
+        return Ok(Some(status));
+    }
+    let triggered = read_debug_register(tid, DR6)? & 0xf;
+    if triggered == 0 {
+        return Ok(Some(status));
+    }
+    write_debug_register(tid, DR6, 0)?;
+    // A breakpoint hit at once wins.
+    let pc = crate::arch::get_registers(tid).map_err(DbgError::ptrace("read the registers"))?.pc();
+    if is_breakpoint(&debugger.breakpoint_table, trap_address(pc)) {
+        return Ok(Some(status));
+    }
+    for index in 0..debugger.watchpoints.list.len() {
+        let watchpoint = &debugger.watchpoints.list[index];
+        if watchpoint.slot.is_none_or(|slot| triggered & (1 << slot) == 0) {
+            continue;
+        }
+        let value = read_bytes(&tid, watchpoint.address, watchpoint.len)?;
+        if watchpoint.kind == WatchKind::Write && value == watchpoint.value {
+            continue;
+        }
+        let watchpoint = &mut debugger.watchpoints.list[index];
+        watchpoint.old = Some(std::mem::replace(&mut watchpoint.value, value));
+        debugger.watchpoints.hit = Some(watchpoint.address);
+        return Ok(Some(status));
+    }
+    crate::antidebug::resume(debugger.anti_debug.enabled, tid, None)?;
+    threads::set_state(debugger, tid, ThreadState::Running);
+    Ok(None)
+}
+
+/// Take the watchpoint hit found by [`hardware_hit`] as the stop of the program, once it is
+/// stopped. Its banner describes the change, with [`describe_hit`].
+///
+/// # Returns
+///
+/// The stop, or None if no watchpoint was hit.
+pub fn take_hit(debugger: &mut Debugger) -> Option<StepStop> {
+    if let Some(stop) = crate::pagewatch::take_hit(debugger) {
+        return Some(stop);
+    }
+    let address = debugger.watchpoints.hit.take()?;
+    debugger.watchpoints.find(address).map(|_| StepStop::Watchpoint(address))
+}
+
+/// Describe the hit of a watchpoint, the banner of its stop, as in
+/// `Hardware watchpoint total: value changed 3 -> 4` or
+/// `Hardware access watchpoint counter: value = 7`.
+pub fn describe_hit(debugger: &Debugger, watchpoint: &Watchpoint) -> String {
+    let what = match (watchpoint.slot, watchpoint.kind) {
+        (None, _) => "Software watchpoint",
+        (Some(_), WatchKind::Write) => "Hardware watchpoint",
+        (Some(_), WatchKind::Access) => "Hardware access watchpoint",
+    };
+    let new = format_value(debugger, watchpoint, &watchpoint.value);
+    match &watchpoint.old {
+        Some(old) if *old != watchpoint.value => format!("{} {}: value changed {} -> {}", what, watchpoint.name, format_value(debugger, watchpoint, old), new),
+        _ => format!("{} {}: value = {}", what, watchpoint.name, new),
+    }
+}
+
+/// Continue the current thread by single-stepping it until the variable of the software
+/// watchpoint changes, as `c` does while there is one.
+///
+/// # Returns
+///
+/// None if there is no software watchpoint.
+///
+/// # Errors
+///
+/// Returns the error of [`swatch::watch`].
+pub fn continue_software(debugger: &mut Debugger) -> Result<Option<StepStop>, DbgError> {
+    let Some(index) = debugger.watchpoints.list.iter().position(|watchpoint| watchpoint.slot.is_none()) else {
+        return Ok(None);
+    };
+    let (address, len) = (debugger.watchpoints.list[index].address, debugger.watchpoints.list[index].len);
+    warnln!(debugger, "Single-stepping the program for the software watchpoint, which is very slow: press Ctrl-C to interrupt.");
+    let (stop, _) = swatch::watch(debugger, address, len, &[])?;
+    Ok(Some(match stop {
+        WatchStop::Changed { old, new, .. } => {
+            let watchpoint = &mut debugger.watchpoints.list[index];
+            watchpoint.old = Some(old);
+            watchpoint.value = new;
+            StepStop::Watchpoint(address)
+        }
+        WatchStop::Interrupted => {
+            outln!(debugger, "Interrupted, {} unchanged.", debugger.watchpoints.list[index].name);
+            StepStop::Done
+        }
+        WatchStop::Stopped(stop) => stop,
+    }))
+}
+
+/// Watch again the variables watched in the previous process, at their addresses in the new one
+/// (`restart`).
+///
+/// # Returns
+///
+/// False if one of them can't be watched anymore.
+pub fn rewatch(debugger: &mut Debugger) -> bool {
+    let watched: Vec<(String, WatchKind)> = debugger.watchpoints.list.drain(..).map(|watchpoint| (watchpoint.name, watchpoint.kind)).collect();
+    debugger.watchpoints.hit = None;
+    watched.iter().fold(true, |succeeded, (name, kind)| watch(debugger, name, *kind) && succeeded)
+}
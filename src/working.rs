@@ -1,152 +1,400 @@
+use crate::arch::{Registers, BREAKPOINT_INSTRUCTION, BREAKPOINT_LENGTH, BREAKPOINT_REWIND};
+use crate::commands::{lookup_command, print_ambiguous_command, suggestions, CommandMatch, COMMANDS};
+use crate::debugger::Debugger;
+use crate::error::DbgError;
+use crate::ltrace;
+use crate::output::Style;
+use crate::step::StepStop;
+use crate::threads;
+use crate::tracee::Tracee;
 use nix::sys::ptrace;
-use nix::unistd;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
 use std::collections::HashMap;
 
-static mut BREAKPOINTS: Option<HashMap<u64, u8>> = None;
+/// The armed breakpoints of a process, with the original bytes their instruction replaced. Kept
+/// in [`Debugger::breakpoint_table`].
+#[derive(Debug, Default, Clone)]
+pub struct BreakpointTable(HashMap<u64, [u8; BREAKPOINT_LENGTH]>);
 
+/// The `word` read at the address of a breakpoint, with its first bytes replaced by `bytes`: the
+/// breakpoint instruction to arm it, the original bytes to disarm it.
+pub fn patch_word(word: i64, bytes: &[u8; BREAKPOINT_LENGTH]) -> i64 {
+    let mut word = word.to_le_bytes();
+    word[..BREAKPOINT_LENGTH].copy_from_slice(bytes);
+    i64::from_le_bytes(word)
+}
+
+/// The first bytes of `word`, that a breakpoint replaces.
+fn original_bytes(word: i64) -> [u8; BREAKPOINT_LENGTH] {
+    word.to_le_bytes()[..BREAKPOINT_LENGTH].try_into().unwrap()
+}
+
+/// The address of the breakpoint a thread trapped on, from its program counter `pc`.
+pub fn trap_address(pc: u64) -> u64 {
+    pc.wrapping_sub(BREAKPOINT_REWIND)
+}
 
 
-/// Set a breakpoint at the specified memory address in the debugged process.
+/// Set a breakpoint at the specified memory address in the debugged process. A breakpoint
+/// already armed at `address` is left as is, keeping its original bytes.
 ///
 /// # Arguments
 ///
-/// * `child` - The process ID (Pid) of the child being debugged.
+/// * `breakpoints` - The breakpoints armed in the child.
+/// * `tracee` - The child being debugged.
 /// * `address` - The memory address where the breakpoint is to be set.
 ///
 /// # Errors
 ///
-/// Returns an error if setting the breakpoint fails.
-///
-/// # Safety
+/// Returns [`DbgError::Memory`] if the instruction at `address` can't be read or written.
 ///
-/// This function involves modifying the debugged process's memory and relies on unsafe operations.
-///
-pub fn set_breakpoint(child: unistd::Pid, address: u64) -> Result<(), nix::Error> {
-    let original_byte = ptrace::read(child, address as nix::sys::ptrace::AddressType)?;
-
-    unsafe {
-        if let Some(ref mut breakpoints) = BREAKPOINTS {
-            breakpoints.insert(address, original_byte as u8);
-        } else {
-            let mut breakpoints = HashMap::<u64, u8>::new();
-            breakpoints.insert(address, original_byte as u8);
-            BREAKPOINTS = Some(breakpoints);
-        }
+pub fn set_breakpoint(breakpoints: &mut BreakpointTable, mut tracee: impl Tracee, address: u64) -> Result<(), DbgError> {
+    if is_breakpoint(breakpoints, address) {
+        // The bytes there are the breakpoint instruction, not the original ones.
+        return Ok(());
     }
-
-    let word_to_write = (original_byte & !0xff) | 0xcc;
-    unsafe { ptrace::write(child, address as nix::sys::ptrace::AddressType, word_to_write as nix::sys::ptrace::AddressType) }?;
+    let original_word = tracee.read_word(address).map_err(DbgError::memory(address))?;
+    let original = original_bytes(original_word);
+    let word_to_write = patch_word(original_word, &BREAKPOINT_INSTRUCTION);
+    tracee.write_word(address, word_to_write).map_err(DbgError::memory(address))?;
+    breakpoints.0.insert(address, original);
+    log::debug!("Breakpoint armed at {:#x}, original bytes {:02x?}", address, original);
 
     Ok(())
 }
 
 
-/// Handle a breakpoint hit at the specified address in the debugged process.
+/// Handle a breakpoint hit at the specified address in the debugged process, removing it. The
+/// banner of the stop reports it.
 ///
 /// # Arguments
 ///
-/// * `child` - The process ID (Pid) of the child being debugged.
+/// * `debugger` - The state of the debugging session.
 /// * `address` - The memory address where the breakpoint was hit.
 ///
-pub fn handle_breakpoint(child: unistd::Pid, address: u64) {
-    unsafe {
-        if let Some(ref mut breakpoints) = BREAKPOINTS {
-            if let Some(&original_byte) = breakpoints.get(&address) {
-                let mut original_instruction = ptrace::read(child, address as nix::sys::ptrace::AddressType)
-                    .expect("Failed to read original instruction");
-                // Restaurer l'instruction d'origine à l'adresse du breakpoint
-                // En remplaçant uniquement le dernier octet par l'octet original
-                original_instruction &= !0xff;
-                original_instruction |= original_byte as i64;
-
-                // Écrire l'instruction restaurée dans la mémoire du processus enfant
-                ptrace::write(child, address as nix::sys::ptrace::AddressType, original_instruction as nix::sys::ptrace::AddressType)
-                    .expect("Failed to restore original instruction");
-
-                println!("Hit breakpoint at address {:#x}", address);
-                return;
+/// # Returns
+///
+/// True if `address` was a breakpoint set by the user.
+///
+pub fn handle_breakpoint(debugger: &mut Debugger, address: u64) -> bool {
+    remove_breakpoint(&mut debugger.breakpoint_table, debugger.thread, address).is_ok()
+}
+
+/// Remove the breakpoint at `address`, restoring the original instruction.
+///
+/// # Errors
+///
+/// Returns [`DbgError::NoSuchBreakpoint`] if no breakpoint is armed at `address`, or
+/// [`DbgError::Memory`] if the instruction can't be restored.
+pub fn remove_breakpoint(breakpoints: &mut BreakpointTable, mut tracee: impl Tracee, address: u64) -> Result<(), DbgError> {
+    let Some(original) = breakpoints.0.remove(&address) else {
+        return Err(DbgError::NoSuchBreakpoint(address));
+    };
+    log::debug!("Breakpoint disarmed at {:#x}, original bytes {:02x?}", address, original);
+    let word = tracee.read_word(address).map_err(DbgError::memory(address))?;
+    // Restaurer l'instruction d'origine à l'adresse du breakpoint
+    // En remplaçant uniquement les premiers octets par les octets originaux
+    let original_instruction = patch_word(word, &original);
+
+    // Écrire l'instruction restaurée dans la mémoire du processus enfant
+    tracee.write_word(address, original_instruction).map_err(DbgError::memory(address))
+}
+
+/// Forget every breakpoint, when the process they were set in is gone.
+pub fn clear_breakpoints(breakpoints: &mut BreakpointTable) {
+    breakpoints.0.clear();
+}
+
+/// The addresses of the breakpoints currently armed.
+pub fn armed_breakpoints(breakpoints: &BreakpointTable) -> Vec<u64> {
+    breakpoints.0.keys().copied().collect()
+}
+
+/// Returns true if a breakpoint is currently armed at `address`.
+pub fn is_breakpoint(breakpoints: &BreakpointTable, address: u64) -> bool {
+    breakpoints.0.contains_key(&address)
+}
+
+/// The byte of `bytes` (read from `address`) at `offset` past the breakpoint at `breakpoint`, if
+/// `bytes` covers it. A breakpoint may start before `address`.
+fn byte_at(bytes: &mut [u8], address: u64, breakpoint: u64, offset: usize) -> Option<&mut u8> {
+    (breakpoint + offset as u64).checked_sub(address).and_then(|index| bytes.get_mut(index as usize))
+}
+
+/// Replace the breakpoint instructions patched by the armed breakpoints in `bytes` (read from
+/// `address`) with the original bytes of the program.
+pub fn restore_original_bytes(breakpoints: &BreakpointTable, address: u64, bytes: &mut [u8]) {
+    for (&breakpoint, original) in &breakpoints.0 {
+        for (offset, original_byte) in original.iter().enumerate() {
+            if let Some(byte) = byte_at(bytes, address, breakpoint, offset) {
+                *byte = *original_byte;
             }
         }
     }
-    println!("Hit unknown breakpoint at address {:#x}", address);
 }
 
+/// Keep the armed breakpoints in `bytes`, about to be written at `address`: the bytes written
+/// over a breakpoint instruction become the original bytes of its breakpoint, and the instruction
+/// stays.
+pub fn keep_breakpoints(breakpoints: &mut BreakpointTable, address: u64, bytes: &mut [u8]) {
+    for (&breakpoint, original) in breakpoints.0.iter_mut() {
+        for (offset, original_byte) in original.iter_mut().enumerate() {
+            if let Some(byte) = byte_at(bytes, address, breakpoint, offset) {
+                *original_byte = *byte;
+                *byte = BREAKPOINT_INSTRUCTION[offset];
+            }
+        }
+    }
+}
 
 /// Handle process stopping events and print information when a SIGTRAP signal is received.
 ///
-/// This function continuously waits for the child process to stop and checks if it's due to a SIGTRAP signal,
+/// This function continuously waits for a thread of the child process to stop and checks if it's due to a SIGTRAP signal,
 /// indicating a breakpoint hit. When a SIGTRAP is detected, it prints information about it and then breaks
-/// out of the loop.
+/// out of the loop. A stop by another signal (a crash, ...) or the termination of the child is returned as is.
+/// The thread that stopped becomes the current thread, and the other threads are stopped too, unless in
+/// non-stop mode.
 ///
 /// # Arguments
 ///
-/// * `child` - The process ID (Pid) of the child being debugged.
+/// * `debugger` - The state of the debugging session.
 ///
-/// # Panics
+/// # Returns
 ///
-/// This function panics if it fails to get the register states of the child process.
-
-pub fn prettier(child: unistd::Pid) {
+/// The breakpoint hit, with its address, if the child stopped on a SIGTRAP, the signal it stopped with, or how it terminated.
+///
+/// # Errors
+///
+/// Returns [`DbgError::ProcessExited`] if the child is already gone, or [`DbgError::Ptrace`] if
+/// it can't be waited for or its registers can't be accessed.
+pub fn prettier(debugger: &mut Debugger) -> Result<StepStop, DbgError> {
     loop {
-        match nix::sys::wait::waitpid(child, None) {
-            Ok(status) => {
-                if status == nix::sys::wait::WaitStatus::Stopped(child, nix::sys::signal::Signal::SIGTRAP) {
-                    println!("SIGTRAP");
-                    let regs = ptrace::getregs(child).expect("Failed to get registers");
-                    let rip = regs.rip as u64;
-                    handle_breakpoint(child, rip - 1);
-                    break;
-                }
+        let (tid, status) = threads::wait_any(debugger)?;
+        let Some(status) = filter_event(debugger, tid, status)? else {
+            continue;
+        };
+        if let WaitStatus::Exited(..) | WaitStatus::Signaled(..) = status {
+            debugger.thread = debugger.child;
+        } else {
+            if tid != debugger.thread {
+                debugger.thread = tid;
+                debugger.shown_registers = None;
+                outln!(debugger, "[Switching to thread {} (tid {})]", threads::number(debugger, tid), tid);
+            }
+            // In non-stop mode, the other threads keep running.
+            if !debugger.non_stop {
+                threads::stop_others(debugger)?;
             }
-            Err(e) if nix::errno::Errno::from_raw(e as i32) == nix::errno::Errno::ECHILD => {
-                // The child process has already terminated.
-                println!("rustdbg> Child process has terminated.");
-                std::process::exit(0);
+            if let Some(stop) = crate::watch::take_hit(debugger) {
+                return Ok(stop);
             }
-            Err(e) => {
-                // Handle other errors if needed
-                eprintln!("Error: {}", e);
-                break;
+        }
+        let Some((stop, _)) = classify_stop(&mut debugger.breakpoint_table, tid, status)? else {
+            continue;
+        };
+        return Ok(stop);
+    }
+}
+
+/// Handle the stops of a thread that are not stops of the program, resuming the thread: the
+/// exec of a new program, the system calls checked, the blocks covered, the library calls traced,
+/// the writes leaving a watched variable unchanged, and the events of ptrace.
+///
+/// # Returns
+///
+/// The status if it is a stop of the program or its termination, `None` if the thread was
+/// resumed.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the thread can't be resumed, or the errors of the handlers.
+pub fn filter_event(debugger: &mut Debugger, tid: Pid, status: WaitStatus) -> Result<Option<WaitStatus>, DbgError> {
+    // The breakpoints are set again in the new program the program executed.
+    if crate::exec::is_exec(&status) {
+        crate::exec::follow_exec(debugger, debugger.child);
+        log::debug!("PTRACE_CONT {} after the exec", debugger.child);
+        crate::cache::invalidate();
+        ptrace::cont(debugger.child, None).map_err(DbgError::ptrace("continue execution"))?;
+        return Ok(None);
+    }
+    // The system calls are checked on the way with set anti-anti-debug on.
+    let Some(status) = crate::antidebug::syscall_stop(debugger, tid, status)? else {
+        return Ok(None);
+    };
+    // The blocks entered are recorded on the way by cover.
+    let Some(status) = crate::coverage::block_hit(debugger, tid, status)? else {
+        return Ok(None);
+    };
+    // The library calls traced by ltrace are printed on the way.
+    let Some(status) = ltrace::trace_call(debugger, tid, status)? else {
+        return Ok(None);
+    };
+    // The writes leaving a watched variable unchanged are skipped on the way.
+    let Some(status) = crate::watch::hardware_hit(debugger, tid, status)? else {
+        return Ok(None);
+    };
+    // The writes to the pages of a watched region outside of it are done on the way.
+    let Some(status) = crate::pagewatch::write_fault(debugger, tid, status)? else {
+        return Ok(None);
+    };
+    match status {
+        WaitStatus::Exited(..) | WaitStatus::Signaled(..) | WaitStatus::Stopped(..) => Ok(Some(status)),
+        // Not a stop of the program.
+        _ => {
+            log::debug!("PTRACE_CONT {} after {:?}", tid, status);
+            crate::cache::invalidate();
+            ptrace::cont(tid, None).map_err(DbgError::ptrace("continue execution"))?;
+            Ok(None)
+        }
+    }
+}
+
+/// Tell what a wait status of the debugged process reports. A SIGTRAP is the `int3` of a
+/// breakpoint: a breakpoint set by the user is removed, and the pc rewound to its restored
+/// instruction.
+///
+/// # Arguments
+///
+/// * `breakpoints` - The breakpoints armed in the child.
+/// * `tracee` - The child being debugged.
+/// * `status` - What waiting for it returned.
+///
+/// # Returns
+///
+/// The stop, with true if it is a breakpoint set by the user, or `None` if the status is not a
+/// stop nor a termination.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the registers can't be accessed, or [`DbgError::Memory`] if
+/// the instruction of the breakpoint can't be restored.
+pub fn classify_stop(breakpoints: &mut BreakpointTable, mut tracee: impl Tracee, status: WaitStatus) -> Result<Option<(StepStop, bool)>, DbgError> {
+    let stop = match status {
+        WaitStatus::Stopped(_, Signal::SIGTRAP) => {
+            let mut regs = tracee.getregs().map_err(DbgError::ptrace("read the registers"))?;
+            let address = trap_address(regs.pc());
+            let known = is_breakpoint(breakpoints, address);
+            if known {
+                remove_breakpoint(breakpoints, &mut tracee, address)?;
+                // The trap is past the breakpoint, go back to the restored instruction.
+                log::debug!("Rewinding to the breakpoint at {:#x}", address);
+                regs.set_pc(address);
+                tracee.setregs(regs).map_err(DbgError::ptrace("write the registers"))?;
             }
+            return Ok(Some((StepStop::Breakpoint(address), known)));
         }
+        WaitStatus::Stopped(_, signal) => StepStop::Signal(signal),
+        WaitStatus::Exited(_, code) => StepStop::Exited(code),
+        WaitStatus::Signaled(_, signal, _) => StepStop::Killed(signal),
+        _ => return Ok(None),
+    };
+    Ok(Some((stop, false)))
+}
+
+/// Print register states of the debugged process. The values changed since the registers were
+/// last shown are highlighted.
+///
+/// # Arguments
+///
+/// * `debugger` - The state of the debugging session.
+///
+/// # Errors
+///
+/// Returns [`DbgError::Ptrace`] if the registers can't be read.
+pub fn show_registers(debugger: &mut Debugger) -> Result<(), DbgError> {
+    let regs = debugger.tracee().getregs().map_err(DbgError::ptrace("read the registers"))?;
+    let values = debugger.arch.registers(&regs);
+    let previous = debugger.shown_registers.replace(values.iter().map(|(_, value)| *value).collect());
+    // The registers of the other architecture, after the program was rebuilt, are not compared.
+    let previous = previous.filter(|previous| previous.len() == values.len());
+    crate::json::set_data(debugger, |_| {
+        let registers: serde_json::Map<String, serde_json::Value> =
+            values.iter().map(|(name, value)| (name.trim().to_string(), crate::json::address(*value))).collect();
+        let changed: Vec<&str> = values
+            .iter()
+            .enumerate()
+            .filter(|(index, (_, value))| previous.as_ref().is_some_and(|previous| previous[*index] != *value))
+            .map(|(_, (name, _))| name.trim())
+            .collect();
+        serde_json::json!({ "registers": registers, "changed": changed })
+    });
+    outln!(debugger, "Registers:");
+    for (index, (name, value)) in values.into_iter().enumerate() {
+        let changed = previous.as_ref().is_some_and(|previous| previous[index] != value);
+        let value = format!("0x{:x}", value);
+        let name = debugger.output.paint(name, Style::Register);
+        let value = debugger.output.paint(&value, if changed { Style::Changed } else { Style::Address });
+        outln!(debugger, "  {}: {}", name, value);
     }
+    Ok(())
 }
 
-/// Print register states of the debugged process.
+/// Print available debugger commands and their descriptions, or the details of one command.
 ///
 /// # Arguments
 ///
-/// * `child` - The process ID (Pid) of the child being debugged.
-///
-pub fn show_registers(child: unistd::Pid) {
-    let regs = ptrace::getregs(child).expect("Failed to get registers");
-    println!("Registers:");
-    println!("  rax: 0x{:x}", regs.rax);
-    println!("  rbx: 0x{:x}", regs.rbx);
-    println!("  rcx: 0x{:x}", regs.rcx);
-    println!("  rdx: 0x{:x}", regs.rdx);
-    println!("  rsi: 0x{:x}", regs.rsi);
-    println!("  rdi: 0x{:x}", regs.rdi);
-    println!("  rsp: 0x{:x}", regs.rsp);
-    println!("  rip: 0x{:x}", regs.rip);
-    println!("  rbp: 0x{:x}", regs.rbp);
-    println!("  r8 : 0x{:x}", regs.r8);
-    println!("  r9 : 0x{:x}", regs.r9);
-    println!("  r10: 0x{:x}", regs.r10);
-    println!("  r11: 0x{:x}", regs.r11);
-    println!("  r12: 0x{:x}", regs.r12);
-    println!("  r13: 0x{:x}", regs.r13);
-    println!("  r14: 0x{:x}", regs.r14);
-    println!("  r15: 0x{:x}", regs.r15);
-}
-
-/// Print available debugger commands and their descriptions.
-pub fn help_commands() {
-    println!("Available commands:");
-    println!("  c or continue: Continue the process until completion (or the next breakpoint)");
-    println!("  s or syscall: Continue the process until the next syscall (or end of syscall)");
-    println!("  n or next: Make a single step in the process (Continue to next instruction (single-step))");
-    println!("  r or registers: Show the register states of the process");
-    println!("  m or memory: Show the content of a memory address");
-    println!("  h or help: Enter an instruction to get the list of available instructions.");
-}
\ No newline at end of file
+/// * `debugger` - The state of the debugging session.
+/// * `topic` - The name of a command to describe, or `None` to list them all.
+///
+/// # Returns
+///
+/// False if `topic` is not a command. The commands with a close name are suggested.
+pub fn help_commands(debugger: &mut Debugger, topic: Option<&str>) -> bool {
+    let Some(topic) = topic else {
+        outln!(debugger, "Available commands:");
+        for spec in COMMANDS {
+            outln!(debugger, "  {}: {}", spec.synopsis(), spec.summary);
+        }
+        outln!(debugger, "Type help followed by a command name for its details, help user-defined for the commands defined with define.");
+        return true;
+    };
+    if topic == "user-defined" {
+        return help_user_commands(debugger);
+    }
+    if let Some(body) = debugger.user_commands.get(topic).cloned() {
+        outln!(debugger, "User command {}, defined with define:", topic);
+        for line in body {
+            outln!(debugger, "  {}", line);
+        }
+        return true;
+    }
+    let spec = match lookup_command(topic) {
+        CommandMatch::Found(spec) => spec,
+        CommandMatch::Ambiguous => {
+            print_ambiguous_command(debugger, topic);
+            return false;
+        }
+        CommandMatch::Unknown => {
+            errln!(debugger, "No command named \"{}\".", topic);
+            let close = suggestions(topic, COMMANDS.iter().flat_map(|spec| spec.names.iter().copied()));
+            if !close.is_empty() {
+                outln!(debugger, "Did you mean: {}?", close.join(", "));
+            }
+            return false;
+        }
+    };
+    outln!(debugger, "Usage: {}", spec.synopsis());
+    outln!(debugger);
+    outln!(debugger, "{}", spec.long_help);
+    true
+}
+
+/// List the commands defined with `define`, with the first command of each.
+fn help_user_commands(debugger: &mut Debugger) -> bool {
+    if debugger.user_commands.is_empty() {
+        outln!(debugger, "No user-defined commands, define one with define <name>.");
+        return true;
+    }
+    outln!(debugger, "User-defined commands:");
+    let commands: Vec<(String, String)> = debugger
+        .user_commands
+        .iter()
+        .map(|(name, body)| (name.clone(), body.first().cloned().unwrap_or_default()))
+        .collect();
+    for (name, first) in commands {
+        outln!(debugger, "  {}: {}", name, first);
+    }
+    true
+}
@@ -0,0 +1,793 @@
+//! Drives the debugger binary in batch mode on the fixture programs, checking its JSON records.
+
+mod common;
+
+use common::{run_batch, run_batch_with};
+use serde_json::{json, Value};
+
+#[test]
+fn loop_breakpoint_and_backtrace() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b count", "c", "bt 2", "p i", "c"]);
+    let breakpoint = session.command(0);
+    assert_eq!(breakpoint["ok"], true, "{}", session.text);
+    assert_eq!(breakpoint["data"]["function"], "count");
+    let stops = session.stops();
+    // The breakpoint is removed once hit.
+    assert_eq!(stops.len(), 2, "{:#?}", stops);
+    assert_eq!(stops[0]["reason"], "breakpoint");
+    assert_eq!(stops[0]["breakpoint"], 1);
+    assert_eq!(stops[0]["function"], "count");
+    assert_eq!(stops[0]["pc"], breakpoint["data"]["address"]);
+    let banner = stops[0]["banner"].as_str().unwrap();
+    assert!(banner.starts_with("Breakpoint 1, count at ") && banner.ends_with("loop.c:6"), "{:#?}", stops[0]);
+    assert!(!session.text.contains("SIGTRAP"), "{}", session.text);
+    let frames = session.command(2)["data"]["frames"].as_array().unwrap().clone();
+    assert_eq!(frames.len(), 2);
+    assert_eq!((&frames[0]["function"], &frames[1]["function"]), (&Value::from("count"), &Value::from("main")));
+    assert_eq!(session.command(3)["data"]["output"], serde_json::json!(["i = 0"]));
+    assert_eq!(stops[1]["reason"], "exited");
+    assert_eq!(stops[1]["code"], 0);
+    assert_eq!(stops[1]["banner"], "Child process exited with status 0.");
+    assert_eq!(session.program_output, ["total 10"]);
+    assert_eq!(session.status, 0, "The exit status of the program");
+}
+
+#[test]
+fn crasher_reports_the_fault() {
+    require_ptrace!();
+    let session = run_batch("crash", &["c", "bt", "info signal"]);
+    let stops = session.stops();
+    assert_eq!(stops[0]["reason"], "signal");
+    assert_eq!(stops[0]["signal"], "SIGSEGV");
+    assert_eq!(stops[0]["banner"], "Program received signal SIGSEGV.");
+    assert_eq!(stops[0]["siginfo"]["code_name"], "SEGV_MAPERR");
+    assert_eq!(stops[0]["siginfo"]["address"], "0x10");
+    assert_eq!(session.command(2)["data"], stops[0]["siginfo"], "info signal shows it again");
+    assert!(session.text.contains("si_code SEGV_MAPERR (1): address not mapped to object, at address 0x10"), "{}", session.text);
+    assert_eq!(stops[0]["function"], "store");
+    assert!(session.text.contains("SIGSEGV"), "{}", session.text);
+    let functions: Vec<&Value> = session.command(1)["data"]["frames"].as_array().unwrap().iter().map(|frame| &frame["function"]).collect();
+    assert_eq!(functions[..2], [&Value::from("store"), &Value::from("main")]);
+}
+
+#[test]
+fn forker_exit_status() {
+    require_ptrace!();
+    let session = run_batch("forker", &["c", "c"]);
+    let stops = session.stops();
+    assert_eq!(stops.len(), 2, "{:#?}", stops);
+    // The program is told its child exited.
+    assert_eq!(stops[0]["reason"], "signal");
+    assert_eq!(stops[0]["signal"], "SIGCHLD");
+    assert_eq!(stops[1]["reason"], "exited");
+    assert_eq!(stops[1]["code"], 7, "The status of the forked child, returned by the program");
+    assert_eq!(session.status, 7);
+}
+
+#[test]
+fn threader_runs_to_completion() {
+    require_ptrace!();
+    let session = run_batch("threader", &["b main", "c", "c"]);
+    let stops = session.stops();
+    assert_eq!(stops[0]["reason"], "breakpoint");
+    assert_eq!(stops[1]["reason"], "exited");
+    assert_eq!(stops[1]["code"], 0, "The threads computed their results");
+}
+
+#[test]
+fn syscalls_entry_and_exit() {
+    require_ptrace!();
+    let session = run_batch("syscalls", &["b main", "c", "s", "s", "s"]);
+    let output = |index: usize| session.command(index)["data"]["output"].clone();
+    assert_eq!(output(2), serde_json::json!(["Caught syscall write (1) (entry)"]));
+    assert_eq!(output(3), serde_json::json!(["Caught syscall write (1) (exit), returned 0x5"]));
+    assert_eq!(output(4), serde_json::json!(["Caught syscall write (1) (entry)"]));
+    let stops = session.stops();
+    assert_eq!((&stops[1]["reason"], &stops[1]["syscall"], &stops[1]["entry"]), (&"syscall".into(), &"write".into(), &true.into()));
+    assert_eq!(stops[2]["banner"], "Caught syscall write (1) (exit)");
+}
+
+#[test]
+fn failed_commands_are_reported() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b nowhere", "frobnicate"]);
+    for index in 0..2 {
+        assert_eq!(session.command(index)["ok"], false);
+    }
+    assert_eq!(session.command(1)["error"], "Unknown command: frobnicate");
+    assert_eq!(session.status, 1, "A command failed");
+}
+
+#[test]
+fn threader_breakpoint_in_a_thread() {
+    require_ptrace!();
+    let session = run_batch("threader", &["b work", "c", "info threads", "bt 1", "c"]);
+    let stops = session.stops();
+    assert_eq!(stops.len(), 2, "{:#?}", stops);
+    assert_eq!(stops[0]["reason"], "breakpoint");
+    assert_eq!(stops[0]["function"], "work");
+    let threads = session.command(2)["data"]["threads"].as_array().unwrap().clone();
+    assert!(threads.len() >= 2, "{:#?}", threads);
+    // The main thread, listed first, does not call work.
+    assert_ne!(threads[0]["tid"], stops[0]["thread"]);
+    let current: Vec<&Value> = threads.iter().filter(|thread| thread["current"] == true).collect();
+    assert_eq!(current.len(), 1, "{:#?}", threads);
+    assert_eq!(current[0]["tid"], stops[0]["thread"]);
+    assert_eq!(current[0]["function"], "work");
+    assert!(threads.iter().all(|thread| thread["state"] == "stopped"), "{:#?}", threads);
+    assert!(session.text.contains(&format!("(tid {})]", stops[0]["thread"])), "{}", session.text);
+    assert_eq!(session.command(3)["data"]["frames"][0]["function"], "work", "The backtrace of the thread");
+    assert_eq!(stops[1]["reason"], "exited");
+    assert_eq!(stops[1]["code"], 0, "The other threads computed their results");
+}
+
+#[test]
+fn two_threads_hit_different_breakpoints() {
+    require_ptrace!();
+    let commands = ["b first", "b second", "c", "info threads", "thread 1", "r", "c", "info threads", "si", "info threads", "c"];
+    let session = run_batch("twothreads", &commands);
+    let stops = session.stops();
+    assert_eq!(stops.len(), 4, "{:#?}", stops);
+    let threads = |index: usize| session.command(index)["data"]["threads"].as_array().unwrap().clone();
+    let main = threads(3)[0]["tid"].clone();
+    assert_eq!(threads(3).len(), 3, "{:#?}", threads(3));
+    // Either thread can get there first, each stopping at its own breakpoint.
+    let mut functions = [stops[0]["function"].as_str().unwrap(), stops[1]["function"].as_str().unwrap()];
+    functions.sort();
+    assert_eq!(functions, ["first", "second"]);
+    assert_ne!(stops[0]["thread"], stops[1]["thread"]);
+    assert!(stops[..2].iter().all(|stop| stop["reason"] == "breakpoint" && stop["thread"] != main), "{:#?}", stops);
+    // The main thread selected, waiting for the others.
+    assert_eq!(session.command(4)["data"]["tid"], main);
+    assert_eq!(session.command(5)["data"]["registers"]["rip"], threads(3)[0]["pc"], "The registers of the main thread");
+    assert!(session.text.contains(&format!("(tid {})]", stops[1]["thread"])), "{}", session.text);
+    // Only the current thread steps, the others stay where they stopped.
+    let (before, after) = (threads(7), threads(9));
+    for (before, after) in before.iter().zip(after.iter()) {
+        assert_eq!(before["tid"], after["tid"]);
+        assert_eq!(before["current"], after["tid"] == stops[1]["thread"]);
+        if before["current"] == true {
+            assert_ne!(before["pc"], after["pc"], "The current thread stepped");
+        } else {
+            assert_eq!(before["pc"], after["pc"], "Thread {} stayed stopped", before["number"]);
+        }
+    }
+    assert_eq!(stops[2]["reason"], "step");
+    assert_eq!(stops[3]["reason"], "exited");
+    assert_eq!(stops[3]["code"], 0, "Both threads ran their function");
+}
+
+#[test]
+fn non_stop_keeps_the_other_threads_running() {
+    require_ptrace!();
+    let commands = [
+        "set non-stop on",
+        "b work",
+        "b finish",
+        "c",
+        "info threads",
+        "thread 1",
+        "p progress",
+        "shell sleep 0.1",
+        "p progress",
+        "set mem done = 1",
+        "shell sleep 0.3",
+        "info threads",
+        "thread 1",
+        "c -a",
+    ];
+    let session = run_batch("nonstop", &commands);
+    let stops = session.stops();
+    assert_eq!(stops.len(), 3, "{:#?}", stops);
+    assert_eq!(stops[0]["function"], "work");
+    let threads = |index: usize| session.command(index)["data"]["threads"].as_array().unwrap().clone();
+    let (main, worker) = (threads(4)[0].clone(), threads(4)[1].clone());
+    assert_eq!(worker["tid"], stops[0]["thread"]);
+    assert_eq!((main["state"].as_str(), worker["state"].as_str()), (Some("running"), Some("stopped")), "{:#?}", threads(4));
+    assert_eq!(session.command(5)["ok"], false, "A running thread can't be selected");
+    // The main thread counts while the worker is stopped.
+    let progress = |index: usize| {
+        let output = session.command(index)["data"]["output"][0].as_str().unwrap().to_string();
+        output.trim_start_matches("progress = ").parse::<u64>().unwrap()
+    };
+    assert!(progress(8) > progress(6), "{} then {}", progress(6), progress(8));
+    // The main thread stopped at finish, reported before the command.
+    assert_eq!(stops[1]["function"], "finish");
+    assert_eq!(stops[1]["thread"], main["tid"]);
+    assert!(session.text.contains(&format!("[Thread 1 (tid {}) stopped] Breakpoint 2", main["tid"])), "{}", session.text);
+    assert!(threads(11).iter().all(|thread| thread["state"] == "stopped"), "{:#?}", threads(11));
+    assert_eq!(session.command(12)["data"]["tid"], main["tid"]);
+    assert!(session.text.contains("[Current thread is 1"), "{}", session.text);
+    assert_eq!(stops[2]["reason"], "exited");
+    assert_eq!(stops[2]["code"], 0);
+}
+
+#[test]
+fn interrupt_stops_the_running_threads() {
+    require_ptrace!();
+    let session = run_batch("nonstop", &["set non-stop on", "b work", "c", "interrupt", "info threads", "thread 1", "set mem done = 1", "c -a", "interrupt"]);
+    let stops = session.stops();
+    let main = session.command(4)["data"]["threads"][0]["tid"].clone();
+    assert_eq!(session.command(3)["data"]["stopped"], serde_json::json!([main]), "{}", session.text);
+    assert!(session.text.contains(&format!("[Thread 1 (tid {}) stopped] Interrupted in", main)), "{}", session.text);
+    assert_eq!(session.command(4)["data"]["threads"][0]["state"], "stopped");
+    assert_eq!(session.command(5)["ok"], true);
+    assert_eq!(stops.last().unwrap()["reason"], "exited");
+    assert_eq!(stops.last().unwrap()["code"], 0);
+}
+
+#[test]
+fn loop_info_proc() {
+    require_ptrace!();
+    let session = run_batch("loop", &["info proc", "info proc exe", "info proc cwd", "info proc fd", "info proc maps"]);
+    let status = &session.command(0)["data"];
+    assert_eq!(status["name"], "loop");
+    assert!(status["state"].as_str().unwrap().starts_with("t "), "Stopped by the debugger: {}", status);
+    assert_eq!(status["threads"], 1);
+    assert_eq!(session.command(1)["data"]["exe"], common::fixture("loop"));
+    assert_eq!(session.command(2)["data"]["cwd"], env!("RUSTDBG_FIXTURES"));
+    let fds = session.command(3)["data"]["fds"].as_array().unwrap().clone();
+    assert_eq!(fds[0]["fd"], 0, "{:#?}", fds);
+    assert!(fds.iter().all(|fd| fd["flags"].is_string()), "{:#?}", fds);
+    assert_eq!(session.command(4)["ok"], false, "An unknown topic");
+}
+
+#[test]
+fn crasher_core_file() {
+    require_ptrace!();
+    let core = format!("{}/crash.core", env!("CARGO_TARGET_TMPDIR"));
+    let live = run_batch("crash", &["c", &format!("gcore {}", core), "bt"]);
+    assert_eq!(live.command(1)["ok"], true, "{}", live.text);
+    let session = run_batch_with("crash", &["--core", &core], &["r", "bt", "m $rsp", "disas", "info maps", "c", "si", "b main", "info proc"]);
+    std::fs::remove_file(&core).ok();
+    let stop = &session.stops()[0];
+    assert_eq!(stop["reason"], "core");
+    assert_eq!(stop["signal"], "SIGSEGV");
+    assert_eq!(stop["function"], "store");
+    assert!(session.text.contains("Program terminated with signal SIGSEGV."), "{}", session.text);
+    assert_eq!(session.command(0)["data"]["registers"]["rip"], stop["pc"]);
+    assert_eq!(session.command(1)["data"], live.command(2)["data"], "The same backtrace as the process");
+    assert_eq!(session.command(2)["ok"], true, "The stack is in the core file");
+    assert_eq!(session.command(3)["ok"], true, "The code is read from the executable");
+    let mappings = session.command(4)["data"]["mappings"].as_array().unwrap().clone();
+    assert!(mappings.iter().any(|mapping| mapping["file"] == common::fixture("crash") && mapping["perms"] == "r-xp"), "{:#?}", mappings);
+    for index in 5..9 {
+        assert_eq!(session.command(index)["ok"], false);
+        assert_eq!(session.command(index)["error"], "Not supported on core files.", "{}", session.command(index));
+    }
+}
+
+#[test]
+fn loop_software_watchpoint() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b main", "c", "swatch total 4", "swatch total 4 count", "c"]);
+    let changed = &session.command(2)["data"];
+    assert_eq!(changed["old"], serde_json::json!([0, 0, 0, 0]), "{}", session.text);
+    assert_eq!(changed["new"], serde_json::json!([1, 0, 0, 0]), "count(0) adds nothing");
+    assert!(session.text.contains("<count+"), "The instruction of count changed it: {}", session.text);
+    let skipped = &session.command(3)["data"];
+    assert_eq!(skipped["new"], serde_json::json!([3, 0, 0, 0]));
+    assert!(skipped["steps"].as_u64().unwrap() < changed["steps"].as_u64().unwrap(), "count ran at full speed");
+    assert!(session.text.contains("<main+") && session.text.contains("call"), "The call to count is reported: {}", session.text);
+    assert_eq!(session.program_output, ["total 10"]);
+    assert_eq!(session.status, 0);
+}
+
+#[test]
+fn loop_coverage() {
+    require_ptrace!();
+    let drcov = format!("{}/loop.drcov", env!("CARGO_TARGET_TMPDIR"));
+    let session = run_batch("loop", &["b main", "c", "cover start", "b loop.c:13", "c", "cover report", &format!("cover save --drcov {}", drcov), "c"]);
+    let blocks = session.command(2)["data"]["blocks"].as_u64().unwrap();
+    assert!(blocks > 5, "{}", session.text);
+    let stops = session.stops();
+    assert_eq!(stops[1]["breakpoint"], 2, "The blocks don't stop the program: {:#?}", stops);
+    let functions = session.command(5)["data"]["functions"].as_array().unwrap().clone();
+    let count = functions.iter().find(|function| function["function"] == "count").unwrap();
+    assert!(count["hits"].as_u64().unwrap() >= 1 && count["hits"] == count["blocks"], "{:#?}", functions);
+    assert_eq!(session.program_output, ["total 10"]);
+    let bytes = std::fs::read(&drcov).unwrap();
+    std::fs::remove_file(&drcov).ok();
+    let text = String::from_utf8_lossy(&bytes);
+    assert!(text.starts_with("DRCOV VERSION: 2\n"), "{}", text);
+    assert!(text.contains(&format!(", {}\n", common::fixture("loop"))), "The program in the module table: {}", text);
+    let table = text.find("BB Table: ").unwrap();
+    let count: usize = text[table + 10..].split(' ').next().unwrap().parse().unwrap();
+    let header_end = bytes[table..].iter().position(|byte| *byte == b'\n').unwrap() + table + 1;
+    assert_eq!(bytes.len() - header_end, count * 8, "8 bytes per block");
+}
+
+#[test]
+fn streams_captured_separately() {
+    require_ptrace!();
+    let session = run_batch("streams", &["c"]);
+    assert_eq!(session.program_output, ["to stdout", "no newline"], "{:#?}", session.records);
+    assert_eq!(session.program_errors, ["to stderr"]);
+    let position = |kind: &str| session.records.iter().position(|record| record["type"] == kind).unwrap();
+    assert!(position("output") < position("stop"), "The output comes before the exit: {:#?}", session.records);
+    assert_eq!(session.stops()[0]["reason"], "exited");
+}
+
+#[test]
+fn reader_input_and_redirection() {
+    require_ptrace!();
+    let session = run_batch("reader", &["input first", "input second line", "input --eof", "c"]);
+    assert_eq!(session.program_output, ["read first", "read second line", "2 lines"], "{}", session.text);
+    let path = format!("{}/reader.txt", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "from a file\n").unwrap();
+    let session = run_batch("reader", &["b main", &format!("run < {}", path), "input ignored", "c"]);
+    std::fs::remove_file(&path).ok();
+    assert_eq!(session.stops()[0]["reason"], "breakpoint", "The breakpoint is set again: {}", session.text);
+    assert_eq!(session.program_output, ["read from a file", "1 lines"], "{}", session.text);
+    assert_eq!(session.command(2)["ok"], false, "The input is the file: {}", session.text);
+}
+
+#[test]
+fn info_frame_saved_registers() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b count", "c", "info frame", "up", "info frame"]);
+    let inner = &session.command(2)["data"];
+    let outer = &session.command(4)["data"];
+    assert_eq!(inner["frame"]["function"], "count", "{}", session.text);
+    assert_eq!(outer["frame"]["function"], "main", "{}", session.text);
+    assert_eq!(inner["unwinder"], "cfi", "{:#?}", inner);
+    let parse = |value: &Value| u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+    let saved = inner["saved"].as_array().unwrap();
+    let return_address = saved.iter().find(|saved| saved["register"] == "rip").unwrap();
+    assert_eq!(return_address["value"], outer["frame"]["pc"], "The return address is the pc of the caller: {:#?}", inner);
+    assert_eq!(parse(&return_address["address"]) + 8, parse(&inner["cfa"]), "The return address is just below the CFA: {:#?}", inner);
+    assert!(parse(&outer["cfa"]) > parse(&inner["cfa"]), "{:#?} {:#?}", inner, outer);
+}
+
+#[test]
+fn disassemble_the_selected_frame() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b count", "c", "disas", "up", "disas"]);
+    let inner = session.command(2)["data"]["output"].to_string();
+    let outer = session.command(4)["data"]["output"].to_string();
+    assert!(inner.contains("=> 0x") && inner.contains("<count+7>"), "{}", inner);
+    assert!(outer.contains("call 0x") && outer.contains("<main+"), "The code of the caller around its call: {}", outer);
+    assert!(!outer.contains("=>"), "The current instruction is not in the caller: {}", outer);
+}
+
+#[test]
+fn call_functions_of_the_program() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b count", "c", "call puts(\"from the call\")", "call abs(-5)", "call abort()", "p i", "c"]);
+    let puts = &session.command(2)["data"];
+    assert_eq!(puts["function"], "puts", "{}", session.text);
+    assert_eq!(puts["value"], 14, "The length and the newline: {}", session.text);
+    assert_eq!(session.command(3)["data"]["value"], 5, "{}", session.text);
+    let abort = session.command(4);
+    assert_eq!(abort["ok"], false, "{}", session.text);
+    assert!(abort["error"].as_str().unwrap().contains("SIGABRT"), "{:#?}", abort);
+    assert_eq!(session.command(5)["data"]["output"][0], "i = 0", "The frame is restored: {}", session.text);
+    assert_eq!(session.program_output, ["from the call", "total 10"], "{}", session.text);
+    assert_eq!(session.stops().last().unwrap()["code"], 0, "The program goes on as before: {}", session.text);
+}
+
+#[test]
+fn where_summary_at_each_stop() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b count", "c", "where", "set stop-summary off", "si"]);
+    let stop = session.stops()[0];
+    let summary = stop["summary"].as_str().unwrap();
+    assert!(summary.contains(" in count+0x") && summary.contains("loop.c:6 [loop r-xp], breakpoint 1, thread 1 (tid "), "{}", summary);
+    assert_eq!(stop["mapping"], common::fixture("loop"), "{:#?}", stop);
+    let data = &session.command(2)["data"];
+    assert_eq!(data["summary"], summary, "where prints the line of the stop: {:#?}", data);
+    assert_eq!(data["reason"], "breakpoint");
+    let output = session.command(1)["data"]["output"].as_array().unwrap().clone();
+    assert!(output.iter().any(|line| line == summary), "Shown at the stop: {:#?}", output);
+    let stepped = session.command(4)["data"]["output"].as_array().unwrap().clone();
+    assert!(stepped.iter().all(|line| !line.as_str().unwrap().contains(", step, thread")), "set stop-summary off: {:#?}", stepped);
+    assert_eq!(session.stops()[1]["banner"], "Step completed.");
+}
+
+#[test]
+fn anti_debugging_checks() {
+    require_ptrace!();
+    let session = run_batch("antidebug", &["c"]);
+    assert_eq!(session.program_output, ["TracerPid set", "debugger detected"]);
+    assert_eq!(session.status, 1);
+    let session = run_batch("antidebug", &["set anti-anti-debug on", "c"]);
+    assert!(session.text.contains("Anti-debugging: the program opens /proc/self/status"), "{}", session.text);
+    assert!(session.text.contains("Anti-debugging: the program calls ptrace(PTRACE_TRACEME)"), "{}", session.text);
+    assert!(session.text.contains("[anti-anti-debug] ptrace(PTRACE_TRACEME) returns 0 instead of failing with EPERM"), "{}", session.text);
+    assert_eq!(session.program_output, ["TracerPid set", "no debugger"]);
+    assert_eq!(session.status, 0);
+    let stops = session.stops();
+    assert_eq!(stops.len(), 1, "Only the exit stops the program: {:#?}", stops);
+    assert_eq!(stops[0]["reason"], "exited");
+}
+
+#[test]
+fn assertions_end_a_batch_session() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b count", "c", "assert $rip >= count", "assert *$rsp != 0", "assert $rsp > -1", "assert -1 < 0", "assert $rip == 0x10", "c"]);
+    for index in 2..6 {
+        assert_eq!(session.command(index)["ok"], true, "{}", session.text);
+    }
+    assert_eq!(session.command(2)["data"]["passed"], true);
+    let failed = session.command(6);
+    assert_eq!(failed["ok"], false);
+    assert!(failed["error"].as_str().unwrap().starts_with("Assertion failed: $rip == 0x10, with $rip = 0x"), "{}", failed);
+    assert_eq!(session.status, 3, "A failed assertion ends the session");
+    assert!(session.program_output.is_empty(), "The last c is not run: {:?}", session.program_output);
+    let session = run_batch("loop", &["assert 0xffffffffffffffff == -1", "assert 5", "c"]);
+    assert_eq!(session.status, 0, "{}", session.text);
+}
+
+#[test]
+fn watchpoints_on_static_variables() {
+    require_ptrace!();
+    let session = run_batch("watch", &["watch level", "awatch counter", "watch origin", "c", "c", "c", "watch", "unwatch level", "unwatch counter", "watch origin", "c", "c"]);
+    assert_eq!(session.command(0)["data"]["hardware"], true, "{}", session.text);
+    assert_eq!(session.command(1)["data"]["kind"], "access");
+    assert_eq!(session.command(2)["ok"], false, "A software watchpoint watches alone");
+    assert!(session.text.contains("Hardware watchpoint level: value changed 0 -> 2"), "{}", session.text);
+    assert!(session.text.contains("Hardware access watchpoint counter: value changed 0 -> 7"), "{}", session.text);
+    assert!(session.text.contains("Hardware access watchpoint counter: value = 7"), "{}", session.text);
+    assert_eq!(session.command(6)["data"]["watchpoints"].as_array().unwrap().len(), 2);
+    assert!(session.text.contains("Software watchpoint origin: value changed point { x: 0, y: 0 } -> point { x: 0, y: 5 }"), "{}", session.text);
+    let stops = session.stops();
+    assert_eq!(stops[0]["reason"], "watchpoint");
+    assert_eq!(stops[0]["watchpoint"], "level");
+    assert_eq!(stops[2]["function"], "read_counter");
+    assert_eq!(stops[3]["watchpoint"], "origin");
+    assert_eq!(session.program_output, ["2 7 5"]);
+    assert_eq!(session.status, 0);
+}
+
+#[test]
+fn run_status_while_the_program_runs() {
+    require_ptrace!();
+    let session = run_batch("sleepy", &["set run-status interval 1", "c"]);
+    assert!(session.text.contains("[running for 1s] S (sleeping) in clock_nanosleep"), "{}", session.text);
+    assert!(session.text.contains("R (running), "), "The program spins after it sleeps: {}", session.text);
+    assert_eq!(session.program_output, ["done"]);
+    assert_eq!(session.status, 0);
+    let session = run_batch("sleepy", &["c"]);
+    assert!(!session.text.contains("[running for"), "Off by default: {}", session.text);
+}
+
+#[test]
+fn tracked_descriptors_are_reported_at_each_stop() {
+    require_ptrace!();
+    let session = run_batch("fds", &["set track-fds on", "b fds.c:11", "b fds.c:12", "b fds.c:19", "b fds.c:21", "c", "c", "c", "c", "c"]);
+    let output = |index: usize| session.command(index)["data"]["output"].as_array().unwrap().iter().map(|line| line.as_str().unwrap().to_string()).collect::<Vec<_>>();
+    assert!(output(5).contains(&"opened fd 3 -> /dev/null".to_string()), "{:?}", output(5));
+    assert!(output(6).iter().all(|line| !line.contains("fd ")), "Silent when nothing changed: {:?}", output(6));
+    let third = output(7);
+    assert!(third.contains(&"reopened fd 3 -> /dev/zero (was /dev/null)".to_string()), "{:?}", third);
+    assert!(third.iter().any(|line| line.starts_with("opened fd 4 -> pipe:[")) && third.iter().any(|line| line.starts_with("opened fd 5 -> pipe:[")), "{:?}", third);
+    assert!(output(8).iter().any(|line| line.starts_with("reopened fd 3 -> pipe:[") && line.ends_with("(was /dev/zero)")), "{:?}", output(8));
+    assert_eq!(session.program_output, ["3 3"]);
+    let session = run_batch("fds", &["b fds.c:11", "c", "c"]);
+    assert!(!session.text.contains("opened fd"), "Off by default: {}", session.text);
+}
+
+#[test]
+fn start_stops_in_the_main_function() {
+    require_ptrace!();
+    let session = run_batch(
+        "rustmain",
+        &["start", "bt 1", "b rustmain.rs:16", "c", "p found", "p missing", "p outcome", "p circle", "p square", "p config", "print/full config", "p/x config", "c"],
+    );
+    let output = session.command(0)["data"]["output"].to_string();
+    assert!(output.contains("Stopping at rustmain::main, marked as the main function by the debugging information."), "{}", output);
+    let banner = session.stops()[0]["banner"].as_str().unwrap();
+    assert!(banner.starts_with("Temporary breakpoint (start), rustmain::main at ") && banner.ends_with("rustmain.rs:6"), "{}", banner);
+    assert!(output.contains("let value = answer(41);"), "The source line is shown: {}", output);
+    assert!(session.stops()[1]["banner"].as_str().unwrap().starts_with("Breakpoint 1, rustmain::main at "), "The temporary breakpoint is gone: {:#?}", session.stops());
+    let output = |index: usize| session.command(index)["data"]["output"][0].clone();
+    assert_eq!(output(4), "found = Some(7)", "{}", session.text);
+    assert_eq!(output(5), "missing = None");
+    assert_eq!(output(6), "outcome = Err(\"bad\")");
+    assert_eq!(output(7), "circle = Shape::Circle { radius: 9 }");
+    assert_eq!(output(8), "square = Shape::Square(2, 3)");
+    let nested = output(9).as_str().unwrap().to_string();
+    assert!(nested.starts_with("config = Config { inner: Inner { id: 1, leaf: {...} }, limit: 0x"), "{}", nested);
+    assert!(nested.contains(" -> 5, sizes: [6, 6,") && nested.ends_with("... 4 more] }"), "{}", nested);
+    assert!(output(10).as_str().unwrap().contains("leaf: Leaf { value: 4 }"), "{}", output(10));
+    assert_eq!(session.command(11)["error"], "Unknown format /x, print takes /full.");
+    assert_eq!(session.program_output, ["answer 42"]);
+
+    let session = run_batch("loop", &["start", "c"]);
+    assert!(session.command(0)["data"]["output"].to_string().contains("Stopping at main, the main symbol."), "{:#?}", session.command(0));
+    assert!(session.stops()[0]["banner"].as_str().unwrap().ends_with("loop.c:10"), "{:#?}", session.stops());
+    let session = run_batch("stripped", &["start"]);
+    let output = session.command(0)["data"]["output"].to_string();
+    assert!(output.contains("no main function is in the symbols, as when the executable is stripped"), "{}", output);
+
+    // A breakpoint of the user on main is the one reported, and stays.
+    let session = run_batch("loop", &["b main", "start", "start"]);
+    assert!(session.stops()[0]["banner"].as_str().unwrap().starts_with("Breakpoint 1, main at "), "{:#?}", session.stops());
+    assert!(session.text.contains("already running") || session.command(2)["success"] == false, "{}", session.text);
+
+    let session = run_batch_with("rustmain", &["--stop-at-main"], &["where"]);
+    assert_eq!((&session.command(0)["data"]["function"], &session.command(0)["data"]["line"]), (&json!("rustmain::main"), &json!(6)), "{:#?}", session.command(0));
+}
+
+#[test]
+fn dynamic_loader_state_at_early_stops() {
+    require_ptrace!();
+    let session = run_batch("loop", &["info dl", "si", "b main", "c", "info dl"]);
+    let early = &session.command(0)["data"];
+    assert_eq!((&early["in_loader"], &early["before_relocation"], &early["r_debug"]), (&json!(true), &json!(true), &Value::Null), "{:#?}", early);
+    assert!(early["interpreter"].as_str().unwrap().contains("ld-linux"), "{:#?}", early);
+    assert!(early["base"].is_string() && early["entry"].is_string(), "{:#?}", early);
+    let stops = session.stops();
+    assert_eq!(stops[0]["banner"], "Step completed. Stopped in the dynamic loader before relocation; use 'b main' then 'c' to run to main.");
+    assert!(stops[1]["banner"].as_str().unwrap().starts_with("Breakpoint 1, main at "), "No hint past the loader: {:#?}", stops[1]);
+    let late = &session.command(4)["data"];
+    assert_eq!((&late["in_loader"], &late["before_relocation"], &late["r_state"]), (&json!(false), &json!(false), &json!(0)), "{:#?}", late);
+    assert!(late["r_debug"].is_string() && late["objects"].as_u64().unwrap() >= 3, "{:#?}", late);
+
+    let session = run_batch("i386", &["info dl"]);
+    let data = &session.command(0)["data"];
+    assert_eq!((&data["interpreter"], &data["base"], &data["in_loader"]), (&Value::Null, &Value::Null, &json!(false)), "Statically linked: {:#?}", data);
+}
+
+#[test]
+fn register_diff_between_snapshots() {
+    require_ptrace!();
+    let session = run_batch("loop", &["b count", "c", "snapshot", "si", "si", "si", "snapshot", "regs diff 1 2", "r diff 2", "si", "r diff 2 live", "r diff 9", "r diff 1 x"]);
+    let diff = &session.command(7)["data"];
+    assert_eq!((&diff["from"], &diff["to"]), (&json!("snapshot 1"), &json!("snapshot 2")), "{:#?}", diff);
+    let registers = diff["registers"].as_array().unwrap();
+    let rip = registers.iter().find(|register| register["name"] == "rip").expect("rip moved");
+    let (from, to) = (u64::from_str_radix(&rip["from"].as_str().unwrap()[2..], 16).unwrap(), u64::from_str_radix(&rip["to"].as_str().unwrap()[2..], 16).unwrap());
+    assert_eq!(rip["xor"], json!(format!("{:#x}", from ^ to)), "{:#?}", rip);
+    assert!(registers.iter().all(|register| register["from"] != register["to"]), "Only the differing registers: {:#?}", registers);
+    if registers.iter().any(|register| register["name"] == "eflags") {
+        assert!(diff["flags"]["flipped"].as_array().is_some_and(|flipped| !flipped.is_empty()), "{:#?}", diff);
+    }
+    let same = &session.command(8)["data"];
+    assert_eq!((&same["to"], &same["registers"]), (&json!("live"), &json!([])), "Nothing ran since snapshot 2: {:#?}", same);
+    assert!(session.command(10)["data"]["registers"].as_array().unwrap().iter().any(|register| register["name"] == "rip"));
+    assert_eq!(session.command(11)["error"], "No snapshot 9, see info snapshots.");
+    assert!(session.command(12)["error"].as_str().unwrap().starts_with("Invalid snapshot \"x\""), "{}", session.command(12));
+}
+
+#[test]
+fn labels_name_addresses() {
+    require_ptrace!();
+    let saved = std::env::temp_dir().join(format!("rustdbg-labels-{}.gdb", std::process::id()));
+    let save = format!("label --save {}", saved.display());
+    let session = run_batch("loop", &["b count", "c", "label total sum 4", "disas count 6", "m sum", "info labels", &save, "unlabel sum", "disas count 6", "unlabel sum"]);
+    let listing = session.command(3)["data"]["output"].to_string();
+    assert!(listing.contains("<sum> = 0x0"), "The operand is described by its label: {}", listing);
+    assert_eq!(session.command(4)["data"]["value"], "0x0", "A label is a value");
+    let labels = &session.command(5)["data"]["labels"];
+    assert_eq!((&labels[0]["name"], &labels[0]["length"]), (&json!("sum"), &json!(4)), "{:#?}", labels);
+    assert!(session.command(8)["data"]["output"].to_string().contains("<total> = 0x0"), "Back to the symbol once removed");
+    assert_eq!(session.command(9)["error"], "No label named \"sum\".");
+
+    let source = format!("source {}", saved.display());
+    let session = run_batch("loop", &[&source, "info labels"]);
+    std::fs::remove_file(&saved).ok();
+    assert_eq!(session.command(2)["data"]["labels"][0]["name"], "sum", "Saved labels are sourced back: {:#?}", session.command(2));
+}
+
+#[test]
+fn session_saved_and_replayed() {
+    require_ptrace!();
+    let saved = std::env::temp_dir().join(format!("rustdbg-session-{}.rdbg", std::process::id()));
+    let save = format!("session save {}", saved.display());
+    let replay = format!("session replay {}", saved.display());
+    let stack = run_batch_with("loop", &["--no-aslr"], &["b count", "c", "r"]).command(2)["data"]["registers"]["rsp"].as_str().unwrap().to_string();
+    let examine = format!("m {}", stack);
+    run_batch_with("loop", &["--no-aslr"], &["b count", "c", &examine, "help", "frobnicate", &save]);
+    let text = std::fs::read_to_string(&saved).unwrap();
+    assert!(text.contains("# aslr off\nb count\nc\n# region "), "{}", text);
+    assert!(text.contains(&format!(" [stack]\n{}\nfrobnicate\n# failed\n", examine)), "help is not saved, failures are noted: {}", text);
+
+    // Started with ASLR on, the program is started again with it off and the stack is where it was.
+    let session = run_batch("loop", &[&replay]);
+    assert_eq!(session.command(5)["command"], json!(replay), "restart and the 4 commands replayed first: {:#?}", session.records);
+    assert_eq!(session.command(3)["ok"], true, "The stack address is mapped again: {:#?}", session.command(3));
+    assert_eq!(session.command(4)["error"], "Unknown command: frobnicate");
+    assert_eq!(session.command(5)["ok"], true, "Failing as recorded is not a failure");
+
+    std::fs::write(&saved, text.replace("# aslr off", "# aslr on")).unwrap();
+    let session = run_batch_with("loop", &["--no-aslr"], &[&replay]);
+    std::fs::remove_file(&saved).ok();
+    let output = session.command(3)["data"]["output"].to_string();
+    assert!(output.contains(&format!("Stopping the replay before \\\"{}\\\": [stack] was loaded at", examine)), "{}", output);
+}
+
+#[test]
+fn rbreak_sets_a_group_of_breakpoints() {
+    require_ptrace!();
+    let session = run_batch("loop", &["rbreak ^(count|main)$", "restart", "info breakpoints", "delete group 1", "info breakpoints", "restart", "info breakpoints", "delete group 1", "rbreak ^nothing_like_this$"]);
+    let set = &session.command(0)["data"];
+    assert_eq!(set["group"], 1, "{:#?}", set);
+    let functions: Vec<&Value> = set["breakpoints"].as_array().unwrap().iter().map(|breakpoint| &breakpoint["function"]).collect();
+    assert_eq!(functions, [&json!("count"), &json!("main")]);
+    let armed = session.command(2)["data"]["breakpoints"].as_array().unwrap().clone();
+    assert_eq!(armed.iter().map(|breakpoint| &breakpoint["group"]).collect::<Vec<_>>(), [&json!(1), &json!(1)], "Set again by restart, in their group");
+    assert_eq!(session.command(4)["data"]["breakpoints"], json!([]));
+    assert_eq!(session.command(6)["data"]["breakpoints"], json!([]), "restart does not set the deleted breakpoints again");
+    assert_eq!(session.command(7)["error"], "No breakpoint group 1.");
+    assert_eq!(session.command(8)["error"], "No function matches \"^nothing_like_this$\".");
+
+    let session = run_batch("loop", &["b count", "b main", "delete 1", "restart", "info breakpoints", "delete 2", "delete"]);
+    assert_eq!(session.command(2)["ok"], true, "{}", session.text);
+    let armed = session.command(4)["data"]["breakpoints"].as_array().unwrap().clone();
+    assert!(armed.len() == 1 && armed[0]["what"].as_str().unwrap().starts_with("main"), "A deleted breakpoint is not set again: {:#?}", armed);
+    assert_eq!(session.command(5)["error"], "No breakpoint number 2.", "restart numbers the breakpoints again");
+    assert_eq!(session.command(6)["ok"], false, "{}", session.text);
+}
+
+#[test]
+fn examine_with_gdb_formats() {
+    require_ptrace!();
+    let session = run_batch("locals", &["b inspect", "c", "x/2s $rsi", "x/6xb $rsi", "x/2i $rip", "x/4q $rsi", "x/2cb $rsi"]);
+    let strings = &session.command(2)["data"]["strings"];
+    assert_eq!(strings[0]["text"], "hello", "{}", session.text);
+    assert_eq!(strings[1]["text"], "", "The string after the NUL");
+    let bytes = &session.command(3)["data"];
+    assert_eq!(bytes["values"], json!(["0x68", "0x65", "0x6c", "0x6c", "0x6f", "0x00"]));
+    assert_eq!(bytes["address"], strings[0]["address"]);
+    let instructions = session.command(4)["data"]["instructions"].as_array().unwrap().clone();
+    assert_eq!(instructions.len(), 2, "{:#?}", instructions);
+    let error = session.command(5)["error"].as_str().unwrap().to_string();
+    assert!(error.starts_with("Unknown format letter 'q' in /4q, x takes the formats x (hex), d (decimal)"), "{}", error);
+    assert_eq!(session.command(6)["data"]["values"], json!(["104 'h'", "101 'e'"]));
+}
+
+#[test]
+fn exec_sets_the_breakpoints_again() {
+    require_ptrace!();
+    let session = run_batch("exec", &["b main", "b before_exec", "c", "b $rip", "c", "c", "c", "info breakpoints", "bt 1", "c"]);
+    let stops = session.stops();
+    assert_eq!(stops.len(), 5, "{:#?}", stops);
+    assert_eq!((&stops[2]["breakpoint"], &stops[2]["function"]), (&json!(2), &json!("before_exec")));
+    // Breakpoint 1 is hit again in the new program.
+    assert_eq!((&stops[3]["breakpoint"], &stops[3]["function"]), (&json!(1), &json!("main")), "{}", session.text);
+    let output = session.command(6)["data"]["output"].to_string();
+    assert!(output.contains("is executing a new program: ") && output.contains("/exechelper"), "{}", output);
+    assert!(output.contains("Breakpoint 1 (main) set again at 0x"), "{}", output);
+    assert!(output.contains("Breakpoint 2 (before_exec) is pending, it is not in "), "{}", output);
+    assert!(output.contains("Breakpoint 3 ($rip) dropped: its address was in the previous program."), "{}", output);
+    let breakpoints = &session.command(7)["data"]["breakpoints"];
+    assert_eq!(breakpoints, &json!([{ "number": 2, "address": null, "what": "before_exec", "pending": true, "group": null }]));
+    let frame = &session.command(8)["data"]["frames"][0];
+    assert_eq!(frame["function"], "main");
+    assert!(frame["file"].as_str().unwrap_or_default().ends_with("exechelper.c"), "{:#?}", frame);
+    assert_eq!(session.program_output, ["executing exechelper", "helper 42"]);
+    assert_eq!(stops[4]["reason"], "exited");
+}
+
+#[test]
+fn vdso_symbols_name_its_frames() {
+    require_ptrace!();
+    let session = run_batch("vdso", &["b main", "c", "info sharedlibrary", "b __vdso_clock_gettime", "c", "bt 1", "c"]);
+    assert!(session.text.contains(" Yes        No          [vdso]"), "info sharedlibrary lists the vDSO: {}", session.text);
+    assert_eq!(session.command(3)["ok"], true, "{}", session.text);
+    let stops = session.stops();
+    assert_eq!(stops[1]["breakpoint"], 2, "{:#?}", stops);
+    assert!(stops[1]["function"].as_str().unwrap_or_default().ends_with("clock_gettime"), "{:#?}", stops[1]);
+    let frame = &session.command(5)["data"]["frames"][0];
+    assert!(frame["function"].as_str().unwrap_or_default().ends_with("clock_gettime"), "{:#?}", frame);
+    assert_eq!(session.program_output, ["clock read"]);
+}
+
+#[test]
+fn interrupted_syscall_is_explained() {
+    require_ptrace!();
+    let session = run_batch("interrupted", &["info syscall", "b main", "c", "info syscall", "c", "info syscall"]);
+    assert_eq!(session.command(0)["ok"], false, "{}", session.text);
+    let before = &session.command(3)["data"];
+    assert_eq!(before["syscall"], Value::Null, "Stopped by a breakpoint: {:#?}", before);
+    assert!(session.text.contains("is not in a syscall."), "{}", session.text);
+    let stops = session.stops();
+    assert_eq!(stops[1]["signal"], "SIGINT", "{:#?}", stops);
+    assert!(session.text.contains("Interrupted in read(3, 0x"), "The SIGINT stop names the blocked read: {}", session.text);
+    let data = &session.command(5)["data"];
+    assert_eq!(data["syscall"], "read", "{:#?}", data);
+    assert!(data["call"].as_str().unwrap_or_default().starts_with("read(3, 0x"), "{:#?}", data);
+    assert!(data["call"].as_str().unwrap_or_default().ends_with(", 16)"), "{:#?}", data);
+    assert_eq!(data["result"], "= ? ERESTARTSYS (To be restarted if SA_RESTART is set)", "{:#?}", data);
+}
+
+#[test]
+fn display_expressions_at_every_stop() {
+    require_ptrace!();
+    let session = run_batch("locals", &["display counter", "b inspect", "c", "display *0x10", "display $rdi", "n", "undisplay 2", "info display", "undisplay 9"]);
+    assert_eq!(session.command(0)["data"], json!({ "number": 1, "expression": "counter", "value": null, "error": null }), "{}", session.text);
+    assert_eq!(session.text.matches("1: counter = 7").count(), 3, "Shown at the breakpoint, after n and by info display: {}", session.text);
+    assert_eq!(session.text.matches("2: *0x10 = <error: ").count(), 2, "The error in place of the value: {}", session.text);
+    assert!(session.text.contains("3: $rdi = 0x1 (1)"), "{}", session.text);
+    let failed = &session.command(3)["data"];
+    assert_eq!(failed["value"], Value::Null, "{:#?}", failed);
+    assert!(failed["error"].as_str().is_some(), "{:#?}", failed);
+    let displays = &session.command(7)["data"]["displays"];
+    assert_eq!(
+        *displays,
+        json!([{ "number": 1, "expression": "counter", "last": "7" }, { "number": 3, "expression": "$rdi", "last": "0x1 (1)" }]),
+        "{}",
+        session.text
+    );
+    assert_eq!(session.command(8)["ok"], false, "{}", session.text);
+}
+
+#[test]
+fn run_arguments_and_working_directory() {
+    require_ptrace!();
+    let directory = std::fs::canonicalize(env!("CARGO_TARGET_TMPDIR")).unwrap().to_string_lossy().into_owned();
+    let set_cwd = format!("set cwd {}", directory);
+    let session = run_batch(
+        "workdir",
+        &["set args saved", &set_cwd, "show cwd", "b main", "run once twice", "p argc", "info proc cwd", "restart", "c", "p argc", "show args", "set cwd /nonexistent", "c"],
+    );
+    assert!(session.text.contains(&format!("cwd = \"{}\"", directory)), "{}", session.text);
+    assert!(session.text.contains("argc = 3"), "run takes its arguments: {}", session.text);
+    assert_eq!(session.command(6)["data"]["cwd"], directory, "{}", session.text);
+    assert!(session.text.contains("argc = 2"), "restart takes the ones of set args: {}", session.text);
+    assert!(session.text.contains("args = \"saved\""), "{}", session.text);
+    assert_eq!(session.command(11)["ok"], false, "{}", session.text);
+    assert_eq!(session.program_output, [format!("cwd {}", directory), "arg saved".to_string()]);
+}
+
+#[test]
+fn resource_limits_and_clean_environment() {
+    require_ptrace!();
+    let session = run_batch(
+        "limits",
+        &[
+            "set env --clear",
+            "set env GREETING=hello world",
+            "set rlimit NOFILE 64:128",
+            "set rlimit CORE 0",
+            "set rlimit CPU 60",
+            "set rlimit CPU",
+            "set rlimit NOFILE 256:128",
+            "set rlimit FILES 1",
+            "show rlimits",
+            "show env",
+            "run",
+        ],
+    );
+    assert_eq!(session.command(6)["ok"], false, "{}", session.text);
+    assert_eq!(session.command(7)["ok"], false, "{}", session.text);
+    let limits = session.command(8)["data"]["rlimits"].as_array().unwrap().clone();
+    let nofile = limits.iter().find(|limit| limit["resource"] == "NOFILE").unwrap();
+    assert_eq!(*nofile, json!({ "resource": "NOFILE", "soft": 64, "hard": 128, "set": true }));
+    assert!(limits.iter().any(|limit| limit["resource"] == "CPU" && limit["set"] == false), "{:?}", limits);
+    assert!(session.text.contains("env = \"--clear GREETING=hello world\""), "{}", session.text);
+    let output = &session.program_output;
+    assert!(output.contains(&"NOFILE 64 128".to_string()), "The program runs with the limits: {:?}", output);
+    assert!(output.iter().any(|line| line.starts_with("CORE 0 ")), "{:?}", output);
+    assert!(!output.iter().any(|line| line.starts_with("CPU 60")), "{:?}", output);
+    let environment: Vec<&String> = output.iter().filter(|line| line.starts_with("env ")).collect();
+    assert_eq!(environment, ["env GREETING=hello world"], "Only the variables set");
+}
+
+#[test]
+fn region_watchpoints_catch_the_writes_to_a_region() {
+    require_ptrace!();
+    let session = run_batch("region", &["watch-region area+64 64", "c", "watch-region", "c", "unwatch-region 1", "c"]);
+    assert_eq!(session.command(0)["data"]["pages"], 1, "{}", session.text);
+    let stops = session.stops();
+    assert_eq!(stops[0]["reason"], "watchpoint", "{:#?}", stops);
+    assert_eq!(stops[0]["region"], 1);
+    assert_eq!(stops[0]["function"], "fill");
+    assert!(stops[0]["new"].as_str().unwrap().starts_with("2a 00 00 00 00 00 00 00 00"), "{:#?}", stops[0]);
+    let banner = stops[0]["banner"].as_str().unwrap();
+    assert!(banner.starts_with("Region watchpoint 1 (64 bytes at 0x") && banner.contains(" written by 0x") && banner.ends_with(", 00 -> 2a"), "{}", banner);
+    let regions = &session.command(2)["data"]["regions"];
+    assert_eq!(regions[0]["hits"], 1, "{}", session.text);
+    assert_eq!(regions[0]["emulated"], 1, "The write before the region is single-stepped");
+    assert!(stops[1]["banner"].as_str().unwrap().ends_with(", 00 -> 09"), "{:#?}", stops[1]);
+    assert_eq!(session.command(4)["ok"], true, "{}", session.text);
+    assert_eq!(session.program_output, ["8 42 9"]);
+    assert_eq!(session.status, 0);
+    let session = run_batch("region", &["watch-region area 0", "watch-region main 16", "watch-region area 8", "watch-region area+64 8", "c"]);
+    assert_eq!(session.command(0)["ok"], false);
+    assert_eq!(session.command(1)["ok"], false, "The text is not writable: {}", session.text);
+    assert_eq!(session.command(3)["ok"], false, "The pages overlap: {}", session.text);
+    assert_eq!(session.stops()[0]["region"], 1);
+}
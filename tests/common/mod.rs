@@ -0,0 +1,96 @@
+//! The harness of the integration tests: runs the debugger binary in batch mode on the fixture
+//! programs of `tests/fixtures`, compiled by `build.rs`, and reads its JSON records.
+
+use serde_json::Value;
+use std::process::Command;
+
+/// Skip the test, returning from it, when ptrace is not available, as in a container without
+/// `CAP_SYS_PTRACE`.
+#[macro_export]
+macro_rules! require_ptrace {
+    () => {
+        if !$crate::common::ptrace_available() {
+            eprintln!("Skipping: ptrace is not available here.");
+            return;
+        }
+    };
+}
+
+/// Returns true if a child can ask to be traced, as the debugger does when it starts a program.
+pub fn ptrace_available() -> bool {
+    rustdbg::launch::ptrace_available()
+}
+
+/// The path of a fixture program, by the name of its source file without `.c`.
+pub fn fixture(name: &str) -> String {
+    let path = format!("{}/{}", env!("RUSTDBG_FIXTURES"), name);
+    assert!(std::path::Path::new(&path).is_file(), "The fixture {} was not built, is a C compiler (cc) installed?", name);
+    path
+}
+
+/// What a run of the debugger wrote.
+pub struct Session {
+    /// The records of the commands, the stops and the lines of the program, in order.
+    pub records: Vec<Value>,
+    /// The lines the program wrote to its standard output.
+    pub program_output: Vec<String>,
+    /// The lines the program wrote to its standard error.
+    pub program_errors: Vec<String>,
+    /// The text for people, on the standard error.
+    pub text: String,
+    /// The exit status of the debugger.
+    pub status: i32,
+}
+
+impl Session {
+    /// The record of the `index`th command run (from 0), skipping the stop records.
+    pub fn command(&self, index: usize) -> &Value {
+        self.records.iter().filter(|record| record.get("command").is_some()).nth(index).unwrap_or_else(|| {
+            panic!("No record for the command {} in {:#?}", index, self.records)
+        })
+    }
+
+    /// The stop records, in order.
+    pub fn stops(&self) -> Vec<&Value> {
+        self.records.iter().filter(|record| record["type"] == "stop").collect()
+    }
+}
+
+/// Run the debugger on a fixture in batch mode with JSON records, running `commands` as `-ex`.
+pub fn run_batch(fixture_name: &str, commands: &[&str]) -> Session {
+    run_batch_with(fixture_name, &[], commands)
+}
+
+/// Run the debugger as [`run_batch`] does, with more `options`, as `--core <file>`.
+pub fn run_batch_with(fixture_name: &str, options: &[&str], commands: &[&str]) -> Session {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_dbg_rust"));
+    command.args(["--batch", "--no-color", "--interpreter", "json"]).args(options);
+    for line in commands {
+        command.args(["-ex", line]);
+    }
+    // No init file of the user nor of the current directory.
+    command.arg(fixture(fixture_name)).env("XDG_CONFIG_HOME", "/nonexistent").current_dir(env!("RUSTDBG_FIXTURES"));
+    let output = command.output().expect("Failed to run the debugger");
+    // The output of the program is captured into records, unless it is given the same standard
+    // output as the records.
+    let (mut records, mut program_output, mut program_errors) = (Vec::new(), Vec::new(), Vec::new());
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match serde_json::from_str::<Value>(line) {
+            Ok(record) if record.is_object() => {
+                if record["type"] == "output" {
+                    let lines = if record["stream"] == "stderr" { &mut program_errors } else { &mut program_output };
+                    lines.push(record["line"].as_str().unwrap_or_default().to_string());
+                }
+                records.push(record);
+            }
+            _ => program_output.push(line.to_string()),
+        }
+    }
+    Session {
+        records,
+        program_output,
+        program_errors,
+        text: String::from_utf8_lossy(&output.stderr).into_owned(),
+        status: output.status.code().unwrap_or(-1),
+    }
+}
@@ -0,0 +1,26 @@
+#include <stdio.h>
+#include <string.h>
+#include <sys/ptrace.h>
+
+/* The two usual checks for a debugger: the TracerPid of /proc/self/status, and PTRACE_TRACEME,
+   which fails when the program is already traced. */
+int main(void) {
+    char line[256];
+    int tracer = -1;
+    FILE *status = fopen("/proc/self/status", "r");
+    while (status != NULL && fgets(line, sizeof(line), status) != NULL) {
+        if (strncmp(line, "TracerPid:", 10) == 0) {
+            sscanf(line + 10, "%d", &tracer);
+        }
+    }
+    if (status != NULL) {
+        fclose(status);
+    }
+    printf("TracerPid %s\n", tracer == 0 ? "0" : "set");
+    if (ptrace(PTRACE_TRACEME, 0, NULL, NULL) == -1) {
+        puts("debugger detected");
+        return 1;
+    }
+    puts("no debugger");
+    return 0;
+}
@@ -0,0 +1,14 @@
+struct node {
+    int value;
+    struct node *next;
+};
+
+static void store(struct node *node, int value) {
+    node->next->value = value;
+}
+
+int main(void) {
+    struct node last = {1, (struct node *)0x10};
+    store(&last, 42);
+    return 0;
+}
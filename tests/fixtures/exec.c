@@ -0,0 +1,22 @@
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+static void before_exec(const char *path) {
+    printf("executing %s\n", strrchr(path, '/') + 1);
+    fflush(stdout);
+}
+
+/* Executes exechelper, built next to it. */
+int main(void) {
+    char path[4096];
+    ssize_t len = readlink("/proc/self/exe", path, sizeof(path) - 16);
+    if (len < 0) {
+        return 1;
+    }
+    path[len] = '\0';
+    strcpy(strrchr(path, '/') + 1, "exechelper");
+    before_exec(path);
+    execl(path, path, (char *)NULL);
+    return 2;
+}
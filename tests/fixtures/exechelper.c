@@ -0,0 +1,11 @@
+#include <stdio.h>
+
+int helper_work(int n) {
+    return n * 2;
+}
+
+/* Executed by exec. */
+int main(void) {
+    printf("helper %d\n", helper_work(21));
+    return 0;
+}
@@ -0,0 +1,24 @@
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+// Stops the program between the changes of its descriptors.
+static void checkpoint(void) {
+}
+
+int main(void) {
+    int null = open("/dev/null", O_RDONLY);
+    checkpoint();
+    checkpoint();
+    int pipes[2];
+    if (pipe(pipes) != 0) {
+        return 1;
+    }
+    close(null);
+    int zero = open("/dev/zero", O_RDONLY);
+    checkpoint();
+    dup2(pipes[0], zero);
+    checkpoint();
+    printf("%d %d\n", null, zero);
+    return 0;
+}
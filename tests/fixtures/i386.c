@@ -0,0 +1,18 @@
+/* A 32-bit program without the C library, which may not be installed for i386: built with
+   -m32 -nostdlib -static, it makes its system calls with int $0x80. */
+
+static int add(int a, int b) {
+    return a + b;
+}
+
+static void write_line(const char *text, int len) {
+    int result;
+    __asm__ volatile("int $0x80" : "=a"(result) : "a"(4), "b"(1), "c"(text), "d"(len) : "memory");
+}
+
+void _start(void) {
+    int sum = add(2, 3);
+    write_line("sum\n", 4);
+    __asm__ volatile("int $0x80" :: "a"(1), "b"(sum));
+    __builtin_unreachable();
+}
@@ -0,0 +1,13 @@
+static inline __attribute__((always_inline)) int square(int value) {
+    int result = value * value;
+    return result;
+}
+
+static inline __attribute__((always_inline)) int sum_of_squares(int a, int b) {
+    return square(a) + square(b);
+}
+
+int main(int argc, char **argv) {
+    (void)argv;
+    return sum_of_squares(argc, 2) == 0;
+}
@@ -0,0 +1,19 @@
+#include <signal.h>
+#include <time.h>
+#include <unistd.h>
+
+// Blocks in read on a pipe nothing writes to, until a child sends it the SIGINT of Ctrl-C.
+int main(void) {
+    int fds[2];
+    if (pipe(fds) != 0) {
+        return 1;
+    }
+    if (fork() == 0) {
+        struct timespec pause = {0, 300000000};
+        nanosleep(&pause, NULL);
+        kill(getppid(), SIGINT);
+        _exit(0);
+    }
+    char buffer[16];
+    return read(fds[0], buffer, sizeof buffer) < 0;
+}
@@ -0,0 +1,33 @@
+#include <stdio.h>
+#include <sys/resource.h>
+
+extern char **environ;
+
+static void print_limit(const char *name, int resource) {
+    struct rlimit limit;
+    if (getrlimit(resource, &limit) != 0) {
+        return;
+    }
+    printf("%s", name);
+    rlim_t values[] = {limit.rlim_cur, limit.rlim_max};
+    for (int i = 0; i < 2; i++) {
+        if (values[i] == RLIM_INFINITY) {
+            printf(" unlimited");
+        } else {
+            printf(" %llu", (unsigned long long)values[i]);
+        }
+    }
+    printf("\n");
+}
+
+// Prints the resource limits it runs with, then its environment.
+int main(void) {
+    print_limit("AS", RLIMIT_AS);
+    print_limit("CORE", RLIMIT_CORE);
+    print_limit("CPU", RLIMIT_CPU);
+    print_limit("NOFILE", RLIMIT_NOFILE);
+    for (char **variable = environ; *variable != NULL; variable++) {
+        printf("env %s\n", *variable);
+    }
+    return 0;
+}
@@ -0,0 +1,25 @@
+#include <stdbool.h>
+
+struct point {
+    int x;
+    int y;
+};
+
+enum color { RED, GREEN, BLUE };
+
+int counter = 7;
+
+int inspect(int depth, const char *label) {
+    int answer = 42;
+    bool flag = true;
+    char letter = 'A';
+    double ratio = 2.5;
+    struct point origin = { 3, -4 };
+    enum color color = BLUE;
+    const char *message = label;
+    return answer + depth + origin.x + flag + letter + (int)ratio + color + message[0];
+}
+
+int main(void) {
+    return inspect(1, "hello") == counter;
+}
@@ -0,0 +1,15 @@
+#include <stdio.h>
+
+static int total;
+
+static void count(int i) {
+    total += i;
+}
+
+int main(void) {
+    for (int i = 0; i < 5; i++) {
+        count(i);
+    }
+    printf("total %d\n", total);
+    return total == 10 ? 0 : 1;
+}
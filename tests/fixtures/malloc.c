@@ -0,0 +1,10 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+int main(void) {
+    char *buffer = malloc(64);
+    snprintf(buffer, 64, "allocated at %p", (void *)buffer);
+    puts(buffer);
+    free(buffer);
+    return 0;
+}
@@ -0,0 +1,27 @@
+#include <pthread.h>
+
+volatile int done;
+volatile unsigned long progress;
+
+void work(void) {
+}
+
+void finish(void) {
+}
+
+static void *run_worker(void *arg) {
+    (void)arg;
+    work();
+    return 0;
+}
+
+int main(void) {
+    pthread_t worker;
+    pthread_create(&worker, 0, run_worker, 0);
+    while (!done) {
+        progress++;
+    }
+    finish();
+    pthread_join(worker, 0);
+    return 0;
+}
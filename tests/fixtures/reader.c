@@ -0,0 +1,12 @@
+#include <stdio.h>
+
+int main(void) {
+    char line[64];
+    int lines = 0;
+    while (fgets(line, sizeof line, stdin)) {
+        lines++;
+        printf("read %s", line);
+    }
+    printf("%d lines\n", lines);
+    return 0;
+}
@@ -0,0 +1,23 @@
+#include <stdio.h>
+
+struct area {
+    long before[8];
+    long watched[8];
+    long after[8];
+};
+
+static struct area area __attribute__((aligned(4096)));
+
+static void fill(long *slot, long value) {
+    *slot = value;
+}
+
+int main(void) {
+    fill(&area.before[0], 1);
+    fill(&area.watched[2], 42);
+    fill(&area.after[0], 3);
+    fill(&area.watched[7], 9);
+    fill(&area.after[1], 4);
+    printf("%ld %ld %ld\n", area.before[0] + area.after[0] + area.after[1], area.watched[2], area.watched[7]);
+    return 0;
+}
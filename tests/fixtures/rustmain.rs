@@ -0,0 +1,42 @@
+fn answer(base: u32) -> u32 {
+    base + 1
+}
+
+fn main() {
+    let value = answer(41);
+    println!("answer {}", value);
+    let limit = 5i32;
+    let found: Option<i32> = Some(7);
+    let missing: Option<i32> = None;
+    let reference: Option<&i32> = Some(&limit);
+    let outcome: Result<u8, &str> = Err("bad");
+    let circle = Shape::Circle { radius: 9 };
+    let square = Shape::Square(2, 3);
+    let config = Config { inner: Inner { id: 1, leaf: Leaf { value: 4 } }, limit: &limit, sizes: [6; 20] };
+    stop();
+    std::hint::black_box((&found, &missing, &reference, &outcome, &circle, &square, &config));
+}
+
+// The values shown by print.
+enum Shape {
+    Circle { radius: u32 },
+    Square(u16, u16),
+}
+
+struct Leaf {
+    value: u8,
+}
+
+struct Inner {
+    id: u8,
+    leaf: Leaf,
+}
+
+struct Config {
+    inner: Inner,
+    limit: *const i32,
+    sizes: [u32; 20],
+}
+
+#[inline(never)]
+fn stop() {}
@@ -0,0 +1,15 @@
+#include <stdio.h>
+#include <time.h>
+
+// Sleeps, then spins, for the status lines shown while the program runs.
+int main(void) {
+    struct timespec pause = {1, 200000000};
+    nanosleep(&pause, NULL);
+    struct timespec start, now;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    do {
+        clock_gettime(CLOCK_MONOTONIC, &now);
+    } while (now.tv_sec - start.tv_sec < 2);
+    printf("done\n");
+    return 0;
+}
@@ -0,0 +1,14 @@
+#include <stdio.h>
+
+int square(int x) {
+    int r = x * x;
+    return r;
+}
+
+int main(void) {
+    int a = 3;
+    int b = square(a);
+    printf("%d\n", b);
+    b = square(b);
+    return b;
+}
@@ -0,0 +1,9 @@
+#include <stdio.h>
+
+int main(void) {
+    printf("to stdout\n");
+    fflush(stdout);
+    fprintf(stderr, "to stderr\n");
+    printf("no newline");
+    return 0;
+}
@@ -0,0 +1,8 @@
+#include <unistd.h>
+
+int main(void) {
+    for (int i = 0; i < 3; i++) {
+        write(1, "tick\n", 5);
+    }
+    return 0;
+}
@@ -0,0 +1,24 @@
+#include <pthread.h>
+
+static int results[4];
+
+static void *work(void *arg) {
+    int index = *(int *)arg;
+    results[index] = index * index;
+    return 0;
+}
+
+int main(void) {
+    pthread_t threads[4];
+    int indices[4];
+    for (int i = 0; i < 4; i++) {
+        indices[i] = i;
+        pthread_create(&threads[i], 0, work, &indices[i]);
+    }
+    int sum = 0;
+    for (int i = 0; i < 4; i++) {
+        pthread_join(threads[i], 0);
+        sum += results[i];
+    }
+    return sum == 14 ? 0 : 1;
+}
@@ -0,0 +1,23 @@
+#include <pthread.h>
+
+__thread int counter = 1;
+int worker_counter;
+
+static void *worker(void *arg) {
+    (void)arg;
+    counter += 41;
+    worker_counter = counter;
+    return 0;
+}
+
+int report(void) {
+    return counter;
+}
+
+int main(void) {
+    pthread_t thread;
+    counter = 5;
+    pthread_create(&thread, 0, worker, 0);
+    pthread_join(thread, 0);
+    return report() == worker_counter;
+}
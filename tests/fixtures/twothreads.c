@@ -0,0 +1,36 @@
+#include <pthread.h>
+
+static pthread_barrier_t barrier;
+static int left, right;
+
+void first(void) {
+    left = 1;
+}
+
+void second(void) {
+    right = 2;
+}
+
+static void *run_first(void *arg) {
+    (void)arg;
+    pthread_barrier_wait(&barrier);
+    first();
+    return 0;
+}
+
+static void *run_second(void *arg) {
+    (void)arg;
+    pthread_barrier_wait(&barrier);
+    second();
+    return 0;
+}
+
+int main(void) {
+    pthread_t threads[2];
+    pthread_barrier_init(&barrier, 0, 2);
+    pthread_create(&threads[0], 0, run_first, 0);
+    pthread_create(&threads[1], 0, run_second, 0);
+    pthread_join(threads[0], 0);
+    pthread_join(threads[1], 0);
+    return left + right == 3 ? 0 : 1;
+}
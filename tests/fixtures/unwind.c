@@ -0,0 +1,26 @@
+#include <stdio.h>
+
+volatile int sink;
+
+__attribute__((noinline)) int level3(int x) {
+    sink = x;
+    return sink * 3;
+}
+
+__attribute__((noinline)) int level2(int x) {
+    int r = level3(x + 1);
+    sink = r;
+    return r + 2;
+}
+
+__attribute__((noinline)) int level1(int x) {
+    int r = level2(x * 2);
+    sink = r;
+    return r + 1;
+}
+
+int main(int argc, char **argv) {
+    (void)argv;
+    printf("%d\n", level1(argc));
+    return 0;
+}
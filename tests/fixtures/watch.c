@@ -0,0 +1,23 @@
+#include <stdio.h>
+
+struct point {
+    long x;
+    long y;
+};
+
+static struct point origin;
+static short level;
+static int counter;
+
+static int read_counter(void) {
+    return counter;
+}
+
+int main(void) {
+    level = 2;
+    counter = 7;
+    int seen = read_counter();
+    origin.y = 5;
+    printf("%d %d %ld\n", level, seen, origin.y);
+    return 0;
+}
@@ -0,0 +1,15 @@
+#include <stdio.h>
+#include <unistd.h>
+
+// Prints the directory it runs in and its arguments.
+int main(int argc, char **argv) {
+    char directory[4096];
+    if (getcwd(directory, sizeof directory) == NULL) {
+        return 1;
+    }
+    printf("cwd %s\n", directory);
+    for (int i = 1; i < argc; i++) {
+        printf("arg %s\n", argv[i]);
+    }
+    return 0;
+}
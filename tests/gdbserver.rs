@@ -0,0 +1,136 @@
+//! Drives the debugger binary as a gdbserver (`--gdbserver`) on the fixture programs, with the
+//! remote protocol spoken by the test, and by gdb where it is installed.
+
+// The batch mode helpers are not used here.
+#[allow(dead_code)]
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+/// Start the debugger serving gdb on a fixture, on a free port of the local host.
+///
+/// # Returns
+///
+/// The debugger, and the port it listens on.
+fn start_server(fixture_name: &str) -> (Child, u16) {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_dbg_rust"))
+        .args(["--no-color", "--gdbserver", "127.0.0.1:0"])
+        .arg(common::fixture(fixture_name))
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the debugger");
+    let mut lines = BufReader::new(server.stdout.take().unwrap()).lines();
+    let port = lines
+        .by_ref()
+        .map_while(Result::ok)
+        .find_map(|line| line.strip_prefix("Listening on port ").map(|port| port.parse().unwrap()))
+        .expect("The debugger did not listen");
+    // The rest of the output is not read, keep it flowing.
+    std::thread::spawn(move || lines.for_each(drop));
+    (server, port)
+}
+
+/// A client of the remote protocol, acknowledging the packets until `QStartNoAckMode`.
+struct Client {
+    stream: TcpStream,
+    ack: bool,
+}
+
+impl Client {
+    /// Send a packet and return its reply.
+    fn request(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+        if self.ack {
+            assert_eq!(self.read_byte(), b'+', "{} acknowledged", data);
+        }
+        assert_eq!(self.read_byte(), b'$');
+        let mut reply = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => reply.push(byte),
+            }
+        }
+        let sum = [self.read_byte(), self.read_byte()];
+        let expected = u8::from_str_radix(std::str::from_utf8(&sum).unwrap(), 16).unwrap();
+        assert_eq!(reply.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)), expected, "The checksum of the reply");
+        if self.ack {
+            self.stream.write_all(b"+").unwrap();
+        }
+        if data == "QStartNoAckMode" {
+            self.ack = false;
+        }
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+/// The address of the entry point of a fixture in the process `pid`, the executables being
+/// position independent or not.
+fn entry_point(fixture_name: &str, pid: i32) -> u64 {
+    let path = common::fixture(fixture_name);
+    let elf = std::fs::read(&path).unwrap();
+    let entry = u64::from_le_bytes(elf[24..32].try_into().unwrap());
+    // ET_DYN is loaded at the start of its first mapping.
+    if u16::from_le_bytes([elf[16], elf[17]]) != 3 {
+        return entry;
+    }
+    let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid)).unwrap();
+    let line = maps.lines().find(|line| line.ends_with(&path)).unwrap();
+    entry + u64::from_str_radix(line.split('-').next().unwrap(), 16).unwrap()
+}
+
+#[test]
+fn loop_remote_protocol() {
+    require_ptrace!();
+    let (mut server, port) = start_server("loop");
+    let mut client = Client { stream: TcpStream::connect(("127.0.0.1", port)).unwrap(), ack: true };
+    assert!(client.request("qSupported:multiprocess+;swbreak+;vContSupported+").contains("swbreak+"));
+    assert_eq!(client.request("qAttached"), "0");
+    assert_eq!(client.request("QStartNoAckMode"), "OK");
+    let thread = client.request("qC");
+    let pid = i32::from_str_radix(thread.strip_prefix("QC").unwrap(), 16).unwrap();
+    assert_eq!(client.request("?"), format!("T05thread:{:x};", pid));
+    let entry = entry_point("loop", pid);
+    assert_eq!(client.request(&format!("Z0,{:x},1", entry)), "OK");
+    assert_eq!(client.request("vCont;c"), format!("T05thread:{:x};swbreak:;", pid));
+    let registers = client.request("g");
+    let rip = u64::from_str_radix(&registers[256..272], 16).unwrap().swap_bytes();
+    assert_eq!(rip, entry, "The pc is rewound to the breakpoint");
+    assert_eq!(client.request(&format!("z0,{:x},1", entry)), "OK");
+    assert_eq!(client.request(&format!("vCont;s:{:x};c", pid)), format!("T05thread:{:x};", pid));
+    assert_eq!(client.request("m0,8"), "E01");
+    assert_eq!(client.request("c"), "W00");
+    assert_eq!(server.wait().unwrap().code(), Some(0), "The exit status of the program");
+}
+
+#[test]
+fn loop_with_gdb() {
+    require_ptrace!();
+    if Command::new("gdb").arg("--version").output().is_err() {
+        eprintln!("Skipping: gdb is not installed.");
+        return;
+    }
+    let (mut server, port) = start_server("loop");
+    let output = Command::new("gdb")
+        .args(["-nx", "-batch", "-ex", &format!("target remote 127.0.0.1:{}", port)])
+        .args(["-ex", "b count", "-ex", "c", "-ex", "bt", "-ex", "p i", "-ex", "delete", "-ex", "c"])
+        .arg(common::fixture("loop"))
+        .output()
+        .expect("Failed to run gdb");
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.contains("Breakpoint 1, count (i=0)"), "{}", text);
+    assert!(text.contains("in main ()") || text.contains(" main () at"), "{}", text);
+    assert!(text.contains("$1 = 0"), "{}", text);
+    assert!(text.contains("exited normally"), "{}", text);
+    assert_eq!(server.wait().unwrap().code(), Some(0));
+}
@@ -0,0 +1,42 @@
+//! Debugs a 32-bit program, the `i386` fixture, in batch mode: its registers, arguments, stack,
+//! code and system calls are those of i386.
+
+// The standard error of the program is not checked here.
+#[allow(dead_code)]
+mod common;
+
+#[test]
+fn i386_program() {
+    require_ptrace!();
+    let session = common::run_batch(
+        "i386",
+        &["b add", "c", "r", "info args", "bt", "m $esp+4", "disas", "s", "s", "c"],
+    );
+    assert!(session.command(1)["ok"] == true, "{}", session.text);
+    let registers = &session.command(2)["data"]["registers"];
+    assert!(registers.get("eip").is_some() && registers.get("r8").is_none(), "{}", registers);
+    // The arguments of add(2, 3) are on the stack.
+    assert!(session.text.contains("arg1 = 0x2\narg2 = 0x3\n"), "{}", session.text);
+    let frames = session.command(4)["data"]["frames"].as_array().unwrap().clone();
+    let functions: Vec<&str> = frames.iter().map(|frame| frame["function"].as_str().unwrap_or("??")).collect();
+    assert_eq!(functions, ["add", "_start"], "{:#?}", frames);
+    // A 4-byte word: the return address into _start, above the saved ebp.
+    let word = session.command(5)["data"]["value"].as_str().unwrap().to_string();
+    assert_eq!(frames[1]["pc"].as_str(), Some(word.as_str()));
+    assert!(session.text.contains("push ebp") && session.text.contains("mov edx, [ebp+8]"), "{}", session.text);
+    assert!(session.text.contains("Caught syscall write (4) (entry)"), "{}", session.text);
+    assert!(session.text.contains("Caught syscall write (4) (exit), returned 0x4"), "{}", session.text);
+    assert_eq!(session.program_output, ["sum"]);
+    assert_eq!(session.status, 5);
+    assert!(session.stops().iter().any(|stop| stop["reason"] == "exited" && stop["code"] == 5), "{:#?}", session.records);
+}
+
+#[test]
+fn i386_alloc() {
+    require_ptrace!();
+    let session = common::run_batch("i386", &["b add", "c", "alloc 10", "c"]);
+    let data = &session.command(2)["data"];
+    let address = u64::from_str_radix(data["address"].as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+    assert!(address != 0 && address < 1 << 32 && address.is_multiple_of(4096), "mmap2 maps below 4 GiB: {}", session.text);
+    assert_eq!(session.status, 5, "The program goes on: {}", session.text);
+}
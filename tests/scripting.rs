@@ -0,0 +1,21 @@
+//! Runs the example Rhai scripts of `examples` on the fixture programs, in batch mode.
+
+// The standard error of the program is not checked here.
+#[allow(dead_code)]
+mod common;
+
+#[test]
+fn malloc_sizes_example() {
+    require_ptrace!();
+    let script = format!("{}/examples/malloc_sizes.rhai", env!("CARGO_MANIFEST_DIR"));
+    let session = common::run_batch("malloc", &[&format!("script file {}", script)]);
+    let record = session.command(0);
+    assert_eq!(record["ok"], true, "{}", session.text);
+    let output: Vec<&str> = record["data"]["output"].as_array().unwrap().iter().map(|line| line.as_str().unwrap()).collect();
+    assert!(output.contains(&"malloc(64)"), "{:?}", output);
+    let logged = output.iter().filter(|line| line.starts_with("malloc(")).count();
+    assert_eq!(output.last(), Some(&format!("{} allocations", logged).as_str()), "{:?}", output);
+    assert!(session.program_output.iter().any(|line| line.starts_with("allocated at")), "{:?}", session.program_output);
+    assert!(session.stops().iter().any(|stop| stop["reason"] == "exited"), "{:#?}", session.records);
+    assert_eq!(session.status, 0);
+}